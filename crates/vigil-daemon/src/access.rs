@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+use vigil_lib::permissions::Permissions;
+
+/// Which requests a connected client may send, decided when it connects from
+/// the executable of the connecting process and `permissions.toml`.
+#[derive(Debug)]
+pub struct ClientAccess {
+    exe: Option<PathBuf>,
+    rules: Rules,
+}
+
+#[derive(Debug)]
+enum Rules {
+    /// No permissions file, or a trusted vigil executable.
+    Unrestricted,
+    Limited(Permissions),
+    /// The file could not be read; every request but `Ping` is refused.
+    Invalid(String),
+}
+
+impl ClientAccess {
+    /// Access for the process `pid`, reading the permissions file at `path`.
+    pub fn for_peer(pid: Option<i32>, path: &Path) -> Self {
+        let exe = pid.and_then(peer_exe);
+        let rules = if exe.as_deref().is_some_and(is_trusted) {
+            Rules::Unrestricted
+        } else {
+            match Permissions::load(path) {
+                Ok(None) => Rules::Unrestricted,
+                Ok(Some(permissions)) => Rules::Limited(permissions),
+                Err(e) => {
+                    let error = e.to_string();
+                    warn_invalid(path, &error);
+                    Rules::Invalid(error)
+                }
+            }
+        };
+        Self { exe, rules }
+    }
+
+    /// Executable of the connected process, when the platform tells.
+    pub fn exe(&self) -> Option<&Path> {
+        self.exe.as_deref()
+    }
+
+    /// Refuses a request of type `kind` the client may not send, explaining why.
+    pub fn check(&self, kind: &str) -> Result<(), String> {
+        let permissions = match &self.rules {
+            Rules::Unrestricted => return Ok(()),
+            Rules::Limited(permissions) => permissions,
+            Rules::Invalid(_) if kind == "Ping" => return Ok(()),
+            Rules::Invalid(error) => {
+                return Err(format!(
+                    "{} is refused because permissions.toml is invalid: {}",
+                    kind, error
+                ))
+            }
+        };
+        if permissions.allows(self.exe.as_deref(), kind) {
+            return Ok(());
+        }
+        Err(match &self.exe {
+            Some(exe) => format!(
+                "{} is not allowed for {} by permissions.toml",
+                kind,
+                exe.display()
+            ),
+            None => format!(
+                "{} is not allowed for clients of unknown executable by permissions.toml",
+                kind
+            ),
+        })
+    }
+}
+
+/// Logs an invalid permissions file once per distinct error, not on every
+/// connection of a polling client.
+fn warn_invalid(path: &Path, error: &str) {
+    static LAST: Mutex<Option<String>> = Mutex::new(None);
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+    if last.as_deref() != Some(error) {
+        warn!(
+            "Refusing client requests: {} is invalid: {}",
+            path.display(),
+            error
+        );
+        *last = Some(error.to_string());
+    }
+}
+
+/// The executable of a connected process, as `/proc/<pid>/exe` links it.
+#[cfg(target_os = "linux")]
+fn peer_exe(pid: i32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_exe(_pid: i32) -> Option<PathBuf> {
+    None
+}
+
+/// Executables with full access whatever `permissions.toml` says: this daemon
+/// (a replacing daemon sends `Handoff`) and the `vigil` CLI installed next to it.
+fn is_trusted(exe: &Path) -> bool {
+    static TRUSTED: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    TRUSTED
+        .get_or_init(|| {
+            let Ok(daemon) = std::env::current_exe() else {
+                return Vec::new();
+            };
+            let cli = daemon.with_file_name("vigil");
+            [daemon, cli]
+                .into_iter()
+                .map(|p| p.canonicalize().unwrap_or(p))
+                .collect()
+        })
+        .iter()
+        .any(|trusted| trusted == exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_access() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("permissions.toml");
+        let socat = Some(PathBuf::from("/usr/bin/socat"));
+
+        let access = ClientAccess::for_peer(None, &path);
+        assert!(access.check("Purge").is_ok());
+
+        std::fs::write(&path, "default = [\"Status\"]\n").unwrap();
+        let access = ClientAccess {
+            exe: socat,
+            rules: Rules::Limited(Permissions::load(&path).unwrap().unwrap()),
+        };
+        assert!(access.check("Status").is_ok());
+        let err = access.check("Purge").unwrap_err();
+        assert!(err.contains("/usr/bin/socat"), "{}", err);
+
+        std::fs::write(&path, "default = [\"Statuz\"]\n").unwrap();
+        let access = ClientAccess::for_peer(None, &path);
+        assert!(access.check("Ping").is_ok());
+        assert!(access.check("Status").unwrap_err().contains("invalid"));
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{bail, Result};
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use tracing::debug;
+
+/// First file descriptor systemd passes to an activated service (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The descriptor of the socket systemd passed to process `pid`, given the
+/// values of `LISTEN_PID` and `LISTEN_FDS`. `None` when the variables are
+/// missing or meant for another process, e.g. inherited from a parent.
+fn passed_fd(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    pid: u32,
+) -> Result<Option<RawFd>> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(None);
+    };
+    if listen_pid.trim().parse::<u32>().ok() != Some(pid) {
+        debug!("Ignoring LISTEN_FDS meant for PID {}", listen_pid);
+        return Ok(None);
+    }
+    match listen_fds.trim().parse::<u32>() {
+        Ok(0) => Ok(None),
+        Ok(1) => Ok(Some(LISTEN_FDS_START)),
+        Ok(n) => bail!(
+            "systemd passed {} sockets; the socket unit must have exactly one ListenStream",
+            n
+        ),
+        Err(_) => bail!("Invalid LISTEN_FDS value '{}'", listen_fds),
+    }
+}
+
+/// Adopts the listening Unix socket passed by systemd socket activation, or
+/// returns `None` when the daemon was started without one and has to bind
+/// its own. The descriptor is marked close-on-exec so engine and hook
+/// processes do not inherit it.
+pub fn listener_from_env() -> Result<Option<UnixListener>> {
+    let listen_pid = std::env::var("LISTEN_PID").ok();
+    let listen_fds = std::env::var("LISTEN_FDS").ok();
+    let Some(fd) = passed_fd(
+        listen_pid.as_deref(),
+        listen_fds.as_deref(),
+        std::process::id(),
+    )?
+    else {
+        return Ok(None);
+    };
+
+    // SAFETY: fstat only writes into the zero-initialized struct
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 || stat.st_mode & libc::S_IFMT != libc::S_IFSOCK {
+        bail!("File descriptor {} passed by systemd is not a socket", fd);
+    }
+    // SAFETY: plain syscall on a descriptor this process owns
+    unsafe {
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    // SAFETY: systemd hands the descriptor over to this process, which owns it from here on
+    let listener = unsafe { UnixListener::from_raw_fd(fd) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passed_fd() {
+        assert_eq!(passed_fd(None, None, 42).unwrap(), None);
+        assert_eq!(passed_fd(Some("42"), None, 42).unwrap(), None);
+        assert_eq!(passed_fd(Some("42"), Some("1"), 42).unwrap(), Some(3));
+        // Inherited from the process that was activated, not this one
+        assert_eq!(passed_fd(Some("41"), Some("1"), 42).unwrap(), None);
+        assert_eq!(passed_fd(Some("42"), Some("0"), 42).unwrap(), None);
+        assert!(passed_fd(Some("42"), Some("2"), 42).is_err());
+        assert!(passed_fd(Some("42"), Some("x"), 42).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::engine::Engine;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use vigil_lib::config::BackupSet;
+
+/// Name of the canary file written at the top of each source directory.
+pub const FILE_NAME: &str = ".vigil-canary";
+
+/// A canary written before a backup, to be found again in the new snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canary {
+    /// Position of the source in the set, so the file can be found in a set
+    /// whose sources were remapped (e.g. into a filesystem snapshot).
+    pub source_index: usize,
+    pub content: String,
+}
+
+/// The set's sources in configuration order.
+fn sources(set: &BackupSet) -> Vec<&str> {
+    set.source
+        .iter()
+        .chain(set.sources.iter().flatten())
+        .map(String::as_str)
+        .collect()
+}
+
+fn content(set_name: &str, now: DateTime<Utc>) -> String {
+    format!("vigil canary {} {}\n", set_name, now.to_rfc3339())
+}
+
+/// Writes a fresh canary into every source directory of `set`. Sources that
+/// are not directories or cannot be written are skipped with a warning.
+pub fn write(set: &BackupSet, now: DateTime<Utc>) -> Vec<Canary> {
+    let content = content(&set.name, now);
+    let mut canaries = Vec::new();
+    for (source_index, source) in sources(set).into_iter().enumerate() {
+        let dir = Path::new(source);
+        if !dir.is_dir() {
+            debug!("Not writing a canary into {}: not a directory", source);
+            continue;
+        }
+        match std::fs::write(dir.join(FILE_NAME), &content) {
+            Ok(()) => canaries.push(Canary {
+                source_index,
+                content: content.clone(),
+            }),
+            Err(e) => warn!(
+                "Failed to write canary for set {} into {}: {}",
+                set.name, source, e
+            ),
+        }
+    }
+    canaries
+}
+
+/// Where the canary of `canary.source_index` lives in a snapshot of `set`.
+fn snapshot_path(set: &BackupSet, canary: &Canary) -> Option<PathBuf> {
+    sources(set)
+        .get(canary.source_index)
+        .map(|source| Path::new(source).join(FILE_NAME))
+}
+
+/// Reads each canary back from `snapshot_id` and compares its content. `set`
+/// is the set as it was backed up. Returns a description of every canary
+/// that is missing or stale.
+pub async fn verify(
+    engine: &Engine,
+    set: &BackupSet,
+    snapshot_id: &str,
+    canaries: &[Canary],
+) -> Result<(), String> {
+    let mut problems = Vec::new();
+    for canary in canaries {
+        let Some(path) = snapshot_path(set, canary) else {
+            continue;
+        };
+        let shown = path.to_string_lossy().to_string();
+        let found = match engine.dump(set, snapshot_id, &shown) {
+            Ok(child) => match child.wait_with_output().await {
+                Ok(out) if out.status.success() => Some(out.stdout),
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("Failed to read canary {}: {}", shown, e);
+                    None
+                }
+            },
+            Err(e) => {
+                warn!("Failed to read canary {}: {}", shown, e);
+                None
+            }
+        };
+        match found {
+            None => problems.push(format!("{} missing from snapshot", shown)),
+            Some(bytes) if bytes != canary.content.as_bytes() => {
+                problems.push(format!("{} has stale content", shown))
+            }
+            Some(_) => {}
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Canary check failed: {}; check the set's source paths and excludes",
+            problems.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_skips_missing_and_file_sources() {
+        let tmp = tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        std::fs::create_dir(&docs).unwrap();
+        let file = tmp.path().join("notes.txt");
+        std::fs::write(&file, "x").unwrap();
+        let set = BackupSet {
+            name: "home".to_string(),
+            sources: Some(vec![
+                tmp.path().join("missing").to_string_lossy().to_string(),
+                file.to_string_lossy().to_string(),
+                docs.to_string_lossy().to_string(),
+            ]),
+            ..Default::default()
+        };
+        let now = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let canaries = write(&set, now);
+        assert_eq!(canaries.len(), 1);
+        assert_eq!(canaries[0].source_index, 2);
+        assert_eq!(
+            std::fs::read_to_string(docs.join(FILE_NAME)).unwrap(),
+            "vigil canary home 2026-01-02T03:04:05+00:00\n"
+        );
+        assert_eq!(
+            snapshot_path(&set, &canaries[0]),
+            Some(docs.join(FILE_NAME))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_path_follows_remapped_sources() {
+        let canary = Canary {
+            source_index: 0,
+            content: String::new(),
+        };
+        let remapped = BackupSet {
+            source: Some("/run/vigil/snap/home".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_path(&remapped, &canary),
+            Some(PathBuf::from("/run/vigil/snap/home/.vigil-canary"))
+        );
+    }
+}
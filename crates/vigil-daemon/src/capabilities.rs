@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use vigil_lib::types::CapabilityStatus;
+
+/// Optional system tools the daemon can take advantage of when present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// FUSE unmount helper, required for browsing snapshots via `mount`.
+    Fuse,
+    /// Desktop notification support.
+    Notifications,
+    /// I/O scheduling priority control for background restic processes.
+    IoPriority,
+    /// systemd user manager for service installation and socket activation.
+    Systemd,
+    /// btrfs tools, required for `snapshot_source = "btrfs"`.
+    Btrfs,
+    /// LVM tools, required for `snapshot_source = "lvm"`.
+    Lvm,
+}
+
+impl Capability {
+    /// All capabilities, in display order.
+    pub const ALL: [Capability; 6] = [
+        Capability::Fuse,
+        Capability::Notifications,
+        Capability::IoPriority,
+        Capability::Systemd,
+        Capability::Btrfs,
+        Capability::Lvm,
+    ];
+
+    /// Name of the tool backing this capability.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Fuse => vigil_lib::platform::FUSE_HELPER,
+            Capability::Notifications => "notify-send",
+            Capability::IoPriority => "ionice",
+            Capability::Systemd => "systemctl",
+            Capability::Btrfs => "btrfs",
+            Capability::Lvm => "lvcreate",
+        }
+    }
+
+    /// Plain-English hint explaining how to make the capability available.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Capability::Fuse if !vigil_lib::platform::SUPPORTS_MOUNTS => {
+                "Mounting snapshots as folders is not supported on this platform."
+            }
+            Capability::Fuse if cfg!(target_os = "macos") => {
+                "Install macFUSE (https://osxfuse.github.io) to mount snapshots as folders."
+            }
+            Capability::Fuse => {
+                "Install FUSE 3 (e.g. `apt install fuse3`) to mount snapshots as folders."
+            }
+            Capability::Notifications => {
+                "Install libnotify (e.g. `apt install libnotify-bin`) to receive desktop notifications."
+            }
+            Capability::IoPriority => {
+                "Install util-linux to lower the I/O priority of background backups."
+            }
+            Capability::Systemd => {
+                "A systemd user session is required to run vigil as a background service."
+            }
+            Capability::Btrfs => {
+                "Install btrfs-progs to back up sets with snapshot_source = \"btrfs\"."
+            }
+            Capability::Lvm => "Install lvm2 to back up sets with snapshot_source = \"lvm\".",
+        }
+    }
+}
+
+/// Error returned when an operation needs a capability that was not detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityMissing {
+    /// Name of the missing tool.
+    pub name: String,
+    /// How to make it available.
+    pub hint: String,
+}
+
+impl std::fmt::Display for CapabilityMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Required tool '{}' is not available. {}",
+            self.name, self.hint
+        )
+    }
+}
+
+impl std::error::Error for CapabilityMissing {}
+
+/// Set of capabilities detected at daemon startup.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    available: HashSet<Capability>,
+}
+
+impl Capabilities {
+    /// Probes `$PATH` for the tools backing each capability.
+    pub fn probe() -> Self {
+        let search_path = std::env::var_os("PATH").unwrap_or_default();
+        let dirs: Vec<PathBuf> = std::env::split_paths(&search_path).collect();
+        let available = Capability::ALL
+            .into_iter()
+            .filter(|cap| dirs.iter().any(|dir| is_executable(&dir.join(cap.name()))))
+            .filter(|cap| *cap != Capability::Fuse || fuse_installed())
+            .collect();
+        Self { available }
+    }
+
+    /// Builds a capability set from an explicit list (used in tests).
+    pub fn with(caps: &[Capability]) -> Self {
+        Self {
+            available: caps.iter().copied().collect(),
+        }
+    }
+
+    /// Returns whether the given capability is available.
+    pub fn has(&self, cap: Capability) -> bool {
+        self.available.contains(&cap)
+    }
+
+    /// Returns an error describing the missing tool if the capability is unavailable.
+    pub fn require(&self, cap: Capability) -> Result<(), CapabilityMissing> {
+        if self.has(cap) {
+            Ok(())
+        } else {
+            Err(CapabilityMissing {
+                name: cap.name().to_string(),
+                hint: cap.hint().to_string(),
+            })
+        }
+    }
+
+    /// Returns the full capability matrix for display.
+    pub fn matrix(&self) -> Vec<CapabilityStatus> {
+        Capability::ALL
+            .into_iter()
+            .map(|cap| CapabilityStatus {
+                name: cap.name().to_string(),
+                available: self.has(cap),
+                hint: if self.has(cap) {
+                    None
+                } else {
+                    Some(cap.hint().to_string())
+                },
+            })
+            .collect()
+    }
+
+    /// Logs the detected capabilities. Missing tools are logged as warnings.
+    pub fn log_summary(&self) {
+        for cap in Capability::ALL {
+            if self.has(cap) {
+                info!("Capability available: {}", cap.name());
+            } else {
+                warn!("Capability missing: {} ({})", cap.name(), cap.hint());
+            }
+        }
+    }
+}
+
+/// Whether FUSE itself is there besides its unmount helper. macOS always
+/// ships `umount`, so mounts need macFUSE's bundle as well.
+fn fuse_installed() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        Path::new(vigil_lib::platform::MACFUSE_BUNDLE).exists()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        vigil_lib::platform::SUPPORTS_MOUNTS
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Windows has no executable bit; a file on the search path counts, with
+/// or without its `.exe` extension.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_missing_capability() {
+        let caps = Capabilities::with(&[Capability::Notifications]);
+        assert!(caps.require(Capability::Notifications).is_ok());
+
+        let err = caps.require(Capability::Fuse).unwrap_err();
+        assert_eq!(err.name, "fusermount3");
+        assert!(err.to_string().contains("fusermount3"));
+    }
+
+    #[test]
+    fn test_matrix_includes_all_capabilities() {
+        let caps = Capabilities::with(&[Capability::Fuse]);
+        let matrix = caps.matrix();
+        assert_eq!(matrix.len(), Capability::ALL.len());
+
+        let fuse = matrix.iter().find(|c| c.name == "fusermount3").unwrap();
+        assert!(fuse.available);
+        assert!(fuse.hint.is_none());
+
+        let ionice = matrix.iter().find(|c| c.name == "ionice").unwrap();
+        assert!(!ionice.available);
+        assert!(ionice.hint.is_some());
+    }
+
+    #[test]
+    fn test_is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let tool = tmp.path().join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        assert!(!is_executable(&tool));
+
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_executable(&tool));
+        assert!(!is_executable(&tmp.path().join("missing")));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::manager::JobManager;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use vigil_lib::humanize;
+
+/// How often the daemon checks whether a burst of backups has gone quiet.
+const DIGEST_TICK: Duration = Duration::from_secs(5);
+
+/// Backup outcomes collected since the last summary notification.
+#[derive(Debug, Default)]
+pub struct Digest {
+    backed_up: BTreeSet<String>,
+    added_bytes: u64,
+    failed: BTreeSet<String>,
+    last_activity: Option<Instant>,
+}
+
+/// One summary notification covering a burst of backups.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestSummary {
+    /// Sets with at least one successful backup in the burst.
+    pub backed_up: Vec<String>,
+    pub added_bytes: u64,
+    /// Sets with at least one failed backup in the burst.
+    pub failed: Vec<String>,
+}
+
+impl Digest {
+    pub fn record_success(&mut self, set_name: &str, added_bytes: u64, now: Instant) {
+        self.backed_up.insert(set_name.to_string());
+        self.added_bytes += added_bytes;
+        self.last_activity = Some(now);
+    }
+
+    pub fn record_failure(&mut self, set_name: &str, now: Instant) {
+        self.failed.insert(set_name.to_string());
+        self.last_activity = Some(now);
+    }
+
+    /// Takes the collected outcomes once `quiet` has passed since the last one
+    /// and nothing is running, starting a new burst.
+    pub fn take_if_quiet(
+        &mut self,
+        now: Instant,
+        quiet: Duration,
+        busy: bool,
+    ) -> Option<DigestSummary> {
+        let last = self.last_activity?;
+        if busy || now.duration_since(last) < quiet {
+            return None;
+        }
+        let digest = std::mem::take(self);
+        Some(DigestSummary {
+            backed_up: digest.backed_up.into_iter().collect(),
+            added_bytes: digest.added_bytes,
+            failed: digest.failed.into_iter().collect(),
+        })
+    }
+}
+
+impl DigestSummary {
+    pub fn title(&self) -> &'static str {
+        if self.failed.is_empty() {
+            "Backups Complete"
+        } else {
+            "Backups Finished With Failures"
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        if self.failed.is_empty() {
+            "dialog-information"
+        } else {
+            "dialog-warning"
+        }
+    }
+
+    /// e.g. `3 sets backed up, 142.0 MiB added, 1 failure (work)`.
+    pub fn body(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.backed_up.is_empty() {
+            parts.push(format!(
+                "{} backed up, {} added",
+                plural(self.backed_up.len(), "set"),
+                humanize::format_size(self.added_bytes)
+            ));
+        }
+        if !self.failed.is_empty() {
+            parts.push(format!(
+                "{} ({})",
+                plural(self.failed.len(), "failure"),
+                self.failed.join(", ")
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// Sends the digest notification after each burst of backups until `token`
+/// is cancelled. Does nothing while `notification_digest_secs` is unset.
+pub async fn run(manager: Arc<JobManager>, token: CancellationToken) {
+    loop {
+        manager.flush_digest(Instant::now()).await;
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(DIGEST_TICK) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_waits_for_quiet_period() {
+        let start = Instant::now();
+        let quiet = Duration::from_secs(60);
+        let mut digest = Digest::default();
+        assert_eq!(digest.take_if_quiet(start, quiet, false), None);
+
+        digest.record_success("home", 100 * 1024 * 1024, start);
+        digest.record_success("docs", 42 * 1024 * 1024, start + Duration::from_secs(30));
+        digest.record_failure("work", start + Duration::from_secs(40));
+        digest.record_success("home", 0, start + Duration::from_secs(50));
+
+        // Quiet is measured from the last outcome, and a running job holds it back
+        assert_eq!(
+            digest.take_if_quiet(start + Duration::from_secs(100), quiet, false),
+            None
+        );
+        assert_eq!(
+            digest.take_if_quiet(start + Duration::from_secs(120), quiet, true),
+            None
+        );
+        let summary = digest
+            .take_if_quiet(start + Duration::from_secs(120), quiet, false)
+            .unwrap();
+        assert_eq!(summary.backed_up, vec!["docs", "home"]);
+        assert_eq!(
+            summary.body(),
+            "2 sets backed up, 142.0 MiB added, 1 failure (work)"
+        );
+        assert_eq!(summary.title(), "Backups Finished With Failures");
+
+        // The next burst starts empty
+        assert_eq!(
+            digest.take_if_quiet(start + Duration::from_secs(500), quiet, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_digest_body_without_successes() {
+        let summary = DigestSummary {
+            backed_up: vec![],
+            added_bytes: 0,
+            failed: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(summary.body(), "2 failures (a, b)");
+    }
+}
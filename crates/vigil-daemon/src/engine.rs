@@ -0,0 +1,305 @@
+use crate::executor::{
+    EngineDefaults, ProgressSender, ResticExecutor, RestoreSummary, SnapshotDiff,
+};
+use crate::run_log::RunLog;
+use crate::rustic::RusticExecutor;
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use tokio::process::Child;
+use tokio_util::sync::CancellationToken;
+use vigil_lib::config::{BackupSet, EngineKind};
+use vigil_lib::types::{BackupResult, FileEntry, RepairStep, RepoStats, SnapshotInfo};
+
+/// Backup engine for a set. Dispatches each operation to the executor of the
+/// configured tool so callers never need to know which one produced a snapshot.
+pub enum Engine {
+    Restic(ResticExecutor),
+    Rustic(RusticExecutor),
+}
+
+impl Engine {
+    pub fn new(kind: EngineKind) -> Self {
+        match kind {
+            EngineKind::Restic => Engine::Restic(ResticExecutor::new()),
+            EngineKind::Rustic => Engine::Rustic(RusticExecutor::new()),
+        }
+    }
+
+    /// Engine configured for the given set, including its password and backend
+    /// credentials, with `defaults` from `[global]`.
+    pub fn for_set(set: &BackupSet, defaults: &EngineDefaults) -> Self {
+        match set.engine {
+            EngineKind::Restic => Engine::Restic(ResticExecutor::for_set(set, defaults)),
+            EngineKind::Rustic => Engine::Rustic(RusticExecutor::for_set(set, defaults)),
+        }
+    }
+
+    pub async fn init(&self, target: &str) -> Result<()> {
+        match self {
+            Engine::Restic(e) => e.init(target).await,
+            Engine::Rustic(e) => e.init(target).await,
+        }
+    }
+
+    /// Runs a backup, writing the engine's output to `log`. Progress is only
+    /// reported by restic; rustic prints none in JSON mode.
+    pub async fn backup(
+        &self,
+        set: &BackupSet,
+        token: Option<CancellationToken>,
+        progress: Option<ProgressSender>,
+        log: Option<&RunLog>,
+    ) -> Result<BackupResult> {
+        match self {
+            Engine::Restic(e) => e.backup(set, token, progress, log).await,
+            Engine::Rustic(e) => e.backup(set, token, log).await,
+        }
+    }
+
+    /// Restores a snapshot into `dest`. Progress is only reported by restic.
+    pub async fn restore(
+        &self,
+        set: &BackupSet,
+        snapshot_id: Option<&str>,
+        include: Option<&str>,
+        dest: &Path,
+        token: Option<CancellationToken>,
+        progress: Option<ProgressSender>,
+    ) -> Result<RestoreSummary> {
+        match self {
+            Engine::Restic(e) => {
+                e.restore(set, snapshot_id, include, dest, token, progress)
+                    .await
+            }
+            Engine::Rustic(e) => e.restore(set, snapshot_id, include, dest, token).await,
+        }
+    }
+
+    /// Size of a regular file in a snapshot. Only supported by restic.
+    pub async fn file_size(
+        &self,
+        set: &BackupSet,
+        snapshot_id: &str,
+        path: &str,
+        token: Option<CancellationToken>,
+    ) -> Result<u64> {
+        match self {
+            Engine::Restic(e) => e.file_size(set, snapshot_id, path, token).await,
+            Engine::Rustic(_) => Err(anyhow!("`cat` is not supported for rustic sets yet")),
+        }
+    }
+
+    /// Copies the set's snapshots to `replica`. Only supported by restic.
+    pub async fn copy(
+        &self,
+        set: &BackupSet,
+        replica: &str,
+        token: Option<CancellationToken>,
+    ) -> Result<usize> {
+        match self {
+            Engine::Restic(e) => e.copy(set, replica, token).await,
+            Engine::Rustic(_) => Err(anyhow!("Replication is not supported for rustic sets yet")),
+        }
+    }
+
+    /// Entries of a directory in a snapshot. Only supported by restic.
+    pub async fn list_files(
+        &self,
+        set: &BackupSet,
+        snapshot_id: &str,
+        path: &str,
+        recursive: bool,
+        token: Option<CancellationToken>,
+    ) -> Result<Vec<FileEntry>> {
+        match self {
+            Engine::Restic(e) => e.list_files(set, snapshot_id, path, recursive, token).await,
+            Engine::Rustic(_) => Err(anyhow!("`ls` is not supported for rustic sets yet")),
+        }
+    }
+
+    /// Spawns a process that writes one file from a snapshot to stdout.
+    pub fn dump(&self, set: &BackupSet, snapshot_id: &str, path: &str) -> Result<Child> {
+        match self {
+            Engine::Restic(e) => e.dump(set, snapshot_id, path),
+            Engine::Rustic(_) => Err(anyhow!("`cat` is not supported for rustic sets yet")),
+        }
+    }
+
+    pub async fn snapshots(
+        &self,
+        target: &str,
+        limit: Option<usize>,
+        token: Option<CancellationToken>,
+    ) -> Result<Vec<SnapshotInfo>> {
+        match self {
+            Engine::Restic(e) => e.snapshots(target, limit, token).await,
+            Engine::Rustic(e) => e.snapshots(target, limit, token).await,
+        }
+    }
+
+    /// Paths that differ between two snapshots. Only supported by restic.
+    pub async fn diff(
+        &self,
+        target: &str,
+        snapshot_a: &str,
+        snapshot_b: &str,
+        token: Option<CancellationToken>,
+    ) -> Result<SnapshotDiff> {
+        match self {
+            Engine::Restic(e) => e.diff(target, snapshot_a, snapshot_b, token).await,
+            Engine::Rustic(_) => Err(anyhow!("`diff` is not supported for rustic sets yet")),
+        }
+    }
+
+    /// Deep repository check reading back `subset` of the data. Only supported by restic.
+    pub async fn verify(
+        &self,
+        target: &str,
+        subset: &str,
+        token: Option<CancellationToken>,
+        progress: Option<ProgressSender>,
+    ) -> Result<()> {
+        match self {
+            Engine::Restic(e) => e.verify(target, subset, token, progress).await,
+            Engine::Rustic(_) => Err(anyhow!(
+                "`check --deep` is not supported for rustic sets yet"
+            )),
+        }
+    }
+
+    /// Runs one step of a guided repair. Only supported by restic.
+    pub async fn repair<F>(
+        &self,
+        target: &str,
+        step: RepairStep,
+        token: Option<CancellationToken>,
+        on_line: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        match self {
+            Engine::Restic(e) => e.repair(target, step, token, on_line).await,
+            Engine::Rustic(_) => Err(anyhow!(
+                "`check --repair` is not supported for rustic sets yet"
+            )),
+        }
+    }
+
+    pub async fn snapshot_size(
+        &self,
+        target: &str,
+        snapshot_id: &str,
+        token: Option<CancellationToken>,
+    ) -> Result<u64> {
+        match self {
+            Engine::Restic(e) => e.snapshot_size(target, snapshot_id, token).await,
+            Engine::Rustic(e) => e.snapshot_size(target, snapshot_id, token).await,
+        }
+    }
+
+    /// Space the repository uses on its backend. Only supported by restic.
+    pub async fn repo_size(&self, target: &str, token: Option<CancellationToken>) -> Result<u64> {
+        match self {
+            Engine::Restic(e) => e.repo_size(target, token).await,
+            Engine::Rustic(_) => Err(anyhow!(
+                "Measuring remote repositories is not supported for rustic sets yet"
+            )),
+        }
+    }
+
+    /// Deduplication and size statistics of the repository. Only supported by restic.
+    pub async fn stats(&self, target: &str, token: Option<CancellationToken>) -> Result<RepoStats> {
+        match self {
+            Engine::Restic(e) => e.stats(target, token).await,
+            Engine::Rustic(_) => Err(anyhow!(
+                "Repository statistics are not supported for rustic sets yet"
+            )),
+        }
+    }
+
+    /// The repository's unique ID, assigned when it was initialized.
+    pub async fn repo_id(&self, target: &str, token: Option<CancellationToken>) -> Result<String> {
+        match self {
+            Engine::Restic(e) => e.repo_id(target, token).await,
+            Engine::Rustic(e) => e.repo_id(target, token).await,
+        }
+    }
+
+    /// The engine's name and version, as it reports them.
+    pub async fn version(&self) -> Result<String> {
+        match self {
+            Engine::Restic(e) => e.version().await,
+            Engine::Rustic(e) => e.version().await,
+        }
+    }
+
+    pub async fn list_locks(
+        &self,
+        target: &str,
+        token: Option<CancellationToken>,
+    ) -> Result<usize> {
+        match self {
+            Engine::Restic(e) => e.list_locks(target, token).await,
+            Engine::Rustic(e) => e.list_locks(target, token).await,
+        }
+    }
+
+    pub async fn forget(
+        &self,
+        target: &str,
+        snapshot_id: &str,
+        prune: bool,
+        token: Option<CancellationToken>,
+    ) -> Result<u64> {
+        match self {
+            Engine::Restic(e) => e.forget(target, snapshot_id, prune, token).await,
+            Engine::Rustic(e) => e.forget(target, snapshot_id, prune, token).await,
+        }
+    }
+
+    pub async fn unlock(
+        &self,
+        target: &str,
+        remove_all: bool,
+        token: Option<CancellationToken>,
+    ) -> Result<()> {
+        match self {
+            Engine::Restic(e) => e.unlock(target, remove_all, token).await,
+            // rustic takes no repository locks
+            Engine::Rustic(_) => Ok(()),
+        }
+    }
+
+    pub async fn prune(&self, set: &BackupSet, token: Option<CancellationToken>) -> Result<u64> {
+        match self {
+            Engine::Restic(e) => e.prune(set, token).await,
+            Engine::Rustic(e) => e.prune(set, token).await,
+        }
+    }
+
+    /// The snapshots the set's retention policy would forget, and how many
+    /// it would keep, without changing the repository.
+    pub async fn forget_preview(
+        &self,
+        set: &BackupSet,
+        token: Option<CancellationToken>,
+    ) -> Result<(Vec<SnapshotInfo>, usize)> {
+        match self {
+            Engine::Restic(e) => e.forget_preview(set, token).await,
+            Engine::Rustic(e) => e.forget_preview(set, token).await,
+        }
+    }
+
+    pub async fn mount(
+        &self,
+        target: &str,
+        snapshot_id: Option<&str>,
+        mountpoint: &Path,
+    ) -> Result<Child> {
+        match self {
+            Engine::Restic(e) => e.mount(target, snapshot_id, mountpoint).await,
+            Engine::Rustic(e) => e.mount(target, snapshot_id, mountpoint).await,
+        }
+    }
+}
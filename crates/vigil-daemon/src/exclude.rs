@@ -0,0 +1,373 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use vigil_lib::config::{ExcludeMarker, ExcludePattern};
+
+/// Decides which watcher events a set's exclude patterns filter out, matching
+/// exactly the paths restic skips for the same patterns.
+#[derive(Debug, Clone)]
+pub struct ExcludeMatcher {
+    globs: GlobSet,
+}
+
+impl ExcludeMatcher {
+    pub fn new(patterns: &[ExcludePattern]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            // Paths are matched relative to the root: with a leading `/`, globset
+            // lets `*` match the empty component before it
+            for glob in pattern.globs() {
+                let glob = glob.trim_start_matches('/');
+                builder.add(
+                    GlobBuilder::new(glob)
+                        .literal_separator(true)
+                        .build()
+                        .with_context(|| format!("Invalid exclusion pattern: {}", glob))?,
+                );
+            }
+        }
+        Ok(Self {
+            globs: builder.build().context("Failed to build GlobSet")?,
+        })
+    }
+
+    /// Whether `path` (absolute) or any of its parent directories matches.
+    /// restic does not descend into an excluded directory, so its contents are
+    /// excluded too.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        relative
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| self.globs.is_match(p))
+    }
+}
+
+/// The entries of `excludes` that `include` lifts: those equal to one of the
+/// include patterns, or matching one of them read as a path (so `*.db` is
+/// lifted by `keep.db`, and `/home/me/.cache` by `/home/me/.cache/app`).
+/// Entries that do not parse are never lifted.
+pub fn lifted_excludes(excludes: &[String], include: &[String]) -> Vec<String> {
+    excludes
+        .iter()
+        .filter(|exclude| {
+            let Ok(pattern) = ExcludePattern::parse(exclude) else {
+                return false;
+            };
+            let Ok(matcher) = ExcludeMatcher::new(std::slice::from_ref(&pattern)) else {
+                return false;
+            };
+            include
+                .iter()
+                .any(|path| path == *exclude || matcher.is_excluded(Path::new(path)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Whether a directory between `root` and `path` (both included) holds one of
+/// `markers`, which makes restic skip that directory and everything below it.
+pub fn has_exclude_marker(path: &Path, root: &Path, markers: &[ExcludeMarker]) -> bool {
+    if markers.is_empty() {
+        return false;
+    }
+    path.ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .any(|dir| markers.iter().any(|marker| marker_present(dir, marker)))
+}
+
+fn marker_present(dir: &Path, marker: &ExcludeMarker) -> bool {
+    let file = dir.join(&marker.file);
+    let Some(ref header) = marker.header else {
+        return file.is_file();
+    };
+    let Ok(f) = std::fs::File::open(&file) else {
+        return false;
+    };
+    let mut start = Vec::with_capacity(header.len());
+    f.take(header.len() as u64).read_to_end(&mut start).is_ok() && start == header.as_bytes()
+}
+
+/// Applies `.gitignore` files the way rustic's `--git-ignore` does: only inside
+/// a git work tree, with the deepest `.gitignore` that has a matching rule
+/// deciding. Parsed files are cached until [`GitignoreMatcher::invalidate`].
+#[derive(Debug, Default)]
+pub struct GitignoreMatcher {
+    cache: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl GitignoreMatcher {
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let dirs: Vec<&Path> = path.ancestors().skip(1).collect();
+        let Some(work_tree) = dirs.iter().position(|dir| dir.join(".git").exists()) else {
+            return false;
+        };
+        for dir in &dirs[..=work_tree] {
+            let Some(gitignore) = self.load(dir) else {
+                continue;
+            };
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// Forgets the parsed `.gitignore` of `dir`, after it changed.
+    pub fn invalidate(&self, dir: &Path) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.remove(dir);
+        }
+    }
+
+    fn load(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut cache = self.cache.lock().ok()?;
+        cache
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let file = dir.join(".gitignore");
+                file.is_file().then(|| Arc::new(Gitignore::new(&file).0))
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn matcher(patterns: &[&str]) -> ExcludeMatcher {
+        let patterns: Vec<ExcludePattern> = patterns
+            .iter()
+            .map(|p| ExcludePattern::parse(p).unwrap())
+            .collect();
+        ExcludeMatcher::new(&patterns).unwrap()
+    }
+
+    /// Reference model of restic's `filter.Match` applied the way the archiver
+    /// uses it: an unanchored pattern gets a `**` component prepended, `**`
+    /// matches any number of components, other components are matched one to
+    /// one with `filepath.Match`, and a path is excluded when it or a parent
+    /// directory matches.
+    fn restic_excludes(pattern: &ExcludePattern, path: &str) -> bool {
+        let mut parts: Vec<&str> = Vec::new();
+        if !pattern.is_anchored() {
+            parts.push("**");
+        }
+        parts.extend(pattern.restic_arg().split('/').filter(|c| !c.is_empty()));
+        let comps: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        (1..=comps.len()).any(|len| match_parts(&parts, &comps[..len]))
+    }
+
+    fn match_parts(parts: &[&str], comps: &[&str]) -> bool {
+        match parts.split_first() {
+            None => comps.is_empty(),
+            Some((&"**", rest)) => (0..=comps.len()).any(|skip| match_parts(rest, &comps[skip..])),
+            Some((part, rest)) => match comps.split_first() {
+                Some((comp, comps)) => {
+                    match_component(part.as_bytes(), comp.as_bytes()) && match_parts(rest, comps)
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// `filepath.Match` for a single component: `*`, `?` and `[...]`/`[^...]` classes with ranges.
+    fn match_component(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some((b'*', rest)) => (0..=name.len()).any(|skip| match_component(rest, &name[skip..])),
+            Some((b'?', rest)) => !name.is_empty() && match_component(rest, &name[1..]),
+            Some((b'[', rest)) => {
+                let Some(close) = rest.iter().position(|&c| c == b']') else {
+                    return false;
+                };
+                let Some((&c, name_rest)) = name.split_first() else {
+                    return false;
+                };
+                let (negated, class) = match rest[..close].split_first() {
+                    Some((b'^', class)) => (true, class),
+                    _ => (false, &rest[..close]),
+                };
+                let mut matched = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == b'-' {
+                        matched |= class[i] <= c && c <= class[i + 2];
+                        i += 3;
+                    } else {
+                        matched |= class[i] == c;
+                        i += 1;
+                    }
+                }
+                matched != negated && match_component(&rest[close + 1..], name_rest)
+            }
+            Some((&p, rest)) => name.first() == Some(&p) && match_component(rest, &name[1..]),
+        }
+    }
+
+    #[test]
+    fn test_lifted_excludes() {
+        let excludes: Vec<String> = ["*.db", "/home/me/.cache", "node_modules", "[x"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let lifted = |include: &[&str]| {
+            let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+            lifted_excludes(&excludes, &include)
+        };
+        assert_eq!(lifted(&["*.db"]), vec!["*.db"]);
+        assert_eq!(lifted(&["/home/me/notes/keep.db"]), vec!["*.db"]);
+        assert_eq!(
+            lifted(&["/home/me/.cache/app/state"]),
+            vec!["/home/me/.cache"]
+        );
+        assert_eq!(
+            lifted(&["/srv/web/node_modules", "/home/me/.cache"]),
+            vec!["/home/me/.cache", "node_modules"]
+        );
+        assert!(lifted(&["/home/me/notes.txt"]).is_empty());
+    }
+
+    #[test]
+    fn test_unanchored_patterns_match_at_any_depth() {
+        let m = matcher(&["*.tmp", "node_modules", "cache/*"]);
+        assert!(m.is_excluded(Path::new("/home/u/a.tmp")));
+        assert!(m.is_excluded(Path::new("/home/u/proj/node_modules/x/index.js")));
+        assert!(m.is_excluded(Path::new("/home/u/cache/blob")));
+        assert!(m.is_excluded(Path::new("/home/u/cache/dir/blob")));
+        assert!(!m.is_excluded(Path::new("/home/u/a.tmp.txt")));
+        assert!(!m.is_excluded(Path::new("/home/u/cache")));
+    }
+
+    #[test]
+    fn test_star_does_not_cross_separator() {
+        let m = matcher(&["docs*.txt", "a[^b]c"]);
+        assert!(m.is_excluded(Path::new("/home/docs-old.txt")));
+        assert!(!m.is_excluded(Path::new("/home/docs/old.txt")));
+        assert!(!m.is_excluded(Path::new("/x/a/c")));
+    }
+
+    #[test]
+    fn test_exclude_markers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("proj/cache/deep")).unwrap();
+        std::fs::create_dir_all(root.join("proj/fake")).unwrap();
+        std::fs::write(
+            root.join("proj/cache/CACHEDIR.TAG"),
+            format!("{}\n# cache", vigil_lib::config::CACHEDIR_TAG_SIGNATURE),
+        )
+        .unwrap();
+        std::fs::write(root.join("proj/fake/CACHEDIR.TAG"), "not a cache").unwrap();
+        let markers = [ExcludeMarker::cachedir_tag()];
+
+        assert!(has_exclude_marker(
+            &root.join("proj/cache/deep/x"),
+            root,
+            &markers
+        ));
+        assert!(!has_exclude_marker(
+            &root.join("proj/fake/x"),
+            root,
+            &markers
+        ));
+        assert!(!has_exclude_marker(&root.join("proj/x"), root, &markers));
+        // Markers above the source root do not count
+        assert!(!has_exclude_marker(
+            &root.join("proj/cache/deep/x"),
+            &root.join("proj/cache/deep"),
+            &markers
+        ));
+    }
+
+    #[test]
+    fn test_gitignore_matcher() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("web/node_modules/pkg")).unwrap();
+        std::fs::write(repo.join(".gitignore"), "node_modules/\n*.log\n").unwrap();
+        std::fs::write(repo.join("web/.gitignore"), "!keep.log\n").unwrap();
+        let matcher = GitignoreMatcher::default();
+
+        assert!(matcher.is_ignored(&repo.join("web/node_modules/pkg/index.js"), false));
+        assert!(matcher.is_ignored(&repo.join("debug.log"), false));
+        assert!(!matcher.is_ignored(&repo.join("web/keep.log"), false));
+        assert!(!matcher.is_ignored(&repo.join("web/app.js"), false));
+
+        // Outside a work tree .gitignore files are not applied
+        let plain = tmp.path().join("plain");
+        std::fs::create_dir_all(&plain).unwrap();
+        std::fs::write(plain.join(".gitignore"), "*.log\n").unwrap();
+        assert!(!matcher.is_ignored(&plain.join("debug.log"), false));
+
+        std::fs::write(repo.join(".gitignore"), "node_modules/\n").unwrap();
+        matcher.invalidate(&repo);
+        assert!(!matcher.is_ignored(&repo.join("debug.log"), false));
+    }
+
+    #[test]
+    fn test_own_path_pattern_covers_contents() {
+        let pattern = ExcludePattern::for_path(Path::new("/home/u/.local/share/vigil")).unwrap();
+        let matcher = ExcludeMatcher::new(&[pattern]).unwrap();
+        assert!(matcher.is_excluded(Path::new("/home/u/.local/share/vigil/vigil.log")));
+        assert!(matcher.is_excluded(Path::new("/home/u/.local/share/vigil")));
+        assert!(!matcher.is_excluded(Path::new("/home/u/.local/share/vigil-notes")));
+        assert!(!matcher.is_excluded(Path::new("/srv/home/u/.local/share/vigil/x")));
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        let m = matcher(&["/home/u/.cache", "/data/**"]);
+        assert!(m.is_excluded(Path::new("/home/u/.cache/thumbs/1.png")));
+        assert!(!m.is_excluded(Path::new("/other/home/u/.cache/x")));
+        assert!(m.is_excluded(Path::new("/data")));
+        assert!(m.is_excluded(Path::new("/data/a/b")));
+    }
+
+    fn component() -> impl Strategy<Value = String> {
+        "[ab][ab.]?"
+    }
+
+    fn pattern_component() -> impl Strategy<Value = String> {
+        prop_oneof![
+            component(),
+            Just("*".to_string()),
+            Just("**".to_string()),
+            Just("?".to_string()),
+            Just("*.a".to_string()),
+            Just("a*".to_string()),
+            Just("[ab]".to_string()),
+            Just("[^a]*".to_string()),
+            Just("[a-b].?".to_string()),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_watcher_and_restic_agree(
+            anchored in any::<bool>(),
+            parts in prop::collection::vec(pattern_component(), 1..4),
+            comps in prop::collection::vec(component(), 1..6),
+        ) {
+            let raw = format!("{}{}", if anchored { "/" } else { "" }, parts.join("/"));
+            let pattern = ExcludePattern::parse(&raw).unwrap();
+            let path = format!("/{}", comps.join("/"));
+            let m = ExcludeMatcher::new(std::slice::from_ref(&pattern)).unwrap();
+            prop_assert_eq!(
+                m.is_excluded(Path::new(&path)),
+                restic_excludes(&pattern, &path),
+                "pattern {} path {}", raw, path
+            );
+        }
+    }
+}
@@ -1,22 +1,134 @@
+use crate::run_log::RunLog;
 use anyhow::{anyhow, Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde_json::Value;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::process::{Child, Command};
-use tracing::{debug, error, info};
-use vigil_lib::config::BackupSet;
-use vigil_lib::paths;
-use vigil_lib::types::{BackupResult, SnapshotInfo};
+use tracing::{debug, error, info, warn};
+use vigil_lib::config::{
+    engine_process_env, BackupSet, EngineEnv, GlobalConfig, PasswordSource, Priority, Throttle,
+};
+use vigil_lib::types::{BackupResult, FileEntry, RepairStep, RepoStats, Scanning, SnapshotInfo};
 
 /// How long to wait after spawning restic mount to check for immediate failures
 /// (e.g., invalid snapshot ID, mount point busy, missing fusermount3)
 const MOUNT_STARTUP_CHECK_MS: u64 = 200;
 
+/// Progress below this fraction is too early to extrapolate a useful ETA from.
+const MIN_ETA_FRACTION: f64 = 0.01;
+
+/// Whether `ionice` was found at startup. Without it, a lowered priority only
+/// affects CPU scheduling.
+static IONICE_AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// Settings from `[global]` that apply to the processes of every engine.
+/// Executors copy them when they are built, so a reload never changes the
+/// environment of a job that is already running.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineDefaults {
+    /// `RESTIC_*`/`RUSTIC_*` variables engine processes may inherit from the
+    /// daemon, from `restic_env_passthrough`.
+    pub env_passthrough: Vec<String>,
+    /// Variables from the global `env_file` and `env`.
+    pub env: EngineEnv,
+    /// Bandwidth limits and priority used where a set has none of its own.
+    pub throttle: Throttle,
+}
+
+impl EngineDefaults {
+    pub fn from_config(global: &GlobalConfig) -> Self {
+        Self {
+            env_passthrough: global.restic_env_passthrough.clone(),
+            env: global.engine_env(),
+            throttle: global.throttle(),
+        }
+    }
+}
+
 #[derive(Default)]
-pub struct ResticExecutor;
+pub struct ResticExecutor {
+    /// Repository password passed to every restic invocation.
+    password: PasswordSource,
+    /// Credentials file whose variables are passed to every restic invocation.
+    credentials_file: Option<PathBuf>,
+    /// The global `env_file` and `env`, set before the set's own variables.
+    global_env: EngineEnv,
+    /// The set's `env_file` and `env`, passed to every restic invocation.
+    env: EngineEnv,
+    /// `RESTIC_*` variables restic may inherit from the daemon's environment.
+    env_passthrough: Vec<String>,
+    /// Bandwidth limits passed to every restic invocation, and the priority of
+    /// backups and prunes; the global ones where the set has none.
+    throttle: Throttle,
+}
+
+/// Progress of a running backup or restore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Completion in percent (0-100).
+    pub percent: f64,
+    /// Estimated seconds until completion, once enough progress has been made.
+    pub eta_secs: Option<u64>,
+    /// Bytes processed so far.
+    pub bytes_done: u64,
+    /// Total bytes to process; 0 while restic is still scanning.
+    pub total_bytes: u64,
+    /// A file currently being processed, if the engine reports one.
+    pub current_file: Option<String>,
+    /// Set while a backup is still discovering the files to back up.
+    pub scanning: Option<Scanning>,
+}
+
+impl Progress {
+    /// Extrapolates the remaining time from the fraction done and time elapsed so far.
+    pub fn new(fraction: f64, elapsed_secs: Option<u64>) -> Self {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let eta_secs = elapsed_secs
+            .filter(|_| fraction >= MIN_ETA_FRACTION)
+            .map(|elapsed| (elapsed as f64 * (1.0 - fraction) / fraction).round() as u64);
+        Self {
+            percent: fraction * 100.0,
+            eta_secs,
+            bytes_done: 0,
+            total_bytes: 0,
+            current_file: None,
+            scanning: None,
+        }
+    }
+}
+
+/// Peak memory and CPU time of a finished tool process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceUsage {
+    pub peak_rss_bytes: u64,
+    /// User plus system CPU time.
+    pub cpu_secs: f64,
+}
+
+/// Receives progress updates while an operation runs. Only the latest value is kept.
+pub type ProgressSender = tokio::sync::watch::Sender<Option<Progress>>;
+
+#[derive(Debug, Deserialize)]
+struct ResticStatus {
+    // message_type is "status"
+    percent_done: f64,
+    seconds_elapsed: Option<u64>,
+    /// Only reported once restic has found every file to back up.
+    seconds_remaining: Option<u64>,
+    #[serde(default)]
+    bytes_done: u64,
+    #[serde(default)]
+    total_bytes: u64,
+    #[serde(default)]
+    total_files: u64,
+    #[serde(default)]
+    current_files: Vec<String>,
+}
 
 #[derive(Debug, Deserialize)]
 struct ResticSummary {
@@ -26,24 +138,111 @@ struct ResticSummary {
     snapshot_id: String,
 }
 
+/// Outcome of a completed restore.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub struct RestoreSummary {
+    #[serde(default)]
+    pub files_restored: u64,
+    #[serde(default)]
+    pub bytes_restored: u64,
+    #[serde(default, rename = "seconds_elapsed")]
+    pub duration_secs: f64,
+}
+
+/// Paths that differ between two snapshots.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Paths whose content, type or metadata changed.
+    pub modified: Vec<String>,
+}
+
+/// A `change` message of `restic diff --json`.
+#[derive(Debug, Deserialize)]
+struct ResticDiffChange {
+    path: String,
+    modifier: String,
+}
+
+/// An entry of `restic ls --json`. The first line describes the snapshot and
+/// has no `path`.
+#[derive(Debug, Deserialize)]
+struct ResticNode {
+    path: Option<String>,
+    #[serde(rename = "type")]
+    node_type: Option<String>,
+    size: Option<u64>,
+    mtime: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ResticSnapshotSummary {
     total_bytes_processed: u64,
 }
 
+#[derive(Debug, Deserialize)]
+struct ResticStats {
+    total_size: u64,
+    #[serde(default)]
+    total_uncompressed_size: u64,
+    #[serde(default)]
+    total_file_count: u64,
+    #[serde(default)]
+    total_blob_count: u64,
+    #[serde(default)]
+    snapshots_count: u64,
+}
+
 #[derive(Debug, Deserialize)]
 struct ResticSnapshot {
     id: String,
     short_id: String,
     time: chrono::DateTime<chrono::Utc>,
+    hostname: Option<String>,
     paths: Vec<PathBuf>,
     tags: Option<Vec<String>>,
     summary: Option<ResticSnapshotSummary>,
 }
 
+impl ResticSnapshot {
+    fn into_info(self) -> SnapshotInfo {
+        SnapshotInfo {
+            id: self.id,
+            short_id: self.short_id,
+            timestamp: self.time,
+            paths: self.paths,
+            tags: self.tags.unwrap_or_default(),
+            total_bytes: self.summary.map(|sum| sum.total_bytes_processed),
+            hostname: self.hostname,
+        }
+    }
+}
+
+/// One group of `restic forget --json`; `remove` is null when nothing goes.
+#[derive(Debug, Deserialize)]
+struct ResticForgetGroup {
+    #[serde(default)]
+    keep: Vec<ResticSnapshot>,
+    remove: Option<Vec<ResticSnapshot>>,
+}
+
 impl ResticExecutor {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Executor for a set's repository, using its password source and backend
+    /// credentials, with `defaults` where the set has no settings of its own.
+    pub fn for_set(set: &BackupSet, defaults: &EngineDefaults) -> Self {
+        Self {
+            password: set.password_source(),
+            credentials_file: set.credentials_file.as_ref().map(PathBuf::from),
+            global_env: defaults.env.clone(),
+            env: set.engine_env(),
+            env_passthrough: defaults.env_passthrough.clone(),
+            throttle: set.throttle().or(defaults.throttle),
+        }
     }
 
     async fn run_restic(
@@ -51,77 +250,70 @@ impl ResticExecutor {
         args: Vec<String>,
         token: Option<tokio_util::sync::CancellationToken>,
     ) -> Result<(String, String)> {
-        let mut cmd = Command::new("restic");
-        cmd.args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        debug!("Running restic command: restic {}", args.join(" "));
-
-        // We use spawn() so we can interact with the child process (kill it on cancellation)
-        let mut child = cmd.spawn().context("Failed to execute restic")?;
-        let stdout_pipe = child.stdout.take().context("Failed to take stdout")?;
-        let stderr_pipe = child.stderr.take().context("Failed to take stderr")?;
-
-        let stdout_handle = tokio::spawn(async move {
-            let mut buf = Vec::new();
-            use tokio::io::AsyncReadExt;
-            let mut reader = stdout_pipe;
-            let _ = reader.read_to_end(&mut buf).await;
-            buf
-        });
-
-        let stderr_handle = tokio::spawn(async move {
-            let mut buf = Vec::new();
-            use tokio::io::AsyncReadExt;
-            let mut reader = stderr_pipe;
-            let _ = reader.read_to_end(&mut buf).await;
-            buf
-        });
-
-        let status_res = if let Some(token) = token {
-            tokio::select! {
-                res = child.wait() => res,
-                _ = token.cancelled() => {
-                    info!("Restic command cancelled, killing process...");
-                    let _ = child.kill().await;
-                    return Err(anyhow!("Restic command cancelled"));
-                }
-            }
-        } else {
-            child.wait().await
-        };
-
-        let status = status_res.context("Failed to wait for restic process")?;
-        let stdout_bytes = stdout_handle.await.unwrap_or_default();
-        let stderr_bytes = stderr_handle.await.unwrap_or_default();
+        self.run_restic_streaming(args, token, |_| true).await
+    }
 
-        let stdout = String::from_utf8_lossy(&stdout_bytes).to_string();
-        let stderr = String::from_utf8_lossy(&stderr_bytes).to_string();
+    async fn run_restic_streaming<F>(
+        &self,
+        args: Vec<String>,
+        token: Option<tokio_util::sync::CancellationToken>,
+        on_line: F,
+    ) -> Result<(String, String)>
+    where
+        F: FnMut(&str) -> bool + Send + 'static,
+    {
+        let (stdout, stderr, _) = self
+            .run_restic_measured(args, Priority::Normal, token, on_line)
+            .await?;
+        Ok((stdout, stderr))
+    }
 
-        if !status.success() {
-            // Restic backup can return non-zero (3) for some warnings but still produce a snapshot
-            if args.contains(&"backup".to_string()) && !stdout.is_empty() {
-                debug!("Restic backup returned non-zero ({}) but produced output, checking for summary", status);
-            } else {
-                error!("Restic failed: {}", stderr);
-                return Err(anyhow!("Restic error: {}", stderr));
-            }
-        }
+    /// Runs restic with the set's bandwidth limits at `priority`, also
+    /// returning its resource usage.
+    async fn run_restic_measured<F>(
+        &self,
+        mut args: Vec<String>,
+        priority: Priority,
+        token: Option<tokio_util::sync::CancellationToken>,
+        on_line: F,
+    ) -> Result<(String, String, Option<ResourceUsage>)>
+    where
+        F: FnMut(&str) -> bool + Send + 'static,
+    {
+        args.extend(limit_args(&self.throttle));
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        let (program, args) = prioritized("restic", args, priority);
+        run_tool_measured(
+            &program,
+            "Restic",
+            args,
+            &env,
+            &self.env_passthrough,
+            token,
+            on_line,
+        )
+        .await
+    }
 
-        Ok((stdout, stderr))
+    /// Priority of background work (backups and prunes) for this set.
+    fn background_priority(&self) -> Priority {
+        self.throttle.priority.unwrap_or_default()
     }
 
     pub async fn init(&self, target: &str) -> Result<()> {
         info!("Initializing restic repository at {}", target);
-        let password_file = paths::password_path();
+        let [password_flag, password] = self.password.args();
         self.run_restic(
             vec![
                 "init".to_string(),
                 "--repo".to_string(),
                 target.to_string(),
-                "--password-file".to_string(),
-                password_file.to_string_lossy().to_string(),
+                password_flag,
+                password,
             ],
             None,
         )
@@ -133,26 +325,59 @@ impl ResticExecutor {
         &self,
         set: &BackupSet,
         token: Option<tokio_util::sync::CancellationToken>,
+        progress: Option<ProgressSender>,
+        log: Option<&RunLog>,
     ) -> Result<BackupResult> {
         info!("Starting backup for set: {}", set.name);
-        let password_file = paths::password_path();
+        let [password_flag, password] = self.password.args();
 
         let mut args = vec![
             "backup".to_string(),
             "--repo".to_string(),
             set.target.clone(),
-            "--password-file".to_string(),
-            password_file.to_string_lossy().to_string(),
+            password_flag,
+            password,
             "--json".to_string(),
             "--retry-lock".to_string(),
             "1m".to_string(),
         ];
 
-        if let Some(ref excludes) = set.exclude {
-            for exclude in excludes {
-                args.push("--exclude".to_string());
-                args.push(exclude.clone());
+        for exclude in set.backup_excludes() {
+            args.push("--exclude".to_string());
+            args.push(exclude.restic_arg().to_string());
+        }
+        if let Some(ref file) = set.exclude_file {
+            args.push("--exclude-file".to_string());
+            args.push(file.clone());
+        }
+        for marker in &set.exclude_if_present {
+            args.push("--exclude-if-present".to_string());
+            args.push(marker.clone());
+        }
+        if set.exclude_caches {
+            args.push("--exclude-caches".to_string());
+        }
+
+        for tag in &set.tags {
+            args.push("--tag".to_string());
+            args.push(tag.clone());
+        }
+        if let Some(tag) = set.logical_path_tag() {
+            // restic picks the parent by source paths, which change when the
+            // sources move; the logical path's latest snapshot is the real parent.
+            match self.latest_tagged(&set.target, &tag, token.clone()).await {
+                Ok(Some(parent)) => {
+                    args.push("--parent".to_string());
+                    args.push(parent);
+                }
+                Ok(None) => {}
+                Err(e) => warn!(
+                    "Could not find the parent snapshot of set {}: {}",
+                    set.name, e
+                ),
             }
+            args.push("--tag".to_string());
+            args.push(tag);
         }
 
         if let Some(ref source) = set.source {
@@ -163,10 +388,44 @@ impl ResticExecutor {
                 args.push(source.clone());
             }
         }
+        if let Some(ref list) = set.files_from {
+            args.push("--files-from".to_string());
+            args.push(list.clone());
+        }
+
+        // Status lines are consumed as progress and kept out of the collected
+        // output; the run log gets every line
+        let mut log_writer = log.and_then(RunLog::writer);
+        let mut scan_finished = false;
+        let on_line = move |line: &str| {
+            if let Some(ref mut writer) = log_writer {
+                let _ = writeln!(writer, "{}", line);
+            }
+            match parse_backup_status_line(line, &mut scan_finished) {
+                Some(update) => {
+                    if let Some(ref progress) = progress {
+                        progress.send_replace(Some(update));
+                    }
+                    false
+                }
+                None => true,
+            }
+        };
 
-        let (stdout, _) = match self.run_restic(args, token).await {
-            Ok(res) => res,
+        let (stdout, usage) = match self
+            .run_restic_measured(args, self.background_priority(), token, on_line)
+            .await
+        {
+            Ok((stdout, stderr, usage)) => {
+                if let Some(log) = log {
+                    log.append_section("stderr", &stderr);
+                }
+                (stdout, usage)
+            }
             Err(e) => {
+                if let Some(log) = log {
+                    log.append_section("error", &e.to_string());
+                }
                 return Ok(BackupResult {
                     snapshot_id: String::new(),
                     timestamp: Utc::now(),
@@ -174,6 +433,9 @@ impl ResticExecutor {
                     duration_secs: 0.0,
                     success: false,
                     error_message: Some(e.to_string()),
+                    peak_rss_bytes: None,
+                    cpu_secs: None,
+                    hooks: Vec::new(),
                 });
             }
         };
@@ -192,6 +454,9 @@ impl ResticExecutor {
                         duration_secs: summary.total_duration,
                         success: true,
                         error_message: None,
+                        peak_rss_bytes: usage.map(|u| u.peak_rss_bytes),
+                        cpu_secs: usage.map(|u| u.cpu_secs),
+                        hooks: Vec::new(),
                     });
                 }
             }
@@ -204,22 +469,137 @@ impl ResticExecutor {
             duration_secs: 0.0,
             success: false,
             error_message: Some("Could not find summary in restic output".to_string()),
+            peak_rss_bytes: usage.map(|u| u.peak_rss_bytes),
+            cpu_secs: usage.map(|u| u.cpu_secs),
+            hooks: Vec::new(),
         })
     }
 
+    /// Restores a snapshot (the latest if `snapshot_id` is None) into `dest`.
+    /// With `include`, only that path and its contents are restored, keeping
+    /// their location relative to the snapshot root.
+    pub async fn restore(
+        &self,
+        set: &BackupSet,
+        snapshot_id: Option<&str>,
+        include: Option<&str>,
+        dest: &Path,
+        token: Option<tokio_util::sync::CancellationToken>,
+        progress: Option<ProgressSender>,
+    ) -> Result<RestoreSummary> {
+        info!("Restoring set {} into {:?}", set.name, dest);
+        let [password_flag, password] = self.password.args();
+
+        let mut args = vec![
+            "restore".to_string(),
+            snapshot_id.unwrap_or("latest").to_string(),
+            "--repo".to_string(),
+            set.target.clone(),
+            password_flag,
+            password,
+            "--target".to_string(),
+            dest.to_string_lossy().to_string(),
+            "--json".to_string(),
+        ];
+
+        // "latest" in a shared repository means this machine's latest snapshot
+        if snapshot_id.is_none() && set.shared_repo {
+            args.push("--host".to_string());
+            args.push(local_hostname());
+        }
+
+        if let Some(include) = include {
+            args.push("--include".to_string());
+            args.push(include.to_string());
+        }
+
+        let on_line = move |line: &str| match parse_status_line(line) {
+            Some(update) => {
+                if let Some(ref progress) = progress {
+                    progress.send_replace(Some(update));
+                }
+                false
+            }
+            None => true,
+        };
+
+        let (stdout, _) = self.run_restic_streaming(args, token, on_line).await?;
+        Ok(parse_restore_summary(&stdout))
+    }
+
+    /// Returns the size of a regular file in a snapshot via `restic ls`.
+    pub async fn file_size(
+        &self,
+        set: &BackupSet,
+        snapshot_id: &str,
+        path: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        let mut args = vec!["ls".to_string()];
+        args.extend(snapshot_args(set, snapshot_id));
+        args.extend(["--json".to_string(), path.to_string()]);
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        parse_file_size(&stdout, path)
+    }
+
+    /// Lists directory `path` of a snapshot via `restic ls`, everything below
+    /// it with `recursive`. A file path lists just that file.
+    pub async fn list_files(
+        &self,
+        set: &BackupSet,
+        snapshot_id: &str,
+        path: &str,
+        recursive: bool,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<Vec<FileEntry>> {
+        let mut args = vec!["ls".to_string()];
+        args.extend(snapshot_args(set, snapshot_id));
+        args.push("--json".to_string());
+        if recursive {
+            args.push("--recursive".to_string());
+        }
+        args.push(path.to_string());
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        parse_file_list(&stdout, path)
+    }
+
+    /// Spawns `restic dump` for a single file with its contents on stdout.
+    pub fn dump(&self, set: &BackupSet, snapshot_id: &str, path: &str) -> Result<Child> {
+        let mut args = vec!["dump".to_string()];
+        args.extend(snapshot_args(set, snapshot_id));
+        args.push(path.to_string());
+        args.extend(limit_args(&self.throttle));
+
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        debug!("Running Restic command: restic {}", args.join(" "));
+        engine_command("restic", &env, &self.env_passthrough)
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to execute restic")
+    }
+
     pub async fn snapshots(
         &self,
         target: &str,
         limit: Option<usize>,
         token: Option<tokio_util::sync::CancellationToken>,
     ) -> Result<Vec<SnapshotInfo>> {
-        let password_file = paths::password_path();
+        let [password_flag, password] = self.password.args();
         let mut args = vec![
             "snapshots".to_string(),
             "--repo".to_string(),
             target.to_string(),
-            "--password-file".to_string(),
-            password_file.to_string_lossy().to_string(),
+            password_flag,
+            password,
             "--json".to_string(),
         ];
 
@@ -235,146 +615,1153 @@ impl ResticExecutor {
 
         Ok(snapshots
             .into_iter()
-            .map(|s| SnapshotInfo {
-                id: s.id,
-                short_id: s.short_id,
-                timestamp: s.time,
-                paths: s.paths,
-                tags: s.tags.unwrap_or_default(),
-                total_bytes: s.summary.map(|sum| sum.total_bytes_processed),
-            })
+            .map(ResticSnapshot::into_info)
             .collect())
     }
 
-    pub async fn prune(
+    /// ID of this host's newest snapshot carrying `tag`, if there is one.
+    async fn latest_tagged(
         &self,
-        set: &BackupSet,
+        target: &str,
+        tag: &str,
         token: Option<tokio_util::sync::CancellationToken>,
-    ) -> Result<u64> {
-        info!("Pruning repository for set: {}", set.name);
-        let password_file = paths::password_path();
+    ) -> Result<Option<String>> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "snapshots".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--json".to_string(),
+            "--no-lock".to_string(),
+            "--host".to_string(),
+            local_hostname(),
+            "--tag".to_string(),
+            tag.to_string(),
+            "--latest".to_string(),
+            "1".to_string(),
+        ];
+        let (stdout, _) = self.run_restic(args, token).await?;
+        let snapshots: Vec<ResticSnapshot> =
+            serde_json::from_str(&stdout).context("Failed to parse restic snapshots JSON")?;
+        Ok(snapshots.into_iter().max_by_key(|s| s.time).map(|s| s.id))
+    }
 
-        // SAFETY: Require at least one retention policy to prevent deleting all snapshots.
-        // Running `restic forget --prune` without any --keep-* flags deletes everything.
-        let retention = set.retention.as_ref().ok_or_else(|| {
-            anyhow!("Cannot prune set '{}': no retention policy specified. This would delete all snapshots.", set.name)
-        })?;
+    /// Lists the paths that differ between two snapshots via `restic diff`.
+    pub async fn diff(
+        &self,
+        target: &str,
+        snapshot_a: &str,
+        snapshot_b: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<SnapshotDiff> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "diff".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--json".to_string(),
+            snapshot_a.to_string(),
+            snapshot_b.to_string(),
+        ];
 
-        let has_any_policy = retention.keep_last.is_some()
-            || retention.keep_daily.is_some()
-            || retention.keep_weekly.is_some()
-            || retention.keep_monthly.is_some();
+        let (stdout, _) = self.run_restic(args, token).await?;
+        Ok(parse_diff(&stdout))
+    }
 
-        if !has_any_policy {
-            return Err(anyhow!(
-                "Cannot prune set '{}': retention policy has no keep rules. This would delete all snapshots.",
-                set.name
-            ));
+    /// Checks the repository structure and reads back `subset` of its data via
+    /// `restic check --read-data-subset`, reporting progress as packs are read.
+    pub async fn verify(
+        &self,
+        target: &str,
+        subset: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+        progress: Option<ProgressSender>,
+    ) -> Result<()> {
+        if !is_valid_read_subset(subset) {
+            return Err(anyhow!("Invalid read-data subset: {}", subset));
         }
-
+        info!("Verifying restic repository at {} ({})", target, subset);
+        let [password_flag, password] = self.password.args();
         let mut args = vec![
-            "forget".to_string(),
+            "check".to_string(),
             "--repo".to_string(),
-            set.target.clone(),
-            "--password-file".to_string(),
-            password_file.to_string_lossy().to_string(),
-            "--prune".to_string(),
-            "--retry-lock".to_string(),
-            "1m".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            format!("--read-data-subset={}", subset),
         ];
+        args.extend(limit_args(&self.throttle));
 
-        if let Some(last) = retention.keep_last {
-            args.push("--keep-last".to_string());
-            args.push(last.to_string());
-        }
-        if let Some(daily) = retention.keep_daily {
-            args.push("--keep-daily".to_string());
-            args.push(daily.to_string());
-        }
-        if let Some(weekly) = retention.keep_weekly {
-            args.push("--keep-weekly".to_string());
-            args.push(weekly.to_string());
-        }
-        if let Some(monthly) = retention.keep_monthly {
-            args.push("--keep-monthly".to_string());
-            args.push(monthly.to_string());
-        }
-
-        let (stdout, _) = self.run_restic(args, token).await?;
+        // restic only prints progress to a pipe when asked for a refresh rate
+        let mut env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        env.push(("RESTIC_PROGRESS_FPS".to_string(), "1".to_string()));
+        let on_line = move |line: &str| match parse_check_progress(line) {
+            Some(update) => {
+                if let Some(ref progress) = progress {
+                    progress.send_replace(Some(update));
+                }
+                false
+            }
+            None => true,
+        };
 
-        // Parse reclaimed bytes from text output.
-        // Example: "total bytes reclaimed: 1.23 MiB" or "reclaimed 123 bytes"
-        // Since restic output can vary, we'll look for "reclaimed" and try to parse the number.
-        // A more robust way is to look for "total bytes reclaimed: "
-        let reclaimed = parse_reclaimed_bytes(&stdout);
-        Ok(reclaimed)
+        run_tool_streaming(
+            "restic",
+            "Restic",
+            args,
+            &env,
+            &self.env_passthrough,
+            token,
+            on_line,
+        )
+        .await?;
+        Ok(())
     }
 
-    pub async fn mount(
+    /// Runs one step of a guided repair, passing each non-empty output line to
+    /// `on_line`. Returns restic's output.
+    pub async fn repair<F>(
         &self,
         target: &str,
-        snapshot_id: Option<&str>,
-        mountpoint: &Path,
-    ) -> Result<Child> {
-        info!("Mounting repository at {:?}", mountpoint);
-        let password_file = paths::password_path();
+        step: RepairStep,
+        token: Option<tokio_util::sync::CancellationToken>,
+        mut on_line: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        info!("Running {} on restic repository at {}", step, target);
+        let [password_flag, password] = self.password.args();
+        let mut args: Vec<String> = repair_args(step).iter().map(|a| a.to_string()).collect();
+        args.extend([
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+        ]);
+        args.extend(limit_args(&self.throttle));
 
-        let mut args = vec![
-            "mount".to_string(),
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        let (stdout, _) = run_tool_streaming(
+            "restic",
+            "Restic",
+            args,
+            &env,
+            &self.env_passthrough,
+            token,
+            move |line| {
+                if !line.trim().is_empty() {
+                    on_line(line);
+                }
+                true
+            },
+        )
+        .await?;
+        Ok(stdout)
+    }
+
+    /// Computes the restore size of a single snapshot via `restic stats`.
+    /// Used for snapshots written by restic versions that do not record a summary.
+    pub async fn snapshot_size(
+        &self,
+        target: &str,
+        snapshot_id: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "stats".to_string(),
             "--repo".to_string(),
             target.to_string(),
-            "--password-file".to_string(),
-            password_file.to_string_lossy().to_string(),
+            password_flag,
+            password,
+            "--json".to_string(),
+            "--mode".to_string(),
+            "restore-size".to_string(),
+            snapshot_id.to_string(),
         ];
 
-        // Note: restic mount doesn't have a --snapshot flag. It mounts the entire repository
-        // and snapshots are accessible via directory paths like /ids/<snapshot_id>/ or /snapshots/<timestamp>/
-        // The snapshot_id parameter is passed for informational purposes but not used here.
-        let _ = snapshot_id; // Suppress unused warning
-
-        args.push(mountpoint.to_string_lossy().to_string());
+        let (stdout, _) = self.run_restic(args, token).await?;
+        let stats: ResticStats =
+            serde_json::from_str(&stdout).context("Failed to parse restic stats JSON")?;
+        Ok(stats.total_size)
+    }
 
-        let mut cmd = Command::new("restic");
-        cmd.args(&args).stdout(Stdio::null()).stderr(Stdio::piped());
+    /// Computes the space the repository's data uses via `restic stats`, which
+    /// also works for remote repositories that cannot be walked.
+    pub async fn repo_size(
+        &self,
+        target: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "stats".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--json".to_string(),
+            "--mode".to_string(),
+            "raw-data".to_string(),
+            "--no-lock".to_string(),
+        ];
 
-        let mut child = cmd.spawn().context("Failed to spawn restic mount")?;
+        let (stdout, _) = self.run_restic(args, token).await?;
+        let stats: ResticStats =
+            serde_json::from_str(&stdout).context("Failed to parse restic stats JSON")?;
+        Ok(stats.total_size)
+    }
 
-        // Give it a moment to see if it fails immediately (e.g. bad snapshot ID or mount point busy)
-        tokio::time::sleep(Duration::from_millis(MOUNT_STARTUP_CHECK_MS)).await;
-        match child.try_wait() {
-            Ok(Some(status)) if !status.success() => {
-                let mut stderr = String::new();
-                if let Some(mut reader) = child.stderr.take() {
-                    use tokio::io::AsyncReadExt;
-                    let _ = reader.read_to_string(&mut stderr).await;
-                }
-                anyhow::bail!("Restic mount failed: {}", stderr);
-            }
-            _ => Ok(child),
+    /// Repository statistics: `restic stats` in `raw-data` mode for the unique
+    /// data and in `restore-size` mode for what the snapshots hold.
+    pub async fn stats(
+        &self,
+        target: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<RepoStats> {
+        let mut outputs = Vec::new();
+        for mode in ["raw-data", "restore-size"] {
+            let [password_flag, password] = self.password.args();
+            let args = vec![
+                "stats".to_string(),
+                "--repo".to_string(),
+                target.to_string(),
+                password_flag,
+                password,
+                "--json".to_string(),
+                "--mode".to_string(),
+                mode.to_string(),
+                "--no-lock".to_string(),
+            ];
+            let (stdout, _) = self.run_restic(args, token.clone()).await?;
+            outputs.push(stdout);
         }
+        parse_stats(&outputs[0], &outputs[1])
     }
-}
 
-fn parse_reclaimed_bytes(stdout: &str) -> u64 {
-    for line in stdout.lines() {
-        if line.contains("total bytes reclaimed:") {
-            if let Some(val_str) = line.split(':').nth(1) {
-                return parse_restic_size(val_str.trim());
-            }
-        }
+    /// The repository's unique ID, from its config file.
+    pub async fn repo_id(
+        &self,
+        target: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<String> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "cat".to_string(),
+            "config".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--no-lock".to_string(),
+        ];
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        parse_repo_id(&stdout)
     }
-    0
-}
 
-fn parse_restic_size(s: &str) -> u64 {
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    if parts.is_empty() {
-        return 0;
+    /// First line of `restic version`, e.g. `restic 0.17.3 compiled with go1.23.3 on linux/amd64`.
+    pub async fn version(&self) -> Result<String> {
+        let (stdout, _) = run_tool("restic", "Restic", vec!["version".to_string()], None).await?;
+        Ok(first_line(&stdout))
     }
 
-    let val: f64 = parts[0].parse().unwrap_or(0.0);
-    if parts.len() < 2 {
-        return val as u64;
+    /// Counts the lock files currently held in the repository, without taking a lock.
+    pub async fn list_locks(
+        &self,
+        target: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<usize> {
+        let [password_flag, password] = self.password.args();
+        let args = vec![
+            "list".to_string(),
+            "locks".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--no-lock".to_string(),
+        ];
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        Ok(stdout.lines().filter(|l| !l.trim().is_empty()).count())
+    }
+
+    /// Removes the repository's stale locks, those whose process is gone on
+    /// this host or which have not been refreshed for half an hour. With
+    /// `remove_all`, every lock is removed, including those of operations
+    /// that may still be running.
+    pub async fn unlock(
+        &self,
+        target: &str,
+        remove_all: bool,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<()> {
+        let [password_flag, password] = self.password.args();
+        let mut args = vec![
+            "unlock".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+        ];
+        if remove_all {
+            args.push("--remove-all".to_string());
+        }
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        debug!("restic unlock: {}", stdout.trim());
+        Ok(())
+    }
+
+    pub async fn prune(
+        &self,
+        set: &BackupSet,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        info!("Pruning repository for set: {}", set.name);
+        let [password_flag, password] = self.password.args();
+
+        let keep_args = retention_args(set)?;
+
+        // A shared repository may be mid-backup from another machine. Restic's
+        // exclusive lock would wait on it, so refuse up front with a clear error.
+        if set.shared_repo {
+            let locks = self.list_locks(&set.target, token.clone()).await?;
+            if locks > 0 {
+                return Err(anyhow!(
+                    "Cannot prune set '{}': the shared repository is locked by {} other operation(s), possibly from another host. Try again later.",
+                    set.name,
+                    locks
+                ));
+            }
+        }
+
+        let mut args = vec![
+            "forget".to_string(),
+            "--repo".to_string(),
+            set.target.clone(),
+            password_flag,
+            password,
+            "--prune".to_string(),
+            "--retry-lock".to_string(),
+            "1m".to_string(),
+        ];
+
+        if let Some(group_by) = retention_group_by(set) {
+            args.push("--group-by".to_string());
+            args.push(group_by.to_string());
+        }
+        // Only apply retention to this machine's snapshots; other hosts manage their own.
+        if set.shared_repo {
+            args.push("--host".to_string());
+            args.push(local_hostname());
+        }
+
+        args.extend(keep_args);
+
+        let (stdout, _, _) = self
+            .run_restic_measured(args, self.background_priority(), token, |_| true)
+            .await?;
+
+        // Parse reclaimed bytes from text output.
+        // Example: "total bytes reclaimed: 1.23 MiB" or "reclaimed 123 bytes"
+        // Since restic output can vary, we'll look for "reclaimed" and try to parse the number.
+        // A more robust way is to look for "total bytes reclaimed: "
+        let reclaimed = parse_reclaimed_bytes(&stdout);
+        Ok(reclaimed)
+    }
+
+    /// Copies the snapshots `replica` lacks from the set's repository into it
+    /// (`restic copy`). A replica that does not exist yet is created with the
+    /// repository's chunker parameters, so copied data deduplicates. Returns
+    /// how many snapshots were copied.
+    pub async fn copy(
+        &self,
+        set: &BackupSet,
+        replica: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<usize> {
+        let [password_flag, password] = self.password.args();
+        let [from_password_flag, from_password] = self.password.from_repo_args();
+        let repos = vec![
+            "--repo".to_string(),
+            replica.to_string(),
+            password_flag,
+            password,
+            "--from-repo".to_string(),
+            set.target.clone(),
+            from_password_flag,
+            from_password,
+        ];
+        let mut args = vec!["copy".to_string()];
+        args.extend(repos.iter().cloned());
+
+        let priority = self.background_priority();
+        let stdout = match self
+            .run_restic_measured(args.clone(), priority, token.clone(), |_| true)
+            .await
+        {
+            Ok((stdout, _, _)) => stdout,
+            Err(e) if e.to_string().contains("repository does not exist") => {
+                info!("Initializing replica of set {} at {}", set.name, replica);
+                let mut init = vec!["init".to_string()];
+                init.extend(repos);
+                init.push("--copy-chunker-params".to_string());
+                self.run_restic(init, token.clone()).await?;
+                self.run_restic_measured(args, priority, token, |_| true)
+                    .await?
+                    .0
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(parse_copied_snapshots(&stdout))
+    }
+
+    /// Runs the set's retention policy with `--dry-run`. Returns the snapshots
+    /// it would forget, oldest first, and how many it would keep.
+    pub async fn forget_preview(
+        &self,
+        set: &BackupSet,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<(Vec<SnapshotInfo>, usize)> {
+        let [password_flag, password] = self.password.args();
+        let keep_args = retention_args(set)?;
+
+        let mut args = vec![
+            "forget".to_string(),
+            "--repo".to_string(),
+            set.target.clone(),
+            password_flag,
+            password,
+            "--dry-run".to_string(),
+            "--json".to_string(),
+            "--no-lock".to_string(),
+        ];
+        if let Some(group_by) = retention_group_by(set) {
+            args.push("--group-by".to_string());
+            args.push(group_by.to_string());
+        }
+        if set.shared_repo {
+            args.push("--host".to_string());
+            args.push(local_hostname());
+        }
+        args.extend(keep_args);
+
+        let (stdout, _) = self.run_restic(args, token).await?;
+        parse_forget_preview(&stdout)
+    }
+
+    /// Removes one snapshot, and with `prune` the data no other snapshot
+    /// references. Returns the bytes reclaimed, 0 without `prune`.
+    pub async fn forget(
+        &self,
+        target: &str,
+        snapshot_id: &str,
+        prune: bool,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        info!("Forgetting snapshot {} in {}", snapshot_id, target);
+        let [password_flag, password] = self.password.args();
+        let mut args = vec![
+            "forget".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+            "--retry-lock".to_string(),
+            "1m".to_string(),
+        ];
+        if prune {
+            args.push("--prune".to_string());
+        }
+        args.push(snapshot_id.to_string());
+
+        let (stdout, _, _) = self
+            .run_restic_measured(args, self.background_priority(), token, |_| true)
+            .await?;
+        Ok(parse_reclaimed_bytes(&stdout))
+    }
+
+    pub async fn mount(
+        &self,
+        target: &str,
+        snapshot_id: Option<&str>,
+        mountpoint: &Path,
+    ) -> Result<Child> {
+        info!("Mounting repository at {:?}", mountpoint);
+        let [password_flag, password] = self.password.args();
+
+        let mut args = vec![
+            "mount".to_string(),
+            "--repo".to_string(),
+            target.to_string(),
+            password_flag,
+            password,
+        ];
+
+        // Note: restic mount doesn't have a --snapshot flag. It mounts the entire repository
+        // and snapshots are accessible via directory paths like /ids/<snapshot_id>/ or /snapshots/<timestamp>/
+        // The snapshot_id parameter is passed for informational purposes but not used here.
+        let _ = snapshot_id; // Suppress unused warning
+
+        args.push(mountpoint.to_string_lossy().to_string());
+        args.extend(limit_args(&self.throttle));
+
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        spawn_mount("restic", "Restic", args, &env, &self.env_passthrough).await
+    }
+}
+
+/// Records whether `ionice` is available to lower the I/O priority of engine processes.
+pub fn set_ionice_available(available: bool) {
+    IONICE_AVAILABLE.store(available, Ordering::Relaxed);
+}
+
+/// restic's `--limit-upload`/`--limit-download` arguments for `throttle`.
+fn limit_args(throttle: &Throttle) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(kib) = throttle.upload_limit_kib {
+        args.extend(["--limit-upload".to_string(), kib.to_string()]);
+    }
+    if let Some(kib) = throttle.download_limit_kib {
+        args.extend(["--limit-download".to_string(), kib.to_string()]);
+    }
+    args
+}
+
+/// restic subcommand and flags of a repair step. The repairs need an exclusive
+/// lock, so they wait a little for other operations to finish.
+fn repair_args(step: RepairStep) -> &'static [&'static str] {
+    match step {
+        RepairStep::Check => &["check"],
+        RepairStep::Index => &["repair", "index", "--retry-lock", "1m"],
+        RepairStep::Snapshots => &["repair", "snapshots", "--forget", "--retry-lock", "1m"],
+    }
+}
+
+/// Program and arguments that run `program` at `priority`, through `nice` and,
+/// when it is available, `ionice`.
+pub(crate) fn prioritized(
+    program: &str,
+    args: Vec<String>,
+    priority: Priority,
+) -> (String, Vec<String>) {
+    wrap_priority(
+        program,
+        args,
+        priority,
+        IONICE_AVAILABLE.load(Ordering::Relaxed),
+    )
+}
+
+fn wrap_priority(
+    program: &str,
+    args: Vec<String>,
+    priority: Priority,
+    ionice: bool,
+) -> (String, Vec<String>) {
+    let (niceness, io_class): (&str, &[&str]) = match priority {
+        Priority::Normal => return (program.to_string(), args),
+        Priority::Low => ("10", &["-c", "2", "-n", "7"]),
+        Priority::Idle => ("19", &["-c", "3"]),
+    };
+    let mut wrapped: Vec<String> = Vec::new();
+    if ionice {
+        wrapped.extend(io_class.iter().map(|a| a.to_string()));
+        wrapped.push("nice".to_string());
+    }
+    wrapped.extend(["-n".to_string(), niceness.to_string(), program.to_string()]);
+    wrapped.extend(args);
+    let outer = if ionice { "ionice" } else { "nice" };
+    (outer.to_string(), wrapped)
+}
+
+/// [`vigil_lib::config::engine_command`], logging the variables it sets with
+/// their secrets masked.
+pub(crate) fn engine_command(
+    program: &str,
+    env: &[(String, String)],
+    passthrough: &[String],
+) -> Command {
+    if !env.is_empty() {
+        debug!(
+            "{} environment: {}",
+            program,
+            vigil_lib::config::masked_env(env)
+        );
+    }
+    vigil_lib::config::engine_command(program, env, passthrough)
+}
+
+/// Spawns a long-running mount process and checks that it did not fail immediately.
+pub(crate) async fn spawn_mount(
+    program: &str,
+    label: &str,
+    args: Vec<String>,
+    env: &[(String, String)],
+    passthrough: &[String],
+) -> Result<Child> {
+    let mut cmd = engine_command(program, env, passthrough);
+    cmd.args(&args).stdout(Stdio::null()).stderr(Stdio::piped());
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to spawn {} mount", program))?;
+
+    // Give it a moment to see if it fails immediately (e.g. bad snapshot ID or mount point busy)
+    tokio::time::sleep(Duration::from_millis(MOUNT_STARTUP_CHECK_MS)).await;
+    match child.try_wait() {
+        Ok(Some(status)) if !status.success() => {
+            let mut stderr = String::new();
+            if let Some(mut reader) = child.stderr.take() {
+                use tokio::io::AsyncReadExt;
+                let _ = reader.read_to_string(&mut stderr).await;
+            }
+            anyhow::bail!("{} mount failed: {}", label, stderr);
+        }
+        _ => Ok(child),
+    }
+}
+
+/// Parses a restic `--json` status line. Returns None for any other line.
+fn parse_status_line(line: &str) -> Option<Progress> {
+    parse_status(line).map(ResticStatus::into_progress)
+}
+
+/// Like [`parse_status_line`], also telling `restic backup`'s scan phase
+/// apart. restic only estimates the remaining time once it has found every
+/// file, so lines before the first `seconds_remaining` are still scanning.
+/// The estimate can drop out again near the end, so `scan_finished` keeps
+/// the scan over for the rest of the run.
+fn parse_backup_status_line(line: &str, scan_finished: &mut bool) -> Option<Progress> {
+    let status = parse_status(line)?;
+    *scan_finished |= status.seconds_remaining.is_some();
+    let scanning = (!*scan_finished).then_some(Scanning {
+        files: status.total_files,
+        bytes: status.total_bytes,
+    });
+    Some(Progress {
+        scanning,
+        ..status.into_progress()
+    })
+}
+
+fn parse_status(line: &str) -> Option<ResticStatus> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    if value.get("message_type").and_then(|v| v.as_str()) != Some("status") {
+        return None;
+    }
+    serde_json::from_value(value).ok()
+}
+
+impl ResticStatus {
+    fn into_progress(self) -> Progress {
+        Progress {
+            bytes_done: self.bytes_done,
+            total_bytes: self.total_bytes,
+            current_file: self.current_files.into_iter().next(),
+            ..Progress::new(self.percent_done, self.seconds_elapsed)
+        }
+    }
+}
+
+/// Repository and snapshot arguments shared by `ls` and `dump`. "latest" in a
+/// shared repository means this machine's latest snapshot.
+fn snapshot_args(set: &BackupSet, snapshot_id: &str) -> Vec<String> {
+    let mut args = vec!["--repo".to_string(), set.target.clone()];
+    args.extend(set.password_source().args());
+    if snapshot_id == "latest" && set.shared_repo {
+        args.push("--host".to_string());
+        args.push(local_hostname());
+    }
+    args.push(snapshot_id.to_string());
+    args
+}
+
+/// Finds `path` in `restic ls --json` output and returns its size if it is a regular file.
+fn parse_file_size(stdout: &str, path: &str) -> Result<u64> {
+    let node = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ResticNode>(line).ok())
+        .find(|node| node.path.as_deref() == Some(path))
+        .ok_or_else(|| anyhow!("Path {} not found in snapshot", path))?;
+    match node.node_type.as_deref() {
+        Some("file") => Ok(node.size.unwrap_or(0)),
+        Some("dir") => Err(anyhow!(
+            "{} is a directory; use `vigil restore --path` instead",
+            path
+        )),
+        other => Err(anyhow!(
+            "{} is not a regular file ({})",
+            path,
+            other.unwrap_or("unknown type")
+        )),
+    }
+}
+
+/// Entries of `restic ls --json` output for `path`. restic prints a listed
+/// directory itself before its contents; it is left out. A path the snapshot
+/// does not have lists nothing, which is reported as an error.
+fn parse_file_list(stdout: &str, path: &str) -> Result<Vec<FileEntry>> {
+    let mut found = path == "/";
+    let mut entries = Vec::new();
+    for node in stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<ResticNode>(line).ok())
+    {
+        let Some(node_path) = node.path else {
+            continue;
+        };
+        let node_type = node.node_type.unwrap_or_else(|| "unknown".to_string());
+        if node_path == path {
+            found = true;
+            if node_type == "dir" {
+                continue;
+            }
+        }
+        entries.push(FileEntry {
+            path: node_path,
+            size: node.size.filter(|_| node_type == "file"),
+            mtime: node
+                .mtime
+                .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                .map(|t| t.with_timezone(&Utc)),
+            node_type,
+        });
+    }
+    if !found {
+        return Err(anyhow!("Path {} not found in snapshot", path));
+    }
+    Ok(entries)
+}
+
+/// Snapshots `restic copy` saved in the destination, from its
+/// `snapshot <id> saved` lines. Skipped snapshots are not counted.
+fn parse_copied_snapshots(stdout: &str) -> usize {
+    stdout
+        .lines()
+        .filter(|line| line.starts_with("snapshot ") && line.trim_end().ends_with(" saved"))
+        .count()
+}
+
+/// Whether `subset` is a `--read-data-subset` value restic accepts: a
+/// percentage (`5%`), a fraction (`1/10`) or a size with a unit (`500M`).
+pub fn is_valid_read_subset(subset: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.parse::<f64>().is_ok_and(|n| n > 0.0);
+    if let Some(percent) = subset.strip_suffix('%') {
+        return is_number(percent) && percent.parse::<f64>().is_ok_and(|n| n <= 100.0);
+    }
+    if let Some((n, m)) = subset.split_once('/') {
+        return match (n.parse::<u32>(), m.parse::<u32>()) {
+            (Ok(n), Ok(m)) => n >= 1 && n <= m,
+            _ => false,
+        };
+    }
+    match subset.strip_suffix(['K', 'M', 'G', 'T', 'k', 'm', 'g', 't']) {
+        Some(size) => size.bytes().all(|b| b.is_ascii_digit()) && is_number(size),
+        None => false,
+    }
+}
+
+/// Parses a progress line of `restic check --read-data-subset`, such as
+/// `[0:42] 45.00%  9 / 20 packs`. Returns None for any other line.
+fn parse_check_progress(line: &str) -> Option<Progress> {
+    let (elapsed, rest) = line.trim().strip_prefix('[')?.split_once(']')?;
+    let (percent, rest) = rest.trim_start().split_once('%')?;
+    if !rest.trim_end().ends_with("packs") {
+        return None;
+    }
+    let percent: f64 = percent.parse().ok()?;
+    let elapsed_secs = elapsed
+        .split(':')
+        .try_fold(0u64, |acc, part| Some(acc * 60 + part.parse::<u64>().ok()?));
+    Some(Progress::new(percent / 100.0, elapsed_secs))
+}
+
+/// Sorts the `change` messages of `restic diff --json` by modifier: `+` is an
+/// added path, `-` a removed one, and anything else (`M`, `T`, `U`, `?`) a modification.
+fn parse_diff(stdout: &str) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    for line in stdout.lines() {
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if value.get("message_type").and_then(|v| v.as_str()) != Some("change") {
+            continue;
+        }
+        let Ok(change) = serde_json::from_value::<ResticDiffChange>(value) else {
+            continue;
+        };
+        match change.modifier.as_str() {
+            "+" => diff.added.push(change.path),
+            "-" => diff.removed.push(change.path),
+            _ => diff.modified.push(change.path),
+        }
+    }
+    diff
+}
+
+/// Finds restic's restore summary. Versions without JSON restore output
+/// produce none, which yields zero counts.
+fn parse_restore_summary(stdout: &str) -> RestoreSummary {
+    stdout
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|value| value.get("message_type").and_then(|v| v.as_str()) == Some("summary"))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Runs a backup tool to completion and returns its stdout and stderr, killing it
+/// if `token` is cancelled. `label` names the tool in log and error messages.
+pub(crate) async fn run_tool(
+    program: &str,
+    label: &str,
+    args: Vec<String>,
+    token: Option<tokio_util::sync::CancellationToken>,
+) -> Result<(String, String)> {
+    run_tool_streaming(program, label, args, &[], &[], token, |_| true).await
+}
+
+/// Like [`run_tool`], but sets `env` for the tool, keeps the inherited engine
+/// variables in `passthrough`, and passes each stdout line to `on_line` as it
+/// arrives. Lines for which `on_line` returns false are left out of the
+/// returned stdout.
+pub(crate) async fn run_tool_streaming<F>(
+    program: &str,
+    label: &str,
+    args: Vec<String>,
+    env: &[(String, String)],
+    passthrough: &[String],
+    token: Option<tokio_util::sync::CancellationToken>,
+    on_line: F,
+) -> Result<(String, String)>
+where
+    F: FnMut(&str) -> bool + Send + 'static,
+{
+    let (stdout, stderr, _) =
+        run_tool_measured(program, label, args, env, passthrough, token, on_line).await?;
+    Ok((stdout, stderr))
+}
+
+/// Like [`run_tool_streaming`], but also returns the tool's resource usage.
+pub(crate) async fn run_tool_measured<F>(
+    program: &str,
+    label: &str,
+    args: Vec<String>,
+    env: &[(String, String)],
+    passthrough: &[String],
+    token: Option<tokio_util::sync::CancellationToken>,
+    mut on_line: F,
+) -> Result<(String, String, Option<ResourceUsage>)>
+where
+    F: FnMut(&str) -> bool + Send + 'static,
+{
+    let mut cmd = engine_command(program, env, passthrough);
+    cmd.args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    debug!("Running {} command: {} {}", label, program, args.join(" "));
+
+    // We use spawn() so we can interact with the child process (kill it on cancellation)
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to execute {}", program))?;
+    let stdout_pipe = child.stdout.take().context("Failed to take stdout")?;
+    let stderr_pipe = child.stderr.take().context("Failed to take stderr")?;
+
+    let stdout_handle = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let mut line = Vec::new();
+        use tokio::io::AsyncBufReadExt;
+        let mut reader = tokio::io::BufReader::new(stdout_pipe);
+        while matches!(reader.read_until(b'\n', &mut line).await, Ok(n) if n > 0) {
+            if on_line(String::from_utf8_lossy(&line).trim_end()) {
+                buf.append(&mut line);
+            }
+            line.clear();
+        }
+        buf
+    });
+
+    let stderr_handle = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        use tokio::io::AsyncReadExt;
+        let mut reader = stderr_pipe;
+        let _ = reader.read_to_end(&mut buf).await;
+        buf
+    });
+
+    let (status_res, usage) = if let Some(token) = token {
+        tokio::select! {
+            waited = wait_with_usage(&mut child) => waited,
+            _ = token.cancelled() => {
+                info!("{} command cancelled, killing process...", label);
+                let _ = child.kill().await;
+                return Err(anyhow!("{} command cancelled", label));
+            }
+        }
+    } else {
+        wait_with_usage(&mut child).await
+    };
+
+    let status = status_res.with_context(|| format!("Failed to wait for {} process", program))?;
+    let stdout_bytes = stdout_handle.await.unwrap_or_default();
+    let stderr_bytes = stderr_handle.await.unwrap_or_default();
+
+    let stdout = String::from_utf8_lossy(&stdout_bytes).to_string();
+    let stderr = String::from_utf8_lossy(&stderr_bytes).to_string();
+
+    if !status.success() {
+        // Restic backup can return non-zero (3) for some warnings but still produce a snapshot
+        if args.contains(&"backup".to_string()) && !stdout.is_empty() {
+            debug!(
+                "{} backup returned non-zero ({}) but produced output, checking for summary",
+                label, status
+            );
+        } else {
+            error!("{} failed: {}", label, stderr);
+            return Err(anyhow!("{} error: {}", label, stderr));
+        }
+    }
+
+    Ok((stdout, stderr, usage))
+}
+
+/// Waits for `child` to exit, returning its exit status and resource usage.
+async fn wait_with_usage(
+    child: &mut Child,
+) -> (
+    std::io::Result<std::process::ExitStatus>,
+    Option<ResourceUsage>,
+) {
+    // The usage is read once the child exits but before `wait` reaps it
+    #[cfg(unix)]
+    let usage = match child.id() {
+        Some(pid) => tokio::task::spawn_blocking(move || exit_usage(pid))
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+    // Windows has no unreaped zombie to read the usage from
+    #[cfg(not(unix))]
+    let usage = None;
+    (child.wait().await, usage)
+}
+
+/// Waits for process `pid` to exit and returns its resource usage. The process
+/// is left unreaped, so `Child::wait` still collects its exit status.
+#[cfg(target_os = "linux")]
+fn exit_usage(pid: u32) -> Option<ResourceUsage> {
+    // SAFETY: both structs are plain C data for which all-zero bytes are valid
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // The raw syscall takes the `rusage` argument that libc's waitid() lacks
+        // SAFETY: the pointers are valid for the duration of the call
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+        if rc == 0 {
+            break;
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
+    let secs = |t: libc::timeval| t.tv_sec as f64 + t.tv_usec as f64 / 1_000_000.0;
+    Some(ResourceUsage {
+        // Linux reports the peak resident set size in KiB
+        peak_rss_bytes: usage.ru_maxrss.max(0) as u64 * 1024,
+        cpu_secs: secs(usage.ru_utime) + secs(usage.ru_stime),
+    })
+}
+
+/// Waits for process `pid` to exit, leaving it unreaped. Only Linux's
+/// `waitid` reports a single child's resource usage, so there is none here.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn exit_usage(pid: u32) -> Option<ResourceUsage> {
+    // SAFETY: siginfo_t is plain C data for which all-zero bytes are valid
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: the pointer is valid for the duration of the call
+        let rc = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if rc == 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
+}
+
+/// Whether an engine error shows the backend refusing to delete files, as a
+/// REST server in append-only mode does (HTTP 403).
+pub(crate) fn is_append_only_rejection(message: &str) -> bool {
+    message.contains("403 Forbidden") || message.contains("(403)")
+}
+
+/// Whether an engine error shows the repository locked by another operation.
+pub fn is_lock_error(message: &str) -> bool {
+    message.contains("repository is already locked") || message.contains("unable to create lock")
+}
+
+/// Splits `restic forget --dry-run --json` output into the snapshots that
+/// would be removed, oldest first, and the number kept.
+fn parse_forget_preview(stdout: &str) -> Result<(Vec<SnapshotInfo>, usize)> {
+    // restic prints nothing for a repository without snapshots
+    if stdout.trim().is_empty() {
+        return Ok((Vec::new(), 0));
+    }
+    let groups: Vec<ResticForgetGroup> =
+        serde_json::from_str(stdout).context("Failed to parse restic forget JSON")?;
+    let to_keep = groups.iter().map(|g| g.keep.len()).sum();
+    let mut to_remove: Vec<SnapshotInfo> = groups
+        .into_iter()
+        .flat_map(|g| g.remove.unwrap_or_default())
+        .map(ResticSnapshot::into_info)
+        .collect();
+    to_remove.sort_by_key(|s| s.timestamp);
+    Ok((to_remove, to_keep))
+}
+
+/// Builds the `--keep-*` arguments for a set's retention policy.
+///
+/// SAFETY: Requires at least one keep rule. Running `forget --prune` without any
+/// --keep-* flags deletes every snapshot.
+pub(crate) fn retention_args(set: &BackupSet) -> Result<Vec<String>> {
+    let retention = set.retention.as_ref().ok_or_else(|| {
+        anyhow!("Cannot prune set '{}': no retention policy specified. This would delete all snapshots.", set.name)
+    })?;
+
+    let mut args = Vec::new();
+    if let Some(last) = retention.keep_last {
+        args.push("--keep-last".to_string());
+        args.push(last.to_string());
+    }
+    if let Some(daily) = retention.keep_daily {
+        args.push("--keep-daily".to_string());
+        args.push(daily.to_string());
+    }
+    if let Some(weekly) = retention.keep_weekly {
+        args.push("--keep-weekly".to_string());
+        args.push(weekly.to_string());
+    }
+    if let Some(monthly) = retention.keep_monthly {
+        args.push("--keep-monthly".to_string());
+        args.push(monthly.to_string());
+    }
+    if let Some(yearly) = retention.keep_yearly {
+        args.push("--keep-yearly".to_string());
+        args.push(yearly.to_string());
+    }
+    if let Some(ref within) = retention.keep_within {
+        args.push("--keep-within".to_string());
+        args.push(within.clone());
+    }
+
+    if args.is_empty() {
+        return Err(anyhow!(
+            "Cannot prune set '{}': retention policy has no keep rules. This would delete all snapshots.",
+            set.name
+        ));
+    }
+    // Only added after the check: `--keep-tag` alone forgets every untagged snapshot
+    for tag in &retention.keep_tag {
+        args.push("--keep-tag".to_string());
+        args.push(tag.clone());
+    }
+    Ok(args)
+}
+
+/// How `forget` groups snapshots before applying the keep rules, when it must
+/// differ from the engine's default. A shared repository groups by host so
+/// only this machine's snapshots are thinned; `per_tag` retention adds the tags.
+/// A set with a `logical_path` groups by tags instead of paths, so snapshots
+/// from before and after a move of its sources share one history.
+pub(crate) fn retention_group_by(set: &BackupSet) -> Option<&'static str> {
+    let per_tag = set.retention.as_ref().is_some_and(|r| r.per_tag);
+    if set.logical_path.is_some() {
+        return Some("host,tags");
+    }
+    match (set.shared_repo, per_tag) {
+        (_, true) => Some("host,paths,tags"),
+        (true, false) => Some("host,paths"),
+        (false, false) => None,
+    }
+}
+
+/// Hostname of this machine, as restic records it in new snapshots.
+#[cfg(unix)]
+pub fn local_hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: buf is valid for buf.len() bytes; gethostname NUL-terminates on success.
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Hostname of this machine, as Windows reports it to processes.
+#[cfg(not(unix))]
+pub fn local_hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+pub(crate) fn parse_reclaimed_bytes(stdout: &str) -> u64 {
+    for line in stdout.lines() {
+        if line.contains("total bytes reclaimed:") {
+            if let Some(val_str) = line.split(':').nth(1) {
+                return parse_restic_size(val_str.trim());
+            }
+        }
+    }
+    0
+}
+
+fn parse_restic_size(s: &str) -> u64 {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if parts.is_empty() {
+        return 0;
+    }
+
+    let val: f64 = parts[0].parse().unwrap_or(0.0);
+    if parts.len() < 2 {
+        return val as u64;
     }
 
     let unit = parts[1].to_lowercase();
@@ -393,3 +1780,510 @@ fn parse_restic_size(s: &str) -> u64 {
 
     (val * multiplier) as u64
 }
+
+/// Repository config as printed by `cat config`, by restic and rustic alike.
+#[derive(Deserialize)]
+struct RepoConfig {
+    id: String,
+}
+
+pub(crate) fn parse_repo_id(stdout: &str) -> Result<String> {
+    let config: RepoConfig =
+        serde_json::from_str(stdout).context("Failed to parse repository config JSON")?;
+    Ok(config.id)
+}
+
+pub(crate) fn first_line(stdout: &str) -> String {
+    stdout.lines().next().unwrap_or_default().trim().to_string()
+}
+
+/// Combines the JSON of `restic stats --mode raw-data` and `--mode restore-size`.
+/// Repositories without compression report no uncompressed size; their stored
+/// size is the raw size.
+fn parse_stats(raw_data: &str, restore_size: &str) -> Result<RepoStats> {
+    let raw: ResticStats =
+        serde_json::from_str(raw_data).context("Failed to parse restic stats JSON")?;
+    let restore: ResticStats =
+        serde_json::from_str(restore_size).context("Failed to parse restic stats JSON")?;
+    Ok(RepoStats {
+        snapshot_count: restore.snapshots_count,
+        restore_bytes: restore.total_size,
+        file_count: restore.total_file_count,
+        raw_bytes: if raw.total_uncompressed_size > 0 {
+            raw.total_uncompressed_size
+        } else {
+            raw.total_size
+        },
+        stored_bytes: raw.total_size,
+        blob_count: raw.total_blob_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vigil_lib::config::RetentionPolicy;
+
+    #[test]
+    fn test_parse_stats() {
+        let raw = r#"{"total_size":500,"total_uncompressed_size":1000,"compression_ratio":2,"total_blob_count":42,"snapshots_count":3}"#;
+        let restore = r#"{"total_size":15000,"total_file_count":120,"snapshots_count":3}"#;
+        let stats = parse_stats(raw, restore).unwrap();
+        assert_eq!(stats.snapshot_count, 3);
+        assert_eq!(stats.file_count, 120);
+        assert_eq!(stats.raw_bytes, 1000);
+        assert_eq!(stats.stored_bytes, 500);
+        assert_eq!(stats.blob_count, 42);
+        assert_eq!(stats.dedup_ratio(), Some(15.0));
+        assert_eq!(stats.compression_ratio(), Some(2.0));
+
+        // Repository format 1 has no compression fields
+        let stats = parse_stats(r#"{"total_size":800,"total_blob_count":9}"#, restore).unwrap();
+        assert_eq!(stats.raw_bytes, 800);
+        assert_eq!(stats.compression_ratio(), Some(1.0));
+        assert!(parse_stats("not json", restore).is_err());
+    }
+
+    #[test]
+    fn test_parse_repo_id() {
+        let config = r#"{"version":2,"id":"5c1b4ef4f1a2","chunker_polynomial":"3da3358b4c6e2b"}"#;
+        assert_eq!(parse_repo_id(config).unwrap(), "5c1b4ef4f1a2");
+        assert!(parse_repo_id("Fatal: wrong password").is_err());
+    }
+
+    #[test]
+    fn test_parse_forget_preview() {
+        let snapshot = |id: &str, day: u32| {
+            format!(
+                r#"{{"id":"{id}0000","short_id":"{id}","time":"2026-01-{day:02}T10:00:00Z","hostname":"laptop","paths":["/home/user"]}}"#
+            )
+        };
+        let json = format!(
+            r#"[{{"host":"laptop","paths":["/home/user"],"keep":[{}],"remove":[{},{}],"reasons":[]}},{{"host":"laptop","paths":["/srv"],"keep":[{}],"remove":null}}]"#,
+            snapshot("cccc", 3),
+            snapshot("bbbb", 2),
+            snapshot("aaaa", 1),
+            snapshot("dddd", 4)
+        );
+        let (to_remove, to_keep) = parse_forget_preview(&json).unwrap();
+        assert_eq!(to_keep, 2);
+        let ids: Vec<&str> = to_remove.iter().map(|s| s.short_id.as_str()).collect();
+        assert_eq!(ids, vec!["aaaa", "bbbb"]);
+
+        assert_eq!(parse_forget_preview("").unwrap(), (Vec::new(), 0));
+        assert!(parse_forget_preview("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_status_line() {
+        let line = r#"{"message_type":"status","seconds_elapsed":60,"percent_done":0.25,"total_files":100}"#;
+        let progress = parse_status_line(line).unwrap();
+        assert_eq!(progress.percent, 25.0);
+        assert_eq!(progress.eta_secs, Some(180));
+        assert_eq!(progress.bytes_done, 0);
+        assert_eq!(progress.current_file, None);
+
+        let line = r#"{"message_type":"status","percent_done":0.5,"total_bytes":2048,"bytes_done":1024,"current_files":["/home/u/a.txt","/home/u/b.txt"]}"#;
+        let progress = parse_status_line(line).unwrap();
+        assert_eq!(progress.bytes_done, 1024);
+        assert_eq!(progress.total_bytes, 2048);
+        assert_eq!(progress.current_file.as_deref(), Some("/home/u/a.txt"));
+
+        let summary =
+            r#"{"message_type":"summary","data_added":1,"total_duration":1.0,"snapshot_id":"abc"}"#;
+        assert!(parse_status_line(summary).is_none());
+        assert!(parse_status_line("not json").is_none());
+    }
+
+    #[test]
+    fn test_parse_backup_status_line_tracks_scan() {
+        let mut scan_finished = false;
+        let scanning = r#"{"message_type":"status","seconds_elapsed":30,"percent_done":0.01,"total_files":52000,"total_bytes":9000000000,"bytes_done":90000000}"#;
+        let progress = parse_backup_status_line(scanning, &mut scan_finished).unwrap();
+        assert_eq!(
+            progress.scanning,
+            Some(Scanning {
+                files: 52000,
+                bytes: 9000000000
+            })
+        );
+
+        let scanned = r#"{"message_type":"status","seconds_elapsed":60,"seconds_remaining":540,"percent_done":0.1,"total_files":60000}"#;
+        let progress = parse_backup_status_line(scanned, &mut scan_finished).unwrap();
+        assert_eq!(progress.scanning, None);
+        assert_eq!(progress.percent, 10.0);
+
+        // restic drops the estimate once it reaches zero
+        let finishing = r#"{"message_type":"status","seconds_elapsed":600,"percent_done":1.0}"#;
+        let progress = parse_backup_status_line(finishing, &mut scan_finished).unwrap();
+        assert_eq!(progress.scanning, None);
+    }
+
+    #[test]
+    fn test_parse_file_size() {
+        let stdout = concat!(
+            r#"{"time":"2026-01-01T00:00:00Z","paths":["/home/u"],"id":"abc","struct_type":"snapshot"}"#,
+            "\n",
+            r#"{"name":"u","type":"dir","path":"/home/u","struct_type":"node"}"#,
+            "\n",
+            r#"{"name":"a.conf","type":"file","path":"/home/u/a.conf","size":42,"struct_type":"node"}"#,
+            "\n",
+        );
+        assert_eq!(parse_file_size(stdout, "/home/u/a.conf").unwrap(), 42);
+        assert!(parse_file_size(stdout, "/home/u")
+            .unwrap_err()
+            .to_string()
+            .contains("directory"));
+        assert!(parse_file_size(stdout, "/home/u/missing")
+            .unwrap_err()
+            .to_string()
+            .contains("not found"));
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let stdout = concat!(
+            r#"{"time":"2026-01-01T00:00:00Z","paths":["/home/u"],"id":"abc","struct_type":"snapshot"}"#,
+            "\n",
+            r#"{"name":"u","type":"dir","path":"/home/u","mtime":"2026-10-01T10:00:00.5+02:00","struct_type":"node"}"#,
+            "\n",
+            r#"{"name":"a.conf","type":"file","path":"/home/u/a.conf","size":42,"mtime":"2026-10-02T08:30:00Z","struct_type":"node"}"#,
+            "\n",
+            r#"{"name":"docs","type":"dir","path":"/home/u/docs","size":0,"struct_type":"node"}"#,
+            "\n",
+        );
+        let entries = parse_file_list(stdout, "/home/u").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                FileEntry {
+                    path: "/home/u/a.conf".to_string(),
+                    node_type: "file".to_string(),
+                    size: Some(42),
+                    mtime: Some("2026-10-02T08:30:00Z".parse().unwrap()),
+                },
+                FileEntry {
+                    path: "/home/u/docs".to_string(),
+                    node_type: "dir".to_string(),
+                    size: None,
+                    mtime: None,
+                },
+            ]
+        );
+
+        let file = parse_file_list(stdout, "/home/u/a.conf").unwrap();
+        assert_eq!(file.len(), 3);
+        assert!(parse_file_list("", "/home/u/missing")
+            .unwrap_err()
+            .to_string()
+            .contains("not found"));
+        assert!(parse_file_list("", "/").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_copied_snapshots() {
+        let stdout = "\
+snapshot 410b18a2 of [/home/user/work] at 2026-10-09 23:15:57.305305 +0200 CEST by user@desk
+  copy started, this may take a while...
+snapshot 7a746a07 saved
+
+skipping snapshot 4e5d5487, was already copied to snapshot 50eb62b7
+snapshot 9f8e7d6c of [/home/user/work] at 2026-10-16 08:00:00.1 +0200 CEST by user@desk
+  copy started, this may take a while...
+snapshot 0c1d2e3f saved
+";
+        assert_eq!(parse_copied_snapshots(stdout), 2);
+        assert_eq!(parse_copied_snapshots(""), 0);
+    }
+
+    #[test]
+    fn test_retention_args() {
+        let mut set = BackupSet {
+            name: "home".to_string(),
+            retention: Some(
+                RetentionPolicy::parse("daily=7,yearly=2,within=1y6m,tag=keep").unwrap(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(
+            retention_args(&set).unwrap(),
+            [
+                "--keep-daily",
+                "7",
+                "--keep-yearly",
+                "2",
+                "--keep-within",
+                "1y6m",
+                "--keep-tag",
+                "keep"
+            ]
+        );
+        set.retention = Some(RetentionPolicy {
+            keep_tag: vec!["keep".to_string()],
+            ..Default::default()
+        });
+        assert!(retention_args(&set).is_err());
+    }
+
+    #[test]
+    fn test_retention_group_by() {
+        let mut set = BackupSet {
+            retention: Some(RetentionPolicy {
+                keep_last: Some(3),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(retention_group_by(&set), None);
+        set.shared_repo = true;
+        assert_eq!(retention_group_by(&set), Some("host,paths"));
+        set.retention.as_mut().unwrap().per_tag = true;
+        assert_eq!(retention_group_by(&set), Some("host,paths,tags"));
+        set.shared_repo = false;
+        assert_eq!(retention_group_by(&set), Some("host,paths,tags"));
+        set.logical_path = Some("/home/me".to_string());
+        assert_eq!(retention_group_by(&set), Some("host,tags"));
+    }
+
+    #[test]
+    fn test_wrap_priority() {
+        let args = vec!["backup".to_string()];
+        assert_eq!(
+            wrap_priority("restic", args.clone(), Priority::Normal, true),
+            ("restic".to_string(), args.clone())
+        );
+        assert_eq!(
+            wrap_priority("restic", args.clone(), Priority::Idle, true),
+            (
+                "ionice".to_string(),
+                ["-c", "3", "nice", "-n", "19", "restic", "backup"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            wrap_priority("restic", args, Priority::Low, false),
+            (
+                "nice".to_string(),
+                ["-n", "10", "restic", "backup"].map(String::from).to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_append_only_rejection() {
+        assert!(is_append_only_rejection(
+            "Restic error: Remove(<data/1a2b3c4d>) returned error, retrying after 552ms: unexpected HTTP response (403): 403 Forbidden"
+        ));
+        assert!(!is_append_only_rejection(
+            "Restic error: Fatal: unable to open repository: wrong password"
+        ));
+    }
+
+    #[test]
+    fn test_is_lock_error() {
+        assert!(is_lock_error(
+            "Restic error: unable to create lock in backend: repository is already locked by PID 4242 on laptop by me (UID 1000, GID 1000)\nlock was created at 2026-10-16 09:12:03 (3h2m ago)"
+        ));
+        assert!(is_lock_error(
+            "Restic error: Fatal: unable to create lock in backend: repository is already locked exclusively by PID 77 on nas by root"
+        ));
+        assert!(!is_lock_error(
+            "Restic error: Fatal: unable to open repository: wrong password"
+        ));
+    }
+
+    #[test]
+    fn test_repair_args() {
+        assert_eq!(repair_args(RepairStep::Check), ["check"]);
+        assert_eq!(
+            repair_args(RepairStep::Snapshots)[..3],
+            ["repair", "snapshots", "--forget"]
+        );
+    }
+
+    #[test]
+    fn test_limit_args() {
+        let throttle = Throttle {
+            upload_limit_kib: Some(512),
+            download_limit_kib: None,
+            priority: Some(Priority::Idle),
+        };
+        assert_eq!(limit_args(&throttle), vec!["--limit-upload", "512"]);
+        assert!(limit_args(&Throttle::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_check_progress() {
+        let progress = parse_check_progress("[0:30] 25.00%  5 / 20 packs").unwrap();
+        assert_eq!(progress.percent, 25.0);
+        assert_eq!(progress.eta_secs, Some(90));
+
+        let progress = parse_check_progress("[1:00:00] 100.00%  20 / 20 packs").unwrap();
+        assert_eq!(progress.percent, 100.0);
+        assert_eq!(progress.eta_secs, Some(0));
+
+        assert!(parse_check_progress("using temporary cache in /tmp/restic-check-cache").is_none());
+        assert!(parse_check_progress("[0:01] 50.00%  1 / 2 index files loaded").is_none());
+        assert!(parse_check_progress("no errors were found").is_none());
+    }
+
+    #[test]
+    fn test_is_valid_read_subset() {
+        for valid in ["5%", "2.5%", "100%", "1/10", "10/10", "500M", "2G", "1k"] {
+            assert!(is_valid_read_subset(valid), "{}", valid);
+        }
+        for invalid in [
+            "",
+            "0%",
+            "101%",
+            "-5%",
+            "0/10",
+            "11/10",
+            "1/",
+            "500",
+            "M",
+            "-1M",
+            "1.5G",
+            "5%x",
+            "--no-lock",
+        ] {
+            assert!(!is_valid_read_subset(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let stdout = concat!(
+            r#"{"message_type":"change","path":"/home/u/new.txt","modifier":"+"}"#,
+            "\n",
+            r#"{"message_type":"change","path":"/home/u/old.txt","modifier":"-"}"#,
+            "\n",
+            r#"{"message_type":"change","path":"/home/u/notes.md","modifier":"M"}"#,
+            "\n",
+            r#"{"message_type":"change","path":"/home/u/link","modifier":"T"}"#,
+            "\n",
+            r#"{"message_type":"statistics","source_snapshot":"a","target_snapshot":"b"}"#,
+            "\n",
+        );
+        let diff = parse_diff(stdout);
+        assert_eq!(diff.added, vec!["/home/u/new.txt"]);
+        assert_eq!(diff.removed, vec!["/home/u/old.txt"]);
+        assert_eq!(diff.modified, vec!["/home/u/notes.md", "/home/u/link"]);
+        assert_eq!(parse_diff(""), SnapshotDiff::default());
+    }
+
+    #[test]
+    fn test_parse_restore_summary() {
+        let stdout = "{\"message_type\":\"summary\",\"seconds_elapsed\":3,\"total_files\":4,\"files_restored\":4,\"total_bytes\":2048,\"bytes_restored\":2048}\n";
+        let summary = parse_restore_summary(stdout);
+        assert_eq!(summary.files_restored, 4);
+        assert_eq!(summary.bytes_restored, 2048);
+        assert_eq!(summary.duration_secs, 3.0);
+        assert_eq!(parse_restore_summary(""), RestoreSummary::default());
+    }
+
+    #[test]
+    fn test_progress_eta_needs_enough_progress() {
+        assert_eq!(Progress::new(0.0, Some(10)).eta_secs, None);
+        assert_eq!(Progress::new(0.5, None).eta_secs, None);
+        assert_eq!(Progress::new(1.0, Some(10)).eta_secs, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_streaming_filters_lines() -> Result<()> {
+        let mut seen = Vec::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (stdout, _) = run_tool_streaming(
+            "printf",
+            "printf",
+            vec!["keep\\ndrop\\nkeep2".to_string()],
+            &[],
+            &[],
+            None,
+            move |line: &str| {
+                let _ = tx.send(line.to_string());
+                line != "drop"
+            },
+        )
+        .await?;
+        while let Some(line) = rx.recv().await {
+            seen.push(line);
+        }
+        assert_eq!(seen, vec!["keep", "drop", "keep2"]);
+        assert_eq!(stdout, "keep\nkeep2");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_tool_measured_reports_usage() -> Result<()> {
+        let (stdout, _, usage) = run_tool_measured(
+            "sh",
+            "sh",
+            vec!["-c".to_string(), "echo done".to_string()],
+            &[],
+            &[],
+            None,
+            |_| true,
+        )
+        .await?;
+        assert_eq!(stdout, "done\n");
+        let usage = usage.expect("usage of a finished child");
+        assert!(usage.peak_rss_bytes > 0);
+        assert!(usage.cpu_secs >= 0.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_reach_the_tool() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let file = tmp.path().join("creds.env");
+        std::fs::write(&file, "VIGIL_TEST_SECRET=s3cret\n")?;
+
+        let env = engine_process_env(&EngineEnv::default(), Some(&file), &EngineEnv::default())?;
+        let (stdout, _) = run_tool_streaming(
+            "sh",
+            "sh",
+            vec![
+                "-c".to_string(),
+                "printf %s \"$VIGIL_TEST_SECRET\"".to_string(),
+            ],
+            &env,
+            &[],
+            None,
+            |_| true,
+        )
+        .await?;
+        assert_eq!(stdout, "s3cret");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_engine_env_passthrough() -> Result<()> {
+        std::env::set_var("RESTIC_REPOSITORY", "/leaked/repo");
+        std::env::set_var("RESTIC_CACHE_DIR", "/var/cache/restic");
+        let passthrough = ["RESTIC_CACHE_DIR".to_string()];
+        let print = |var: &str, env: &[(String, String)]| {
+            let script = format!("printf %s \"${{{}:-unset}}\"", var);
+            let mut cmd = engine_command("sh", env, &passthrough);
+            async move {
+                let output = cmd.args(["-c", &script]).output().await?;
+                Ok::<_, anyhow::Error>(String::from_utf8_lossy(&output.stdout).to_string())
+            }
+        };
+
+        let repo = print("RESTIC_REPOSITORY", &[]).await?;
+        let cache = print("RESTIC_CACHE_DIR", &[]).await?;
+        // Explicit variables (e.g. from a credentials file) are still set
+        let env = [("RESTIC_REPOSITORY".to_string(), "/explicit".to_string())];
+        let explicit = print("RESTIC_REPOSITORY", &env).await?;
+
+        std::env::remove_var("RESTIC_REPOSITORY");
+        std::env::remove_var("RESTIC_CACHE_DIR");
+        assert_eq!(repo, "unset");
+        assert_eq!(cache, "/var/cache/restic");
+        assert_eq!(explicit, "/explicit");
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+use crate::capabilities::Capability;
+use crate::executor::run_tool;
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use vigil_lib::config::{BackupSet, SnapshotSource};
+use vigil_lib::paths;
+
+/// Copy-on-write space reserved for an LVM snapshot when the set does not specify one.
+const DEFAULT_LVM_SNAPSHOT_SIZE: &str = "1G";
+
+/// Returns the system tool a snapshot mechanism depends on.
+pub fn capability(kind: SnapshotSource) -> Capability {
+    match kind {
+        SnapshotSource::Btrfs => Capability::Btrfs,
+        SnapshotSource::Lvm => Capability::Lvm,
+    }
+}
+
+/// Something created while taking a snapshot that must be released afterwards.
+#[derive(Debug, PartialEq)]
+enum Resource {
+    BtrfsSubvolume(PathBuf),
+    LvmVolume(String),
+    Mount(PathBuf),
+}
+
+/// Filesystem snapshot of a set's sources, held for the duration of one backup.
+///
+/// Snapshot paths are stable across runs so restic keeps finding the previous
+/// snapshot as the parent for change detection.
+#[derive(Debug)]
+pub struct FsSnapshot {
+    set_name: String,
+    /// (original source, same directory inside the snapshot)
+    sources: Vec<(String, PathBuf)>,
+    /// Created resources, in creation order.
+    resources: Vec<Resource>,
+}
+
+impl FsSnapshot {
+    /// Snapshots every source of `set` using its configured mechanism. On failure,
+    /// anything already created is released before the error is returned.
+    pub async fn create(set: &BackupSet, kind: SnapshotSource) -> Result<Self> {
+        let mut snapshot = FsSnapshot {
+            set_name: set.name.clone(),
+            sources: Vec::new(),
+            resources: Vec::new(),
+        };
+        let res = match kind {
+            SnapshotSource::Btrfs => snapshot.create_btrfs(set).await,
+            SnapshotSource::Lvm => snapshot.create_lvm(set).await,
+        };
+        match res {
+            Ok(()) => {
+                info!(
+                    "Created {} snapshot of {} source(s) for set {}",
+                    kind,
+                    snapshot.sources.len(),
+                    set.name
+                );
+                Ok(snapshot)
+            }
+            Err(e) => {
+                if let Err(cleanup) = snapshot.release().await {
+                    warn!("Cleanup after failed snapshot also failed: {}", cleanup);
+                }
+                Err(e.context(format!(
+                    "Failed to create {} snapshot for set '{}'",
+                    kind, set.name
+                )))
+            }
+        }
+    }
+
+    async fn create_btrfs(&mut self, set: &BackupSet) -> Result<()> {
+        for source in source_list(set) {
+            let dest = btrfs_snapshot_path(Path::new(&source));
+            if dest.exists() {
+                debug!("Removing stale btrfs snapshot {:?}", dest);
+                btrfs_delete(&dest).await?;
+            }
+            run_tool(
+                "btrfs",
+                "btrfs",
+                vec![
+                    "subvolume".to_string(),
+                    "snapshot".to_string(),
+                    "-r".to_string(),
+                    source.clone(),
+                    dest.to_string_lossy().to_string(),
+                ],
+                None,
+            )
+            .await?;
+            self.resources.push(Resource::BtrfsSubvolume(dest.clone()));
+            self.sources.push((source, dest));
+        }
+        Ok(())
+    }
+
+    async fn create_lvm(&mut self, set: &BackupSet) -> Result<()> {
+        let size = set
+            .lvm_snapshot_size
+            .as_deref()
+            .unwrap_or(DEFAULT_LVM_SNAPSHOT_SIZE);
+        // Sources on the same logical volume share one snapshot: (device, snapshot mount dir)
+        let mut volumes: Vec<(String, PathBuf)> = Vec::new();
+
+        for source in source_list(set) {
+            let (out, _) = run_tool(
+                "findmnt",
+                "findmnt",
+                vec![
+                    "-n".to_string(),
+                    "-P".to_string(),
+                    "-o".to_string(),
+                    "SOURCE,TARGET,FSTYPE".to_string(),
+                    "--target".to_string(),
+                    source.clone(),
+                ],
+                None,
+            )
+            .await?;
+            let fs = parse_findmnt(&out)
+                .with_context(|| format!("Could not determine the filesystem of {}", source))?;
+            let relative = Path::new(&source)
+                .strip_prefix(&fs.target)
+                .with_context(|| format!("{} is not under its mount point", source))?
+                .to_path_buf();
+
+            let existing = volumes.iter().find(|(device, _)| *device == fs.source);
+            let snapshot_root = match existing {
+                Some((_, root)) => root.clone(),
+                None => {
+                    let root = self.snapshot_volume(set, &fs, size, volumes.len()).await?;
+                    volumes.push((fs.source.clone(), root.clone()));
+                    root
+                }
+            };
+            self.sources
+                .push((source, join_relative(&snapshot_root, &relative)));
+        }
+        Ok(())
+    }
+
+    /// Creates and mounts a read-only LVM snapshot of the volume behind `fs`.
+    async fn snapshot_volume(
+        &mut self,
+        set: &BackupSet,
+        fs: &MountInfo,
+        size: &str,
+        index: usize,
+    ) -> Result<PathBuf> {
+        let (out, _) = run_tool(
+            "lvs",
+            "lvs",
+            vec![
+                "--noheadings".to_string(),
+                "-o".to_string(),
+                "vg_name,lv_name".to_string(),
+                fs.source.clone(),
+            ],
+            None,
+        )
+        .await?;
+        let (vg, lv) = parse_lvs(&out)
+            .with_context(|| format!("{} is not an LVM logical volume", fs.source))?;
+
+        let name = format!("vigil-{}-{}", set.name, index);
+        let volume = format!("{}/{}", vg, name);
+        run_tool(
+            "lvcreate",
+            "lvcreate",
+            vec![
+                "--snapshot".to_string(),
+                "--size".to_string(),
+                size.to_string(),
+                "--name".to_string(),
+                name,
+                format!("{}/{}", vg, lv),
+            ],
+            None,
+        )
+        .await?;
+        self.resources.push(Resource::LvmVolume(volume.clone()));
+
+        let mount_dir = paths::fs_snapshot_dir(&set.name).join(index.to_string());
+        std::fs::create_dir_all(&mount_dir)?;
+        let mut options = "ro".to_string();
+        if fs.fstype == "xfs" {
+            // The snapshot shares the origin's UUID, which XFS refuses to mount twice.
+            options.push_str(",nouuid");
+        }
+        run_tool(
+            "mount",
+            "mount",
+            vec![
+                "-o".to_string(),
+                options,
+                format!("/dev/{}", volume),
+                mount_dir.to_string_lossy().to_string(),
+            ],
+            None,
+        )
+        .await?;
+        self.resources.push(Resource::Mount(mount_dir.clone()));
+        Ok(mount_dir)
+    }
+
+    /// Returns a copy of `set` whose sources and absolute excludes point into the snapshot.
+    pub fn apply(&self, set: &BackupSet) -> BackupSet {
+        let remap = |path: &str| -> String {
+            for (original, snapshot) in &self.sources {
+                if let Ok(rest) = Path::new(path).strip_prefix(original) {
+                    return join_relative(snapshot, rest).to_string_lossy().to_string();
+                }
+            }
+            path.to_string()
+        };
+        let mut effective = set.clone();
+        effective.source = set.source.as_deref().map(remap);
+        effective.sources = set
+            .sources
+            .as_ref()
+            .map(|ss| ss.iter().map(|s| remap(s)).collect());
+        effective.exclude = set.exclude.as_ref().map(|ex| {
+            ex.iter()
+                .map(|e| {
+                    if e.starts_with('/') {
+                        remap(e)
+                    } else {
+                        e.clone()
+                    }
+                })
+                .collect()
+        });
+        effective
+    }
+
+    /// Unmounts and deletes everything the snapshot created. Keeps going after a
+    /// failure and returns the first error.
+    pub async fn release(mut self) -> Result<()> {
+        let mut first_error = None;
+        while let Some(resource) = self.resources.pop() {
+            let res = match &resource {
+                Resource::BtrfsSubvolume(path) => btrfs_delete(path).await,
+                Resource::Mount(dir) => run_tool(
+                    "umount",
+                    "umount",
+                    vec![dir.to_string_lossy().to_string()],
+                    None,
+                )
+                .await
+                .map(|_| ()),
+                Resource::LvmVolume(volume) => run_tool(
+                    "lvremove",
+                    "lvremove",
+                    vec!["-f".to_string(), volume.clone()],
+                    None,
+                )
+                .await
+                .map(|_| ()),
+            };
+            if let Err(e) = res {
+                warn!(
+                    "Failed to release {:?} for set {}: {}",
+                    resource, self.set_name, e
+                );
+                first_error.get_or_insert(e);
+            }
+        }
+        match first_error {
+            Some(e) => Err(anyhow!(
+                "Failed to clean up filesystem snapshot for set '{}': {}",
+                self.set_name,
+                e
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn btrfs_delete(path: &Path) -> Result<()> {
+    run_tool(
+        "btrfs",
+        "btrfs",
+        vec![
+            "subvolume".to_string(),
+            "delete".to_string(),
+            path.to_string_lossy().to_string(),
+        ],
+        None,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Joins without the trailing slash `Path::join` adds for an empty `rest`.
+fn join_relative(base: &Path, rest: &Path) -> PathBuf {
+    if rest.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(rest)
+    }
+}
+
+fn source_list(set: &BackupSet) -> Vec<String> {
+    set.source
+        .iter()
+        .chain(set.sources.iter().flatten())
+        .cloned()
+        .collect()
+}
+
+/// Read-only btrfs snapshots live next to their subvolume (same filesystem,
+/// outside the watched tree): `/data/photos` → `/data/.photos.vigil-snapshot`.
+fn btrfs_snapshot_path(source: &Path) -> PathBuf {
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    source
+        .parent()
+        .unwrap_or(source)
+        .join(format!(".{}.vigil-snapshot", name))
+}
+
+/// Filesystem holding a source directory, as reported by `findmnt`.
+#[derive(Debug, PartialEq)]
+struct MountInfo {
+    source: String,
+    target: PathBuf,
+    fstype: String,
+}
+
+/// Parses `findmnt -n -P -o SOURCE,TARGET,FSTYPE` output (`KEY="value"` pairs).
+fn parse_findmnt(output: &str) -> Option<MountInfo> {
+    let line = output.lines().next()?;
+    let field = |key: &str| -> Option<String> {
+        let start = line.find(&format!("{}=\"", key))? + key.len() + 2;
+        let end = line[start..].find('"')? + start;
+        Some(line[start..end].to_string())
+    };
+    Some(MountInfo {
+        source: field("SOURCE")?,
+        target: PathBuf::from(field("TARGET")?),
+        fstype: field("FSTYPE")?,
+    })
+}
+
+/// Parses `lvs --noheadings -o vg_name,lv_name` output into (volume group, logical volume).
+fn parse_lvs(output: &str) -> Option<(String, String)> {
+    let mut fields = output.split_whitespace();
+    Some((fields.next()?.to_string(), fields.next()?.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_btrfs_snapshot_path() {
+        assert_eq!(
+            btrfs_snapshot_path(Path::new("/data/photos")),
+            PathBuf::from("/data/.photos.vigil-snapshot")
+        );
+    }
+
+    #[test]
+    fn test_parse_findmnt() {
+        let out = "SOURCE=\"/dev/mapper/vg0-home\" TARGET=\"/home\" FSTYPE=\"ext4\"\n";
+        assert_eq!(
+            parse_findmnt(out),
+            Some(MountInfo {
+                source: "/dev/mapper/vg0-home".to_string(),
+                target: PathBuf::from("/home"),
+                fstype: "ext4".to_string(),
+            })
+        );
+        assert_eq!(parse_findmnt(""), None);
+    }
+
+    #[test]
+    fn test_parse_lvs() {
+        assert_eq!(
+            parse_lvs("  vg0   home\n"),
+            Some(("vg0".to_string(), "home".to_string()))
+        );
+        assert_eq!(parse_lvs("   \n"), None);
+    }
+
+    #[test]
+    fn test_apply_remaps_sources_and_excludes() {
+        let set = BackupSet {
+            name: "docs".to_string(),
+            sources: Some(vec!["/home/u/docs".to_string(), "/srv/other".to_string()]),
+            target: "/repo".to_string(),
+            exclude: Some(vec!["*.tmp".to_string(), "/home/u/docs/cache".to_string()]),
+            ..Default::default()
+        };
+        let snapshot = FsSnapshot {
+            set_name: "docs".to_string(),
+            sources: vec![("/home/u/docs".to_string(), PathBuf::from("/snap/0/u/docs"))],
+            resources: Vec::new(),
+        };
+
+        let effective = snapshot.apply(&set);
+        assert_eq!(
+            effective.sources.unwrap(),
+            vec!["/snap/0/u/docs".to_string(), "/srv/other".to_string()]
+        );
+        assert_eq!(
+            effective.exclude.unwrap(),
+            vec!["*.tmp".to_string(), "/snap/0/u/docs/cache".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_release_without_resources() {
+        let snapshot = FsSnapshot {
+            set_name: "docs".to_string(),
+            sources: Vec::new(),
+            resources: Vec::new(),
+        };
+        assert!(snapshot.release().await.is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::manager::JobManager;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use vigil_lib::ipc::{Request, Response, ResponseData};
+
+/// How long the replacing daemon waits for the old one to hand over its socket.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often the replacing daemon checks whether the old one has exited.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `sendmsg` flags. Elsewhere SIGPIPE stays ignored, as the Rust runtime sets it up.
+#[cfg(target_os = "linux")]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(target_os = "linux"))]
+const SEND_FLAGS: libc::c_int = 0;
+
+/// Sends `payload` over `stream` with `fd` attached as `SCM_RIGHTS`
+/// ancillary data. Returns the number of payload bytes sent.
+pub fn send_with_fd(stream: RawFd, payload: &[u8], fd: RawFd) -> std::io::Result<usize> {
+    // SAFETY: the control buffer is sized with CMSG_SPACE for exactly one
+    // descriptor, and every pointer handed to sendmsg outlives the call.
+    unsafe {
+        let space = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) as usize;
+        let mut control = vec![0u8; space];
+        let mut iov = libc::iovec {
+            iov_base: payload.as_ptr() as *mut libc::c_void,
+            iov_len: payload.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        let sent = libc::sendmsg(stream, &msg, SEND_FLAGS);
+        if sent < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(sent as usize)
+    }
+}
+
+/// Reads from `stream` into `buf`, returning the byte count and the first
+/// descriptor passed along with the data, if any. Received descriptors are
+/// close-on-exec.
+pub fn recv_with_fd(stream: RawFd, buf: &mut [u8]) -> std::io::Result<(usize, Option<RawFd>)> {
+    // SAFETY: as in `send_with_fd`; the kernel writes at most `space` bytes of
+    // control data, and `CMSG_NXTHDR` stops at the end of what it wrote.
+    unsafe {
+        let space = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) as usize;
+        let mut control = vec![0u8; space];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        #[cfg(target_os = "linux")]
+        let read = libc::recvmsg(stream, &mut msg, libc::MSG_CMSG_CLOEXEC);
+        #[cfg(not(target_os = "linux"))]
+        let read = libc::recvmsg(stream, &mut msg, 0);
+        if read < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut fd = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let received = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd);
+                match fd {
+                    None => {
+                        // Without MSG_CMSG_CLOEXEC the flag is set afterwards
+                        #[cfg(not(target_os = "linux"))]
+                        libc::fcntl(received, libc::F_SETFD, libc::FD_CLOEXEC);
+                        fd = Some(received);
+                    }
+                    // Only one descriptor is expected; never leak extras
+                    Some(_) => {
+                        libc::close(received);
+                    }
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        Ok((read as usize, fd))
+    }
+}
+
+/// Asks the daemon listening on `socket_path` to hand over its socket.
+/// Returns the listening socket, ready for `tokio::net::UnixListener::from_std`,
+/// the old daemon's PID, and the sets whose backups keep running in it.
+pub fn take_over(socket_path: &Path) -> Result<(UnixListener, u32, Vec<String>)> {
+    let mut stream = UnixStream::connect(socket_path)
+        .with_context(|| format!("No daemon to replace at {:?}", socket_path))?;
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+    let request = serde_json::to_string(&Request::Handoff)? + "\n";
+    stream.write_all(request.as_bytes())?;
+
+    // Events broadcast by the old daemon may arrive before the reply
+    let mut pending = Vec::new();
+    let mut passed_fd = None;
+    let mut buf = [0u8; 4096];
+    loop {
+        let (read, fd) = recv_with_fd(stream.as_raw_fd(), &mut buf)
+            .context("Failed to receive the socket from the old daemon")?;
+        if let Some(fd) = fd {
+            // SAFETY: the descriptor was just received and is owned by nobody else
+            passed_fd = Some(unsafe { UnixListener::from_raw_fd(fd) });
+        }
+        if read == 0 {
+            bail!("The old daemon closed the connection without handing over its socket");
+        }
+        pending.extend_from_slice(&buf[..read]);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            match serde_json::from_slice::<Response>(&line) {
+                Ok(Response::Ok(Some(ResponseData::HandoffReady { pid, running }))) => {
+                    let listener = passed_fd
+                        .take()
+                        .context("The old daemon replied without attaching its socket")?;
+                    listener.set_nonblocking(true)?;
+                    info!("Took over the listening socket from daemon PID {}", pid);
+                    return Ok((listener, pid, running));
+                }
+                Ok(Response::Error { code, message }) => {
+                    bail!("The old daemon refused the handoff ({}): {}", code, message)
+                }
+                Ok(other) => debug!("Ignoring message during handoff: {:?}", other),
+                Err(e) => debug!("Ignoring unreadable line during handoff: {}", e),
+            }
+        }
+    }
+}
+
+/// Releases the sets held for backups still running in the replaced daemon
+/// once its process `pid` has exited, unless `token` is cancelled first.
+pub async fn release_when_exited(manager: Arc<JobManager>, pid: u32, token: CancellationToken) {
+    while vigil_lib::platform::process_exists(pid) {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(EXIT_POLL_INTERVAL) => {}
+        }
+    }
+    manager.release_handed_over().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_listener_survives_fd_passing() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("vigil.sock");
+        let listener = UnixListener::bind(&path)?;
+        let (old, new) = UnixStream::pair()?;
+
+        let payload = b"{\"type\":\"Ok\"}\n";
+        let sent = send_with_fd(old.as_raw_fd(), payload, listener.as_raw_fd())?;
+        assert_eq!(sent, payload.len());
+        drop(listener);
+
+        let mut buf = [0u8; 64];
+        let (read, fd) = recv_with_fd(new.as_raw_fd(), &mut buf)?;
+        assert_eq!(&buf[..read], payload);
+        let received = unsafe { UnixListener::from_raw_fd(fd.expect("descriptor passed")) };
+        assert_eq!(received.local_addr()?.as_pathname(), Some(path.as_path()));
+
+        // Still accepting on the original path after the sender's copy is closed
+        let _client = UnixStream::connect(&path)?;
+        received.accept()?;
+
+        // Plain data carries no descriptor
+        (&old).write_all(b"x")?;
+        let (read, fd) = recv_with_fd(new.as_raw_fd(), &mut buf)?;
+        assert_eq!((read, fd), (1, None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_over_skips_events_before_reply() -> Result<()> {
+        use std::io::{BufRead, BufReader};
+
+        let tmp = tempdir()?;
+        let path = tmp.path().join("vigil.sock");
+        let listener = UnixListener::bind(&path)?;
+        let old_daemon = std::thread::spawn(move || -> Result<()> {
+            let (stream, _) = listener.accept()?;
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request)?;
+            assert_eq!(serde_json::from_str::<Request>(&request)?, Request::Handoff);
+
+            let event = Response::Event(ResponseData::StatusInitialized {
+                sets: 1,
+                duration_secs: 0.5,
+            });
+            (&stream).write_all((serde_json::to_string(&event)? + "\n").as_bytes())?;
+            let reply = Response::Ok(Some(ResponseData::HandoffReady {
+                pid: 4242,
+                running: vec!["home".to_string()],
+            }));
+            let reply = serde_json::to_string(&reply)? + "\n";
+            send_with_fd(stream.as_raw_fd(), reply.as_bytes(), listener.as_raw_fd())?;
+            Ok(())
+        });
+
+        let (taken, pid, running) = take_over(&path)?;
+        old_daemon.join().unwrap()?;
+        assert_eq!(pid, 4242);
+        assert_eq!(running, vec!["home".to_string()]);
+        assert_eq!(taken.local_addr()?.as_pathname(), Some(path.as_path()));
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use vigil_lib::types::BackupRun;
+
+/// Size at which a history file is rotated. One older file is kept, so a set's
+/// history holds a few thousand records of each kind at most.
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// The rotated predecessor of `path`, e.g. `home.jsonl.1`.
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Appends `record` (a [`BackupRun`] or a restore) as one JSON line, rotating
+/// the file first once it has grown past `MAX_FILE_BYTES`.
+pub fn append<T: Serialize>(path: &Path, record: &T) -> Result<()> {
+    append_with_limit(path, record, MAX_FILE_BYTES)
+}
+
+fn append_with_limit<T: Serialize>(path: &Path, record: &T, max_bytes: u64) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::metadata(path).is_ok_and(|m| m.len() >= max_bytes) {
+        debug!("Rotating backup history {:?}", path);
+        std::fs::rename(path, rotated(path))
+            .with_context(|| format!("Failed to rotate {:?}", path))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let line = serde_json::to_string(record)? + "\n";
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+/// Reads the records, oldest first. With `limit`, only the newest `limit`
+/// records are returned. Unreadable lines (e.g. a write cut short by a
+/// crash) are skipped.
+pub fn read<T: DeserializeOwned>(path: &Path, limit: Option<usize>) -> Vec<T> {
+    let mut runs: Vec<T> = [rotated(path), path.to_path_buf()]
+        .iter()
+        .filter_map(|file| std::fs::read_to_string(file).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if let Some(limit) = limit {
+        runs.drain(..runs.len().saturating_sub(limit));
+    }
+    runs
+}
+
+/// How far back `growth_per_day` looks.
+const GROWTH_WINDOW_DAYS: i64 = 7;
+
+/// Average bytes added to the repository per day by the successful runs of
+/// the last week. A history shorter than that is averaged over the time it
+/// covers, but at least one day. `None` without runs in the window.
+pub fn growth_per_day(runs: &[BackupRun], now: DateTime<Utc>) -> Option<u64> {
+    let since = now - Duration::days(GROWTH_WINDOW_DAYS);
+    let recent: Vec<&BackupRun> = runs.iter().filter(|r| r.started_at >= since).collect();
+    if recent.is_empty() {
+        return None;
+    }
+    let oldest = runs.iter().map(|r| r.started_at).min()?;
+    let covered = (now - oldest.max(since)).max(Duration::days(1));
+    let added: u64 = recent
+        .iter()
+        .filter(|r| r.success)
+        .map(|r| r.added_bytes)
+        .sum();
+    Some((added as f64 * 86_400.0 / covered.num_seconds() as f64).round() as u64)
+}
+
+/// Deletes a set's history, including the rotated file.
+pub fn remove(path: &Path) {
+    let _ = std::fs::remove_file(rotated(path));
+    let _ = std::fs::remove_file(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn run(n: i64, success: bool) -> BackupRun {
+        let started_at = Utc::now() + Duration::minutes(n);
+        BackupRun {
+            set_name: "home".to_string(),
+            started_at,
+            finished_at: started_at + Duration::seconds(5),
+            success,
+            snapshot_id: success.then(|| format!("snap{}", n)),
+            added_bytes: n as u64,
+            error: (!success).then(|| "repository locked".to_string()),
+            skipped_trivial: false,
+        }
+    }
+
+    #[test]
+    fn test_history_appends_and_limits() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("history/home.jsonl");
+        assert!(read::<BackupRun>(&path, None).is_empty());
+
+        append(&path, &run(0, true))?;
+        append(&path, &run(1, false))?;
+        append(&path, &run(2, true))?;
+        let runs: Vec<BackupRun> = read(&path, None);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[1].error.as_deref(), Some("repository locked"));
+        assert_eq!(runs[1].duration_secs(), 5.0);
+
+        assert_eq!(read::<BackupRun>(&path, Some(2)), runs[1..].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_rotation_keeps_one_older_file() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("home.jsonl");
+        // A tiny limit rotates before every append after the first
+        for n in 0..4 {
+            append_with_limit(&path, &run(n, true), 1)?;
+        }
+        let runs: Vec<BackupRun> = read(&path, None);
+        let added: Vec<u64> = runs.iter().map(|r| r.added_bytes).collect();
+        assert_eq!(added, vec![2, 3]);
+
+        std::fs::write(&path, "{ cut short\n")?;
+        assert_eq!(read::<BackupRun>(&path, None).len(), 1);
+
+        remove(&path);
+        assert!(read::<BackupRun>(&path, None).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_growth_per_day() {
+        let now = Utc::now();
+        let at = |days_ago: i64, added_bytes: u64, success: bool| BackupRun {
+            started_at: now - Duration::days(days_ago),
+            added_bytes,
+            ..run(0, success)
+        };
+        assert_eq!(growth_per_day(&[], now), None);
+        assert_eq!(growth_per_day(&[at(10, 1000, true)], now), None);
+
+        // A full week: older runs and failures do not count
+        let runs = [
+            at(30, 1 << 30, true),
+            at(6, 7000, true),
+            at(3, 7000, true),
+            at(1, 5000, false),
+        ];
+        assert_eq!(growth_per_day(&runs, now), Some(2000));
+
+        // Two days of history are averaged over two days, a few hours over one
+        assert_eq!(growth_per_day(&[at(2, 4000, true)], now), Some(2000));
+        assert_eq!(growth_per_day(&[at(0, 4000, true)], now), Some(4000));
+    }
+}
@@ -0,0 +1,197 @@
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use vigil_lib::config::BackupSet;
+use vigil_lib::types::{HookOutcome, HookStage};
+
+/// How long a hook may run when the set has no `hook_timeout_secs`.
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 300;
+
+/// Hook output kept in `BackupResult`. Longer output keeps its end, where errors usually are.
+const HOOK_OUTPUT_LIMIT: usize = 8 * 1024;
+
+/// Runs a set's hook command through `sh -c`, with `VIGIL_SET` and `env` in
+/// its environment. The command and anything it started are killed when it
+/// exceeds the set's hook timeout or `token` is cancelled. Each output line is
+/// logged.
+pub async fn run_hook(
+    stage: HookStage,
+    command: &str,
+    set: &BackupSet,
+    env: &[(String, String)],
+    token: &CancellationToken,
+) -> HookOutcome {
+    let timeout = Duration::from_secs(set.hook_timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS));
+    let started = Instant::now();
+    info!("Running {} hook for set {}: {}", stage, set.name, command);
+
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("VIGIL_SET", &set.name)
+        .envs(env.iter().cloned())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Its own process group, so a timeout also stops what the shell started
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to start {} hook for set {}: {}", stage, set.name, e);
+            return HookOutcome {
+                stage,
+                success: false,
+                exit_code: None,
+                output: format!("failed to start: {}", e),
+                duration_secs: started.elapsed().as_secs_f64(),
+            };
+        }
+    };
+    let pid = child.id();
+
+    let (exit_code, output) = tokio::select! {
+        result = child.wait_with_output() => match result {
+            Ok(out) => {
+                let mut text = String::from_utf8_lossy(&out.stdout).to_string();
+                text.push_str(&String::from_utf8_lossy(&out.stderr));
+                (out.status.code(), text)
+            }
+            Err(e) => (None, format!("failed to wait for hook: {}", e)),
+        },
+        _ = tokio::time::sleep(timeout) => {
+            kill_group(pid);
+            (None, format!("timed out after {}s", timeout.as_secs()))
+        }
+        _ = token.cancelled() => {
+            kill_group(pid);
+            (None, "cancelled".to_string())
+        }
+    };
+
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        info!("{} hook for set {}: {}", stage, set.name, line);
+    }
+    let success = exit_code == Some(0);
+    if !success {
+        warn!(
+            "{} hook for set {} failed ({})",
+            stage,
+            set.name,
+            exit_code.map_or_else(|| "no exit status".to_string(), |c| format!("exit {}", c))
+        );
+    }
+
+    HookOutcome {
+        stage,
+        success,
+        exit_code,
+        output: tail(&output, HOOK_OUTPUT_LIMIT),
+        duration_secs: started.elapsed().as_secs_f64(),
+    }
+}
+
+/// One-line reason for a failed hook, for error messages and notifications.
+pub fn failure_reason(outcome: &HookOutcome) -> String {
+    let status = match outcome.exit_code {
+        Some(code) => format!("exit {}", code),
+        None => "no exit status".to_string(),
+    };
+    match outcome.output.lines().rev().find(|l| !l.trim().is_empty()) {
+        Some(last) => format!(
+            "{} command failed ({}): {}",
+            outcome.stage,
+            status,
+            last.trim()
+        ),
+        None => format!("{} command failed ({})", outcome.stage, status),
+    }
+}
+
+#[cfg(unix)]
+fn kill_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: plain syscall; the group was created for this hook by `process_group(0)`
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+/// Windows has no process groups to signal; `kill_on_drop` stops the shell.
+#[cfg(not(unix))]
+fn kill_group(_pid: Option<u32>) {}
+
+/// The last `limit` bytes of `text`, cut at a character boundary.
+fn tail(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let mut start = text.len() - limit;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &text[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(timeout: Option<u64>) -> BackupSet {
+        BackupSet {
+            name: "db".to_string(),
+            hook_timeout_secs: timeout,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hook_captures_output_and_env() {
+        let env = [("VIGIL_SNAPSHOT_ID".to_string(), "a1b2c3d4".to_string())];
+        let outcome = run_hook(
+            HookStage::Post,
+            "echo \"$VIGIL_SET $VIGIL_SNAPSHOT_ID\"; echo oops >&2; exit 3",
+            &set(None),
+            &env,
+            &CancellationToken::new(),
+        )
+        .await;
+        assert!(!outcome.success);
+        assert_eq!(outcome.exit_code, Some(3));
+        assert_eq!(outcome.output, "db a1b2c3d4\noops\n");
+        assert_eq!(
+            failure_reason(&outcome),
+            "post-backup command failed (exit 3): oops"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_timeout_kills_command() {
+        let started = Instant::now();
+        let outcome = run_hook(
+            HookStage::Pre,
+            "sleep 30",
+            &set(Some(1)),
+            &[],
+            &CancellationToken::new(),
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!outcome.success);
+        assert_eq!(outcome.exit_code, None);
+        assert!(outcome.output.contains("timed out"));
+    }
+
+    #[test]
+    fn test_tail_keeps_end() {
+        assert_eq!(tail("short", 10), "short");
+        assert_eq!(tail("0123456789", 4), "…6789");
+        assert_eq!(tail("ééé", 3), "…é");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::manager::JobManager;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// How often the janitor removes stale mount directories.
+const JANITOR_INTERVAL_SECS: u64 = 3600;
+
+/// Result of a mount directory cleanup pass.
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanReport {
+    /// Directories removed (or, in a dry run, that would be removed).
+    pub removed: Vec<PathBuf>,
+    /// Stale directories left in place, with the reason.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Removes directories under `base` that belong to no configured set, are not
+/// mount points, and are empty. Non-empty or mounted leftovers are reported as
+/// skipped and never touched. With `dry_run`, nothing is removed.
+pub fn clean_mount_dirs(base: &Path, set_names: &HashSet<String>, dry_run: bool) -> CleanReport {
+    let mut report = CleanReport::default();
+    let entries = match std::fs::read_dir(base) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Skipping mount directory cleanup for {:?}: {}", base, e);
+            return report;
+        }
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .map(|n| !set_names.contains(n.to_string_lossy().as_ref()))
+                .unwrap_or(false)
+        })
+        .collect();
+    dirs.sort();
+
+    for dir in dirs {
+        if vigil_lib::paths::is_mount_point(&dir) {
+            report.skipped.push((dir, "still mounted".to_string()));
+            continue;
+        }
+        match std::fs::read_dir(&dir).map(|mut d| d.next().is_none()) {
+            Ok(true) => {}
+            Ok(false) => {
+                report.skipped.push((dir, "not empty".to_string()));
+                continue;
+            }
+            Err(e) => {
+                report.skipped.push((dir, e.to_string()));
+                continue;
+            }
+        }
+        if !dry_run {
+            if let Err(e) = std::fs::remove_dir(&dir) {
+                report.skipped.push((dir, e.to_string()));
+                continue;
+            }
+        }
+        report.removed.push(dir);
+    }
+
+    report
+}
+
+/// Background task that keeps the mount base directory tidy.
+///
+/// The first pass at startup is a dry run that only logs what would be removed,
+/// so a misconfiguration shows up in the log before anything is deleted.
+/// Subsequent passes run every hour.
+pub async fn run(manager: Arc<JobManager>, shutdown: CancellationToken) {
+    let mut dry_run = true;
+    loop {
+        let report = manager.clean_mount_dirs(dry_run).await;
+        if dry_run {
+            for path in &report.removed {
+                info!(
+                    "Janitor (dry run): would remove stale mount directory {:?}",
+                    path
+                );
+            }
+        }
+        for (path, reason) in &report.skipped {
+            warn!(
+                "Janitor: leaving stale mount directory {:?} ({})",
+                path, reason
+            );
+        }
+        dry_run = false;
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(JANITOR_INTERVAL_SECS)) => {}
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_mount_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path();
+        std::fs::create_dir(base.join("current")).unwrap();
+        std::fs::create_dir(base.join("old-empty")).unwrap();
+        std::fs::create_dir(base.join("old-full")).unwrap();
+        std::fs::write(base.join("old-full/file"), "data").unwrap();
+        std::fs::write(base.join("stray-file"), "data").unwrap();
+
+        let sets: HashSet<String> = ["current".to_string()].into_iter().collect();
+
+        let report = clean_mount_dirs(base, &sets, true);
+        assert_eq!(report.removed, vec![base.join("old-empty")]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, base.join("old-full"));
+        assert!(base.join("old-empty").exists());
+
+        let report = clean_mount_dirs(base, &sets, false);
+        assert_eq!(report.removed, vec![base.join("old-empty")]);
+        assert!(!base.join("old-empty").exists());
+        assert!(base.join("current").exists());
+        assert!(base.join("old-full").exists());
+        assert!(base.join("stray-file").exists());
+    }
+
+    #[test]
+    fn test_clean_missing_base_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = clean_mount_dirs(&tmp.path().join("missing"), &HashSet::new(), false);
+        assert_eq!(report, CleanReport::default());
+    }
+}
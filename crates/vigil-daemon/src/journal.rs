@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Number of changed paths stored verbatim before switching to hashes.
+const MAX_PATHS: usize = 1_000;
+/// Number of path hashes stored before only the overflow count grows.
+const MAX_HASHES: usize = 100_000;
+/// Minimum time between writes while changes keep arriving.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Paths changed since the last successful backup of a set, persisted so
+/// pending changes survive daemon restarts.
+///
+/// The first `MAX_PATHS` paths are kept verbatim for display. Further paths are
+/// kept as 64-bit FNV-1a hashes so repeated changes to the same file are still
+/// counted once; past `MAX_HASHES` only a counter grows.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ChangeJournal {
+    #[serde(skip)]
+    file: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    last_save: Option<Instant>,
+    paths: BTreeSet<String>,
+    hashes: BTreeSet<u64>,
+    overflow: u64,
+}
+
+impl ChangeJournal {
+    /// Loads the journal from disk. A missing or unreadable file yields an empty journal.
+    pub fn load(file: &Path) -> Self {
+        let mut journal: Self = std::fs::read_to_string(file)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        journal.file = file.to_path_buf();
+        journal
+    }
+
+    /// Binds the journal to `file`, so `save` writes there from now on.
+    pub fn bind(&mut self, file: &Path) {
+        self.file = file.to_path_buf();
+    }
+
+    /// Records a changed path.
+    pub fn record(&mut self, path: &Path) {
+        let path = path.to_string_lossy();
+        if self.paths.contains(path.as_ref()) {
+            return;
+        }
+        let inserted = if self.paths.len() < MAX_PATHS {
+            self.paths.insert(path.into_owned())
+        } else if self.hashes.len() < MAX_HASHES {
+            self.hashes.insert(fnv1a(path.as_bytes()))
+        } else {
+            self.overflow += 1;
+            true
+        };
+        self.dirty |= inserted;
+    }
+
+    /// Counts `count` changes whose paths were not passed on, like paths past
+    /// `MAX_HASHES`.
+    pub fn record_unlisted(&mut self, count: u64) {
+        if count > 0 {
+            self.overflow += count;
+            self.dirty = true;
+        }
+    }
+
+    /// Number of distinct changed paths (an upper bound once hashes overflow).
+    pub fn len(&self) -> u64 {
+        (self.paths.len() + self.hashes.len()) as u64 + self.overflow
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns up to `limit` changed paths that are stored verbatim.
+    pub fn sample(&self, limit: usize) -> Vec<String> {
+        self.paths.iter().take(limit).cloned().collect()
+    }
+
+    /// Current size of the changed files, counting deleted paths and
+    /// directories as 0. `None` once paths are only kept as hashes, since
+    /// those cannot be looked up.
+    pub fn changed_bytes(&self) -> Option<u64> {
+        if !self.hashes.is_empty() || self.overflow > 0 {
+            return None;
+        }
+        Some(
+            self.paths
+                .iter()
+                .filter_map(|path| std::fs::symlink_metadata(path).ok())
+                .filter(|meta| meta.is_file())
+                .map(|meta| meta.len())
+                .sum(),
+        )
+    }
+
+    /// Moves all entries out, leaving this journal empty but bound to the same file.
+    pub fn take(&mut self) -> ChangeJournal {
+        let entries = ChangeJournal {
+            paths: std::mem::take(&mut self.paths),
+            hashes: std::mem::take(&mut self.hashes),
+            overflow: std::mem::take(&mut self.overflow),
+            ..Default::default()
+        };
+        self.dirty = true;
+        entries
+    }
+
+    /// Adds all entries of `other` to this journal.
+    pub fn merge(&mut self, other: &ChangeJournal) {
+        for path in &other.paths {
+            self.record(Path::new(path));
+        }
+        for hash in &other.hashes {
+            if self.hashes.len() < MAX_HASHES {
+                self.hashes.insert(*hash);
+            } else {
+                self.overflow += 1;
+            }
+        }
+        self.overflow += other.overflow;
+        self.dirty = true;
+    }
+
+    /// Writes the journal merged with `in_flight` (entries a running backup is
+    /// still capturing). An empty journal removes the file.
+    pub fn save(&mut self, in_flight: Option<&ChangeJournal>) -> Result<()> {
+        let mut merged = self.clone();
+        if let Some(in_flight) = in_flight {
+            merged.merge(in_flight);
+        }
+        if merged.is_empty() {
+            match std::fs::remove_file(&self.file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).context("Failed to remove change journal");
+                }
+                _ => {}
+            }
+        } else {
+            if let Some(parent) = self.file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let tmp = self.file.with_extension("json.tmp");
+            std::fs::write(&tmp, serde_json::to_string(&merged)?)
+                .context("Failed to write change journal")?;
+            std::fs::rename(&tmp, &self.file).context("Failed to replace change journal")?;
+        }
+        self.dirty = false;
+        self.last_save = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Saves if there are unsaved changes and the last write was long enough ago.
+    pub fn save_if_due(&mut self, in_flight: Option<&ChangeJournal>) -> Result<()> {
+        let due = self.last_save.is_none_or(|t| t.elapsed() >= SAVE_INTERVAL);
+        if self.dirty && due {
+            self.save(in_flight)?;
+        }
+        Ok(())
+    }
+
+    /// Whether entries were recorded since the last save.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds so persisted hashes stay comparable.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_roundtrip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let file = tmp.path().join("journal/home.json");
+
+        let mut journal = ChangeJournal::load(&file);
+        assert!(journal.is_empty());
+        journal.record(Path::new("/home/u/a.txt"));
+        journal.record(Path::new("/home/u/a.txt"));
+        journal.record(Path::new("/home/u/b.txt"));
+        assert_eq!(journal.len(), 2);
+        journal.save(None)?;
+
+        let mut journal = ChangeJournal::load(&file);
+        assert_eq!(journal.sample(10), vec!["/home/u/a.txt", "/home/u/b.txt"]);
+
+        // An empty journal removes the file
+        journal.take();
+        journal.save(None)?;
+        assert!(!file.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_hashes_past_bound() {
+        let mut journal = ChangeJournal::default();
+        for i in 0..MAX_PATHS + 10 {
+            journal.record(Path::new(&format!("/f/{}", i)));
+        }
+        journal.record(Path::new("/f/5"));
+        journal.record(Path::new(&format!("/f/{}", MAX_PATHS + 5)));
+        assert_eq!(journal.len(), (MAX_PATHS + 10) as u64);
+        assert_eq!(journal.sample(usize::MAX).len(), MAX_PATHS);
+        assert_eq!(journal.changed_bytes(), None);
+    }
+
+    #[test]
+    fn test_changed_bytes() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("a.txt"), "hello")?;
+        std::fs::write(tmp.path().join("b.txt"), "hi")?;
+
+        let mut journal = ChangeJournal::default();
+        assert_eq!(journal.changed_bytes(), Some(0));
+        journal.record(&tmp.path().join("a.txt"));
+        journal.record(&tmp.path().join("b.txt"));
+        journal.record(&tmp.path().join("deleted.txt"));
+        journal.record(tmp.path());
+        assert_eq!(journal.changed_bytes(), Some(7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_includes_in_flight_entries() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let file = tmp.path().join("home.json");
+
+        let mut journal = ChangeJournal::load(&file);
+        journal.record(Path::new("/a"));
+        let in_flight = journal.take();
+        journal.record(Path::new("/b"));
+        journal.save(Some(&in_flight))?;
+
+        assert_eq!(ChangeJournal::load(&file).len(), 2);
+        assert_eq!(journal.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -1,3 +1,28 @@
+pub mod access;
+#[cfg(unix)]
+pub mod activation;
+pub mod canary;
+pub mod capabilities;
+pub mod digest;
+pub mod engine;
+pub mod exclude;
 pub mod executor;
+pub mod fs_snapshot;
+#[cfg(unix)]
+pub mod handoff;
+pub mod history;
+pub mod hooks;
+pub mod janitor;
+pub mod journal;
+pub mod maintenance;
 pub mod manager;
+pub mod mount_monitor;
+pub mod notifier;
+pub mod prefetch;
+pub mod purge;
+pub mod run_log;
+pub mod rustic;
+pub mod scheduler;
+pub mod state;
+pub mod stats_cache;
 pub mod watcher;
@@ -1,40 +1,135 @@
 use anyhow::{Context, Result};
 use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher};
 use std::fs;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
 use std::path::PathBuf;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
-use tokio::signal::unix::{signal, SignalKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use vigil_lib::config::{load_config, Config};
-use vigil_lib::ipc::{Request, Response, ResponseData};
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+use vigil_lib::config::{load_config, modify_config, Config};
+use vigil_lib::ipc::{error_codes, Envelope, EventFilter, Request, Response, ResponseData};
 use vigil_lib::paths;
+use vigil_lib::transport::{self, IpcListener, IpcStream};
+use vigil_lib::types::{Requester, SnapshotFilter};
 
 use std::sync::Arc;
-use vigil_daemon::manager::JobManager;
+use vigil_daemon::access::ClientAccess;
+use vigil_daemon::capabilities::CapabilityMissing;
+use vigil_daemon::executor;
+use vigil_daemon::manager::{AppendOnlyRepo, FileTooLarge, JobManager, NotMounted, RepoBusy};
+use vigil_daemon::purge::InvalidPurgeToken;
+use vigil_daemon::state;
 use vigil_daemon::watcher::{FileWatcher, WatcherEvent};
 
+/// Size of each `FileChunk` sent while streaming a file.
+const CAT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Quiet period after a config change before the file is read. Editors that
+/// save through swap files and renames cause several changes per save, which
+/// are coalesced into one reload.
+const CONFIG_RELOAD_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Swaps the log filter at runtime (`SetLogLevel`). Set once by `init_logging`.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// A listening socket passed in by systemd or a replaced daemon. Only Unix
+/// sockets can be passed on, so there are none elsewhere.
+#[cfg(unix)]
+type InheritedSocket = std::os::unix::net::UnixListener;
+#[cfg(not(unix))]
+type InheritedSocket = std::convert::Infallible;
+
+/// The signals that stop the daemon: SIGTERM and SIGINT on Unix, Ctrl+C
+/// elsewhere.
+struct Signals {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    fn new() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                terminate: signal(SignalKind::terminate())?,
+                interrupt: signal(SignalKind::interrupt())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Waits for the next signal and returns its name.
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.terminate.recv() => "SIGTERM",
+                _ = self.interrupt.recv() => "SIGINT",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "Ctrl+C"
+        }
+    }
+}
+
 struct Daemon {
     pid_path: PathBuf,
     socket_path: PathBuf,
+    /// Listening socket passed by systemd socket activation. systemd owns the
+    /// socket file then, so the daemon neither binds nor removes it.
+    activated_socket: Option<InheritedSocket>,
+    /// Listening socket taken over from the daemon this one replaces (`--replace`).
+    replaced_socket: Option<InheritedSocket>,
+    /// PID of the replaced daemon and the sets whose backups still run in it.
+    replaced_backups: Option<(u32, Vec<String>)>,
+    /// Set once this daemon handed its socket to a replacement, which owns
+    /// the socket file from then on.
+    handed_off: AtomicBool,
     config: Config,
     shutdown_token: CancellationToken,
     job_manager: Arc<JobManager>,
 }
 
 impl Daemon {
-    fn new(shutdown_token: CancellationToken) -> Result<Self> {
+    fn new(
+        shutdown_token: CancellationToken,
+        config: Config,
+        replaced: Option<(InheritedSocket, u32, Vec<String>)>,
+    ) -> Result<Self> {
+        let (replaced_socket, replaced_backups) = match replaced {
+            Some((socket, pid, running)) => (Some(socket), Some((pid, running))),
+            None => (None, None),
+        };
         let pid_path = paths::pid_path();
         let socket_path = paths::socket_path();
-        let config = load_config().context("Failed to load configuration")?;
+        #[cfg(unix)]
+        let activated_socket = vigil_daemon::activation::listener_from_env()
+            .context("Failed to adopt socket from systemd")?;
+        #[cfg(not(unix))]
+        let activated_socket = None;
         let job_manager = Arc::new(JobManager::new(&config, shutdown_token.clone()));
         Ok(Self {
             pid_path,
             socket_path,
+            activated_socket,
+            replaced_socket,
+            replaced_backups,
+            handed_off: AtomicBool::new(false),
             config,
             shutdown_token,
             job_manager,
@@ -44,10 +139,12 @@ impl Daemon {
     fn create_pid_file(&self) -> Result<()> {
         if self.pid_path.exists() {
             let old_pid = fs::read_to_string(&self.pid_path)?;
-            if let Ok(pid) = old_pid.trim().parse::<i32>() {
-                // Check if process exists
-                if unsafe { libc::kill(pid, 0) } == 0 {
-                    anyhow::bail!("Daemon is already running with PID {}", pid);
+            if let Ok(pid) = old_pid.trim().parse::<u32>() {
+                if vigil_lib::platform::process_exists(pid) {
+                    // The replaced daemon keeps running until its jobs finish
+                    if self.replaced_socket.is_none() {
+                        anyhow::bail!("Daemon is already running with PID {}", pid);
+                    }
                 } else {
                     warn!("Stale PID file found (PID {}), removing...", pid);
                     let _ = fs::remove_file(&self.pid_path);
@@ -70,7 +167,9 @@ impl Daemon {
             if content.trim() == std::process::id().to_string() {
                 info!("Cleaning up PID and socket files...");
                 let _ = fs::remove_file(&self.pid_path);
-                let _ = fs::remove_file(&self.socket_path);
+                if self.activated_socket.is_none() && !self.handed_off.load(Ordering::SeqCst) {
+                    let _ = fs::remove_file(&self.socket_path);
+                }
             }
         }
     }
@@ -78,52 +177,101 @@ impl Daemon {
     async fn run(&self) -> Result<()> {
         self.create_pid_file()?;
 
-        // Query existing snapshots to populate status
-        self.job_manager.initialize_status().await;
+        self.job_manager.capabilities().log_summary();
 
-        // Ensure socket directory exists
-        if let Some(parent) = self.socket_path.parent() {
-            fs::create_dir_all(parent)?;
+        // Show the results saved before the last shutdown until the repositories answer
+        self.job_manager.restore_state().await;
+        if let Some((pid, ref running)) = self.replaced_backups {
+            if !running.is_empty() {
+                self.job_manager.hold_handed_over(pid, running).await;
+                #[cfg(unix)]
+                tokio::spawn(vigil_daemon::handoff::release_when_exited(
+                    self.job_manager.clone(),
+                    pid,
+                    self.shutdown_token.clone(),
+                ));
+            }
         }
+        // Query existing snapshots to populate status. Slow targets must not
+        // delay the socket, so this runs in the background.
+        let job_manager = self.job_manager.clone();
+        tokio::spawn(async move { job_manager.initialize_status().await });
+        self.job_manager.resume_pending_changes().await;
 
-        // Remove old socket if it exists
-        if self.socket_path.exists() {
-            fs::remove_file(&self.socket_path)?;
-        }
+        tokio::spawn(vigil_daemon::janitor::run(
+            self.job_manager.clone(),
+            self.shutdown_token.clone(),
+        ));
+        tokio::spawn(vigil_daemon::scheduler::run(
+            self.job_manager.clone(),
+            self.shutdown_token.clone(),
+        ));
+        tokio::spawn(vigil_daemon::mount_monitor::run(
+            self.job_manager.clone(),
+            self.shutdown_token.clone(),
+        ));
+        tokio::spawn(vigil_daemon::digest::run(
+            self.job_manager.clone(),
+            self.shutdown_token.clone(),
+        ));
+        tokio::spawn(vigil_daemon::maintenance::run(
+            self.job_manager.clone(),
+            self.shutdown_token.clone(),
+        ));
 
-        let listener =
-            UnixListener::bind(&self.socket_path).context("Failed to bind Unix socket")?;
+        let listener = match self.inherited_listener()? {
+            Some(listener) => listener,
+            // Creates the socket directory and replaces a stale socket
+            None => transport::bind()
+                .await
+                .context("Failed to bind IPC socket")?,
+        };
 
         let (watcher_tx, mut watcher_rx) = tokio::sync::mpsc::channel(100);
         let mut _watcher = FileWatcher::new(&self.config, watcher_tx.clone())
             .context("Failed to start file watcher")?;
+        // The configuration the watcher was built from, to rebuild it when a
+        // `files_from` list or exclude file changes
+        let mut watched_config = self.config.clone();
 
         let (reload_tx, mut reload_rx) = tokio::sync::mpsc::channel(1);
+        let (handoff_tx, mut handoff_rx) = tokio::sync::mpsc::channel(1);
+        #[cfg(unix)]
+        let listener_fd = listener.as_raw_fd();
         let (config_update_tx, mut config_update_rx) = tokio::sync::mpsc::channel::<Config>(1);
 
-        // Watch config file for changes
-        let config_path = std::env::var("VIGIL_CONFIG")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| paths::config_path());
+        // Watch config file for changes. The parent directory is watched so that
+        // atomic saves (write to a temp file, then rename) are still noticed.
+        let config_path = paths::active_config_path();
+        let config_file_name = config_path.file_name().map(|n| n.to_os_string());
         let config_reload_tx = reload_tx.clone();
         let mut _config_watcher = RecommendedWatcher::new(
             move |res: std::result::Result<notify::Event, notify::Error>| {
                 if let Ok(event) = res {
-                    if !event.kind.is_access() {
+                    let is_config = event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name().map(|n| n.to_os_string()) == config_file_name);
+                    if is_config && !event.kind.is_access() {
                         let _ = config_reload_tx.try_send(());
                     }
                 }
             },
             NotifyConfig::default(),
         )?;
-        if config_path.exists() {
-            _config_watcher.watch(&config_path, RecursiveMode::NonRecursive)?;
+        if let Some(config_dir) = config_path.parent().filter(|p| p.is_dir()) {
+            _config_watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
         }
 
         info!("Daemon listening on {:?}", self.socket_path);
 
-        let mut sigterm = signal(SignalKind::terminate())?;
-        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut signals = Signals::new()?;
+        // Every reload request pushes the timer back; the config is read once it fires
+        let reload_timer = tokio::time::sleep(CONFIG_RELOAD_DEBOUNCE);
+        tokio::pin!(reload_timer);
+        let mut reload_pending = false;
+        // Set when a signal, rather than an IPC request, stops the daemon
+        let mut signalled = false;
 
         loop {
             tokio::select! {
@@ -133,8 +281,25 @@ impl Daemon {
                             let shutdown_token = self.shutdown_token.clone();
                             let reload_tx = reload_tx.clone();
                             let job_manager = self.job_manager.clone();
+                            let handoff = Handoff {
+                                #[cfg(unix)]
+                                listener_fd,
+                                done_tx: handoff_tx.clone(),
+                            };
+                            #[cfg(not(unix))]
+                            let token = listener.token().to_owned();
                             tokio::spawn(async move {
-                                if let Err(e) = handle_client(stream, shutdown_token, reload_tx, job_manager).await {
+                                // Any local user can reach the localhost fallback
+                                #[cfg(not(unix))]
+                                let stream = {
+                                    let mut stream = stream;
+                                    if let Err(e) = transport::authenticate(&mut stream, &token).await {
+                                        warn!("Refused IPC connection: {}", e);
+                                        return;
+                                    }
+                                    stream
+                                };
+                                if let Err(e) = handle_client(stream, shutdown_token, reload_tx, job_manager, handoff).await {
                                     error!("Error handling client: {}", e);
                                 }
                             });
@@ -144,36 +309,50 @@ impl Daemon {
                         }
                     }
                 }
-                _ = sigterm.recv() => {
-                    info!("Received SIGTERM, shutting down...");
-                    self.shutdown_token.cancel();
-                    break;
-                }
-                _ = sigint.recv() => {
-                    info!("Received SIGINT, shutting down...");
-                    self.shutdown_token.cancel();
+                name = signals.recv() => {
+                    info!("Received {}, shutting down...", name);
+                    signalled = true;
                     break;
                 }
                 res = watcher_rx.recv() => {
                     if let Some(event) = res {
                         match event {
-                            WatcherEvent::FileChanged { set_name, path } => {
-                                debug!("File change detected for set {}: {:?}", set_name, path);
-                                if let Err(e) = self.job_manager.handle_file_change(&set_name).await {
+                            WatcherEvent::FilesChanged { set_name, batch } => {
+                                info!("{} changed path(s) detected in set {}", batch.paths.len() as u64 + batch.dropped, set_name);
+                                if let Err(e) = self.job_manager.handle_file_changes(&set_name, &batch).await {
+                                    error!("Error handling file change for set {}: {}", set_name, e);
+                                }
+                            }
+                            WatcherEvent::FileListChanged { set_name, path } => {
+                                // Editing the list changes what the next backup covers
+                                if let Err(e) = self.job_manager.handle_file_change(&set_name, &path).await {
                                     error!("Error handling file change for set {}: {}", set_name, e);
                                 }
+                                match FileWatcher::new(&watched_config, watcher_tx.clone()) {
+                                    Ok(new_watcher) => {
+                                        _watcher = new_watcher;
+                                        info!("File watcher updated for the new file list of set {}", set_name);
+                                    }
+                                    Err(e) => {
+                                        error!("Failed to restart file watcher after file list change: {}", e);
+                                    }
+                                }
                             }
                         }
                     }
                 }
-                _ = reload_rx.recv() => {
+                Some(()) = reload_rx.recv() => {
+                    reload_pending = true;
+                    reload_timer
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + CONFIG_RELOAD_DEBOUNCE);
+                }
+                _ = &mut reload_timer, if reload_pending => {
+                    reload_pending = false;
                     let config_update_tx = config_update_tx.clone();
                     let shutdown_token = self.shutdown_token.clone();
 
                     tokio::spawn(async move {
-                        // Wait a short duration to avoid reading a partial file during atomic saves
-                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-
                         let mut attempts = 0;
                         let max_attempts = 3;
                         let retry_delay = std::time::Duration::from_secs(2);
@@ -204,7 +383,14 @@ impl Daemon {
                     });
                 }
                 Some(new_config) = config_update_rx.recv() => {
+                    if new_config == watched_config {
+                        info!("Configuration unchanged, skipping reload");
+                        continue;
+                    }
                     info!("Applying new configuration...");
+                    for warning in new_config.warnings() {
+                        warn!("{}", warning);
+                    }
                     if let Err(e) = self.job_manager.sync_config(&new_config).await {
                         error!("Failed to sync job manager with new config: {}", e);
                     } else {
@@ -212,6 +398,7 @@ impl Daemon {
                         match FileWatcher::new(&new_config, watcher_tx.clone()) {
                             Ok(new_watcher) => {
                                 _watcher = new_watcher;
+                                watched_config = new_config;
                                 info!("Configuration reloaded and file watcher updated");
                             }
                             Err(e) => {
@@ -220,6 +407,11 @@ impl Daemon {
                         }
                     }
                 }
+                Some(()) = handoff_rx.recv() => {
+                    info!("Handed the socket over to the replacing daemon");
+                    self.handed_off.store(true, Ordering::SeqCst);
+                    break;
+                }
                 _ = self.shutdown_token.cancelled() => {
                     info!("Shutdown requested via IPC, shutting down...");
                     break;
@@ -227,25 +419,121 @@ impl Daemon {
             }
         }
 
+        if self.handed_off.load(Ordering::SeqCst) {
+            // The replacement serves clients and watches files from now on
+            drop(listener);
+            drop(_watcher);
+            drop(_config_watcher);
+            info!("Waiting for running jobs to finish before exiting...");
+            tokio::select! {
+                _ = self.job_manager.wait_for_running_jobs() => {}
+                name = signals.recv() => info!("Received {}, cancelling running jobs...", name),
+            }
+            self.shutdown_token.cancel();
+        } else if signalled {
+            let (grace_secs, running) = self.job_manager.begin_shutdown(true).await;
+            if grace_secs > 0 && !running.is_empty() {
+                drop(listener);
+                info!(
+                    "Waiting up to {}s for running jobs of {:?} to finish...",
+                    grace_secs, running
+                );
+                tokio::select! {
+                    _ = self.job_manager.wait_for_running_jobs() => {}
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(grace_secs)) => {
+                        info!("Shutdown grace period over, cancelling running jobs...");
+                    }
+                    name = signals.recv() => info!("Received {}, cancelling running jobs...", name),
+                }
+            }
+            self.job_manager.mark_interrupted().await;
+            self.shutdown_token.cancel();
+        }
+
         // Cleanup any active mounts on shutdown
-        if let Err(e) = self.job_manager.unmount(None).await {
+        if let Err(e) = self.job_manager.unmount(None, None).await {
             error!("Error unmounting sets on shutdown: {}", e);
         }
+        self.job_manager.flush_journals().await;
 
         Ok(())
     }
+
+    /// Adopts the socket passed by systemd or the replaced daemon, if any.
+    #[cfg(unix)]
+    fn inherited_listener(&self) -> Result<Option<IpcListener>> {
+        let listener = match (&self.activated_socket, &self.replaced_socket) {
+            (Some(socket), _) => {
+                info!("Using the socket passed by systemd socket activation");
+                IpcListener::from_std(socket.try_clone()?)
+                    .context("Failed to adopt socket from systemd")?
+            }
+            (None, Some(socket)) => {
+                info!("Using the socket handed over by the replaced daemon");
+                IpcListener::from_std(socket.try_clone()?)
+                    .context("Failed to adopt socket from the replaced daemon")?
+            }
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(listener))
+    }
+
+    #[cfg(not(unix))]
+    fn inherited_listener(&self) -> Result<Option<IpcListener>> {
+        Ok(None)
+    }
+}
+
+/// What a client connection needs to hand the listening socket to a
+/// replacing daemon.
+struct Handoff {
+    #[cfg(unix)]
+    listener_fd: RawFd,
+    /// Tells the accept loop that the socket was handed over.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    done_tx: tokio::sync::mpsc::Sender<()>,
+}
+
+/// Sends `reply` with the listening socket attached, waiting while the
+/// connection's send buffer is full.
+#[cfg(unix)]
+async fn send_handoff_reply(
+    stream_fd: RawFd,
+    reply: &[u8],
+    listener_fd: RawFd,
+) -> std::io::Result<usize> {
+    loop {
+        match vigil_daemon::handoff::send_with_fd(stream_fd, reply, listener_fd) {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            res => return res,
+        }
+    }
 }
 
 async fn handle_client(
-    mut stream: UnixStream,
+    mut stream: IpcStream,
     shutdown_token: CancellationToken,
     reload_tx: tokio::sync::mpsc::Sender<()>,
     job_manager: Arc<JobManager>,
+    handoff: Handoff,
 ) -> Result<()> {
+    #[cfg(unix)]
+    let stream_fd = stream.as_raw_fd();
+    let peer_pid = peer_pid(&stream);
+    let peer_uid = peer_uid(&stream);
+    let access = ClientAccess::for_peer(peer_pid, &paths::permissions_path());
     let (reader, mut writer) = stream.split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
     let mut event_rx = job_manager.subscribe();
+    // Every event until the client sends `Subscribe`; `None` after
+    // `Unsubscribe`, and for clients not allowed to subscribe
+    let mut subscription = access
+        .check("Subscribe")
+        .ok()
+        .map(|_| EventFilter::default());
 
     loop {
         tokio::select! {
@@ -255,39 +543,118 @@ async fn handle_client(
                     break;
                 }
 
-                let request: Request = match serde_json::from_str(&line) {
-                    Ok(req) => req,
+                let Envelope::<Request> { id, message: request } = match serde_json::from_str(&line) {
+                    Ok(envelope) => envelope,
                     Err(e) => {
                         let err_resp = Response::Error {
                             code: "InvalidRequest".into(),
                             message: format!("Failed to parse JSON: {}", e),
                         };
-                        let json = serde_json::to_string(&err_resp)? + "\n";
-                        writer.write_all(json.as_bytes()).await?;
+                        // Echo the id of a well-formed line with an unknown request
+                        let id = serde_json::from_str::<serde_json::Value>(&line)
+                            .ok()
+                            .and_then(|v| v.get("id")?.as_u64());
+                        writer.write_all(reply_line(id, &err_resp)?.as_bytes()).await?;
                         line.clear();
                         continue;
                     }
                 };
 
                 line.clear();
-                let is_shutdown = matches!(request, Request::Shutdown);
+                let allowed = match request {
+                    // Checked against the replaced daemon's PID instead, as
+                    // its executable may have been upgraded away
+                    Request::HandoffResult { .. } => Ok(()),
+                    _ => access.check(request.kind()),
+                };
+                if let Err(message) = allowed {
+                    let denied = Response::Error {
+                        code: error_codes::PERMISSION_DENIED.into(),
+                        message,
+                    };
+                    writer.write_all(reply_line(id, &denied)?.as_bytes()).await?;
+                    continue;
+                }
+                let mut is_shutdown = false;
+
 
                 let response = match request {
                     Request::Ping => Response::Pong,
+                    #[cfg(not(unix))]
+                    Request::Handoff => {
+                        let _ = &handoff;
+                        Response::Error {
+                            code: error_codes::INVALID_REQUEST.into(),
+                            message: "Replacing a running daemon needs Unix sockets".into(),
+                        }
+                    }
+                    #[cfg(unix)]
+                    Request::Handoff => {
+                        let running = match job_manager.begin_handoff().await {
+                            Ok(running) => running,
+                            Err(e) => {
+                                let json = reply_line(id, &error_response(error_codes::DAEMON_BUSY, e))?;
+                                writer.write_all(json.as_bytes()).await?;
+                                continue;
+                            }
+                        };
+                        info!("Handing the socket over to a replacing daemon");
+                        let reply = Response::Ok(Some(ResponseData::HandoffReady {
+                            pid: std::process::id(),
+                            running,
+                        }));
+                        let json = reply_line(id, &reply)?;
+                        writer.flush().await?;
+                        match send_handoff_reply(stream_fd, json.as_bytes(), handoff.listener_fd).await {
+                            Ok(sent) => writer.write_all(&json.as_bytes()[sent..]).await?,
+                            Err(e) => {
+                                job_manager.abort_handoff();
+                                return Err(e).context("Failed to hand over the socket");
+                            }
+                        }
+                        let _ = handoff.done_tx.send(()).await;
+                        break;
+                    }
+                    Request::HandoffResult { set_name, result } => {
+                        match job_manager.finish_handed_over(peer_pid, &set_name, result).await {
+                            Ok(()) => Response::Ok(None),
+                            Err(e) => error_response(error_codes::INVALID_REQUEST, e),
+                        }
+                    }
                     Request::Status => {
                         let sets = job_manager.get_status().await;
                         Response::Ok(Some(ResponseData::Status { sets }))
                     }
-                    Request::Shutdown => {
-                        info!("Shutdown requested via IPC");
-                        // Trigger shutdown
-                        shutdown_token.cancel();
-                        Response::Ok(None)
+                    Request::Health => {
+                        let (overall, issues) = job_manager.health(chrono::Utc::now()).await;
+                        Response::Ok(Some(ResponseData::Health { overall, issues }))
+                    }
+                    Request::StatusLine => Response::Ok(Some(ResponseData::StatusLine {
+                        status: job_manager.status_line(chrono::Utc::now()).await,
+                    })),
+                    Request::Shutdown { force } => {
+                        let running = job_manager.running_jobs().await;
+                        if !force && !running.is_empty() {
+                            info!("Refusing shutdown: jobs running for {:?}", running);
+                            Response::JobsRunning { sets: running }
+                        } else {
+                            if !running.is_empty() {
+                                warn!("Forced shutdown while jobs running for {:?}", running);
+                            }
+                            info!("Shutdown requested via IPC");
+                            // A forced shutdown gets no grace period
+                            job_manager.begin_shutdown(false).await;
+                            job_manager.mark_interrupted().await;
+                            // Trigger shutdown
+                            shutdown_token.cancel();
+                            is_shutdown = true;
+                            Response::Ok(None)
+                        }
                     }
-                    Request::Backup { set_name } => {
+                    Request::Backup { set_name, tags } => {
                         match set_name {
-                            Some(name) => match job_manager.trigger_backup(&name).await {
-                                Ok(_) => Response::Ok(Some(ResponseData::BackupStarted { set_name: name })),
+                            Some(name) => match job_manager.trigger_tagged_backup(&name, &tags).await {
+                                Ok(_) => Response::Ok(Some(ResponseData::BackupStarted { set_name: name, recent_changes: Vec::new() })),
                                 Err(e) => Response::Error {
                                     code: "BackupFailed".into(),
                                     message: e.to_string(),
@@ -299,7 +666,7 @@ async fn handle_client(
                                 let mut started = Vec::new();
                                 let mut failed = Vec::new();
                                 for status in statuses {
-                                    match job_manager.trigger_backup(&status.name).await {
+                                    match job_manager.trigger_tagged_backup(&status.name, &tags).await {
                                         Ok(_) => started.push(status.name),
                                         Err(e) => {
                                             warn!(
@@ -314,8 +681,31 @@ async fn handle_client(
                             }
                         }
                     }
-                    Request::Snapshots { set_name, limit } => {
-                        match job_manager.get_snapshots(&set_name, limit).await {
+                    Request::Rebackup { set_name, include } => {
+                        match job_manager.trigger_rescue_backup(&set_name, &include).await {
+                            Ok(lifted_excludes) => Response::Ok(Some(ResponseData::RescueStarted {
+                                set_name,
+                                lifted_excludes,
+                            })),
+                            Err(e) => Response::Error {
+                                code: "BackupFailed".into(),
+                                message: e.to_string(),
+                            },
+                        }
+                    }
+                    Request::Snapshots {
+                        set_name,
+                        limit,
+                        tag,
+                        since,
+                        until,
+                    } => {
+                        let filter = SnapshotFilter {
+                            tag,
+                            since,
+                            until,
+                        };
+                        match job_manager.get_snapshots(&set_name, limit, &filter).await {
                             Ok(snapshots) => Response::Ok(Some(ResponseData::Snapshots { snapshots })),
                             Err(e) => Response::Error {
                                 code: "ResticError".into(),
@@ -323,20 +713,113 @@ async fn handle_client(
                             },
                         }
                     }
+                    Request::Stats { set_name } => match job_manager.stats(&set_name).await {
+                        Ok((stats, snapshots)) => {
+                            Response::Ok(Some(ResponseData::Stats { stats, snapshots }))
+                        }
+                        Err(e) => Response::Error {
+                            code: error_codes::RESTIC_ERROR.into(),
+                            message: e.to_string(),
+                        },
+                    },
+                    Request::History {
+                        set_name,
+                        limit,
+                        ops,
+                    } => match job_manager.history(&set_name, limit, &ops).await {
+                        Ok((runs, restores)) => {
+                            Response::Ok(Some(ResponseData::History { runs, restores }))
+                        }
+                        Err(e) => Response::Error {
+                            code: error_codes::UNKNOWN_SET.into(),
+                            message: e.to_string(),
+                        },
+                    },
                     Request::Mount {
                         set_name,
                         snapshot_id,
                     } => match job_manager.mount(&set_name, snapshot_id).await {
-                        Ok(path) => Response::Ok(Some(ResponseData::MountPath {
+                        Ok((path, warming)) => Response::Ok(Some(ResponseData::MountPath {
                             path: path.to_string_lossy().to_string(),
+                            warming,
                         })),
-                        Err(e) => Response::Error {
-                            code: "MountFailed".into(),
+                        Err(e) => error_response("MountFailed", e),
+                    },
+                    Request::Restore {
+                        set_name,
+                        snapshot_id,
+                        path,
+                        target,
+                    } => {
+                        let requested_by = Requester {
+                            uid: peer_uid,
+                            user: peer_uid.and_then(vigil_lib::platform::user_name),
+                            client: access.exe().map(|exe| exe.display().to_string()),
+                        };
+                        match job_manager
+                            .restore(
+                                &set_name,
+                                snapshot_id,
+                                path,
+                                PathBuf::from(target),
+                                requested_by,
+                            )
+                            .await
+                        {
+                        Ok(data) => Response::Ok(Some(data)),
+                            Err(e) => error_response(error_codes::RESTORE_FAILED, e),
+                        }
+                    }
+                    Request::Cat {
+                        set_name,
+                        snapshot_id,
+                        path,
+                        max_bytes,
+                    } => match job_manager.cat(&set_name, &snapshot_id, &path, max_bytes).await {
+                        Ok((size, child)) => stream_file(&mut writer, id, child, size).await?,
+                        Err(e) if e.is::<FileTooLarge>() => Response::Error {
+                            code: error_codes::FILE_TOO_LARGE.into(),
                             message: e.to_string(),
                         },
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                    },
+                    Request::ListFiles {
+                        set_name,
+                        snapshot_id,
+                        path,
+                        recursive,
+                    } => match job_manager
+                        .list_files(&set_name, &snapshot_id, path.as_deref(), recursive)
+                        .await
+                    {
+                        Ok((entries, truncated)) => {
+                            Response::Ok(Some(ResponseData::FileList { entries, truncated }))
+                        }
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
                     },
-                    Request::Unmount { set_name } => match job_manager.unmount(set_name).await {
+                    Request::Diff {
+                        set_name,
+                        snapshot_a,
+                        snapshot_b,
+                    } => match job_manager
+                        .diff(&set_name, &snapshot_a, snapshot_b.as_deref())
+                        .await
+                    {
+                        Ok(diff) => Response::Ok(Some(ResponseData::Diff {
+                            added: diff.added,
+                            removed: diff.removed,
+                            modified: diff.modified,
+                        })),
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                    },
+                    Request::Unmount {
+                        set_name,
+                        snapshot_id,
+                    } => match job_manager.unmount(set_name, snapshot_id).await {
                         Ok(_) => Response::Ok(None),
+                        Err(e) if e.is::<NotMounted>() => {
+                            error_response(error_codes::NOT_MOUNTED, e)
+                        }
                         Err(e) => Response::Error {
                             code: "ResticError".into(),
                             message: e.to_string(),
@@ -344,19 +827,243 @@ async fn handle_client(
                     },
                     Request::Prune { set_name } => match job_manager.prune(set_name).await {
                         Ok(data) => Response::Ok(Some(data)),
+                        Err(e) if e.is::<AppendOnlyRepo>() => Response::Error {
+                            code: error_codes::APPEND_ONLY.into(),
+                            message: e.to_string(),
+                        },
                         Err(e) => Response::Error {
                             code: "ResticError".into(),
                             message: e.to_string(),
                         },
                     },
+                    Request::Replicate { set_name } => {
+                        match job_manager.replicate(&set_name).await {
+                            Ok(data) => Response::Ok(Some(data)),
+                            Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                        }
+                    }
+                    Request::PrunePreview { set_name } => {
+                        match job_manager.prune_preview(&set_name).await {
+                            Ok(data) => Response::Ok(Some(data)),
+                            Err(e) if e.is::<AppendOnlyRepo>() => Response::Error {
+                                code: error_codes::APPEND_ONLY.into(),
+                                message: e.to_string(),
+                            },
+                            Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                        }
+                    }
+                    Request::Forget {
+                        set_name,
+                        snapshot_id,
+                        prune,
+                    } => match job_manager.forget(&set_name, &snapshot_id, prune).await {
+                        Ok(data) => Response::Ok(Some(data)),
+                        Err(e) if e.is::<AppendOnlyRepo>() => Response::Error {
+                            code: error_codes::APPEND_ONLY.into(),
+                            message: e.to_string(),
+                        },
+                        Err(e) if e.is::<RepoBusy>() => Response::Error {
+                            code: error_codes::DAEMON_BUSY.into(),
+                            message: e.to_string(),
+                        },
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                    },
                     Request::ReloadConfig => {
                         let _ = reload_tx.send(()).await;
                         Response::Ok(None)
                     }
+                    Request::ModifyConfig { ops } => {
+                        // Apply and sync before replying so follow-up requests see the edit
+                        let result = match tokio::task::spawn_blocking(move || modify_config(&ops)).await? {
+                            Ok(new_config) => job_manager.sync_config(&new_config).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        match result {
+                            Ok(()) => {
+                                // Refresh file watchers for added or removed sets
+                                let _ = reload_tx.send(()).await;
+                                Response::Ok(None)
+                            }
+                            Err(e) => Response::Error {
+                                code: error_codes::CONFIG_INVALID.into(),
+                                message: e.to_string(),
+                            },
+                        }
+                    }
+                    Request::Subscribe { sets, kinds } => match EventFilter::new(sets, kinds) {
+                        Ok(filter) => {
+                            subscription = Some(filter);
+                            Response::Ok(None)
+                        }
+                        Err(message) => Response::Error {
+                            code: error_codes::INVALID_REQUEST.into(),
+                            message,
+                        },
+                    },
+                    Request::Unsubscribe => {
+                        subscription = None;
+                        Response::Ok(None)
+                    }
+                    Request::SetLogLevel { filter } => match set_log_filter(&filter) {
+                        Ok((filter, previous)) => {
+                            Response::Ok(Some(ResponseData::LogLevel { filter, previous }))
+                        }
+                        Err(e) => Response::Error {
+                            code: error_codes::INVALID_REQUEST.into(),
+                            message: format!("{:#}", e),
+                        },
+                    },
+                    Request::Capabilities => Response::Ok(Some(ResponseData::Capabilities {
+                        capabilities: job_manager.capabilities().matrix(),
+                    })),
+                    Request::EffectiveConfig { set_name } => {
+                        match job_manager.effective_config(&set_name).await {
+                            Ok(config) => Response::Ok(Some(ResponseData::EffectiveConfig {
+                                config: Box::new(config),
+                            })),
+                            Err(e) => Response::Error {
+                                code: "UnknownSet".into(),
+                                message: e.to_string(),
+                            },
+                        }
+                    }
+                    Request::Init { set_name } => match job_manager.init_repos(set_name).await {
+                        Ok(results) => Response::Ok(Some(ResponseData::InitResults { results })),
+                        Err(e) => Response::Error {
+                            code: "UnknownSet".into(),
+                            message: e.to_string(),
+                        },
+                    },
+                    Request::Check { set_name } => match job_manager.check_repos(set_name).await {
+                        Ok(results) => Response::Ok(Some(ResponseData::CheckResults { results })),
+                        Err(e) => Response::Error {
+                            code: "UnknownSet".into(),
+                            message: e.to_string(),
+                        },
+                    },
+                    Request::Verify {
+                        set_name,
+                        subset,
+                        now,
+                    } => {
+                        if !executor::is_valid_read_subset(&subset) {
+                            Response::Error {
+                                code: error_codes::INVALID_REQUEST.into(),
+                                message: format!(
+                                    "Invalid subset '{}'. Use a percentage (5%), a fraction (1/10) or a size (500M).",
+                                    subset
+                                ),
+                            }
+                        } else {
+                            match job_manager.verify_repos(set_name, &subset, now).await {
+                                Ok(results) => {
+                                    Response::Ok(Some(ResponseData::VerifyResults { results }))
+                                }
+                                Err(e) => Response::Error {
+                                    code: error_codes::UNKNOWN_SET.into(),
+                                    message: e.to_string(),
+                                },
+                            }
+                        }
+                    }
+                    Request::Repair { set_name, step } => {
+                        match job_manager.repair(&set_name, step).await {
+                            Ok(outcome) => {
+                                Response::Ok(Some(ResponseData::RepairResult { outcome }))
+                            }
+                            Err(e) if e.is::<RepoBusy>() => Response::Error {
+                                code: error_codes::DAEMON_BUSY.into(),
+                                message: e.to_string(),
+                            },
+                            Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                        }
+                    }
+                    Request::Unlock {
+                        set_name,
+                        remove_all,
+                    } => match job_manager.unlock(&set_name, remove_all).await {
+                        Ok(data) => Response::Ok(Some(data)),
+                        Err(e) if e.is::<RepoBusy>() => Response::Error {
+                            code: error_codes::DAEMON_BUSY.into(),
+                            message: e.to_string(),
+                        },
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                    },
+                    Request::PurgeToken { set_name } => {
+                        match job_manager.purge_token(&set_name).await {
+                            Ok(data) => Response::Ok(Some(data)),
+                            Err(e) => error_response(error_codes::INVALID_REQUEST, e),
+                        }
+                    }
+                    Request::Purge { set_name, token } => {
+                        match job_manager.purge(&set_name, &token).await {
+                            Ok(data) => Response::Ok(Some(data)),
+                            Err(e) if e.is::<RepoBusy>() => Response::Error {
+                                code: error_codes::DAEMON_BUSY.into(),
+                                message: e.to_string(),
+                            },
+                            Err(e) if e.is::<InvalidPurgeToken>() => {
+                                error_response(error_codes::INVALID_REQUEST, e)
+                            }
+                            Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                        }
+                    }
+                    Request::Reinit {
+                        set_name,
+                        token,
+                        discard_old,
+                    } => match job_manager.reinit(&set_name, &token, discard_old).await {
+                        Ok(data) => Response::Ok(Some(data)),
+                        Err(e) if e.is::<RepoBusy>() => Response::Error {
+                            code: error_codes::DAEMON_BUSY.into(),
+                            message: e.to_string(),
+                        },
+                        Err(e) if e.is::<InvalidPurgeToken>() => {
+                            error_response(error_codes::INVALID_REQUEST, e)
+                        }
+                        Err(e) => error_response(error_codes::RESTIC_ERROR, e),
+                    },
+                    Request::Resume { set_name } => match job_manager.resume(&set_name).await {
+                        Ok(()) => Response::Ok(None),
+                        Err(e) => error_response(error_codes::INVALID_REQUEST, e),
+                    },
+                    Request::Clean { dry_run } => {
+                        let report = job_manager.clean_mount_dirs(dry_run).await;
+                        Response::Ok(Some(ResponseData::CleanReport {
+                            dry_run,
+                            removed: report
+                                .removed
+                                .iter()
+                                .map(|p| p.to_string_lossy().to_string())
+                                .collect(),
+                            skipped: report
+                                .skipped
+                                .into_iter()
+                                .map(|(p, reason)| (p.to_string_lossy().to_string(), reason))
+                                .collect(),
+                        }))
+                    }
+                    Request::Info { set_name } => match job_manager.get_info(&set_name).await {
+                        Ok(info) => Response::Ok(Some(ResponseData::Info {
+                            info: Box::new(info),
+                        })),
+                        Err(e) => Response::Error {
+                            code: "ResticError".into(),
+                            message: e.to_string(),
+                        },
+                    },
+                    Request::Attest { set_name } => match job_manager.attest(&set_name).await {
+                        Ok(document) => Response::Ok(Some(ResponseData::Attestation {
+                            document: Box::new(document),
+                        })),
+                        Err(e) => Response::Error {
+                            code: error_codes::RESTIC_ERROR.into(),
+                            message: e.to_string(),
+                        },
+                    },
                 };
 
-                let json = serde_json::to_string(&response)? + "\n";
-                writer.write_all(json.as_bytes()).await?;
+                writer.write_all(reply_line(id, &response)?.as_bytes()).await?;
 
                 // If shutdown was requested, close connection after responding
                 if is_shutdown {
@@ -366,8 +1073,10 @@ async fn handle_client(
             event_res = event_rx.recv() => {
                 match event_res {
                     Ok(response) => {
-                        let json = serde_json::to_string(&response)? + "\n";
-                        writer.write_all(json.as_bytes()).await?;
+                        if subscription.as_ref().is_some_and(|f| f.matches(&response)) {
+                            let json = serde_json::to_string(&response)? + "\n";
+                            writer.write_all(json.as_bytes()).await?;
+                        }
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         warn!("Client lagged behind on broadcast events by {}", n);
@@ -384,6 +1093,110 @@ async fn handle_client(
     Ok(())
 }
 
+/// One line of the reply to the request with `id`.
+fn reply_line(id: Option<u64>, response: &Response) -> Result<String> {
+    Ok(serde_json::to_string(&Envelope::new(id, response))? + "\n")
+}
+
+/// Streams a dump process's stdout to the client as `FileChunk`s and returns the
+/// final response. Stops if the output grows past the size checked up front.
+async fn stream_file<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    id: Option<u64>,
+    mut child: tokio::process::Child,
+    size: u64,
+) -> Result<Response> {
+    let mut stdout = child.stdout.take().context("Failed to take stdout")?;
+    let mut buf = vec![0u8; CAT_CHUNK_BYTES];
+    let mut sent = 0u64;
+    loop {
+        let n = stdout.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        sent += n as u64;
+        if sent > size {
+            let _ = child.kill().await;
+            return Ok(Response::Error {
+                code: error_codes::RESTIC_ERROR.into(),
+                message: format!("Dump produced more than the expected {} bytes", size),
+            });
+        }
+        let chunk = Response::Ok(Some(ResponseData::FileChunk {
+            data: buf[..n].to_vec(),
+        }));
+        writer.write_all(reply_line(id, &chunk)?.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Ok(Response::Error {
+            code: error_codes::RESTIC_ERROR.into(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(Response::Ok(Some(ResponseData::CatComplete {
+        bytes: sent,
+    })))
+}
+
+/// PID of the client at the other end of `stream`, used to look up its
+/// permissions. Only Unix sockets report it; on the localhost fallback it is
+/// always `None`.
+fn peer_pid(stream: &IpcStream) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        stream.peer_cred().ok().and_then(|cred| cred.pid())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+/// User ID of the client at the other end of `stream`, recorded with the
+/// restores it asks for. Like [`peer_pid`], only Unix sockets report it.
+fn peer_uid(stream: &IpcStream) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        stream.peer_cred().ok().map(|cred| cred.uid())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+/// Converts an operation error into an IPC response, surfacing missing
+/// capabilities as a structured `CapabilityMissing` response.
+fn error_response(code: &str, e: anyhow::Error) -> Response {
+    match e.downcast_ref::<CapabilityMissing>() {
+        Some(missing) => Response::CapabilityMissing {
+            name: missing.name.clone(),
+            hint: missing.hint.clone(),
+        },
+        None => Response::Error {
+            code: code.into(),
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Replaces the log filter, returning the new and the previous one as text.
+fn set_log_filter(directives: &str) -> Result<(String, String)> {
+    let filter = EnvFilter::try_new(directives)
+        .with_context(|| format!("Invalid log filter '{}'", directives))?;
+    let handle = LOG_FILTER.get().context("Logging is not initialized")?;
+    let previous = handle.with_current(|f| f.to_string())?;
+    let current = filter.to_string();
+    // Logged under the old filter, so lowering the level still leaves a trace
+    info!("Changing log filter from '{}' to '{}'", previous, current);
+    handle.reload(filter)?;
+    Ok((current, previous))
+}
+
 fn init_logging() -> WorkerGuard {
     let log_path_full = paths::log_path();
     let log_dir = log_path_full
@@ -404,6 +1217,8 @@ fn init_logging() -> WorkerGuard {
     let filter = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new("info"))
         .unwrap();
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER.set(handle);
 
     tracing_subscriber::registry()
         .with(filter)
@@ -419,8 +1234,51 @@ async fn main() -> Result<()> {
     // Initialize logging with rotation
     let _guard = init_logging();
 
+    let mut reset_state = false;
+    let mut replace = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--reset-state" => reset_state = true,
+            "--replace" => replace = true,
+            other => anyhow::bail!(
+                "Unknown argument: {} (supported: --reset-state, --replace)",
+                other
+            ),
+        }
+    }
+
+    // Refuse to start on state this version cannot read, before anything loads it
+    if let Err(e) = state::prepare(&paths::data_dir(), reset_state) {
+        error!("{:#}", e);
+        return Err(e);
+    }
+
+    // Checked before taking over: once the running daemon hands over its
+    // socket, a config this one cannot start with would leave nobody serving it
+    let config = load_config().context("Failed to load configuration")?;
+    for warning in config.warnings() {
+        warn!("{}", warning);
+    }
+
+    // Take over before loading any state: the old daemon writes its state and
+    // change journals for this one first
+    #[cfg(unix)]
+    let replaced = if replace {
+        let (socket, pid, running) = vigil_daemon::handoff::take_over(&paths::socket_path())
+            .context("Failed to take over from the running daemon")?;
+        Some((socket, pid, running))
+    } else {
+        None
+    };
+    #[cfg(not(unix))]
+    let replaced = if replace {
+        anyhow::bail!("--replace needs Unix sockets to hand over the listening socket");
+    } else {
+        None
+    };
+
     let shutdown_token = CancellationToken::new();
-    let daemon = Daemon::new(shutdown_token)?;
+    let daemon = Daemon::new(shutdown_token, config, replaced)?;
 
     let res = daemon.run().await;
 
@@ -444,6 +1302,10 @@ mod tests {
         let daemon = Daemon {
             pid_path: pid_path.clone(),
             socket_path: socket_path.clone(),
+            activated_socket: None,
+            replaced_socket: None,
+            replaced_backups: None,
+            handed_off: AtomicBool::new(false),
             config: Config {
                 global: Default::default(),
                 backup_sets: vec![],
@@ -469,4 +1331,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_set_log_filter_rejects_invalid_filter() {
+        let err = set_log_filter("vigil_daemon=loud").unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Invalid log filter 'vigil_daemon=loud'"));
+    }
+
+    #[test]
+    fn test_error_response_maps_capability_missing() {
+        let err = anyhow::Error::new(CapabilityMissing {
+            name: "fusermount3".to_string(),
+            hint: "Install FUSE 3".to_string(),
+        });
+        assert_eq!(
+            error_response("MountFailed", err),
+            Response::CapabilityMissing {
+                name: "fusermount3".to_string(),
+                hint: "Install FUSE 3".to_string(),
+            }
+        );
+
+        let err = anyhow::anyhow!("boom");
+        assert_eq!(
+            error_response("MountFailed", err),
+            Response::Error {
+                code: "MountFailed".to_string(),
+                message: "boom".to_string(),
+            }
+        );
+    }
 }
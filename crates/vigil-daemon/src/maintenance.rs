@@ -0,0 +1,53 @@
+use crate::manager::JobManager;
+use crate::scheduler;
+use chrono::Utc;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use vigil_lib::ipc::{Response, ResponseData};
+
+/// Runs the automatic prunes the backup worker queues, the deep checks
+/// deferred to a maintenance window and the hourly replica comparisons, until
+/// `token` is cancelled. Prunes and checks start once their window is open and
+/// no backup, prune or replication uses the repository. Events only wake the
+/// task early; after missing some it checks the queue again at once.
+pub async fn run(manager: Arc<JobManager>, token: CancellationToken) {
+    let mut events = manager.subscribe();
+    loop {
+        let now = Utc::now();
+        let earliest = [
+            manager.run_pending_prunes(now).await,
+            manager.run_pending_verifications(now).await,
+            manager.run_due_replica_checks(now).await,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let wake = Instant::now() + scheduler::sleep_until(earliest.map(|at| at - Utc::now()));
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep_until(wake) => break,
+                event = events.recv() => match event {
+                    // A finished backup queues a prune, and a finished backup,
+                    // prune or replication may free a repository one waits for
+                    Ok(Response::Event(
+                        ResponseData::BackupComplete { .. }
+                        | ResponseData::BackupFailed { .. }
+                        | ResponseData::PruneComplete { .. }
+                        | ResponseData::ReplicationComplete { .. }
+                        | ResponseData::ReplicationFailed { .. },
+                    )) => break,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        debug!("Maintenance task missed {} events, checking the queue", missed);
+                        break;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+    }
+}
@@ -1,45 +1,579 @@
-use crate::executor::ResticExecutor;
-use anyhow::Result;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use crate::canary;
+use crate::capabilities::{Capabilities, Capability};
+use crate::digest::Digest;
+use crate::engine::Engine;
+use crate::exclude::lifted_excludes;
+use crate::executor::{self, EngineDefaults, Progress, ProgressSender, SnapshotDiff};
+use crate::fs_snapshot::{self, FsSnapshot};
+use crate::history;
+use crate::hooks;
+use crate::janitor::{self, CleanReport};
+use crate::journal::ChangeJournal;
+use crate::notifier::{self, Notification, Notifier};
+use crate::prefetch;
+use crate::purge::{self, InvalidPurgeToken, PurgeIntent, PurgeTokens};
+use crate::run_log::RunLog;
+use crate::state::{DaemonState, SetState};
+use crate::stats_cache::SnapshotStatsCache;
+use crate::watcher::ChangeBatch;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
 use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
-use vigil_lib::config::{BackupSet, Config, RetentionPolicy};
-use vigil_lib::ipc::{Response, ResponseData};
-use vigil_lib::types::{BackupResult, JobState, SetStatus, SnapshotInfo};
+use vigil_lib::attest::{
+    self, Attestation, AttestedSnapshot, SignedAttestation, ToolVersions, ATTESTATION_FORMAT,
+};
+use vigil_lib::config::{
+    retry_delay_secs, Backend, BackupSet, Config, EffectiveConfig, GlobalConfig, HookFailure,
+    MaintenanceWindow, NotificationPolicy, NotifyEvent, RetentionPolicy, Schedule,
+};
+use vigil_lib::humanize;
+use vigil_lib::ipc::{Request, Response, ResponseData};
+use vigil_lib::types::{
+    BackupResult, BackupRun, CompactStatus, FileEntry, HealthIssue, HealthIssueKind, HealthLevel,
+    HistoryOp, HookStage, HostSnapshots, JobState, MountInfo, RepairOutcome, RepairStep,
+    ReplicaLag, RepoOpResult, RepoOpStatus, RepoStats, Requester, RestoreRecord, SetInfo,
+    SetStatus, SnapshotFilter, SnapshotInfo, UnmountReason, VerifyOutcome,
+};
+
+/// Maximum number of pending paths returned by `Info`.
+const PENDING_SAMPLE_SIZE: usize = 20;
+
+/// Maximum number of recently changed paths kept per set for `Status` and
+/// the `BackupStarted` event.
+const RECENT_CHANGES_MAX: usize = 20;
+
+/// Time between two comparisons of a set's replica with its repository. A
+/// finished replication makes the next one due at once.
+const REPLICA_CHECK_INTERVAL_SECS: i64 = 3600;
 
 /// How long to wait for restic mount process to exit gracefully after fusermount3 -u
 const MOUNT_GRACEFUL_EXIT_TIMEOUT_SECS: u64 = 2;
 
+/// Upper bound on files streamed by `Cat`, whatever the client asks for.
+pub const CAT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Most entries a `ListFiles` response carries; the rest are left out.
+pub const LIST_FILES_MAX_ENTRIES: usize = 100_000;
+
+/// Error returned when a file requested by `Cat` exceeds the size limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTooLarge {
+    pub size: u64,
+    pub limit: u64,
+}
+
+impl std::fmt::Display for FileTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "File is {}, above the {} limit. Use `vigil restore --path` for large files.",
+            humanize::format_size(self.size),
+            humanize::format_size(self.limit)
+        )
+    }
+}
+
+impl std::error::Error for FileTooLarge {}
+
+/// Error returned when pruning a set whose repository refuses deletions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppendOnlyRepo {
+    pub set_name: String,
+    /// Whether the daemon found out from a rejected prune rather than the config.
+    pub detected: bool,
+}
+
+impl std::fmt::Display for AppendOnlyRepo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Set '{}' uses an append-only repository, which refuses deletions. Retention cannot be applied from this machine; remove old snapshots on the server (e.g. `restic forget --prune` with direct access to the repository storage).",
+            self.set_name
+        )?;
+        if self.detected {
+            write!(
+                f,
+                " Add `append_only = true` to the set to stop prune attempts."
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AppendOnlyRepo {}
+
+/// Error returned when unmounting a snapshot that is not mounted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotMounted {
+    pub set_name: String,
+    pub snapshot: String,
+}
+
+impl std::fmt::Display for NotMounted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Snapshot '{}' of set '{}' is not mounted",
+            self.snapshot, self.set_name
+        )
+    }
+}
+
+impl std::error::Error for NotMounted {}
+
+/// Error returned when a repository operation would collide with a running backup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoBusy {
+    pub set_name: String,
+}
+
+impl std::fmt::Display for RepoBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "A backup is currently running against the repository of '{}'. Try again when it finishes.",
+            self.set_name
+        )
+    }
+}
+
+impl std::error::Error for RepoBusy {}
+
+/// Number of sets whose status is loaded at once during startup.
+const STATUS_INIT_CONCURRENCY: usize = 4;
+
+/// What the daemon remembers about a set's deep verifications.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VerificationRecord {
+    /// When the repository was last verified successfully.
+    verified_at: Option<DateTime<Utc>>,
+    /// The most recent verification, successful or not.
+    last_check: Option<VerifyOutcome>,
+}
+
+/// Reads a set's verification record; empty if it was never verified. Older
+/// versions stored only the time of the last success.
+fn load_verification(set_name: &str) -> VerificationRecord {
+    std::fs::read_to_string(vigil_lib::paths::verification_path(set_name))
+        .map(|content| parse_verification(&content))
+        .unwrap_or_default()
+}
+
+fn parse_verification(content: &str) -> VerificationRecord {
+    serde_json::from_str(content)
+        .or_else(|_| {
+            serde_json::from_str(content).map(|verified_at| VerificationRecord {
+                verified_at: Some(verified_at),
+                last_check: None,
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// Reads when a set's repository was last verified, if ever.
+fn load_last_verified(set_name: &str) -> Option<DateTime<Utc>> {
+    load_verification(set_name).verified_at
+}
+
+fn save_verification(set_name: &str, record: &VerificationRecord) -> Result<()> {
+    let file = vigil_lib::paths::verification_path(set_name);
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = file.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string(record)?)?;
+    std::fs::rename(&tmp, &file)?;
+    Ok(())
+}
+
+/// Whether a set needs a deep verification: never verified, or longer ago
+/// than `max_age_days`. A limit of 0 disables the warning.
+fn is_verification_stale(
+    last_verified: Option<DateTime<Utc>>,
+    max_age_days: u64,
+    now: DateTime<Utc>,
+) -> bool {
+    if max_age_days == 0 {
+        return false;
+    }
+    last_verified.is_none_or(|at| now - at > chrono::Duration::days(max_age_days as i64))
+}
+
+/// Minimum time between backup, restore or verify progress events.
+const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 pub struct JobManager {
     jobs: Arc<Mutex<HashMap<String, Job>>>,
-    executor: Arc<ResticExecutor>,
     /// Global retention policy for fallback when per-set retention is not specified.
     global_retention: Arc<Mutex<Option<RetentionPolicy>>>,
     /// Global debounce delay in seconds for fallback (atomic to avoid nested locks).
     global_debounce: Arc<AtomicU64>,
+    /// Global number of automatic retries of a failed backup.
+    global_max_retries: Arc<AtomicU32>,
+    /// Global delay in seconds before the first retry.
+    global_retry_backoff: Arc<AtomicU64>,
+    /// Whether stale repository locks are removed when they fail a backup.
+    global_auto_unlock: Arc<AtomicBool>,
+    /// Global mount prefetch depth for fallback when per-set depth is not specified.
+    global_prefetch_depth: Arc<Mutex<Option<u32>>>,
+    /// Seconds an unused mount stays up when a set has no timeout of its own;
+    /// 0 keeps mounts.
+    global_mount_idle_timeout: Arc<AtomicU64>,
+    /// Days before a set's last deep verification counts as stale; 0 disables
+    /// the warning (atomic so `get_status` can read it under the jobs lock).
+    global_verify_max_age: Arc<AtomicU64>,
+    /// Hours before `health` reports a set's last backup as stale; 0 disables
+    /// the check.
+    global_max_age_hours: Arc<AtomicU64>,
+    /// Hours a replica may trail its repository, for sets without a limit of
+    /// their own; 0 disables the comparison.
+    global_max_copy_lag_hours: Arc<AtomicU64>,
+    /// Global maintenance window for fallback when a set has none.
+    global_maintenance_window: Arc<std::sync::Mutex<Option<String>>>,
+    /// Quiet period in seconds before the notification digest is sent; 0
+    /// notifies per backup instead.
+    global_digest_secs: Arc<AtomicU64>,
+    /// Seconds running backups may take to finish once shutdown starts.
+    global_shutdown_grace_secs: Arc<AtomicU64>,
+    /// Seconds change-triggered backups stay paused after a restore into a
+    /// set's sources, for sets without a pause of their own.
+    global_restore_shield: Arc<AtomicU64>,
+    /// Backup outcomes waiting for the next digest notification.
+    digest: Arc<std::sync::Mutex<Digest>>,
+    /// Global notification policy; sets override single fields of it.
+    global_notifications: Arc<std::sync::Mutex<NotificationPolicy>>,
+    /// Global engine environment, passthrough list and throttle, copied into
+    /// each engine when it is built.
+    engine_defaults: Arc<std::sync::Mutex<EngineDefaults>>,
     /// Broadcast sender for async events (e.g. backup completion)
     event_tx: broadcast::Sender<Response>,
     /// Token to signal shutdown
     shutdown_token: CancellationToken,
+    /// Optional system tools detected at startup.
+    capabilities: Arc<Capabilities>,
+    /// Serializes read-modify-write cycles on the snapshot stats cache files.
+    stats_cache_lock: Arc<Mutex<()>>,
+    /// Names of sets with a prune (manual or automatic) or a deferred deep
+    /// check in flight.
+    active_prunes: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Names of sets copying snapshots to their replica, and whether another
+    /// backup finished meanwhile so the copy has to run again.
+    replications: Arc<std::sync::Mutex<HashMap<String, bool>>>,
+    /// Limits how many backups and prunes run at once.
+    job_slots: Arc<std::sync::Mutex<JobSlots>>,
+    /// Set once the socket is handed to a replacing daemon. That daemon owns
+    /// the state and journal files from then on, so this one stops writing
+    /// them and starts no new jobs.
+    handing_off: Arc<AtomicBool>,
+    /// PID of the daemon this one replaced while sets are held for its
+    /// backups, 0 otherwise. Only it may send their results.
+    replaced_pid: Arc<AtomicU32>,
+    /// Set once the daemon starts shutting down. Running backups may finish
+    /// during the shutdown grace period, but no new jobs start.
+    draining: Arc<AtomicBool>,
+    /// Purges announced with `PurgeToken` and not yet confirmed.
+    purge_tokens: Arc<std::sync::Mutex<PurgeTokens>>,
+    /// Repositories of sets removed from the config, by set name. Kept in
+    /// `state.json` until purged, so only known repositories can be purged.
+    untracked_targets: Arc<std::sync::Mutex<BTreeMap<String, String>>>,
+}
+
+/// Caps concurrent backups and prunes at `global.max_concurrent_jobs`. A new
+/// limit replaces the semaphore; jobs holding a slot of the old one keep it.
+struct JobSlots {
+    limit: Option<usize>,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl JobSlots {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            semaphore: limit.map(|n| Arc::new(Semaphore::new(n))),
+        }
+    }
+}
+
+/// Removes a set from `active_prunes` when its prune finishes, however it exits.
+struct PruneGuard {
+    active_prunes: Arc<std::sync::Mutex<HashSet<String>>>,
+    set_name: String,
+}
+
+impl Drop for PruneGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.active_prunes.lock() {
+            active.remove(&self.set_name);
+        }
+    }
+}
+
+/// Removes a set from `replications` when its replication finishes, however it exits.
+struct ReplicationGuard {
+    replications: Arc<std::sync::Mutex<HashMap<String, bool>>>,
+    set_name: String,
+}
+
+impl Drop for ReplicationGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.replications.lock() {
+            active.remove(&self.set_name);
+        }
+    }
+}
+
+impl Job {
+    fn is_append_only(&self) -> bool {
+        self.set.append_only || self.append_only_detected
+    }
+
+    /// Adds `paths` to the recently changed paths, starting over if a backup
+    /// began since the last change. A path seen again moves to the end; the
+    /// oldest are dropped past `RECENT_CHANGES_MAX`.
+    fn note_recent_changes(&mut self, paths: &[PathBuf]) {
+        if paths.is_empty() {
+            return;
+        }
+        if std::mem::take(&mut self.recent_changes_consumed) {
+            self.recent_changes.clear();
+        }
+        let newest = &paths[paths.len().saturating_sub(RECENT_CHANGES_MAX)..];
+        for path in newest {
+            self.recent_changes.retain(|p| p != path);
+            self.recent_changes.push(path.clone());
+        }
+        let excess = self.recent_changes.len().saturating_sub(RECENT_CHANGES_MAX);
+        self.recent_changes.drain(..excess);
+    }
+
+    /// The state to report at `now`. A debounce countdown is computed from the
+    /// deadline, rounded up so it reaches 0 only as the timer expires.
+    fn reported_state(&self, now: Instant) -> JobState {
+        match (&self.state, self.debounce_deadline) {
+            (JobState::Debouncing { .. }, Some(deadline)) => JobState::Debouncing {
+                remaining_secs: deadline
+                    .saturating_duration_since(now)
+                    .as_millis()
+                    .div_ceil(1000) as u64,
+            },
+            (state, _) => state.clone(),
+        }
+    }
+
+    /// Whether change-triggered backups wait for a restore into the set's
+    /// sources, or for the pause after one.
+    fn is_paused(&self, now: DateTime<Utc>) -> bool {
+        self.restores_in_place > 0 || self.paused_until.is_some_and(|until| now < until)
+    }
+
+    /// The `Paused` state for the current pause, open-ended while a restore runs.
+    fn paused_state(&self) -> JobState {
+        JobState::Paused {
+            until: self.paused_until.filter(|_| self.restores_in_place == 0),
+        }
+    }
+
+    /// Problems a monitoring check should hear about. Staleness is only
+    /// judged once the repository status has loaded, and only matters while
+    /// there is something to back up: pending changes or a schedule.
+    fn health_issues(&self, max_age_hours: u64, now: DateTime<Utc>) -> Vec<HealthIssue> {
+        let name = &self.set.name;
+        let issue = |kind, level, message: String| HealthIssue {
+            set_name: name.clone(),
+            kind,
+            level,
+            message,
+        };
+        let last_error = || {
+            self.last_backup
+                .as_ref()
+                .and_then(|b| b.error_message.clone())
+                .unwrap_or_else(|| "unknown error".to_string())
+        };
+        let mut issues = Vec::new();
+        match self.state {
+            JobState::Error => issues.push(issue(
+                HealthIssueKind::Failed,
+                HealthLevel::Failing,
+                format!("Last backup failed: {}", last_error()),
+            )),
+            JobState::Retrying { attempt, .. } => issues.push(issue(
+                HealthIssueKind::Retrying,
+                HealthLevel::Degraded,
+                format!("Backup failed, retry {} pending: {}", attempt, last_error()),
+            )),
+            _ => {}
+        }
+        if self.repo_unreachable {
+            issues.push(issue(
+                HealthIssueKind::Unreachable,
+                HealthLevel::Failing,
+                format!("Repository {} cannot be reached", self.set.target),
+            ));
+        }
+        let pending = self.journal.len()
+            + self
+                .journal_in_flight
+                .as_ref()
+                .map_or(0, ChangeJournal::len);
+        if max_age_hours > 0 && self.status_loaded && (pending > 0 || self.set.schedule.is_some()) {
+            let max_age = chrono::Duration::hours(max_age_hours as i64);
+            let last_success = self
+                .last_backup
+                .as_ref()
+                .filter(|b| b.success)
+                .map(|b| b.timestamp);
+            match last_success {
+                Some(at) if now - at <= max_age => {}
+                Some(at) => issues.push(issue(
+                    HealthIssueKind::Stale,
+                    HealthLevel::Degraded,
+                    format!(
+                        "Last successful backup was {} (limit {}h)",
+                        humanize::format_relative(now - at),
+                        max_age_hours
+                    ),
+                )),
+                // A failed last attempt is already reported above
+                None if self.last_backup.is_none() => issues.push(issue(
+                    HealthIssueKind::Stale,
+                    HealthLevel::Degraded,
+                    "Never backed up".to_string(),
+                )),
+                None => {}
+            }
+        }
+        if let Some(lag) = &self.replica_lag {
+            let replica = self.set.replica_target.as_deref().unwrap_or_default();
+            if let Some(error) = &lag.error {
+                issues.push(issue(
+                    HealthIssueKind::ReplicaLag,
+                    HealthLevel::Degraded,
+                    format!("Replica {} could not be compared: {}", replica, error),
+                ));
+            } else if lag.behind {
+                issues.push(issue(
+                    HealthIssueKind::ReplicaLag,
+                    HealthLevel::Degraded,
+                    format!(
+                        "Replica {} lacks {} snapshot(s), the oldest from {}",
+                        replica,
+                        lag.missing_snapshots,
+                        humanize::format_relative(chrono::Duration::seconds(lag.lag_secs as i64))
+                    ),
+                ));
+            }
+        }
+        issues
+    }
+}
+
+/// A snapshot mounted for browsing.
+struct ActiveMount {
+    path: PathBuf,
+    /// The engine's mount process; `None` for a mount found after a restart.
+    process: Option<tokio::process::Child>,
+    /// Cancels the background cache prefetch while it is still walking the mount.
+    prefetch: Option<CancellationToken>,
+    /// When the mount was last seen in use, or was mounted.
+    used_at: Option<Instant>,
+}
+
+impl ActiveMount {
+    /// A mount found on the filesystem that this daemon did not start.
+    fn adopted(path: PathBuf) -> Self {
+        Self {
+            path,
+            process: None,
+            prefetch: None,
+            used_at: None,
+        }
+    }
 }
 
 struct Job {
     set: BackupSet,
     state: JobState,
     last_change: Option<Instant>,
+    /// When the debounce timer expires; every change pushes it back. The
+    /// remaining time reported while `Debouncing` is computed from it.
+    debounce_deadline: Option<Instant>,
     last_backup: Option<BackupResult>,
-    is_mounted: bool,
     immediate_trigger: bool,
-    mount_process: Option<tokio::process::Child>,
+    /// Mounted snapshots, keyed by short snapshot ID or `latest`.
+    mounts: BTreeMap<String, ActiveMount>,
+    /// Paths changed since the last successful backup.
+    journal: ChangeJournal,
+    /// Journal entries the running backup is capturing; merged back if it fails.
+    journal_in_flight: Option<ChangeJournal>,
     snapshot_count: Option<usize>,
     total_bytes: Option<u64>,
+    /// Whether the repository status has been loaded at least once.
+    status_loaded: bool,
+    /// When the set's schedule next starts a backup.
+    next_run: Option<DateTime<Utc>>,
+    /// When `check --deep` last verified the repository successfully.
+    last_verified: Option<DateTime<Utc>>,
+    /// Extra tags requested for the next backup run, on top of the set's own.
+    run_tags: Vec<String>,
+    /// Exclude patterns the next backup run ignores, for a rescue backup.
+    run_lifted_excludes: Vec<String>,
+    /// Whether the repository rejected a prune's deletions, so it is treated
+    /// as append-only until the daemon restarts.
+    append_only_detected: bool,
+    /// Whether the last status refresh could not reach the repository.
+    repo_unreachable: bool,
+    /// Automatic retries made since the last successful backup.
+    retry_attempt: u32,
+    /// When the next automatic retry starts, while `Retrying`.
+    retry_at: Option<Instant>,
+    /// When the last debounced backup was skipped for falling below the
+    /// set's min-change threshold. Cleared when a backup starts.
+    skipped_trivial: Option<DateTime<Utc>>,
+    /// Free space on the target filesystem, as of the last status refresh.
+    target_free_bytes: Option<u64>,
+    /// Average daily repository growth over the last week of history.
+    growth_bytes_per_day: Option<u64>,
+    /// Whether an automatic prune is queued for the maintenance task. It waits
+    /// for the maintenance window and for the repository to be free.
+    prune_pending: bool,
+    /// Share of the data a deep check queued for the maintenance window reads.
+    verify_pending: Option<String>,
+    /// Whether a backup was cancelled by shutdown before it finished. Kept in
+    /// `state.json` so the next daemon start runs it again.
+    interrupted: bool,
+    /// Whether the set's backup still runs in the daemon this one replaced.
+    /// The set shows as running until that daemon sends the result or exits.
+    handed_over: bool,
     worker_active: bool,
+    /// Watcher events folded into another for the same path since the daemon started.
+    watch_coalesced: u64,
+    /// Watcher events past the per-tick path limit since the daemon started.
+    watch_dropped: u64,
+    /// Latest paths changed in the current debounce cycle, oldest first.
+    recent_changes: Vec<PathBuf>,
+    /// Whether a backup started since `recent_changes` was last added to. The
+    /// next change then begins a new cycle.
+    recent_changes_consumed: bool,
+    /// Restores into the set's sources that are still running.
+    restores_in_place: u32,
+    /// When the pause of change-triggered backups after the last restore into
+    /// the set's sources ends.
+    paused_until: Option<DateTime<Utc>>,
+    /// The last comparison of the set's replica with its repository.
+    replica_lag: Option<ReplicaLag>,
+    /// When the replica is compared next; due at once when unset.
+    replica_next_check: Option<DateTime<Utc>>,
 }
 
 impl JobManager {
@@ -52,24 +586,96 @@ impl JobManager {
                     set: set.clone(),
                     state: JobState::Idle,
                     last_change: None,
+                    debounce_deadline: None,
                     last_backup: None,
-                    is_mounted: false,
+                    journal: ChangeJournal::load(&vigil_lib::paths::change_journal_path(&set.name)),
+                    journal_in_flight: None,
                     immediate_trigger: false,
-                    mount_process: None,
+                    mounts: BTreeMap::new(),
                     snapshot_count: None,
                     total_bytes: None,
+                    status_loaded: false,
+                    next_run: None,
+                    last_verified: load_last_verified(&set.name),
+                    run_tags: Vec::new(),
+                    run_lifted_excludes: Vec::new(),
+                    append_only_detected: false,
+                    repo_unreachable: false,
+                    retry_attempt: 0,
+                    retry_at: None,
+                    skipped_trivial: None,
+                    target_free_bytes: None,
+                    growth_bytes_per_day: None,
+                    prune_pending: false,
+                    verify_pending: None,
+                    interrupted: false,
+                    handed_over: false,
                     worker_active: false,
+                    watch_coalesced: 0,
+                    watch_dropped: 0,
+                    recent_changes: Vec::new(),
+                    recent_changes_consumed: false,
+                    restores_in_place: 0,
+                    paused_until: None,
+                    replica_lag: None,
+                    replica_next_check: None,
                 },
             );
         }
         let (event_tx, _) = broadcast::channel(100);
+        let capabilities = Capabilities::probe();
+        executor::set_ionice_available(capabilities.has(Capability::IoPriority));
         Self {
             jobs: Arc::new(Mutex::new(jobs)),
-            executor: Arc::new(ResticExecutor::new()),
             global_retention: Arc::new(Mutex::new(config.global.retention.clone())),
             global_debounce: Arc::new(AtomicU64::new(config.global.debounce_seconds)),
+            global_max_retries: Arc::new(AtomicU32::new(config.global.max_retries)),
+            global_retry_backoff: Arc::new(AtomicU64::new(config.global.retry_backoff_secs)),
+            global_auto_unlock: Arc::new(AtomicBool::new(config.global.auto_unlock)),
+            global_prefetch_depth: Arc::new(Mutex::new(config.global.mount_prefetch_depth)),
+            global_verify_max_age: Arc::new(AtomicU64::new(
+                config.global.verify_max_age_days.unwrap_or(0).into(),
+            )),
+            global_max_age_hours: Arc::new(AtomicU64::new(
+                config.global.max_age_hours.unwrap_or(0),
+            )),
+            global_max_copy_lag_hours: Arc::new(AtomicU64::new(
+                config.global.max_copy_lag_hours.unwrap_or(0),
+            )),
+            global_mount_idle_timeout: Arc::new(AtomicU64::new(
+                config.global.mount_idle_timeout_secs.unwrap_or(0),
+            )),
+            global_maintenance_window: Arc::new(std::sync::Mutex::new(
+                config.global.maintenance_window.clone(),
+            )),
+            global_digest_secs: Arc::new(AtomicU64::new(
+                config.global.notification_digest_secs.unwrap_or(0),
+            )),
+            global_shutdown_grace_secs: Arc::new(AtomicU64::new(
+                config.global.shutdown_grace_secs.unwrap_or(0),
+            )),
+            global_restore_shield: Arc::new(AtomicU64::new(config.global.restore_shield_secs)),
+            digest: Arc::new(std::sync::Mutex::new(Digest::default())),
+            global_notifications: Arc::new(std::sync::Mutex::new(
+                config.global.notifications.clone(),
+            )),
+            engine_defaults: Arc::new(std::sync::Mutex::new(EngineDefaults::from_config(
+                &config.global,
+            ))),
             event_tx,
             shutdown_token,
+            capabilities: Arc::new(capabilities),
+            stats_cache_lock: Arc::new(Mutex::new(())),
+            active_prunes: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            replications: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            job_slots: Arc::new(std::sync::Mutex::new(JobSlots::new(
+                config.global.max_concurrent_jobs,
+            ))),
+            handing_off: Arc::new(AtomicBool::new(false)),
+            replaced_pid: Arc::new(AtomicU32::new(0)),
+            draining: Arc::new(AtomicBool::new(false)),
+            purge_tokens: Arc::new(std::sync::Mutex::new(PurgeTokens::default())),
+            untracked_targets: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
         }
     }
 
@@ -77,49 +683,320 @@ impl JobManager {
         self.event_tx.subscribe()
     }
 
+    /// Returns the sorted names of sets with a backup or prune in flight.
+    pub async fn running_jobs(&self) -> Vec<String> {
+        let mut running: Vec<String> = self
+            .jobs
+            .lock()
+            .await
+            .values()
+            .filter(|job| matches!(job.state, JobState::Running { .. }))
+            .map(|job| job.set.name.clone())
+            .collect();
+        if let Ok(active) = self.active_prunes.lock() {
+            running.extend(active.iter().cloned());
+        }
+        running.sort();
+        running.dedup();
+        running
+    }
+
+    /// Marks a set as pruning until the returned guard is dropped.
+    fn track_prune(&self, set_name: &str) -> PruneGuard {
+        if let Ok(mut active) = self.active_prunes.lock() {
+            active.insert(set_name.to_string());
+        }
+        PruneGuard {
+            active_prunes: self.active_prunes.clone(),
+            set_name: set_name.to_string(),
+        }
+    }
+
+    /// Marks a set as replicating until the returned guard is dropped. `None`
+    /// if it already is; with `again`, that replication then runs once more.
+    fn track_replication(&self, set_name: &str, again: bool) -> Option<ReplicationGuard> {
+        let mut active = self.replications.lock().ok()?;
+        if let Some(rerun) = active.get_mut(set_name) {
+            *rerun |= again;
+            return None;
+        }
+        active.insert(set_name.to_string(), false);
+        Some(ReplicationGuard {
+            replications: self.replications.clone(),
+            set_name: set_name.to_string(),
+        })
+    }
+
+    /// Whether a replication of `set_name` was asked for while one ran,
+    /// clearing the request.
+    fn take_replication_rerun(&self, set_name: &str) -> bool {
+        self.replications
+            .lock()
+            .ok()
+            .and_then(|mut active| active.get_mut(set_name).map(std::mem::take))
+            .unwrap_or(false)
+    }
+
+    /// Waits for a free job slot when `max_concurrent_jobs` is set; the slot is
+    /// released when the permit is dropped. Returns `None` at once without a
+    /// limit, and when the daemon shuts down while waiting.
+    async fn acquire_job_slot(&self, set_name: &str) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self.job_slots.lock().ok()?.semaphore.clone()?;
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+        info!("Set {} is waiting for a free job slot", set_name);
+        tokio::select! {
+            permit = semaphore.acquire_owned() => permit.ok(),
+            _ = self.shutdown_token.cancelled() => None,
+        }
+    }
+
+    /// Returns the optional system tools detected at startup.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Notifies about `event` of `set`, if the set's notification policy
+    /// enables it.
+    fn notify(
+        &self,
+        set: &BackupSet,
+        event: NotifyEvent,
+        title: &str,
+        body: &str,
+        icon: &'static str,
+    ) {
+        self.deliver(
+            Some(set),
+            Notification {
+                event: Some(event),
+                set_name: Some(set.name.clone()),
+                title: title.to_string(),
+                body: body.to_string(),
+                icon,
+            },
+        );
+    }
+
+    /// Sends `notification` through the backend of `set`'s notification
+    /// policy, or of the global one without a set, and runs the set's
+    /// `notify_command`. Nothing is sent while shutting down or when the
+    /// policy leaves out the notification's event.
+    fn deliver(&self, set: Option<&BackupSet>, notification: Notification) {
+        if self.shutdown_token.is_cancelled() {
+            return;
+        }
+        let global = self
+            .global_notifications
+            .lock()
+            .map(|policy| policy.clone())
+            .unwrap_or_default();
+        let policy = match set {
+            Some(set) => set.notifications.or(&global),
+            None => global,
+        };
+        if let Some(event) = notification.event {
+            if !policy.notifies(event) {
+                debug!(
+                    "Not notifying {} event (disabled): {}",
+                    event, notification.title
+                );
+                return;
+            }
+        }
+        notifier::for_policy(&policy, &self.capabilities).send(&notification);
+        if let Some(template) = set.and_then(|set| set.notify_command.as_deref()) {
+            notifier::TemplateNotifier::new(template).send(&notification);
+        }
+    }
+
+    /// Adds a successful backup to the notification digest. Returns false when
+    /// digests are disabled and the success should be notified on its own.
+    fn record_digest_success(&self, set_name: &str, added_bytes: u64) -> bool {
+        if self.global_digest_secs.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        if let Ok(mut digest) = self.digest.lock() {
+            digest.record_success(set_name, added_bytes, Instant::now());
+        }
+        true
+    }
+
+    /// Adds a failed backup to the notification digest. Returns false when
+    /// digests are disabled and the failure should be notified on its own.
+    fn record_digest_failure(&self, set_name: &str) -> bool {
+        if self.global_digest_secs.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        if let Ok(mut digest) = self.digest.lock() {
+            digest.record_failure(set_name, Instant::now());
+        }
+        true
+    }
+
+    /// Sends the digest notification once the current burst of backups has
+    /// been quiet for the configured period and nothing is running.
+    pub async fn flush_digest(&self, now: Instant) {
+        let quiet = Duration::from_secs(self.global_digest_secs.load(Ordering::Relaxed));
+        let busy = !self.running_jobs().await.is_empty();
+        let summary = match self.digest.lock() {
+            Ok(mut digest) => digest.take_if_quiet(now, quiet, busy),
+            Err(_) => None,
+        };
+        if let Some(summary) = summary {
+            info!("Backup digest: {}", summary.body());
+            self.deliver(
+                None,
+                Notification {
+                    event: None,
+                    set_name: None,
+                    title: summary.title().to_string(),
+                    body: summary.body(),
+                    icon: summary.icon(),
+                },
+            );
+        }
+    }
+
     /// Queries restic for the latest snapshot of each backup set and populates `last_backup`.
     /// This should be called on daemon startup.
+    ///
+    /// Up to `STATUS_INIT_CONCURRENCY` sets are refreshed at once. Sets report
+    /// `initializing` until their refresh finishes, and `StatusInitialized` is
+    /// broadcast once all have.
     pub async fn initialize_status(&self) {
+        let started = Instant::now();
         let names: Vec<String> = {
             let jobs = self.jobs.lock().await;
             jobs.keys().cloned().collect()
         };
 
+        let permits = Arc::new(Semaphore::new(STATUS_INIT_CONCURRENCY));
+        let mut refreshes = JoinSet::new();
+        let count = names.len();
         for name in names {
-            self.refresh_set_status(&name).await;
+            let manager = self.clone();
+            let permits = permits.clone();
+            refreshes.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                manager.refresh_set_status(&name).await;
+            });
         }
+        while refreshes.join_next().await.is_some() {}
+
+        let duration_secs = started.elapsed().as_secs_f64();
+        info!(
+            "Loaded status of {} set(s) in {}",
+            count,
+            humanize::format_secs(duration_secs)
+        );
+        let _ = self
+            .event_tx
+            .send(Response::Event(ResponseData::StatusInitialized {
+                sets: count,
+                duration_secs,
+            }));
     }
 
     /// Refresh status for a specific backup set by querying restic and calculating repo size.
     /// All I/O is performed outside the lock; results are applied under the lock.
     async fn refresh_set_status(&self, set_name: &str) {
-        let target = {
+        let set = {
             let jobs = self.jobs.lock().await;
             match jobs.get(set_name) {
-                Some(j) => j.set.target.clone(),
+                Some(j) => j.set.clone(),
                 None => return,
             }
         };
+        let engine = self.engine(&set);
 
         debug!("Refreshing status for backup set '{}'", set_name);
 
         // Query all snapshots in a single call (no limit) so we get both latest info and total count
-        let snapshots_res = self
-            .executor
-            .snapshots(&target, None, Some(self.shutdown_token.clone()))
+        let snapshots_res = engine
+            .snapshots(&set.target, None, Some(self.shutdown_token.clone()))
             .await;
 
-        let size_res = Self::calculate_dir_size(std::path::Path::new(&target)).await;
-        let is_mounted_res =
-            vigil_lib::paths::is_mount_point(&vigil_lib::paths::mount_path(set_name));
+        // The engine counts the stored data exactly; walking the directory is
+        // the fallback for local repositories it cannot measure
+        let size_res = match engine
+            .repo_size(&set.target, Some(self.shutdown_token.clone()))
+            .await
+        {
+            Ok(size) => Ok(Some(size)),
+            Err(e) => {
+                debug!("Failed to measure repository of '{}': {}", set_name, e);
+                if set.backend().is_remote() {
+                    Ok(None)
+                } else {
+                    Self::calculate_dir_size(std::path::Path::new(&set.target)).await
+                }
+            }
+        };
+        let detected_mounts = detect_mounts(set_name);
+        let free_bytes = if set.backend().is_remote() {
+            None
+        } else {
+            free_space(Path::new(&set.target))
+        };
+        let history_path = vigil_lib::paths::history_path(set_name);
+        let growth = tokio::task::spawn_blocking(move || {
+            history::growth_per_day(&history::read(&history_path, None), Utc::now())
+        })
+        .await
+        .unwrap_or(None);
+
+        // Drop cached stats for snapshots that no longer exist (e.g. after prune)
+        if let Ok(ref snapshots) = snapshots_res {
+            let _guard = self.stats_cache_lock.lock().await;
+            let mut cache =
+                SnapshotStatsCache::load(&vigil_lib::paths::snapshot_stats_cache_path(set_name));
+            let ids = snapshots.iter().map(|s| s.id.as_str()).collect();
+            if cache.retain_ids(&ids) {
+                if let Err(e) = cache.save() {
+                    warn!(
+                        "Failed to save snapshot stats cache for '{}': {}",
+                        set_name, e
+                    );
+                }
+            }
+        }
 
         // Apply results under the lock
         let mut jobs = self.jobs.lock().await;
         if let Some(job) = jobs.get_mut(set_name) {
+            job.status_loaded = true;
             match snapshots_res {
                 Ok(snapshots) => {
+                    if std::mem::take(&mut job.repo_unreachable) {
+                        info!("Repository for set '{}' is reachable again", set_name);
+                    }
+                    if !job.set.shared_repo {
+                        let local = executor::local_hostname();
+                        let foreign: Vec<String> = group_by_host(&snapshots, &local)
+                            .into_iter()
+                            .filter(|h| !h.is_local)
+                            .map(|h| h.hostname)
+                            .collect();
+                        if !foreign.is_empty() {
+                            warn!(
+                                "Repository for set '{}' contains snapshots from other hosts ({}). Set `shared_repo = true` if it is shared.",
+                                set_name,
+                                foreign.join(", ")
+                            );
+                        }
+                    }
                     job.snapshot_count = Some(snapshots.len());
-                    if let Some(latest) = snapshots.last() {
+                    let failed_since_latest = job.last_backup.as_ref().is_some_and(|current| {
+                        !current.success
+                            && snapshots
+                                .last()
+                                .is_none_or(|latest| current.timestamp > latest.timestamp)
+                    });
+                    if failed_since_latest {
+                        // Keep the failed attempt and its error; it is newer than every snapshot
+                    } else if let Some(latest) = snapshots.last() {
                         let mut new_result = BackupResult {
                             snapshot_id: latest.short_id.clone(),
                             timestamp: latest.timestamp,
@@ -127,6 +1004,9 @@ impl JobManager {
                             duration_secs: 0.0,
                             success: true,
                             error_message: None,
+                            peak_rss_bytes: None,
+                            cpu_secs: None,
+                            hooks: Vec::new(),
                         };
 
                         // If this is the same snapshot as we already have, preserve the metrics
@@ -134,6 +1014,9 @@ impl JobManager {
                             if current.snapshot_id == latest.short_id {
                                 new_result.added_bytes = current.added_bytes;
                                 new_result.duration_secs = current.duration_secs;
+                                new_result.peak_rss_bytes = current.peak_rss_bytes;
+                                new_result.cpu_secs = current.cpu_secs;
+                                new_result.hooks = current.hooks.clone();
                             }
                         }
                         job.last_backup = Some(new_result);
@@ -144,6 +1027,23 @@ impl JobManager {
                 Err(e) => {
                     warn!("Failed to query snapshots for '{}': {}", set_name, e);
                     let err_str = e.to_string();
+                    // Only repositories that have held snapshots can become
+                    // unreachable; a set that was never backed up just lacks one
+                    let had_snapshots =
+                        job.last_backup.is_some() || job.snapshot_count.is_some_and(|n| n > 0);
+                    if !job.repo_unreachable && had_snapshots {
+                        self.notify(
+                            &job.set,
+                            NotifyEvent::Unreachable,
+                            "Repository Unreachable",
+                            &format!(
+                                "The repository of '{}' at {} cannot be reached.",
+                                set_name, job.set.target
+                            ),
+                            "dialog-warning",
+                        );
+                    }
+                    job.repo_unreachable = true;
                     if err_str.contains("repository does not exist")
                         || err_str.contains("no such file or directory")
                         || err_str.contains("failed to execute restic")
@@ -158,22 +1058,26 @@ impl JobManager {
                 Ok(size_opt) => job.total_bytes = size_opt,
                 Err(e) => warn!("Failed to calculate repo size for '{}': {}", set_name, e),
             }
+            job.target_free_bytes = free_bytes;
+            job.growth_bytes_per_day = growth;
 
             // Update mount status from filesystem
-            if is_mounted_res {
-                if !job.is_mounted {
+            for (key, path) in &detected_mounts {
+                if !job.mounts.contains_key(key) {
                     info!(
-                        "Detected existing mount for set '{}', updating state",
-                        set_name
+                        "Detected existing mount of snapshot {} for set '{}', updating state",
+                        key, set_name
                     );
-                    job.is_mounted = true;
+                    job.mounts
+                        .insert(key.clone(), ActiveMount::adopted(path.clone()));
                 }
-            } else if job.is_mounted && job.mount_process.is_none() {
-                // If we thought it was mounted but there's no process and no actual mount, clear it
-                debug!("Set '{}' reported as mounted but no mount detected on filesystem, clearing state", set_name);
-                job.is_mounted = false;
             }
+            // A mount we track without a process and that is not on the filesystem is gone
+            job.mounts.retain(|key, mount| {
+                mount.process.is_some() || detected_mounts.iter().any(|(k, _)| k == key)
+            });
         }
+        self.save_state(&jobs);
     }
 
     pub async fn sync_config(&self, config: &Config) -> Result<()> {
@@ -190,14 +1094,24 @@ impl JobManager {
                 .cloned()
                 .collect();
 
+            let any_removed = !removed_set_names.is_empty();
             for name in removed_set_names {
                 info!("Backup set '{}' removed from config, cleaning up...", name);
                 if let Some(mut job) = jobs.remove(&name) {
                     // Unmount if mounted
-                    if let Err(e) = Self::perform_unmount(&name, &mut job).await {
-                        error!("Failed to unmount removed set '{}': {}", name, e);
+                    Self::perform_unmount(&name, &mut job).await;
+                    // Remembered so `vigil purge` can still delete it
+                    if let Ok(mut untracked) = self.untracked_targets.lock() {
+                        untracked.insert(name.clone(), job.set.target);
                     }
                 }
+                let _ = std::fs::remove_file(vigil_lib::paths::change_journal_path(&name));
+                history::remove(&vigil_lib::paths::history_path(&name));
+                history::remove(&vigil_lib::paths::restore_history_path(&name));
+                let _ = std::fs::remove_dir_all(vigil_lib::paths::run_log_dir(&name));
+            }
+            if any_removed {
+                self.save_state(&jobs);
             }
 
             // 2. Add or update remaining sets
@@ -213,25 +1127,63 @@ impl JobManager {
                         job.snapshot_count = None;
                         job.total_bytes = None;
                     }
+                    if job.set.schedule != set.schedule {
+                        job.next_run = None;
+                    }
+                    if job.set.replica_target != set.replica_target {
+                        job.replica_lag = None;
+                        job.replica_next_check = None;
+                    }
                     // Update existing job config
                     debug!("Updating config for backup set '{}'", set.name);
                     job.set = set.clone();
                 } else {
                     // Add new job
                     info!("New backup set '{}' added to config", set.name);
+                    if let Ok(mut untracked) = self.untracked_targets.lock() {
+                        untracked.remove(&set.name);
+                    }
                     jobs.insert(
                         set.name.clone(),
                         Job {
                             set: set.clone(),
                             state: JobState::Idle,
                             last_change: None,
+                            debounce_deadline: None,
                             last_backup: None,
-                            is_mounted: false,
+                            journal: ChangeJournal::load(&vigil_lib::paths::change_journal_path(
+                                &set.name,
+                            )),
+                            journal_in_flight: None,
                             immediate_trigger: false,
-                            mount_process: None,
+                            mounts: BTreeMap::new(),
                             snapshot_count: None,
                             total_bytes: None,
+                            status_loaded: false,
+                            next_run: None,
+                            last_verified: load_last_verified(&set.name),
+                            run_tags: Vec::new(),
+                            run_lifted_excludes: Vec::new(),
+                            append_only_detected: false,
+                            repo_unreachable: false,
+                            retry_attempt: 0,
+                            retry_at: None,
+                            skipped_trivial: None,
+                            target_free_bytes: None,
+                            growth_bytes_per_day: None,
+                            prune_pending: false,
+                            verify_pending: None,
+                            interrupted: false,
+                            handed_over: false,
                             worker_active: false,
+                            watch_coalesced: 0,
+                            watch_dropped: 0,
+                            recent_changes: Vec::new(),
+                            recent_changes_consumed: false,
+                            restores_in_place: 0,
+                            paused_until: None,
+                            replica_lag: None,
+                            replica_next_check: None,
                         },
                     );
                 }
@@ -245,6 +1197,51 @@ impl JobManager {
             *global_retention = config.global.retention.clone();
             self.global_debounce
                 .store(config.global.debounce_seconds, Ordering::Relaxed);
+            self.global_max_retries
+                .store(config.global.max_retries, Ordering::Relaxed);
+            self.global_retry_backoff
+                .store(config.global.retry_backoff_secs, Ordering::Relaxed);
+            self.global_auto_unlock
+                .store(config.global.auto_unlock, Ordering::Relaxed);
+            *self.global_prefetch_depth.lock().await = config.global.mount_prefetch_depth;
+            self.global_verify_max_age.store(
+                config.global.verify_max_age_days.unwrap_or(0).into(),
+                Ordering::Relaxed,
+            );
+            self.global_max_age_hours
+                .store(config.global.max_age_hours.unwrap_or(0), Ordering::Relaxed);
+            self.global_max_copy_lag_hours.store(
+                config.global.max_copy_lag_hours.unwrap_or(0),
+                Ordering::Relaxed,
+            );
+            self.global_mount_idle_timeout.store(
+                config.global.mount_idle_timeout_secs.unwrap_or(0),
+                Ordering::Relaxed,
+            );
+            if let Ok(mut window) = self.global_maintenance_window.lock() {
+                window.clone_from(&config.global.maintenance_window);
+            }
+            self.global_digest_secs.store(
+                config.global.notification_digest_secs.unwrap_or(0),
+                Ordering::Relaxed,
+            );
+            self.global_shutdown_grace_secs.store(
+                config.global.shutdown_grace_secs.unwrap_or(0),
+                Ordering::Relaxed,
+            );
+            self.global_restore_shield
+                .store(config.global.restore_shield_secs, Ordering::Relaxed);
+            if let Ok(mut policy) = self.global_notifications.lock() {
+                *policy = config.global.notifications.clone();
+            }
+            if let Ok(mut slots) = self.job_slots.lock() {
+                if slots.limit != config.global.max_concurrent_jobs {
+                    *slots = JobSlots::new(config.global.max_concurrent_jobs);
+                }
+            }
+            if let Ok(mut defaults) = self.engine_defaults.lock() {
+                *defaults = EngineDefaults::from_config(&config.global);
+            }
         }
 
         // Trigger background refresh for new/changed sets
@@ -276,578 +1273,3441 @@ impl JobManager {
         }
     }
 
-    pub async fn handle_file_change(&self, set_name: &str) -> Result<()> {
+    pub async fn handle_file_change(&self, set_name: &str, path: &Path) -> Result<()> {
+        let batch = ChangeBatch {
+            paths: vec![path.to_path_buf()],
+            ..Default::default()
+        };
+        self.handle_file_changes(set_name, &batch).await
+    }
+
+    /// Records the changes the watcher gathered for a set over one tick and
+    /// restarts its debounce timer once.
+    pub async fn handle_file_changes(&self, set_name: &str, batch: &ChangeBatch) -> Result<()> {
         let mut jobs = self.jobs.lock().await;
         if let Some(job) = jobs.get_mut(set_name) {
-            let now = Instant::now();
-            job.last_change = Some(now);
-
-            match job.state {
-                JobState::Idle | JobState::Error => {
-                    let debounce_secs = job
-                        .set
-                        .debounce_seconds
-                        .unwrap_or(self.global_debounce.load(Ordering::Relaxed));
-                    job.state = JobState::Debouncing {
-                        remaining_secs: debounce_secs,
-                    };
-                    if !job.worker_active {
-                        job.worker_active = true;
-                        let manager = self.clone();
-                        let set_name_owned = set_name.to_string();
-
-                        tokio::spawn(async move {
-                            Self::job_worker(manager, set_name_owned).await;
-                        });
-                    }
-                }
-                JobState::Debouncing { .. } => {
-                    debug!("Set {} is already debouncing, timer reset", set_name);
-                    // Timer will be automatically reset because we updated last_change
-                }
-                JobState::Running => {
-                    debug!(
-                        "Set {} is currently running, will re-debounce after completion",
-                        set_name
-                    );
-                    // When the current backup finishes, it will check last_change
-                }
+            for path in &batch.paths {
+                job.journal.record(path);
+            }
+            job.journal.record_unlisted(batch.dropped);
+            job.watch_coalesced += batch.coalesced;
+            job.watch_dropped += batch.dropped;
+            job.note_recent_changes(&batch.paths);
+            if let Err(e) = job.journal.save_if_due(job.journal_in_flight.as_ref()) {
+                warn!("Failed to save change journal for set {}: {}", set_name, e);
             }
+            self.schedule_debounce(set_name, job);
             Ok(())
         } else {
             anyhow::bail!("Unknown backup set: {}", set_name)
         }
     }
 
-    pub async fn trigger_backup(&self, set_name: &str) -> Result<()> {
+    /// Loads the results saved in `state.json` before the last shutdown, so
+    /// metrics and failures are shown while the repositories are queried again.
+    /// A set whose last backup failed starts in the error state.
+    pub async fn restore_state(&self) {
+        let saved = DaemonState::load(&vigil_lib::paths::daemon_state_path());
         let mut jobs = self.jobs.lock().await;
-        if let Some(job) = jobs.get_mut(set_name) {
-            match job.state {
-                JobState::Running => {
-                    anyhow::bail!("Backup for set {} is already running", set_name);
-                }
-                JobState::Debouncing { .. } => {
-                    job.immediate_trigger = true;
-                    info!(
-                        "Immediate backup triggered for set {} (was debouncing)",
-                        set_name
-                    );
+        if let Ok(mut untracked) = self.untracked_targets.lock() {
+            for (name, set) in &saved.sets {
+                if let Some(target) = set.target.as_ref().filter(|_| !jobs.contains_key(name)) {
+                    untracked.insert(name.clone(), target.clone());
                 }
-                JobState::Idle | JobState::Error => {
-                    job.state = JobState::Running; // Set to running immediately
-                    info!("Immediate backup triggered for set {}", set_name);
+            }
+        }
+        for (name, job) in jobs.iter_mut() {
+            let Some(saved) = saved.sets.get(name) else {
+                continue;
+            };
+            // Maintenance deferred before the restart still waits for its window
+            job.prune_pending |= saved.prune_pending;
+            if job.verify_pending.is_none() {
+                job.verify_pending.clone_from(&saved.verify_pending);
+            }
+            if job.paused_until.is_none() {
+                job.paused_until = saved.paused_until.filter(|until| *until > Utc::now());
+            }
+            if job.status_loaded || job.last_backup.is_some() {
+                continue;
+            }
+            job.last_backup = saved.last_backup.clone();
+            job.snapshot_count = saved.snapshot_count;
+            job.total_bytes = saved.total_bytes;
+            job.interrupted = saved.interrupted;
+            if saved.last_backup.as_ref().is_some_and(|b| !b.success) && job.state == JobState::Idle
+            {
+                job.state = JobState::Error;
+            }
+        }
+    }
 
-                    if !job.worker_active {
-                        job.worker_active = true;
-                        let manager = self.clone();
-                        let set_name_owned = set_name.to_string();
+    /// Writes every set's results to `state.json`, and the repositories of
+    /// sets removed from the config. Failures are logged; the state is
+    /// rebuilt from the repositories if it is lost.
+    fn save_state(&self, jobs: &HashMap<String, Job>) {
+        let mut sets: BTreeMap<String, SetState> = self
+            .untracked_targets
+            .lock()
+            .map(|untracked| {
+                untracked
+                    .iter()
+                    .map(|(name, target)| {
+                        (
+                            name.clone(),
+                            SetState {
+                                target: Some(target.clone()),
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        sets.extend(jobs.iter().map(|(name, job)| {
+            (
+                name.clone(),
+                SetState {
+                    target: Some(job.set.target.clone()),
+                    last_backup: job.last_backup.clone(),
+                    snapshot_count: job.snapshot_count,
+                    total_bytes: job.total_bytes,
+                    interrupted: job.interrupted,
+                    prune_pending: job.prune_pending,
+                    verify_pending: job.verify_pending.clone(),
+                    paused_until: job.paused_until,
+                },
+            )
+        }));
+        let state = DaemonState { sets };
+        if let Err(e) = state.save(&vigil_lib::paths::daemon_state_path()) {
+            warn!("Failed to save daemon state: {:#}", e);
+        }
+    }
 
-                        tokio::spawn(async move {
-                            Self::job_worker(manager, set_name_owned).await;
-                        });
-                    }
-                }
+    /// Restarts debouncing for sets whose journal still lists changes from before
+    /// the daemon stopped, so they are backed up without waiting for a new change.
+    /// Sets whose backup the last shutdown interrupted are backed up at once,
+    /// and a post-restore pause that outlasted the restart is shown again.
+    pub async fn resume_pending_changes(&self) {
+        let mut jobs = self.jobs.lock().await;
+        for (name, job) in jobs.iter_mut() {
+            if !matches!(job.state, JobState::Idle | JobState::Error) {
+                continue;
+            }
+            if job.interrupted {
+                info!(
+                    "Backup of set {} was interrupted by the last shutdown, running it again",
+                    name
+                );
+                job.immediate_trigger = true;
+                self.schedule_debounce(name, job);
+            } else if !job.journal.is_empty()
+                && matches!(job.state, JobState::Idle | JobState::Error)
+            {
+                info!(
+                    "Set {} has {} change(s) pending from before restart, scheduling backup",
+                    name,
+                    job.journal.len()
+                );
+                self.schedule_debounce(name, job);
+            } else if job.is_paused(Utc::now()) {
+                self.show_pause(name, job);
             }
-            Ok(())
-        } else {
-            anyhow::bail!("Unknown backup set: {}", set_name)
         }
     }
 
-    async fn job_worker(manager: JobManager, set_name: String) {
-        let jobs = manager.jobs.clone();
-        let executor = manager.executor.clone();
-        let event_tx = manager.event_tx.clone();
-        let shutdown_token = manager.shutdown_token.clone();
-        loop {
-            // Check for shutdown at start of loop
-            if shutdown_token.is_cancelled() {
-                info!("Shutdown: stopping worker for {}", set_name);
-                break;
+    /// Writes every set's change journal. Called on shutdown.
+    pub async fn flush_journals(&self) {
+        let mut jobs = self.jobs.lock().await;
+        if self.is_handing_off() {
+            return;
+        }
+        for (name, job) in jobs.iter_mut() {
+            if let Err(e) = job.journal.save(job.journal_in_flight.as_ref()) {
+                warn!("Failed to save change journal for set {}: {}", name, e);
             }
+        }
+    }
 
-            // Debouncing phase: wait for timer to stabilize
-            let debounce_duration;
-            let mut start_time;
-            {
-                let mut jobs_lock = jobs.lock().await;
-                if let Some(job) = jobs_lock.get_mut(&set_name) {
-                    if matches!(job.state, JobState::Running) {
-                        // Already in running state (immediate trigger)
-                        job.immediate_trigger = false;
-                        debounce_duration = Duration::ZERO; // Skip loop effectively
-                        start_time = Instant::now();
-                    } else {
-                        debounce_duration = Duration::from_secs(
-                            job.set
-                                .debounce_seconds
-                                .unwrap_or(manager.global_debounce.load(Ordering::Relaxed)),
-                        );
-                        start_time = job.last_change.unwrap_or_else(Instant::now);
-                    }
-                } else {
-                    return; // Job removed
-                }
+    /// Prepares handing the daemon over to a replacement: writes the state and
+    /// every change journal for the new daemon to load. The entries running
+    /// backups are capturing go to handoff journals instead, which the new
+    /// daemon keeps aside until it hears how those backups went. From then on
+    /// this daemon starts no new jobs and leaves those files alone; running
+    /// jobs continue. Returns the sets with a backup running.
+    pub async fn begin_handoff(&self) -> Result<Vec<String>> {
+        let mut jobs = self.jobs.lock().await;
+        if self.is_handing_off() {
+            anyhow::bail!("Another daemon is already taking over");
+        }
+        let mut running = Vec::new();
+        for (name, job) in jobs.iter_mut() {
+            job.journal
+                .save(None)
+                .with_context(|| format!("Failed to save change journal for set {}", name))?;
+            if let Some(in_flight) = job.journal_in_flight.as_ref() {
+                let mut captured = in_flight.clone();
+                captured.bind(&vigil_lib::paths::handoff_journal_path(name));
+                captured
+                    .save(None)
+                    .with_context(|| format!("Failed to save handoff journal for set {}", name))?;
+                running.push(name.clone());
             }
+        }
+        self.save_state(&jobs);
+        // Set under the jobs lock, so no worker writes the files after this
+        self.handing_off.store(true, Ordering::SeqCst);
+        running.sort();
+        Ok(running)
+    }
 
-            // Poll every 500ms to update remaining time and check for expiration
-            loop {
-                // Check shutdown
-                if shutdown_token.is_cancelled() {
-                    return;
-                }
-
-                let mut jobs_lock = jobs.lock().await;
-                if let Some(job) = jobs_lock.get_mut(&set_name) {
-                    if matches!(job.state, JobState::Running) {
-                        break;
-                    }
+    /// Resumes normal operation after a handoff could not be completed.
+    pub fn abort_handoff(&self) {
+        self.handing_off.store(false, Ordering::SeqCst);
+    }
 
-                    if job.immediate_trigger {
-                        job.immediate_trigger = false;
-                        job.state = JobState::Running;
-                        info!(
-                            "Immediate trigger detected for set {}, skipping debounce",
-                            set_name
-                        );
-                        break;
-                    }
+    /// Whether the socket was handed to a replacing daemon.
+    pub fn is_handing_off(&self) -> bool {
+        self.handing_off.load(Ordering::SeqCst)
+    }
 
-                    if let Some(last_change) = job.last_change {
-                        // Check if the timer was reset (new file change)
-                        if last_change > start_time {
-                            info!("Timer reset for set {}, restarting debounce", set_name);
-                            start_time = last_change;
-                        }
+    /// Sends the result of a backup that kept running after the handoff to
+    /// the replacing daemon, which holds the set until it arrives.
+    async fn hand_over_result(&self, set_name: &str, result: &BackupResult) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-                        let elapsed = start_time.elapsed();
-                        if elapsed >= debounce_duration {
-                            info!(
-                                "Debounce timer expired for set {}, transitioning to Running",
-                                set_name
-                            );
-                            job.state = JobState::Running;
-                            break;
-                        } else {
-                            let remaining = debounce_duration.saturating_sub(elapsed).as_secs();
-                            job.state = JobState::Debouncing {
-                                remaining_secs: remaining,
-                            };
-                        }
-                    } else {
-                        // This shouldn't really happen if we are debouncing
-                        job.state = JobState::Running;
-                        break;
-                    }
-                } else {
-                    return; // Job removed
+        let request = Request::HandoffResult {
+            set_name: set_name.to_string(),
+            result: result.clone(),
+        };
+        let send = async {
+            let mut stream = vigil_lib::transport::connect().await?;
+            stream
+                .write_all((serde_json::to_string(&request)? + "\n").as_bytes())
+                .await?;
+            // Events broadcast by the new daemon may arrive before the reply
+            let mut lines = BufReader::new(stream).lines();
+            while let Some(line) = lines.next_line().await? {
+                match serde_json::from_str::<Response>(&line) {
+                    Ok(Response::Event(_)) => {}
+                    Ok(Response::Ok(_)) => return Ok(()),
+                    Ok(Response::Error { message, .. }) => anyhow::bail!(message),
+                    other => anyhow::bail!("Unexpected reply: {:?}", other),
                 }
-                drop(jobs_lock);
+            }
+            anyhow::bail!("The replacing daemon closed the connection")
+        };
+        match tokio::time::timeout(Duration::from_secs(10), send).await {
+            Ok(Ok(())) => info!(
+                "Sent the result of the backup of set {} to the replacing daemon",
+                set_name
+            ),
+            Ok(Err(e)) => warn!(
+                "Failed to send the result of the backup of set {} to the replacing daemon: {:#}",
+                set_name, e
+            ),
+            Err(_) => warn!(
+                "Timed out sending the result of the backup of set {} to the replacing daemon",
+                set_name
+            ),
+        }
+    }
 
-                tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_millis(500)) => {}
-                    _ = shutdown_token.cancelled() => {
-                        return;
-                    }
+    /// Holds the sets whose backups keep running in the daemon `pid` this one
+    /// replaced. They show as running and start no backup until that daemon
+    /// sends the result (`finish_handed_over`) or exits
+    /// (`release_handed_over`). The changes those backups captured are kept
+    /// as in flight, so they are dropped or pending again accordingly.
+    pub async fn hold_handed_over(&self, pid: u32, running: &[String]) {
+        let mut jobs = self.jobs.lock().await;
+        for name in running {
+            let path = vigil_lib::paths::handoff_journal_path(name);
+            if let Some(job) = jobs.get_mut(name) {
+                job.journal_in_flight = Some(ChangeJournal::load(&path));
+                job.state = JobState::running();
+                job.handed_over = true;
+                // A crash of this daemon leaves them pending, as for its own backups
+                if let Err(e) = job.journal.save(job.journal_in_flight.as_ref()) {
+                    warn!("Failed to save change journal for set {}: {}", name, e);
                 }
+                info!(
+                    "Backup of set {} is still running in the replaced daemon, holding the set",
+                    name
+                );
             }
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove handoff journal for set {}: {}", name, e);
+                }
+            }
+        }
+        self.replaced_pid.store(pid, Ordering::SeqCst);
+    }
 
-            // Running phase
-            let backup_start_time = Instant::now();
-            debug!("Starting backup execution for set {}", set_name);
-
-            let result = {
-                let backup_set = {
-                    let jobs_lock = jobs.lock().await;
-                    let Some(job) = jobs_lock.get(&set_name) else {
-                        // Job was removed during execution, nothing to clean up
-                        return;
-                    };
-                    job.set.clone()
-                }; // CRITICAL: Release lock before backup
-
-                // Pass shutdown token to executor so it can kill the process if shutdown occurs
-                executor
-                    .backup(&backup_set, Some(shutdown_token.clone()))
-                    .await
+    /// Applies the result of a backup of a held set that the replaced daemon
+    /// finished, sent from process `peer_pid`, as if this daemon had run it.
+    pub async fn finish_handed_over(
+        &self,
+        peer_pid: Option<i32>,
+        set_name: &str,
+        result: BackupResult,
+    ) -> Result<()> {
+        let replaced = self.replaced_pid.load(Ordering::SeqCst);
+        if replaced == 0 || peer_pid != i32::try_from(replaced).ok() {
+            anyhow::bail!("Only the replaced daemon may send backup results");
+        }
+        {
+            let mut jobs = self.jobs.lock().await;
+            let Some(job) = jobs.get_mut(set_name).filter(|job| job.handed_over) else {
+                anyhow::bail!(
+                    "No backup of set {} is running in the replaced daemon",
+                    set_name
+                );
+            };
+            job.handed_over = false;
+            job.last_backup = Some(result.clone());
+            self.settle_journal(set_name, job, result.success);
+            job.state = if result.success {
+                JobState::Idle
+            } else {
+                JobState::Error
             };
+            // Changes that arrived meanwhile, or a failed backup's, are backed up next
+            if !job.journal.is_empty() {
+                self.schedule_debounce(set_name, job);
+            }
+            self.save_state(&jobs);
+        }
+        info!(
+            "Backup of set {} finished in the replaced daemon. Success: {}",
+            set_name, result.success
+        );
 
-            match result {
-                Ok(backup_result) => {
-                    info!(
-                        "Backup completed for set {} in {:.2}s. Success: {}",
-                        set_name,
-                        backup_start_time.elapsed().as_secs_f64(),
-                        backup_result.success
-                    );
+        if !result.success {
+            let _ = self
+                .event_tx
+                .send(Response::Event(ResponseData::BackupFailed {
+                    set_name: set_name.to_string(),
+                    error: result
+                        .error_message
+                        .unwrap_or_else(|| "Unknown error".to_string()),
+                }));
+            return Ok(());
+        }
+        // The replaced daemon waits for the reply, not for the repository
+        let manager = self.clone();
+        let set_name = set_name.to_string();
+        tokio::spawn(async move {
+            manager.refresh_set_status(&set_name).await;
+            manager.queue_prune(&set_name).await;
+            manager.start_auto_replication(&set_name).await;
+            let _ = manager
+                .event_tx
+                .send(Response::Event(ResponseData::BackupComplete {
+                    set_name,
+                    snapshot_id: result.snapshot_id,
+                    added_bytes: result.added_bytes,
+                    duration_secs: result.duration_secs,
+                }));
+        });
+        Ok(())
+    }
 
-                    let mut metrics_target = None;
-                    {
-                        let mut jobs_lock = jobs.lock().await;
-                        if let Some(job) = jobs_lock.get_mut(&set_name) {
-                            job.last_backup = Some(backup_result.clone());
-                            if !backup_result.success {
-                                job.state = JobState::Error;
-                                let err_msg = backup_result
-                                    .error_message
-                                    .clone()
-                                    .unwrap_or_else(|| "Unknown error".to_string());
-                                error!("Backup failed for set {}: {}", set_name, err_msg);
+    /// Releases the sets still held once the replaced daemon exited without
+    /// sending their results. Their backups count as interrupted: the
+    /// captured changes are pending again and the backups run at once.
+    pub async fn release_handed_over(&self) {
+        let mut jobs = self.jobs.lock().await;
+        for (name, job) in jobs.iter_mut() {
+            if !job.handed_over {
+                continue;
+            }
+            warn!(
+                "The replaced daemon exited without the result of the backup of set {}, running it again",
+                name
+            );
+            job.handed_over = false;
+            job.state = JobState::Idle;
+            self.settle_journal(name, job, false);
+            job.immediate_trigger = true;
+            self.schedule_debounce(name, job);
+        }
+        self.replaced_pid.store(0, Ordering::SeqCst);
+    }
 
-                                // Only notify if not cancelled due to shutdown
-                                if !shutdown_token.is_cancelled() {
-                                    let _ = notify_rust::Notification::new()
-                                        .summary("Backup Failed")
-                                        .body(&format!(
-                                            "Backup for set '{}' failed: {}",
-                                            set_name, err_msg
-                                        ))
-                                        .icon("dialog-error")
-                                        .show();
-                                }
+    /// Starts shutting down: no new jobs start from now on, and clients are
+    /// told that backups of the returned sets get the returned number of
+    /// seconds to finish: `global.shutdown_grace_secs` if `graceful`, else 0.
+    pub async fn begin_shutdown(&self, graceful: bool) -> (u64, Vec<String>) {
+        self.draining.store(true, Ordering::SeqCst);
+        let grace_secs = if graceful {
+            self.global_shutdown_grace_secs.load(Ordering::Relaxed)
+        } else {
+            0
+        };
+        let running = self.running_jobs().await;
+        let _ = self
+            .event_tx
+            .send(Response::Event(ResponseData::ShuttingDown {
+                grace_secs,
+                running: running.clone(),
+            }));
+        (grace_secs, running)
+    }
 
-                                // Broadcast failure event
-                                let _ =
-                                    event_tx.send(Response::Ok(Some(ResponseData::BackupFailed {
-                                        set_name: set_name.clone(),
-                                        error: err_msg,
-                                    })));
-                                break;
-                            }
+    /// Whether the daemon is shutting down and starts no new jobs.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
 
-                            // Check if new changes occurred during backup
-                            if let Some(last_change) = job.last_change {
-                                if last_change > backup_start_time {
-                                    info!(
-                                    "New changes detected for set {} during backup, re-debouncing",
-                                    set_name
-                                );
-                                    let debounce_secs = job
-                                        .set
-                                        .debounce_seconds
-                                        .unwrap_or(manager.global_debounce.load(Ordering::Relaxed));
-                                    job.state = JobState::Debouncing {
-                                        remaining_secs: debounce_secs,
-                                    };
-                                    continue;
-                                }
-                            }
-                            metrics_target = Some(job.set.target.clone());
-                        }
-                    }
+    /// Marks the backups still running or queued as interrupted and saves the
+    /// state, so the next daemon start runs them again. Called right before running jobs
+    /// are cancelled.
+    pub async fn mark_interrupted(&self) {
+        let mut jobs = self.jobs.lock().await;
+        let mut interrupted = Vec::new();
+        for (name, job) in jobs.iter_mut() {
+            if matches!(job.state, JobState::Running { .. } | JobState::Queued) {
+                job.interrupted = true;
+                interrupted.push(name.clone());
+            }
+        }
+        if interrupted.is_empty() || self.is_handing_off() {
+            return;
+        }
+        interrupted.sort();
+        warn!(
+            "Cancelling backups of {:?}; they run again at the next start",
+            interrupted
+        );
+        self.save_state(&jobs);
+    }
 
-                    if let Some(target) = metrics_target {
-                        let manager_clone = manager.clone();
-                        let set_name_clone = set_name.clone();
+    /// Waits until no backup or prune is in flight, or the daemon shuts down.
+    pub async fn wait_for_running_jobs(&self) {
+        loop {
+            let running = self.running_jobs().await;
+            if running.is_empty() {
+                return;
+            }
+            debug!("Waiting for running jobs: {:?}", running);
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                _ = self.shutdown_token.cancelled() => return,
+            }
+        }
+    }
 
-                        // Refresh status for this set and related sets deterministically
-                        // so that subsequent auto-prune or status requests see updated metrics.
-                        manager_clone.refresh_set_status(&set_name_clone).await;
-                        manager_clone
-                            .refresh_related_sets(&target, &set_name_clone)
-                            .await;
+    /// Settles the journal after a backup attempt: captured entries are dropped on
+    /// success and returned to the journal on failure.
+    fn settle_journal(&self, set_name: &str, job: &mut Job, success: bool) {
+        if let Some(in_flight) = job.journal_in_flight.take() {
+            if !success {
+                job.journal.merge(&in_flight);
+            }
+        }
+        if self.is_handing_off() {
+            return;
+        }
+        if let Err(e) = job.journal.save(None) {
+            warn!("Failed to save change journal for set {}: {}", set_name, e);
+        }
+    }
 
-                        {
-                            let mut jobs_lock = jobs.lock().await;
-                            if let Some(job) = jobs_lock.get_mut(&set_name) {
-                                job.state = JobState::Idle;
-                            }
-                        }
+    /// Puts a set whose backup just failed into `Retrying`, unless its retries
+    /// are used up or the daemon is shutting down or replaced, in which case it goes to
+    /// `Error` and the next failure streak starts counting from zero again.
+    /// A set whose repository has no snapshot yet is retried past the limit
+    /// until its initial backup succeeds, unless retries are off: restic keeps
+    /// what an interrupted backup uploaded, so each attempt gets further.
+    /// Returns whether a retry was scheduled.
+    fn schedule_retry(&self, set_name: &str, job: &mut Job) -> bool {
+        let max_retries = job
+            .set
+            .max_retries
+            .unwrap_or(self.global_max_retries.load(Ordering::Relaxed));
+        let seeding = job.snapshot_count == Some(0) && max_retries > 0;
+        if (job.retry_attempt >= max_retries && !seeding)
+            || self.shutdown_token.is_cancelled()
+            || self.is_handing_off()
+        {
+            if max_retries > 0 && job.retry_attempt >= max_retries {
+                warn!(
+                    "Backup for set {} failed after {} retries, giving up",
+                    set_name, job.retry_attempt
+                );
+            }
+            job.retry_attempt = 0;
+            job.retry_at = None;
+            job.state = JobState::Error;
+            return false;
+        }
+        job.retry_attempt += 1;
+        let backoff = job
+            .set
+            .retry_backoff_secs
+            .unwrap_or(self.global_retry_backoff.load(Ordering::Relaxed));
+        let delay = retry_delay_secs(backoff, job.retry_attempt);
+        if seeding {
+            info!(
+                "Retrying initial backup for set {} in {} (retry {}, until the first snapshot exists)",
+                set_name,
+                humanize::format_secs(delay as f64),
+                job.retry_attempt
+            );
+        } else {
+            info!(
+                "Retrying backup for set {} in {} (retry {} of {})",
+                set_name,
+                humanize::format_secs(delay as f64),
+                job.retry_attempt,
+                max_retries
+            );
+        }
+        job.retry_at = Some(Instant::now() + Duration::from_secs(delay));
+        job.state = JobState::Retrying {
+            attempt: job.retry_attempt,
+            next_retry_secs: delay,
+        };
+        true
+    }
 
-                        // Broadcast completion event
-                        let _ = event_tx.send(Response::Ok(Some(ResponseData::BackupComplete {
-                            set_name: set_name.clone(),
-                            snapshot_id: backup_result.snapshot_id.clone(),
-                            added_bytes: backup_result.added_bytes,
-                            duration_secs: backup_result.duration_secs,
-                        })));
-
-                        // Now trigger automatic pruning if retention policy exists
-                        let jobs_lock = jobs.lock().await;
-                        if let Some(job) = jobs_lock.get(&set_name) {
-                            let effective_set = manager.with_effective_retention(&job.set).await;
-                            if effective_set.retention.is_some() {
-                                let manager_clone2 = manager.clone();
-                                let set_name_clone2 = set_name.clone();
-                                let event_tx_clone = event_tx.clone();
-
-                                tokio::spawn(async move {
-                                    manager_clone2
-                                        .auto_prune_after_backup(&set_name_clone2, event_tx_clone)
-                                        .await;
-                                });
-                            }
-                        }
-                        break;
+    /// Marks a change and starts (or resets) the set's debounce timer.
+    /// While the set is paused after a restore, the change waits for the
+    /// pause to end instead.
+    fn schedule_debounce(&self, set_name: &str, job: &mut Job) {
+        let now = Instant::now();
+        let debounce_secs = self.debounce_secs(&job.set);
+        job.last_change = Some(now);
+        job.debounce_deadline = Some(now + Duration::from_secs(debounce_secs));
+
+        match job.state {
+            JobState::Idle | JobState::Error => {
+                job.state = if job.is_paused(Utc::now()) {
+                    job.paused_state()
+                } else {
+                    JobState::Debouncing {
+                        remaining_secs: debounce_secs,
                     }
+                };
+                if !job.worker_active {
+                    job.worker_active = true;
+                    let manager = self.clone();
+                    let set_name_owned = set_name.to_string();
+
+                    tokio::spawn(async move {
+                        Self::job_worker(manager, set_name_owned).await;
+                    });
                 }
-                Err(e) => {
-                    let err_msg = e.to_string();
-                    error!("Backup job error for set {}: {}", set_name, err_msg);
-
-                    {
-                        let mut jobs_lock = jobs.lock().await;
-                        if let Some(job) = jobs_lock.get_mut(&set_name) {
-                            job.state = JobState::Error;
-                        }
-                    }
+            }
+            JobState::Debouncing { .. } => {
+                debug!("Set {} is already debouncing, timer reset", set_name);
+                // The worker picks up the new deadline on its next poll
+            }
+            JobState::Retrying { .. } => {
+                debug!(
+                    "Set {} is waiting to retry, the retry will include the change",
+                    set_name
+                );
+            }
+            JobState::Scheduled { .. } => {
+                debug!(
+                    "Set {} is waiting for its maintenance window, the backup will include the change",
+                    set_name
+                );
+            }
+            JobState::Paused { .. } => {
+                debug!(
+                    "Set {} is paused after a restore, the change is backed up when the pause ends",
+                    set_name
+                );
+            }
+            JobState::Queued => {
+                debug!(
+                    "Set {} is waiting for a job slot, the backup will include the change",
+                    set_name
+                );
+            }
+            JobState::Running { .. } => {
+                debug!(
+                    "Set {} is currently running, will re-debounce after completion",
+                    set_name
+                );
+                // When the current backup finishes, it will check last_change
+            }
+        }
+    }
 
-                    if !shutdown_token.is_cancelled() {
-                        let _ = notify_rust::Notification::new()
-                            .summary("Backup Failed")
-                            .body(&format!(
-                                "Internal error backing up set '{}': {}",
-                                set_name, err_msg
-                            ))
-                            .icon("dialog-error")
-                            .show();
-                    }
+    /// Shows a paused set that has nothing else to do as `Paused`, with a
+    /// worker to end the pause.
+    fn show_pause(&self, set_name: &str, job: &mut Job) {
+        if !matches!(job.state, JobState::Idle | JobState::Debouncing { .. }) {
+            return;
+        }
+        job.state = job.paused_state();
+        if !job.worker_active {
+            job.worker_active = true;
+            let manager = self.clone();
+            let set_name_owned = set_name.to_string();
 
-                    // Broadcast failure event
-                    let _ = event_tx.send(Response::Ok(Some(ResponseData::BackupFailed {
-                        set_name: set_name.clone(),
-                        error: err_msg,
-                    })));
+            tokio::spawn(async move {
+                Self::job_worker(manager, set_name_owned).await;
+            });
+        }
+    }
 
-                    break;
-                }
-            }
+    /// Ends the pause of a set's change-triggered backups after a restore
+    /// early. Changes made meanwhile are then debounced as usual.
+    pub async fn resume(&self, set_name: &str) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        let job = jobs
+            .get_mut(set_name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown backup set: {}", set_name))?;
+        if job.restores_in_place > 0 {
+            anyhow::bail!(
+                "A restore into the sources of set {} is still running",
+                set_name
+            );
         }
-        // Worker is exiting, clear the active flag
-        let mut jobs_lock = jobs.lock().await;
-        if let Some(job) = jobs_lock.get_mut(&set_name) {
-            job.worker_active = false;
+        if !job.is_paused(Utc::now()) {
+            anyhow::bail!("Backups of set {} are not paused", set_name);
         }
+        // The worker ends the pause on its next poll
+        job.paused_until = None;
+        info!("Change-triggered backups of set {} resumed early", set_name);
+        self.save_state(&jobs);
+        Ok(())
     }
 
-    /// Get status for all backup sets.
-    ///
-    /// **Note**: This function has side effects - it monitors mount processes and updates
-    /// `is_mounted` state if a mount process has died unexpectedly.
-    pub async fn get_status(&self) -> Vec<SetStatus> {
-        let mut jobs = self.jobs.lock().await;
+    /// The set's debounce delay in seconds, falling back to the global one.
+    fn debounce_secs(&self, set: &BackupSet) -> u64 {
+        set.debounce_seconds
+            .unwrap_or(self.global_debounce.load(Ordering::Relaxed))
+    }
 
-        let mut statuses = Vec::new();
-        for job in jobs.values_mut() {
-            // Monitor mount process
-            if job.is_mounted {
-                if let Some(ref mut child) = job.mount_process {
-                    match child.try_wait() {
-                        Ok(Some(status)) => {
-                            warn!(
-                                "Mount process for set {} exited unexpectedly with status: {}",
-                                job.set.name, status
-                            );
-                            job.mount_process = None;
+    pub async fn trigger_backup(&self, set_name: &str) -> Result<()> {
+        self.trigger_tagged_backup(set_name, &[]).await
+    }
 
-                            // Check if it's still mounted despite the process exiting
-                            if !vigil_lib::paths::is_mount_point(&vigil_lib::paths::mount_path(
-                                &job.set.name,
-                            )) {
-                                job.is_mounted = false;
-                            } else {
-                                info!(
-                                    "Mount for set {} still active after process exit (orphaned mount)",
-                                    job.set.name
-                                );
-                            }
-                        }
-                        Ok(None) => {
-                            // Still running
-                        }
-                        Err(e) => {
-                            error!(
-                                "Error checking mount process for set {}: {}",
-                                job.set.name, e
-                            );
-                        }
-                    }
-                } else {
-                    // No mount process tracked — this can happen for orphaned mounts detected
-                    // via /proc/mounts on daemon restart. Verify the mount is still active.
-                    if !vigil_lib::paths::is_mount_point(&vigil_lib::paths::mount_path(
-                        &job.set.name,
-                    )) {
-                        debug!(
-                            "Set '{}' was marked mounted but mount no longer exists, clearing state",
-                            job.set.name
-                        );
-                        job.is_mounted = false;
-                    }
-                }
-            }
-
-            statuses.push(SetStatus {
-                name: job.set.name.clone(),
-                state: job.state.clone(),
-                last_backup: job.last_backup.clone(),
-                source_paths: {
-                    let mut paths = Vec::new();
-                    if let Some(ref s) = job.set.source {
-                        paths.push(s.into());
-                    }
-                    if let Some(ref ss) = job.set.sources {
-                        for s in ss {
-                            paths.push(s.into());
-                        }
-                    }
-                    paths
-                },
-                target: job.set.target.clone().into(),
-                is_mounted: job.is_mounted,
-                snapshot_count: job.snapshot_count,
-                total_bytes: job.total_bytes,
-            });
-        }
-        statuses
+    /// Starts a backup whose snapshot carries `tags` in addition to the set's own.
+    pub async fn trigger_tagged_backup(&self, set_name: &str, tags: &[String]) -> Result<()> {
+        self.start_backup(set_name, tags, Vec::new()).await
     }
 
-    pub async fn get_snapshots(
+    /// Starts a backup tagged `rescue` that ignores the set's exclude patterns
+    /// lifted by `include` (see [`lifted_excludes`]), to capture files a wrong
+    /// exclude kept out. The configuration is left as it is. Returns the
+    /// lifted patterns; fails when `include` lifts none of them.
+    pub async fn trigger_rescue_backup(
         &self,
         set_name: &str,
-        limit: Option<usize>,
-    ) -> Result<Vec<SnapshotInfo>> {
-        let jobs = self.jobs.lock().await;
-        if let Some(job) = jobs.get(set_name) {
-            // Snapshots query typically redundant to be cancelled by shutdown?
-            // We can pass token if we want strict shutdown, but for now user-initiated reads are probably fine to finish or fail on pipe close.
-            // Let's pass the token to be consistent.
-            self.executor
-                .snapshots(&job.set.target, limit, Some(self.shutdown_token.clone()))
-                .await
-        } else {
-            anyhow::bail!("Unknown backup set: {}", set_name)
+        include: &[String],
+    ) -> Result<Vec<String>> {
+        if include.is_empty() {
+            anyhow::bail!("A rescue backup needs at least one include pattern");
+        }
+        let excludes = {
+            let jobs = self.jobs.lock().await;
+            let job = jobs
+                .get(set_name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown backup set: {}", set_name))?;
+            job.set.exclude.clone().unwrap_or_default()
+        };
+        let lifted = lifted_excludes(&excludes, include);
+        if lifted.is_empty() {
+            anyhow::bail!(
+                "No exclude pattern of set {} matches {}; those paths are already backed up",
+                set_name,
+                include.join(", ")
+            );
         }
+        info!(
+            "Rescue backup for set {} lifts excludes: {}",
+            set_name,
+            lifted.join(", ")
+        );
+        self.start_backup(set_name, &["rescue".to_string()], lifted.clone())
+            .await?;
+        Ok(lifted)
     }
 
-    pub async fn mount(&self, set_name: &str, snapshot_id: Option<String>) -> Result<PathBuf> {
+    async fn start_backup(
+        &self,
+        set_name: &str,
+        tags: &[String],
+        lifted_excludes: Vec<String>,
+    ) -> Result<()> {
+        for tag in tags {
+            vigil_lib::config::validate_tag(tag)
+                .map_err(|e| anyhow::anyhow!("Invalid tag '{}': {}", tag, e))?;
+        }
+        if self.is_handing_off() {
+            anyhow::bail!("The daemon is being replaced; try again shortly");
+        }
+        if self.is_draining() {
+            anyhow::bail!("The daemon is shutting down");
+        }
         let mut jobs = self.jobs.lock().await;
         if let Some(job) = jobs.get_mut(set_name) {
-            if job.is_mounted {
-                return Ok(vigil_lib::paths::mount_path(set_name));
-            }
+            match job.state {
+                JobState::Running { .. } => {
+                    anyhow::bail!("Backup for set {} is already running", set_name);
+                }
+                JobState::Queued => {
+                    info!(
+                        "Backup of set {} is already queued for a job slot",
+                        set_name
+                    );
+                }
+                JobState::Debouncing { .. }
+                | JobState::Retrying { .. }
+                | JobState::Scheduled { .. }
+                | JobState::Paused { .. } => {
+                    job.immediate_trigger = true;
+                    info!(
+                        "Immediate backup triggered for set {} (was waiting)",
+                        set_name
+                    );
+                }
+                JobState::Idle | JobState::Error => {
+                    // Running once the worker has a job slot
+                    job.state = JobState::Queued;
+                    info!("Immediate backup triggered for set {}", set_name);
 
-            let mount_path = vigil_lib::paths::mount_path(set_name);
-            if !mount_path.exists() {
-                std::fs::create_dir_all(&mount_path)?;
-                // Set restrictive permissions for sensitive backup data
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    std::fs::set_permissions(&mount_path, std::fs::Permissions::from_mode(0o700))?;
+                    if !job.worker_active {
+                        job.worker_active = true;
+                        let manager = self.clone();
+                        let set_name_owned = set_name.to_string();
+
+                        tokio::spawn(async move {
+                            Self::job_worker(manager, set_name_owned).await;
+                        });
+                    }
+                }
+            }
+            // The worker picks these up when the backup starts, after this lock is released
+            for tag in tags {
+                if !job.run_tags.contains(tag) {
+                    job.run_tags.push(tag.clone());
+                }
+            }
+            for exclude in lifted_excludes {
+                if !job.run_lifted_excludes.contains(&exclude) {
+                    job.run_lifted_excludes.push(exclude);
                 }
             }
+            Ok(())
+        } else {
+            anyhow::bail!("Unknown backup set: {}", set_name)
+        }
+    }
 
-            info!("Mounting set {} at {:?}", set_name, mount_path);
-            let child = self
-                .executor
-                .mount(&job.set.target, snapshot_id.as_deref(), &mount_path)
-                .await?;
+    /// The engine for `set`, with the current global engine settings. It keeps
+    /// them for its lifetime, so a reload does not affect running jobs.
+    fn engine(&self, set: &BackupSet) -> Engine {
+        let defaults = self
+            .engine_defaults
+            .lock()
+            .map(|defaults| defaults.clone())
+            .unwrap_or_default();
+        Engine::for_set(set, &defaults)
+    }
 
-            job.mount_process = Some(child);
-            job.is_mounted = true;
+    /// The set's maintenance window, falling back to the global one.
+    fn maintenance_window(&self, set: &BackupSet) -> Option<MaintenanceWindow> {
+        let global = self
+            .global_maintenance_window
+            .lock()
+            .ok()
+            .and_then(|window| window.clone());
+        set.maintenance_window(global.as_deref())
+    }
 
-            Ok(mount_path)
-        } else {
-            anyhow::bail!("Unknown backup set: {}", set_name)
+    /// When a debounced backup of `set` may start, if it has to wait for the
+    /// maintenance window.
+    fn deferred_until(&self, set: &BackupSet, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !set.defer_backups {
+            return None;
         }
+        let at = self.maintenance_window(set)?.next_open(now);
+        (at > now).then_some(at)
     }
 
-    pub async fn unmount(&self, set_name: Option<String>) -> Result<()> {
-        let mut jobs = self.jobs.lock().await;
-        if let Some(name) = set_name {
-            if let Some(job) = jobs.get_mut(&name) {
-                Self::perform_unmount(&name, job).await?;
-                Ok(())
-            } else {
-                anyhow::bail!("Unknown backup set: {}", name)
-            }
-        } else {
-            info!("Unmounting all sets");
+    /// Starts backups for sets whose schedule is due and plans their next run.
+    /// Returns the earliest planned run across all sets.
+    ///
+    /// Interval schedules count from the last backup attempt, so change-triggered
+    /// backups push the next scheduled one back. A set with an interval and no
+    /// backup yet runs right away. Sets are skipped until their repository status
+    /// has loaded and while a backup is running.
+    pub async fn run_due_schedules(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut due = Vec::new();
+        let mut earliest: Option<DateTime<Utc>> = None;
+        {
+            let mut jobs = self.jobs.lock().await;
             for (name, job) in jobs.iter_mut() {
-                if let Err(e) = Self::perform_unmount(name, job).await {
-                    error!("Failed to unmount set {}: {}", name, e);
+                let Some(schedule) = job.set.schedule() else {
+                    job.next_run = None;
+                    continue;
+                };
+                if !job.status_loaded {
+                    continue;
+                }
+                if !matches!(job.state, JobState::Running { .. } | JobState::Queued) {
+                    let next = match (&schedule, &job.last_backup) {
+                        (Schedule::Every(_), Some(last)) => schedule.next_after(last.timestamp),
+                        (Schedule::Every(_), None) => Some(now),
+                        (Schedule::Cron(_), _) => job.next_run.or_else(|| schedule.next_after(now)),
+                    };
+                    job.next_run = match next {
+                        Some(next) if next <= now => {
+                            due.push(name.clone());
+                            schedule.next_after(now)
+                        }
+                        next => next,
+                    };
+                }
+                if let Some(next) = job.next_run {
+                    earliest = Some(earliest.map_or(next, |e| e.min(next)));
                 }
             }
-            Ok(())
         }
+
+        for name in due {
+            info!("Scheduled backup due for set {}", name);
+            if let Err(e) = self.trigger_backup(&name).await {
+                warn!("Failed to start scheduled backup for set {}: {}", name, e);
+            }
+        }
+        earliest
     }
 
-    /// Core prune logic for a single set. Used by both manual prune and auto-prune.
-    async fn prune_set(&self, set_name: &str, effective_set: &BackupSet) -> Result<u64> {
-        info!("Pruning set {}", set_name);
-        let reclaimed = self
-            .executor
-            .prune(effective_set, Some(self.shutdown_token.clone()))
-            .await?;
-        info!("Pruned set {}: {} bytes reclaimed", set_name, reclaimed);
+    /// Queues an automatic prune of `set_name` after a successful backup, if
+    /// the set has a retention policy and prunes automatically.
+    pub async fn queue_prune(&self, set_name: &str) {
+        // A replacing daemon prunes after its own next backup
+        if self.shutdown_token.is_cancelled() || self.is_handing_off() || self.is_draining() {
+            return;
+        }
+        let mut jobs = self.jobs.lock().await;
+        let Some(job) = jobs.get_mut(set_name) else {
+            return;
+        };
+        let effective_set = self.with_effective_retention(&job.set).await;
+        // Append-only repositories are pruned on the server
+        if effective_set.retention.is_some() && effective_set.auto_prune() && !job.is_append_only()
+        {
+            let now = Utc::now();
+            match self
+                .maintenance_window(&effective_set)
+                .map(|window| window.next_open(now))
+                .filter(|at| *at > now)
+            {
+                Some(at) => info!(
+                    "Deferring auto-prune of set {} to the maintenance window at {}",
+                    set_name, at
+                ),
+                None => debug!("Queued automatic prune of set {}", set_name),
+            }
+            job.prune_pending = true;
+            self.save_state(&jobs);
+        }
+    }
 
-        // Refresh metrics after prune deterministically
-        self.refresh_set_status(set_name).await;
-        self.refresh_related_sets(&effective_set.target, set_name)
-            .await;
+    /// Starts queued automatic prunes whose maintenance window is open and
+    /// whose repository no backup or prune is using, one per repository.
+    /// Returns when the earliest window still holding a prune back opens.
+    pub async fn run_pending_prunes(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // The replacing daemon restored the queue from `state.json`
+        if self.is_handing_off() {
+            return None;
+        }
+        let mut ready = Vec::new();
+        let mut earliest: Option<DateTime<Utc>> = None;
+        {
+            let mut jobs = self.jobs.lock().await;
+            let pruning = self
+                .active_prunes
+                .lock()
+                .map(|active| active.clone())
+                .unwrap_or_default();
+            let replicating: HashSet<String> = self
+                .replications
+                .lock()
+                .map(|active| active.keys().cloned().collect())
+                .unwrap_or_default();
+            // A prune waits for copies reading the repository, which hold its lock
+            let mut busy_targets: HashSet<String> = jobs
+                .values()
+                .filter(|job| {
+                    matches!(job.state, JobState::Running { .. })
+                        || pruning.contains(&job.set.name)
+                        || replicating.contains(&job.set.name)
+                })
+                .map(|job| job.set.target.clone())
+                .collect();
+            for (name, job) in jobs.iter_mut() {
+                if !job.prune_pending {
+                    continue;
+                }
+                // A window removed from the config no longer holds the prune back
+                let opens = self
+                    .maintenance_window(&job.set)
+                    .map_or(now, |window| window.next_open(now));
+                if opens > now {
+                    earliest = Some(earliest.map_or(opens, |e| e.min(opens)));
+                    continue;
+                }
+                if !busy_targets.insert(job.set.target.clone()) {
+                    debug!(
+                        "Repository of set {} is busy, keeping its prune queued",
+                        name
+                    );
+                    continue;
+                }
+                job.prune_pending = false;
+                // Holds the repository until the prune is done, so the next
+                // pass does not start another one on it while this one waits for a slot
+                ready.push((name.clone(), self.track_prune(name)));
+            }
+            if !ready.is_empty() {
+                self.save_state(&jobs);
+            }
+        }
 
-        Ok(reclaimed)
+        for (name, guard) in ready {
+            let manager = self.clone();
+            tokio::spawn(async move {
+                manager.auto_prune(&name).await;
+                drop(guard);
+            });
+        }
+        earliest
     }
 
-    pub async fn prune(&self, set_name: Option<String>) -> Result<vigil_lib::ipc::ResponseData> {
-        if let Some(name) = set_name {
-            let effective_set = {
-                let jobs = self.jobs.lock().await;
-                if let Some(job) = jobs.get(&name) {
-                    self.with_effective_retention(&job.set).await
-                } else {
-                    anyhow::bail!("Unknown backup set: {}", name)
-                }
-            };
+    async fn job_worker(manager: JobManager, set_name: String) {
+        let jobs = manager.jobs.clone();
+        let event_tx = manager.event_tx.clone();
+        let shutdown_token = manager.shutdown_token.clone();
+        loop {
+            // Check for shutdown at start of loop
+            if shutdown_token.is_cancelled() {
+                info!("Shutdown: stopping worker for {}", set_name);
+                break;
+            }
 
-            let reclaimed = self.prune_set(&name, &effective_set).await?;
+            {
+                let mut jobs_lock = jobs.lock().await;
+                let Some(job) = jobs_lock.get_mut(&set_name) else {
+                    return; // Job removed
+                };
+                if job.state == JobState::Queued {
+                    // Already queued (immediate trigger)
+                    job.immediate_trigger = false;
+                }
+            }
 
-            Ok(vigil_lib::ipc::ResponseData::PruneResult {
-                set_name: name,
-                reclaimed_bytes: reclaimed,
-            })
-        } else {
-            // Collect effective sets under the lock, then drop it
-            let sets_to_prune: Vec<(String, BackupSet)> = {
-                let jobs = self.jobs.lock().await;
-                let mut sets = Vec::new();
-                for (name, job) in jobs.iter() {
-                    let effective_set = self.with_effective_retention(&job.set).await;
-                    sets.push((name.clone(), effective_set));
+            // Debouncing phase: poll every 500ms until the timer or another wait expires
+            loop {
+                // Check shutdown
+                if shutdown_token.is_cancelled() {
+                    return;
                 }
-                sets
-            };
 
-            info!("Pruning all sets");
-            let mut succeeded = Vec::new();
-            let mut failed = Vec::new();
-            let mut targets_to_refresh = Vec::new();
+                let mut jobs_lock = jobs.lock().await;
+                if let Some(job) = jobs_lock.get_mut(&set_name) {
+                    if job.journal.is_dirty() {
+                        if let Err(e) = job.journal.save_if_due(None) {
+                            warn!("Failed to save change journal for set {}: {}", set_name, e);
+                        }
+                    }
+                    if job.state == JobState::Queued {
+                        break;
+                    }
 
-            for (name, effective_set) in &sets_to_prune {
-                // Check shutdown before starting next prune
-                if self.shutdown_token.is_cancelled() {
-                    break;
-                }
-                match self
-                    .executor
-                    .prune(effective_set, Some(self.shutdown_token.clone()))
-                    .await
-                {
-                    Ok(reclaimed) => {
-                        info!("Pruned set {}: {} bytes reclaimed", name, reclaimed);
-                        succeeded.push((name.clone(), reclaimed));
-                        targets_to_refresh.push((name.clone(), effective_set.target.clone()));
+                    if job.immediate_trigger {
+                        job.immediate_trigger = false;
+                        job.state = JobState::Queued;
+                        info!(
+                            "Immediate trigger detected for set {}, skipping debounce",
+                            set_name
+                        );
+                        break;
                     }
-                    Err(e) => {
-                        error!("Failed to prune set {}: {}", name, e);
-                        failed.push((name.clone(), e.to_string()));
+
+                    if let JobState::Scheduled { at } = job.state {
+                        if Utc::now() >= at {
+                            info!(
+                                "Maintenance window open, starting deferred backup of set {}",
+                                set_name
+                            );
+                            job.state = JobState::Queued;
+                            break;
+                        }
+                    } else if let JobState::Retrying { attempt, .. } = job.state {
+                        let remaining = job.retry_at.map_or(Duration::ZERO, |at| {
+                            at.saturating_duration_since(Instant::now())
+                        });
+                        if remaining.is_zero() {
+                            info!("Retrying backup for set {} (retry {})", set_name, attempt);
+                            job.state = JobState::Queued;
+                            break;
+                        }
+                        job.state = JobState::Retrying {
+                            attempt,
+                            next_retry_secs: remaining.as_secs(),
+                        };
+                    } else if job.is_paused(Utc::now()) {
+                        // The debounce starts over once the pause ends
+                        job.state = job.paused_state();
+                    } else if matches!(job.state, JobState::Paused { .. }) {
+                        job.paused_until = None;
+                        if job.journal.is_empty() {
+                            info!("Post-restore pause of set {} ended", set_name);
+                            job.state = JobState::Idle;
+                            job.worker_active = false;
+                            return;
+                        }
+                        let debounce_secs = manager.debounce_secs(&job.set);
+                        info!(
+                            "Post-restore pause of set {} ended, debouncing {} change(s) made meanwhile",
+                            set_name,
+                            job.journal.len()
+                        );
+                        job.debounce_deadline =
+                            Some(Instant::now() + Duration::from_secs(debounce_secs));
+                        job.state = JobState::Debouncing {
+                            remaining_secs: debounce_secs,
+                        };
+                    } else if let Some(deadline) = job.debounce_deadline {
+                        if Instant::now() >= deadline {
+                            let changed_files = job.journal.len();
+                            let changed_bytes = job
+                                .set
+                                .min_changed_bytes
+                                .and_then(|_| job.journal.changed_bytes());
+                            if !job.set.meets_change_threshold(changed_files, changed_bytes) {
+                                info!(
+                                    "Skipping backup of set {}: {} changed file(s) are below its min-change threshold and carry over to the next backup",
+                                    set_name, changed_files
+                                );
+                                job.state = JobState::Idle;
+                                job.skipped_trivial = Some(Utc::now());
+                                // Cleared under the same lock, so the next change starts a new worker
+                                job.worker_active = false;
+                                drop(jobs_lock);
+                                Self::record_skip(&set_name);
+                                return;
+                            }
+                            if let Some(at) = manager.deferred_until(&job.set, Utc::now()) {
+                                info!(
+                                    "Debounce timer expired for set {}, deferring the backup to the maintenance window at {}",
+                                    set_name, at
+                                );
+                                job.state = JobState::Scheduled { at };
+                            } else {
+                                info!(
+                                    "Debounce timer expired for set {}, queueing the backup",
+                                    set_name
+                                );
+                                job.state = JobState::Queued;
+                                break;
+                            }
+                        }
+                    } else {
+                        // This shouldn't really happen if we are debouncing
+                        job.state = JobState::Queued;
+                        break;
                     }
+                } else {
+                    return; // Job removed
                 }
-            }
+                drop(jobs_lock);
 
-            // Refresh metrics for successfully pruned sets
-            for (name, target) in targets_to_refresh {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+                    _ = shutdown_token.cancelled() => {
+                        return;
+                    }
+                }
+            }
+
+            // Running phase
+            let slot = manager.acquire_job_slot(&set_name).await;
+            if shutdown_token.is_cancelled() {
+                return;
+            }
+            if manager.is_handing_off() {
+                info!(
+                    "Not starting backup for set {}: the daemon was replaced",
+                    set_name
+                );
+                if let Some(job) = jobs.lock().await.get_mut(&set_name) {
+                    job.state = JobState::Idle;
+                }
+                return;
+            }
+            if manager.is_draining() {
+                info!(
+                    "Not starting backup for set {}: the daemon is shutting down",
+                    set_name
+                );
+                if let Some(job) = jobs.lock().await.get_mut(&set_name) {
+                    job.state = JobState::Idle;
+                }
+                return;
+            }
+            let backup_start_time = Instant::now();
+            let started_at = Utc::now();
+            debug!("Starting backup execution for set {}", set_name);
+
+            let (backup_set, recent_changes) = {
+                let mut jobs_lock = jobs.lock().await;
+                let Some(job) = jobs_lock.get_mut(&set_name) else {
+                    // Job was removed during execution, nothing to clean up
+                    return;
+                };
+                job.state = JobState::running();
+                job.skipped_trivial = None;
+                job.interrupted = false;
+                // Changes arriving from now on belong to the next backup
+                let captured = job.journal.take();
+                match job.journal_in_flight.as_mut() {
+                    Some(in_flight) => in_flight.merge(&captured),
+                    None => job.journal_in_flight = Some(captured),
+                }
+                let mut set = job.set.clone();
+                for tag in std::mem::take(&mut job.run_tags) {
+                    if !set.tags.contains(&tag) {
+                        set.tags.push(tag);
+                    }
+                }
+                let lifted = std::mem::take(&mut job.run_lifted_excludes);
+                if let Some(exclude) = set.exclude.as_mut() {
+                    exclude.retain(|pattern| !lifted.contains(pattern));
+                }
+                let recent_changes = if job.recent_changes_consumed {
+                    Vec::new()
+                } else {
+                    job.recent_changes.clone()
+                };
+                job.recent_changes_consumed = true;
+                (set, recent_changes)
+            }; // CRITICAL: Release lock before backup
+
+            let _ = event_tx.send(Response::Event(ResponseData::BackupStarted {
+                set_name: set_name.clone(),
+                recent_changes,
+            }));
+
+            // Pass shutdown token to executor so it can kill the process if shutdown occurs
+            let result = manager
+                .run_backup(&backup_set, shutdown_token.clone())
+                .await;
+            drop(slot);
+            Self::record_run(&set_name, started_at, &result);
+
+            match result {
+                Ok(backup_result) => {
+                    info!(
+                        "Backup completed for set {} in {}. Success: {}",
+                        set_name,
+                        humanize::format_secs(backup_start_time.elapsed().as_secs_f64()),
+                        backup_result.success
+                    );
+                    if let (Some(rss), Some(cpu)) =
+                        (backup_result.peak_rss_bytes, backup_result.cpu_secs)
+                    {
+                        info!(
+                            "Backup of set {} used {} peak RSS and {} CPU",
+                            set_name,
+                            humanize::format_size(rss),
+                            humanize::format_secs(cpu)
+                        );
+                    }
+
+                    {
+                        let mut jobs_lock = jobs.lock().await;
+                        if let Some(job) = jobs_lock.get_mut(&set_name) {
+                            job.last_backup = Some(backup_result.clone());
+                            manager.settle_journal(&set_name, job, backup_result.success);
+                        }
+                        if !manager.is_handing_off() {
+                            manager.save_state(&jobs_lock);
+                        }
+                    }
+                    if manager.is_handing_off() {
+                        // The replacement owns the state and journal files now
+                        manager.hand_over_result(&set_name, &backup_result).await;
+                    }
+
+                    let mut metrics_target = None;
+                    {
+                        let mut jobs_lock = jobs.lock().await;
+                        if let Some(job) = jobs_lock.get_mut(&set_name) {
+                            if !backup_result.success {
+                                let err_msg = backup_result
+                                    .error_message
+                                    .clone()
+                                    .unwrap_or_else(|| "Unknown error".to_string());
+                                error!("Backup failed for set {}: {}", set_name, err_msg);
+
+                                let retrying = manager.schedule_retry(&set_name, job);
+                                if !retrying && !manager.record_digest_failure(&set_name) {
+                                    manager.notify(
+                                        &job.set,
+                                        NotifyEvent::Failure,
+                                        "Backup Failed",
+                                        &format!(
+                                            "Backup for set '{}' failed: {}",
+                                            set_name, err_msg
+                                        ),
+                                        "dialog-error",
+                                    );
+                                }
+
+                                // Broadcast failure event
+                                let _ =
+                                    event_tx.send(Response::Event(ResponseData::BackupFailed {
+                                        set_name: set_name.clone(),
+                                        error: err_msg,
+                                    }));
+                                if retrying {
+                                    continue;
+                                }
+                                break;
+                            }
+                            job.retry_attempt = 0;
+                            job.retry_at = None;
+                            if !manager.record_digest_success(&set_name, backup_result.added_bytes)
+                            {
+                                manager.notify(
+                                    &job.set,
+                                    NotifyEvent::Success,
+                                    "Backup Complete",
+                                    &format!(
+                                        "Set '{}' backed up, {} added",
+                                        set_name,
+                                        humanize::format_size(backup_result.added_bytes)
+                                    ),
+                                    "dialog-information",
+                                );
+                            }
+
+                            // Check if new changes occurred during backup
+                            if let Some(last_change) = job.last_change {
+                                if last_change > backup_start_time {
+                                    info!(
+                                    "New changes detected for set {} during backup, re-debouncing",
+                                    set_name
+                                );
+                                    // The deadline was set by the change itself
+                                    job.state = JobState::Debouncing { remaining_secs: 0 };
+                                    continue;
+                                }
+                            }
+                            metrics_target = Some(job.set.target.clone());
+                        }
+                    }
+
+                    if let Some(target) = metrics_target {
+                        let manager_clone = manager.clone();
+                        let set_name_clone = set_name.clone();
+
+                        // Refresh status for this set and related sets deterministically
+                        // so that subsequent auto-prune or status requests see updated metrics.
+                        manager_clone.refresh_set_status(&set_name_clone).await;
+                        manager_clone
+                            .refresh_related_sets(&target, &set_name_clone)
+                            .await;
+
+                        {
+                            let mut jobs_lock = jobs.lock().await;
+                            if let Some(job) = jobs_lock.get_mut(&set_name) {
+                                job.state = JobState::Idle;
+                            }
+                        }
+
+                        // Queued here rather than on the event, which a lagging
+                        // subscriber may miss; the maintenance task runs them
+                        manager.queue_prune(&set_name).await;
+                        manager.start_auto_replication(&set_name).await;
+
+                        // Broadcast completion event
+                        let _ = event_tx.send(Response::Event(ResponseData::BackupComplete {
+                            set_name: set_name.clone(),
+                            snapshot_id: backup_result.snapshot_id.clone(),
+                            added_bytes: backup_result.added_bytes,
+                            duration_secs: backup_result.duration_secs,
+                        }));
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let err_msg = e.to_string();
+                    error!("Backup job error for set {}: {}", set_name, err_msg);
+
+                    let retrying = {
+                        let mut jobs_lock = jobs.lock().await;
+                        match jobs_lock.get_mut(&set_name) {
+                            Some(job) => {
+                                manager.settle_journal(&set_name, job, false);
+                                manager.schedule_retry(&set_name, job)
+                            }
+                            None => false,
+                        }
+                    };
+
+                    if !retrying && !manager.record_digest_failure(&set_name) {
+                        manager.notify(
+                            &backup_set,
+                            NotifyEvent::Failure,
+                            "Backup Failed",
+                            &format!("Internal error backing up set '{}': {}", set_name, err_msg),
+                            "dialog-error",
+                        );
+                    }
+
+                    // Broadcast failure event
+                    let _ = event_tx.send(Response::Event(ResponseData::BackupFailed {
+                        set_name: set_name.clone(),
+                        error: err_msg,
+                    }));
+
+                    if retrying {
+                        continue;
+                    }
+                    break;
+                }
+            }
+        }
+        // Worker is exiting, clear the active flag
+        let mut jobs_lock = jobs.lock().await;
+        if let Some(job) = jobs_lock.get_mut(&set_name) {
+            job.worker_active = false;
+        }
+    }
+
+    /// Health of all sets for monitoring: every issue found, and the worst
+    /// of their levels.
+    pub async fn health(&self, now: DateTime<Utc>) -> (HealthLevel, Vec<HealthIssue>) {
+        let max_age_hours = self.global_max_age_hours.load(Ordering::Relaxed);
+        let jobs = self.jobs.lock().await;
+        let mut names: Vec<&String> = jobs.keys().collect();
+        names.sort();
+        let issues: Vec<HealthIssue> = names
+            .into_iter()
+            .flat_map(|name| jobs[name].health_issues(max_age_hours, now))
+            .collect();
+        let overall = issues.iter().map(|i| i.level).max().unwrap_or_default();
+        (overall, issues)
+    }
+
+    /// One-line summary of all sets for status bars.
+    pub async fn status_line(&self, now: DateTime<Utc>) -> CompactStatus {
+        let (overall, issues) = self.health(now).await;
+        let mut sets = self.get_status().await;
+        sets.sort_by(|a, b| a.name.cmp(&b.name));
+        compact_status(&sets, overall, &issues, now)
+    }
+
+    /// Forgets mounts that are gone and broadcasts `Unmounted` for them: the
+    /// mount process exited and the path is no longer a mount point, or a
+    /// mount adopted after a restart was unmounted outside the daemon. Mount
+    /// points are checked outside the jobs lock. Called by `mount_monitor`,
+    /// which keeps `get_status` free of process and filesystem checks.
+    pub async fn check_mounts(&self) {
+        let candidates: Vec<(String, String, PathBuf, bool)> = {
+            let mut jobs = self.jobs.lock().await;
+            let mut candidates = Vec::new();
+            for (name, job) in jobs.iter_mut() {
+                for (key, mount) in job.mounts.iter_mut() {
+                    let mut exited = false;
+                    if let Some(ref mut child) = mount.process {
+                        match child.try_wait() {
+                            Ok(Some(status)) => {
+                                warn!(
+                                    "Mount process for snapshot {} of set {} exited unexpectedly with status: {}",
+                                    key, name, status
+                                );
+                                mount.process = None;
+                                exited = true;
+                            }
+                            // Still running
+                            Ok(None) => continue,
+                            Err(e) => {
+                                error!(
+                                    "Error checking mount process for snapshot {} of set {}: {}",
+                                    key, name, e
+                                );
+                                continue;
+                            }
+                        }
+                    }
+                    candidates.push((name.clone(), key.clone(), mount.path.clone(), exited));
+                }
+            }
+            candidates
+        };
+        if candidates.is_empty() {
+            return;
+        }
+        let paths: Vec<PathBuf> = candidates
+            .iter()
+            .map(|(_, _, path, _)| path.clone())
+            .collect();
+        let mounted = tokio::task::spawn_blocking(move || {
+            paths
+                .iter()
+                .map(|path| vigil_lib::paths::is_mount_point(path))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        let mut jobs = self.jobs.lock().await;
+        for (i, (name, key, path, exited)) in candidates.iter().enumerate() {
+            if mounted.get(i).copied().unwrap_or(true) {
+                if *exited {
+                    info!(
+                        "Mount of snapshot {} for set {} still active after process exit (orphaned mount)",
+                        key, name
+                    );
+                }
+                continue;
+            }
+            let Some(job) = jobs.get_mut(name) else {
+                continue;
+            };
+            // Skip a snapshot mounted again while the lock was released
+            if !job
+                .mounts
+                .get(key)
+                .is_some_and(|m| m.process.is_none() && &m.path == path)
+            {
+                continue;
+            }
+            debug!(
+                "Snapshot {} of set '{}' was marked mounted but mount no longer exists, clearing state",
+                key, name
+            );
+            job.mounts.remove(key);
+            self.broadcast_unmounted(name, key, UnmountReason::Lost);
+        }
+    }
+
+    /// Status of all backup sets, built from cached state only. Mount
+    /// liveness is kept current by `check_mounts`.
+    pub async fn get_status(&self) -> Vec<SetStatus> {
+        let verify_max_age = self.global_verify_max_age.load(Ordering::Relaxed);
+        let now = Utc::now();
+        let jobs = self.jobs.lock().await;
+
+        let mut statuses = Vec::new();
+        for job in jobs.values() {
+            statuses.push(SetStatus {
+                name: job.set.name.clone(),
+                state: job.reported_state(Instant::now()),
+                last_backup: job.last_backup.clone(),
+                source_paths: {
+                    let mut paths = Vec::new();
+                    if let Some(ref s) = job.set.source {
+                        paths.push(s.into());
+                    }
+                    if let Some(ref ss) = job.set.sources {
+                        for s in ss {
+                            paths.push(s.into());
+                        }
+                    }
+                    if let Some(ref list) = job.set.files_from {
+                        paths.push(list.into());
+                    }
+                    paths
+                },
+                target: job.set.target.clone().into(),
+                is_mounted: !job.mounts.is_empty(),
+                mount_warming: job.mounts.values().any(|m| m.prefetch.is_some()),
+                mounts: job
+                    .mounts
+                    .iter()
+                    .map(|(key, mount)| MountInfo {
+                        snapshot: key.clone(),
+                        path: mount.path.clone(),
+                        warming: mount.prefetch.is_some(),
+                    })
+                    .collect(),
+                pending_changes: job.journal.len()
+                    + job.journal_in_flight.as_ref().map_or(0, ChangeJournal::len),
+                snapshot_count: job.snapshot_count,
+                total_bytes: job.total_bytes,
+                initializing: !job.status_loaded,
+                next_run: job.next_run,
+                last_verified: job.last_verified,
+                verification_stale: is_verification_stale(job.last_verified, verify_max_age, now),
+                append_only: job.is_append_only(),
+                skipped_trivial: job.skipped_trivial,
+                target_free_bytes: job.target_free_bytes,
+                growth_bytes_per_day: job.growth_bytes_per_day,
+                watch_events_coalesced: job.watch_coalesced,
+                watch_events_dropped: job.watch_dropped,
+                recent_changes: job.recent_changes.clone(),
+                replica_lag: job.replica_lag.clone(),
+            });
+        }
+        statuses
+    }
+
+    /// Lists a set's snapshots, oldest first. With `tag`, only snapshots
+    /// carrying it are listed, and `limit` counts matching snapshots.
+    pub async fn get_snapshots(
+        &self,
+        set_name: &str,
+        limit: Option<usize>,
+        filter: &SnapshotFilter,
+    ) -> Result<Vec<SnapshotInfo>> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+
+        let engine = self.engine(&set);
+        // A filter has to see every snapshot before the newest matches are kept
+        let mut snapshots = engine
+            .snapshots(
+                &set.target,
+                if filter.is_empty() { limit } else { None },
+                Some(self.shutdown_token.clone()),
+            )
+            .await?;
+        if !filter.is_empty() {
+            snapshots.retain(|s| filter.matches(s));
+            if let Some(limit) = limit {
+                let excess = snapshots.len().saturating_sub(limit);
+                snapshots.drain(..excess);
+            }
+        }
+        self.fill_snapshot_sizes(set_name, &set.target, &engine, &mut snapshots)
+            .await;
+        Ok(snapshots)
+    }
+
+    /// Appends a finished backup attempt to the set's history.
+    fn record_run(set_name: &str, started_at: DateTime<Utc>, result: &Result<BackupResult>) {
+        let run = match result {
+            Ok(r) => BackupRun {
+                set_name: set_name.to_string(),
+                started_at,
+                finished_at: Utc::now(),
+                success: r.success,
+                snapshot_id: Some(r.snapshot_id.clone()).filter(|id| r.success && !id.is_empty()),
+                added_bytes: r.added_bytes,
+                error: r.error_message.clone(),
+                skipped_trivial: false,
+            },
+            Err(e) => BackupRun {
+                set_name: set_name.to_string(),
+                started_at,
+                finished_at: Utc::now(),
+                success: false,
+                snapshot_id: None,
+                added_bytes: 0,
+                error: Some(e.to_string()),
+                skipped_trivial: false,
+            },
+        };
+        if let Err(e) = history::append(&vigil_lib::paths::history_path(set_name), &run) {
+            warn!(
+                "Failed to record backup history for set {}: {:#}",
+                set_name, e
+            );
+        }
+    }
+
+    /// Records a debounced backup skipped for too few changes in the set's history.
+    fn record_skip(set_name: &str) {
+        let now = Utc::now();
+        let run = BackupRun {
+            set_name: set_name.to_string(),
+            started_at: now,
+            finished_at: now,
+            success: true,
+            snapshot_id: None,
+            added_bytes: 0,
+            error: None,
+            skipped_trivial: true,
+        };
+        if let Err(e) = history::append(&vigil_lib::paths::history_path(set_name), &run) {
+            warn!(
+                "Failed to record backup history for set {}: {:#}",
+                set_name, e
+            );
+        }
+    }
+
+    /// The set's recorded backup runs and restores of the kinds in `ops`
+    /// (backups only when empty), each oldest first; `limit` keeps the newest
+    /// of each.
+    pub async fn history(
+        &self,
+        set_name: &str,
+        limit: Option<usize>,
+        ops: &[HistoryOp],
+    ) -> Result<(Vec<BackupRun>, Vec<RestoreRecord>)> {
+        if !self.jobs.lock().await.contains_key(set_name) {
+            anyhow::bail!("Unknown backup set: {}", set_name);
+        }
+        let backups = ops.is_empty() || ops.contains(&HistoryOp::Backup);
+        let restores = ops.contains(&HistoryOp::Restore);
+        let runs_path = vigil_lib::paths::history_path(set_name);
+        let restores_path = vigil_lib::paths::restore_history_path(set_name);
+        Ok(tokio::task::spawn_blocking(move || {
+            let runs = if backups {
+                history::read(&runs_path, limit)
+            } else {
+                Vec::new()
+            };
+            let restores = if restores {
+                history::read(&restores_path, limit)
+            } else {
+                Vec::new()
+            };
+            (runs, restores)
+        })
+        .await?)
+    }
+
+    /// Repository statistics of a set, with the size of every snapshot.
+    pub async fn stats(&self, set_name: &str) -> Result<(RepoStats, Vec<SnapshotInfo>)> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let stats = self
+            .engine(&set)
+            .stats(&set.target, Some(self.shutdown_token.clone()))
+            .await?;
+        let snapshots = self
+            .get_snapshots(set_name, None, &SnapshotFilter::default())
+            .await?;
+        Ok((stats, snapshots))
+    }
+
+    /// Builds and signs an attestation of a set's repository: its ID and
+    /// snapshots as the engine reports them now, the last deep verification
+    /// and the tool versions. The signing key is created on first use.
+    pub async fn attest(&self, set_name: &str) -> Result<SignedAttestation> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let engine = self.engine(&set);
+        let repository_id = engine
+            .repo_id(&set.target, Some(self.shutdown_token.clone()))
+            .await?;
+        let snapshots = self
+            .get_snapshots(set_name, None, &SnapshotFilter::default())
+            .await?;
+        let attestation = Attestation {
+            format: ATTESTATION_FORMAT,
+            set_name: set.name.clone(),
+            hostname: executor::local_hostname(),
+            created_at: Utc::now(),
+            repository: set.target.clone(),
+            repository_id,
+            snapshot_count: snapshots.len(),
+            latest_snapshot: snapshots.last().map(|s| AttestedSnapshot {
+                id: s.id.clone(),
+                time: s.timestamp,
+            }),
+            last_check: load_verification(&set.name).last_check,
+            tools: ToolVersions {
+                vigil: env!("CARGO_PKG_VERSION").to_string(),
+                engine: set.engine.binary().to_string(),
+                engine_version: engine.version().await?,
+            },
+        };
+        let key = attest::load_or_create_key(&vigil_lib::paths::attestation_key_path())?;
+        SignedAttestation::sign(attestation, &key)
+    }
+
+    /// Removes empty mount directories left behind by removed or renamed sets.
+    pub async fn clean_mount_dirs(&self, dry_run: bool) -> CleanReport {
+        let set_names: HashSet<String> = self.jobs.lock().await.keys().cloned().collect();
+        let report =
+            janitor::clean_mount_dirs(&vigil_lib::paths::mount_base_dir(), &set_names, dry_run);
+        if !dry_run {
+            for path in &report.removed {
+                info!("Removed stale mount directory {:?}", path);
+            }
+        }
+        report
+    }
+
+    /// Resolves `set_name` (or all sets when `None`) to their configurations, sorted
+    /// by name, each paired with whether a backup is running against the same repository.
+    async fn repo_targets(&self, set_name: Option<String>) -> Result<Vec<(BackupSet, bool)>> {
+        let jobs = self.jobs.lock().await;
+        let running_targets: Vec<&str> = jobs
+            .values()
+            .filter(|j| matches!(j.state, JobState::Running { .. }))
+            .map(|j| j.set.target.as_str())
+            .collect();
+
+        let mut sets: Vec<BackupSet> = match set_name {
+            Some(name) => match jobs.get(&name) {
+                Some(job) => vec![job.set.clone()],
+                None => anyhow::bail!("Unknown backup set: {}", name),
+            },
+            None => jobs.values().map(|job| job.set.clone()).collect(),
+        };
+        sets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(sets
+            .into_iter()
+            .map(|set| {
+                let busy = running_targets.contains(&set.target.as_str());
+                (set, busy)
+            })
+            .collect())
+    }
+
+    /// Initializes the repositories of one or all sets. Repositories a backup is
+    /// currently writing to are skipped and reported as failed.
+    pub async fn init_repos(&self, set_name: Option<String>) -> Result<Vec<RepoOpResult>> {
+        let mut results = Vec::new();
+        for (set, busy) in self.repo_targets(set_name).await? {
+            let name = set.name.clone();
+            let target = &set.target;
+            let (status, error) = if busy {
+                (
+                    RepoOpStatus::Failed,
+                    Some("A backup is currently running against this repository. Try again when it finishes.".to_string()),
+                )
+            } else {
+                match self.engine(&set).init(target).await {
+                    Ok(()) => {
+                        info!("Initialized repository for set '{}' at {}", name, target);
+                        (RepoOpStatus::Initialized, None)
+                    }
+                    Err(e) if is_already_initialized(&e.to_string()) => {
+                        (RepoOpStatus::AlreadyInitialized, None)
+                    }
+                    Err(e) => {
+                        warn!("Failed to initialize repository for set '{}': {}", name, e);
+                        (RepoOpStatus::Failed, Some(e.to_string()))
+                    }
+                }
+            };
+            results.push(RepoOpResult {
+                set_name: name,
+                status,
+                error,
+                deferred_until: None,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Checks that the repositories of one or all sets can be opened by listing
+    /// the latest snapshot.
+    pub async fn check_repos(&self, set_name: Option<String>) -> Result<Vec<RepoOpResult>> {
+        let mut results = Vec::new();
+        for (set, _) in self.repo_targets(set_name).await? {
+            let name = set.name.clone();
+            info!("Checking repository for set '{}'", name);
+            let (status, error) = match self
+                .engine(&set)
+                .snapshots(&set.target, Some(1), Some(self.shutdown_token.clone()))
+                .await
+            {
+                Ok(_) => (RepoOpStatus::Accessible, None),
+                Err(e) => {
+                    warn!("Repository check failed for set '{}': {}", name, e);
+                    (RepoOpStatus::Failed, Some(e.to_string()))
+                }
+            };
+            results.push(RepoOpResult {
+                set_name: name,
+                status,
+                error,
+                deferred_until: None,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Verifies the repositories of one or all sets with a deep check that reads
+    /// back `subset` of the data, broadcasting `VerifyProgress` while it runs.
+    /// Repositories a backup is currently writing to are skipped and reported as failed.
+    pub async fn verify_repos(
+        &self,
+        set_name: Option<String>,
+        subset: &str,
+        now: bool,
+    ) -> Result<Vec<RepoOpResult>> {
+        let mut results = Vec::new();
+        for (set, busy) in self.repo_targets(set_name).await? {
+            let name = set.name.clone();
+            let started = Utc::now();
+            let opens = self
+                .maintenance_window(&set)
+                .map(|window| window.next_open(started))
+                .filter(|at| *at > started && !now);
+            if let Some(at) = opens {
+                info!(
+                    "Deferring deep check of set '{}' to the maintenance window at {}",
+                    name, at
+                );
+                let mut jobs = self.jobs.lock().await;
+                if let Some(job) = jobs.get_mut(&name) {
+                    job.verify_pending = Some(subset.to_string());
+                    self.save_state(&jobs);
+                }
+                drop(jobs);
+                results.push(RepoOpResult {
+                    set_name: name,
+                    status: RepoOpStatus::Deferred,
+                    error: None,
+                    deferred_until: Some(at),
+                });
+                continue;
+            }
+            let (status, error) = if busy {
+                (
+                    RepoOpStatus::Failed,
+                    Some("A backup is currently running against this repository. Try again when it finishes.".to_string()),
+                )
+            } else {
+                info!("Verifying repository for set '{}'", name);
+                match self.verify_set(&set, subset).await {
+                    Ok(()) => (RepoOpStatus::Verified, None),
+                    Err(e) => {
+                        warn!("Repository verification failed for set '{}': {}", name, e);
+                        (RepoOpStatus::Failed, Some(e.to_string()))
+                    }
+                }
+            };
+            results.push(RepoOpResult {
+                set_name: name,
+                status,
+                error,
+                deferred_until: None,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Starts deep checks queued for a maintenance window once it is open and
+    /// no backup, prune or replication uses the repository. Returns when the
+    /// earliest window still holding a check back opens.
+    pub async fn run_pending_verifications(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // The replacing daemon restored the queue from `state.json`
+        if self.is_handing_off() {
+            return None;
+        }
+        let mut ready = Vec::new();
+        let mut earliest: Option<DateTime<Utc>> = None;
+        {
+            let mut jobs = self.jobs.lock().await;
+            let pruning = self
+                .active_prunes
+                .lock()
+                .map(|active| active.clone())
+                .unwrap_or_default();
+            let replicating: HashSet<String> = self
+                .replications
+                .lock()
+                .map(|active| active.keys().cloned().collect())
+                .unwrap_or_default();
+            let mut busy_targets: HashSet<String> = jobs
+                .values()
+                .filter(|job| {
+                    matches!(job.state, JobState::Running { .. })
+                        || pruning.contains(&job.set.name)
+                        || replicating.contains(&job.set.name)
+                })
+                .map(|job| job.set.target.clone())
+                .collect();
+            for (name, job) in jobs.iter_mut() {
+                if job.verify_pending.is_none() {
+                    continue;
+                }
+                let opens = self
+                    .maintenance_window(&job.set)
+                    .map_or(now, |window| window.next_open(now));
+                if opens > now {
+                    earliest = Some(earliest.map_or(opens, |e| e.min(opens)));
+                    continue;
+                }
+                if !busy_targets.insert(job.set.target.clone()) {
+                    debug!(
+                        "Repository of set {} is busy, keeping its deep check queued",
+                        name
+                    );
+                    continue;
+                }
+                if let Some(subset) = job.verify_pending.take() {
+                    // Holds the repository like a prune, so no prune, backup
+                    // or replication starts on it while the check runs
+                    ready.push((job.set.clone(), subset, self.track_prune(name)));
+                }
+            }
+            if !ready.is_empty() {
+                self.save_state(&jobs);
+            }
+        }
+
+        for (set, subset, guard) in ready {
+            let manager = self.clone();
+            tokio::spawn(async move {
+                let _slot = manager.acquire_job_slot(&set.name).await;
+                if manager.shutdown_token.is_cancelled() {
+                    return;
+                }
+                info!("Running deferred deep check of set '{}'", set.name);
+                if let Err(e) = manager.verify_set(&set, &subset).await {
+                    warn!(
+                        "Repository verification failed for set '{}': {}",
+                        set.name, e
+                    );
+                }
+                drop(guard);
+            });
+        }
+        earliest
+    }
+
+    /// Removes a set's stale repository locks, or with `remove_all` every
+    /// lock. Refused while this daemon writes to the repository, since its
+    /// own backup's lock would be removed too.
+    pub async fn unlock(&self, set_name: &str, remove_all: bool) -> Result<ResponseData> {
+        let Some((set, busy)) = self.repo_targets(Some(set_name.to_string())).await?.pop() else {
+            anyhow::bail!("Unknown backup set: {}", set_name);
+        };
+        if busy {
+            return Err(RepoBusy { set_name: set.name }.into());
+        }
+
+        let engine = self.engine(&set);
+        let token = Some(self.shutdown_token.clone());
+        let before = engine.list_locks(&set.target, token.clone()).await?;
+        engine
+            .unlock(&set.target, remove_all, token.clone())
+            .await?;
+        let remaining = engine.list_locks(&set.target, token).await?;
+        info!(
+            "Unlocked repository of set {}: {} lock(s) removed, {} remaining",
+            set.name,
+            before.saturating_sub(remaining),
+            remaining
+        );
+        Ok(ResponseData::Unlocked {
+            set_name: set.name,
+            removed: before.saturating_sub(remaining),
+            remaining,
+        })
+    }
+
+    /// The repository a purge of `set_name` would delete, and whether a backup
+    /// or prune is using it. Only a set in the config, or one removed from it
+    /// whose repository `state.json` recorded, can be purged.
+    async fn purge_target(&self, set_name: &str) -> Result<(String, bool)> {
+        let jobs = self.jobs.lock().await;
+        let target = match jobs.get(set_name) {
+            Some(job) => job.set.target.clone(),
+            None => {
+                let untracked = self
+                    .untracked_targets
+                    .lock()
+                    .ok()
+                    .and_then(|untracked| untracked.get(set_name).cloned());
+                let target = untracked.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown backup set '{}'; the daemon has no record of its repository",
+                        set_name
+                    )
+                })?;
+                if let Some(other) = jobs.values().find(|j| j.set.target == target) {
+                    anyhow::bail!(
+                        "Repository {} is used by set '{}'; refusing to purge it",
+                        target,
+                        other.set.name
+                    );
+                }
+                target
+            }
+        };
+        let pruning = self
+            .active_prunes
+            .lock()
+            .is_ok_and(|active| active.contains(set_name));
+        let busy = pruning
+            || jobs
+                .values()
+                .any(|j| j.set.target == target && matches!(j.state, JobState::Running { .. }));
+        Ok((target, busy))
+    }
+
+    /// First step of a purge: logs the intent and issues a short-lived,
+    /// single-use token that `purge` requires.
+    pub async fn purge_token(&self, set_name: &str) -> Result<ResponseData> {
+        let (target, _) = self.purge_target(set_name).await?;
+        let token = self
+            .purge_tokens
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Purge token store poisoned"))?
+            .issue(set_name, &target, std::time::Instant::now())?;
+        warn!(
+            "Deletion of set {}'s repository {} requested; awaiting confirmation for {}s",
+            set_name,
+            target,
+            purge::TOKEN_TTL.as_secs()
+        );
+        Ok(ResponseData::PurgeToken {
+            set_name: set_name.to_string(),
+            target,
+            token,
+            expires_in_secs: purge::TOKEN_TTL.as_secs(),
+        })
+    }
+
+    /// Consumes `token` for `set_name` and returns the confirmed intent.
+    /// Refused while a backup or prune uses the repository; the token stays
+    /// valid then.
+    async fn redeem_purge_token(&self, set_name: &str, token: &str) -> Result<PurgeIntent> {
+        let target = {
+            let tokens = self
+                .purge_tokens
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Purge token store poisoned"))?;
+            tokens.peek(set_name, token, std::time::Instant::now())
+        };
+        let Some(target) = target else {
+            return Err(InvalidPurgeToken {
+                set_name: set_name.to_string(),
+            }
+            .into());
+        };
+        let (current, busy) = self.purge_target(set_name).await?;
+        if current != target {
+            return Err(InvalidPurgeToken {
+                set_name: set_name.to_string(),
+            }
+            .into());
+        }
+        if busy {
+            return Err(RepoBusy {
+                set_name: set_name.to_string(),
+            }
+            .into());
+        }
+        let intent = self
+            .purge_tokens
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Purge token store poisoned"))?
+            .redeem(set_name, token, std::time::Instant::now())
+            .ok_or_else(|| InvalidPurgeToken {
+                set_name: set_name.to_string(),
+            })?;
+        Ok(intent)
+    }
+
+    /// Deletes a set's repository, mount point and cached metadata once
+    /// `token` confirms the intent recorded by `purge_token`.
+    pub async fn purge(&self, set_name: &str, token: &str) -> Result<ResponseData> {
+        let intent = self.redeem_purge_token(set_name, token).await?;
+        warn!("Purging set {} (repository {})", set_name, intent.target);
+        if let Err(e) = self.unmount(Some(set_name.to_string()), None).await {
+            debug!("Unmount before purging {}: {}", set_name, e);
+        }
+        let repository_deleted = purge::delete_repository(&intent.target)?;
+        purge::remove_local_state(set_name).await;
+        let forgotten = self
+            .untracked_targets
+            .lock()
+            .is_ok_and(|mut untracked| untracked.remove(set_name).is_some());
+        if forgotten {
+            self.save_state(&*self.jobs.lock().await);
+        }
+        info!("Purged set {}", set_name);
+        Ok(ResponseData::Purged {
+            set_name: set_name.to_string(),
+            target: intent.target,
+            repository_deleted,
+        })
+    }
+
+    /// Replaces a tracked set's local repository with a freshly initialized
+    /// one once `token` from `purge_token` confirms it. The old repository is
+    /// moved aside, or deleted with `discard_old`. Metrics, history and the
+    /// last verification describe the old repository and are reset.
+    pub async fn reinit(
+        &self,
+        set_name: &str,
+        token: &str,
+        discard_old: bool,
+    ) -> Result<ResponseData> {
+        let set = match self.jobs.lock().await.get(set_name) {
+            Some(job) => job.set.clone(),
+            None => anyhow::bail!("Unknown backup set: {}", set_name),
+        };
+        if Backend::of(&set.target).is_remote() {
+            anyhow::bail!(
+                "Repository {} is remote; delete it with your storage provider's tools, then run init",
+                set.target
+            );
+        }
+        let intent = self.redeem_purge_token(set_name, token).await?;
+
+        warn!(
+            "Reinitializing set {} (repository {})",
+            set_name, intent.target
+        );
+        if let Err(e) = self.unmount(Some(set_name.to_string()), None).await {
+            debug!("Unmount before reinitializing {}: {}", set_name, e);
+        }
+        let archived_to = if discard_old {
+            purge::delete_repository(&intent.target)?;
+            None
+        } else {
+            Some(purge::archive_repository(&intent.target, Utc::now())?)
+        };
+        purge::remove_local_state(set_name).await;
+        history::remove(&vigil_lib::paths::history_path(set_name));
+        let _ = std::fs::remove_file(vigil_lib::paths::verification_path(set_name));
+        {
+            let mut jobs = self.jobs.lock().await;
+            if let Some(job) = jobs.get_mut(set_name) {
+                job.last_backup = None;
+                job.snapshot_count = Some(0);
+                job.total_bytes = None;
+                job.last_verified = None;
+                job.append_only_detected = false;
+                job.repo_unreachable = false;
+                job.retry_attempt = 0;
+                job.retry_at = None;
+                job.growth_bytes_per_day = None;
+                if job.state == JobState::Error {
+                    job.state = JobState::Idle;
+                }
+            }
+            self.save_state(&jobs);
+        }
+
+        self.engine(&set).init(&intent.target).await?;
+        info!(
+            "Initialized fresh repository for set {} at {}",
+            set_name, intent.target
+        );
+        let manager = self.clone();
+        let name = set_name.to_string();
+        tokio::spawn(async move {
+            manager.refresh_set_status(&name).await;
+        });
+        Ok(ResponseData::Reinitialized {
+            set_name: set_name.to_string(),
+            target: intent.target,
+            archived_to: archived_to.map(|p| p.to_string_lossy().to_string()),
+        })
+    }
+
+    /// Runs one step of a guided repair on a set's repository, broadcasting
+    /// each line of restic output as `RepairProgress`. A `Check` that finds
+    /// errors is an unsuccessful outcome, not an error: it is what a repair
+    /// starts from. Refused while a backup writes to the repository.
+    pub async fn repair(&self, set_name: &str, step: RepairStep) -> Result<RepairOutcome> {
+        let Some((set, busy)) = self.repo_targets(Some(set_name.to_string())).await?.pop() else {
+            anyhow::bail!("Unknown backup set: {}", set_name);
+        };
+        if busy {
+            return Err(RepoBusy { set_name: set.name }.into());
+        }
+
+        let on_line = {
+            let event_tx = self.event_tx.clone();
+            let set_name = set.name.clone();
+            move |line: &str| {
+                let _ = event_tx.send(Response::Event(ResponseData::RepairProgress {
+                    set_name: set_name.clone(),
+                    step,
+                    line: line.to_string(),
+                }));
+            }
+        };
+        let result = self
+            .engine(&set)
+            .repair(
+                &set.target,
+                step,
+                Some(self.shutdown_token.clone()),
+                on_line,
+            )
+            .await;
+        let (success, output) = match result {
+            Ok(output) => (true, output),
+            Err(e) if step == RepairStep::Check => {
+                warn!("Repository check found errors for set '{}'", set.name);
+                (false, e.to_string())
+            }
+            Err(e) => return Err(e),
+        };
+        if step == RepairStep::Snapshots {
+            // Rewritten snapshots have new IDs
+            let manager = self.clone();
+            let name = set.name.clone();
+            tokio::spawn(async move {
+                manager.refresh_set_status(&name).await;
+            });
+        }
+        Ok(RepairOutcome {
+            set_name: set.name,
+            step,
+            success,
+            output,
+        })
+    }
+
+    async fn verify_set(&self, set: &BackupSet, subset: &str) -> Result<()> {
+        let (progress_tx, mut progress_rx) = tokio::sync::watch::channel::<Option<Progress>>(None);
+        let progress_task = {
+            let event_tx = self.event_tx.clone();
+            let set_name = set.name.clone();
+            tokio::spawn(async move {
+                while progress_rx.changed().await.is_ok() {
+                    let Some(progress) = progress_rx.borrow_and_update().clone() else {
+                        continue;
+                    };
+                    let _ = event_tx.send(Response::Event(ResponseData::VerifyProgress {
+                        set_name: set_name.clone(),
+                        percent: progress.percent,
+                        eta_secs: progress.eta_secs,
+                    }));
+                    tokio::time::sleep(PROGRESS_EVENT_INTERVAL).await;
+                }
+            })
+        };
+
+        let result = self
+            .engine(set)
+            .verify(
+                &set.target,
+                subset,
+                Some(self.shutdown_token.clone()),
+                Some(progress_tx),
+            )
+            .await;
+        progress_task.abort();
+        if self.shutdown_token.is_cancelled() {
+            // Interrupted, not failed: keep the previous outcome
+            return result;
+        }
+
+        let outcome = VerifyOutcome {
+            checked_at: Utc::now(),
+            subset: subset.to_string(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        let mut record = load_verification(&set.name);
+        if outcome.success {
+            record.verified_at = Some(outcome.checked_at);
+        }
+        let verified_at = record.verified_at;
+        record.last_check = Some(outcome);
+        if let Err(e) = save_verification(&set.name, &record) {
+            warn!("Failed to record verification of set '{}': {}", set.name, e);
+        }
+        if result.is_ok() {
+            if let Some(job) = self.jobs.lock().await.get_mut(&set.name) {
+                job.last_verified = verified_at;
+            }
+        }
+        result
+    }
+
+    /// Detailed information about a set, including the hosts writing to its repository.
+    pub async fn get_info(&self, set_name: &str) -> Result<SetInfo> {
+        let (set, pending_paths) = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => {
+                    let mut pending = job.journal.sample(PENDING_SAMPLE_SIZE);
+                    if let Some(ref in_flight) = job.journal_in_flight {
+                        pending.extend(in_flight.sample(PENDING_SAMPLE_SIZE));
+                    }
+                    pending.sort();
+                    pending.dedup();
+                    pending.truncate(PENDING_SAMPLE_SIZE);
+                    (job.set.clone(), pending)
+                }
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+
+        let snapshots = self
+            .engine(&set)
+            .snapshots(&set.target, None, Some(self.shutdown_token.clone()))
+            .await?;
+        let local_hostname = executor::local_hostname();
+        let hosts = group_by_host(&snapshots, &local_hostname);
+
+        let status = self
+            .get_status()
+            .await
+            .into_iter()
+            .find(|s| s.name == set_name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown backup set: {}", set_name))?;
+
+        let retention = self.with_effective_retention(&set).await.retention;
+
+        Ok(SetInfo {
+            status,
+            shared_repo: set.shared_repo,
+            local_hostname,
+            hosts,
+            pending_paths,
+            retention,
+        })
+    }
+
+    /// Fills in sizes for snapshots without a restic summary, using the sidecar
+    /// cache and computing (then caching) any sizes not seen before.
+    async fn fill_snapshot_sizes(
+        &self,
+        set_name: &str,
+        target: &str,
+        engine: &Engine,
+        snapshots: &mut [SnapshotInfo],
+    ) {
+        if snapshots.iter().all(|s| s.total_bytes.is_some()) {
+            return;
+        }
+
+        let _guard = self.stats_cache_lock.lock().await;
+        let mut cache =
+            SnapshotStatsCache::load(&vigil_lib::paths::snapshot_stats_cache_path(set_name));
+        let mut updated = false;
+
+        for snapshot in snapshots.iter_mut().filter(|s| s.total_bytes.is_none()) {
+            if let Some(size) = cache.get(&snapshot.id) {
+                snapshot.total_bytes = Some(size);
+                continue;
+            }
+            match engine
+                .snapshot_size(target, &snapshot.id, Some(self.shutdown_token.clone()))
+                .await
+            {
+                Ok(size) => {
+                    cache.insert(&snapshot.id, size);
+                    snapshot.total_bytes = Some(size);
+                    updated = true;
+                }
+                Err(e) => debug!(
+                    "Failed to compute size of snapshot {} for '{}': {}",
+                    snapshot.short_id, set_name, e
+                ),
+            }
+        }
+
+        if updated {
+            if let Err(e) = cache.save() {
+                warn!(
+                    "Failed to save snapshot stats cache for '{}': {}",
+                    set_name, e
+                );
+            }
+        }
+    }
+
+    /// Mounts a snapshot of a set's repository and returns the mount path, plus
+    /// whether a background prefetch was started to warm the cache. Each
+    /// snapshot gets its own mount, so several can be browsed at once.
+    pub async fn mount(
+        &self,
+        set_name: &str,
+        snapshot_id: Option<String>,
+    ) -> Result<(PathBuf, bool)> {
+        self.capabilities.require(Capability::Fuse)?;
+        let global_depth = *self.global_prefetch_depth.lock().await;
+        let mut jobs = self.jobs.lock().await;
+        if let Some(job) = jobs.get_mut(set_name) {
+            let key = vigil_lib::paths::mount_key(snapshot_id.as_deref());
+            if let Some(mount) = job.mounts.get(&key) {
+                return Ok((mount.path.clone(), mount.prefetch.is_some()));
+            }
+
+            let mount_path =
+                vigil_lib::paths::snapshot_mount_path(set_name, snapshot_id.as_deref());
+            if !mount_path.exists() {
+                std::fs::create_dir_all(&mount_path)?;
+                // Set restrictive permissions for sensitive backup data
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    for dir in [vigil_lib::paths::mount_path(set_name), mount_path.clone()] {
+                        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+                    }
+                }
+            }
+
+            info!(
+                "Mounting snapshot {} of set {} at {:?}",
+                key, set_name, mount_path
+            );
+            let child = self
+                .engine(&job.set)
+                .mount(&job.set.target, snapshot_id.as_deref(), &mount_path)
+                .await?;
+
+            let mut mount = ActiveMount {
+                path: mount_path.clone(),
+                process: Some(child),
+                prefetch: None,
+                used_at: Some(Instant::now()),
+            };
+            let depth = job.set.prefetch_depth(global_depth);
+            if depth > 0 {
+                let token = self.shutdown_token.child_token();
+                mount.prefetch = Some(token.clone());
+                self.spawn_prefetch(
+                    set_name.to_string(),
+                    key.clone(),
+                    prefetch::snapshot_root(&mount_path, snapshot_id.as_deref()),
+                    depth,
+                    token,
+                );
+            }
+            let warming = mount.prefetch.is_some();
+            job.mounts.insert(key, mount);
+
+            Ok((mount_path, warming))
+        } else {
+            anyhow::bail!("Unknown backup set: {}", set_name)
+        }
+    }
+
+    /// Checks that `path` is a regular file within the size limit and starts
+    /// dumping it. Returns the file size and the process writing it to stdout.
+    pub async fn cat(
+        &self,
+        set_name: &str,
+        snapshot_id: &str,
+        path: &str,
+        max_bytes: Option<u64>,
+    ) -> Result<(u64, tokio::process::Child)> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let engine = self.engine(&set);
+        let size = engine
+            .file_size(&set, snapshot_id, path, Some(self.shutdown_token.clone()))
+            .await?;
+        let limit = max_bytes.unwrap_or(CAT_MAX_BYTES).min(CAT_MAX_BYTES);
+        if size > limit {
+            return Err(FileTooLarge { size, limit }.into());
+        }
+        Ok((size, engine.dump(&set, snapshot_id, path)?))
+    }
+
+    /// Lists `path` (the snapshot root if `None`) in a snapshot of `set_name`.
+    /// Returns at most `LIST_FILES_MAX_ENTRIES` entries and whether any were
+    /// left out.
+    pub async fn list_files(
+        &self,
+        set_name: &str,
+        snapshot_id: &str,
+        path: Option<&str>,
+        recursive: bool,
+    ) -> Result<(Vec<FileEntry>, bool)> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let path = path.unwrap_or("/");
+        let mut entries = self
+            .engine(&set)
+            .list_files(
+                &set,
+                snapshot_id,
+                path,
+                recursive,
+                Some(self.shutdown_token.clone()),
+            )
+            .await?;
+        let truncated = entries.len() > LIST_FILES_MAX_ENTRIES;
+        entries.truncate(LIST_FILES_MAX_ENTRIES);
+        Ok((entries, truncated))
+    }
+
+    /// Lists the paths that differ between two snapshots of `set_name`.
+    /// `snapshot_b` defaults to the latest snapshot, which in a shared
+    /// repository means this machine's latest.
+    pub async fn diff(
+        &self,
+        set_name: &str,
+        snapshot_a: &str,
+        snapshot_b: Option<&str>,
+    ) -> Result<SnapshotDiff> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let engine = self.engine(&set);
+        let token = Some(self.shutdown_token.clone());
+        let snapshot_b = snapshot_b.unwrap_or("latest");
+        let tag = set.logical_path_tag();
+        let (snapshot_a, snapshot_b) =
+            if snapshot_a == "latest" || snapshot_b == "latest" || tag.is_some() {
+                let snapshots = engine.snapshots(&set.target, None, token.clone()).await?;
+                let local = executor::local_hostname();
+                let latest = snapshots
+                    .iter()
+                    .rev()
+                    .filter(|s| tag.as_ref().is_none_or(|t| s.tags.contains(t)))
+                    .find(|s| !set.shared_repo || s.hostname.as_deref() == Some(local.as_str()))
+                    .map(|s| s.id.clone());
+                let resolve = |id: &str| -> Result<String> {
+                    if id == "latest" {
+                        latest.clone().ok_or_else(|| {
+                            anyhow::anyhow!("No snapshots found for set '{}'", set_name)
+                        })
+                    } else {
+                        Ok(id.to_string())
+                    }
+                };
+                let (a, b) = (resolve(snapshot_a)?, resolve(snapshot_b)?);
+                let find = |id: &str| snapshots.iter().find(|s| s.id.starts_with(id));
+                match (tag.is_some(), find(&a), find(&b)) {
+                    (true, Some(info_a), Some(info_b)) => {
+                        moved_diff_operands(info_a, info_b).unwrap_or((a, b))
+                    }
+                    _ => (a, b),
+                }
+            } else {
+                (snapshot_a.to_string(), snapshot_b.to_string())
+            };
+        engine
+            .diff(&set.target, &snapshot_a, &snapshot_b, token)
+            .await
+    }
+
+    /// Starts restoring a snapshot of `set_name` into `target` in the background.
+    /// The target must be absolute and either missing or an empty directory.
+    /// Progress, completion, and failure are broadcast as events, and the
+    /// outcome is recorded in the set's restore history with `requested_by`.
+    /// A target inside the set's sources pauses its change-triggered backups
+    /// until `restore_shield_secs` after the restore finishes.
+    pub async fn restore(
+        &self,
+        set_name: &str,
+        snapshot_id: Option<String>,
+        path: Option<String>,
+        target: PathBuf,
+        requested_by: Requester,
+    ) -> Result<ResponseData> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        if !target.is_absolute() {
+            anyhow::bail!("Restore target must be an absolute path: {:?}", target);
+        }
+        if target.exists() && (!target.is_dir() || std::fs::read_dir(&target)?.next().is_some()) {
+            anyhow::bail!(
+                "Restore target {:?} already exists and is not an empty directory",
+                target
+            );
+        }
+
+        let started = ResponseData::RestoreStarted {
+            set_name: set_name.to_string(),
+            snapshot_id: snapshot_id.clone().unwrap_or_else(|| "latest".to_string()),
+            target: target.to_string_lossy().to_string(),
+        };
+        let in_place = set.overlaps_sources(&target);
+        if in_place {
+            self.begin_restore_pause(set_name).await;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let set_name = set.name.clone();
+            manager
+                .run_restore(set, snapshot_id, path, target, requested_by)
+                .await;
+            if in_place {
+                manager.end_restore_pause(&set_name).await;
+            }
+        });
+        Ok(started)
+    }
+
+    /// Pauses the set's change-triggered backups while a restore writes into
+    /// its sources, so the watcher does not back up a half-restored tree.
+    async fn begin_restore_pause(&self, set_name: &str) {
+        let mut jobs = self.jobs.lock().await;
+        let Some(job) = jobs.get_mut(set_name) else {
+            return;
+        };
+        info!(
+            "Restoring into the sources of set {}, pausing change-triggered backups",
+            set_name
+        );
+        job.restores_in_place += 1;
+        self.show_pause(set_name, job);
+    }
+
+    /// Once the last restore into the set's sources finished, keeps its
+    /// change-triggered backups paused for `restore_shield_secs`, so the
+    /// restored files can be checked before they are backed up.
+    async fn end_restore_pause(&self, set_name: &str) {
+        let mut jobs = self.jobs.lock().await;
+        let Some(job) = jobs.get_mut(set_name) else {
+            return;
+        };
+        job.restores_in_place = job.restores_in_place.saturating_sub(1);
+        if job.restores_in_place > 0 {
+            return;
+        }
+        let pause_secs = job
+            .set
+            .restore_shield_secs
+            .unwrap_or(self.global_restore_shield.load(Ordering::Relaxed));
+        job.paused_until =
+            (pause_secs > 0).then(|| Utc::now() + chrono::Duration::seconds(pause_secs as i64));
+        if let Some(until) = job.paused_until {
+            info!(
+                "Change-triggered backups of set {} stay paused until {}",
+                set_name, until
+            );
+            self.show_pause(set_name, job);
+        }
+        self.save_state(&jobs);
+    }
+
+    async fn run_restore(
+        &self,
+        set: BackupSet,
+        snapshot_id: Option<String>,
+        path: Option<String>,
+        target: PathBuf,
+        requested_by: Requester,
+    ) {
+        let started_at = Utc::now();
+        let engine = self.engine(&set);
+        // Resolved up front, so the history names the snapshot that was
+        // restored even if a backup finishes meanwhile
+        let snapshot_id = match snapshot_id {
+            Some(id) => Some(id),
+            None => self.latest_snapshot_id(&engine, &set).await,
+        };
+        let target_str = target.to_string_lossy().to_string();
+        let (progress_tx, mut progress_rx) = tokio::sync::watch::channel::<Option<Progress>>(None);
+        let progress_task = {
+            let event_tx = self.event_tx.clone();
+            let set_name = set.name.clone();
+            let target = target_str.clone();
+            tokio::spawn(async move {
+                while progress_rx.changed().await.is_ok() {
+                    let Some(progress) = progress_rx.borrow_and_update().clone() else {
+                        continue;
+                    };
+                    let _ = event_tx.send(Response::Event(ResponseData::RestoreProgress {
+                        set_name: set_name.clone(),
+                        target: target.clone(),
+                        percent: progress.percent,
+                        eta_secs: progress.eta_secs,
+                    }));
+                    tokio::time::sleep(PROGRESS_EVENT_INTERVAL).await;
+                }
+            })
+        };
+
+        let result = engine
+            .restore(
+                &set,
+                snapshot_id.as_deref(),
+                path.as_deref(),
+                &target,
+                Some(self.shutdown_token.clone()),
+                Some(progress_tx),
+            )
+            .await;
+        progress_task.abort();
+
+        let mut record = RestoreRecord {
+            set_name: set.name.clone(),
+            started_at,
+            finished_at: Utc::now(),
+            snapshot_id: snapshot_id.unwrap_or_else(|| "latest".to_string()),
+            path,
+            target: target_str.clone(),
+            requested_by,
+            success: result.is_ok(),
+            files_restored: 0,
+            bytes_restored: 0,
+            error: None,
+        };
+        let event = match result {
+            Ok(summary) => {
+                record.files_restored = summary.files_restored;
+                record.bytes_restored = summary.bytes_restored;
+                info!(
+                    "Restored set {} into {:?}: {} files, {}",
+                    set.name,
+                    target,
+                    summary.files_restored,
+                    humanize::format_size(summary.bytes_restored)
+                );
+                ResponseData::RestoreComplete {
+                    set_name: set.name.clone(),
+                    target: target_str,
+                    files_restored: summary.files_restored,
+                    bytes_restored: summary.bytes_restored,
+                    duration_secs: summary.duration_secs,
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Restore of set {} into {:?} failed: {}",
+                    set.name, target, e
+                );
+                record.error = Some(e.to_string());
+                ResponseData::RestoreFailed {
+                    set_name: set.name.clone(),
+                    target: target_str,
+                    error: e.to_string(),
+                }
+            }
+        };
+        let _ = self.event_tx.send(Response::Event(event));
+        let path = vigil_lib::paths::restore_history_path(&set.name);
+        if let Err(e) = history::append(&path, &record) {
+            warn!(
+                "Failed to record restore history for set {}: {:#}",
+                set.name, e
+            );
+        }
+    }
+
+    /// ID of the snapshot a restore of "latest" reads: the newest one, or in a
+    /// shared repository this machine's newest. `None` if it cannot be listed.
+    async fn latest_snapshot_id(&self, engine: &Engine, set: &BackupSet) -> Option<String> {
+        let snapshots = match engine
+            .snapshots(&set.target, None, Some(self.shutdown_token.clone()))
+            .await
+        {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                debug!(
+                    "Failed to resolve the latest snapshot of {}: {}",
+                    set.name, e
+                );
+                return None;
+            }
+        };
+        let local = executor::local_hostname();
+        snapshots
+            .iter()
+            .rev()
+            .find(|s| !set.shared_repo || s.hostname.as_deref() == Some(local.as_str()))
+            .map(|s| s.id.clone())
+    }
+
+    /// Walks the mounted snapshot in the background and clears the mount's
+    /// warming flag when done. A cancelled walk leaves the flag to whoever cancelled it.
+    fn spawn_prefetch(
+        &self,
+        set_name: String,
+        key: String,
+        root: PathBuf,
+        depth: u32,
+        token: CancellationToken,
+    ) {
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            debug!(
+                "Prefetching {} levels of {:?} for set {}",
+                depth, root, set_name
+            );
+            let listed = prefetch::run(root, depth, token.clone()).await;
+            if token.is_cancelled() {
+                return;
+            }
+            info!(
+                "Prefetch for set {} finished ({} directories)",
+                set_name, listed
+            );
+            if let Some(mount) = jobs
+                .lock()
+                .await
+                .get_mut(&set_name)
+                .and_then(|job| job.mounts.get_mut(&key))
+            {
+                mount.prefetch = None;
+            }
+        });
+    }
+
+    /// Unmounts one snapshot of a set, or all of the set's snapshots without
+    /// `snapshot_id`, or every mount of every set without `set_name`.
+    pub async fn unmount(
+        &self,
+        set_name: Option<String>,
+        snapshot_id: Option<String>,
+    ) -> Result<()> {
+        let mut jobs = self.jobs.lock().await;
+        let Some(name) = set_name else {
+            if snapshot_id.is_some() {
+                anyhow::bail!("Unmounting a single snapshot needs the set name");
+            }
+            info!("Unmounting all sets");
+            for (name, job) in jobs.iter_mut() {
+                for key in Self::perform_unmount(name, job).await {
+                    self.broadcast_unmounted(name, &key, UnmountReason::Requested);
+                }
+            }
+            return Ok(());
+        };
+        let Some(job) = jobs.get_mut(&name) else {
+            anyhow::bail!("Unknown backup set: {}", name)
+        };
+        let Some(snapshot_id) = snapshot_id else {
+            for key in Self::perform_unmount(&name, job).await {
+                self.broadcast_unmounted(&name, &key, UnmountReason::Requested);
+            }
+            return Ok(());
+        };
+        let key = vigil_lib::paths::mount_key(Some(&snapshot_id));
+        let backup_running = matches!(job.state, JobState::Running { .. });
+        let Some(mount) = job.mounts.remove(&key) else {
+            return Err(NotMounted {
+                set_name: name,
+                snapshot: key,
+            }
+            .into());
+        };
+        Self::release_mount(&name, &key, mount, backup_running).await;
+        self.broadcast_unmounted(&name, &key, UnmountReason::Requested);
+        Ok(())
+    }
+
+    /// Unmounts snapshots nothing has used for their set's idle timeout.
+    /// Whether a mount is in use is checked outside the jobs lock.
+    pub async fn unmount_idle(&self, now: Instant) {
+        let global = self.global_mount_idle_timeout_secs();
+        let candidates: Vec<(String, String, PathBuf)> = {
+            let jobs = self.jobs.lock().await;
+            jobs.iter()
+                .filter(|(_, job)| job.set.mount_idle_timeout(global).is_some())
+                .flat_map(|(name, job)| {
+                    job.mounts
+                        .iter()
+                        .map(|(key, mount)| (name.clone(), key.clone(), mount.path.clone()))
+                })
+                .collect()
+        };
+        if candidates.is_empty() {
+            return;
+        }
+        let paths: Vec<PathBuf> = candidates.iter().map(|(_, _, path)| path.clone()).collect();
+        let in_use = tokio::task::spawn_blocking(move || mounts_in_use(&paths))
+            .await
+            .unwrap_or_default();
+
+        let mut jobs = self.jobs.lock().await;
+        for (i, (name, key, _)) in candidates.iter().enumerate() {
+            let Some(job) = jobs.get_mut(name) else {
+                continue;
+            };
+            let Some(timeout) = job.set.mount_idle_timeout(global) else {
+                continue;
+            };
+            let backup_running = matches!(job.state, JobState::Running { .. });
+            let Some(mount) = job.mounts.get_mut(key) else {
+                continue;
+            };
+            if in_use.get(i).copied().unwrap_or(true) {
+                mount.used_at = Some(now);
+                continue;
+            }
+            // A mount found after a restart counts from when it was first seen
+            let used_at = *mount.used_at.get_or_insert(now);
+            if now.saturating_duration_since(used_at) < Duration::from_secs(timeout) {
+                continue;
+            }
+            info!(
+                "Unmounting snapshot {} of set {} after {} without use",
+                key,
+                name,
+                humanize::format_secs(timeout as f64)
+            );
+            if let Some(mount) = job.mounts.remove(key) {
+                Self::release_mount(name, key, mount, backup_running).await;
+                self.broadcast_unmounted(name, key, UnmountReason::Idle);
+            }
+        }
+    }
+
+    fn broadcast_unmounted(&self, set_name: &str, snapshot: &str, reason: UnmountReason) {
+        let _ = self.event_tx.send(Response::Event(ResponseData::Unmounted {
+            set_name: set_name.to_string(),
+            snapshot: snapshot.to_string(),
+            reason,
+        }));
+    }
+
+    /// The global mount idle timeout, `None` when unset.
+    fn global_mount_idle_timeout_secs(&self) -> Option<u64> {
+        match self.global_mount_idle_timeout.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+
+    /// Runs a backup between the set's pre- and post-backup hooks. A failing
+    /// pre-backup hook skips the backup unless `hook_failure = "continue"`; a
+    /// failing post-backup hook is reported but does not fail the backup.
+    async fn run_backup(&self, set: &BackupSet, token: CancellationToken) -> Result<BackupResult> {
+        let mut hook_outcomes = Vec::new();
+        if let Some(ref command) = set.pre_backup_command {
+            let outcome = hooks::run_hook(HookStage::Pre, command, set, &[], &token).await;
+            let failed = !outcome.success;
+            let reason = hooks::failure_reason(&outcome);
+            hook_outcomes.push(outcome);
+            if failed && set.hook_failure == HookFailure::Abort {
+                return Ok(BackupResult {
+                    snapshot_id: String::new(),
+                    timestamp: Utc::now(),
+                    added_bytes: 0,
+                    duration_secs: 0.0,
+                    success: false,
+                    error_message: Some(reason),
+                    peak_rss_bytes: None,
+                    cpu_secs: None,
+                    hooks: hook_outcomes,
+                });
+            }
+        }
+
+        let (progress_tx, progress_rx) = tokio::sync::watch::channel(None);
+        let progress_task = tokio::spawn(Self::track_progress(
+            self.jobs.clone(),
+            self.event_tx.clone(),
+            set.name.clone(),
+            progress_rx,
+        ));
+        let log = RunLog::create(
+            &vigil_lib::paths::run_log_dir(&set.name),
+            &set.name,
+            Utc::now(),
+        );
+        let mut result = self
+            .run_backup_with_progress(set, token, progress_tx, log.as_ref())
+            .await;
+        progress_task.abort();
+        if let Some(ref log) = log {
+            log.finish(&result, Utc::now());
+            if let Ok(ref mut r) = result {
+                if let Some(ref mut message) = r.error_message {
+                    message.push_str(&format!(" (full output: {})", log.path().display()));
+                }
+            }
+        }
+
+        if let Some(ref command) = set.post_backup_command {
+            let (success, snapshot_id) = match result {
+                Ok(ref r) => (r.success, r.snapshot_id.clone()),
+                Err(_) => (false, String::new()),
+            };
+            let env = [
+                (
+                    "VIGIL_BACKUP_SUCCESS".to_string(),
+                    if success { "1" } else { "0" }.to_string(),
+                ),
+                ("VIGIL_SNAPSHOT_ID".to_string(), snapshot_id),
+            ];
+            // Not tied to shutdown: the hook may have to undo what the pre-backup hook did
+            let outcome = hooks::run_hook(
+                HookStage::Post,
+                command,
+                set,
+                &env,
+                &CancellationToken::new(),
+            )
+            .await;
+            if !outcome.success {
+                self.notify(
+                    set,
+                    NotifyEvent::Failure,
+                    "Backup Hook Failed",
+                    &format!(
+                        "The post-backup command for '{}' failed. Check the daemon logs.",
+                        set.name
+                    ),
+                    "dialog-warning",
+                );
+            }
+            hook_outcomes.push(outcome);
+        }
+
+        if let Ok(ref mut r) = result {
+            r.hooks = hook_outcomes;
+        }
+        result
+    }
+
+    /// Runs the backup itself, reading from a filesystem snapshot when the set
+    /// has `snapshot_source` configured. Failing to remove the snapshot
+    /// afterwards is reported but does not fail the backup.
+    async fn run_backup_with_progress(
+        &self,
+        set: &BackupSet,
+        token: CancellationToken,
+        progress: ProgressSender,
+        log: Option<&RunLog>,
+    ) -> Result<BackupResult> {
+        let canaries = if set.canary {
+            canary::write(set, Utc::now())
+        } else {
+            Vec::new()
+        };
+        let Some(kind) = set.snapshot_source else {
+            let result = self.backup_unlocking(set, token, progress, log).await;
+            return self.verify_canaries(set, result, &canaries).await;
+        };
+        self.capabilities.require(fs_snapshot::capability(kind))?;
+
+        let snapshot = FsSnapshot::create(set, kind).await?;
+        let effective = snapshot.apply(set);
+        let result = self
+            .backup_unlocking(&effective, token, progress, log)
+            .await;
+        let result = self.verify_canaries(&effective, result, &canaries).await;
+        if let Err(e) = snapshot.release().await {
+            error!("{}", e);
+            self.notify(
+                set,
+                NotifyEvent::Failure,
+                "Snapshot Cleanup Failed",
+                &format!(
+                    "The {} snapshot for '{}' could not be removed. Check the daemon logs.",
+                    kind, set.name
+                ),
+                "dialog-warning",
+            );
+        }
+        result
+    }
+
+    /// Runs the engine's backup. When it fails on a locked repository and
+    /// `auto_unlock` is on, stale locks are removed and the backup is retried
+    /// once if no lock is left. Locks held by running operations stay.
+    async fn backup_unlocking(
+        &self,
+        set: &BackupSet,
+        token: CancellationToken,
+        progress: ProgressSender,
+        log: Option<&RunLog>,
+    ) -> Result<BackupResult> {
+        let engine = self.engine(set);
+        let result = engine
+            .backup(set, Some(token.clone()), Some(progress.clone()), log)
+            .await;
+        let locked = matches!(result, Ok(ref r) if !r.success
+            && r.error_message.as_deref().is_some_and(executor::is_lock_error));
+        if !locked || !self.global_auto_unlock.load(Ordering::Relaxed) || token.is_cancelled() {
+            return result;
+        }
+
+        info!(
+            "Repository of set {} is locked, removing stale locks",
+            set.name
+        );
+        let remaining = match engine.unlock(&set.target, false, Some(token.clone())).await {
+            Ok(()) => engine.list_locks(&set.target, Some(token.clone())).await,
+            Err(e) => Err(e),
+        };
+        match remaining {
+            Ok(0) => {
+                info!("Removed stale locks of set {}, retrying backup", set.name);
+                engine.backup(set, Some(token), Some(progress), log).await
+            }
+            Ok(held) => {
+                let mut result = result?;
+                result.error_message = result.error_message.map(|msg| {
+                    format!(
+                        "{}\n{} lock(s) belong to operations that may still be running. If none is, remove them with `vigil unlock {} --all`.",
+                        msg, held, set.name
+                    )
+                });
+                Ok(result)
+            }
+            Err(e) => {
+                warn!("Failed to remove stale locks of set {}: {}", set.name, e);
+                result
+            }
+        }
+    }
+
+    /// Fails a successful backup whose snapshot lacks one of `canaries`.
+    /// `set` is the set as it was backed up.
+    async fn verify_canaries(
+        &self,
+        set: &BackupSet,
+        result: Result<BackupResult>,
+        canaries: &[canary::Canary],
+    ) -> Result<BackupResult> {
+        let mut result = result?;
+        if !result.success || canaries.is_empty() {
+            return Ok(result);
+        }
+        if let Err(reason) =
+            canary::verify(&self.engine(set), set, &result.snapshot_id, canaries).await
+        {
+            warn!("Backup of set {}: {}", set.name, reason);
+            result.success = false;
+            result.error_message = Some(reason);
+        }
+        Ok(result)
+    }
+
+    /// Copies backup progress into the set's `Running` state and broadcasts it
+    /// as `BackupProgress`, at most once per `PROGRESS_EVENT_INTERVAL`, until
+    /// the sender is dropped.
+    async fn track_progress(
+        jobs: Arc<Mutex<HashMap<String, Job>>>,
+        event_tx: broadcast::Sender<Response>,
+        set_name: String,
+        mut progress_rx: tokio::sync::watch::Receiver<Option<Progress>>,
+    ) {
+        let mut last_event: Option<Instant> = None;
+        while progress_rx.changed().await.is_ok() {
+            let Some(progress) = progress_rx.borrow_and_update().clone() else {
+                continue;
+            };
+            {
+                let mut jobs = jobs.lock().await;
+                if let Some(job) = jobs.get_mut(&set_name) {
+                    if matches!(job.state, JobState::Running { .. }) {
+                        // A percentage of what was found so far is no progress yet
+                        let scanned = progress.scanning.is_none();
+                        job.state = JobState::Running {
+                            percent: scanned.then_some(progress.percent),
+                            eta_secs: progress.eta_secs.filter(|_| scanned),
+                            scanning: progress.scanning,
+                        };
+                    }
+                }
+            }
+            if last_event.is_some_and(|at| at.elapsed() < PROGRESS_EVENT_INTERVAL) {
+                continue;
+            }
+            last_event = Some(Instant::now());
+            let _ = event_tx.send(Response::Event(ResponseData::BackupProgress {
+                set_name: set_name.clone(),
+                percent: progress.percent,
+                bytes_done: progress.bytes_done,
+                total_bytes: progress.total_bytes,
+                eta_secs: progress.eta_secs,
+                current_file: progress.current_file,
+                scanning: progress.scanning,
+            }));
+        }
+    }
+
+    /// Core prune logic for a single set. Used by both manual prune and auto-prune.
+    async fn prune_set(&self, set_name: &str, effective_set: &BackupSet) -> Result<u64> {
+        info!("Pruning set {}", set_name);
+        let reclaimed = self.run_prune(set_name, effective_set).await?;
+        info!(
+            "Pruned set {}: {} reclaimed",
+            set_name,
+            humanize::format_size(reclaimed)
+        );
+
+        // Refresh metrics after prune deterministically
+        self.refresh_set_status(set_name).await;
+        self.refresh_related_sets(&effective_set.target, set_name)
+            .await;
+
+        Ok(reclaimed)
+    }
+
+    /// Runs the engine's prune for one set. Append-only repositories are
+    /// refused up front, or recognized when the backend rejects the deletions.
+    async fn run_prune(&self, set_name: &str, effective_set: &BackupSet) -> Result<u64> {
+        let detected = {
+            let jobs = self.jobs.lock().await;
+            jobs.get(set_name)
+                .is_some_and(|job| job.append_only_detected)
+        };
+        if effective_set.append_only || detected {
+            return Err(AppendOnlyRepo {
+                set_name: set_name.to_string(),
+                detected: !effective_set.append_only,
+            }
+            .into());
+        }
+
+        let _slot = self.acquire_job_slot(set_name).await;
+        let _guard = self.track_prune(set_name);
+        match self
+            .engine(effective_set)
+            .prune(effective_set, Some(self.shutdown_token.clone()))
+            .await
+        {
+            Err(e) if executor::is_append_only_rejection(&e.to_string()) => {
+                warn!(
+                    "Repository of set {} refused deletions; treating it as append-only",
+                    set_name
+                );
+                if let Some(job) = self.jobs.lock().await.get_mut(set_name) {
+                    job.append_only_detected = true;
+                }
+                Err(AppendOnlyRepo {
+                    set_name: set_name.to_string(),
+                    detected: true,
+                }
+                .into())
+            }
+            result => result,
+        }
+    }
+
+    /// Removes one snapshot of a set, named by its ID or a unique prefix of
+    /// it, and with `prune` the data only it referenced. Snapshots of other
+    /// hosts in a shared repository are refused, as is any deletion while a
+    /// backup writes to the repository or when it is append-only.
+    pub async fn forget(
+        &self,
+        set_name: &str,
+        snapshot_id: &str,
+        prune: bool,
+    ) -> Result<ResponseData> {
+        let Some((set, busy)) = self.repo_targets(Some(set_name.to_string())).await?.pop() else {
+            anyhow::bail!("Unknown backup set: {}", set_name);
+        };
+        let detected = {
+            let jobs = self.jobs.lock().await;
+            jobs.get(set_name)
+                .is_some_and(|job| job.append_only_detected)
+        };
+        if set.append_only || detected {
+            return Err(AppendOnlyRepo {
+                set_name: set.name,
+                detected: !set.append_only,
+            }
+            .into());
+        }
+        if busy {
+            return Err(RepoBusy { set_name: set.name }.into());
+        }
+
+        let engine = self.engine(&set);
+        let token = Some(self.shutdown_token.clone());
+        let snapshots = engine.snapshots(&set.target, None, token.clone()).await?;
+        let snapshot = find_snapshot(&snapshots, snapshot_id)?;
+        if set.shared_repo {
+            let host = executor::local_hostname();
+            if let Some(ref other) = snapshot.hostname.as_ref().filter(|h| **h != host) {
+                anyhow::bail!(
+                    "Snapshot {} was created by host '{}'; forget it from that machine",
+                    snapshot.short_id,
+                    other
+                );
+            }
+        }
+
+        let _slot = self.acquire_job_slot(set_name).await;
+        let _guard = self.track_prune(set_name);
+        let reclaimed = match engine.forget(&set.target, &snapshot.id, prune, token).await {
+            Err(e) if executor::is_append_only_rejection(&e.to_string()) => {
+                if let Some(job) = self.jobs.lock().await.get_mut(set_name) {
+                    job.append_only_detected = true;
+                }
+                return Err(AppendOnlyRepo {
+                    set_name: set.name,
+                    detected: true,
+                }
+                .into());
+            }
+            result => result?,
+        };
+        info!(
+            "Forgot snapshot {} of set {}{}",
+            snapshot.short_id,
+            set.name,
+            if prune {
+                format!(", {} reclaimed", humanize::format_size(reclaimed))
+            } else {
+                String::new()
+            }
+        );
+
+        self.refresh_set_status(&set.name).await;
+        self.refresh_related_sets(&set.target, &set.name).await;
+        Ok(ResponseData::Forgotten {
+            set_name: set.name,
+            snapshot_id: snapshot.short_id.clone(),
+            reclaimed_bytes: prune.then_some(reclaimed),
+        })
+    }
+
+    /// Lists the snapshots a prune of `set_name` would forget under its
+    /// effective retention policy, without changing the repository.
+    pub async fn prune_preview(&self, set_name: &str) -> Result<ResponseData> {
+        let (effective_set, append_only) = {
+            let jobs = self.jobs.lock().await;
+            let Some(job) = jobs.get(set_name) else {
+                anyhow::bail!("Unknown backup set: {}", set_name);
+            };
+            (
+                self.with_effective_retention(&job.set).await,
+                job.is_append_only(),
+            )
+        };
+        if append_only {
+            return Err(AppendOnlyRepo {
+                set_name: set_name.to_string(),
+                detected: !effective_set.append_only,
+            }
+            .into());
+        }
+
+        let (to_remove, to_keep) = self
+            .engine(&effective_set)
+            .forget_preview(&effective_set, Some(self.shutdown_token.clone()))
+            .await?;
+        debug!(
+            "Prune of set {} would forget {} snapshot(s) and keep {}",
+            set_name,
+            to_remove.len(),
+            to_keep
+        );
+        Ok(ResponseData::PrunePreview {
+            set_name: set_name.to_string(),
+            to_remove,
+            to_keep,
+        })
+    }
+
+    pub async fn prune(&self, set_name: Option<String>) -> Result<vigil_lib::ipc::ResponseData> {
+        if let Some(name) = set_name {
+            let effective_set = {
+                let jobs = self.jobs.lock().await;
+                if let Some(job) = jobs.get(&name) {
+                    self.with_effective_retention(&job.set).await
+                } else {
+                    anyhow::bail!("Unknown backup set: {}", name)
+                }
+            };
+
+            let reclaimed = self.prune_set(&name, &effective_set).await?;
+
+            Ok(vigil_lib::ipc::ResponseData::PruneResult {
+                set_name: name,
+                reclaimed_bytes: reclaimed,
+            })
+        } else {
+            // Collect effective sets under the lock, then drop it
+            let sets_to_prune: Vec<(String, BackupSet)> = {
+                let jobs = self.jobs.lock().await;
+                let mut sets = Vec::new();
+                for (name, job) in jobs.iter() {
+                    if job.is_append_only() {
+                        info!("Skipping prune of set {}: append-only repository", name);
+                        continue;
+                    }
+                    let effective_set = self.with_effective_retention(&job.set).await;
+                    sets.push((name.clone(), effective_set));
+                }
+                sets
+            };
+
+            info!("Pruning all sets");
+            let mut succeeded = Vec::new();
+            let mut failed = Vec::new();
+            let mut targets_to_refresh = Vec::new();
+
+            // Repositories are pruned in parallel (up to `max_concurrent_jobs`),
+            // sets sharing one in turn since prune locks the repository
+            let mut by_target: BTreeMap<String, Vec<(String, BackupSet)>> = BTreeMap::new();
+            for (name, effective_set) in sets_to_prune {
+                by_target
+                    .entry(effective_set.target.clone())
+                    .or_default()
+                    .push((name, effective_set));
+            }
+            let mut prunes = JoinSet::new();
+            for group in by_target.into_values() {
+                let manager = self.clone();
+                prunes.spawn(async move {
+                    let mut outcomes = Vec::new();
+                    for (name, effective_set) in group {
+                        // Check shutdown before starting next prune
+                        if manager.shutdown_token.is_cancelled() {
+                            break;
+                        }
+                        let result = manager.run_prune(&name, &effective_set).await;
+                        outcomes.push((name, effective_set.target, result));
+                    }
+                    outcomes
+                });
+            }
+
+            while let Some(joined) = prunes.join_next().await {
+                let outcomes = match joined {
+                    Ok(outcomes) => outcomes,
+                    Err(e) => {
+                        error!("Prune task failed: {}", e);
+                        continue;
+                    }
+                };
+                for (name, target, result) in outcomes {
+                    match result {
+                        Ok(reclaimed) => {
+                            info!(
+                                "Pruned set {}: {} reclaimed",
+                                name,
+                                humanize::format_size(reclaimed)
+                            );
+                            succeeded.push((name.clone(), reclaimed));
+                            targets_to_refresh.push((name, target));
+                        }
+                        Err(e) => {
+                            error!("Failed to prune set {}: {}", name, e);
+                            failed.push((name, e.to_string()));
+                        }
+                    }
+                }
+            }
+            succeeded.sort();
+            failed.sort();
+
+            // Refresh metrics for successfully pruned sets
+            for (name, target) in targets_to_refresh {
                 let manager = self.clone();
                 tokio::spawn(async move {
                     manager.refresh_set_status(&name).await;
@@ -855,432 +4715,2629 @@ impl JobManager {
                 });
             }
 
-            Ok(vigil_lib::ipc::ResponseData::PrunesTriggered { succeeded, failed })
+            Ok(vigil_lib::ipc::ResponseData::PrunesTriggered { succeeded, failed })
+        }
+    }
+
+    /// Applies a set's retention policy after a successful backup. Started by
+    /// `run_pending_prunes`; logs errors instead of returning them.
+    async fn auto_prune(&self, set_name: &str) {
+        if self.shutdown_token.is_cancelled() {
+            return;
+        }
+
+        let effective_set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => self.with_effective_retention(&job.set).await,
+                None => {
+                    warn!("Cannot auto-prune set {}: set no longer exists", set_name);
+                    return;
+                }
+            }
+        };
+
+        info!("Auto-pruning set {} after successful backup", set_name);
+
+        // Reuse existing prune_set() logic
+        match self.prune_set(set_name, &effective_set).await {
+            Ok(reclaimed) => {
+                // Send event for transparency
+                let _ = self
+                    .event_tx
+                    .send(Response::Event(ResponseData::PruneComplete {
+                        set_name: set_name.to_string(),
+                        reclaimed_bytes: reclaimed,
+                    }));
+            }
+            Err(e) => {
+                error!(
+                    "Auto-prune failed for set {} (backup succeeded): {}",
+                    set_name, e
+                );
+
+                self.notify(
+                    &effective_set,
+                    NotifyEvent::Prune,
+                    "Automatic Prune Failed",
+                    &format!(
+                        "Retention cleanup failed for '{}'. Manual prune may be needed.",
+                        set_name
+                    ),
+                    "dialog-warning",
+                );
+            }
+        }
+    }
+
+    /// Copies the snapshots of `set_name` that its replica lacks into it, on
+    /// request. Refused while a replication of the set is running.
+    pub async fn replicate(&self, set_name: &str) -> Result<ResponseData> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let Some(replica) = set.replica_target.clone() else {
+            anyhow::bail!("Set '{}' has no replica_target", set_name);
+        };
+        let Some(_guard) = self.track_replication(set_name, false) else {
+            anyhow::bail!("A replication of set '{}' is already running", set_name);
+        };
+        self.copy_to_replica(&set, &replica).await
+    }
+
+    /// Starts copying a set's snapshots to its replica after a successful
+    /// backup, if it replicates automatically. The set counts as replicating
+    /// once this returns. A backup finishing while the copy runs makes it run
+    /// again.
+    pub async fn start_auto_replication(&self, set_name: &str) {
+        let set = match self.jobs.lock().await.get(set_name) {
+            Some(job) => job.set.clone(),
+            None => return,
+        };
+        let Some(replica) = set.replica_target.clone().filter(|_| set.auto_replicate()) else {
+            return;
+        };
+        let Some(guard) = self.track_replication(set_name, true) else {
+            debug!(
+                "Replication of set {} is running; it copies again when done",
+                set_name
+            );
+            return;
+        };
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.auto_replicate(&set, &replica).await;
+            drop(guard);
+        });
+    }
+
+    /// Replicates a set until no backup finished during the last copy. Logs
+    /// and notifies errors instead of returning them.
+    async fn auto_replicate(&self, set: &BackupSet, replica: &str) {
+        let set_name = set.name.as_str();
+        loop {
+            if self.shutdown_token.is_cancelled() {
+                return;
+            }
+            info!(
+                "Replicating set {} to {} after successful backup",
+                set_name, replica
+            );
+            if let Err(e) = self.copy_to_replica(set, replica).await {
+                error!(
+                    "Replication of set {} failed (backup succeeded): {}",
+                    set_name, e
+                );
+                self.notify(
+                    set,
+                    NotifyEvent::Replication,
+                    "Replication Failed",
+                    &format!(
+                        "Copying the snapshots of '{}' to {} failed.",
+                        set_name, replica
+                    ),
+                    "dialog-warning",
+                );
+            }
+            if !self.take_replication_rerun(set_name) {
+                return;
+            }
+        }
+    }
+
+    /// Runs `restic copy` into `replica` and broadcasts the outcome.
+    async fn copy_to_replica(&self, set: &BackupSet, replica: &str) -> Result<ResponseData> {
+        let _slot = self.acquire_job_slot(&set.name).await;
+        match self
+            .engine(set)
+            .copy(set, replica, Some(self.shutdown_token.clone()))
+            .await
+        {
+            Ok(copied) => {
+                info!(
+                    "Replicated set {} to {}: {} snapshot(s) copied",
+                    set.name, replica, copied
+                );
+                if let Some(job) = self.jobs.lock().await.get_mut(&set.name) {
+                    job.replica_next_check = None;
+                }
+                let data = ResponseData::ReplicationComplete {
+                    set_name: set.name.clone(),
+                    replica_target: replica.to_string(),
+                    copied,
+                };
+                let _ = self.event_tx.send(Response::Event(data.clone()));
+                Ok(data)
+            }
+            Err(e) => {
+                let _ = self
+                    .event_tx
+                    .send(Response::Event(ResponseData::ReplicationFailed {
+                        set_name: set.name.clone(),
+                        error: e.to_string(),
+                    }));
+                Err(e)
+            }
+        }
+    }
+
+    /// Compares the snapshots of each set's replica with its repository once
+    /// `REPLICA_CHECK_INTERVAL_SECS` passed since the last comparison, for
+    /// sets with a `max_copy_lag_hours`. Sets being replicated or pruned wait.
+    /// Returns when the next comparison is due.
+    pub async fn run_due_replica_checks(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.is_handing_off() {
+            return None;
+        }
+        let global_limit =
+            Some(self.global_max_copy_lag_hours.load(Ordering::Relaxed)).filter(|h| *h > 0);
+        let mut due = Vec::new();
+        let mut earliest: Option<DateTime<Utc>> = None;
+        {
+            let mut jobs = self.jobs.lock().await;
+            let pruning = self
+                .active_prunes
+                .lock()
+                .map(|active| active.clone())
+                .unwrap_or_default();
+            let replicating: HashSet<String> = self
+                .replications
+                .lock()
+                .map(|active| active.keys().cloned().collect())
+                .unwrap_or_default();
+            for (name, job) in jobs.iter_mut() {
+                let Some(limit_hours) = job.set.max_copy_lag_hours(global_limit) else {
+                    job.replica_lag = None;
+                    job.replica_next_check = None;
+                    continue;
+                };
+                // A finished replication or prune wakes the maintenance task
+                if !job.status_loaded || pruning.contains(name) || replicating.contains(name) {
+                    continue;
+                }
+                let next = job.replica_next_check.unwrap_or(now);
+                if next > now {
+                    earliest = Some(earliest.map_or(next, |e| e.min(next)));
+                    continue;
+                }
+                let next = now + chrono::Duration::seconds(REPLICA_CHECK_INTERVAL_SECS);
+                job.replica_next_check = Some(next);
+                earliest = Some(earliest.map_or(next, |e| e.min(next)));
+                due.push((job.set.clone(), limit_hours));
+            }
+        }
+
+        for (set, limit_hours) in due {
+            let manager = self.clone();
+            tokio::spawn(async move {
+                manager.check_replica(&set, limit_hours).await;
+            });
+        }
+        earliest
+    }
+
+    /// Compares the set's replica with its repository and records the lag.
+    /// Notifies when the replica falls more than `limit_hours` behind or can
+    /// no longer be compared, once until it catches up again.
+    async fn check_replica(&self, set: &BackupSet, limit_hours: u64) {
+        let Some(replica) = set.replica_target.as_deref() else {
+            return;
+        };
+        let engine = self.engine(set);
+        let token = Some(self.shutdown_token.clone());
+        let compared = async {
+            let primary = engine.snapshots(&set.target, None, token.clone()).await?;
+            let copies = engine.snapshots(replica, None, token).await?;
+            anyhow::Ok((primary, copies))
+        }
+        .await;
+        if self.shutdown_token.is_cancelled() {
+            return;
+        }
+        let now = Utc::now();
+        let lag = match compared {
+            Ok((primary, copies)) => {
+                let (missing_snapshots, lag_secs) = replica_lag(&primary, &copies, now);
+                ReplicaLag {
+                    checked_at: now,
+                    missing_snapshots,
+                    lag_secs,
+                    behind: lag_secs > limit_hours.saturating_mul(3600),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to compare the replica of set {} with its repository: {:#}",
+                    set.name, e
+                );
+                ReplicaLag {
+                    checked_at: now,
+                    missing_snapshots: 0,
+                    lag_secs: 0,
+                    behind: false,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        let failing = |lag: &ReplicaLag| lag.behind || lag.error.is_some();
+        let newly_failing = {
+            let mut jobs = self.jobs.lock().await;
+            let Some(job) = jobs.get_mut(&set.name) else {
+                return;
+            };
+            let was_failing = job.replica_lag.as_ref().is_some_and(failing);
+            job.replica_lag = Some(lag.clone());
+            failing(&lag) && !was_failing
+        };
+        debug!(
+            "Replica of set {} lacks {} snapshot(s), lagging {}s",
+            set.name, lag.missing_snapshots, lag.lag_secs
+        );
+        if !newly_failing {
+            return;
+        }
+        let body = match &lag.error {
+            Some(error) => format!(
+                "The replica of '{}' at {} could not be compared with the repository: {}",
+                set.name, replica, error
+            ),
+            None => format!(
+                "The replica of '{}' at {} lacks {} snapshot(s), the oldest from {} (limit {}h).",
+                set.name,
+                replica,
+                lag.missing_snapshots,
+                humanize::format_relative(chrono::Duration::seconds(lag.lag_secs as i64)),
+                limit_hours
+            ),
+        };
+        warn!("{}", body);
+        self.notify(
+            set,
+            NotifyEvent::Replication,
+            "Replica Behind",
+            &body,
+            "dialog-warning",
+        );
+    }
+
+    /// Returns a set's configuration with the daemon's current global fallbacks applied.
+    pub async fn effective_config(&self, set_name: &str) -> Result<EffectiveConfig> {
+        let set = {
+            let jobs = self.jobs.lock().await;
+            match jobs.get(set_name) {
+                Some(job) => job.set.clone(),
+                None => anyhow::bail!("Unknown backup set: {}", set_name),
+            }
+        };
+        let throttle = self
+            .engine_defaults
+            .lock()
+            .map(|defaults| defaults.throttle)
+            .unwrap_or_default();
+        let global = GlobalConfig {
+            debounce_seconds: self.global_debounce.load(Ordering::Relaxed),
+            max_retries: self.global_max_retries.load(Ordering::Relaxed),
+            retry_backoff_secs: self.global_retry_backoff.load(Ordering::Relaxed),
+            auto_unlock: self.global_auto_unlock.load(Ordering::Relaxed),
+            restore_shield_secs: self.global_restore_shield.load(Ordering::Relaxed),
+            max_copy_lag_hours: Some(self.global_max_copy_lag_hours.load(Ordering::Relaxed))
+                .filter(|hours| *hours > 0),
+            retention: self.global_retention.lock().await.clone(),
+            mount_prefetch_depth: *self.global_prefetch_depth.lock().await,
+            mount_idle_timeout_secs: self.global_mount_idle_timeout_secs(),
+            upload_limit_kib: throttle.upload_limit_kib,
+            download_limit_kib: throttle.download_limit_kib,
+            priority: throttle.priority,
+            maintenance_window: self
+                .global_maintenance_window
+                .lock()
+                .ok()
+                .and_then(|window| window.clone()),
+            notifications: self
+                .global_notifications
+                .lock()
+                .map(|policy| policy.clone())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        Ok(set.resolve(&global))
+    }
+
+    /// Creates a copy of the BackupSet with effective retention policy.
+    /// Falls back to global retention if per-set retention is not specified.
+    async fn with_effective_retention(&self, set: &BackupSet) -> BackupSet {
+        let mut effective = set.clone();
+        if effective.retention.is_none() {
+            effective.retention = self.global_retention.lock().await.clone();
+        }
+        effective
+    }
+
+    /// Unmounts every snapshot of a set and returns the keys of those that were mounted.
+    async fn perform_unmount(name: &str, job: &mut Job) -> Vec<String> {
+        let backup_running = matches!(job.state, JobState::Running { .. });
+        let mut released = Vec::new();
+        for (key, mount) in std::mem::take(&mut job.mounts) {
+            Self::release_mount(name, &key, mount, backup_running).await;
+            released.push(key);
+        }
+        released
+    }
+
+    /// Unmounts one snapshot, stops its mount process and removes its
+    /// directory. A directory that is still mounted is left in place.
+    async fn release_mount(name: &str, key: &str, mut mount: ActiveMount, backup_running: bool) {
+        // Warn if unmounting during an active backup
+        if backup_running {
+            warn!(
+                "Unmounting set {} while backup is running - this may cause the backup to fail",
+                name
+            );
+        }
+
+        info!("Unmounting snapshot {} of set {}", key, name);
+        if let Some(token) = mount.prefetch.take() {
+            token.cancel();
+        }
+
+        // 1. Try the FUSE unmount helper (fusermount3 -u, or umount on macOS)
+        let child = vigil_lib::platform::unmount_command(&mount.path)
+            .map(|(program, args)| tokio::process::Command::new(program).args(args).spawn());
+
+        let success = match child {
+            Some(Ok(mut c)) => c.wait().await.is_ok_and(|status| status.success()),
+            _ => false, // helper not found or failed to spawn
+        };
+
+        if !success {
+            debug!(
+                "{} failed or not found, killing restic process for {}",
+                vigil_lib::platform::FUSE_HELPER,
+                name
+            );
+            if let Some(mut child) = mount.process.take() {
+                let _ = child.kill().await;
+            }
+        } else {
+            // Even if the unmount succeeded, we should clean up the restic process
+            if let Some(mut child) = mount.process.take() {
+                // Restic should exit on its own when unmounted, but we'll wait a bit then kill if needed
+                match tokio::time::timeout(
+                    Duration::from_secs(MOUNT_GRACEFUL_EXIT_TIMEOUT_SECS),
+                    child.wait(),
+                )
+                .await
+                {
+                    Ok(_) => debug!("Restic mount process for {} exited cleanly", name),
+                    Err(_) => {
+                        debug!("Restic mount process for {} did not exit, killing", name);
+                        let _ = child.kill().await;
+                    }
+                }
+            }
+        }
+
+        let _ = std::fs::remove_dir(&mount.path);
+    }
+
+    async fn calculate_dir_size(path: &std::path::Path) -> Result<Option<u64>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut total_size = 0;
+        let mut entries = match tokio::fs::read_dir(path).await {
+            Ok(e) => e,
+            Err(_) => return Ok(None), // treat inaccessible dirs as unknown size
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                if let Some(size) = Box::pin(Self::calculate_dir_size(&entry.path())).await? {
+                    total_size += size;
+                }
+            } else {
+                total_size += metadata.len();
+            }
+        }
+        Ok(Some(total_size))
+    }
+}
+
+/// Renders the `StatusLine` summary. A running backup takes precedence over
+/// health, since it is what changes from second to second.
+fn compact_status(
+    sets: &[SetStatus],
+    overall: HealthLevel,
+    issues: &[HealthIssue],
+    now: DateTime<Utc>,
+) -> CompactStatus {
+    let names_at = |level: HealthLevel| {
+        let mut names: Vec<String> = issues
+            .iter()
+            .filter(|issue| issue.level == level)
+            .map(|issue| issue.set_name.clone())
+            .collect();
+        names.dedup();
+        names
+    };
+    let running: Vec<&SetStatus> = sets
+        .iter()
+        .filter(|set| matches!(set.state, JobState::Running { .. }))
+        .collect();
+    let failing = names_at(HealthLevel::Failing);
+    let pending_changes = sets.iter().map(|set| set.pending_changes).sum();
+    let last_backup = sets
+        .iter()
+        .filter_map(|set| set.last_backup.as_ref())
+        .filter(|backup| backup.success)
+        .map(|backup| backup.timestamp)
+        .max();
+
+    let text = if !running.is_empty() {
+        let progress: Vec<String> = running
+            .iter()
+            .map(|set| match set.state {
+                JobState::Running {
+                    percent: Some(percent),
+                    ..
+                } => format!("{} {:.0}%", set.name, percent),
+                _ => set.name.clone(),
+            })
+            .collect();
+        format!("backing up {}", progress.join(", "))
+    } else if sets.is_empty() {
+        "no backup sets".to_string()
+    } else {
+        let mut parts = vec![match overall {
+            HealthLevel::Healthy => "ok".to_string(),
+            HealthLevel::Degraded => {
+                format!("degraded: {}", names_at(HealthLevel::Degraded).join(", "))
+            }
+            HealthLevel::Failing => format!("failing: {}", failing.join(", ")),
+        }];
+        if let Some(at) = last_backup {
+            parts.push(format!("last {}", humanize::format_compact(now - at)));
+        }
+        if pending_changes > 0 {
+            parts.push(format!("{} pending", pending_changes));
+        }
+        parts.join(" · ")
+    };
+
+    let tooltip = sets
+        .iter()
+        .map(|set| {
+            let detail = match (&set.state, &set.last_backup) {
+                (JobState::Running { .. }, _) => "backing up".to_string(),
+                (JobState::Queued, _) => "waiting for a job slot".to_string(),
+                (_, Some(backup)) if backup.success => format!(
+                    "last backup {}",
+                    humanize::format_relative(now - backup.timestamp)
+                ),
+                (_, Some(backup)) => format!(
+                    "failed {}",
+                    humanize::format_relative(now - backup.timestamp)
+                ),
+                (_, None) => "never backed up".to_string(),
+            };
+            format!("{}: {}", set.name, detail)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    CompactStatus {
+        text,
+        tooltip,
+        level: overall,
+        sets: sets.len(),
+        running: running.iter().map(|set| set.name.clone()).collect(),
+        failing,
+        pending_changes,
+        last_backup,
+    }
+}
+
+/// The snapshots of `set_name` mounted right now, keyed like `JobManager::mount`
+/// keys them. A mount an older version made at the set's mount directory
+/// itself is reported as `latest`.
+fn detect_mounts(set_name: &str) -> Vec<(String, PathBuf)> {
+    let set_dir = vigil_lib::paths::mount_path(set_name);
+    if vigil_lib::paths::is_mount_point(&set_dir) {
+        return vec![(vigil_lib::paths::mount_key(None), set_dir)];
+    }
+    std::fs::read_dir(&set_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| vigil_lib::paths::is_mount_point(path))
+        .filter_map(|path| Some((path.file_name()?.to_string_lossy().into_owned(), path)))
+        .collect()
+}
+
+/// For each of `mounts`, whether a process has its working directory or an
+/// open file inside it, found by reading the links under `/proc`. Processes
+/// of other users cannot be inspected and are not counted.
+fn mounts_in_use(mounts: &[PathBuf]) -> Vec<bool> {
+    let mut in_use = vec![false; mounts.len()];
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return in_use;
+    };
+    for proc_dir in procs.flatten() {
+        if !proc_dir
+            .file_name()
+            .as_encoded_bytes()
+            .iter()
+            .all(u8::is_ascii_digit)
+        {
+            continue;
+        }
+        let dir = proc_dir.path();
+        let fds = std::fs::read_dir(dir.join("fd"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|fd| fd.path());
+        for link in std::iter::once(dir.join("cwd")).chain(fds) {
+            let Ok(target) = std::fs::read_link(&link) else {
+                continue;
+            };
+            for (used, mount) in in_use.iter_mut().zip(mounts) {
+                *used |= target.starts_with(mount);
+            }
+        }
+        if in_use.iter().all(|used| *used) {
+            break;
+        }
+    }
+    in_use
+}
+
+/// Space available to this user on the filesystem holding `path`, or on its
+/// nearest existing ancestor for a repository that is not created yet.
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_encoded_bytes()).ok()?;
+    // SAFETY: statvfs only writes into the zero-initialized struct
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Not measured off Unix, so no low-space warnings there.
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether a restic `init` error means the repository already exists.
+fn is_already_initialized(error: &str) -> bool {
+    error.contains("repository master key and config already initialized")
+        || error.contains("config already initialized")
+        || error.contains("config file already exists")
+}
+
+/// `restic diff` operands for two snapshots of a set with a `logical_path`
+/// whose single source moved between them. Each side is rooted at its own
+/// source (`<id>:<path>`) so the diff compares contents instead of listing
+/// every file as removed and added. `None` when the paths match.
+fn moved_diff_operands(a: &SnapshotInfo, b: &SnapshotInfo) -> Option<(String, String)> {
+    match (a.paths.as_slice(), b.paths.as_slice()) {
+        ([path_a], [path_b]) if path_a != path_b => Some((
+            format!("{}:{}", a.id, path_a.display()),
+            format!("{}:{}", b.id, path_b.display()),
+        )),
+        _ => None,
+    }
+}
+
+/// The snapshot whose ID is `id` or starts with it. A prefix matching several
+/// snapshots is refused rather than guessed.
+fn find_snapshot<'a>(snapshots: &'a [SnapshotInfo], id: &str) -> Result<&'a SnapshotInfo> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!(
+            "Invalid snapshot ID '{}': expected a hex ID such as 1a2b3c4d",
+            id
+        );
+    }
+    let id = id.to_ascii_lowercase();
+    let mut matches = snapshots.iter().filter(|s| s.id.starts_with(&id));
+    match (matches.next(), matches.next()) {
+        (Some(snapshot), None) => Ok(snapshot),
+        (None, _) => anyhow::bail!("No snapshot with ID {} in this set", id),
+        (Some(_), Some(_)) => anyhow::bail!(
+            "Snapshot ID {} is ambiguous; give more characters of the ID",
+            id
+        ),
+    }
+}
+
+/// How far `replica` is behind `primary`: the number of primary snapshots it
+/// lacks and the age in seconds of the oldest of them, 0 when none are
+/// missing. `restic copy` keeps a snapshot's time and host, so a copy is found
+/// by those rather than by its ID.
+fn replica_lag(
+    primary: &[SnapshotInfo],
+    replica: &[SnapshotInfo],
+    now: DateTime<Utc>,
+) -> (usize, u64) {
+    let copied: HashSet<_> = replica
+        .iter()
+        .map(|s| (s.timestamp, s.hostname.as_deref()))
+        .collect();
+    let missing: Vec<_> = primary
+        .iter()
+        .filter(|s| !copied.contains(&(s.timestamp, s.hostname.as_deref())))
+        .collect();
+    let lag_secs = missing
+        .iter()
+        .map(|s| s.timestamp)
+        .min()
+        .map_or(0, |oldest| (now - oldest).num_seconds().max(0) as u64);
+    (missing.len(), lag_secs)
+}
+
+/// Groups snapshots by the host that created them, local host first and then by
+/// most recent activity. Snapshots without a recorded hostname are grouped under
+/// an empty name.
+fn group_by_host(snapshots: &[SnapshotInfo], local_hostname: &str) -> Vec<HostSnapshots> {
+    let mut hosts: Vec<HostSnapshots> = Vec::new();
+    for snapshot in snapshots {
+        let hostname = snapshot.hostname.clone().unwrap_or_default();
+        match hosts.iter_mut().find(|h| h.hostname == hostname) {
+            Some(host) => {
+                host.snapshot_count += 1;
+                host.latest = host.latest.max(snapshot.timestamp);
+            }
+            None => hosts.push(HostSnapshots {
+                is_local: hostname == local_hostname,
+                hostname,
+                snapshot_count: 1,
+                latest: snapshot.timestamp,
+            }),
+        }
+    }
+    hosts.sort_by(|a, b| b.is_local.cmp(&a.is_local).then(b.latest.cmp(&a.latest)));
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+    use tokio_util::sync::CancellationToken;
+    use vigil_lib::config::GlobalConfig;
+    use vigil_lib::paths;
+
+    /// Tests the debounce state machine with real restic integration.
+    ///
+    /// **NOTE:** This test modifies XDG environment variables and must be run single-threaded:
+    /// ```bash
+    /// cargo test -p vigil-daemon --lib -- --ignored --test-threads=1
+    /// ```
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_debounce_logic() -> Result<()> {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        // Setup: Isolated temp environment
+        let tmp = tempdir()?;
+        let source_path = tmp.path().join("source");
+        let repo_path = tmp.path().join("repo");
+        fs::create_dir(&source_path)?;
+        fs::write(source_path.join("test.txt"), "test data")?;
+
+        // Setup: Isolated config/data dirs via env vars
+        let config_home = tmp.path().join("config");
+        let data_home = tmp.path().join("data");
+        fs::create_dir_all(&config_home)?;
+        fs::create_dir_all(&data_home)?;
+        std::env::set_var("XDG_CONFIG_HOME", &config_home);
+        std::env::set_var("XDG_DATA_HOME", &data_home);
+
+        // Setup: Create password file
+        let pw_file = paths::password_path();
+        fs::create_dir_all(pw_file.parent().unwrap())?;
+        fs::write(&pw_file, "testpassword")?;
+        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
+
+        // Setup: Initialize restic repository
+        let executor = crate::executor::ResticExecutor::new();
+        executor.init(repo_path.to_str().unwrap()).await?;
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some(source_path.to_string_lossy().to_string()),
+                sources: None,
+                target: repo_path.to_string_lossy().to_string(),
+                exclude: None,
+                debounce_seconds: Some(1), // 1 second for faster test
+                retention: None,
+                ..Default::default()
+            }],
+        };
+
+        let manager = JobManager::new(&config, CancellationToken::new());
+
+        // Helper to get state for "test" set
+        let get_test_state = || async {
+            manager
+                .get_status()
+                .await
+                .into_iter()
+                .find(|s| s.name == "test")
+                .map(|s| s.state)
+        };
+
+        // Initial state should be Idle
+        assert_eq!(get_test_state().await.unwrap(), JobState::Idle);
+
+        // Trigger a file change
+        manager
+            .handle_file_change("test", &source_path.join("test.txt"))
+            .await?;
+
+        // Should enter Debouncing state
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let state = get_test_state().await.unwrap();
+        assert!(
+            matches!(state, JobState::Debouncing { .. }),
+            "Expected Debouncing, got {:?}",
+            state
+        );
+
+        // Wait for debounce to complete and backup to finish
+        // (1s debounce + real backup which is fast for small files)
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        let state = get_test_state().await.unwrap();
+        assert_eq!(
+            state,
+            JobState::Idle,
+            "Expected Idle after backup completes"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_manual_trigger() -> Result<()> {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let tmp = tempdir()?;
+        let source_path = tmp.path().join("source");
+        let repo_path = tmp.path().join("repo");
+        fs::create_dir(&source_path)?;
+        fs::write(source_path.join("test.txt"), "test data")?;
+
+        let config_home = tmp.path().join("config");
+        let data_home = tmp.path().join("data");
+        fs::create_dir_all(&config_home)?;
+        fs::create_dir_all(&data_home)?;
+        std::env::set_var("XDG_CONFIG_HOME", &config_home);
+        std::env::set_var("XDG_DATA_HOME", &data_home);
+
+        let pw_file = paths::password_path();
+        fs::create_dir_all(pw_file.parent().unwrap())?;
+        fs::write(&pw_file, "testpassword")?;
+        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
+
+        let executor = crate::executor::ResticExecutor::new();
+        executor.init(repo_path.to_str().unwrap()).await?;
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some(source_path.to_string_lossy().to_string()),
+                sources: None,
+                target: repo_path.to_string_lossy().to_string(),
+                exclude: None,
+                debounce_seconds: Some(60), // Long debounce to verify skip
+                retention: None,
+                ..Default::default()
+            }],
+        };
+
+        let manager = JobManager::new(&config, CancellationToken::new());
+
+        let get_test_state = || async {
+            manager
+                .get_status()
+                .await
+                .into_iter()
+                .find(|s| s.name == "test")
+                .map(|s| s.state)
+        };
+
+        // 1. Test trigger from Idle
+        manager.trigger_backup("test").await?;
+
+        // Should enter Running immediately
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let state = get_test_state().await.unwrap();
+        assert!(matches!(state, JobState::Running { .. }));
+
+        // Wait for completion
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+        assert_eq!(get_test_state().await.unwrap(), JobState::Idle);
+
+        // 2. Test trigger from Debouncing
+        manager
+            .handle_file_change("test", &source_path.join("test.txt"))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let state = get_test_state().await.unwrap();
+        assert!(matches!(state, JobState::Debouncing { .. }));
+
+        manager.trigger_backup("test").await?;
+
+        // Should transition to Running soon (after poll)
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        let state = get_test_state().await.unwrap();
+        // It might be Running or already Idle if the backup was fast
+        assert!(matches!(state, JobState::Running { .. } | JobState::Idle));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_initialize_status() -> Result<()> {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let tmp = tempdir()?;
+        let source_path = tmp.path().join("source");
+        let repo_path = tmp.path().join("repo");
+        fs::create_dir(&source_path)?;
+        fs::write(source_path.join("test.txt"), "test data")?;
+
+        let config_home = tmp.path().join("config");
+        let data_home = tmp.path().join("data");
+        fs::create_dir_all(&config_home)?;
+        fs::create_dir_all(&data_home)?;
+        std::env::set_var("XDG_CONFIG_HOME", &config_home);
+        std::env::set_var("XDG_DATA_HOME", &data_home);
+
+        let pw_file = paths::password_path();
+        fs::create_dir_all(pw_file.parent().unwrap())?;
+        fs::write(&pw_file, "testpassword")?;
+        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
+
+        let executor = crate::executor::ResticExecutor::new();
+        executor.init(repo_path.to_str().unwrap()).await?;
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some(source_path.to_string_lossy().to_string()),
+                sources: None,
+                target: repo_path.to_string_lossy().to_string(),
+                exclude: None,
+                debounce_seconds: Some(1),
+                retention: None,
+                ..Default::default()
+            }],
+        };
+
+        // 1. Create a backup first
+        let manager = JobManager::new(&config, CancellationToken::new());
+        manager.trigger_backup("test").await?;
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+
+        let status = manager.get_status().await;
+        let original_snapshot_id = status[0].last_backup.as_ref().unwrap().snapshot_id.clone();
+        assert!(!original_snapshot_id.is_empty());
+
+        // 2. Create a new manager (simulating daemon restart)
+        let manager2 = JobManager::new(&config, CancellationToken::new());
+        // Initially last_backup should be None
+        assert!(manager2.get_status().await[0].last_backup.is_none());
+        assert!(manager2.get_status().await[0].initializing);
+
+        // 3. Initialize status
+        manager2.initialize_status().await;
+
+        // 4. Verify last_backup is now populated with the same snapshot ID
+        let status2 = manager2.get_status().await;
+        assert_eq!(
+            status2[0].last_backup.as_ref().unwrap().snapshot_id,
+            original_snapshot_id
+        );
+        assert!(status2[0].last_backup.as_ref().unwrap().success);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_initialize_status_reports_progress() -> Result<()> {
+        let tmp = tempdir()?;
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: (0..6)
+                .map(|i| BackupSet {
+                    name: format!("set{}", i),
+                    source: Some(tmp.path().to_string_lossy().to_string()),
+                    target: tmp
+                        .path()
+                        .join(format!("missing{}", i))
+                        .to_string_lossy()
+                        .to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut events = manager.subscribe();
+
+        assert!(manager.get_status().await.iter().all(|s| s.initializing));
+
+        // Unreachable repositories still count as loaded
+        manager.initialize_status().await;
+        assert!(manager.get_status().await.iter().all(|s| !s.initializing));
+        match events.try_recv()? {
+            Response::Event(ResponseData::StatusInitialized { sets, .. }) => assert_eq!(sets, 6),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_calculate_dir_size() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path();
+
+        // Create some files
+        fs::write(path.join("file1.txt"), "hello")?; // 5 bytes
+        fs::write(path.join("file2.txt"), "world")?; // 5 bytes
+        fs::create_dir(path.join("subdir"))?;
+        fs::write(path.join("subdir/file3.txt"), "test")?; // 4 bytes
+
+        let size = JobManager::calculate_dir_size(path).await?;
+        assert_eq!(size, Some(14));
+
+        // Test non-existent path
+        let non_existent = path.join("does_not_exist");
+        let size = JobManager::calculate_dir_size(&non_existent).await?;
+        assert_eq!(size, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_running_jobs_tracks_prunes() {
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        assert!(manager.running_jobs().await.is_empty());
+
+        let guard = manager.track_prune("test");
+        assert_eq!(manager.running_jobs().await, vec!["test".to_string()]);
+
+        drop(guard);
+        assert!(manager.running_jobs().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replication_runs_once_more_after_overlapping_backup() {
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![
+                BackupSet {
+                    name: "test".to_string(),
+                    source: Some("/tmp/source".to_string()),
+                    target: "/tmp/repo".to_string(),
+                    replica_target: Some("/tmp/replica".to_string()),
+                    ..Default::default()
+                },
+                BackupSet {
+                    name: "plain".to_string(),
+                    source: Some("/tmp/source".to_string()),
+                    target: "/tmp/repo-plain".to_string(),
+                    ..Default::default()
+                },
+            ],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+
+        let guard = manager.track_replication("test", true).unwrap();
+        let err = manager.replicate("test").await.unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+        assert!(!manager.take_replication_rerun("test"));
+
+        // A backup finishing meanwhile asks for one more copy
+        assert!(manager.track_replication("test", true).is_none());
+        assert!(manager.take_replication_rerun("test"));
+        assert!(!manager.take_replication_rerun("test"));
+
+        drop(guard);
+        assert!(manager.track_replication("test", false).is_some());
+
+        let err = manager.replicate("plain").await.unwrap_err();
+        assert!(err.to_string().contains("no replica_target"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_prune_refuses_append_only_repo() {
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "rest:https://backup.example/test".to_string(),
+                append_only: true,
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+
+        let err = manager.prune(Some("test".to_string())).await.unwrap_err();
+        let err = err.downcast_ref::<AppendOnlyRepo>().unwrap();
+        assert!(!err.detected);
+        assert!(err.to_string().contains("on the server"));
+
+        // Pruning all sets leaves append-only ones out instead of failing
+        match manager.prune(None).await.unwrap() {
+            ResponseData::PrunesTriggered { succeeded, failed } => {
+                assert!(succeeded.is_empty());
+                assert!(failed.is_empty());
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(manager.get_status().await[0].append_only);
+    }
+
+    #[tokio::test]
+    async fn test_job_slots_limit_concurrency() {
+        let mut config = Config {
+            global: GlobalConfig {
+                max_concurrent_jobs: Some(1),
+                ..Default::default()
+            },
+            backup_sets: vec![],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+
+        let first = manager.acquire_job_slot("a").await;
+        assert!(first.is_some());
+        let waiting =
+            tokio::time::timeout(Duration::from_millis(50), manager.acquire_job_slot("b")).await;
+        assert!(waiting.is_err(), "second job should wait for the slot");
+        drop(first);
+        assert!(manager.acquire_job_slot("b").await.is_some());
+
+        // Removing the limit takes effect for the next job
+        config.global.max_concurrent_jobs = None;
+        manager.sync_config(&config).await.unwrap();
+        assert!(manager.acquire_job_slot("c").await.is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_backup_waiting_for_slot_is_queued() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig {
+                max_concurrent_jobs: Some(1),
+                ..Default::default()
+            },
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let _busy = manager.acquire_job_slot("other").await;
+
+        manager.trigger_backup("test").await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.get_status().await[0].state, JobState::Queued);
+        assert!(manager.running_jobs().await.is_empty());
+        // A second trigger joins the queued backup instead of failing
+        manager.trigger_backup("test").await?;
+        assert_eq!(manager.get_status().await[0].state, JobState::Queued);
+
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_pending_changes_survive_restart() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                debounce_seconds: Some(3600),
+                ..Default::default()
+            }],
+        };
+
+        let token = CancellationToken::new();
+        let manager = JobManager::new(&config, token.clone());
+        manager
+            .handle_file_change("test", Path::new("/tmp/source/a.txt"))
+            .await?;
+        assert_eq!(manager.get_status().await[0].pending_changes, 1);
+        token.cancel();
+
+        // A new manager (daemon restart) picks the journal up and resumes debouncing
+        let manager2 = JobManager::new(&config, CancellationToken::new());
+        let status = &manager2.get_status().await[0];
+        assert_eq!(status.pending_changes, 1);
+        assert_eq!(status.state, JobState::Idle);
+        manager2.resume_pending_changes().await;
+        assert!(matches!(
+            manager2.get_status().await[0].state,
+            JobState::Debouncing { .. }
+        ));
+        manager2.shutdown_token.cancel();
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_recent_changes_are_bounded_per_cycle() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                debounce_seconds: Some(3600),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let batch = |names: &[&str]| ChangeBatch {
+            paths: names
+                .iter()
+                .map(|n| PathBuf::from(format!("/tmp/source/{}", n)))
+                .collect(),
+            coalesced: 0,
+            dropped: 0,
+        };
+
+        let many: Vec<String> = (0..25).map(|i| format!("f{:02}", i)).collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        manager.handle_file_changes("test", &batch(&many)).await?;
+        manager
+            .handle_file_changes("test", &batch(&["f10"]))
+            .await?;
+        let recent = manager.get_status().await[0].recent_changes.clone();
+        assert_eq!(recent.len(), RECENT_CHANGES_MAX);
+        assert_eq!(recent[0], Path::new("/tmp/source/f05"));
+        assert_eq!(recent.last().unwrap(), Path::new("/tmp/source/f10"));
+
+        // A started backup keeps the list until the next change begins a new cycle
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("test")
+            .unwrap()
+            .recent_changes_consumed = true;
+        assert_eq!(
+            manager.get_status().await[0].recent_changes.len(),
+            RECENT_CHANGES_MAX
+        );
+        manager
+            .handle_file_changes("test", &batch(&["new.txt"]))
+            .await?;
+        assert_eq!(
+            manager.get_status().await[0].recent_changes,
+            [PathBuf::from("/tmp/source/new.txt")]
+        );
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_schedule_retry_backs_off_then_gives_up() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig {
+                max_retries: 2,
+                retry_backoff_secs: 10,
+                ..Default::default()
+            },
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                ..Default::default()
+            }],
+        };
+
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut jobs = manager.jobs.lock().await;
+        let job = jobs.get_mut("test").unwrap();
+        assert!(manager.schedule_retry("test", job));
+        assert_eq!(
+            job.state,
+            JobState::Retrying {
+                attempt: 1,
+                next_retry_secs: 10
+            }
+        );
+        assert!(manager.schedule_retry("test", job));
+        assert_eq!(
+            job.state,
+            JobState::Retrying {
+                attempt: 2,
+                next_retry_secs: 20
+            }
+        );
+        assert!(!manager.schedule_retry("test", job));
+        assert_eq!(job.state, JobState::Error);
+        // The next failure starts a new streak
+        assert!(manager.schedule_retry("test", job));
+
+        job.set.max_retries = Some(0);
+        job.retry_attempt = 0;
+        assert!(!manager.schedule_retry("test", job));
+        assert_eq!(job.state, JobState::Error);
+
+        // The initial backup is retried past the limit until a snapshot exists
+        job.snapshot_count = Some(0);
+        assert!(!manager.schedule_retry("test", job));
+        job.set.max_retries = None;
+        for _ in 0..3 {
+            assert!(manager.schedule_retry("test", job));
+        }
+        assert_eq!(
+            job.state,
+            JobState::Retrying {
+                attempt: 3,
+                next_retry_secs: 40
+            }
+        );
+        job.snapshot_count = Some(1);
+        assert!(!manager.schedule_retry("test", job));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_failed_backup_survives_restart() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                ..Default::default()
+            }],
+        };
+
+        let manager = JobManager::new(&config, CancellationToken::new());
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let job = jobs.get_mut("test").unwrap();
+            job.last_backup = Some(BackupResult {
+                snapshot_id: String::new(),
+                timestamp: Utc::now(),
+                added_bytes: 0,
+                duration_secs: 2.0,
+                success: false,
+                error_message: Some("repository locked".to_string()),
+                peak_rss_bytes: None,
+                cpu_secs: None,
+                hooks: Vec::new(),
+            });
+            job.snapshot_count = Some(4);
+            manager.save_state(&jobs);
+        }
+
+        // A restarted daemon shows the failure before querying the repository
+        let manager2 = JobManager::new(&config, CancellationToken::new());
+        assert_eq!(manager2.get_status().await[0].last_backup, None);
+        manager2.restore_state().await;
+        let status = &manager2.get_status().await[0];
+        assert_eq!(status.state, JobState::Error);
+        assert_eq!(status.snapshot_count, Some(4));
+        assert_eq!(
+            status
+                .last_backup
+                .as_ref()
+                .unwrap()
+                .error_message
+                .as_deref(),
+            Some("repository locked")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_interrupted_backup_runs_again_after_restart() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig {
+                shutdown_grace_secs: Some(30),
+                ..Default::default()
+            },
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                ..Default::default()
+            }],
+        };
+
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut events = manager.subscribe();
+        manager.jobs.lock().await.get_mut("test").unwrap().state = JobState::running();
+        assert_eq!(
+            manager.begin_shutdown(true).await,
+            (30, vec!["test".to_string()])
+        );
+        match events.try_recv()? {
+            Response::Event(ResponseData::ShuttingDown {
+                grace_secs,
+                running,
+            }) => {
+                assert_eq!(grace_secs, 30);
+                assert_eq!(running, vec!["test".to_string()]);
+            }
+            other => panic!("unexpected event {:?}", other),
         }
+        // Nothing new starts while running backups finish
+        let err = manager.trigger_backup("test").await.unwrap_err();
+        assert!(err.to_string().contains("shutting down"), "{}", err);
+        manager.mark_interrupted().await;
+
+        // The restarted daemon runs the backup again without waiting for a change
+        let token = CancellationToken::new();
+        token.cancel();
+        let manager2 = JobManager::new(&config, token);
+        manager2.restore_state().await;
+        manager2.resume_pending_changes().await;
+        let jobs = manager2.jobs.lock().await;
+        let job = &jobs["test"];
+        assert!(job.immediate_trigger);
+        assert!(matches!(job.state, JobState::Debouncing { .. }));
+        assert!(job.interrupted);
+        Ok(())
     }
 
-    /// Automatically prune a set after successful backup if retention policy exists.
-    /// This is called asynchronously and logs errors instead of returning them.
-    async fn auto_prune_after_backup(&self, set_name: &str, event_tx: broadcast::Sender<Response>) {
-        if self.shutdown_token.is_cancelled() {
-            return;
-        }
-
-        // We no longer need to sleep here because we await the refresh_set_status
-        // in the backup path before calling this, ensuring the repo lock is released
-        // and metrics are up to date.
+    #[tokio::test]
+    #[serial]
+    async fn test_handoff_holds_sets_until_old_backup_finishes() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str| BackupSet {
+            name: name.to_string(),
+            source: Some(format!("/tmp/source-{}", name)),
+            target: format!("/tmp/repo-{}", name),
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![set("docs"), set("home")],
+        };
 
-        if self.shutdown_token.is_cancelled() {
-            return;
+        // Both backups run in the old daemon, each with a change pending after it
+        let old = JobManager::new(&config, CancellationToken::new());
+        {
+            let mut jobs = old.jobs.lock().await;
+            for name in ["docs", "home"] {
+                let job = jobs.get_mut(name).unwrap();
+                job.state = JobState::running();
+                let mut captured = ChangeJournal::default();
+                captured.record(Path::new("/captured.txt"));
+                job.journal_in_flight = Some(captured);
+                job.journal.record(Path::new("/later.txt"));
+            }
         }
+        let running = old.begin_handoff().await?;
+        assert_eq!(running, vec!["docs".to_string(), "home".to_string()]);
+        assert_eq!(
+            ChangeJournal::load(&paths::change_journal_path("home")).len(),
+            1
+        );
 
-        let effective_set = {
-            let jobs = self.jobs.lock().await;
-            match jobs.get(set_name) {
-                Some(job) => self.with_effective_retention(&job.set).await,
-                None => {
-                    warn!("Cannot auto-prune set {}: set no longer exists", set_name);
-                    return;
-                }
-            }
+        let token = CancellationToken::new();
+        token.cancel();
+        let new = JobManager::new(&config, token);
+        new.hold_handed_over(4242, &running).await;
+        assert!(!paths::handoff_journal_path("home").exists());
+        let err = new.trigger_backup("home").await.unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+
+        let result = BackupResult {
+            snapshot_id: "abc123".to_string(),
+            timestamp: Utc::now(),
+            added_bytes: 10,
+            duration_secs: 2.0,
+            success: true,
+            error_message: None,
+            peak_rss_bytes: None,
+            cpu_secs: None,
+            hooks: Vec::new(),
         };
+        // Only the replaced daemon's process may report
+        assert!(new
+            .finish_handed_over(Some(1), "home", result.clone())
+            .await
+            .is_err());
+        new.finish_handed_over(Some(4242), "home", result.clone())
+            .await?;
+        {
+            let jobs = new.jobs.lock().await;
+            let home = &jobs["home"];
+            assert_eq!(home.last_backup.as_ref(), Some(&result));
+            assert_eq!(home.journal.sample(10), vec!["/later.txt"]);
+            assert!(!home.handed_over);
+        }
 
-        info!("Auto-pruning set {} after successful backup", set_name);
+        // A backup the old daemon never reported runs again with its changes
+        new.release_handed_over().await;
+        let jobs = new.jobs.lock().await;
+        let docs = &jobs["docs"];
+        assert!(docs.immediate_trigger);
+        assert_eq!(docs.journal.sample(10), vec!["/captured.txt", "/later.txt"]);
+        assert!(matches!(docs.state, JobState::Debouncing { .. }));
+        Ok(())
+    }
 
-        // Reuse existing prune_set() logic
-        match self.prune_set(set_name, &effective_set).await {
-            Ok(reclaimed) => {
-                // Send event for transparency
-                let _ = event_tx.send(Response::Ok(Some(ResponseData::PruneComplete {
-                    set_name: set_name.to_string(),
-                    reclaimed_bytes: reclaimed,
-                })));
-            }
-            Err(e) => {
-                error!(
-                    "Auto-prune failed for set {} (backup succeeded): {}",
-                    set_name, e
-                );
+    #[tokio::test]
+    #[serial]
+    async fn test_health() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str| BackupSet {
+            name: name.to_string(),
+            source: Some("/tmp/source".to_string()),
+            target: format!("/tmp/{}", name),
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig {
+                max_age_hours: Some(24),
+                ..Default::default()
+            },
+            backup_sets: vec![set("broken"), set("fresh"), set("new"), set("stale")],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let now = Utc::now();
+        let backup = |age_hours: i64, error: Option<&str>| BackupResult {
+            snapshot_id: "abc".to_string(),
+            timestamp: now - chrono::Duration::hours(age_hours),
+            added_bytes: 0,
+            duration_secs: 1.0,
+            success: error.is_none(),
+            error_message: error.map(str::to_string),
+            peak_rss_bytes: None,
+            cpu_secs: None,
+            hooks: Vec::new(),
+        };
 
-                if !self.shutdown_token.is_cancelled() {
-                    let _ = notify_rust::Notification::new()
-                        .summary("Automatic Prune Failed")
-                        .body(&format!(
-                            "Retention cleanup failed for '{}'. Manual prune may be needed.",
-                            set_name
-                        ))
-                        .icon("dialog-warning")
-                        .show();
-                }
+        assert_eq!(manager.health(now).await, (HealthLevel::Healthy, vec![]));
+        {
+            let mut jobs = manager.jobs.lock().await;
+            for job in jobs.values_mut() {
+                job.status_loaded = true;
+                job.journal.record(Path::new("/tmp/source/a.txt"));
             }
+            let broken = jobs.get_mut("broken").unwrap();
+            broken.state = JobState::Error;
+            broken.last_backup = Some(backup(1, Some("repository locked")));
+            jobs.get_mut("fresh").unwrap().last_backup = Some(backup(2, None));
+            jobs.get_mut("stale").unwrap().last_backup = Some(backup(48, None));
         }
-    }
 
-    /// Creates a copy of the BackupSet with effective retention policy.
-    /// Falls back to global retention if per-set retention is not specified.
-    async fn with_effective_retention(&self, set: &BackupSet) -> BackupSet {
-        let mut effective = set.clone();
-        if effective.retention.is_none() {
-            effective.retention = self.global_retention.lock().await.clone();
+        let (overall, issues) = manager.health(now).await;
+        assert_eq!(overall, HealthLevel::Failing);
+        let summary: Vec<(&str, HealthIssueKind)> = issues
+            .iter()
+            .map(|i| (i.set_name.as_str(), i.kind))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("broken", HealthIssueKind::Failed),
+                ("new", HealthIssueKind::Stale),
+                ("stale", HealthIssueKind::Stale),
+            ]
+        );
+        assert_eq!(issues[0].message, "Last backup failed: repository locked");
+        assert_eq!(
+            issues[2].message,
+            "Last successful backup was 2 days ago (limit 24h)"
+        );
+
+        // Without pending changes or a schedule, an old backup is up to date
+        {
+            let mut jobs = manager.jobs.lock().await;
+            jobs.get_mut("broken").unwrap().state = JobState::Idle;
+            jobs.get_mut("broken").unwrap().last_backup = Some(backup(1, None));
+            jobs.get_mut("new").unwrap().last_backup = Some(backup(1, None));
+            jobs.get_mut("stale").unwrap().journal.take();
         }
-        effective
+        assert_eq!(manager.health(now).await, (HealthLevel::Healthy, vec![]));
+
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("fresh")
+            .unwrap()
+            .repo_unreachable = true;
+        assert_eq!(manager.health(now).await.0, HealthLevel::Failing);
+        manager.shutdown_token.cancel();
+        Ok(())
     }
 
-    async fn perform_unmount(name: &str, job: &mut Job) -> Result<()> {
-        if !job.is_mounted {
-            return Ok(());
+    #[tokio::test]
+    #[serial]
+    async fn test_reinit_moves_old_repository_aside() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join("data"))?;
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "home".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: repo.to_string_lossy().to_string(),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let history_path = vigil_lib::paths::history_path("home");
+        std::fs::create_dir_all(history_path.parent().unwrap())?;
+        std::fs::write(&history_path, "{}\n")?;
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let job = jobs.get_mut("home").unwrap();
+            job.state = JobState::Error;
+            job.snapshot_count = Some(12);
+            job.last_verified = Some(Utc::now());
         }
 
-        // Warn if unmounting during an active backup
-        if matches!(job.state, JobState::Running) {
-            warn!(
-                "Unmounting set {} while backup is running - this may cause the backup to fail",
-                name
-            );
+        let token = |data| match data {
+            ResponseData::PurgeToken { token, .. } => token,
+            other => panic!("unexpected {:?}", other),
+        };
+        let issued = token(manager.purge_token("home").await?);
+        assert!(manager
+            .reinit("home", "0000", false)
+            .await
+            .unwrap_err()
+            .is::<InvalidPurgeToken>());
+
+        // Busy while a backup runs; the token survives
+        manager.jobs.lock().await.get_mut("home").unwrap().state = JobState::Running {
+            percent: None,
+            eta_secs: None,
+            scanning: None,
+        };
+        assert!(manager
+            .reinit("home", &issued, false)
+            .await
+            .unwrap_err()
+            .is::<RepoBusy>());
+        manager.jobs.lock().await.get_mut("home").unwrap().state = JobState::Error;
+
+        // The engine may be missing here, so only the steps before `init` are checked
+        let _ = manager.reinit("home", &issued, false).await;
+        let archived: Vec<_> = std::fs::read_dir(tmp.path())?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("repo.old-"))
+            .collect();
+        assert_eq!(archived.len(), 1);
+        assert!(tmp.path().join(&archived[0]).join("data").is_dir());
+        assert!(!history_path.exists());
+        {
+            let jobs = manager.jobs.lock().await;
+            let job = jobs.get("home").unwrap();
+            assert_eq!(job.state, JobState::Idle);
+            assert_eq!(job.snapshot_count, Some(0));
+            assert_eq!(job.last_verified, None);
         }
+        // Single use
+        assert!(manager
+            .reinit("home", &issued, false)
+            .await
+            .unwrap_err()
+            .is::<InvalidPurgeToken>());
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
-        info!("Unmounting set {}", name);
-        let mount_path = vigil_lib::paths::mount_path(name);
-
-        // 1. Try fusermount3 -u
-        let child = tokio::process::Command::new("fusermount3")
-            .arg("-u")
-            .arg(&mount_path)
-            .spawn();
-
-        let success = match child {
-            Ok(mut c) => {
-                let status = c.wait().await?;
-                status.success()
-            }
-            Err(_) => false, // fusermount3 not found or failed to spawn
+    #[tokio::test]
+    #[serial]
+    async fn test_restore_is_recorded_in_history() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "home".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: tmp
+                    .path()
+                    .join("missing-repo")
+                    .to_string_lossy()
+                    .to_string(),
+                ..Default::default()
+            }],
         };
-
-        if !success {
-            debug!(
-                "fusermount3 failed or not found, killing restic process for {}",
-                name
-            );
-            if let Some(mut child) = job.mount_process.take() {
-                let _ = child.kill().await;
-            }
-        } else {
-            // Even if fusermount3 succeeded, we should clean up the restic process
-            if let Some(mut child) = job.mount_process.take() {
-                // Restic should exit on its own when unmounted, but we'll wait a bit then kill if needed
-                match tokio::time::timeout(
-                    Duration::from_secs(MOUNT_GRACEFUL_EXIT_TIMEOUT_SECS),
-                    child.wait(),
-                )
-                .await
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut events = manager.subscribe();
+        let requester = Requester {
+            uid: Some(1000),
+            user: Some("alice".to_string()),
+            client: Some("/usr/bin/vigil".to_string()),
+        };
+        let target = tmp.path().join("restored");
+        manager
+            .restore(
+                "home",
+                Some("a1b2c3d4".to_string()),
+                Some("/tmp/source/notes.txt".to_string()),
+                target.clone(),
+                requester.clone(),
+            )
+            .await?;
+        // The repository does not exist, so the restore fails either way
+        tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                if let Ok(Response::Event(ResponseData::RestoreFailed { .. })) = events.recv().await
                 {
-                    Ok(_) => debug!("Restic mount process for {} exited cleanly", name),
-                    Err(_) => {
-                        debug!("Restic mount process for {} did not exit, killing", name);
-                        let _ = child.kill().await;
-                    }
+                    break;
                 }
             }
-        }
-
-        job.is_mounted = false;
-        job.mount_process = None;
-
+        })
+        .await?;
+
+        let (runs, restores) = manager.history("home", None, &[HistoryOp::Restore]).await?;
+        assert!(runs.is_empty());
+        assert_eq!(restores.len(), 1);
+        let record = &restores[0];
+        assert_eq!(record.snapshot_id, "a1b2c3d4");
+        assert_eq!(record.path.as_deref(), Some("/tmp/source/notes.txt"));
+        assert_eq!(record.target, target.to_string_lossy());
+        assert_eq!(record.requested_by, requester);
+        assert!(!record.success);
+        assert!(record.error.is_some());
+        // Backups only by default
+        let (_, restores) = manager.history("home", None, &[]).await?;
+        assert!(restores.is_empty());
+        manager.shutdown_token.cancel();
         Ok(())
     }
 
-    async fn calculate_dir_size(path: &std::path::Path) -> Result<Option<u64>> {
-        if !path.exists() {
-            return Ok(None);
-        }
-        let mut total_size = 0;
-        let mut entries = match tokio::fs::read_dir(path).await {
-            Ok(e) => e,
-            Err(_) => return Ok(None), // treat inaccessible dirs as unknown size
+    #[tokio::test]
+    #[serial]
+    async fn test_restore_into_sources_pauses_change_triggered_backups() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let source = tmp.path().join("source");
+        std::fs::create_dir_all(&source)?;
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "home".to_string(),
+                source: Some(source.to_string_lossy().to_string()),
+                target: tmp
+                    .path()
+                    .join("missing-repo")
+                    .to_string_lossy()
+                    .to_string(),
+                debounce_seconds: Some(3600),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let state = |manager: JobManager| async move {
+            manager.jobs.lock().await.get("home").unwrap().state.clone()
         };
 
-        while let Some(entry) = entries.next_entry().await? {
-            let metadata = entry.metadata().await?;
-            if metadata.is_dir() {
-                if let Some(size) = Box::pin(Self::calculate_dir_size(&entry.path())).await? {
-                    total_size += size;
+        // A restore elsewhere leaves backups alone
+        manager
+            .restore(
+                "home",
+                None,
+                None,
+                tmp.path().join("elsewhere"),
+                Requester::default(),
+            )
+            .await?;
+        assert_eq!(state(manager.clone()).await, JobState::Idle);
+        assert!(manager.resume("home").await.is_err());
+
+        manager
+            .restore(
+                "home",
+                None,
+                None,
+                source.join("restored"),
+                Requester::default(),
+            )
+            .await?;
+        // The repository does not exist, so the restore fails quickly; the
+        // pause applies either way
+        let until = tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                if let JobState::Paused { until: Some(until) } = state(manager.clone()).await {
+                    break until;
                 }
-            } else {
-                total_size += metadata.len();
+                tokio::time::sleep(Duration::from_millis(50)).await;
             }
-        }
-        Ok(Some(total_size))
-    }
-}
+        })
+        .await?;
+        assert!(until > Utc::now() + chrono::Duration::seconds(590));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serial_test::serial;
-    use std::fs;
-    use std::os::unix::fs::PermissionsExt;
-    use tempfile::tempdir;
-    use tokio_util::sync::CancellationToken;
-    use vigil_lib::config::GlobalConfig;
-    use vigil_lib::paths;
+        // Changes are recorded but wait for the pause to end
+        manager
+            .handle_file_change("home", &source.join("restored/notes.txt"))
+            .await?;
+        assert!(matches!(
+            state(manager.clone()).await,
+            JobState::Paused { .. }
+        ));
+        assert_eq!(manager.jobs.lock().await["home"].journal.len(), 1);
+        let saved = DaemonState::load(&vigil_lib::paths::daemon_state_path());
+        assert_eq!(saved.sets["home"].paused_until, Some(until));
+
+        manager.resume("home").await?;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !matches!(state(manager.clone()).await, JobState::Debouncing { .. }) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await?;
+        assert!(manager.resume("home").await.is_err());
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
-    /// Tests the debounce state machine with real restic integration.
-    ///
-    /// **NOTE:** This test modifies XDG environment variables and must be run single-threaded:
-    /// ```bash
-    /// cargo test -p vigil-daemon --lib -- --ignored --test-threads=1
-    /// ```
     #[tokio::test]
     #[serial]
-    #[ignore]
-    async fn test_debounce_logic() -> Result<()> {
-        let _ = tracing_subscriber::fmt::try_init();
-
-        // Setup: Isolated temp environment
+    async fn test_purge_only_known_repositories() -> Result<()> {
         let tmp = tempdir()?;
-        let source_path = tmp.path().join("source");
-        let repo_path = tmp.path().join("repo");
-        fs::create_dir(&source_path)?;
-        fs::write(source_path.join("test.txt"), "test data")?;
-
-        // Setup: Isolated config/data dirs via env vars
-        let config_home = tmp.path().join("config");
-        let data_home = tmp.path().join("data");
-        fs::create_dir_all(&config_home)?;
-        fs::create_dir_all(&data_home)?;
-        std::env::set_var("XDG_CONFIG_HOME", &config_home);
-        std::env::set_var("XDG_DATA_HOME", &data_home);
-
-        // Setup: Create password file
-        let pw_file = paths::password_path();
-        fs::create_dir_all(pw_file.parent().unwrap())?;
-        fs::write(&pw_file, "testpassword")?;
-        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join("keys"))?;
+        std::fs::write(repo.join("config"), "x")?;
+        let documents = tmp.path().join("documents");
+        std::fs::create_dir_all(&documents)?;
+        let set = |name: &str, target: &Path| BackupSet {
+            name: name.to_string(),
+            source: Some("/tmp/source".to_string()),
+            target: target.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let config = |sets: Vec<BackupSet>| Config {
+            global: GlobalConfig::default(),
+            backup_sets: sets,
+        };
+        let token = |data| match data {
+            ResponseData::PurgeToken { token, .. } => token,
+            other => panic!("unexpected {:?}", other),
+        };
 
-        // Setup: Initialize restic repository
-        let executor = crate::executor::ResticExecutor::new();
-        executor.init(repo_path.to_str().unwrap()).await?;
+        let manager = JobManager::new(
+            &config(vec![set("home", &repo), set("docs", &documents)]),
+            CancellationToken::new(),
+        );
+        assert!(manager.purge_token("stranger").await.is_err());
+        manager.sync_config(&config(vec![])).await?;
+        manager.shutdown_token.cancel();
+
+        // The removed sets' repositories are known after a restart
+        let manager = JobManager::new(&config(vec![]), CancellationToken::new());
+        manager.restore_state().await;
+        let issued = token(manager.purge_token("docs").await?);
+        assert!(manager.purge("docs", &issued).await.is_err());
+        assert!(documents.is_dir());
+
+        let issued = token(manager.purge_token("home").await?);
+        manager.purge("home", &issued).await?;
+        assert!(!repo.exists());
+        assert!(manager.purge_token("home").await.is_err());
+        manager.shutdown_token.cancel();
+
+        let manager = JobManager::new(&config(vec![]), CancellationToken::new());
+        manager.restore_state().await;
+        assert!(manager.purge_token("home").await.is_err());
+        assert!(manager.purge_token("docs").await.is_ok());
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_status_line() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str| BackupSet {
+            name: name.to_string(),
+            source: Some("/tmp/source".to_string()),
+            target: format!("/tmp/{}", name),
+            ..Default::default()
+        };
         let config = Config {
             global: GlobalConfig::default(),
-            backup_sets: vec![BackupSet {
-                name: "test".to_string(),
-                source: Some(source_path.to_string_lossy().to_string()),
-                sources: None,
-                target: repo_path.to_string_lossy().to_string(),
-                exclude: None,
-                debounce_seconds: Some(1), // 1 second for faster test
-                retention: None,
-            }],
+            backup_sets: vec![set("docs"), set("home")],
         };
-
         let manager = JobManager::new(&config, CancellationToken::new());
-
-        // Helper to get state for "test" set
-        let get_test_state = || async {
-            manager
-                .get_status()
-                .await
-                .into_iter()
-                .find(|s| s.name == "test")
-                .map(|s| s.state)
+        let now = Utc::now();
+        let backup = |age_hours: i64, success: bool| BackupResult {
+            snapshot_id: "abc".to_string(),
+            timestamp: now - chrono::Duration::hours(age_hours),
+            added_bytes: 0,
+            duration_secs: 1.0,
+            success,
+            error_message: (!success).then(|| "repository locked".to_string()),
+            peak_rss_bytes: None,
+            cpu_secs: None,
+            hooks: Vec::new(),
         };
+        {
+            let mut jobs = manager.jobs.lock().await;
+            for job in jobs.values_mut() {
+                job.status_loaded = true;
+                job.last_backup = Some(backup(2, true));
+            }
+            jobs.get_mut("docs")
+                .unwrap()
+                .journal
+                .record(Path::new("/tmp/source/a.txt"));
+        }
 
-        // Initial state should be Idle
-        assert_eq!(get_test_state().await.unwrap(), JobState::Idle);
+        let status = manager.status_line(now).await;
+        assert_eq!(status.text, "ok · last 2h · 1 pending");
+        assert_eq!(status.level, HealthLevel::Healthy);
+        assert_eq!(status.sets, 2);
+        assert_eq!(
+            status.tooltip,
+            "docs: last backup 2 hours ago\nhome: last backup 2 hours ago"
+        );
 
-        // Trigger a file change
-        manager.handle_file_change("test").await?;
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let home = jobs.get_mut("home").unwrap();
+            home.state = JobState::Error;
+            home.last_backup = Some(backup(1, false));
+        }
+        let status = manager.status_line(now).await;
+        assert_eq!(status.text, "failing: home · last 2h · 1 pending");
+        assert_eq!(status.failing, vec!["home".to_string()]);
+
+        manager.jobs.lock().await.get_mut("docs").unwrap().state = JobState::Running {
+            percent: Some(42.0),
+            eta_secs: None,
+            scanning: None,
+        };
+        let status = manager.status_line(now).await;
+        assert_eq!(status.text, "backing up docs 42%");
+        assert_eq!(status.running, vec!["docs".to_string()]);
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
-        // Should enter Debouncing state
-        tokio::time::sleep(Duration::from_millis(100)).await;
-        let state = get_test_state().await.unwrap();
-        assert!(
-            matches!(state, JobState::Debouncing { .. }),
-            "Expected Debouncing, got {:?}",
-            state
+    #[tokio::test]
+    #[serial]
+    async fn test_backup_and_prune_wait_for_maintenance_window() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        // A window that opens two hours from now
+        let local = chrono::Local::now();
+        let window = format!(
+            "{}-{}",
+            (local + chrono::Duration::hours(2)).format("%H:%M"),
+            (local + chrono::Duration::hours(3)).format("%H:%M")
         );
+        let config = Config {
+            global: GlobalConfig {
+                maintenance_window: Some(window),
+                ..Default::default()
+            },
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                debounce_seconds: Some(0),
+                defer_backups: true,
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
 
-        // Wait for debounce to complete and backup to finish
-        // (1s debounce + real backup which is fast for small files)
-        tokio::time::sleep(Duration::from_millis(2500)).await;
-        let state = get_test_state().await.unwrap();
+        manager
+            .handle_file_change("test", Path::new("/tmp/source/a.txt"))
+            .await?;
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        let now = Utc::now();
+        let state = manager.get_status().await[0].state.clone();
+        let JobState::Scheduled { at } = state else {
+            panic!("Expected Scheduled, got {:?}", state);
+        };
+        assert!(at > now + chrono::Duration::minutes(118));
+        assert!(at < now + chrono::Duration::minutes(121));
+
+        // A queued prune waits for the same window
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let job = jobs.get_mut("test").unwrap();
+            job.prune_pending = true;
+        }
+        let earliest = manager.run_pending_prunes(now).await.unwrap();
+        assert!((earliest - at).num_seconds().abs() < 60);
+        assert!(manager.jobs.lock().await["test"].prune_pending);
+
+        // So does a deep check, unless asked to run now
+        let results = manager.verify_repos(None, "1%", false).await?;
+        assert_eq!(results[0].status, RepoOpStatus::Deferred);
+        let deferred_until = results[0].deferred_until.unwrap();
+        assert!((deferred_until - at).num_seconds().abs() < 60);
+        let earliest = manager.run_pending_verifications(now).await.unwrap();
+        assert!((earliest - at).num_seconds().abs() < 60);
         assert_eq!(
-            state,
-            JobState::Idle,
-            "Expected Idle after backup completes"
+            manager.jobs.lock().await["test"].verify_pending.as_deref(),
+            Some("1%")
         );
+        manager.shutdown_token.cancel();
 
+        // Both still wait for the window after a restart
+        let restarted = JobManager::new(&config, CancellationToken::new());
+        restarted.restore_state().await;
+        {
+            let jobs = restarted.jobs.lock().await;
+            assert!(jobs["test"].prune_pending);
+            assert_eq!(jobs["test"].verify_pending.as_deref(), Some("1%"));
+        }
+        let earliest = restarted.run_pending_verifications(now).await.unwrap();
+        assert!((earliest - at).num_seconds().abs() < 60);
+        restarted.shutdown_token.cancel();
         Ok(())
     }
 
     #[tokio::test]
     #[serial]
-    #[ignore]
-    async fn test_manual_trigger() -> Result<()> {
-        let _ = tracing_subscriber::fmt::try_init();
-
+    async fn test_queued_prune_waits_for_busy_repository() -> Result<()> {
         let tmp = tempdir()?;
-        let source_path = tmp.path().join("source");
-        let repo_path = tmp.path().join("repo");
-        fs::create_dir(&source_path)?;
-        fs::write(source_path.join("test.txt"), "test data")?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str, target: &str| BackupSet {
+            name: name.to_string(),
+            source: Some(format!("/tmp/source-{}", name)),
+            target: target.to_string(),
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig {
+                retention: Some(RetentionPolicy {
+                    keep_last: Some(3),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            backup_sets: vec![
+                set("docs", "/tmp/repo-shared"),
+                set("home", "/tmp/repo-shared"),
+                BackupSet {
+                    auto_prune: Some(false),
+                    ..set("manual", "/tmp/repo-manual")
+                },
+            ],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        for name in ["docs", "home", "manual"] {
+            manager.queue_prune(name).await;
+        }
+        {
+            let mut jobs = manager.jobs.lock().await;
+            assert!(jobs["docs"].prune_pending);
+            assert!(!jobs["manual"].prune_pending);
+            jobs.get_mut("home").unwrap().state = JobState::Running {
+                percent: None,
+                eta_secs: None,
+                scanning: None,
+            };
+        }
 
-        let config_home = tmp.path().join("config");
-        let data_home = tmp.path().join("data");
-        fs::create_dir_all(&config_home)?;
-        fs::create_dir_all(&data_home)?;
-        std::env::set_var("XDG_CONFIG_HOME", &config_home);
-        std::env::set_var("XDG_DATA_HOME", &data_home);
+        // A backup of another set writes to the shared repository
+        assert_eq!(manager.run_pending_prunes(Utc::now()).await, None);
+        assert!(manager.jobs.lock().await["docs"].prune_pending);
 
-        let pw_file = paths::password_path();
-        fs::create_dir_all(pw_file.parent().unwrap())?;
-        fs::write(&pw_file, "testpassword")?;
-        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
+        // Once it is done, one prune per repository starts
+        manager.shutdown_token.cancel();
+        manager.jobs.lock().await.get_mut("home").unwrap().state = JobState::Idle;
+        manager.run_pending_prunes(Utc::now()).await;
+        let jobs = manager.jobs.lock().await;
+        assert_ne!(jobs["docs"].prune_pending, jobs["home"].prune_pending);
+        Ok(())
+    }
 
-        let executor = crate::executor::ResticExecutor::new();
-        executor.init(repo_path.to_str().unwrap()).await?;
+    #[tokio::test]
+    #[serial]
+    async fn test_deferred_deep_check_holds_repository_and_job_slot() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str| BackupSet {
+            name: name.to_string(),
+            source: Some(format!("/tmp/source-{}", name)),
+            target: "/tmp/repo-shared".to_string(),
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig {
+                max_concurrent_jobs: Some(1),
+                ..Default::default()
+            },
+            backup_sets: vec![set("docs"), set("home")],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let busy = manager.acquire_job_slot("other").await;
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("docs")
+            .unwrap()
+            .verify_pending = Some("1%".into());
+
+        // The check waits for a slot while holding the repository
+        assert_eq!(manager.run_pending_verifications(Utc::now()).await, None);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(manager.jobs.lock().await["docs"].verify_pending.is_none());
+        assert_eq!(manager.running_jobs().await, vec!["docs".to_string()]);
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("home")
+            .unwrap()
+            .prune_pending = true;
+        manager.run_pending_prunes(Utc::now()).await;
+        assert!(manager.jobs.lock().await["home"].prune_pending);
+
+        // The repository is released once the check is done
+        drop(busy);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !manager.running_jobs().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await?;
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_rescue_backup_lifts_matching_excludes() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
         let config = Config {
             global: GlobalConfig::default(),
             backup_sets: vec![BackupSet {
                 name: "test".to_string(),
-                source: Some(source_path.to_string_lossy().to_string()),
-                sources: None,
-                target: repo_path.to_string_lossy().to_string(),
-                exclude: None,
-                debounce_seconds: Some(60), // Long debounce to verify skip
-                retention: None,
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                exclude: Some(vec!["*.db".to_string(), "cache".to_string()]),
+                ..Default::default()
             }],
         };
-
         let manager = JobManager::new(&config, CancellationToken::new());
+        // Keep the worker from starting so the queued run can be inspected
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("test")
+            .unwrap()
+            .worker_active = true;
+
+        let err = manager
+            .trigger_rescue_backup("test", &["/tmp/source/notes.txt".to_string()])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already backed up"), "{}", err);
+        assert!(manager
+            .trigger_rescue_backup("other", &["*.db".to_string()])
+            .await
+            .is_err());
+
+        let lifted = manager
+            .trigger_rescue_backup("test", &["/tmp/source/app/state.db".to_string()])
+            .await?;
+        assert_eq!(lifted, vec!["*.db"]);
+        let jobs = manager.jobs.lock().await;
+        let job = &jobs["test"];
+        assert_eq!(job.state, JobState::Queued);
+        assert_eq!(job.run_tags, vec!["rescue"]);
+        assert_eq!(job.run_lifted_excludes, vec!["*.db"]);
+        // The configuration keeps its excludes
+        assert_eq!(job.set.exclude.as_ref().unwrap().len(), 2);
+        Ok(())
+    }
 
-        let get_test_state = || async {
-            manager
-                .get_status()
-                .await
-                .into_iter()
-                .find(|s| s.name == "test")
-                .map(|s| s.state)
+    #[tokio::test]
+    #[serial]
+    async fn test_debounce_countdown_follows_deadline() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo".to_string(),
+                debounce_seconds: Some(600),
+                ..Default::default()
+            }],
         };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        manager
+            .handle_file_change("test", Path::new("/tmp/source/a.txt"))
+            .await?;
+        assert_eq!(
+            manager.get_status().await[0].state,
+            JobState::Debouncing {
+                remaining_secs: 600
+            }
+        );
 
-        // 1. Test trigger from Idle
-        manager.trigger_backup("test").await?;
-
-        // Should enter Running immediately
-        tokio::time::sleep(Duration::from_millis(100)).await;
-        let state = get_test_state().await.unwrap();
-        assert_eq!(state, JobState::Running);
+        let deadline = Instant::now() + Duration::from_millis(1500);
+        manager
+            .jobs
+            .lock()
+            .await
+            .get_mut("test")
+            .unwrap()
+            .debounce_deadline = Some(deadline);
+        let job = &manager.jobs.lock().await["test"];
+        assert_eq!(
+            job.reported_state(deadline - Duration::from_millis(1500)),
+            JobState::Debouncing { remaining_secs: 2 }
+        );
+        assert_eq!(
+            job.reported_state(deadline - Duration::from_millis(200)),
+            JobState::Debouncing { remaining_secs: 1 }
+        );
+        assert_eq!(
+            job.reported_state(deadline + Duration::from_secs(1)),
+            JobState::Debouncing { remaining_secs: 0 }
+        );
 
-        // Wait for completion
-        tokio::time::sleep(Duration::from_millis(2000)).await;
-        assert_eq!(get_test_state().await.unwrap(), JobState::Idle);
+        manager.shutdown_token.cancel();
+        Ok(())
+    }
 
-        // 2. Test trigger from Debouncing
-        manager.handle_file_change("test").await?;
-        tokio::time::sleep(Duration::from_millis(200)).await;
-        let state = get_test_state().await.unwrap();
-        assert!(matches!(state, JobState::Debouncing { .. }));
+    #[test]
+    fn test_mounts_in_use() {
+        let used = tempdir().unwrap();
+        let unused = tempdir().unwrap();
+        let _open = fs::File::create(used.path().join("open.txt")).unwrap();
+        assert_eq!(
+            mounts_in_use(&[used.path().to_path_buf(), unused.path().to_path_buf()]),
+            vec![true, false]
+        );
+    }
 
-        manager.trigger_backup("test").await?;
+    #[tokio::test]
+    #[serial]
+    async fn test_idle_mount_is_unmounted() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let set = |name: &str, timeout: Option<u64>| BackupSet {
+            name: name.to_string(),
+            source: Some("/tmp/source".to_string()),
+            target: format!("/tmp/repo-{}", name),
+            mount_idle_timeout_secs: timeout,
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig {
+                mount_idle_timeout_secs: Some(60),
+                ..Default::default()
+            },
+            backup_sets: vec![set("idle", None), set("kept", Some(0))],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut events = manager.subscribe();
+        let now = Instant::now();
+        for (name, job) in manager.jobs.lock().await.iter_mut() {
+            let mut mount = ActiveMount::adopted(tmp.path().join(name));
+            mount.used_at = Some(now);
+            job.mounts.insert("latest".to_string(), mount);
+        }
 
-        // Should transition to Running soon (after poll)
-        tokio::time::sleep(Duration::from_millis(1000)).await;
-        let state = get_test_state().await.unwrap();
-        // It might be Running or already Idle if the backup was fast
-        assert!(matches!(state, JobState::Running | JobState::Idle));
+        manager.unmount_idle(now + Duration::from_secs(30)).await;
+        assert_eq!(manager.jobs.lock().await["idle"].mounts.len(), 1);
 
+        manager.unmount_idle(now + Duration::from_secs(61)).await;
+        let jobs = manager.jobs.lock().await;
+        assert!(jobs["idle"].mounts.is_empty());
+        assert_eq!(jobs["kept"].mounts.len(), 1);
+        assert_eq!(
+            events.try_recv()?,
+            Response::Event(ResponseData::Unmounted {
+                set_name: "idle".to_string(),
+                snapshot: "latest".to_string(),
+                reason: UnmountReason::Idle,
+            })
+        );
         Ok(())
     }
 
     #[tokio::test]
     #[serial]
-    #[ignore]
-    async fn test_initialize_status() -> Result<()> {
-        let _ = tracing_subscriber::fmt::try_init();
-
+    async fn test_unmount_one_of_several_snapshots() -> Result<()> {
         let tmp = tempdir()?;
-        let source_path = tmp.path().join("source");
-        let repo_path = tmp.path().join("repo");
-        fs::create_dir(&source_path)?;
-        fs::write(source_path.join("test.txt"), "test data")?;
-
-        let config_home = tmp.path().join("config");
-        let data_home = tmp.path().join("data");
-        fs::create_dir_all(&config_home)?;
-        fs::create_dir_all(&data_home)?;
-        std::env::set_var("XDG_CONFIG_HOME", &config_home);
-        std::env::set_var("XDG_DATA_HOME", &data_home);
-
-        let pw_file = paths::password_path();
-        fs::create_dir_all(pw_file.parent().unwrap())?;
-        fs::write(&pw_file, "testpassword")?;
-        fs::set_permissions(&pw_file, fs::Permissions::from_mode(0o600))?;
-
-        let executor = crate::executor::ResticExecutor::new();
-        executor.init(repo_path.to_str().unwrap()).await?;
-
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
         let config = Config {
             global: GlobalConfig::default(),
             backup_sets: vec![BackupSet {
-                name: "test".to_string(),
-                source: Some(source_path.to_string_lossy().to_string()),
-                sources: None,
-                target: repo_path.to_string_lossy().to_string(),
-                exclude: None,
-                debounce_seconds: Some(1),
-                retention: None,
+                name: "home".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo-home".to_string(),
+                ..Default::default()
             }],
         };
-
-        // 1. Create a backup first
         let manager = JobManager::new(&config, CancellationToken::new());
-        manager.trigger_backup("test").await?;
-        tokio::time::sleep(Duration::from_millis(2000)).await;
+        let mut events = manager.subscribe();
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let job = jobs.get_mut("home").unwrap();
+            for key in ["latest", "0123abcd"] {
+                let path = vigil_lib::paths::mount_path("home").join(key);
+                std::fs::create_dir_all(&path)?;
+                job.mounts
+                    .insert(key.to_string(), ActiveMount::adopted(path));
+            }
+        }
 
-        let status = manager.get_status().await;
-        let original_snapshot_id = status[0].last_backup.as_ref().unwrap().snapshot_id.clone();
-        assert!(!original_snapshot_id.is_empty());
+        // Full IDs are matched by their short form
+        manager
+            .unmount(Some("home".to_string()), Some("0123abcd4567ef".to_string()))
+            .await?;
+        assert_eq!(
+            events.try_recv()?,
+            Response::Event(ResponseData::Unmounted {
+                set_name: "home".to_string(),
+                snapshot: "0123abcd".to_string(),
+                reason: UnmountReason::Requested,
+            })
+        );
+        assert!(!vigil_lib::paths::mount_path("home")
+            .join("0123abcd")
+            .exists());
+        let keys: Vec<String> = manager.jobs.lock().await["home"]
+            .mounts
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(keys, vec!["latest".to_string()]);
+
+        let err = manager
+            .unmount(Some("home".to_string()), Some("0123abcd".to_string()))
+            .await
+            .unwrap_err();
+        assert!(err.is::<NotMounted>());
+        assert!(manager
+            .unmount(None, Some("latest".to_string()))
+            .await
+            .is_err());
+
+        manager.unmount(None, None).await?;
+        assert!(manager.jobs.lock().await["home"].mounts.is_empty());
+        Ok(())
+    }
 
-        // 2. Create a new manager (simulating daemon restart)
-        let manager2 = JobManager::new(&config, CancellationToken::new());
-        // Initially last_backup should be None
-        assert!(manager2.get_status().await[0].last_backup.is_none());
+    #[tokio::test]
+    #[serial]
+    async fn test_check_mounts_forgets_lost_mounts() -> Result<()> {
+        let tmp = tempdir()?;
+        std::env::set_var("XDG_DATA_HOME", tmp.path().join("data"));
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "home".to_string(),
+                source: Some("/tmp/source".to_string()),
+                target: "/tmp/repo-home".to_string(),
+                ..Default::default()
+            }],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let mut events = manager.subscribe();
+        // A plain directory stands in for a mount that was unmounted behind our back
+        let path = tmp.path().join("mnt");
+        std::fs::create_dir_all(&path)?;
+        {
+            let mut jobs = manager.jobs.lock().await;
+            let job = jobs.get_mut("home").unwrap();
+            job.mounts
+                .insert("latest".to_string(), ActiveMount::adopted(path.clone()));
+            let mut running = ActiveMount::adopted(path.clone());
+            running.process = Some(
+                tokio::process::Command::new("sleep")
+                    .arg("30")
+                    .kill_on_drop(true)
+                    .spawn()?,
+            );
+            job.mounts.insert("0123abcd".to_string(), running);
+        }
 
-        // 3. Initialize status
-        manager2.initialize_status().await;
+        // Status is a pure read and reports what is cached
+        assert_eq!(manager.get_status().await[0].mounts.len(), 2);
 
-        // 4. Verify last_backup is now populated with the same snapshot ID
-        let status2 = manager2.get_status().await;
+        manager.check_mounts().await;
         assert_eq!(
-            status2[0].last_backup.as_ref().unwrap().snapshot_id,
-            original_snapshot_id
+            events.try_recv()?,
+            Response::Event(ResponseData::Unmounted {
+                set_name: "home".to_string(),
+                snapshot: "latest".to_string(),
+                reason: UnmountReason::Lost,
+            })
         );
-        assert!(status2[0].last_backup.as_ref().unwrap().success);
-
+        // A running mount process is trusted without looking at the path
+        let status = &manager.get_status().await[0];
+        assert_eq!(status.mounts.len(), 1);
+        assert_eq!(status.mounts[0].snapshot, "0123abcd");
+        assert!(events.try_recv().is_err());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_calculate_dir_size() -> Result<()> {
-        let tmp = tempdir()?;
-        let path = tmp.path();
+    async fn test_run_due_schedules_plans_next_run() {
+        let set = |name: &str, schedule: &str| BackupSet {
+            name: name.to_string(),
+            source: Some("/tmp/source".to_string()),
+            target: "/tmp/repo".to_string(),
+            schedule: Some(schedule.to_string()),
+            ..Default::default()
+        };
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![set("hourly", "1h"), set("nightly", "0 3 * * *")],
+        };
+        let manager = JobManager::new(&config, CancellationToken::new());
+        let now = chrono::Utc::now();
 
-        // Create some files
-        fs::write(path.join("file1.txt"), "hello")?; // 5 bytes
-        fs::write(path.join("file2.txt"), "world")?; // 5 bytes
-        fs::create_dir(path.join("subdir"))?;
-        fs::write(path.join("subdir/file3.txt"), "test")?; // 4 bytes
+        // Nothing is planned until the repository status has loaded
+        assert_eq!(manager.run_due_schedules(now).await, None);
 
-        let size = JobManager::calculate_dir_size(path).await?;
-        assert_eq!(size, Some(14));
+        let last_backup = now - chrono::Duration::minutes(20);
+        {
+            let mut jobs = manager.jobs.lock().await;
+            for job in jobs.values_mut() {
+                job.status_loaded = true;
+            }
+            jobs.get_mut("hourly").unwrap().last_backup = Some(BackupResult {
+                snapshot_id: "abc".to_string(),
+                timestamp: last_backup,
+                added_bytes: 0,
+                duration_secs: 1.0,
+                success: true,
+                error_message: None,
+                peak_rss_bytes: None,
+                cpu_secs: None,
+                hooks: Vec::new(),
+            });
+        }
 
-        // Test non-existent path
-        let non_existent = path.join("does_not_exist");
-        let size = JobManager::calculate_dir_size(&non_existent).await?;
-        assert_eq!(size, None);
+        let earliest = manager.run_due_schedules(now).await;
+        assert_eq!(earliest, Some(last_backup + chrono::Duration::hours(1)));
+        let status = manager.get_status().await;
+        let nightly = status.iter().find(|s| s.name == "nightly").unwrap();
+        assert!(nightly.next_run.unwrap() > now);
+        assert!(status.iter().all(|s| s.state == JobState::Idle));
+        manager.shutdown_token.cancel();
+    }
 
-        Ok(())
+    #[test]
+    fn test_is_verification_stale() {
+        let now = Utc::now();
+        let days_ago = |d| Some(now - chrono::Duration::days(d));
+        assert!(!is_verification_stale(None, 0, now));
+        assert!(!is_verification_stale(days_ago(100), 0, now));
+        assert!(is_verification_stale(None, 30, now));
+        assert!(!is_verification_stale(days_ago(29), 30, now));
+        assert!(is_verification_stale(days_ago(31), 30, now));
+    }
+
+    #[test]
+    fn test_parse_verification_reads_old_records() {
+        let at: DateTime<Utc> = "2026-10-01T03:00:00Z".parse().unwrap();
+        let old = parse_verification(&serde_json::to_string(&at).unwrap());
+        assert_eq!(old.verified_at, Some(at));
+        assert!(old.last_check.is_none());
+
+        let record = VerificationRecord {
+            verified_at: Some(at),
+            last_check: Some(VerifyOutcome {
+                checked_at: at + chrono::Duration::days(7),
+                subset: "5%".to_string(),
+                success: false,
+                error: Some("pack damaged".to_string()),
+            }),
+        };
+        let parsed = parse_verification(&serde_json::to_string(&record).unwrap());
+        assert_eq!(parsed.verified_at, Some(at));
+        assert_eq!(parsed.last_check, record.last_check);
+        assert!(parse_verification("garbage").verified_at.is_none());
+    }
+
+    #[test]
+    fn test_group_by_host() {
+        let snapshot = |host: Option<&str>, hours_ago: i64| SnapshotInfo {
+            id: format!("{:064}", hours_ago),
+            short_id: format!("{:08}", hours_ago),
+            timestamp: chrono::Utc::now() - chrono::Duration::hours(hours_ago),
+            paths: vec![],
+            tags: vec![],
+            total_bytes: None,
+            hostname: host.map(str::to_string),
+        };
+        let snapshots = vec![
+            snapshot(Some("desktop"), 30),
+            snapshot(Some("laptop"), 20),
+            snapshot(Some("nas"), 5),
+            snapshot(Some("desktop"), 2),
+            snapshot(None, 50),
+        ];
+
+        let hosts = group_by_host(&snapshots, "laptop");
+        let names: Vec<&str> = hosts.iter().map(|h| h.hostname.as_str()).collect();
+        assert_eq!(names, vec!["laptop", "desktop", "nas", ""]);
+        assert!(hosts[0].is_local);
+        assert!(hosts[1..].iter().all(|h| !h.is_local));
+        assert_eq!(hosts[1].snapshot_count, 2);
+        assert_eq!(hosts[1].latest, snapshots[3].timestamp);
+    }
+
+    #[test]
+    fn test_moved_diff_operands() {
+        let snapshot = |id: &str, paths: &[&str]| SnapshotInfo {
+            id: id.to_string(),
+            short_id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            paths: paths.iter().map(PathBuf::from).collect(),
+            tags: vec!["vigil-path:/home/me".to_string()],
+            total_bytes: None,
+            hostname: None,
+        };
+        let before = snapshot("aaaa", &["/home/me"]);
+        let after = snapshot("bbbb", &["/mnt/new-disk/me"]);
+        assert_eq!(
+            moved_diff_operands(&before, &after),
+            Some((
+                "aaaa:/home/me".to_string(),
+                "bbbb:/mnt/new-disk/me".to_string()
+            ))
+        );
+        assert_eq!(moved_diff_operands(&after, &after), None);
+        // Several sources cannot be paired up reliably
+        let multi = snapshot("cccc", &["/home/me", "/etc"]);
+        assert_eq!(moved_diff_operands(&before, &multi), None);
+    }
+
+    #[test]
+    fn test_find_snapshot() {
+        let snapshot = |id: &str| SnapshotInfo {
+            id: id.to_string(),
+            short_id: id[..8].to_string(),
+            timestamp: chrono::Utc::now(),
+            paths: Vec::new(),
+            tags: Vec::new(),
+            total_bytes: None,
+            hostname: None,
+        };
+        let snapshots = [snapshot("1a2b3c4d5e6f"), snapshot("1a2b9999aaaa")];
+        assert_eq!(
+            find_snapshot(&snapshots, "1A2B3C4D").unwrap().id,
+            "1a2b3c4d5e6f"
+        );
+        assert_eq!(
+            find_snapshot(&snapshots, "1a2b9999aaaa").unwrap().id,
+            "1a2b9999aaaa"
+        );
+        assert!(find_snapshot(&snapshots, "1a2b")
+            .unwrap_err()
+            .to_string()
+            .contains("ambiguous"));
+        assert!(find_snapshot(&snapshots, "ffff").is_err());
+        // Forgetting needs an explicit ID
+        assert!(find_snapshot(&snapshots, "latest").is_err());
+        assert!(find_snapshot(&snapshots, "").is_err());
+    }
+
+    #[test]
+    fn test_replica_lag() {
+        let now: DateTime<Utc> = "2026-10-16T12:00:00Z".parse().unwrap();
+        let snapshot = |id: &str, host: &str, hours_ago: i64| SnapshotInfo {
+            id: id.to_string(),
+            short_id: id.to_string(),
+            timestamp: now - chrono::Duration::hours(hours_ago),
+            paths: Vec::new(),
+            tags: Vec::new(),
+            total_bytes: None,
+            hostname: Some(host.to_string()),
+        };
+        let primary = [
+            snapshot("aaaa", "laptop", 30),
+            snapshot("bbbb", "laptop", 6),
+            snapshot("cccc", "laptop", 1),
+        ];
+        // Copies get new IDs but keep the time and host
+        let replica = [
+            snapshot("1111", "laptop", 30),
+            snapshot("2222", "desktop", 6),
+        ];
+        assert_eq!(replica_lag(&primary, &replica, now), (2, 6 * 3600));
+
+        let caught_up = [
+            snapshot("1111", "laptop", 30),
+            snapshot("2222", "laptop", 6),
+            snapshot("3333", "laptop", 1),
+        ];
+        assert_eq!(replica_lag(&primary, &caught_up, now), (0, 0));
+        assert_eq!(replica_lag(&[], &[], now), (0, 0));
     }
 }
@@ -0,0 +1,20 @@
+use crate::manager::JobManager;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How often mount processes and mount points are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Forgets mounts whose process died or that were unmounted outside the
+/// daemon until `token` is cancelled, so `Status` replies from cached state
+/// without touching processes or `/proc/mounts`.
+pub async fn run(manager: Arc<JobManager>, token: CancellationToken) {
+    loop {
+        manager.check_mounts().await;
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+        }
+    }
+}
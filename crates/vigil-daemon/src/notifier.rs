@@ -0,0 +1,258 @@
+use crate::capabilities::{Capabilities, Capability};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, warn};
+use vigil_lib::config::{render_notify_command, NotificationPolicy, NotifyBackend, NotifyEvent};
+
+/// How long a notification command may run before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One notification, independent of how it is delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// The event that raised it; `None` for the digest summary, which is not
+    /// filtered by event.
+    pub event: Option<NotifyEvent>,
+    pub set_name: Option<String>,
+    pub title: String,
+    pub body: String,
+    /// Freedesktop icon name, e.g. `dialog-error`.
+    pub icon: &'static str,
+}
+
+impl Notification {
+    /// The event's name as commands see it; `digest` for the digest summary.
+    fn event_name(&self) -> String {
+        self.event
+            .map_or_else(|| "digest".to_string(), |e| e.to_string())
+    }
+
+    /// `VIGIL_*` variables describing the notification to a command.
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("VIGIL_EVENT", self.event_name()),
+            ("VIGIL_SET", self.set_name.clone().unwrap_or_default()),
+            ("VIGIL_TITLE", self.title.clone()),
+            ("VIGIL_BODY", self.body.clone()),
+        ]
+    }
+}
+
+/// Delivers notifications. Delivery is best effort: failures are logged, never returned.
+pub trait Notifier: Send + Sync {
+    fn send(&self, notification: &Notification);
+}
+
+/// Desktop notifications through `notify-send`'s D-Bus interface.
+pub struct DesktopNotifier {
+    available: bool,
+}
+
+impl Notifier for DesktopNotifier {
+    fn send(&self, notification: &Notification) {
+        if !self.available {
+            debug!(
+                "Skipping desktop notification (notify-send unavailable): {}",
+                notification.title
+            );
+            return;
+        }
+        let _ = notify_rust::Notification::new()
+            .summary(&notification.title)
+            .body(&notification.body)
+            .icon(notification.icon)
+            .show();
+    }
+}
+
+/// Runs a shell command per notification, e.g. to forward it to a push service.
+pub struct CommandNotifier {
+    command: String,
+}
+
+impl Notifier for CommandNotifier {
+    fn send(&self, notification: &Notification) {
+        let command = self.command.clone();
+        let notification = notification.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_command(&command, &[], &notification).await {
+                warn!(
+                    "Notification command failed for '{}': {}",
+                    notification.title, e
+                );
+            }
+        });
+    }
+}
+
+/// Runs a set's `notify_command` per notification, with the placeholders
+/// filled in. The `VIGIL_*` variables are set as for `CommandNotifier`.
+pub struct TemplateNotifier {
+    template: String,
+}
+
+impl TemplateNotifier {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.to_string(),
+        }
+    }
+
+    /// The placeholder values of `notification`, in `NOTIFY_PLACEHOLDERS` order.
+    fn args(notification: &Notification) -> Vec<String> {
+        vec![
+            notification.event_name(),
+            notification.set_name.clone().unwrap_or_default(),
+            notification.body.clone(),
+        ]
+    }
+}
+
+impl Notifier for TemplateNotifier {
+    fn send(&self, notification: &Notification) {
+        // Validation rejects templates that do not render
+        let command = match render_notify_command(&self.template) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Not running notify_command: {}", e);
+                return;
+            }
+        };
+        let notification = notification.clone();
+        tokio::spawn(async move {
+            let args = Self::args(&notification);
+            if let Err(e) = run_command(&command, &args, &notification).await {
+                warn!("notify_command failed for '{}': {}", notification.title, e);
+            }
+        });
+    }
+}
+
+/// Drops every notification.
+pub struct NullNotifier;
+
+impl Notifier for NullNotifier {
+    fn send(&self, notification: &Notification) {
+        debug!("Notifications disabled, dropping: {}", notification.title);
+    }
+}
+
+/// The notifier for `policy`'s backend.
+pub fn for_policy(policy: &NotificationPolicy, capabilities: &Capabilities) -> Box<dyn Notifier> {
+    match (policy.backend(), policy.command.as_ref()) {
+        (NotifyBackend::Desktop, _) => Box::new(DesktopNotifier {
+            available: capabilities.has(Capability::Notifications),
+        }),
+        (NotifyBackend::Command, Some(command)) => Box::new(CommandNotifier {
+            command: command.clone(),
+        }),
+        // Validation rejects a command backend without a command
+        (NotifyBackend::Command, None) | (NotifyBackend::None, _) => Box::new(NullNotifier),
+    }
+}
+
+/// Runs `command` through `sh -c` with `args` as its positional parameters and
+/// the notification's `VIGIL_*` variables, killing it after `COMMAND_TIMEOUT`.
+async fn run_command(
+    command: &str,
+    args: &[String],
+    notification: &Notification,
+) -> Result<(), String> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .arg("sh")
+        .args(args)
+        .envs(notification.env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let child = cmd.spawn().map_err(|e| format!("failed to start: {}", e))?;
+    let output = tokio::time::timeout(COMMAND_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {}s", COMMAND_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+        Some(last) => format!("{}: {}", output.status, last.trim()),
+        None => output.status.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn failure() -> Notification {
+        Notification {
+            event: Some(NotifyEvent::Failure),
+            set_name: Some("home".to_string()),
+            title: "Backup Failed".to_string(),
+            body: "Backup for set 'home' failed: repository locked".to_string(),
+            icon: "dialog-error",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_receives_notification_env() {
+        let tmp = tempdir().unwrap();
+        let out = tmp.path().join("out.txt");
+        let command = format!(
+            "printf '%s|%s|%s|%s' \"$VIGIL_EVENT\" \"$VIGIL_SET\" \"$VIGIL_TITLE\" \"$VIGIL_BODY\" > {}",
+            out.display()
+        );
+        run_command(&command, &[], &failure()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "failure|home|Backup Failed|Backup for set 'home' failed: repository locked"
+        );
+
+        let digest = Notification {
+            event: None,
+            set_name: None,
+            ..failure()
+        };
+        run_command(&command, &[], &digest).await.unwrap();
+        assert!(std::fs::read_to_string(&out)
+            .unwrap()
+            .starts_with("digest||"));
+    }
+
+    #[tokio::test]
+    async fn test_template_fills_in_quoted_placeholders() {
+        let tmp = tempdir().unwrap();
+        let out = tmp.path().join("out.txt");
+        let command = render_notify_command(&format!(
+            "printf '%s|' {{event}} {{set}} {{message}} \"msg={{message}}\" \"$VIGIL_TITLE\" > {}",
+            out.display()
+        ))
+        .unwrap();
+        let pwned = tmp.path().join("pwned");
+        let notification = Notification {
+            body: format!("it's $HOME; $(touch {}) `rm -rf`", pwned.display()),
+            ..failure()
+        };
+        let args = TemplateNotifier::args(&notification);
+        run_command(&command, &args, &notification).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            format!("failure|home|{0}|msg={0}|Backup Failed|", notification.body)
+        );
+        assert!(!pwned.exists());
+    }
+
+    #[tokio::test]
+    async fn test_command_failure_reports_stderr() {
+        let err = run_command("echo 'no route to host' >&2; exit 7", &[], &failure())
+            .await
+            .unwrap_err();
+        assert!(err.contains("7"), "{}", err);
+        assert!(err.ends_with("no route to host"), "{}", err);
+    }
+}
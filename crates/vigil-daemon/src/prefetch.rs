@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// How long to wait for a fresh FUSE mount to expose the snapshot directory.
+const MOUNT_READY_TIMEOUT_SECS: u64 = 30;
+/// Poll interval while waiting for the mount to become ready.
+const MOUNT_READY_POLL_MS: u64 = 250;
+
+/// Returns the directory inside a mount that holds the selected snapshot.
+///
+/// With a snapshot ID this is `ids/<short-id>`; otherwise the latest snapshot.
+pub fn snapshot_root(mount_path: &Path, snapshot_id: Option<&str>) -> PathBuf {
+    match snapshot_id {
+        Some(id) => mount_path.join("ids").join(&id[..id.len().min(8)]),
+        None => mount_path.join("snapshots").join("latest"),
+    }
+}
+
+/// Waits for the mount to expose `root`, then lists the top `depth` directory
+/// levels beneath it so the engine's cache is warm before the user browses.
+///
+/// Returns the number of directories listed. Stops early when `token` is cancelled.
+pub async fn run(root: PathBuf, depth: u32, token: CancellationToken) -> usize {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(MOUNT_READY_TIMEOUT_SECS);
+    while !root.is_dir() {
+        if tokio::time::Instant::now() >= deadline {
+            debug!("Prefetch root {:?} did not appear, skipping", root);
+            return 0;
+        }
+        tokio::select! {
+            _ = token.cancelled() => return 0,
+            _ = tokio::time::sleep(Duration::from_millis(MOUNT_READY_POLL_MS)) => {}
+        }
+    }
+
+    tokio::task::spawn_blocking(move || walk(&root, depth, &token))
+        .await
+        .unwrap_or(0)
+}
+
+/// Breadth-first listing of `root` down to `depth` levels. Symlinks are not followed.
+pub fn walk(root: &Path, depth: u32, token: &CancellationToken) -> usize {
+    let mut listed = 0;
+    let mut queue = VecDeque::from([(root.to_path_buf(), 1)]);
+    while let Some((dir, level)) = queue.pop_front() {
+        if token.is_cancelled() {
+            break;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        listed += 1;
+        for entry in entries.flatten() {
+            if level < depth && entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                queue.push_back((entry.path(), level + 1));
+            }
+        }
+    }
+    listed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_root() {
+        let mnt = Path::new("/mnt/vigil/home");
+        assert_eq!(
+            snapshot_root(mnt, Some("a1b2c3d4e5f60718")),
+            PathBuf::from("/mnt/vigil/home/ids/a1b2c3d4")
+        );
+        assert_eq!(
+            snapshot_root(mnt, Some("a1b2")),
+            PathBuf::from("/mnt/vigil/home/ids/a1b2")
+        );
+        assert_eq!(
+            snapshot_root(mnt, None),
+            PathBuf::from("/mnt/vigil/home/snapshots/latest")
+        );
+    }
+
+    #[test]
+    fn test_walk_respects_depth() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("a/b/c")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("d")).unwrap();
+        std::fs::write(tmp.path().join("file"), "x").unwrap();
+
+        let token = CancellationToken::new();
+        assert_eq!(walk(tmp.path(), 1, &token), 1);
+        assert_eq!(walk(tmp.path(), 2, &token), 3);
+        assert_eq!(walk(tmp.path(), 10, &token), 5);
+    }
+
+    #[test]
+    fn test_walk_stops_when_cancelled() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("a")).unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(walk(tmp.path(), 3, &token), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_skips_missing_root_when_cancelled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(run(tmp.path().join("missing"), 2, token).await, 0);
+    }
+}
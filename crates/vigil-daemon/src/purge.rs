@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use vigil_lib::config::Backend;
+
+/// How long a purge token can be redeemed after it was issued.
+pub const TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Error returned when a purge is submitted with a token that was never
+/// issued for the set, has expired, or was already used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidPurgeToken {
+    pub set_name: String,
+}
+
+impl std::fmt::Display for InvalidPurgeToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid or expired purge token for '{}'. Request a new one and confirm again.",
+            self.set_name
+        )
+    }
+}
+
+impl std::error::Error for InvalidPurgeToken {}
+
+/// A purge the daemon agreed to, waiting for its confirmation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PurgeIntent {
+    pub set_name: String,
+    pub target: String,
+    expires_at: Instant,
+}
+
+/// Outstanding purge tokens. Each is single use and expires after `TOKEN_TTL`.
+#[derive(Debug, Default)]
+pub struct PurgeTokens {
+    intents: HashMap<String, PurgeIntent>,
+}
+
+impl PurgeTokens {
+    /// Records the intent to purge `set_name`'s repository at `target` and
+    /// returns the token that confirms it.
+    pub fn issue(&mut self, set_name: &str, target: &str, now: Instant) -> Result<String> {
+        self.intents.retain(|_, intent| intent.expires_at > now);
+        let token = random_token()?;
+        self.intents.insert(
+            token.clone(),
+            PurgeIntent {
+                set_name: set_name.to_string(),
+                target: target.to_string(),
+                expires_at: now + TOKEN_TTL,
+            },
+        );
+        Ok(token)
+    }
+
+    /// The target `token` would purge, if it was issued for `set_name` and
+    /// has not expired. Does not consume the token.
+    pub fn peek(&self, set_name: &str, token: &str, now: Instant) -> Option<String> {
+        self.intents
+            .get(token)
+            .filter(|intent| intent.set_name == set_name && intent.expires_at > now)
+            .map(|intent| intent.target.clone())
+    }
+
+    /// Consumes `token` if it was issued for `set_name` and has not expired.
+    pub fn redeem(&mut self, set_name: &str, token: &str, now: Instant) -> Option<PurgeIntent> {
+        self.peek(set_name, token, now)?;
+        self.intents.remove(token)
+    }
+}
+
+/// 128 random bits from the operating system, hex encoded.
+fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow!("Failed to generate a purge token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Deletes the repository at `target`. Returns false for a remote repository,
+/// which restic has no command to delete and is left in place. A directory
+/// that is not a restic repository (a `config` file and a `keys/` directory)
+/// is refused.
+pub fn delete_repository(target: &str) -> Result<bool> {
+    if Backend::of(target).is_remote() {
+        warn!("Not deleting remote repository {}", target);
+        return Ok(false);
+    }
+    let path = Path::new(target);
+    if path.is_dir() {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", target))?;
+        if !path.join("config").is_file() || !path.join("keys").is_dir() {
+            anyhow::bail!(
+                "'{}' is not a restic repository. Refusing to delete it.",
+                target
+            );
+        }
+        info!("Deleting repository at {:?}", path);
+        std::fs::remove_dir_all(&path).context("Failed to remove repository directory")?;
+    } else if path.exists() {
+        anyhow::bail!(
+            "Target path '{}' exists but is not a directory. Refusing to delete.",
+            target
+        );
+    }
+    Ok(true)
+}
+
+/// Moves the local repository at `target` aside to `<target>.old-<timestamp>`
+/// and returns the new path, so a fresh repository can be created in its place.
+pub fn archive_repository(target: &str, now: DateTime<Utc>) -> Result<PathBuf> {
+    if Backend::of(target).is_remote() {
+        anyhow::bail!("Cannot move remote repository {} aside", target);
+    }
+    let path = Path::new(target.trim_end_matches('/'));
+    if !path.is_dir() {
+        anyhow::bail!("Repository '{}' is not a directory", target);
+    }
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".old-{}", now.format("%Y%m%d-%H%M%S")));
+    let archived = PathBuf::from(name);
+    if archived.exists() {
+        anyhow::bail!("{:?} already exists; try again in a second", archived);
+    }
+    info!("Moving repository at {} to {:?}", target, archived);
+    std::fs::rename(path, &archived)
+        .with_context(|| format!("Failed to move {} to {:?}", target, archived))?;
+    Ok(archived)
+}
+
+/// Removes a purged set's mount point and cached snapshot metadata. The mount
+/// point is retried briefly, since an unmount takes a moment to propagate.
+pub async fn remove_local_state(set_name: &str) {
+    let mount_path = vigil_lib::paths::mount_path(set_name);
+    if mount_path.exists() {
+        let mut removed = false;
+        for _ in 0..5 {
+            if std::fs::remove_dir_all(&mount_path).is_ok() {
+                removed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        if !removed {
+            warn!(
+                "Could not remove mount point {:?}; it may still be busy",
+                mount_path
+            );
+        }
+    }
+    let cache_dir = vigil_lib::paths::data_dir().join("cache").join(set_name);
+    let _ = std::fs::remove_dir_all(cache_dir);
+    let _ = std::fs::remove_dir_all(vigil_lib::paths::run_log_dir(set_name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_purge_tokens_are_single_use_and_expire() {
+        let mut tokens = PurgeTokens::default();
+        let now = Instant::now();
+        let token = tokens.issue("home", "/mnt/backup/home", now).unwrap();
+        assert_eq!(token.len(), 32);
+
+        assert!(tokens.redeem("work", &token, now).is_none());
+        assert!(tokens.redeem("home", "0000", now).is_none());
+        assert_eq!(
+            tokens.peek("home", &token, now).as_deref(),
+            Some("/mnt/backup/home")
+        );
+        let intent = tokens.redeem("home", &token, now).unwrap();
+        assert_eq!(intent.target, "/mnt/backup/home");
+        assert!(tokens.redeem("home", &token, now).is_none());
+
+        let token = tokens.issue("home", "/mnt/backup/home", now).unwrap();
+        assert!(tokens.redeem("home", &token, now + TOKEN_TTL).is_none());
+    }
+
+    #[test]
+    fn test_delete_repository() {
+        let tmp = tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join("keys")).unwrap();
+        std::fs::write(repo.join("config"), "x").unwrap();
+        assert!(delete_repository(&repo.to_string_lossy()).unwrap());
+        assert!(!repo.exists());
+        // Already gone
+        assert!(delete_repository(&repo.to_string_lossy()).unwrap());
+
+        let file = tmp.path().join("file");
+        std::fs::write(&file, "x").unwrap();
+        assert!(delete_repository(&file.to_string_lossy()).is_err());
+        assert!(file.exists());
+
+        // Any other directory is left alone, through a symlink too
+        let documents = tmp.path().join("documents");
+        std::fs::create_dir_all(documents.join("keys")).unwrap();
+        assert!(delete_repository(&documents.to_string_lossy()).is_err());
+        assert!(documents.join("keys").is_dir());
+        #[cfg(unix)]
+        {
+            let link = tmp.path().join("link");
+            std::os::unix::fs::symlink(&documents, &link).unwrap();
+            assert!(delete_repository(&link.to_string_lossy()).is_err());
+            assert!(documents.is_dir());
+        }
+
+        assert!(!delete_repository("sftp:host:/srv/repo").unwrap());
+    }
+
+    #[test]
+    fn test_archive_repository() {
+        let tmp = tempdir().unwrap();
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(repo.join("data")).unwrap();
+        let now = "2026-03-01T12:30:05Z".parse().unwrap();
+        let archived = archive_repository(&format!("{}/", repo.display()), now).unwrap();
+        assert_eq!(archived, tmp.path().join("repo.old-20260301-123005"));
+        assert!(archived.join("data").is_dir());
+        assert!(!repo.exists());
+
+        // Nothing left to move, and the archive name is taken
+        assert!(archive_repository(&repo.to_string_lossy(), now).is_err());
+        std::fs::create_dir_all(&repo).unwrap();
+        assert!(archive_repository(&repo.to_string_lossy(), now).is_err());
+        assert!(archive_repository("s3:s3.amazonaws.com/bucket", now).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Full engine output of each backup run, one file per run in
+//! `~/.local/share/vigil/runs/<set>/`. The daemon log only keeps the error
+//! line of a failed backup; the run log keeps everything restic printed.
+
+use chrono::{DateTime, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use vigil_lib::types::BackupResult;
+
+/// Run logs kept per set. The oldest are removed when a run starts.
+pub const KEPT_RUN_LOGS: usize = 20;
+
+/// Names sort in the order the runs started.
+const FILE_NAME_FORMAT: &str = "%Y-%m-%dT%H-%M-%SZ.log";
+
+/// The log of one backup run. Writing is best effort: failures are logged,
+/// never returned, so a full disk does not also fail the backup.
+#[derive(Debug)]
+pub struct RunLog {
+    path: PathBuf,
+}
+
+impl RunLog {
+    /// Starts the log of a backup of `set_name` begun at `started` in `dir`,
+    /// removing the oldest logs there so at most `KEPT_RUN_LOGS` remain.
+    pub fn create(dir: &Path, set_name: &str, started: DateTime<Utc>) -> Option<Self> {
+        Self::create_with_limit(dir, set_name, started, KEPT_RUN_LOGS)
+    }
+
+    fn create_with_limit(
+        dir: &Path,
+        set_name: &str,
+        started: DateTime<Utc>,
+        keep: usize,
+    ) -> Option<Self> {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            warn!("Not keeping a run log for set {}: {}", set_name, e);
+            return None;
+        }
+        remove_oldest(dir, keep.saturating_sub(1));
+        let log = Self {
+            path: dir.join(started.format(FILE_NAME_FORMAT).to_string()),
+        };
+        log.append(&format!(
+            "# Backup of set '{}' started {}",
+            set_name,
+            started.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ));
+        Some(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A buffered writer appending to the log, for output streamed line by line.
+    pub fn writer(&self) -> Option<BufWriter<File>> {
+        self.open().map(BufWriter::new)
+    }
+
+    /// Appends `text` as a titled section, unless it is empty.
+    pub fn append_section(&self, title: &str, text: &str) {
+        let text = text.trim_end();
+        if !text.is_empty() {
+            self.append(&format!("--- {} ---\n{}", title, text));
+        }
+    }
+
+    /// Records how the run ended.
+    pub fn finish(&self, result: &anyhow::Result<BackupResult>, finished: DateTime<Utc>) {
+        let outcome = match result {
+            Ok(r) if r.success => format!("saved snapshot {}", r.snapshot_id),
+            Ok(r) => format!(
+                "failed: {}",
+                r.error_message.as_deref().unwrap_or("unknown")
+            ),
+            Err(e) => format!("failed: {}", e),
+        };
+        self.append(&format!(
+            "# Finished {}: {}",
+            finished.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            outcome
+        ));
+    }
+
+    fn append(&self, text: &str) {
+        if let Some(mut file) = self.open() {
+            if let Err(e) = writeln!(file, "{}", text) {
+                warn!("Failed to write {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    fn open(&self) -> Option<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| warn!("Failed to open {}: {}", self.path.display(), e))
+            .ok()
+    }
+}
+
+/// Removes all but the newest `keep` run logs in `dir`.
+fn remove_oldest(dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for old in &logs[..excess] {
+        let _ = std::fs::remove_file(old);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_logs_rotate_and_record_outcome() {
+        let tmp = tempdir().unwrap();
+        let dir = tmp.path().join("runs/home");
+        let start: DateTime<Utc> = "2026-10-16T09:00:00Z".parse().unwrap();
+        for i in 0..5 {
+            RunLog::create_with_limit(&dir, "home", start + Duration::hours(i), 3).unwrap();
+        }
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "2026-10-16T11-00-00Z.log",
+                "2026-10-16T12-00-00Z.log",
+                "2026-10-16T13-00-00Z.log"
+            ]
+        );
+
+        let log = RunLog::create_with_limit(&dir, "home", start + Duration::days(1), 3).unwrap();
+        {
+            let mut writer = log.writer().unwrap();
+            writeln!(writer, "{{\"message_type\":\"status\"}}").unwrap();
+        }
+        log.append_section("stderr", "");
+        log.append_section("error", "Restic error: Fatal: unable to open repository\n");
+        let result = Err(anyhow::anyhow!("unable to open repository"));
+        log.finish(&result, start + Duration::days(1) + Duration::minutes(1));
+
+        let content = std::fs::read_to_string(log.path()).unwrap();
+        assert_eq!(
+            content,
+            "# Backup of set 'home' started 2026-10-17T09:00:00Z\n\
+             {\"message_type\":\"status\"}\n\
+             --- error ---\n\
+             Restic error: Fatal: unable to open repository\n\
+             # Finished 2026-10-17T09:01:00Z: failed: unable to open repository\n"
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::executor::{
+    first_line, local_hostname, parse_reclaimed_bytes, parse_repo_id, prioritized, retention_args,
+    retention_group_by, run_tool, run_tool_measured, spawn_mount, EngineDefaults, ResourceUsage,
+    RestoreSummary,
+};
+use crate::run_log::RunLog;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::process::Child;
+use tracing::info;
+use vigil_lib::config::{engine_process_env, BackupSet, EngineEnv, PasswordSource, Priority};
+use vigil_lib::types::{BackupResult, SnapshotInfo};
+
+/// Executor for the rustic CLI. rustic reads and writes the restic repository
+/// format, but its flags and JSON output differ from restic's.
+#[derive(Default)]
+pub struct RusticExecutor {
+    /// Repository password passed to every rustic invocation.
+    password: PasswordSource,
+    /// Credentials file whose variables are passed to every rustic invocation.
+    credentials_file: Option<PathBuf>,
+    /// The global `env_file` and `env`, set before the set's own variables.
+    global_env: EngineEnv,
+    /// The set's `env_file` and `env`, passed to every rustic invocation.
+    env: EngineEnv,
+    /// `RUSTIC_*` variables rustic may inherit from the daemon's environment.
+    env_passthrough: Vec<String>,
+    /// Priority of backups and prunes. rustic has no bandwidth limits.
+    priority: Priority,
+}
+
+#[derive(Debug, Deserialize)]
+struct RusticSummary {
+    #[serde(default)]
+    data_added: u64,
+    #[serde(default)]
+    total_duration: f64,
+    #[serde(default)]
+    total_bytes_processed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RusticSnapshot {
+    id: String,
+    time: chrono::DateTime<chrono::Utc>,
+    hostname: Option<String>,
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+    #[serde(default)]
+    tags: Vec<String>,
+    summary: Option<RusticSummary>,
+}
+
+/// `rustic snapshots --json` groups snapshots as `[[group, [snapshot, ...]], ...]`.
+/// A flat list is accepted as well.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RusticSnapshotList {
+    Grouped(Vec<(Value, Vec<RusticSnapshot>)>),
+    Flat(Vec<RusticSnapshot>),
+}
+
+/// `rustic forget --json` lists each group's snapshots with their verdict.
+#[derive(Debug, Deserialize)]
+struct RusticForgetGroup {
+    snapshots: Vec<RusticForgetSnapshot>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RusticForgetSnapshot {
+    snapshot: RusticSnapshot,
+    keep: bool,
+}
+
+impl RusticSnapshot {
+    fn into_info(self) -> SnapshotInfo {
+        SnapshotInfo {
+            short_id: self.id.chars().take(8).collect(),
+            id: self.id,
+            timestamp: self.time,
+            paths: self.paths,
+            tags: self.tags,
+            total_bytes: self.summary.and_then(|s| s.total_bytes_processed),
+            hostname: self.hostname,
+        }
+    }
+}
+
+impl RusticExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executor for a set's repository, using its password source and backend
+    /// credentials, with `defaults` where the set has no settings of its own.
+    pub fn for_set(set: &BackupSet, defaults: &EngineDefaults) -> Self {
+        Self {
+            password: set.password_source(),
+            credentials_file: set.credentials_file.as_ref().map(PathBuf::from),
+            global_env: defaults.env.clone(),
+            env: set.engine_env(),
+            env_passthrough: defaults.env_passthrough.clone(),
+            priority: set
+                .throttle()
+                .or(defaults.throttle)
+                .priority
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn run_rustic(
+        &self,
+        target: &str,
+        args: Vec<String>,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<(String, String)> {
+        let (stdout, stderr, _) = self
+            .run_rustic_measured(target, args, Priority::Normal, token)
+            .await?;
+        Ok((stdout, stderr))
+    }
+
+    async fn run_rustic_measured(
+        &self,
+        target: &str,
+        mut args: Vec<String>,
+        priority: Priority,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<(String, String, Option<ResourceUsage>)> {
+        args.extend(repo_args(target, &self.password));
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        let (program, args) = prioritized("rustic", args, priority);
+        run_tool_measured(
+            &program,
+            "Rustic",
+            args,
+            &env,
+            &self.env_passthrough,
+            token,
+            |_| true,
+        )
+        .await
+    }
+
+    pub async fn init(&self, target: &str) -> Result<()> {
+        info!("Initializing rustic repository at {}", target);
+        self.run_rustic(target, vec!["init".to_string()], None)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn backup(
+        &self,
+        set: &BackupSet,
+        token: Option<tokio_util::sync::CancellationToken>,
+        log: Option<&RunLog>,
+    ) -> Result<BackupResult> {
+        info!("Starting rustic backup for set: {}", set.name);
+
+        let mut args = vec!["backup".to_string(), "--json".to_string()];
+        let mut excludes = set.backup_excludes();
+        excludes.extend(set.exclude_file_patterns().map_err(|e| anyhow!(e))?);
+        for glob in excludes.iter().flat_map(|e| e.globs()) {
+            args.push("--glob".to_string());
+            args.push(format!("!{}", glob));
+        }
+        for marker in set.exclude_markers() {
+            args.push("--exclude-if-present".to_string());
+            args.push(marker.file);
+        }
+        if set.use_gitignore {
+            args.push("--git-ignore".to_string());
+        }
+        for tag in &set.tags {
+            args.push("--tag".to_string());
+            args.push(tag.clone());
+        }
+        if let Some(ref source) = set.source {
+            args.push(source.clone());
+        }
+        if let Some(ref multi_sources) = set.sources {
+            args.extend(multi_sources.iter().cloned());
+        }
+
+        let failed = |message: String| BackupResult {
+            snapshot_id: String::new(),
+            timestamp: Utc::now(),
+            added_bytes: 0,
+            duration_secs: 0.0,
+            success: false,
+            error_message: Some(message),
+            peak_rss_bytes: None,
+            cpu_secs: None,
+            hooks: Vec::new(),
+        };
+
+        let (stdout, usage) = match self
+            .run_rustic_measured(&set.target, args, self.priority, token)
+            .await
+        {
+            Ok((stdout, stderr, usage)) => {
+                if let Some(log) = log {
+                    log.append_section("stdout", &stdout);
+                    log.append_section("stderr", &stderr);
+                }
+                (stdout, usage)
+            }
+            Err(e) => {
+                if let Some(log) = log {
+                    log.append_section("error", &e.to_string());
+                }
+                return Ok(failed(e.to_string()));
+            }
+        };
+
+        // rustic prints the saved snapshot as a single JSON document.
+        match serde_json::from_str::<RusticSnapshot>(stdout.trim()) {
+            Ok(snapshot) => {
+                let summary = snapshot.summary.as_ref();
+                Ok(BackupResult {
+                    snapshot_id: snapshot.id.chars().take(8).collect(),
+                    timestamp: Utc::now(),
+                    added_bytes: summary.map(|s| s.data_added).unwrap_or(0),
+                    duration_secs: summary.map(|s| s.total_duration).unwrap_or(0.0),
+                    success: true,
+                    error_message: None,
+                    peak_rss_bytes: usage.map(|u| u.peak_rss_bytes),
+                    cpu_secs: usage.map(|u| u.cpu_secs),
+                    hooks: Vec::new(),
+                })
+            }
+            Err(_) => Ok(failed(
+                "Could not find snapshot in rustic output".to_string(),
+            )),
+        }
+    }
+
+    pub async fn snapshots(
+        &self,
+        target: &str,
+        limit: Option<usize>,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<Vec<SnapshotInfo>> {
+        let args = vec!["snapshots".to_string(), "--json".to_string()];
+        let (stdout, _) = self.run_rustic(target, args, token).await?;
+        parse_snapshots(&stdout, limit)
+    }
+
+    pub async fn snapshot_size(
+        &self,
+        _target: &str,
+        _snapshot_id: &str,
+        _token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        Err(anyhow!("rustic does not support restore-size statistics"))
+    }
+
+    /// rustic does not use repository locks, so there is never anything to wait for.
+    pub async fn list_locks(
+        &self,
+        _target: &str,
+        _token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<usize> {
+        Ok(0)
+    }
+
+    /// The repository's unique ID, from its config file.
+    pub async fn repo_id(
+        &self,
+        target: &str,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<String> {
+        let args = vec!["cat".to_string(), "config".to_string()];
+        let (stdout, _) = self.run_rustic(target, args, token).await?;
+        parse_repo_id(&stdout)
+    }
+
+    /// First line of `rustic --version`, e.g. `rustic v0.9.5`.
+    pub async fn version(&self) -> Result<String> {
+        let (stdout, _) = run_tool("rustic", "Rustic", vec!["--version".to_string()], None).await?;
+        Ok(first_line(&stdout))
+    }
+
+    pub async fn prune(
+        &self,
+        set: &BackupSet,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        info!("Pruning rustic repository for set: {}", set.name);
+        let keep_args = retention_args(set)?;
+
+        let mut args = vec!["forget".to_string(), "--prune".to_string()];
+        if let Some(group_by) = retention_group_by(set) {
+            args.push("--group-by".to_string());
+            args.push(group_by.to_string());
+        }
+        if set.shared_repo {
+            args.push("--filter-host".to_string());
+            args.push(local_hostname());
+        }
+        args.extend(keep_args);
+
+        let (stdout, _, _) = self
+            .run_rustic_measured(&set.target, args, self.priority, token)
+            .await?;
+        Ok(parse_reclaimed_bytes(&stdout))
+    }
+
+    /// Runs the set's retention policy with `--dry-run`. Returns the snapshots
+    /// it would forget, oldest first, and how many it would keep.
+    pub async fn forget_preview(
+        &self,
+        set: &BackupSet,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<(Vec<SnapshotInfo>, usize)> {
+        let keep_args = retention_args(set)?;
+
+        let mut args = vec![
+            "forget".to_string(),
+            "--dry-run".to_string(),
+            "--json".to_string(),
+        ];
+        if let Some(group_by) = retention_group_by(set) {
+            args.push("--group-by".to_string());
+            args.push(group_by.to_string());
+        }
+        if set.shared_repo {
+            args.push("--filter-host".to_string());
+            args.push(local_hostname());
+        }
+        args.extend(keep_args);
+
+        let (stdout, _) = self.run_rustic(&set.target, args, token).await?;
+        parse_forget_preview(&stdout)
+    }
+
+    pub async fn forget(
+        &self,
+        target: &str,
+        snapshot_id: &str,
+        prune: bool,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<u64> {
+        info!("Forgetting rustic snapshot {} in {}", snapshot_id, target);
+        let mut args = vec!["forget".to_string()];
+        if prune {
+            args.push("--prune".to_string());
+        }
+        args.push(snapshot_id.to_string());
+
+        let (stdout, _, _) = self
+            .run_rustic_measured(target, args, self.priority, token)
+            .await?;
+        Ok(parse_reclaimed_bytes(&stdout))
+    }
+
+    /// Restores a whole snapshot. rustic restores a path's contents directly
+    /// into the destination rather than under its original location, so
+    /// partial restores are refused to keep the layout consistent with restic.
+    pub async fn restore(
+        &self,
+        set: &BackupSet,
+        snapshot_id: Option<&str>,
+        include: Option<&str>,
+        dest: &Path,
+        token: Option<tokio_util::sync::CancellationToken>,
+    ) -> Result<RestoreSummary> {
+        if include.is_some() {
+            return Err(anyhow!(
+                "rustic sets can only restore whole snapshots; use `vigil mount` to copy individual paths"
+            ));
+        }
+        info!("Restoring rustic set {} into {:?}", set.name, dest);
+        let args = vec![
+            "restore".to_string(),
+            snapshot_id.unwrap_or("latest").to_string(),
+            dest.to_string_lossy().to_string(),
+        ];
+        self.run_rustic(&set.target, args, token).await?;
+        Ok(RestoreSummary::default())
+    }
+
+    pub async fn mount(
+        &self,
+        target: &str,
+        _snapshot_id: Option<&str>,
+        mountpoint: &Path,
+    ) -> Result<Child> {
+        info!("Mounting rustic repository at {:?}", mountpoint);
+        let mut args = vec!["mount".to_string()];
+        args.extend(repo_args(target, &self.password));
+        args.push(mountpoint.to_string_lossy().to_string());
+        let env = engine_process_env(
+            &self.global_env,
+            self.credentials_file.as_deref(),
+            &self.env,
+        )?;
+        spawn_mount("rustic", "Rustic", args, &env, &self.env_passthrough).await
+    }
+}
+
+fn repo_args(target: &str, password: &PasswordSource) -> Vec<String> {
+    let mut args = vec!["--repository".to_string(), target.to_string()];
+    args.extend(password.args());
+    args.push("--no-progress".to_string());
+    args
+}
+
+/// Flattens rustic's snapshot listing into oldest-first order, keeping the
+/// newest `limit` entries if given.
+fn parse_snapshots(stdout: &str, limit: Option<usize>) -> Result<Vec<SnapshotInfo>> {
+    let list: RusticSnapshotList =
+        serde_json::from_str(stdout).context("Failed to parse rustic snapshots JSON")?;
+    let mut snapshots: Vec<SnapshotInfo> = match list {
+        RusticSnapshotList::Grouped(groups) => groups
+            .into_iter()
+            .flat_map(|(_, snaps)| snaps)
+            .map(RusticSnapshot::into_info)
+            .collect(),
+        RusticSnapshotList::Flat(snaps) => {
+            snaps.into_iter().map(RusticSnapshot::into_info).collect()
+        }
+    };
+    snapshots.sort_by_key(|s| s.timestamp);
+    if let Some(n) = limit {
+        let skip = snapshots.len().saturating_sub(n);
+        snapshots.drain(..skip);
+    }
+    Ok(snapshots)
+}
+
+/// Splits rustic's forget listing into the snapshots that would be removed,
+/// oldest first, and the number kept.
+fn parse_forget_preview(stdout: &str) -> Result<(Vec<SnapshotInfo>, usize)> {
+    let groups: Vec<RusticForgetGroup> =
+        serde_json::from_str(stdout).context("Failed to parse rustic forget JSON")?;
+    let (keep, remove): (Vec<_>, Vec<_>) = groups
+        .into_iter()
+        .flat_map(|g| g.snapshots)
+        .partition(|s| s.keep);
+    let mut to_remove: Vec<SnapshotInfo> =
+        remove.into_iter().map(|s| s.snapshot.into_info()).collect();
+    to_remove.sort_by_key(|s| s.timestamp);
+    Ok((to_remove, keep.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT_A: &str = r#"{"id":"aaaaaaaa11112222","time":"2026-01-02T10:00:00+01:00","hostname":"laptop","paths":["/home/user"],"summary":{"data_added":10,"total_duration":1.5,"total_bytes_processed":2048}}"#;
+    const SNAPSHOT_B: &str = r#"{"id":"bbbbbbbb33334444","time":"2026-01-01T10:00:00Z","hostname":"desktop","paths":["/home/user"],"tags":["x"]}"#;
+
+    #[test]
+    fn test_parse_grouped_snapshots() {
+        let json = format!(
+            r#"[[{{"hostname":"laptop"}},[{}]],[{{"hostname":"desktop"}},[{}]]]"#,
+            SNAPSHOT_A, SNAPSHOT_B
+        );
+        let snapshots = parse_snapshots(&json, None).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].short_id, "bbbbbbbb");
+        assert_eq!(snapshots[0].tags, vec!["x"]);
+        assert_eq!(snapshots[1].short_id, "aaaaaaaa");
+        assert_eq!(snapshots[1].total_bytes, Some(2048));
+        assert_eq!(snapshots[1].hostname.as_deref(), Some("laptop"));
+
+        let latest = parse_snapshots(&json, Some(1)).unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].short_id, "aaaaaaaa");
+    }
+
+    #[test]
+    fn test_parse_forget_preview() {
+        let json = format!(
+            r#"[{{"group":{{"hostname":"laptop"}},"snapshots":[{{"snapshot":{},"keep":true,"reasons":["daily"]}},{{"snapshot":{},"keep":false,"reasons":[]}}]}}]"#,
+            SNAPSHOT_A, SNAPSHOT_B
+        );
+        let (to_remove, to_keep) = parse_forget_preview(&json).unwrap();
+        assert_eq!(to_keep, 1);
+        assert_eq!(to_remove.len(), 1);
+        assert_eq!(to_remove[0].short_id, "bbbbbbbb");
+    }
+
+    #[test]
+    fn test_parse_flat_snapshots() {
+        let json = format!("[{}]", SNAPSHOT_B);
+        let snapshots = parse_snapshots(&json, None).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].total_bytes, None);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::manager::JobManager;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Longest time between schedule checks, so config reloads and finished
+/// backups are picked up even when no run is due soon. Idle mounts are
+/// checked at the same pace.
+const MAX_SLEEP_SECS: u64 = 30;
+
+/// Starts scheduled backups and unmounts idle mounts until `token` is
+/// cancelled. Runs alongside the file watcher; each set's `schedule` is
+/// independent of its debounce timer.
+pub async fn run(manager: Arc<JobManager>, token: CancellationToken) {
+    loop {
+        let earliest = manager.run_due_schedules(Utc::now()).await;
+        manager.unmount_idle(Instant::now()).await;
+        let delay = sleep_until(earliest.map(|next| next - Utc::now()));
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(delay) => {}
+        }
+    }
+}
+
+/// Time to sleep before the next check, given how far away the earliest run is.
+pub(crate) fn sleep_until(until_next: Option<chrono::Duration>) -> Duration {
+    let max = Duration::from_secs(MAX_SLEEP_SECS);
+    match until_next.map(|d| d.to_std()) {
+        Some(Ok(d)) => d.min(max),
+        // The run is already due; check again right away
+        Some(Err(_)) => Duration::ZERO,
+        None => max,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_until() {
+        assert_eq!(
+            sleep_until(Some(chrono::Duration::seconds(5))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            sleep_until(Some(chrono::Duration::hours(2))),
+            Duration::from_secs(MAX_SLEEP_SECS)
+        );
+        assert_eq!(
+            sleep_until(Some(chrono::Duration::seconds(-1))),
+            Duration::ZERO
+        );
+        assert_eq!(sleep_until(None), Duration::from_secs(MAX_SLEEP_SECS));
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{info, warn};
+use vigil_lib::types::BackupResult;
+
+/// Schema of the persisted state this daemon reads and writes. Bump it together
+/// with a new entry in `MIGRATIONS` whenever a persisted format changes.
+pub const STATE_SCHEMA_VERSION: u32 = 1;
+
+/// Entries of the data directory that hold persisted state, relative to it.
+/// Logs are not state and survive a reset.
+const STATE_ENTRIES: &[&str] = &["cache", "journal", "verify", "state.json"];
+
+/// Rewrites persisted state in place from one schema to the next.
+type Migration = fn(&Path) -> Result<()>;
+
+/// Migrations keyed by the schema they upgrade from.
+const MIGRATIONS: &[(u32, Migration)] = &[(0, migrate_v0_to_v1)];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateVersion {
+    schema: u32,
+    /// Daemon version that last wrote the marker, for error messages.
+    written_by: String,
+}
+
+/// Per-set results kept in `state.json`, so backup metrics and failures are
+/// shown after a restart instead of being rebuilt from the repository.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct DaemonState {
+    #[serde(default)]
+    pub sets: BTreeMap<String, SetState>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SetState {
+    /// The set's repository. Kept for sets removed from the config, so
+    /// `vigil purge` can still find it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// The last backup attempt, including its metrics or error message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_backup: Option<BackupResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+    /// Whether shutdown cancelled a backup of the set before it finished.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Whether an automatic prune waits for the maintenance window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub prune_pending: bool,
+    /// Subset of a deep check that waits for the maintenance window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_pending: Option<String>,
+    /// When the pause of change-triggered backups after a restore into the
+    /// set's sources ends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+impl DaemonState {
+    /// Reads the state file. A missing or unreadable file gives an empty state.
+    pub fn load(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to read daemon state {:?}: {}", path, e);
+                }
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable daemon state {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// Writes the state file atomically (temp file + rename).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", tmp))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {:?}", path))?;
+        Ok(())
+    }
+}
+
+/// Makes sure the state in `data_dir` can be used by this daemon.
+///
+/// Older state is migrated in place. State written by a newer daemon, or an
+/// unreadable version marker, is an error that names `--reset-state` as the way out.
+/// With `reset`, all persisted state is deleted first.
+pub fn prepare(data_dir: &Path, reset: bool) -> Result<()> {
+    if reset {
+        reset_state(data_dir)?;
+    }
+
+    let marker = data_dir.join("state_version.json");
+    let found = match std::fs::read_to_string(&marker) {
+        Ok(content) => serde_json::from_str::<StateVersion>(&content).map_err(|e| {
+            anyhow!(
+                "State version marker {:?} is unreadable ({}). Restart with --reset-state to discard persisted state.",
+                marker,
+                e
+            )
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StateVersion {
+            // State from before versioning was introduced is schema 0
+            schema: if has_state(data_dir) { 0 } else { STATE_SCHEMA_VERSION },
+            written_by: "unknown".to_string(),
+        },
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", marker)),
+    };
+
+    if found.schema > STATE_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "State in {:?} uses schema {} (written by vigil {}), but this daemon (vigil {}) only understands schema {}. Upgrade vigil, or restart with --reset-state to discard persisted state.",
+            data_dir,
+            found.schema,
+            found.written_by,
+            env!("CARGO_PKG_VERSION"),
+            STATE_SCHEMA_VERSION
+        ));
+    }
+
+    for schema in found.schema..STATE_SCHEMA_VERSION {
+        let (_, migrate) = MIGRATIONS
+            .iter()
+            .find(|(from, _)| *from == schema)
+            .ok_or_else(|| anyhow!("No migration from state schema {}", schema))?;
+        info!(
+            "Migrating persisted state from schema {} to {}",
+            schema,
+            schema + 1
+        );
+        migrate(data_dir).with_context(|| {
+            format!(
+                "Failed to migrate state from schema {}. Restart with --reset-state to discard persisted state.",
+                schema
+            )
+        })?;
+    }
+
+    write_marker(&marker)
+}
+
+/// Deletes all persisted state, keeping logs.
+fn reset_state(data_dir: &Path) -> Result<()> {
+    warn!(
+        "Resetting persisted state in {:?}; pending changes recorded before the reset are forgotten",
+        data_dir
+    );
+    let entries = STATE_ENTRIES
+        .iter()
+        .map(|entry| data_dir.join(entry))
+        .chain(std::iter::once(data_dir.join("state_version.json")));
+    for path in entries {
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove {:?}", path));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn has_state(data_dir: &Path) -> bool {
+    STATE_ENTRIES
+        .iter()
+        .any(|entry| data_dir.join(entry).exists())
+}
+
+fn write_marker(marker: &Path) -> Result<()> {
+    if let Some(parent) = marker.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let version = StateVersion {
+        schema: STATE_SCHEMA_VERSION,
+        written_by: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let tmp = marker.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string(&version)?)
+        .context("Failed to write state version marker")?;
+    std::fs::rename(&tmp, marker).context("Failed to replace state version marker")?;
+    Ok(())
+}
+
+/// Schema 1 introduced the version marker; the file formats are unchanged.
+fn migrate_v0_to_v1(_data_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker_schema(data_dir: &Path) -> u32 {
+        let content = std::fs::read_to_string(data_dir.join("state_version.json")).unwrap();
+        serde_json::from_str::<StateVersion>(&content)
+            .unwrap()
+            .schema
+    }
+
+    #[test]
+    fn test_fresh_and_legacy_state_are_stamped() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        prepare(tmp.path(), false)?;
+        assert_eq!(marker_schema(tmp.path()), STATE_SCHEMA_VERSION);
+
+        let legacy = tempfile::tempdir()?;
+        std::fs::create_dir_all(legacy.path().join("journal"))?;
+        std::fs::write(legacy.path().join("journal/home.json"), "{}")?;
+        prepare(legacy.path(), false)?;
+        assert_eq!(marker_schema(legacy.path()), STATE_SCHEMA_VERSION);
+        assert!(legacy.path().join("journal/home.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_state_is_refused_until_reset() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::create_dir_all(tmp.path().join("cache/home"))?;
+        std::fs::write(
+            tmp.path().join("state_version.json"),
+            r#"{"schema":99,"written_by":"9.9.9"}"#,
+        )?;
+
+        let err = prepare(tmp.path(), false).unwrap_err().to_string();
+        assert!(err.contains("schema 99"));
+        assert!(err.contains("9.9.9"));
+        assert!(err.contains("--reset-state"));
+
+        std::fs::write(tmp.path().join("vigil.log"), "keep")?;
+        prepare(tmp.path(), true)?;
+        assert!(!tmp.path().join("cache").exists());
+        assert!(tmp.path().join("vigil.log").exists());
+        assert_eq!(marker_schema(tmp.path()), STATE_SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_daemon_state_roundtrip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("state.json");
+        assert_eq!(DaemonState::load(&path), DaemonState::default());
+
+        let mut state = DaemonState::default();
+        state.sets.insert(
+            "home".to_string(),
+            SetState {
+                target: Some("/mnt/backup/home".to_string()),
+                last_backup: Some(BackupResult {
+                    snapshot_id: String::new(),
+                    timestamp: chrono::Utc::now(),
+                    added_bytes: 0,
+                    duration_secs: 1.5,
+                    success: false,
+                    error_message: Some("repository locked".to_string()),
+                    peak_rss_bytes: None,
+                    cpu_secs: None,
+                    hooks: Vec::new(),
+                }),
+                snapshot_count: Some(3),
+                total_bytes: None,
+                interrupted: true,
+                prune_pending: true,
+                verify_pending: Some("5%".to_string()),
+                paused_until: Some(chrono::Utc::now()),
+            },
+        );
+        state.save(&path)?;
+        assert_eq!(DaemonState::load(&path), state);
+        assert!(!tmp.path().join("state.json.tmp").exists());
+
+        std::fs::write(&path, "{ truncated")?;
+        assert_eq!(DaemonState::load(&path), DaemonState::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_marker_is_refused() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("state_version.json"), "not json")?;
+        let err = prepare(tmp.path(), false).unwrap_err().to_string();
+        assert!(err.contains("--reset-state"));
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Sidecar cache of per-snapshot sizes, keyed by full snapshot ID.
+///
+/// Snapshots are immutable, so a cached size never goes stale. Entries are only
+/// dropped when their snapshot disappears from the repository (e.g. after prune).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SnapshotStatsCache {
+    #[serde(skip)]
+    path: PathBuf,
+    sizes: HashMap<String, u64>,
+}
+
+impl SnapshotStatsCache {
+    /// Loads the cache from disk. A missing or unreadable file yields an empty cache.
+    pub fn load(path: &Path) -> Self {
+        let mut cache: Self = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        cache.path = path.to_path_buf();
+        cache
+    }
+
+    /// Returns the cached size for a snapshot.
+    pub fn get(&self, snapshot_id: &str) -> Option<u64> {
+        self.sizes.get(snapshot_id).copied()
+    }
+
+    /// Records the size of a snapshot.
+    pub fn insert(&mut self, snapshot_id: &str, size: u64) {
+        self.sizes.insert(snapshot_id.to_string(), size);
+    }
+
+    /// Drops entries for snapshots that no longer exist.
+    /// Returns whether anything was removed.
+    pub fn retain_ids(&mut self, current_ids: &HashSet<&str>) -> bool {
+        let before = self.sizes.len();
+        self.sizes.retain(|id, _| current_ids.contains(id.as_str()));
+        self.sizes.len() != before
+    }
+
+    /// Writes the cache atomically (temp file + rename).
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(self)?)
+            .context("Failed to write snapshot stats cache")?;
+        std::fs::rename(&tmp, &self.path).context("Failed to replace snapshot stats cache")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_roundtrip_and_invalidation() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("cache/test/snapshot_stats.json");
+
+        let mut cache = SnapshotStatsCache::load(&path);
+        assert_eq!(cache.get("aaa"), None);
+        cache.insert("aaa", 100);
+        cache.insert("bbb", 200);
+        cache.save()?;
+
+        let mut cache = SnapshotStatsCache::load(&path);
+        assert_eq!(cache.get("aaa"), Some(100));
+        assert_eq!(cache.get("bbb"), Some(200));
+
+        // "aaa" was pruned from the repository
+        let current: HashSet<&str> = ["bbb"].into_iter().collect();
+        assert!(cache.retain_ids(&current));
+        assert!(!cache.retain_ids(&current));
+        assert_eq!(cache.get("aaa"), None);
+        assert_eq!(cache.get("bbb"), Some(200));
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("snapshot_stats.json");
+        std::fs::write(&path, "not json").unwrap();
+        let cache = SnapshotStatsCache::load(&path);
+        assert_eq!(cache.get("aaa"), None);
+    }
+}
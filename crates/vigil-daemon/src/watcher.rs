@@ -1,73 +1,261 @@
+use crate::exclude::{has_exclude_marker, ExcludeMatcher, GitignoreMatcher};
 use anyhow::{Context, Result};
-use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::{Config as NotifyConfig, Error, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
-use vigil_lib::config::Config;
+use vigil_lib::config::{parse_file_list, Config, ExcludeMarker};
+
+/// How often coalesced changes are passed on to the daemon.
+const COALESCE_TICK: Duration = Duration::from_millis(250);
+
+/// Distinct changed paths kept per set between ticks. Further paths still
+/// mark the set changed, but are only counted.
+const MAX_BATCH_PATHS: usize = 10_000;
 
 #[derive(Debug)]
 pub enum WatcherEvent {
-    FileChanged { set_name: String, path: PathBuf },
+    /// Files of a set changed during the last tick.
+    FilesChanged {
+        set_name: String,
+        batch: ChangeBatch,
+    },
+    /// The `files_from` list or `exclude_file` of a set changed, so the
+    /// watched paths or exclusions are stale.
+    FileListChanged { set_name: String, path: PathBuf },
+}
+
+/// Changes to one set's files gathered during one tick.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ChangeBatch {
+    /// Distinct changed paths.
+    pub paths: Vec<PathBuf>,
+    /// Events folded into an earlier event for the same path.
+    pub coalesced: u64,
+    /// Events for paths past `MAX_BATCH_PATHS`, counted but not listed in `paths`.
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct PendingBatch {
+    paths: HashSet<PathBuf>,
+    coalesced: u64,
+    dropped: u64,
 }
 
 pub struct FileWatcher {
     watcher: RecommendedWatcher,
-    // Maps watched paths to their backup set name
-    // We use Arc to share it with the watcher callback
+    // Directories to watch and how
+    watches: HashMap<PathBuf, RecursiveMode>,
     inner: Arc<WatcherInner>,
 }
 
 struct WatcherInner {
+    // Mount directories; nothing below them is ever a change
+    ignored_roots: Vec<PathBuf>,
     // Maps watched root paths to their backup set name
     path_to_set: HashMap<PathBuf, String>,
     // Maps backup set name to its exclusion patterns
-    exclusion_sets: HashMap<String, GlobSet>,
+    exclusion_sets: HashMap<String, ExcludeMatcher>,
+    // Maps backup set name to the marker files that exclude a directory
+    exclude_markers: HashMap<String, Vec<ExcludeMarker>>,
+    // `.gitignore` rules of sets with `use_gitignore`
+    gitignores: HashMap<String, GitignoreMatcher>,
+    // Maps `files_from` lists and exclude files to their backup set name
+    file_lists: HashMap<PathBuf, String>,
+    // Changes seen since the last tick, per set. A build in a watched tree
+    // sends thousands of events a second; they reach the daemon as one batch
+    // per set and tick instead of filling the channel.
+    pending: Mutex<HashMap<String, PendingBatch>>,
+    // Whether `pending` has anything to pass on
+    dirty: AtomicBool,
     event_tx: mpsc::Sender<WatcherEvent>,
 }
 
+impl WatcherInner {
+    fn new(
+        path_to_set: HashMap<PathBuf, String>,
+        file_lists: HashMap<PathBuf, String>,
+        event_tx: mpsc::Sender<WatcherEvent>,
+    ) -> Self {
+        Self {
+            ignored_roots: Vec::new(),
+            path_to_set,
+            exclusion_sets: HashMap::new(),
+            exclude_markers: HashMap::new(),
+            gitignores: HashMap::new(),
+            file_lists,
+            pending: Mutex::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+            event_tx,
+        }
+    }
+
+    /// Adds a change of `path` to the set's batch for this tick.
+    fn record(&self, set_name: &str, path: PathBuf) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let batch = pending.entry(set_name.to_string()).or_default();
+        if batch.paths.contains(&path) {
+            batch.coalesced += 1;
+        } else if batch.paths.len() < MAX_BATCH_PATHS {
+            batch.paths.insert(path);
+        } else {
+            batch.dropped += 1;
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Takes the batches gathered since the last call.
+    fn take_batches(&self) -> Vec<(String, ChangeBatch)> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Vec::new();
+        }
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        pending
+            .into_iter()
+            .map(|(set_name, batch)| {
+                let mut paths: Vec<PathBuf> = batch.paths.into_iter().collect();
+                paths.sort();
+                if batch.coalesced > 0 || batch.dropped > 0 {
+                    debug!(
+                        "Set {}: {} changed path(s), {} event(s) coalesced, {} path(s) dropped",
+                        set_name,
+                        paths.len(),
+                        batch.coalesced,
+                        batch.dropped
+                    );
+                }
+                let batch = ChangeBatch {
+                    paths,
+                    coalesced: batch.coalesced,
+                    dropped: batch.dropped,
+                };
+                (set_name, batch)
+            })
+            .collect()
+    }
+}
+
+/// Passes the gathered batches on every tick, waiting for room in the
+/// channel; changes arriving meanwhile join the next batch. Ends once the
+/// watcher is dropped or the daemon stops receiving.
+fn spawn_flusher(inner: Weak<WatcherInner>) -> Result<()> {
+    std::thread::Builder::new()
+        .name("vigil-watch-flush".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(COALESCE_TICK);
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            for (set_name, batch) in inner.take_batches() {
+                let event = WatcherEvent::FilesChanged { set_name, batch };
+                if inner.event_tx.blocking_send(event).is_err() {
+                    return;
+                }
+            }
+        })
+        .context("Failed to start the watcher flush thread")?;
+    Ok(())
+}
+
+/// Adds a watch on `path`, keeping the recursive mode if it is already watched.
+fn add_watch(watches: &mut HashMap<PathBuf, RecursiveMode>, path: PathBuf, mode: RecursiveMode) {
+    let entry = watches.entry(path).or_insert(mode);
+    if mode == RecursiveMode::Recursive {
+        *entry = mode;
+    }
+}
+
+/// Watches a path that is a file or may not exist yet through its parent
+/// directory, which also sees the file being replaced or created.
+fn watch_via_parent(watches: &mut HashMap<PathBuf, RecursiveMode>, path: &std::path::Path) {
+    if path.is_dir() {
+        add_watch(watches, path.to_path_buf(), RecursiveMode::Recursive);
+    } else if let Some(parent) = path.parent() {
+        add_watch(watches, parent.to_path_buf(), RecursiveMode::NonRecursive);
+    }
+}
+
 impl FileWatcher {
     pub fn new(config: &Config, event_tx: mpsc::Sender<WatcherEvent>) -> Result<Self> {
         let mut path_to_set = HashMap::new();
         let mut exclusion_sets = HashMap::new();
+        let mut exclude_markers = HashMap::new();
+        let mut gitignores = HashMap::new();
+        let mut file_lists = HashMap::new();
+        let mut watches = HashMap::new();
 
         for set in &config.backup_sets {
-            // Build exclusion set
-            if let Some(ref excludes) = set.exclude {
-                let mut builder = GlobSetBuilder::new();
-                for pattern in excludes {
-                    builder.add(Glob::new(pattern).context("Invalid exclusion pattern")?);
-                }
-                exclusion_sets.insert(
-                    set.name.clone(),
-                    builder.build().context("Failed to build GlobSet")?,
-                );
+            // Build exclusion set; vigil's own data never counts as a change
+            let mut excludes = set.backup_excludes();
+            match set.exclude_file_patterns() {
+                Ok(patterns) => excludes.extend(patterns),
+                Err(e) => warn!("Set {} has an invalid {}", set.name, e),
+            }
+            if let Some(ref file) = set.exclude_file {
+                let file = PathBuf::from(file);
+                watch_via_parent(&mut watches, &file);
+                file_lists.insert(file, set.name.clone());
+            }
+            if !excludes.is_empty() {
+                exclusion_sets.insert(set.name.clone(), ExcludeMatcher::new(&excludes)?);
+            }
+            let markers = set.exclude_markers();
+            if !markers.is_empty() {
+                exclude_markers.insert(set.name.clone(), markers);
+            }
+            if set.use_gitignore {
+                gitignores.insert(set.name.clone(), GitignoreMatcher::default());
             }
 
             // Register paths
-            if let Some(ref source) = set.source {
+            for source in set.source.iter().chain(set.sources.iter().flatten()) {
                 path_to_set.insert(PathBuf::from(source), set.name.clone());
+                add_watch(
+                    &mut watches,
+                    PathBuf::from(source),
+                    RecursiveMode::Recursive,
+                );
             }
-            if let Some(ref sources) = set.sources {
-                for source in sources {
-                    path_to_set.insert(PathBuf::from(source), set.name.clone());
+            if let Some(ref list) = set.files_from {
+                let list = PathBuf::from(list);
+                let listed = match std::fs::read_to_string(&list) {
+                    Ok(content) => parse_file_list(&content),
+                    Err(e) => {
+                        warn!(
+                            "Cannot read files_from list {:?} of set {}: {}",
+                            list, set.name, e
+                        );
+                        Vec::new()
+                    }
+                };
+                for path in listed.iter().map(PathBuf::from) {
+                    watch_via_parent(&mut watches, &path);
+                    path_to_set.insert(path, set.name.clone());
                 }
+                watch_via_parent(&mut watches, &list);
+                file_lists.insert(list, set.name.clone());
             }
         }
 
         let inner = Arc::new(WatcherInner {
-            path_to_set,
+            ignored_roots: ignored_roots(),
             exclusion_sets,
-            event_tx,
+            exclude_markers,
+            gitignores,
+            ..WatcherInner::new(path_to_set, file_lists, event_tx)
         });
+        spawn_flusher(Arc::downgrade(&inner))?;
 
-        let inner_clone = inner.clone();
+        let handler = inner.clone();
         let watcher = RecommendedWatcher::new(
             move |res: std::result::Result<Event, Error>| match res {
                 Ok(event) => {
-                    if let Err(e) = handle_event(&inner_clone, event) {
+                    if let Err(e) = handle_event(&handler, event) {
                         error!("Error handling watcher event: {}", e);
                     }
                 }
@@ -76,7 +264,11 @@ impl FileWatcher {
             NotifyConfig::default(),
         )?;
 
-        let mut file_watcher = Self { watcher, inner };
+        let mut file_watcher = Self {
+            watcher,
+            watches,
+            inner,
+        };
 
         file_watcher.start_watching()?;
 
@@ -84,11 +276,11 @@ impl FileWatcher {
     }
 
     fn start_watching(&mut self) -> Result<()> {
-        for path in self.inner.path_to_set.keys() {
+        for (path, mode) in &self.watches {
             if path.exists() {
                 info!("Watching path: {:?}", path);
                 self.watcher
-                    .watch(path, RecursiveMode::Recursive)
+                    .watch(path, *mode)
                     .context(format!("Failed to watch path: {:?}", path))?;
             } else {
                 warn!("Source path does not exist, skipping: {:?}", path);
@@ -98,11 +290,74 @@ impl FileWatcher {
     }
 }
 
+impl Drop for FileWatcher {
+    /// Passes on the changes of the last partial tick, so a watcher replaced
+    /// after a reload loses none.
+    fn drop(&mut self) {
+        for (set_name, batch) in self.inner.take_batches() {
+            let _ = self
+                .inner
+                .event_tx
+                .try_send(WatcherEvent::FilesChanged { set_name, batch });
+        }
+    }
+}
+
+/// Mount roots as configured and, where they resolve elsewhere (e.g. a
+/// symlinked data directory), as resolved.
+fn ignored_roots() -> Vec<PathBuf> {
+    let mut roots = vigil_lib::paths::mount_roots();
+    let resolved: Vec<PathBuf> = roots
+        .iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .filter(|resolved| !roots.contains(resolved))
+        .collect();
+    roots.extend(resolved);
+    roots
+}
+
 fn handle_event(inner: &WatcherInner, event: Event) -> Result<()> {
     // Only interested in data changes (creates, modifies, deletes)
     debug!("Event kind: {:?}, paths: {:?}", event.kind, event.paths);
 
     for path in event.paths {
+        // Browsing a mounted snapshot must not look like a change. Checked
+        // before anything stats the path, which would touch the mount again.
+        if inner
+            .ignored_roots
+            .iter()
+            .any(|root| path.starts_with(root))
+        {
+            continue;
+        }
+
+        if path.file_name().is_some_and(|name| name == ".gitignore") {
+            if let Some(dir) = path.parent() {
+                for gitignore in inner.gitignores.values() {
+                    gitignore.invalidate(dir);
+                }
+            }
+        }
+
+        if let Some(set_name) = inner.file_lists.get(&path) {
+            if !event.kind.is_access() {
+                info!("File list of set {} changed: {:?}", set_name, path);
+                let _ = inner.event_tx.try_send(WatcherEvent::FileListChanged {
+                    set_name: set_name.clone(),
+                    path,
+                });
+            }
+            continue;
+        }
+
+        // The daemon rewrites canaries before each backup
+        if path
+            .file_name()
+            .is_some_and(|name| name == crate::canary::FILE_NAME)
+        {
+            continue;
+        }
+
         // Use metadata to check if it's a directory, but don't fail if file is already gone (e.g. rapid delete/move)
         if path.is_dir() {
             debug!("Skipping directory: {:?}", path);
@@ -129,33 +384,31 @@ fn handle_event(inner: &WatcherInner, event: Event) -> Result<()> {
         }
 
         if let Some((root, set_name)) = found_set {
-            // Check exclusions
-            if let Some(exclusion_set) = inner.exclusion_sets.get(set_name) {
-                let is_excluded = exclusion_set.is_match(&path)
-                    || path
-                        .file_name()
-                        .map(|n| exclusion_set.is_match(n))
-                        .unwrap_or(false)
-                    || path
-                        .strip_prefix(root)
-                        .ok()
-                        .map(|p| exclusion_set.is_match(p))
-                        .unwrap_or(false);
-
-                if is_excluded {
+            // Check exclusions with the same semantics restic applies
+            if let Some(matcher) = inner.exclusion_sets.get(set_name) {
+                if matcher.is_excluded(&path) {
                     debug!("Excluding path: {:?}", path);
                     continue;
                 }
             }
+            if let Some(markers) = inner.exclude_markers.get(set_name) {
+                if has_exclude_marker(&path, root, markers) {
+                    debug!("Excluding path in marked directory: {:?}", path);
+                    continue;
+                }
+            }
+            if let Some(gitignore) = inner.gitignores.get(set_name) {
+                if gitignore.is_ignored(&path, false) {
+                    debug!("Excluding path ignored by .gitignore: {:?}", path);
+                    continue;
+                }
+            }
 
-            info!(
+            debug!(
                 "File change detected in set {}: {:?} (event: {:?})",
                 set_name, path, event.kind
             );
-            let _ = inner.event_tx.try_send(WatcherEvent::FileChanged {
-                set_name: set_name.clone(),
-                path,
-            });
+            inner.record(set_name, path);
         } else {
             debug!("Path not in any watched set: {:?}", path);
         }
@@ -172,6 +425,75 @@ mod tests {
     use tokio::sync::mpsc;
     use vigil_lib::config::{BackupSet, GlobalConfig};
 
+    #[test]
+    fn test_mount_roots_are_ignored() -> Result<()> {
+        let tmp = tempdir()?;
+        // A source containing the data directory, as with a set backing up $HOME
+        let source = tmp.path().join("home");
+        let mount = source.join(".local/share/vigil/mnt/docs/snapshots/latest");
+        fs::create_dir_all(&mount)?;
+        fs::write(mount.join("report.txt"), "old")?;
+        fs::write(source.join("notes.txt"), "new")?;
+
+        let (tx, _rx) = mpsc::channel(10);
+        let inner = WatcherInner {
+            ignored_roots: vec![source.join(".local/share/vigil/mnt")],
+            ..WatcherInner::new(
+                HashMap::from([(source.clone(), "home".to_string())]),
+                HashMap::new(),
+                tx,
+            )
+        };
+        let access = |path: PathBuf| {
+            Event::new(notify::EventKind::Access(notify::event::AccessKind::Any)).add_path(path)
+        };
+
+        handle_event(&inner, access(mount.join("report.txt")))?;
+        handle_event(&inner, access(mount.clone()))?;
+        assert!(
+            inner.take_batches().is_empty(),
+            "Mount access counted as a change"
+        );
+
+        handle_event(&inner, access(source.join("notes.txt")))?;
+        let batches = inner.take_batches();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].0, "home");
+        Ok(())
+    }
+
+    #[test]
+    fn test_churn_is_coalesced() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("src");
+        fs::create_dir_all(&source)?;
+        let (tx, _rx) = mpsc::channel(1);
+        let inner = WatcherInner::new(
+            HashMap::from([(source.clone(), "build".to_string())]),
+            HashMap::new(),
+            tx,
+        );
+        let modify = |path: PathBuf| {
+            Event::new(notify::EventKind::Modify(notify::event::ModifyKind::Any)).add_path(path)
+        };
+
+        // Three rounds over the same files, as a rebuild rewrites them
+        for _ in 0..3 {
+            for i in 0..MAX_BATCH_PATHS + 5 {
+                handle_event(&inner, modify(source.join(format!("obj/{}.o", i))))?;
+            }
+        }
+        let batches = inner.take_batches();
+        assert_eq!(batches.len(), 1);
+        let (set_name, batch) = &batches[0];
+        assert_eq!(set_name, "build");
+        assert_eq!(batch.paths.len(), MAX_BATCH_PATHS);
+        assert_eq!(batch.coalesced, 2 * MAX_BATCH_PATHS as u64);
+        assert_eq!(batch.dropped, 15);
+        assert!(inner.take_batches().is_empty());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_watcher_filtering() -> Result<()> {
         let _ = tracing_subscriber::fmt::try_init();
@@ -189,6 +511,7 @@ mod tests {
                 exclude: Some(vec!["*.tmp".to_string(), "ignore_me/*".to_string()]),
                 debounce_seconds: None,
                 retention: None,
+                ..Default::default()
             }],
         };
 
@@ -200,23 +523,25 @@ mod tests {
         fs::write(&file1, "hello")?;
 
         // Wait for event (with timeout)
-        let event = tokio::time::timeout(std::time::Duration::from_millis(500), rx.recv()).await;
+        let event = tokio::time::timeout(std::time::Duration::from_millis(1000), rx.recv()).await;
         assert!(event.is_ok(), "Timed out waiting for event");
         let event = event.unwrap().expect("No event received");
-        let WatcherEvent::FileChanged { set_name, path } = event;
+        let WatcherEvent::FilesChanged { set_name, batch } = event else {
+            panic!("Expected a file change");
+        };
         assert_eq!(set_name, "test");
-        assert!(path.ends_with("file1.txt"));
+        assert!(batch.paths.iter().any(|p| p.ends_with("file1.txt")));
 
-        // Drain any extra events (e.g. Modify after Create)
+        // Drain any extra events (e.g. Modify after Create in the next tick)
         while let Ok(Some(_)) =
-            tokio::time::timeout(std::time::Duration::from_millis(50), rx.recv()).await
+            tokio::time::timeout(std::time::Duration::from_millis(400), rx.recv()).await
         {}
 
         // Test excluded file (glob)
         let file2 = source_path.join("file2.tmp");
         fs::write(&file2, "ignore")?;
 
-        let event = tokio::time::timeout(std::time::Duration::from_millis(200), rx.recv()).await;
+        let event = tokio::time::timeout(std::time::Duration::from_millis(600), rx.recv()).await;
         assert!(event.is_err(), "Received event for excluded file");
 
         // Test excluded directory (glob)
@@ -225,7 +550,7 @@ mod tests {
         let file3 = ignore_dir.join("secret.txt");
         fs::write(&file3, "shh")?;
 
-        let event = tokio::time::timeout(std::time::Duration::from_millis(200), rx.recv()).await;
+        let event = tokio::time::timeout(std::time::Duration::from_millis(600), rx.recv()).await;
         assert!(
             event.is_err(),
             "Received event for excluded directory content"
@@ -233,4 +558,93 @@ mod tests {
 
         Ok(())
     }
+
+    async fn next(rx: &mut mpsc::Receiver<WatcherEvent>, ms: u64) -> Option<WatcherEvent> {
+        tokio::time::timeout(std::time::Duration::from_millis(ms), rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn test_watcher_files_from() -> Result<()> {
+        let tmp = tempdir()?;
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir)?;
+        let listed = dir.join("listed.txt");
+        fs::write(&listed, "a")?;
+        let list = tmp.path().join("list.txt");
+        fs::write(&list, format!("# backed up\n{}\n", listed.display()))?;
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                files_from: Some(list.to_string_lossy().to_string()),
+                target: "/tmp/target".to_string(),
+                ..Default::default()
+            }],
+        };
+        let (tx, mut rx) = mpsc::channel(100);
+        let _watcher = FileWatcher::new(&config, tx)?;
+
+        // A sibling of a listed file is not part of the set
+        fs::write(dir.join("sibling.txt"), "b")?;
+        assert!(
+            next(&mut rx, 600).await.is_none(),
+            "Event for unlisted file"
+        );
+
+        fs::write(&listed, "changed")?;
+        let Some(WatcherEvent::FilesChanged { set_name, batch }) = next(&mut rx, 1000).await else {
+            panic!("Expected a file change");
+        };
+        assert_eq!(set_name, "test");
+        assert!(batch.paths.iter().any(|p| p.ends_with("listed.txt")));
+        while next(&mut rx, 400).await.is_some() {}
+
+        fs::write(&list, "")?;
+        let Some(WatcherEvent::FileListChanged { set_name, .. }) = next(&mut rx, 500).await else {
+            panic!("Expected a file list change");
+        };
+        assert_eq!(set_name, "test");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watcher_exclude_file() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("source");
+        fs::create_dir(&source)?;
+        let excludes = tmp.path().join("excludes.txt");
+        fs::write(&excludes, "*.o\n")?;
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![BackupSet {
+                name: "test".to_string(),
+                source: Some(source.to_string_lossy().to_string()),
+                exclude_file: Some(excludes.to_string_lossy().to_string()),
+                target: "/tmp/target".to_string(),
+                ..Default::default()
+            }],
+        };
+        let (tx, mut rx) = mpsc::channel(100);
+        let _watcher = FileWatcher::new(&config, tx)?;
+
+        fs::write(source.join("main.o"), "obj")?;
+        assert!(
+            next(&mut rx, 600).await.is_none(),
+            "Event for a file the exclude file lists"
+        );
+
+        fs::write(&excludes, "*.o\n*.tmp\n")?;
+        let Some(WatcherEvent::FileListChanged { set_name, path }) = next(&mut rx, 500).await
+        else {
+            panic!("Expected an exclude file change");
+        };
+        assert_eq!(set_name, "test");
+        assert!(path.ends_with("excludes.txt"));
+        Ok(())
+    }
 }
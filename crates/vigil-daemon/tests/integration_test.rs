@@ -90,6 +90,7 @@ async fn test_file_watcher_to_debounce_integration() -> Result<()> {
             exclude: Some(vec!["*.tmp".to_string()]),
             debounce_seconds: Some(1), // 1 second for faster test
             retention: None,
+            ..Default::default()
         }],
     };
 
@@ -122,12 +123,14 @@ async fn test_file_watcher_to_debounce_integration() -> Result<()> {
         .expect("Timeout waiting for file change event")
         .expect("No event received");
 
-    let WatcherEvent::FileChanged { set_name, path } = event;
+    let WatcherEvent::FilesChanged { set_name, batch } = event else {
+        panic!("Expected a file change");
+    };
     assert_eq!(set_name, "test");
-    assert!(path.ends_with("test.txt"));
+    assert!(batch.paths.iter().any(|p| p.ends_with("test.txt")));
 
     // Trigger debounce
-    job_manager.handle_file_change(&set_name).await?;
+    job_manager.handle_file_changes(&set_name, &batch).await?;
 
     // Should enter Debouncing state
     tokio::time::sleep(Duration::from_millis(100)).await;
@@ -153,7 +156,7 @@ async fn test_file_watcher_to_debounce_integration() -> Result<()> {
     assert!(success, "Expected Idle after backup completes");
 
     // Drain any remaining events from the first test
-    while tokio::time::timeout(Duration::from_millis(50), watcher_rx.recv())
+    while tokio::time::timeout(Duration::from_millis(400), watcher_rx.recv())
         .await
         .is_ok()
     {}
@@ -174,13 +177,15 @@ async fn test_file_watcher_to_debounce_integration() -> Result<()> {
     let file3 = source_path.join("final.txt");
     fs::write(&file3, "final test")?;
 
-    let event3 = tokio::time::timeout(Duration::from_millis(500), watcher_rx.recv())
+    let event3 = tokio::time::timeout(Duration::from_millis(1000), watcher_rx.recv())
         .await
         .expect("Timeout waiting for event")
         .expect("No event");
 
-    let WatcherEvent::FileChanged { set_name, .. } = event3;
-    job_manager.handle_file_change(&set_name).await?;
+    let WatcherEvent::FilesChanged { set_name, batch } = event3 else {
+        panic!("Expected a file change");
+    };
+    job_manager.handle_file_changes(&set_name, &batch).await?;
 
     // Should enter Debouncing
     tokio::time::sleep(Duration::from_millis(200)).await;
@@ -262,12 +267,21 @@ async fn test_auto_prune_after_backup() -> Result<()> {
                 keep_daily: None,
                 keep_weekly: None,
                 keep_monthly: None,
+                per_tag: false,
+                ..Default::default()
             }),
+            ..Default::default()
         }],
     };
 
     let job_manager = JobManager::new(&config, CancellationToken::new());
     let mut event_rx = job_manager.subscribe();
+    // The backup worker queues the prune; the maintenance task runs it
+    let maintenance = CancellationToken::new();
+    tokio::spawn(vigil_daemon::maintenance::run(
+        std::sync::Arc::new(job_manager.clone()),
+        maintenance.clone(),
+    ));
 
     // Create initial file
     fs::write(source_path.join("file1.txt"), "data1")?;
@@ -278,7 +292,7 @@ async fn test_auto_prune_after_backup() -> Result<()> {
     // Wait for BackupComplete event
     let mut backup_completed = false;
     while let Ok(event) = tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
-        if let Ok(Response::Ok(Some(ResponseData::BackupComplete { .. }))) = event {
+        if let Ok(Response::Event(ResponseData::BackupComplete { .. })) = event {
             backup_completed = true;
             break;
         }
@@ -288,7 +302,7 @@ async fn test_auto_prune_after_backup() -> Result<()> {
     // Wait for PruneComplete (even if no snapshots pruned, auto-prune still runs)
     let mut prune_completed = false;
     while let Ok(event) = tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
-        if let Ok(Response::Ok(Some(ResponseData::PruneComplete { .. }))) = event {
+        if let Ok(Response::Event(ResponseData::PruneComplete { .. })) = event {
             prune_completed = true;
             break;
         }
@@ -304,7 +318,7 @@ async fn test_auto_prune_after_backup() -> Result<()> {
 
     backup_completed = false;
     while let Ok(event) = tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
-        if let Ok(Response::Ok(Some(ResponseData::BackupComplete { .. }))) = event {
+        if let Ok(Response::Event(ResponseData::BackupComplete { .. })) = event {
             backup_completed = true;
             break;
         }
@@ -313,7 +327,7 @@ async fn test_auto_prune_after_backup() -> Result<()> {
 
     prune_completed = false;
     while let Ok(event) = tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
-        if let Ok(Response::Ok(Some(ResponseData::PruneComplete { .. }))) = event {
+        if let Ok(Response::Event(ResponseData::PruneComplete { .. })) = event {
             prune_completed = true;
             break;
         }
@@ -332,13 +346,13 @@ async fn test_auto_prune_after_backup() -> Result<()> {
 
     for _ in 0..10 {
         match tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
-            Ok(Ok(Response::Ok(Some(ResponseData::BackupComplete { .. })))) => {
+            Ok(Ok(Response::Event(ResponseData::BackupComplete { .. }))) => {
                 backup_completed = true;
             }
-            Ok(Ok(Response::Ok(Some(ResponseData::PruneComplete {
+            Ok(Ok(Response::Event(ResponseData::PruneComplete {
                 set_name,
                 reclaimed_bytes: _,
-            })))) => {
+            }))) => {
                 assert_eq!(set_name, "test");
                 prune_completed = true;
             }
@@ -356,6 +370,7 @@ async fn test_auto_prune_after_backup() -> Result<()> {
 
     // Wait for metrics refresh after prune
     wait_for_snapshot_count(&job_manager, "test", 2).await?;
+    maintenance.cancel();
 
     Ok(())
 }
@@ -5,7 +5,8 @@ use std::time::Duration;
 use tempfile::TempDir;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
-use vigil_lib::ipc::{Request, Response, ResponseData};
+use vigil_lib::config::{BackupSet, ConfigOp};
+use vigil_lib::ipc::{error_codes, Envelope, Request, Response, ResponseData};
 
 struct TestDaemon {
     child: Child,
@@ -124,6 +125,114 @@ async fn test_ipc_ping() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_ipc_echoes_request_id() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let stream = UnixStream::connect(&daemon.socket_path).await?;
+    let mut reader = BufReader::new(stream);
+
+    let mut replies = Vec::new();
+    for line in [
+        r#"{"id":7,"type":"Ping"}"#,
+        r#"{"id":8,"type":"NoSuchRequest"}"#,
+        r#"{"type":"Ping"}"#,
+    ] {
+        reader
+            .get_mut()
+            .write_all(format!("{}\n", line).as_bytes())
+            .await?;
+        let mut reply = String::new();
+        reader.read_line(&mut reply).await?;
+        replies.push(serde_json::from_str::<Envelope<Response>>(&reply)?);
+    }
+
+    assert_eq!(replies[0], Envelope::new(Some(7), Response::Pong));
+    assert_eq!(replies[1].id, Some(8));
+    assert!(matches!(replies[1].message, Response::Error { .. }));
+    assert_eq!(replies[2], Envelope::new(None, Response::Pong));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_permissions_file() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let permissions = daemon.temp_dir.path().join("config/vigil/permissions.toml");
+    let info = || Request::Info {
+        set_name: "test-set".to_string(),
+    };
+    let denied = |resp: &Response| matches!(resp, Response::Error { code, .. } if code == error_codes::PERMISSION_DENIED);
+
+    // Clients not listed get the default list; Ping is always allowed
+    fs::write(&permissions, "default = [\"Status\"]\n")?;
+    assert!(matches!(
+        daemon.send_request(Request::Status).await?,
+        Response::Ok(Some(ResponseData::Status { .. }))
+    ));
+    assert!(denied(&daemon.send_request(info()).await?));
+    assert!(matches!(
+        daemon.send_request(Request::Ping).await?,
+        Response::Pong
+    ));
+
+    // The file is read per connection, so edits apply to the next one
+    fs::write(
+        &permissions,
+        format!(
+            "[[client]]\nexe = \"{}\"\nallow = [\"Info\"]\n",
+            std::env::current_exe()?.display()
+        ),
+    )?;
+    assert!(!denied(&daemon.send_request(info()).await?));
+    assert!(denied(&daemon.send_request(Request::Status).await?));
+
+    // An invalid file refuses everything but Ping
+    fs::write(&permissions, "default = [\"Statuz\"]\n")?;
+    assert!(denied(&daemon.send_request(info()).await?));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_subscriber_sees_backup_start_and_outcome() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let stream = UnixStream::connect(&daemon.socket_path).await?;
+    let mut reader = BufReader::new(stream);
+    let subscribe = Request::Subscribe {
+        sets: Some(vec!["test-set".to_string()]),
+        kinds: Some(vec![
+            "BackupStarted".to_string(),
+            "BackupFailed".to_string(),
+        ]),
+    };
+    let json = serde_json::to_string(&subscribe)? + "\n";
+    reader.get_mut().write_all(json.as_bytes()).await?;
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    assert_eq!(serde_json::from_str::<Response>(&line)?, Response::Ok(None));
+
+    // The target is no repository, so the backup fails
+    let resp = daemon
+        .send_request(Request::Backup {
+            set_name: Some("test-set".to_string()),
+            tags: vec![],
+        })
+        .await?;
+    assert!(matches!(
+        resp,
+        Response::Ok(Some(ResponseData::BackupStarted { .. }))
+    ));
+
+    let mut kinds = Vec::new();
+    while kinds.len() < 2 {
+        line.clear();
+        tokio::time::timeout(Duration::from_secs(30), reader.read_line(&mut line)).await??;
+        let event: Response = serde_json::from_str(&line)?;
+        assert_eq!(event.event_set(), Some("test-set"));
+        kinds.push(event.event_kind().unwrap());
+    }
+    assert_eq!(kinds, ["BackupStarted", "BackupFailed"]);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_ipc_status() -> Result<()> {
     let daemon = TestDaemon::spawn()?;
@@ -137,10 +246,115 @@ async fn test_ipc_status() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_ipc_capabilities() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon.send_request(Request::Capabilities).await?;
+    if let Response::Ok(Some(ResponseData::Capabilities { capabilities })) = resp {
+        let names: Vec<_> = capabilities.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "fusermount3",
+                "notify-send",
+                "ionice",
+                "systemctl",
+                "btrfs",
+                "lvcreate"
+            ]
+        );
+        for cap in &capabilities {
+            assert_eq!(cap.available, cap.hint.is_none());
+        }
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_info_unknown_set() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::Info {
+            set_name: "no-such-set".to_string(),
+        })
+        .await?;
+    if let Response::Error { message, .. } = resp {
+        assert!(message.contains("Unknown backup set"));
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_check_and_init_results() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+
+    let resp = daemon
+        .send_request(Request::Check { set_name: None })
+        .await?;
+    if let Response::Ok(Some(ResponseData::CheckResults { results })) = resp {
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].set_name, "test-set");
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+
+    let resp = daemon
+        .send_request(Request::Init {
+            set_name: Some("no-such-set".to_string()),
+        })
+        .await?;
+    assert!(matches!(resp, Response::Error { ref code, .. } if code == "UnknownSet"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_clean_dry_run() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::Clean { dry_run: true })
+        .await?;
+    assert!(matches!(
+        resp,
+        Response::Ok(Some(ResponseData::CleanReport { dry_run: true, .. }))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_effective_config() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::EffectiveConfig {
+            set_name: "test-set".to_string(),
+        })
+        .await?;
+    if let Response::Ok(Some(ResponseData::EffectiveConfig { config })) = resp {
+        assert_eq!(config.set.name, "test-set");
+        assert!(config.set.debounce_seconds.is_some());
+        assert!(config.sources.contains_key("retention"));
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+
+    let resp = daemon
+        .send_request(Request::EffectiveConfig {
+            set_name: "missing".to_string(),
+        })
+        .await?;
+    assert!(matches!(resp, Response::Error { ref code, .. } if code == "UnknownSet"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_ipc_shutdown() -> Result<()> {
     let mut daemon = TestDaemon::spawn()?;
-    let resp = daemon.send_request(Request::Shutdown).await?;
+    let resp = daemon
+        .send_request(Request::Shutdown { force: false })
+        .await?;
     assert!(matches!(resp, Response::Ok(None)));
 
     // Wait for process to exit
@@ -193,7 +407,7 @@ async fn test_ipc_mount_unmount() -> Result<()> {
         })
         .await?;
 
-    if let Response::Ok(Some(ResponseData::MountPath { path })) = resp {
+    if let Response::Ok(Some(ResponseData::MountPath { path, .. })) = resp {
         let mount_path = std::path::PathBuf::from(path);
         assert!(mount_path.exists());
         // Note: checking if it's actually mounted might be tricky as restic takes a bit to mount
@@ -206,6 +420,7 @@ async fn test_ipc_mount_unmount() -> Result<()> {
     let resp = daemon
         .send_request(Request::Unmount {
             set_name: Some("test-set".to_string()),
+            snapshot_id: None,
         })
         .await?;
 
@@ -243,7 +458,7 @@ async fn test_ipc_mount_cleanup_on_shutdown() -> Result<()> {
         })
         .await?;
 
-    let mount_path = if let Response::Ok(Some(ResponseData::MountPath { path })) = resp {
+    let mount_path = if let Response::Ok(Some(ResponseData::MountPath { path, .. })) = resp {
         std::path::PathBuf::from(path)
     } else {
         panic!("Unexpected response to Mount: {:?}", resp);
@@ -252,7 +467,9 @@ async fn test_ipc_mount_cleanup_on_shutdown() -> Result<()> {
     assert!(mount_path.exists());
 
     // 3. Send Shutdown request
-    let resp = daemon.send_request(Request::Shutdown).await?;
+    let resp = daemon
+        .send_request(Request::Shutdown { force: false })
+        .await?;
     assert!(matches!(resp, Response::Ok(None)));
 
     // Wait for daemon to exit and cleanup
@@ -403,3 +620,233 @@ retention = {{ keep_last = 1 }}
     let _ = child.kill();
     Ok(())
 }
+
+#[tokio::test]
+async fn test_ipc_modify_config() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let source_dir = daemon.temp_dir.path().join("extra-source");
+    fs::create_dir_all(&source_dir)?;
+    let set = BackupSet {
+        name: "extra".to_string(),
+        source: Some(source_dir.display().to_string()),
+        target: daemon
+            .temp_dir
+            .path()
+            .join("extra-target")
+            .display()
+            .to_string(),
+        ..Default::default()
+    };
+
+    let resp = daemon
+        .send_request(Request::ModifyConfig {
+            ops: vec![ConfigOp::AddSet {
+                set: Box::new(set.clone()),
+            }],
+        })
+        .await?;
+    assert!(matches!(resp, Response::Ok(None)), "{:?}", resp);
+
+    // The edit is applied before the daemon replies
+    let resp = daemon.send_request(Request::Status).await?;
+    if let Response::Ok(Some(ResponseData::Status { sets })) = resp {
+        assert!(sets.iter().any(|s| s.name == "extra"));
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+    let config = fs::read_to_string(daemon.temp_dir.path().join("config/vigil/config.toml"))?;
+    assert!(config.contains("name = \"extra\""));
+
+    // Duplicate names are rejected without touching the file
+    let resp = daemon
+        .send_request(Request::ModifyConfig {
+            ops: vec![ConfigOp::AddSet { set: Box::new(set) }],
+        })
+        .await?;
+    assert!(matches!(resp, Response::Error { ref code, .. } if code == "ConfigInvalid"));
+
+    let resp = daemon
+        .send_request(Request::ModifyConfig {
+            ops: vec![ConfigOp::RemoveSet {
+                name: "extra".to_string(),
+            }],
+        })
+        .await?;
+    assert!(matches!(resp, Response::Ok(None)));
+    let resp = daemon.send_request(Request::Status).await?;
+    if let Response::Ok(Some(ResponseData::Status { sets })) = resp {
+        assert!(!sets.iter().any(|s| s.name == "extra"));
+    } else {
+        panic!("Unexpected response: {:?}", resp);
+    }
+    Ok(())
+}
+
+/// Lines of the daemon's logs containing `needle`.
+fn count_log_lines(daemon: &TestDaemon, needle: &str) -> usize {
+    let Ok(entries) = fs::read_dir(daemon.temp_dir.path().join("data/vigil")) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("vigil.log"))
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .map(|log| log.lines().filter(|l| l.contains(needle)).count())
+        .sum()
+}
+
+#[tokio::test]
+async fn test_config_reloads_are_coalesced() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let config_path = daemon.temp_dir.path().join("config/vigil/config.toml");
+    let original = fs::read_to_string(&config_path)?;
+
+    // A burst of changes is read once; a config that parses the same is not applied
+    for _ in 0..3 {
+        fs::write(&config_path, format!("{}\n# edited\n", original))?;
+        let resp = daemon.send_request(Request::ReloadConfig).await?;
+        assert!(matches!(resp, Response::Ok(None)), "{:?}", resp);
+    }
+    let mut unchanged = 0;
+    for _ in 0..50 {
+        unchanged = count_log_lines(&daemon, "Configuration unchanged");
+        if unchanged > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(unchanged, 1);
+    assert_eq!(count_log_lines(&daemon, "Applying new configuration"), 0);
+
+    for seconds in [30, 31, 32] {
+        fs::write(
+            &config_path,
+            original.replace(
+                "debounce_seconds = 60",
+                &format!("debounce_seconds = {}", seconds),
+            ),
+        )?;
+    }
+    let mut applied = 0;
+    for _ in 0..50 {
+        applied = count_log_lines(&daemon, "Configuration reloaded");
+        if applied > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // Give a second reload time to show up if the burst was not coalesced
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(count_log_lines(&daemon, "Applying new configuration"), 1);
+    assert_eq!(applied, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_restore_validates_target() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let restore = |set_name: &str, target: String| Request::Restore {
+        set_name: set_name.to_string(),
+        snapshot_id: None,
+        path: None,
+        target,
+    };
+
+    let resp = daemon
+        .send_request(restore("test-set", "relative/dir".to_string()))
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref code, ref message } if code == "RestoreFailed" && message.contains("absolute")),
+        "{:?}",
+        resp
+    );
+
+    // The source directory is not empty, so restoring into it is refused
+    let occupied = daemon.temp_dir.path().join("occupied");
+    fs::create_dir_all(&occupied)?;
+    fs::write(occupied.join("file"), "x")?;
+    let resp = daemon
+        .send_request(restore("test-set", occupied.display().to_string()))
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref message, .. } if message.contains("not an empty directory")),
+        "{:?}",
+        resp
+    );
+
+    let resp = daemon
+        .send_request(restore("missing", "/tmp/vigil-restore".to_string()))
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref message, .. } if message.contains("Unknown backup set"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_cat_unknown_set() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::Cat {
+            set_name: "missing".to_string(),
+            snapshot_id: "latest".to_string(),
+            path: "/etc/hosts".to_string(),
+            max_bytes: None,
+        })
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref message, .. } if message.contains("Unknown backup set")),
+        "{:?}",
+        resp
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_diff_unknown_set() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::Diff {
+            set_name: "missing".to_string(),
+            snapshot_a: "abc12345".to_string(),
+            snapshot_b: None,
+        })
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref code, ref message } if code == "ResticError" && message.contains("Unknown backup set")),
+        "{:?}",
+        resp
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ipc_verify_rejects_invalid_subset() -> Result<()> {
+    let daemon = TestDaemon::spawn()?;
+    let resp = daemon
+        .send_request(Request::Verify {
+            set_name: None,
+            subset: "--no-lock".to_string(),
+            now: false,
+        })
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref code, .. } if code == "InvalidRequest"),
+        "{:?}",
+        resp
+    );
+
+    let resp = daemon
+        .send_request(Request::Verify {
+            set_name: Some("missing".to_string()),
+            subset: "5%".to_string(),
+            now: false,
+        })
+        .await?;
+    assert!(
+        matches!(resp, Response::Error { ref code, .. } if code == "UnknownSet"),
+        "{:?}",
+        resp
+    );
+    Ok(())
+}
@@ -5,6 +5,7 @@ use tempfile::tempdir;
 use vigil_daemon::executor::ResticExecutor;
 use vigil_lib::config::{BackupSet, RetentionPolicy};
 use vigil_lib::paths;
+use vigil_lib::types::RepairStep;
 
 /// Robust integration test for ResticExecutor.
 /// Requires restic installed and fusermount3 for mount test.
@@ -52,9 +53,10 @@ async fn test_restic_workflow_integration() -> Result<()> {
         exclude: None,
         debounce_seconds: None,
         retention: None,
+        ..Default::default()
     };
 
-    let result = executor.backup(&set, None).await?;
+    let result = executor.backup(&set, None, None, None).await?;
     assert!(result.success, "Backup failed: {:?}", result.error_message);
     assert!(!result.snapshot_id.is_empty());
     assert!(result.added_bytes > 0);
@@ -70,7 +72,33 @@ async fn test_restic_workflow_integration() -> Result<()> {
         .iter()
         .any(|p| p.to_string_lossy().contains("source")));
 
-    // 4. Prune
+    // 4. Restore a single file
+    let restore_dir = tmp.path().join("restore");
+    let include = source_path.join("file1.txt");
+    let summary = executor
+        .restore(
+            &set,
+            None,
+            Some(include.to_str().unwrap()),
+            &restore_dir,
+            None,
+            None,
+        )
+        .await?;
+    assert_eq!(summary.files_restored, 1);
+    let restored = restore_dir.join(include.strip_prefix("/")?);
+    assert_eq!(fs::read_to_string(restored)?, "hello world");
+
+    // Stream the same file without restoring it
+    let file = include.to_str().unwrap();
+    assert_eq!(executor.file_size(&set, "latest", file, None).await?, 11);
+    let dumped = executor
+        .dump(&set, "latest", file)?
+        .wait_with_output()
+        .await?;
+    assert_eq!(dumped.stdout, b"hello world");
+
+    // 5. Prune
     let mut set_with_retention = set.clone();
     set_with_retention.retention = Some(RetentionPolicy {
         keep_last: Some(1),
@@ -86,9 +114,27 @@ async fn test_restic_workflow_integration() -> Result<()> {
         .await?;
     assert_eq!(snapshots.len(), 1);
 
-    // 5. Password Validation: Trigger error with wrong password
+    // 6. Repair: a lost index fails the check and is rebuilt
+    let repo = repo_path.to_str().unwrap();
+    executor
+        .repair(repo, RepairStep::Check, None, |_| {})
+        .await?;
+    fs::remove_dir_all(repo_path.join("index"))?;
+    fs::create_dir(repo_path.join("index"))?;
+    assert!(executor
+        .repair(repo, RepairStep::Check, None, |_| {})
+        .await
+        .is_err());
+    executor
+        .repair(repo, RepairStep::Index, None, |_| {})
+        .await?;
+    executor
+        .repair(repo, RepairStep::Check, None, |_| {})
+        .await?;
+
+    // 7. Password Validation: Trigger error with wrong password
     fs::write(&pw_file, "wrongpassword")?;
-    let bad_result = executor.backup(&set, None).await?;
+    let bad_result = executor.backup(&set, None, None, None).await?;
     assert!(!bad_result.success);
     assert!(
         bad_result
@@ -103,7 +149,7 @@ async fn test_restic_workflow_integration() -> Result<()> {
                 .contains("Restic error")
     );
 
-    // 6. Mount (Pragmatic start/stop)
+    // 8. Mount (Pragmatic start/stop)
     fs::write(&pw_file, "testpassword")?; // Restore correct password
     let mount_point = tmp.path().join("mnt");
     fs::create_dir(&mount_point)?;
@@ -0,0 +1,309 @@
+//! Signed attestations of a backup set's state, for auditors and compliance
+//! systems. The daemon signs them with a per-user Ed25519 key. The document
+//! carries the public key, so anyone can check it was not altered; whether
+//! it comes from the expected signer takes a key obtained separately.
+
+use crate::types::VerifyOutcome;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the attestation document format.
+pub const ATTESTATION_FORMAT: u32 = 1;
+
+/// The only signature algorithm attestations use.
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// What a set's repository looked like when the attestation was made.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct Attestation {
+    pub format: u32,
+    pub set_name: String,
+    /// Hostname of the machine the daemon runs on.
+    pub hostname: String,
+    pub created_at: DateTime<Utc>,
+    /// Repository location as configured.
+    pub repository: String,
+    /// The unique ID restic assigned the repository when it was created.
+    pub repository_id: String,
+    /// Snapshots in the repository, from every host.
+    pub snapshot_count: usize,
+    pub latest_snapshot: Option<AttestedSnapshot>,
+    /// The most recent `vigil check --deep`, successful or not.
+    pub last_check: Option<VerifyOutcome>,
+    pub tools: ToolVersions,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct AttestedSnapshot {
+    pub id: String,
+    pub time: DateTime<Utc>,
+}
+
+/// Versions of the programs that produced the attestation.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ToolVersions {
+    pub vigil: String,
+    /// `restic` or `rustic`.
+    pub engine: String,
+    /// First line of the engine's version output.
+    pub engine_version: String,
+}
+
+/// Signature over an attestation.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct AttestationSignature {
+    /// Always `ed25519`.
+    pub algorithm: String,
+    /// Hex-encoded public key of the signer.
+    pub public_key: String,
+    /// Hex-encoded signature of the attestation's canonical JSON.
+    pub value: String,
+}
+
+/// An attestation with its signature, as written to the output file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SignedAttestation {
+    pub attestation: Attestation,
+    pub signature: AttestationSignature,
+}
+
+/// The bytes that are signed: compact JSON with object keys sorted, which is
+/// how `serde_json::Value` serializes its maps. A verifier re-encodes the
+/// document it was given the same way, so formatting changes do not matter
+/// but any changed value does.
+fn canonical_bytes(attestation: &serde_json::Value) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(attestation)?)
+}
+
+impl SignedAttestation {
+    /// Signs `attestation` with `key`. The signature covers the canonical JSON
+    /// of the `attestation` object (compact, with object keys sorted), and the
+    /// result carries the key's public half, so [`SignedAttestation::verify`]
+    /// needs no other input.
+    pub fn sign(attestation: Attestation, key: &SigningKey) -> Result<Self> {
+        let bytes = canonical_bytes(&serde_json::to_value(&attestation)?)?;
+        let signature = key.sign(&bytes);
+        Ok(Self {
+            attestation,
+            signature: AttestationSignature {
+                algorithm: SIGNATURE_ALGORITHM.to_string(),
+                public_key: hex::encode(key.verifying_key().as_bytes()),
+                value: hex::encode(signature.to_bytes()),
+            },
+        })
+    }
+
+    /// Checks the signature of a document as read from a file and returns it.
+    /// The signature covers the `attestation` object exactly as it appears in
+    /// the document, including fields this version does not know.
+    pub fn verify(document: &str) -> Result<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(document).context("Not a JSON document")?;
+        let attestation = value
+            .get("attestation")
+            .ok_or_else(|| anyhow!("The document has no `attestation` object"))?;
+        let signed: SignedAttestation =
+            serde_json::from_value(value.clone()).context("Not a vigil attestation")?;
+        let signature = &signed.signature;
+        if signature.algorithm != SIGNATURE_ALGORITHM {
+            return Err(anyhow!(
+                "Unsupported signature algorithm '{}'",
+                signature.algorithm
+            ));
+        }
+        let public_key: [u8; 32] = decode_hex(&signature.public_key, "public key")?;
+        let public_key =
+            VerifyingKey::from_bytes(&public_key).context("The public key is not valid")?;
+        let value: [u8; 64] = decode_hex(&signature.value, "signature")?;
+        public_key
+            .verify(
+                &canonical_bytes(attestation)?,
+                &Signature::from_bytes(&value),
+            )
+            .map_err(|_| anyhow!("The signature does not match the attestation"))?;
+        Ok(signed)
+    }
+
+    /// Fails unless the document was signed with `public_key` (hex). A valid
+    /// signature by any other key proves nothing about who made it.
+    pub fn check_signer(&self, public_key: &str) -> Result<()> {
+        if self.signature.public_key.eq_ignore_ascii_case(public_key) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Signed by key {}, not by the expected key {}",
+                self.signature.public_key,
+                public_key
+            ))
+        }
+    }
+}
+
+/// A public key given as hex or as the path of a file holding the hex, as
+/// `vigil attest --verify --public-key` takes it. Returns lowercase hex.
+pub fn read_public_key(value: &str) -> Result<String> {
+    let hex = match decode_hex::<32>(value.trim(), "public key") {
+        Ok(_) => value.trim().to_string(),
+        Err(_) => std::fs::read_to_string(value)
+            .with_context(|| {
+                format!(
+                    "'{}' is neither a hex-encoded public key nor a readable file",
+                    value
+                )
+            })?
+            .trim()
+            .to_string(),
+    };
+    let key: [u8; 32] = decode_hex(&hex, "public key")?;
+    VerifyingKey::from_bytes(&key).context("The public key is not valid")?;
+    Ok(hex::encode(key))
+}
+
+fn decode_hex<const N: usize>(encoded: &str, what: &str) -> Result<[u8; N]> {
+    hex::decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("The {} is not {} hex-encoded bytes", what, N))
+}
+
+/// Reads the signing key at `path` (its 32-byte seed, hex-encoded), creating
+/// one readable only by the user if it does not exist.
+pub fn load_or_create_key(path: &Path) -> Result<SigningKey> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => {
+            let seed: [u8; 32] = decode_hex(content.trim(), "signing key")
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok(SigningKey::from_bytes(&seed))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut seed = [0u8; 32];
+            getrandom::getrandom(&mut seed)
+                .map_err(|e| anyhow!("Failed to generate a signing key: {}", e))?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = crate::platform::create_new_with_mode(path, 0o600)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            std::io::Write::write_all(&mut file, hex::encode(seed).as_bytes())?;
+            Ok(SigningKey::from_bytes(&seed))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Hex-encoded public key of the signing key at `path`, if there is one.
+pub fn local_public_key(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let seed: [u8; 32] = decode_hex(content.trim(), "signing key").ok()?;
+    Some(hex::encode(
+        SigningKey::from_bytes(&seed).verifying_key().as_bytes(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attestation() -> Attestation {
+        Attestation {
+            format: ATTESTATION_FORMAT,
+            set_name: "home".to_string(),
+            hostname: "desk".to_string(),
+            created_at: "2026-10-16T09:00:00Z".parse().unwrap(),
+            repository: "/mnt/backup/home".to_string(),
+            repository_id: "5c1b4ef4f1".to_string(),
+            snapshot_count: 42,
+            latest_snapshot: Some(AttestedSnapshot {
+                id: "a1b2c3d4".to_string(),
+                time: "2026-10-16T08:30:00Z".parse().unwrap(),
+            }),
+            last_check: Some(VerifyOutcome {
+                checked_at: "2026-10-10T03:00:00Z".parse().unwrap(),
+                subset: "5%".to_string(),
+                success: true,
+                error: None,
+            }),
+            tools: ToolVersions {
+                vigil: "0.1.0".to_string(),
+                engine: "restic".to_string(),
+                engine_version: "restic 0.17.3".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_signed_attestation_verifies_until_changed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key_path = tmp.path().join("vigil/attest.key");
+        let key = load_or_create_key(&key_path).unwrap();
+        assert_eq!(
+            load_or_create_key(&key_path).unwrap().to_bytes(),
+            key.to_bytes()
+        );
+
+        let signed = SignedAttestation::sign(attestation(), &key).unwrap();
+        assert_eq!(
+            local_public_key(&key_path),
+            Some(signed.signature.public_key.clone())
+        );
+
+        // Formatting does not matter, values do
+        let pretty = serde_json::to_string_pretty(&signed).unwrap();
+        assert_eq!(SignedAttestation::verify(&pretty).unwrap(), signed);
+        let tampered = pretty.replace("\"snapshot_count\": 42", "\"snapshot_count\": 43");
+        assert_ne!(tampered, pretty);
+        assert!(SignedAttestation::verify(&tampered)
+            .unwrap_err()
+            .to_string()
+            .contains("does not match"));
+
+        // Fields added by a later version are covered by the signature too
+        let mut value = serde_json::to_value(&signed).unwrap();
+        value["attestation"]["extra"] = serde_json::json!(1);
+        assert!(SignedAttestation::verify(&value.to_string()).is_err());
+
+        let other = SigningKey::from_bytes(&[7; 32]);
+        let mut forged = SignedAttestation::sign(attestation(), &other).unwrap();
+        forged.signature.public_key = signed.signature.public_key.clone();
+        assert!(SignedAttestation::verify(&serde_json::to_string(&forged).unwrap()).is_err());
+
+        // Signed with another key, the document verifies but not as the expected signer
+        let forged = SignedAttestation::sign(attestation(), &other).unwrap();
+        let forged = SignedAttestation::verify(&serde_json::to_string(&forged).unwrap()).unwrap();
+        let expected = signed.signature.public_key.to_uppercase();
+        assert!(signed.check_signer(&expected).is_ok());
+        assert!(forged
+            .check_signer(&expected)
+            .unwrap_err()
+            .to_string()
+            .contains("not by the expected key"));
+    }
+
+    #[test]
+    fn test_read_public_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hex = hex::encode(SigningKey::from_bytes(&[7; 32]).verifying_key().as_bytes());
+        assert_eq!(read_public_key(&hex.to_uppercase()).unwrap(), hex);
+        let file = tmp.path().join("signer.pub");
+        std::fs::write(&file, format!("{}\n", hex)).unwrap();
+        assert_eq!(read_public_key(file.to_str().unwrap()).unwrap(), hex);
+        assert!(read_public_key("abcd").is_err());
+        std::fs::write(&file, "not a key").unwrap();
+        assert!(read_public_key(file.to_str().unwrap()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signing_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let key_path = tmp.path().join("attest.key");
+        load_or_create_key(&key_path).unwrap();
+        let mode = std::fs::metadata(&key_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
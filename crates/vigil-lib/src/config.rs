@@ -1,5 +1,8 @@
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,16 +17,261 @@ pub enum ConfigError {
     MissingField(String),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 pub struct Config {
     pub global: GlobalConfig,
     #[serde(rename = "backup_set", default)]
     pub backup_sets: Vec<BackupSet>,
 }
 
+/// A single edit to the configuration file, applied by [`modify_config`].
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ConfigOp {
+    /// Append a new backup set. Fails if a set with the same name exists.
+    AddSet { set: Box<BackupSet> },
+    /// Remove a backup set by name. Fails if no such set exists.
+    RemoveSet { name: String },
+    /// Point a backup set at its own password file. Fails if no such set exists.
+    SetPasswordFile { name: String, password_file: String },
+    /// Set one setting addressed by a [`SettingKey`], e.g.
+    /// `backup_set.home.retention`. A null value removes it.
+    Set {
+        key: String,
+        value: serde_json::Value,
+    },
+}
+
+/// `value` without null object members, which stand for unset settings.
+fn strip_nulls(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), strip_nulls(v)))
+            .collect(),
+        serde_json::Value::Array(items) => items.iter().map(strip_nulls).collect(),
+        other => other.clone(),
+    }
+}
+
+/// A single setting addressed as `global.<field>` or
+/// `backup_set.<name>.<field>`, with further dotted segments for nested
+/// tables such as `global.retention.keep_daily`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingKey {
+    /// The set's name, `None` for `[global]`.
+    pub set: Option<String>,
+    pub path: Vec<String>,
+}
+
+impl SettingKey {
+    pub fn parse(key: &str) -> Result<Self, String> {
+        let (set, rest) = if let Some(rest) = key.strip_prefix("global.") {
+            (None, rest)
+        } else if let Some(rest) = key.strip_prefix("backup_set.") {
+            let (name, rest) = rest.split_once('.').ok_or_else(|| {
+                format!(
+                    "Key '{}' names no setting; use backup_set.<name>.<setting>",
+                    key
+                )
+            })?;
+            (Some(name.to_string()), rest)
+        } else {
+            return Err(format!(
+                "Unknown key '{}'; keys start with 'global.' or 'backup_set.<name>.'",
+                key
+            ));
+        };
+        let path: Vec<String> = rest.split('.').map(str::to_string).collect();
+        if set.as_deref() == Some("") || path.iter().any(String::is_empty) {
+            return Err(format!("Invalid key '{}'", key));
+        }
+        Ok(Self { set, path })
+    }
+
+    /// The setting's own name, e.g. `debounce_seconds`.
+    pub fn field(&self) -> &str {
+        self.path.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// Parses `raw` as this setting's value from the command line. Retention
+    /// takes the `track --retention` form, `*_seconds`/`*_secs` settings take
+    /// intervals such as `5m`, and anything else is read as a TOML value,
+    /// falling back to a plain string.
+    pub fn parse_value(&self, raw: &str) -> Result<serde_json::Value, String> {
+        let field = self.field();
+        if field == "retention" {
+            let policy = serde_json::to_value(RetentionPolicy::parse(raw)?);
+            return policy.map(|v| strip_nulls(&v)).map_err(|e| e.to_string());
+        }
+        if (field.ends_with("_seconds") || field.ends_with("_secs")) && raw.parse::<u64>().is_err()
+        {
+            return Ok(parse_interval(raw)?.as_secs().into());
+        }
+        Ok(toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .and_then(|value| serde_json::to_value(value).ok())
+            .unwrap_or_else(|| raw.into()))
+    }
+}
+
+impl std::fmt::Display for SettingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.set {
+            Some(name) => write!(f, "backup_set.{}.{}", name, self.path.join(".")),
+            None => write!(f, "global.{}", self.path.join(".")),
+        }
+    }
+}
+
 impl Config {
+    /// Problems that do not make the configuration invalid but deserve a
+    /// warning, such as a source that contains vigil's own data directory.
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings_for(&crate::paths::own_paths())
+    }
+
+    fn warnings_for(&self, own: &[PathBuf]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for set in &self.backup_sets {
+            for path in set.own_paths_within(own) {
+                warnings.push(format!(
+                    "Set '{}' contains vigil's own data at {}; it is excluded from watching and backup",
+                    set.name,
+                    path.display()
+                ));
+            }
+        }
+        if let Some(grace) = self
+            .global
+            .shutdown_grace_secs
+            .filter(|&secs| secs >= SYSTEMD_STOP_TIMEOUT_SECS)
+        {
+            warnings.push(format!(
+                "shutdown_grace_secs = {} reaches systemd's default stop timeout of {}s; raise TimeoutStopSec in the service unit or systemd kills running backups first",
+                grace, SYSTEMD_STOP_TIMEOUT_SECS
+            ));
+        }
+        warnings.extend(self.path_overlaps());
+        warnings
+    }
+
+    /// How the sets' paths collide: a local target inside a source (of the
+    /// set itself or of another set), so every backup changes a watched tree
+    /// and triggers another, and sources backed up by several sets. A target
+    /// covered by an anchored exclude pattern of the watching set is fine.
+    /// Expects `~/`-expanded paths.
+    pub fn path_overlaps(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        for set in &self.backup_sets {
+            let Some(target) = set.local_target() else {
+                continue;
+            };
+            for other in &self.backup_sets {
+                let Some(source) = other
+                    .source_roots()
+                    .into_iter()
+                    .find(|source| target.starts_with(source))
+                else {
+                    continue;
+                };
+                if other.excludes_path(target) {
+                    continue;
+                }
+                found.push(if other.name == set.name {
+                    format!(
+                        "Set '{}' has its target {} inside its own source {}; each backup changes the source and triggers another",
+                        set.name,
+                        target.display(),
+                        source.display()
+                    )
+                } else {
+                    format!(
+                        "Set '{}' has its target {} inside source {} of set '{}'; each backup of '{}' triggers a backup of '{}'",
+                        set.name,
+                        target.display(),
+                        source.display(),
+                        other.name,
+                        set.name,
+                        other.name
+                    )
+                });
+            }
+        }
+        for (i, a) in self.backup_sets.iter().enumerate() {
+            for b in &self.backup_sets[i + 1..] {
+                let shared = a.source_roots().into_iter().find_map(|sa| {
+                    b.source_roots().into_iter().find_map(|sb| {
+                        if sa.starts_with(sb) {
+                            Some(sa)
+                        } else if sb.starts_with(sa) {
+                            Some(sb)
+                        } else {
+                            None
+                        }
+                    })
+                });
+                if let Some(path) = shared {
+                    found.push(format!(
+                        "Sets '{}' and '{}' both back up {}; a change there starts a backup of each",
+                        a.name,
+                        b.name,
+                        path.display()
+                    ));
+                }
+            }
+        }
+        found
+    }
+
     /// Validates the configuration structure (unique names, mutually exclusive source fields).
     pub fn check_validity(&self) -> Result<(), ConfigError> {
+        for name in &self.global.restic_env_passthrough {
+            if !is_engine_env(name) {
+                return Err(ConfigError::Validation(format!(
+                    "restic_env_passthrough entry '{}' must start with RESTIC_ or RUSTIC_; other variables are always passed",
+                    name
+                )));
+            }
+        }
+
+        for name in self.global.env.keys() {
+            validate_env_name(name)
+                .map_err(|e| ConfigError::Validation(format!("Invalid global env entry: {}", e)))?;
+        }
+
+        if self.global.retry_backoff_secs == 0 {
+            return Err(ConfigError::Validation(
+                "retry_backoff_secs must be at least 1".to_string(),
+            ));
+        }
+
+        if self.global.max_concurrent_jobs == Some(0) {
+            return Err(ConfigError::Validation(
+                "max_concurrent_jobs must be at least 1".to_string(),
+            ));
+        }
+
+        if self.global.notifications.lacks_command() {
+            return Err(ConfigError::Validation(
+                "notifications use backend = \"command\" but set no command".to_string(),
+            ));
+        }
+
+        if let Some(ref window) = self.global.maintenance_window {
+            MaintenanceWindow::parse(window).map_err(|e| {
+                ConfigError::Validation(format!("Invalid maintenance_window '{}': {}", window, e))
+            })?;
+        }
+
+        if let Some(ref retention) = self.global.retention {
+            retention
+                .check()
+                .map_err(|e| ConfigError::Validation(format!("Invalid global retention: {}", e)))?;
+        }
+
         let mut names = HashSet::new();
         for set in &self.backup_sets {
             if !names.insert(set.name.clone()) {
@@ -40,9 +288,232 @@ impl Config {
                 )));
             }
 
-            if set.source.is_none() && set.sources.is_none() {
+            if set.files_from.is_some() && (set.source.is_some() || set.sources.is_some()) {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' cannot combine 'files_from' with 'source' or 'sources'",
+                    set.name
+                )));
+            }
+
+            if set.source.is_none() && set.sources.is_none() && set.files_from.is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' must have 'source', 'sources' or 'files_from'",
+                    set.name
+                )));
+            }
+
+            if set.files_from.is_some() {
+                if set.engine == EngineKind::Rustic {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' sets files_from, which the rustic engine does not support yet",
+                        set.name
+                    )));
+                }
+                if set.snapshot_source.is_some() {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' cannot combine 'files_from' with 'snapshot_source'",
+                        set.name
+                    )));
+                }
+            }
+
+            if let Some(violation) = set.classification_violation() {
+                return Err(ConfigError::Validation(violation));
+            }
+
+            if set.password_file.is_some() && set.password_command.is_some() {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' cannot have both 'password_file' and 'password_command'",
+                    set.name
+                )));
+            }
+
+            if set.credentials_file.is_some() && !set.backend().is_remote() {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' sets 'credentials_file' but its target '{}' is a local path",
+                    set.name, set.target
+                )));
+            }
+
+            for pattern in set.exclude.iter().flatten() {
+                ExcludePattern::parse(pattern).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid exclude pattern '{}': {}",
+                        set.name, pattern, e
+                    ))
+                })?;
+            }
+
+            for raw in &set.exclude_if_present {
+                let marker = ExcludeMarker::parse(raw).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid exclude_if_present entry '{}': {}",
+                        set.name, raw, e
+                    ))
+                })?;
+                if marker.header.is_some() && set.engine == EngineKind::Rustic {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' checks the header of exclude_if_present file '{}', which the rustic engine does not support",
+                        set.name, marker.file
+                    )));
+                }
+            }
+
+            if set.use_gitignore && set.engine == EngineKind::Restic {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' sets use_gitignore, which restic does not support; use engine = \"rustic\" or exclude patterns",
+                    set.name
+                )));
+            }
+
+            if set.canary && set.engine == EngineKind::Rustic {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' enables canary, which the rustic engine does not support yet",
+                    set.name
+                )));
+            }
+
+            if let Some(ref retention) = set.retention {
+                retention.check().map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid retention: {}",
+                        set.name, e
+                    ))
+                })?;
+            }
+
+            for tag in &set.tags {
+                validate_tag(tag).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid tag '{}': {}",
+                        set.name, tag, e
+                    ))
+                })?;
+            }
+
+            if let Some(tag) = set.logical_path_tag() {
+                if set.engine == EngineKind::Rustic {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' sets logical_path, which the rustic engine does not support yet",
+                        set.name
+                    )));
+                }
+                validate_tag(&tag).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid logical_path: {}",
+                        set.name, e
+                    ))
+                })?;
+            }
+
+            if let Some(ref replica) = set.replica_target {
+                if set.engine == EngineKind::Rustic {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' sets replica_target, which the rustic engine does not support yet",
+                        set.name
+                    )));
+                }
+                if *replica == set.target {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' has replica_target equal to its target",
+                        set.name
+                    )));
+                }
+            } else if set.max_copy_lag_hours.is_some() {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' sets max_copy_lag_hours without a replica_target",
+                    set.name
+                )));
+            }
+
+            if let Some(ref schedule) = set.schedule {
+                Schedule::parse(schedule).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid schedule '{}': {}",
+                        set.name, schedule, e
+                    ))
+                })?;
+            }
+
+            if let Some(ref window) = set.maintenance_window {
+                MaintenanceWindow::parse(window).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid maintenance_window '{}': {}",
+                        set.name, window, e
+                    ))
+                })?;
+            }
+            if set.defer_backups
+                && set.maintenance_window.is_none()
+                && self.global.maintenance_window.is_none()
+            {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' sets defer_backups but no maintenance_window is configured",
+                    set.name
+                )));
+            }
+
+            if set.engine == EngineKind::Rustic
+                && (set.upload_limit_kib.is_some() || set.download_limit_kib.is_some())
+            {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' sets a bandwidth limit, which the rustic engine does not support",
+                    set.name
+                )));
+            }
+
+            if set
+                .notifications
+                .or(&self.global.notifications)
+                .lacks_command()
+            {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' notifies through backend = \"command\" but no command is set",
+                    set.name
+                )));
+            }
+
+            if let Some(ref command) = set.notify_command {
+                if command.trim().is_empty() {
+                    return Err(ConfigError::Validation(format!(
+                        "Set '{}' has an empty notify_command",
+                        set.name
+                    )));
+                }
+                render_notify_command(command).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Invalid notify_command for set '{}': {}",
+                        set.name, e
+                    ))
+                })?;
+            }
+
+            if set.retry_backoff_secs == Some(0) {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' has retry_backoff_secs = 0; retries need at least one second between them",
+                    set.name
+                )));
+            }
+
+            if set.hook_timeout_secs == Some(0) {
+                return Err(ConfigError::Validation(format!(
+                    "Set '{}' has hook_timeout_secs = 0; hooks need at least one second",
+                    set.name
+                )));
+            }
+
+            for name in set.env.keys() {
+                validate_env_name(name).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' has an invalid env entry: {}",
+                        set.name, e
+                    ))
+                })?;
+            }
+
+            if set.lvm_snapshot_size.is_some() && set.snapshot_source != Some(SnapshotSource::Lvm) {
                 return Err(ConfigError::Validation(format!(
-                    "Set '{}' must have either 'source' or 'sources'",
+                    "Set '{}' sets 'lvm_snapshot_size' but does not use snapshot_source = \"lvm\"",
                     set.name
                 )));
             }
@@ -52,6 +523,9 @@ impl Config {
 
     /// Expands `~/` in source and target paths for all backup sets.
     pub fn expand_home_paths(&mut self) {
+        if let Some(ref file) = self.global.env_file {
+            self.global.env_file = Some(expand_home(file));
+        }
         for set in &mut self.backup_sets {
             if let Some(ref s) = set.source {
                 set.source = Some(expand_home(s));
@@ -59,39 +533,202 @@ impl Config {
             if let Some(ref ss) = set.sources {
                 set.sources = Some(ss.iter().map(|s| expand_home(s)).collect());
             }
+            if let Some(ref file) = set.files_from {
+                set.files_from = Some(expand_home(file));
+            }
+            if let Some(ref file) = set.exclude_file {
+                set.exclude_file = Some(expand_home(file));
+            }
             set.target = expand_home(&set.target);
+            if let Some(ref replica) = set.replica_target {
+                set.replica_target = Some(expand_home(replica));
+            }
+            if let Some(ref file) = set.credentials_file {
+                set.credentials_file = Some(expand_home(file));
+            }
+            if let Some(ref file) = set.env_file {
+                set.env_file = Some(expand_home(file));
+            }
+            if let Some(ref file) = set.password_file {
+                set.password_file = Some(expand_home(file));
+            }
         }
     }
 }
 
 /// Global configuration settings.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
 pub struct GlobalConfig {
     /// Wait time in seconds after the last detected change before triggering a backup.
     #[serde(default = "default_debounce")]
     pub debounce_seconds: u64,
+    /// How often a failed backup is retried automatically before the set stays
+    /// in `Error`. 0 disables retries.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay in seconds before the first retry, doubled for each further one
+    /// (see [`retry_delay_secs`]).
+    #[serde(default = "default_retry_backoff")]
+    pub retry_backoff_secs: u64,
+    /// Whether a backup that fails on a locked repository has the stale locks
+    /// removed (`restic unlock`) and is retried once. Locks of operations that
+    /// are still running are never removed.
+    #[serde(default = "default_auto_unlock")]
+    pub auto_unlock: bool,
     /// Default retention policy for all backup sets.
     pub retention: Option<RetentionPolicy>,
+    /// Directory levels to walk in the background after mounting a snapshot,
+    /// warming the repository cache. Unset disables prefetching.
+    pub mount_prefetch_depth: Option<u32>,
+    /// Seconds a mounted snapshot may go unused before the daemon unmounts it.
+    /// A mount is in use while a process has its working directory or an open
+    /// file inside it. Unset or `0` keeps mounts until they are unmounted.
+    pub mount_idle_timeout_secs: Option<u64>,
+    /// `RESTIC_*`/`RUSTIC_*` variables of the daemon's environment that engine
+    /// processes may inherit. All others are removed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restic_env_passthrough: Vec<String>,
+    /// Environment variables set for every engine process, e.g.
+    /// `RESTIC_COMPRESSION = "max"` or `GOMAXPROCS = "2"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// File of `KEY=VALUE` lines set for every engine process, read on each
+    /// run. Variables in `env` win over it.
+    pub env_file: Option<String>,
+    /// Days after which `status` warns that a set's repository has not been
+    /// verified with `vigil check --deep`. Unset disables the warning.
+    pub verify_max_age_days: Option<u32>,
+    /// Hours after which `vigil health` reports a set's last successful
+    /// backup as stale, if changes are pending or it has a schedule. Unset
+    /// disables the check.
+    pub max_age_hours: Option<u64>,
+    /// Hours a set's `replica_target` may trail its repository before `vigil
+    /// health` reports it and a notification is sent. Enables hourly
+    /// comparisons of the two repositories' snapshots. Unset disables them.
+    pub max_copy_lag_hours: Option<u64>,
+    /// Local time range such as `"02:00-06:00"` in which automatic prunes run,
+    /// and debounced backups of sets with `defer_backups`. Unset runs them
+    /// whenever they are due.
+    pub maintenance_window: Option<String>,
+    /// Default upload bandwidth limit for restic, in KiB/s (`--limit-upload`).
+    pub upload_limit_kib: Option<u32>,
+    /// Default download bandwidth limit for restic, in KiB/s (`--limit-download`).
+    pub download_limit_kib: Option<u32>,
+    /// Default CPU and I/O priority of backups and prunes.
+    pub priority: Option<Priority>,
+    /// Seconds without backup activity after which one summary notification
+    /// covers the whole burst, replacing per-backup failure notifications.
+    /// Unset notifies per backup.
+    pub notification_digest_secs: Option<u64>,
+    /// Which events notify and how.
+    #[serde(default, skip_serializing_if = "NotificationPolicy::is_unset")]
+    pub notifications: NotificationPolicy,
+    /// Maximum number of backups and prunes the daemon runs at once, across
+    /// all sets. Unset runs every set's job as soon as it is due.
+    pub max_concurrent_jobs: Option<usize>,
+    /// Seconds running backups may take to finish after SIGTERM or SIGINT
+    /// before they are cancelled. Unset cancels them at once.
+    pub shutdown_grace_secs: Option<u64>,
+    /// Seconds change-triggered backups of a set stay paused after a restore
+    /// into its sources, so the restored files can be checked before they
+    /// are backed up. 0 disables the pause.
+    #[serde(default = "default_restore_shield")]
+    pub restore_shield_secs: u64,
+    /// Refuse configurations whose sets overlap (a local target inside a
+    /// watched source, or sources shared by several sets) instead of warning.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
 }
 
+/// How long systemd waits for a stopping service before killing it, unless
+/// the unit sets `TimeoutStopSec`.
+pub const SYSTEMD_STOP_TIMEOUT_SECS: u64 = 90;
+
 fn default_debounce() -> u64 {
     60
 }
 
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff() -> u64 {
+    60
+}
+
+fn default_auto_unlock() -> bool {
+    true
+}
+
+fn default_restore_shield() -> u64 {
+    600
+}
+
+/// Longest delay between two automatic retries of a failed backup.
+pub const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// Delay before retry number `attempt` (counted from 1): `backoff_secs`,
+/// doubled for each earlier retry and capped at [`MAX_RETRY_DELAY_SECS`].
+pub fn retry_delay_secs(backoff_secs: u64, attempt: u32) -> u64 {
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    backoff_secs
+        .saturating_mul(factor)
+        .min(MAX_RETRY_DELAY_SECS)
+}
+
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
             debounce_seconds: default_debounce(),
+            max_retries: default_max_retries(),
+            retry_backoff_secs: default_retry_backoff(),
+            auto_unlock: default_auto_unlock(),
             retention: Some(RetentionPolicy {
                 keep_last: Some(10),
                 ..Default::default()
             }),
+            mount_prefetch_depth: None,
+            mount_idle_timeout_secs: None,
+            restic_env_passthrough: Vec::new(),
+            env: BTreeMap::new(),
+            env_file: None,
+            verify_max_age_days: None,
+            max_age_hours: None,
+            max_copy_lag_hours: None,
+            maintenance_window: None,
+            upload_limit_kib: None,
+            download_limit_kib: None,
+            priority: None,
+            notification_digest_secs: None,
+            notifications: NotificationPolicy::default(),
+            max_concurrent_jobs: None,
+            shutdown_grace_secs: None,
+            restore_shield_secs: default_restore_shield(),
+            strict: false,
+        }
+    }
+}
+
+impl GlobalConfig {
+    /// Variables from the global `env_file` and `env` table.
+    pub fn engine_env(&self) -> EngineEnv {
+        EngineEnv::new(self.env_file.as_deref(), &self.env)
+    }
+
+    /// The global bandwidth limits and priority.
+    pub fn throttle(&self) -> Throttle {
+        Throttle {
+            upload_limit_kib: self.upload_limit_kib,
+            download_limit_kib: self.download_limit_kib,
+            priority: self.priority,
         }
     }
 }
 
 /// Configuration for a specific backup set.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
 pub struct BackupSet {
     /// Unique identifier for the backup set.
     pub name: String,
@@ -99,226 +736,3081 @@ pub struct BackupSet {
     pub source: Option<String>,
     /// Multiple source directory paths (mutually exclusive with `source`).
     pub sources: Option<Vec<String>>,
-    /// Restic repository target path.
+    /// File listing the paths to back up, one per line (restic
+    /// `--files-from`). Replaces `source`/`sources`; see [`parse_file_list`].
+    #[serde(alias = "include_file")]
+    pub files_from: Option<String>,
+    /// Restic repository: a local path, or a remote backend URL such as
+    /// `sftp:host:/path`, `s3:endpoint/bucket` or `rest:https://host/`.
     pub target: String,
-    /// Optional glob patterns for file exclusion.
+    /// Optional exclude patterns, with restic `--exclude` semantics (see [`ExcludePattern`]).
     pub exclude: Option<Vec<String>>,
+    /// File of exclude patterns, one per line (restic `--exclude-file`), used
+    /// next to `exclude`; see [`parse_exclude_file`].
+    pub exclude_file: Option<String>,
+    /// Directories containing one of these files are skipped, written `name`
+    /// or `name:header` (see [`ExcludeMarker`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_if_present: Vec<String>,
+    /// Skip directories marked as caches by a `CACHEDIR.TAG` file.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_caches: bool,
+    /// Skip paths that `.gitignore` files ignore inside git work trees. Only
+    /// the rustic engine supports this.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub use_gitignore: bool,
+    /// Write a `.vigil-canary` file into each source before a backup and
+    /// fail the backup unless the new snapshot contains it. restic only.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canary: bool,
     /// Override for the global debounce delay.
     pub debounce_seconds: Option<u64>,
+    /// Override for the global number of automatic retries.
+    pub max_retries: Option<u32>,
+    /// Override for the global delay before the first retry.
+    pub retry_backoff_secs: Option<u64>,
+    /// Override for the global pause after a restore into the set's sources.
+    pub restore_shield_secs: Option<u64>,
+    /// Fewest changed files for which a debounced backup runs. Smaller
+    /// change sets are skipped and carried over to the next backup.
+    pub min_changed_files: Option<u64>,
+    /// Fewest changed bytes for which a debounced backup runs (see
+    /// [`BackupSet::meets_change_threshold`]).
+    pub min_changed_bytes: Option<u64>,
     /// Override for the global retention policy.
     pub retention: Option<RetentionPolicy>,
+    /// Whether retention is applied after each successful backup. Defaults to
+    /// true; with `false` the set is only pruned by `vigil prune`.
+    pub auto_prune: Option<bool>,
+    /// Second repository the set's snapshots are copied to (`restic copy`),
+    /// e.g. an off-site `sftp:` or `s3:` URL. Opened with the set's password
+    /// and credentials, and created on first use. restic only.
+    pub replica_target: Option<String>,
+    /// Whether snapshots are copied to `replica_target` after each successful
+    /// backup. Defaults to true; with `false` only `vigil replicate` copies them.
+    pub auto_replicate: Option<bool>,
+    /// Override for the global replica lag limit. `0` disables the
+    /// comparison for this set. Needs `replica_target`.
+    pub max_copy_lag_hours: Option<u64>,
+    /// Whether other machines also back up into this repository. Prune then
+    /// only forgets this host's snapshots and refuses to run while the
+    /// repository is locked.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared_repo: bool,
+    /// Data classification used to enforce handling policies.
+    pub classification: Option<Classification>,
+    /// Backup tool that writes and reads this set's repository.
+    #[serde(default, skip_serializing_if = "EngineKind::is_default")]
+    pub engine: EngineKind,
+    /// Override for the global mount prefetch depth. `0` disables prefetching for this set.
+    pub mount_prefetch_depth: Option<u32>,
+    /// Override for the global mount idle timeout. `0` keeps this set's mounts.
+    pub mount_idle_timeout_secs: Option<u64>,
+    /// Filesystem snapshot taken before each backup so restic reads a consistent view.
+    pub snapshot_source: Option<SnapshotSource>,
+    /// Copy-on-write space reserved for LVM snapshots (`lvcreate --size`), e.g. `"2G"`.
+    pub lvm_snapshot_size: Option<String>,
+    /// File of `KEY=VALUE` lines (e.g. `AWS_ACCESS_KEY_ID=...`) passed to the
+    /// engine as environment variables. Only valid with a remote target.
+    pub credentials_file: Option<String>,
+    /// Environment variables set for this set's engine processes, on top of
+    /// the global `env`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// File of `KEY=VALUE` lines set for this set's engine processes, read on
+    /// each run. Variables in `env` win over it.
+    pub env_file: Option<String>,
+    /// File holding this set's repository password, instead of the shared `.repo_password`.
+    pub password_file: Option<String>,
+    /// Command that prints this set's repository password (e.g. `pass show backup/home`).
+    /// Mutually exclusive with `password_file`.
+    pub password_command: Option<String>,
+    /// Time-based backups on top of change-triggered ones: an interval such as
+    /// `"6h"` or `"1h30m"`, or a cron expression such as `"0 3 * * *"` (local time).
+    pub schedule: Option<String>,
+    /// Override for the global maintenance window.
+    pub maintenance_window: Option<String>,
+    /// Whether backups started by the debounce timer wait for the maintenance
+    /// window. Manual and scheduled backups never wait.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub defer_backups: bool,
+    /// Tags added to every snapshot of this set (`restic backup --tag`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Stable name for the set's data, e.g. `"/home/me"`, that outlives moves of
+    /// its sources on disk. Snapshots are tagged with it (see
+    /// [`BackupSet::logical_path_tag`]) and matched by the tag instead of by
+    /// their source paths. restic only.
+    pub logical_path: Option<String>,
+    /// Override for the global upload bandwidth limit, in KiB/s. restic only.
+    pub upload_limit_kib: Option<u32>,
+    /// Override for the global download bandwidth limit, in KiB/s. restic only.
+    pub download_limit_kib: Option<u32>,
+    /// Override for the global backup and prune priority.
+    pub priority: Option<Priority>,
+    /// The repository refuses deletions (e.g. `rest-server --append-only`), so
+    /// the daemon never prunes it. Old snapshots must be removed on the server.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub append_only: bool,
+    /// Shell command run before each backup, e.g. dumping a database into a source directory.
+    pub pre_backup_command: Option<String>,
+    /// Shell command run after each backup attempt, successful or not.
+    pub post_backup_command: Option<String>,
+    /// Seconds a hook command may run before it is killed. Defaults to 300.
+    pub hook_timeout_secs: Option<u64>,
+    /// Whether a failing `pre_backup_command` cancels the backup.
+    #[serde(default, skip_serializing_if = "HookFailure::is_default")]
+    pub hook_failure: HookFailure,
+    /// Overrides for the global notification policy; unset fields inherit.
+    #[serde(default, skip_serializing_if = "NotificationPolicy::is_unset")]
+    pub notifications: NotificationPolicy,
+    /// Shell command run for each of this set's notifications, next to the
+    /// notification backend. `{event}`, `{set}` and `{message}` expand to the
+    /// notification's values; see [`render_notify_command`].
+    pub notify_command: Option<String>,
 }
 
-/// Retention policy defining how many snapshots to keep.
-#[derive(Debug, Deserialize, Serialize, Clone, Default)]
-pub struct RetentionPolicy {
-    /// Number of most recent snapshots to keep.
-    pub keep_last: Option<u32>,
-    /// Number of daily snapshots to keep.
-    pub keep_daily: Option<u32>,
-    /// Number of weekly snapshots to keep.
-    pub keep_weekly: Option<u32>,
-    /// Number of monthly snapshots to keep.
-    pub keep_monthly: Option<u32>,
-}
+impl BackupSet {
+    /// Applies global fallbacks to this set and records where each inherited
+    /// setting came from.
+    pub fn resolve(&self, global: &GlobalConfig) -> EffectiveConfig {
+        let mut set = self.clone();
+        let mut sources = BTreeMap::new();
 
-impl Config {
-    /// Validates and expands paths.
-    pub fn validate(&mut self) -> Result<(), ConfigError> {
-        self.check_validity()?;
-        self.expand_home_paths();
-        Ok(())
+        let debounce_source = if self.debounce_seconds.is_some() {
+            SettingSource::Set
+        } else {
+            set.debounce_seconds = Some(global.debounce_seconds);
+            SettingSource::Global
+        };
+        sources.insert("debounce_seconds".to_string(), debounce_source);
+        sources.insert(
+            "max_retries".to_string(),
+            inherit(&mut set.max_retries, Some(global.max_retries)),
+        );
+        sources.insert(
+            "retry_backoff_secs".to_string(),
+            inherit(&mut set.retry_backoff_secs, Some(global.retry_backoff_secs)),
+        );
+        sources.insert(
+            "restore_shield_secs".to_string(),
+            inherit(
+                &mut set.restore_shield_secs,
+                Some(global.restore_shield_secs),
+            ),
+        );
+
+        let retention_source = match (&self.retention, &global.retention) {
+            (Some(_), _) => SettingSource::Set,
+            (None, Some(retention)) => {
+                set.retention = Some(retention.clone());
+                SettingSource::Global
+            }
+            (None, None) => SettingSource::Default,
+        };
+        sources.insert("retention".to_string(), retention_source);
+
+        let prefetch_source = match (self.mount_prefetch_depth, global.mount_prefetch_depth) {
+            (Some(_), _) => SettingSource::Set,
+            (None, Some(_)) => SettingSource::Global,
+            (None, None) => SettingSource::Default,
+        };
+        set.mount_prefetch_depth = Some(self.prefetch_depth(global.mount_prefetch_depth));
+        sources.insert("mount_prefetch_depth".to_string(), prefetch_source);
+
+        sources.insert(
+            "upload_limit_kib".to_string(),
+            inherit(&mut set.upload_limit_kib, global.upload_limit_kib),
+        );
+        sources.insert(
+            "download_limit_kib".to_string(),
+            inherit(&mut set.download_limit_kib, global.download_limit_kib),
+        );
+        sources.insert(
+            "priority".to_string(),
+            inherit(&mut set.priority, global.priority),
+        );
+        sources.insert(
+            "maintenance_window".to_string(),
+            inherit(
+                &mut set.maintenance_window,
+                global.maintenance_window.clone(),
+            ),
+        );
+        if set.replica_target.is_some() {
+            sources.insert(
+                "max_copy_lag_hours".to_string(),
+                inherit(&mut set.max_copy_lag_hours, global.max_copy_lag_hours),
+            );
+        }
+        sources.insert(
+            "mount_idle_timeout_secs".to_string(),
+            inherit(
+                &mut set.mount_idle_timeout_secs,
+                global.mount_idle_timeout_secs,
+            ),
+        );
+
+        let notifications_source = if !self.notifications.is_unset() {
+            SettingSource::Set
+        } else if !global.notifications.is_unset() {
+            SettingSource::Global
+        } else {
+            SettingSource::Default
+        };
+        set.notifications = self.notifications.or(&global.notifications);
+        sources.insert("notifications".to_string(), notifications_source);
+
+        EffectiveConfig { set, sources }
     }
-}
 
-pub fn expand_home(path: &str) -> String {
-    if path.starts_with("~/") {
-        if let Some(home) = directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()) {
-            return path.replacen("~", &home.to_string_lossy(), 1);
+    /// This set's own bandwidth limits and priority, without global fallbacks.
+    pub fn throttle(&self) -> Throttle {
+        Throttle {
+            upload_limit_kib: self.upload_limit_kib,
+            download_limit_kib: self.download_limit_kib,
+            priority: self.priority,
         }
     }
-    path.to_string()
-}
 
-/// Loads the configuration from the environment variable `VIGIL_CONFIG`
-/// or the default system location (`~/.config/vigil/config.toml`).
-///
-/// # Errors
-///
-/// Returns `ConfigError` if the file cannot be found, read, or parsed,
-/// or if validation fails.
-pub fn load_config() -> Result<Config, ConfigError> {
-    let mut config = load_config_raw()?;
-    config.validate()?;
-    Ok(config)
-}
+    /// Storage backend of this set's repository.
+    pub fn backend(&self) -> Backend {
+        Backend::of(&self.target)
+    }
 
-/// Loads the configuration without expansion or validation.
-pub fn load_config_raw() -> Result<Config, ConfigError> {
-    let path = crate::paths::active_config_path();
+    /// Variables from this set's `env_file` and `env` table.
+    pub fn engine_env(&self) -> EngineEnv {
+        EngineEnv::new(self.env_file.as_deref(), &self.env)
+    }
 
-    if !path.exists() {
-        return Err(ConfigError::Io(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Config file not found: {:?}", path),
-        )));
+    /// Everything set in the environment of this set's engine processes, in
+    /// increasing precedence: the global `env_file` and `env`, then the set's
+    /// `credentials_file`, `env_file` and `env`.
+    pub fn process_env(&self, global: &GlobalConfig) -> Result<Vec<(String, String)>, ConfigError> {
+        engine_process_env(
+            &global.engine_env(),
+            self.credentials_file.as_deref().map(Path::new),
+            &self.engine_env(),
+        )
     }
 
-    let content = std::fs::read_to_string(path)?;
-    let config: Config = toml::from_str(&content)?;
-    Ok(config)
-}
+    /// Where the engine reads this set's repository password from.
+    pub fn password_source(&self) -> PasswordSource {
+        match (&self.password_command, &self.password_file) {
+            (Some(command), _) => PasswordSource::Command(command.clone()),
+            (None, Some(file)) => PasswordSource::File(PathBuf::from(file)),
+            (None, None) => PasswordSource::default(),
+        }
+    }
 
-/// Saves the configuration to the active config path.
-pub fn save_config(config: &Config) -> Result<(), ConfigError> {
-    config.check_validity()?;
+    /// Whether `changed_files` pending changes of `changed_bytes` (`None` when
+    /// unknown) are enough for a debounced backup: either configured minimum
+    /// suffices, and a set without one always backs up. Unknown sizes count
+    /// as enough, so a backup is never skipped on a guess.
+    pub fn meets_change_threshold(&self, changed_files: u64, changed_bytes: Option<u64>) -> bool {
+        if self.min_changed_files.is_none() && self.min_changed_bytes.is_none() {
+            return true;
+        }
+        self.min_changed_files
+            .is_some_and(|min| changed_files >= min)
+            || self
+                .min_changed_bytes
+                .is_some_and(|min| changed_bytes.is_none_or(|bytes| bytes >= min))
+    }
 
-    let path = crate::paths::active_config_path();
+    /// The tag identifying snapshots of `logical_path`, e.g. `vigil-path:/home/me`.
+    pub fn logical_path_tag(&self) -> Option<String> {
+        self.logical_path
+            .as_ref()
+            .map(|path| format!("{}{}", LOGICAL_PATH_TAG_PREFIX, path))
+    }
 
-    // Create parent directory if it doesn't exist
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
+    /// Parsed `exclude` patterns. Invalid patterns are skipped; validation rejects them.
+    pub fn exclude_patterns(&self) -> Vec<ExcludePattern> {
+        self.exclude
+            .iter()
+            .flatten()
+            .filter_map(|p| ExcludePattern::parse(p).ok())
+            .collect()
     }
 
-    let content = toml::to_string_pretty(config)
-        .map_err(|e| ConfigError::Validation(format!("Failed to serialize config: {}", e)))?;
-    std::fs::write(path, content)?;
-    Ok(())
-}
+    /// Patterns of the set's `exclude_file`, read from disk. Empty without one.
+    pub fn exclude_file_patterns(&self) -> Result<Vec<ExcludePattern>, String> {
+        let Some(ref file) = self.exclude_file else {
+            return Ok(Vec::new());
+        };
+        let content = std::fs::read_to_string(file)
+            .map_err(|e| format!("cannot read exclude_file '{}': {}", file, e))?;
+        parse_exclude_file(&content).map_err(|e| format!("exclude_file '{}': {}", file, e))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serial_test::serial;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    /// Parsed `exclude_if_present` entries, plus the `CACHEDIR.TAG` marker with
+    /// `exclude_caches`. Invalid entries are skipped; validation rejects them.
+    ///
+    /// The rustic engine cannot check headers, so for it the cache marker
+    /// matches any `CACHEDIR.TAG` file.
+    pub fn exclude_markers(&self) -> Vec<ExcludeMarker> {
+        let mut markers: Vec<ExcludeMarker> = self
+            .exclude_if_present
+            .iter()
+            .filter_map(|m| ExcludeMarker::parse(m).ok())
+            .collect();
+        if self.exclude_caches {
+            let mut tag = ExcludeMarker::cachedir_tag();
+            if self.engine == EngineKind::Rustic {
+                tag.header = None;
+            }
+            markers.push(tag);
+        }
+        markers
+    }
 
-    #[test]
-    #[serial]
+    /// `exclude_patterns` plus vigil's own paths inside the set's sources, so
+    /// the daemon's log and cache writes never trigger or enter a backup.
+    pub fn backup_excludes(&self) -> Vec<ExcludePattern> {
+        let mut excludes = self.exclude_patterns();
+        excludes.extend(
+            self.own_paths_within(&crate::paths::own_paths())
+                .iter()
+                .filter_map(|path| ExcludePattern::for_path(path)),
+        );
+        excludes
+    }
+
+    /// The entries of `own` that lie inside one of the set's sources.
+    fn own_paths_within(&self, own: &[PathBuf]) -> Vec<PathBuf> {
+        let sources = self.source_roots();
+        own.iter()
+            .filter(|path| sources.iter().any(|source| path.starts_with(source)))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `path` lies inside one of the set's sources or contains one,
+    /// so writing there changes what the set backs up.
+    pub fn overlaps_sources(&self, path: &Path) -> bool {
+        self.source_roots()
+            .iter()
+            .any(|source| path.starts_with(source) || source.starts_with(path))
+    }
+
+    /// The set's `source` or `sources` directories.
+    fn source_roots(&self) -> Vec<&Path> {
+        self.source
+            .iter()
+            .chain(self.sources.iter().flatten())
+            .map(Path::new)
+            .collect()
+    }
+
+    /// The repository directory, for a target on the local filesystem.
+    fn local_target(&self) -> Option<&Path> {
+        (!self.backend().is_remote()).then(|| Path::new(&self.target))
+    }
+
+    /// Whether an anchored, glob-free exclude pattern covers `path`.
+    fn excludes_path(&self, path: &Path) -> bool {
+        self.exclude_patterns().iter().any(|p| {
+            p.is_anchored()
+                && !p.restic_arg().contains(['*', '?', '['])
+                && path.starts_with(p.restic_arg())
+        })
+    }
+
+    /// Parsed `schedule`, if the set has a valid one.
+    pub fn schedule(&self) -> Option<Schedule> {
+        self.schedule
+            .as_deref()
+            .and_then(|s| Schedule::parse(s).ok())
+    }
+
+    /// The set's maintenance window, falling back to the global one.
+    pub fn maintenance_window(&self, global: Option<&str>) -> Option<MaintenanceWindow> {
+        self.maintenance_window
+            .as_deref()
+            .or(global)
+            .and_then(|w| MaintenanceWindow::parse(w).ok())
+    }
+
+    /// Returns the number of directory levels to prefetch after mounting this set,
+    /// falling back to the global setting. `0` means prefetching is disabled.
+    pub fn prefetch_depth(&self, global: Option<u32>) -> u32 {
+        self.mount_prefetch_depth.or(global).unwrap_or(0)
+    }
+
+    /// Whether the daemon prunes this set after its backups.
+    pub fn auto_prune(&self) -> bool {
+        self.auto_prune.unwrap_or(true)
+    }
+
+    /// Whether the daemon copies this set's snapshots to its replica after
+    /// its backups.
+    pub fn auto_replicate(&self) -> bool {
+        self.replica_target.is_some() && self.auto_replicate.unwrap_or(true)
+    }
+
+    /// Hours the replica may trail the repository, falling back to the global
+    /// limit. `None` without a replica or when the comparison is disabled.
+    pub fn max_copy_lag_hours(&self, global: Option<u64>) -> Option<u64> {
+        self.replica_target.as_ref()?;
+        self.max_copy_lag_hours
+            .or(global)
+            .filter(|hours| *hours > 0)
+    }
+
+    /// Seconds after which an unused mount of this set is unmounted, falling
+    /// back to the global setting. `None` when mounts are kept.
+    pub fn mount_idle_timeout(&self, global: Option<u64>) -> Option<u64> {
+        self.mount_idle_timeout_secs
+            .or(global)
+            .filter(|secs| *secs > 0)
+    }
+
+    /// Returns a description of the first classification policy this set violates, if any.
+    ///
+    /// `sensitive` sets must be stored in a remote repository so the data leaves
+    /// the machine only in restic's encrypted form.
+    pub fn classification_violation(&self) -> Option<String> {
+        match self.classification {
+            Some(Classification::Sensitive) if !self.backend().is_remote() => Some(format!(
+                "Set '{}' is classified as sensitive and must use a remote repository (e.g. `sftp:`, `s3:`, `rest:`), but '{}' is a local path",
+                self.name, self.target
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Origin of a resolved setting.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingSource {
+    /// Written in the set's own `[[backup_set]]` table.
+    Set,
+    /// Inherited from `[global]`.
+    Global,
+    /// Neither the set nor `[global]` sets it; the built-in behavior applies.
+    Default,
+}
+
+impl std::fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingSource::Set => write!(f, "set"),
+            SettingSource::Global => write!(f, "global"),
+            SettingSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Fills `value` from `global` when the set leaves it unset.
+fn inherit<T>(value: &mut Option<T>, global: Option<T>) -> SettingSource {
+    match (value.is_some(), global) {
+        (true, _) => SettingSource::Set,
+        (false, Some(global)) => {
+            *value = Some(global);
+            SettingSource::Global
+        }
+        (false, None) => SettingSource::Default,
+    }
+}
+
+/// A backup set with all global fallbacks applied, as the daemon uses it.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+pub struct EffectiveConfig {
+    /// The resolved set. `~` in paths is already expanded.
+    pub set: BackupSet,
+    /// Origin of each setting that can be inherited, keyed by field name.
+    pub sources: BTreeMap<String, SettingSource>,
+}
+
+/// Backup engine used for a set. Both engines read and write the restic repository format.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    #[default]
+    Restic,
+    Rustic,
+}
+
+impl EngineKind {
+    /// Name of the engine's command-line binary.
+    pub fn binary(&self) -> &'static str {
+        match self {
+            EngineKind::Restic => "restic",
+            EngineKind::Rustic => "rustic",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == EngineKind::default()
+    }
+}
+
+/// CPU and I/O priority of a set's backups and prunes, so they do not compete
+/// with interactive work.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// The daemon's own priority.
+    #[default]
+    Normal,
+    /// `nice -n 10` and the lowest best-effort I/O level.
+    Low,
+    /// `nice -n 19` and idle I/O: the disk is only used when nothing else needs it.
+    Idle,
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::Normal => write!(f, "normal"),
+            Priority::Low => write!(f, "low"),
+            Priority::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+/// What a failing `pre_backup_command` does to the backup.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailure {
+    /// Skip the backup and report it as failed.
+    #[default]
+    Abort,
+    /// Log the failure and back up anyway.
+    Continue,
+}
+
+impl HookFailure {
+    fn is_default(&self) -> bool {
+        *self == HookFailure::default()
+    }
+}
+
+/// Something the daemon can notify about.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyEvent {
+    /// A backup finished successfully.
+    Success,
+    /// A backup failed, or a hook or filesystem snapshot around it did.
+    Failure,
+    /// An automatic prune after a backup failed.
+    Prune,
+    /// The repository stopped answering status queries.
+    Unreachable,
+    /// An automatic replication after a backup failed.
+    Replication,
+}
+
+impl std::fmt::Display for NotifyEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifyEvent::Success => write!(f, "success"),
+            NotifyEvent::Failure => write!(f, "failure"),
+            NotifyEvent::Prune => write!(f, "prune"),
+            NotifyEvent::Unreachable => write!(f, "unreachable"),
+            NotifyEvent::Replication => write!(f, "replication"),
+        }
+    }
+}
+
+/// Where notifications are delivered.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyBackend {
+    /// Desktop notifications through the session's notification daemon.
+    #[default]
+    Desktop,
+    /// Run `command` through `sh -c` with the notification in its environment.
+    Command,
+    /// Do not notify.
+    None,
+}
+
+/// Which events notify and through which backend. The `[global.notifications]`
+/// table is the default; a set's `notifications` table overrides single fields.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct NotificationPolicy {
+    /// Events that notify. Unset means every event but `success`.
+    pub events: Option<Vec<NotifyEvent>>,
+    pub backend: Option<NotifyBackend>,
+    /// Shell command for the `command` backend.
+    pub command: Option<String>,
+}
+
+impl NotificationPolicy {
+    /// Events notified when `events` is unset.
+    pub const DEFAULT_EVENTS: [NotifyEvent; 4] = [
+        NotifyEvent::Failure,
+        NotifyEvent::Prune,
+        NotifyEvent::Unreachable,
+        NotifyEvent::Replication,
+    ];
+
+    /// Fills each unset field from `fallback`.
+    pub fn or(&self, fallback: &NotificationPolicy) -> NotificationPolicy {
+        NotificationPolicy {
+            events: self.events.clone().or_else(|| fallback.events.clone()),
+            backend: self.backend.or(fallback.backend),
+            command: self.command.clone().or_else(|| fallback.command.clone()),
+        }
+    }
+
+    /// Whether `event` should notify under this policy.
+    pub fn notifies(&self, event: NotifyEvent) -> bool {
+        match self.events {
+            Some(ref events) => events.contains(&event),
+            None => Self::DEFAULT_EVENTS.contains(&event),
+        }
+    }
+
+    pub fn backend(&self) -> NotifyBackend {
+        self.backend.unwrap_or_default()
+    }
+
+    fn is_unset(&self) -> bool {
+        *self == NotificationPolicy::default()
+    }
+
+    fn lacks_command(&self) -> bool {
+        self.backend() == NotifyBackend::Command
+            && self.command.as_deref().is_none_or(|c| c.trim().is_empty())
+    }
+}
+
+/// Placeholders a `notify_command` may use, in the order their values are
+/// passed to the script as positional parameters.
+pub const NOTIFY_PLACEHOLDERS: [&str; 3] = ["event", "set", "message"];
+
+/// Turns a `notify_command` into a script for `sh -c <script> sh <values>`,
+/// where the values of [`NOTIFY_PLACEHOLDERS`] follow as `$1`, `$2` and `$3`.
+/// Each placeholder becomes a reference to its parameter, `"$1"` unquoted and
+/// `${1}` inside double quotes, so the shell never parses a value as code.
+/// Inside single quotes a placeholder would not expand and is an error.
+/// Braces around anything but a lowercase word are left alone, so `${HOME}`
+/// keeps working; an unknown `{word}` is an error.
+pub fn render_notify_command(template: &str) -> Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut quote: Option<char> = None;
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\\' if quote != Some('\'') => {
+                rendered.push(c);
+                if let Some(escaped) = rest.chars().next() {
+                    rendered.push(escaped);
+                    rest = &rest[escaped.len_utf8()..];
+                }
+                continue;
+            }
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            '{' => {
+                let word_len = rest
+                    .find(|c: char| !(c.is_ascii_lowercase() || c == '_'))
+                    .unwrap_or(rest.len());
+                if word_len > 0 && rest[word_len..].starts_with('}') && !rendered.ends_with('$') {
+                    let name = &rest[..word_len];
+                    let position = NOTIFY_PLACEHOLDERS
+                        .iter()
+                        .position(|p| *p == name)
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown placeholder {{{}}}; use {}",
+                                name,
+                                NOTIFY_PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+                            )
+                        })?
+                        + 1;
+                    match quote {
+                        None => rendered.push_str(&format!("\"${}\"", position)),
+                        Some('"') => rendered.push_str(&format!("${{{}}}", position)),
+                        Some(_) => {
+                            return Err(format!(
+                                "placeholder {{{}}} is inside single quotes, where it cannot expand; use double quotes",
+                                name
+                            ))
+                        }
+                    }
+                    rest = &rest[word_len + 1..];
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        rendered.push(c);
+    }
+    Ok(rendered)
+}
+
+/// Bandwidth limits and priority applied to a set's engine processes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Throttle {
+    pub upload_limit_kib: Option<u32>,
+    pub download_limit_kib: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+impl Throttle {
+    /// Fills each unset field from `fallback`.
+    pub fn or(self, fallback: Throttle) -> Throttle {
+        Throttle {
+            upload_limit_kib: self.upload_limit_kib.or(fallback.upload_limit_kib),
+            download_limit_kib: self.download_limit_kib.or(fallback.download_limit_kib),
+            priority: self.priority.or(fallback.priority),
+        }
+    }
+}
+
+/// Filesystem snapshot mechanism used to freeze a set's sources during backup.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotSource {
+    /// Read-only btrfs subvolume snapshot. Each source must be a subvolume.
+    Btrfs,
+    /// LVM snapshot of the logical volume holding each source, mounted read-only.
+    Lvm,
+}
+
+impl std::fmt::Display for SnapshotSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotSource::Btrfs => write!(f, "btrfs"),
+            SnapshotSource::Lvm => write!(f, "lvm"),
+        }
+    }
+}
+
+/// Classification of the data in a backup set.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Classification {
+    Personal,
+    Work,
+    Sensitive,
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Classification::Personal => write!(f, "personal"),
+            Classification::Work => write!(f, "work"),
+            Classification::Sensitive => write!(f, "sensitive"),
+        }
+    }
+}
+
+/// Where the engine reads a repository password from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordSource {
+    /// A file holding the password.
+    File(PathBuf),
+    /// A shell command that prints the password.
+    Command(String),
+}
+
+impl Default for PasswordSource {
+    /// The shared `~/.config/vigil/.repo_password`.
+    fn default() -> Self {
+        PasswordSource::File(crate::paths::password_path())
+    }
+}
+
+impl PasswordSource {
+    /// Engine arguments that pass this password. restic and rustic use the same flags.
+    pub fn args(&self) -> [String; 2] {
+        match self {
+            PasswordSource::File(path) => [
+                "--password-file".to_string(),
+                path.to_string_lossy().to_string(),
+            ],
+            PasswordSource::Command(command) => ["--password-command".to_string(), command.clone()],
+        }
+    }
+
+    /// Arguments passing this password for the source repository of
+    /// `restic copy` (`--from-repo`).
+    pub fn from_repo_args(&self) -> [String; 2] {
+        let [flag, value] = self.args();
+        [flag.replacen("--", "--from-", 1), value]
+    }
+
+    /// Whether this is the password file shared by all sets without their own.
+    pub fn is_shared_default(&self) -> bool {
+        *self == PasswordSource::default()
+    }
+}
+
+/// An exclude pattern in the one form both the file watcher and the engine
+/// understand, so a change the watcher reports is never one restic skips.
+///
+/// Semantics follow restic's `--exclude`: `*`, `?` and `[...]` match within a
+/// single path component, `**` matches any number of whole components, and a
+/// path is excluded when it or any of its parent directories matches. A pattern
+/// starting with `/` is anchored at the filesystem root; any other pattern may
+/// match at any depth, as if prefixed with `**/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludePattern {
+    pattern: String,
+}
+
+impl ExcludePattern {
+    /// Parses and normalizes a pattern, rejecting syntax the watcher's glob
+    /// matcher and restic would read differently: `{a,b}` alternation, `[!...]`
+    /// negation (restic only knows `[^...]`), and `**` inside a component.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        if raw.contains(['{', '}']) {
+            return Err("'{...}' alternation is not supported by restic".to_string());
+        }
+        if raw.contains("[!") {
+            return Err("use '[^...]' instead of '[!...]' to negate a class".to_string());
+        }
+
+        let anchored = raw.starts_with('/');
+        let mut components = Vec::new();
+        for component in raw.split('/') {
+            match component {
+                "" | "." => {}
+                c if c.contains("**") && c != "**" => {
+                    return Err("'**' must be a whole path component".to_string());
+                }
+                // Repeated `**` components match the same as one
+                "**" if components.last() == Some(&"**") => {}
+                c => components.push(c),
+            }
+        }
+        if components.is_empty() {
+            return Err("pattern is empty".to_string());
+        }
+
+        let joined = components.join("/");
+        Ok(ExcludePattern {
+            pattern: if anchored {
+                format!("/{}", joined)
+            } else {
+                joined
+            },
+        })
+    }
+
+    /// Anchored pattern matching exactly `path` (and everything below it), with
+    /// glob characters escaped. `None` for paths restic patterns cannot express.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let text = path.to_str()?;
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        Self::parse(&escaped).ok().filter(|p| p.is_anchored())
+    }
+
+    /// Whether the pattern only matches from the filesystem root.
+    pub fn is_anchored(&self) -> bool {
+        self.pattern.starts_with('/')
+    }
+
+    /// Value for restic's `--exclude`.
+    pub fn restic_arg(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Globs that together match the same paths, to be compiled with `*` not
+    /// crossing `/` and tested against a path and each of its parent directories.
+    ///
+    /// Negated classes get `/` added so they stay within one component, and a
+    /// trailing `/**` also yields the glob without it, since restic lets `**`
+    /// match zero components there.
+    pub fn globs(&self) -> Vec<String> {
+        let pattern = self.pattern.replace("[^", "[^/");
+        let glob = if self.is_anchored() {
+            pattern
+        } else {
+            format!("**/{}", pattern)
+        };
+        match glob.strip_suffix("/**") {
+            Some(parent) if !parent.is_empty() => vec![parent.to_string(), glob],
+            _ => vec![glob],
+        }
+    }
+}
+
+/// Signature every `CACHEDIR.TAG` file starts with (<https://bford.info/cachedir/>).
+pub const CACHEDIR_TAG_SIGNATURE: &str = "Signature: 8a477f597d28d172789f06886806bc55";
+
+/// A file whose presence excludes the directory containing it, with restic
+/// `--exclude-if-present` semantics: with a header, the file only counts when
+/// its content starts with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludeMarker {
+    pub file: String,
+    pub header: Option<String>,
+}
+
+impl ExcludeMarker {
+    /// Parses `name` or `name:header`.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let (file, header) = match raw.split_once(':') {
+            Some((file, header)) => (file, Some(header.to_string())),
+            None => (raw, None),
+        };
+        if file.is_empty() {
+            return Err("file name is empty".to_string());
+        }
+        if file.contains('/') {
+            return Err("must be a file name, not a path".to_string());
+        }
+        Ok(Self {
+            file: file.to_string(),
+            header,
+        })
+    }
+
+    /// The marker restic's `--exclude-caches` looks for.
+    pub fn cachedir_tag() -> Self {
+        Self {
+            file: "CACHEDIR.TAG".to_string(),
+            header: Some(CACHEDIR_TAG_SIGNATURE.to_string()),
+        }
+    }
+
+    /// Value for restic's `--exclude-if-present`.
+    pub fn restic_arg(&self) -> String {
+        match self.header {
+            Some(ref header) => format!("{}:{}", self.file, header),
+            None => self.file.clone(),
+        }
+    }
+}
+
+/// When a set is backed up regardless of file changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// A fixed time after the previous backup.
+    Every(std::time::Duration),
+    /// The times matched by a cron expression, evaluated in local time.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// Parses an interval (`"30m"`, `"6h"`, `"1d"`, `"1h30m"`) or a cron
+    /// expression. Five-field expressions get an implicit `0` seconds field.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.split_whitespace().count() > 1 {
+            let expr = if text.split_whitespace().count() == 5 {
+                format!("0 {}", text)
+            } else {
+                text.to_string()
+            };
+            return expr
+                .parse::<cron::Schedule>()
+                .map(|schedule| Schedule::Cron(Box::new(schedule)))
+                .map_err(|e| format!("not a valid cron expression ({})", e));
+        }
+        parse_interval(text).map(Schedule::Every)
+    }
+
+    /// First run strictly after `time`, or `None` if the expression never matches again.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => Some(time + chrono::Duration::from_std(*interval).ok()?),
+            Schedule::Cron(schedule) => schedule
+                .after(&time.with_timezone(&Local))
+                .next()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// A daily range of local time, e.g. `02:00-06:00`. A range whose end is
+/// before its start spans midnight (`22:00-04:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl MaintenanceWindow {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (start, end) = text
+            .split_once('-')
+            .ok_or_else(|| "expected a range such as \"02:00-06:00\"".to_string())?;
+        let time = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .map_err(|_| format!("'{}' is not a time of day (HH:MM)", t.trim()))
+        };
+        let window = Self {
+            start: time(start)?,
+            end: time(end)?,
+        };
+        if window.start == window.end {
+            return Err("start and end must differ".to_string());
+        }
+        Ok(window)
+    }
+
+    /// Whether `time` falls inside the window. The end is exclusive.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// How long from `time` until the window next opens; zero inside it.
+    pub fn until_open(&self, time: NaiveTime) -> chrono::Duration {
+        if self.contains(time) {
+            return chrono::Duration::zero();
+        }
+        let delta = self.start - time;
+        if delta < chrono::Duration::zero() {
+            delta + chrono::Duration::days(1)
+        } else {
+            delta
+        }
+    }
+
+    /// When the window next opens, in local time; `now` inside it.
+    pub fn next_open(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + self.until_open(now.with_timezone(&Local).time())
+    }
+}
+
+/// Parses a run of `<number><unit>` pairs with units `s`, `m`, `h` and `d`.
+pub fn parse_interval(text: &str) -> Result<std::time::Duration, String> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(format!("unknown unit '{}' (use s, m, h or d)", c)),
+        };
+        let value: u64 = digits
+            .parse()
+            .map_err(|_| format!("expected a number before '{}'", c))?;
+        total = total.saturating_add(value.saturating_mul(unit));
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err("missing unit after the last number (use s, m, h or d)".to_string());
+    }
+    if total == 0 {
+        return Err("interval must be longer than zero".to_string());
+    }
+    Ok(std::time::Duration::from_secs(total))
+}
+
+/// Storage backend of a repository, derived from the scheme prefix of its target.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Local,
+    Sftp,
+    Rest,
+    S3,
+    B2,
+    Azure,
+    Gs,
+    Swift,
+    Rclone,
+}
+
+/// Restic backend prefixes for repositories that are not plain local paths.
+const REMOTE_BACKENDS: &[(&str, Backend)] = &[
+    ("sftp:", Backend::Sftp),
+    ("rest:", Backend::Rest),
+    ("s3:", Backend::S3),
+    ("b2:", Backend::B2),
+    ("azure:", Backend::Azure),
+    ("gs:", Backend::Gs),
+    ("swift:", Backend::Swift),
+    ("rclone:", Backend::Rclone),
+];
+
+impl Backend {
+    /// Parses the backend from a repository target. Targets without a known
+    /// scheme prefix are local paths.
+    pub fn of(target: &str) -> Self {
+        REMOTE_BACKENDS
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix))
+            .map(|(_, backend)| *backend)
+            .unwrap_or(Backend::Local)
+    }
+
+    /// Whether the repository lives somewhere other than the local filesystem.
+    /// Its size cannot be measured and it cannot be deleted with file operations.
+    pub fn is_remote(self) -> bool {
+        self != Backend::Local
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Backend::Local => "local",
+            Backend::Sftp => "sftp",
+            Backend::Rest => "rest",
+            Backend::S3 => "s3",
+            Backend::B2 => "b2",
+            Backend::Azure => "azure",
+            Backend::Gs => "gs",
+            Backend::Swift => "swift",
+            Backend::Rclone => "rclone",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Prefixes of the environment variables restic and rustic read their
+/// repository, password and options from.
+const ENGINE_ENV_PREFIXES: &[&str] = &["RESTIC_", "RUSTIC_"];
+
+fn is_engine_env(name: &str) -> bool {
+    ENGINE_ENV_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// Names of engine variables in this process's environment that are not in
+/// `passthrough` and must be removed before spawning restic or rustic, so a
+/// stray `RESTIC_REPOSITORY` or `RESTIC_PASSWORD` never reaches the engine.
+pub fn blocked_engine_env(passthrough: &[String]) -> Vec<String> {
+    std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| is_engine_env(name) && !passthrough.contains(name))
+        .collect()
+}
+
+/// Command for a restic or rustic process with `env` set. Inherited
+/// `RESTIC_*`/`RUSTIC_*` variables outside `passthrough` are removed first.
+pub fn engine_command(
+    program: &str,
+    env: &[(String, String)],
+    passthrough: &[String],
+) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new(program);
+    for name in blocked_engine_env(passthrough) {
+        cmd.env_remove(name);
+    }
+    cmd.envs(env.iter().cloned());
+    cmd
+}
+
+/// Everything set in the environment of an engine process, in increasing
+/// precedence: `global` (the global `env_file` and `env`), the variables of a
+/// set's `credentials_file`, then `env` (the set's `env_file` and `env`).
+pub fn engine_process_env(
+    global: &EngineEnv,
+    credentials_file: Option<&Path>,
+    env: &EngineEnv,
+) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut vars = global.load()?;
+    if let Some(file) = credentials_file {
+        vars.extend(load_env_file(file).map_err(|e| match e {
+            ConfigError::Io(e) => {
+                ConfigError::Validation(format!("cannot read credentials file {:?}: {}", file, e))
+            }
+            e => e,
+        })?);
+    }
+    vars.extend(env.load()?);
+    Ok(vars)
+}
+
+/// Whether vigil sets `name` itself: the engines' repository and password
+/// variables (also the `FROM_` ones of `restic copy`), which an `env` table
+/// or `env_file` must not override.
+fn is_managed_env(name: &str) -> bool {
+    const MANAGED: &[&str] = &[
+        "REPOSITORY",
+        "REPOSITORY_FILE",
+        "PASSWORD",
+        "PASSWORD_FILE",
+        "PASSWORD_COMMAND",
+    ];
+    ENGINE_ENV_PREFIXES
+        .iter()
+        .filter_map(|p| name.strip_prefix(p))
+        .map(|rest| rest.strip_prefix("FROM_").unwrap_or(rest))
+        .any(|rest| MANAGED.contains(&rest))
+}
+
+/// Checks that `name` can be set through an `env` table or `env_file`.
+pub fn validate_env_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("'{}' is not a valid variable name", name));
+    }
+    if is_managed_env(name) {
+        return Err(format!(
+            "'{}' is set by vigil from the set's target and password settings",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Extra environment of engine processes: the variables of an `env_file`,
+/// then an `env` table. The file is read on every [`EngineEnv::load`], so
+/// edits to it apply without a reload.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineEnv {
+    pub env_file: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+}
+
+impl EngineEnv {
+    fn new(env_file: Option<&str>, env: &BTreeMap<String, String>) -> Self {
+        Self {
+            env_file: env_file.map(PathBuf::from),
+            env: env.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+
+    /// The variables to set, with those of `env` last so they win.
+    pub fn load(&self) -> Result<Vec<(String, String)>, ConfigError> {
+        self.try_load().map_err(ConfigError::Validation)
+    }
+
+    fn try_load(&self) -> Result<Vec<(String, String)>, String> {
+        let mut vars = match self.env_file {
+            Some(ref file) => {
+                let content = std::fs::read_to_string(file)
+                    .map_err(|e| format!("cannot read env_file {:?}: {}", file, e))?;
+                let vars = parse_env_file(&content)
+                    .map_err(|e| format!("invalid env_file {:?}: {}", file, e))?;
+                for (name, _) in &vars {
+                    validate_env_name(name)
+                        .map_err(|e| format!("invalid env_file {:?}: {}", file, e))?;
+                }
+                vars
+            }
+            None => Vec::new(),
+        };
+        vars.extend(self.env.iter().cloned());
+        Ok(vars)
+    }
+}
+
+/// `NAME=value` pairs for a log line, with the values of variables that look
+/// like secrets (keys, tokens, passwords, credentials) masked.
+pub fn masked_env(vars: &[(String, String)]) -> String {
+    const SECRET_WORDS: &[&str] = &["KEY", "SECRET", "TOKEN", "PASS", "CREDENTIAL", "AUTH"];
+    vars.iter()
+        .map(|(name, value)| {
+            let upper = name.to_ascii_uppercase();
+            if SECRET_WORDS.iter().any(|w| upper.contains(w)) {
+                format!("{}=***", name)
+            } else {
+                format!("{}={}", name, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reads a credentials file of `KEY=VALUE` lines. Blank lines and `#` comments
+/// are skipped, a leading `export ` is allowed, and values may be quoted.
+pub fn load_env_file(path: &Path) -> Result<Vec<(String, String)>, ConfigError> {
+    let content = std::fs::read_to_string(path)?;
+    parse_env_file(&content)
+        .map_err(|e| ConfigError::Validation(format!("Invalid credentials file {:?}: {}", path, e)))
+}
+
+fn parse_env_file(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {} is not KEY=VALUE", i + 1))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("line {} has an invalid variable name", i + 1));
+        }
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+            .unwrap_or(value);
+        vars.push((key.to_string(), value.to_string()));
+    }
+    Ok(vars)
+}
+
+/// Retention policy defining how many snapshots to keep.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Number of most recent snapshots to keep.
+    pub keep_last: Option<u32>,
+    /// Number of daily snapshots to keep.
+    pub keep_daily: Option<u32>,
+    /// Number of weekly snapshots to keep.
+    pub keep_weekly: Option<u32>,
+    /// Number of monthly snapshots to keep.
+    pub keep_monthly: Option<u32>,
+    /// Number of yearly snapshots to keep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_yearly: Option<u32>,
+    /// Keep every snapshot taken within this long of the newest one, as a
+    /// restic duration such as `30d` or `1y6m` (units `y`, `m`, `d`, `h`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_within: Option<String>,
+    /// Keep every snapshot carrying one of these tags, whatever the other rules say.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep_tag: Vec<String>,
+    /// Apply the keep rules to each combination of tags separately, so a rarely
+    /// used tag keeps its own history instead of being crowded out.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub per_tag: bool,
+}
+
+impl RetentionPolicy {
+    /// Parses a comma-separated policy such as `"last=10,daily=7,weekly=4"`.
+    /// Keys are `last`, `daily`, `weekly`, `monthly` and `yearly` with counts,
+    /// `within` with a duration and `tag` with a tag name (repeatable); a bare
+    /// `per_tag` sets that flag. At least one keep rule is required.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut policy = Self::default();
+        for part in text.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            if part == "per_tag" {
+                policy.per_tag = true;
+                continue;
+            }
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=count, got '{}'", part))?;
+            match key.trim() {
+                "within" => {
+                    policy.keep_within = Some(value.trim().to_string());
+                    continue;
+                }
+                "tag" => {
+                    policy.keep_tag.push(value.trim().to_string());
+                    continue;
+                }
+                _ => {}
+            }
+            let count = value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("'{}' is not a count", value.trim()))?;
+            let slot = match key.trim() {
+                "last" => &mut policy.keep_last,
+                "daily" => &mut policy.keep_daily,
+                "weekly" => &mut policy.keep_weekly,
+                "monthly" => &mut policy.keep_monthly,
+                "yearly" => &mut policy.keep_yearly,
+                other => {
+                    return Err(format!(
+                        "unknown rule '{}' (expected last, daily, weekly, monthly, yearly, within or tag)",
+                        other
+                    ))
+                }
+            };
+            *slot = Some(count);
+        }
+        if !policy.has_keep_rules() {
+            return Err(
+                "at least one of last, daily, weekly, monthly, yearly or within is required"
+                    .to_string(),
+            );
+        }
+        policy.check()?;
+        Ok(policy)
+    }
+
+    /// Checks the `keep_within` duration and `keep_tag` names.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(ref within) = self.keep_within {
+            validate_keep_within(within)
+                .map_err(|e| format!("invalid keep_within '{}': {}", within, e))?;
+        }
+        for tag in &self.keep_tag {
+            validate_tag(tag).map_err(|e| format!("invalid keep_tag '{}': {}", tag, e))?;
+        }
+        Ok(())
+    }
+
+    fn keep_rules(&self) -> [(&'static str, Option<u32>); 5] {
+        [
+            ("last", self.keep_last),
+            ("daily", self.keep_daily),
+            ("weekly", self.keep_weekly),
+            ("monthly", self.keep_monthly),
+            ("yearly", self.keep_yearly),
+        ]
+    }
+
+    /// Whether the policy has a keep rule. Without one, a prune would delete
+    /// every snapshot, so vigil refuses to run it. `keep_tag` alone does not
+    /// count: a repository without the tag would be emptied.
+    pub fn has_keep_rules(&self) -> bool {
+        self.keep_within.is_some() || self.keep_rules().iter().any(|(_, count)| count.is_some())
+    }
+
+    /// The most snapshots the policy can keep per group: the sum of its keep
+    /// rules, reached when no snapshot satisfies two rules at once. `None`
+    /// without keep rules, or when `keep_within` or `keep_tag` keep an
+    /// unbounded number.
+    pub fn max_kept(&self) -> Option<u64> {
+        if !self.has_keep_rules() || self.keep_within.is_some() || !self.keep_tag.is_empty() {
+            return None;
+        }
+        Some(
+            self.keep_rules()
+                .iter()
+                .filter_map(|(_, count)| count.map(u64::from))
+                .sum(),
+        )
+    }
+
+    /// The policy in the form [`RetentionPolicy::parse`] reads, e.g.
+    /// `last=10,daily=7,within=30d,per_tag`.
+    pub fn rules(&self) -> String {
+        let mut parts: Vec<String> = self
+            .keep_rules()
+            .iter()
+            .filter_map(|(key, count)| count.map(|count| format!("{}={}", key, count)))
+            .collect();
+        if let Some(ref within) = self.keep_within {
+            parts.push(format!("within={}", within));
+        }
+        parts.extend(self.keep_tag.iter().map(|tag| format!("tag={}", tag)));
+        if self.per_tag {
+            parts.push("per_tag".to_string());
+        }
+        parts.join(",")
+    }
+}
+
+/// The paths of a `files_from` list as restic reads them: one per line,
+/// trimmed, without empty lines and `#` comments.
+pub fn parse_file_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Patterns of an `exclude_file` as restic's `--exclude-file` reads them: one
+/// per line like a `files_from` list. Negated (`!pattern`) lines and `$VAR`
+/// references are refused, since the watcher cannot match them the way restic
+/// does.
+pub fn parse_exclude_file(content: &str) -> Result<Vec<ExcludePattern>, String> {
+    parse_file_list(content)
+        .iter()
+        .map(|line| {
+            if line.starts_with('!') {
+                return Err(format!("'{}': negated patterns are not supported", line));
+            }
+            if line.contains('$') {
+                return Err(format!(
+                    "'{}': environment variables are not supported",
+                    line
+                ));
+            }
+            ExcludePattern::parse(line).map_err(|e| format!("'{}': {}", line, e))
+        })
+        .collect()
+}
+
+/// Checks a restic retention duration such as `30d` or `1y6m2h`: a run of
+/// `<number><unit>` pairs with units `y`, `m` (months), `d` and `h`.
+pub fn validate_keep_within(text: &str) -> Result<(), String> {
+    let mut digits = String::new();
+    let mut total = 0u64;
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if !matches!(c, 'y' | 'm' | 'd' | 'h') {
+            return Err(format!("unknown unit '{}' (use y, m, d or h)", c));
+        }
+        let value: u64 = digits
+            .parse()
+            .map_err(|_| format!("expected a number before '{}'", c))?;
+        total = total.saturating_add(value);
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err("missing unit after the last number (use y, m, d or h)".to_string());
+    }
+    if total == 0 {
+        return Err("duration must be longer than zero".to_string());
+    }
+    Ok(())
+}
+
+/// Prefix of the tag recording a set's `logical_path` in its snapshots.
+pub const LOGICAL_PATH_TAG_PREFIX: &str = "vigil-path:";
+
+/// Checks that `tag` can be passed to restic as a single tag. restic splits
+/// `--tag` values on commas.
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.trim().is_empty() {
+        return Err("tags cannot be empty".to_string());
+    }
+    if tag.contains(',') {
+        return Err("tags cannot contain ','".to_string());
+    }
+    if tag.starts_with('-') {
+        return Err("tags cannot start with '-'".to_string());
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Where `key` lives in the configuration's JSON form.
+    fn setting_pointer(&self, key: &SettingKey) -> Result<Vec<String>, ConfigError> {
+        let mut pointer = match &key.set {
+            None => vec!["global".to_string()],
+            Some(name) => {
+                let index = self
+                    .backup_sets
+                    .iter()
+                    .position(|s| &s.name == name)
+                    .ok_or_else(|| {
+                        ConfigError::Validation(format!("Backup set '{}' not found", name))
+                    })?;
+                vec!["backup_set".to_string(), index.to_string()]
+            }
+        };
+        pointer.extend(key.path.iter().cloned());
+        Ok(pointer)
+    }
+
+    fn to_json(&self) -> Result<serde_json::Value, ConfigError> {
+        serde_json::to_value(self)
+            .map_err(|e| ConfigError::Validation(format!("Failed to serialize config: {}", e)))
+    }
+
+    /// The value of `key`, including defaults. `None` when it is not set.
+    pub fn get_setting(&self, key: &SettingKey) -> Result<Option<serde_json::Value>, ConfigError> {
+        let mut value = &self.to_json()?;
+        for segment in self.setting_pointer(key)? {
+            value = match value {
+                serde_json::Value::Object(map) => match map.get(&segment) {
+                    Some(value) => value,
+                    None => return Ok(None),
+                },
+                serde_json::Value::Array(items) => {
+                    match segment.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(value) => value,
+                        None => return Ok(None),
+                    }
+                }
+                _ => return Ok(None),
+            };
+        }
+        Ok((!value.is_null()).then(|| strip_nulls(value)))
+    }
+
+    /// The value for a `ConfigOp::Set` of `key` to `raw` from the command
+    /// line, parsed with [`SettingKey::parse_value`] and checked against this
+    /// configuration. A value that does not fit the setting is retried as a
+    /// string, so `target = 2024` needs no quoting.
+    pub fn setting_value(&self, key: &str, raw: &str) -> Result<serde_json::Value, ConfigError> {
+        let parsed = SettingKey::parse(key).map_err(ConfigError::Validation)?;
+        let value = parsed.parse_value(raw).map_err(|e| {
+            ConfigError::Validation(format!("Invalid value for '{}': {}", parsed, e))
+        })?;
+        let fits = |value: &serde_json::Value| self.clone().set_setting(key, value);
+        match fits(&value) {
+            Ok(()) => Ok(value),
+            Err(e) if value.is_string() => Err(e),
+            Err(e) => {
+                let text = serde_json::Value::from(raw);
+                fits(&text).map(|()| text).map_err(|_| e)
+            }
+        }
+    }
+
+    /// Sets `key` to `value`, or removes it for null. Fails for a value of
+    /// the wrong type and for a key that names no setting.
+    fn set_setting(&mut self, key: &str, value: &serde_json::Value) -> Result<(), ConfigError> {
+        let key = SettingKey::parse(key).map_err(ConfigError::Validation)?;
+        let pointer = self.setting_pointer(&key)?;
+        let invalid = || ConfigError::Validation(format!("Invalid key '{}'", key));
+        let mut json = self.to_json()?;
+        let mut current = &mut json;
+        for segment in &pointer[..pointer.len() - 1] {
+            if current.is_null() {
+                *current = serde_json::json!({});
+            }
+            current = match current {
+                serde_json::Value::Array(items) => {
+                    segment.parse::<usize>().ok().and_then(|i| items.get_mut(i))
+                }
+                serde_json::Value::Object(map) => Some(
+                    map.entry(segment.clone())
+                        .or_insert(serde_json::Value::Null),
+                ),
+                _ => None,
+            }
+            .ok_or_else(invalid)?;
+        }
+        if current.is_null() {
+            *current = serde_json::json!({});
+        }
+        current
+            .as_object_mut()
+            .ok_or_else(invalid)?
+            .insert(key.field().to_string(), value.clone());
+        let updated: Config = serde_json::from_value(json)
+            .map_err(|e| ConfigError::Validation(format!("Invalid value for '{}': {}", key, e)))?;
+        // Unknown fields are ignored when deserializing, so check the value arrived
+        if !value.is_null() && updated.get_setting(&key)? != Some(strip_nulls(value)) {
+            return Err(ConfigError::Validation(format!(
+                "Unknown setting '{}'",
+                key
+            )));
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    /// Validates and expands paths. With `global.strict`, overlapping sets
+    /// are refused.
+    pub fn validate(&mut self) -> Result<(), ConfigError> {
+        self.check_validity()?;
+        self.expand_home_paths();
+        if self.global.strict {
+            let overlaps = self.path_overlaps();
+            if !overlaps.is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "{} (global.strict is set)",
+                    overlaps.join("; ")
+                )));
+            }
+        }
+        self.check_list_files()
+    }
+
+    /// Checks that each set's `files_from` and `exclude_file` can be read and
+    /// that the exclude file holds valid patterns, and that the `env_file`s
+    /// hold valid variables. Runs after `~/` expansion.
+    fn check_list_files(&self) -> Result<(), ConfigError> {
+        self.global
+            .engine_env()
+            .try_load()
+            .map_err(|e| ConfigError::Validation(format!("Global settings: {}", e)))?;
+        for set in &self.backup_sets {
+            set.engine_env()
+                .try_load()
+                .map_err(|e| ConfigError::Validation(format!("Set '{}': {}", set.name, e)))?;
+            if let Some(ref list) = set.files_from {
+                std::fs::read_to_string(list).map_err(|e| {
+                    ConfigError::Validation(format!(
+                        "Set '{}' cannot read its files_from list '{}': {}",
+                        set.name, list, e
+                    ))
+                })?;
+            }
+            set.exclude_file_patterns().map_err(|e| {
+                ConfigError::Validation(format!("Set '{}' has an invalid {}", set.name, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Applies edits in order. Stops at the first edit that cannot be applied.
+    pub fn apply(&mut self, ops: &[ConfigOp]) -> Result<(), ConfigError> {
+        for op in ops {
+            match op {
+                ConfigOp::AddSet { set } => {
+                    if self.backup_sets.iter().any(|s| s.name == set.name) {
+                        return Err(ConfigError::Validation(format!(
+                            "Backup set '{}' already exists",
+                            set.name
+                        )));
+                    }
+                    self.backup_sets.push((**set).clone());
+                }
+                ConfigOp::SetPasswordFile {
+                    name,
+                    password_file,
+                } => {
+                    let set = self
+                        .backup_sets
+                        .iter_mut()
+                        .find(|s| &s.name == name)
+                        .ok_or_else(|| {
+                            ConfigError::Validation(format!("Backup set '{}' not found", name))
+                        })?;
+                    set.password_file = Some(password_file.clone());
+                    set.password_command = None;
+                }
+                ConfigOp::RemoveSet { name } => {
+                    let index = self
+                        .backup_sets
+                        .iter()
+                        .position(|s| &s.name == name)
+                        .ok_or_else(|| {
+                            ConfigError::Validation(format!("Backup set '{}' not found", name))
+                        })?;
+                    self.backup_sets.remove(index);
+                }
+                ConfigOp::Set { key, value } => self.set_setting(key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn expand_home(path: &str) -> String {
+    if path.starts_with("~/") {
+        if let Some(home) = directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()) {
+            return path.replacen("~", &home.to_string_lossy(), 1);
+        }
+    }
+    path.to_string()
+}
+
+/// Loads the configuration from the environment variable `VIGIL_CONFIG`
+/// or the default system location (`~/.config/vigil/config.toml`).
+///
+/// # Errors
+///
+/// Returns `ConfigError` if the file cannot be found, read, or parsed,
+/// or if validation fails.
+pub fn load_config() -> Result<Config, ConfigError> {
+    let mut config = load_config_raw()?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Loads the configuration without expansion or validation.
+pub fn load_config_raw() -> Result<Config, ConfigError> {
+    let path = crate::paths::active_config_path();
+
+    if !path.exists() {
+        return Err(ConfigError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Config file not found: {:?}", path),
+        )));
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let config: Config = toml::from_str(&content)?;
+    Ok(config)
+}
+
+/// Parses and validates the text of a configuration file, returning the
+/// configuration with paths expanded.
+pub fn parse_config_text(content: &str) -> Result<Config, ConfigError> {
+    let mut config: Config = toml::from_str(content)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Replaces the configuration file with `content` after validating it,
+/// keeping it exactly as written.
+pub fn replace_config_text(content: &str) -> Result<Config, ConfigError> {
+    let config = parse_config_text(content)?;
+    write_config_file(content)?;
+    Ok(config)
+}
+
+/// Saves the configuration to the active config path.
+pub fn save_config(config: &Config) -> Result<(), ConfigError> {
+    config.check_validity()?;
+    write_config_file(&render_config(config)?)
+}
+
+fn render_config(config: &Config) -> Result<String, ConfigError> {
+    toml::to_string_pretty(config)
+        .map_err(|e| ConfigError::Validation(format!("Failed to serialize config: {}", e)))
+}
+
+/// Writes `content` to the active config path.
+fn write_config_file(content: &str) -> Result<(), ConfigError> {
+    let path = crate::paths::active_config_path();
+
+    // Create parent directory if it doesn't exist
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Write to a sibling file and rename so readers never see a partial config
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// `value` as a TOML item, with nested tables inline. Nulls are dropped,
+/// as TOML has none.
+fn toml_item(value: &serde_json::Value) -> Option<toml_edit::Item> {
+    let wrapped = serde_json::json!({ "value": strip_nulls(value) });
+    let rendered: toml_edit::DocumentMut = toml::to_string(&wrapped).ok()?.parse().ok()?;
+    let mut item = rendered.get("value")?.clone();
+    if item.is_table() {
+        item.make_value();
+    }
+    Some(item)
+}
+
+/// Applies `ops` to the text of a configuration file, keeping its comments
+/// and layout. `None` when an edit cannot be made in place.
+fn edit_document(original: &str, ops: &[ConfigOp]) -> Option<String> {
+    use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike};
+
+    fn set_table<'a>(doc: &'a mut DocumentMut, name: &str) -> Option<&'a mut Table> {
+        doc.get_mut("backup_set")?
+            .as_array_of_tables_mut()?
+            .iter_mut()
+            .find(|table| table.get("name").and_then(Item::as_str) == Some(name))
+    }
+
+    let mut doc: DocumentMut = original.parse().ok()?;
+    for op in ops {
+        match op {
+            ConfigOp::AddSet { set } => {
+                let rendered: DocumentMut = toml::to_string(set).ok()?.parse().ok()?;
+                let mut table = rendered.as_table().clone();
+                // Nested settings such as `retention` as inline tables, so they
+                // stay with the new set instead of sorting before it
+                for (_, item) in table.iter_mut() {
+                    if item.is_table() {
+                        item.make_value();
+                    }
+                }
+                doc.entry("backup_set")
+                    .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+                    .as_array_of_tables_mut()?
+                    .push(table);
+            }
+            ConfigOp::RemoveSet { name } => {
+                let sets = doc.get_mut("backup_set")?.as_array_of_tables_mut()?;
+                let index = sets
+                    .iter()
+                    .position(|table| table.get("name").and_then(Item::as_str) == Some(name))?;
+                sets.remove(index);
+            }
+            ConfigOp::SetPasswordFile {
+                name,
+                password_file,
+            } => {
+                let table = set_table(&mut doc, name)?;
+                table["password_file"] = toml_edit::value(password_file.as_str());
+                table.remove("password_command");
+            }
+            ConfigOp::Set { key, value } => {
+                let key = SettingKey::parse(key).ok()?;
+                let mut current: &mut dyn TableLike = match &key.set {
+                    Some(name) => set_table(&mut doc, name)?,
+                    None => doc
+                        .entry("global")
+                        .or_insert(Item::Table(Table::new()))
+                        .as_table_like_mut()?,
+                };
+                // Descend through existing tables; the rest of the path is
+                // created in one piece as (nested) inline tables
+                let mut depth = 0;
+                while depth + 1 < key.path.len() && current.contains_key(&key.path[depth]) {
+                    current = current.get_mut(&key.path[depth])?.as_table_like_mut()?;
+                    depth += 1;
+                }
+                let value = key.path[depth + 1..].iter().rev().fold(
+                    value.clone(),
+                    |inner, segment| serde_json::json!({ segment.as_str(): inner }),
+                );
+                let field = key.path[depth].as_str();
+                if value.is_null() {
+                    current.remove(field);
+                    continue;
+                }
+                let mut item = toml_item(&value)?;
+                // Keep an end-of-line comment on the replaced value
+                if let (Some(Item::Value(old)), Item::Value(new)) = (current.get(field), &mut item)
+                {
+                    *new.decor_mut() = old.decor().clone();
+                }
+                current.insert(field, item);
+            }
+        }
+    }
+    Some(doc.to_string())
+}
+
+/// Whether `text` parses to exactly `config`.
+fn describes(text: &str, config: &Config) -> bool {
+    toml::from_str::<Config>(text)
+        .is_ok_and(|parsed| serde_json::to_value(&parsed).ok() == serde_json::to_value(config).ok())
+}
+
+/// Loads the configuration file, applies `ops`, and saves it atomically.
+///
+/// The file is edited in place, so comments and layout outside the edited
+/// sets survive. If that does not reproduce the edited configuration exactly,
+/// the whole file is rewritten instead. A missing file starts from an empty
+/// configuration. Edits within one process are serialized, so concurrent
+/// callers never overwrite each other's changes. Returns the updated
+/// configuration, validated and with paths expanded.
+pub fn modify_config(ops: &[ConfigOp]) -> Result<Config, ConfigError> {
+    static EDIT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let original = match std::fs::read_to_string(crate::paths::active_config_path()) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut config = match original {
+        Some(ref text) => toml::from_str(text)?,
+        None => Config {
+            global: GlobalConfig::default(),
+            backup_sets: Vec::new(),
+        },
+    };
+    config.apply(ops)?;
+    config.check_validity()?;
+    let content = match original
+        .as_deref()
+        .and_then(|text| edit_document(text, ops))
+        .filter(|text| describes(text, &config))
+    {
+        Some(text) => text,
+        None => render_config(&config)?,
+    };
+    write_config_file(&content)?;
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    #[serial]
     fn test_valid_config() {
         let mut file = NamedTempFile::new().unwrap();
         writeln!(
             file,
             r#"
 [global]
-debounce_seconds = 30
+debounce_seconds = 30
+
+[[backup_set]]
+name = "test"
+source = "~/test"
+target = "/tmp/backup"
+"#
+        )
+        .unwrap();
+
+        std::env::set_var("VIGIL_CONFIG", file.path());
+        let config = load_config().unwrap();
+        assert_eq!(config.global.debounce_seconds, 30);
+        assert_eq!(config.backup_sets.len(), 1);
+        assert_eq!(config.backup_sets[0].name, "test");
+    }
+
+    #[test]
+    fn test_path_expansion() {
+        let home = directories::BaseDirs::new()
+            .unwrap()
+            .home_dir()
+            .to_path_buf();
+        let home_str = home.to_string_lossy();
+
+        let set = BackupSet {
+            name: "test".to_string(),
+            source: Some("~/test".to_string()),
+            sources: None,
+            target: "~/backup".to_string(),
+            exclude: None,
+            debounce_seconds: None,
+            retention: None,
+            ..Default::default()
+        };
+
+        let mut config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![set],
+        };
+
+        config.validate().unwrap();
+
+        assert_eq!(
+            config.backup_sets[0].source.as_ref().unwrap(),
+            &format!("{}/test", home_str)
+        );
+        assert_eq!(config.backup_sets[0].target, format!("{}/backup", home_str));
+    }
+
+    #[test]
+    fn test_mutually_exclusive_sources() {
+        let config_str = r#"
+[global]
+debounce_seconds = 60
+
+[[backup_set]]
+name = "test"
+source = "~/test"
+sources = ["~/test1", "~/test2"]
+target = "/tmp/backup"
+"#;
+        let mut config: Config = toml::from_str(config_str).unwrap();
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("cannot have both 'source' and 'sources'"));
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let config_str = r#"
+[global]
+debounce_seconds = 60
+
+[[backup_set]]
+name = "dup"
+source = "~/test1"
+target = "/tmp/backup1"
+
+[[backup_set]]
+name = "dup"
+source = "~/test2"
+target = "/tmp/backup2"
+"#;
+        let mut config: Config = toml::from_str(config_str).unwrap();
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Duplicate backup set name"));
+    }
+
+    #[test]
+    fn test_config_serialization() {
+        let set = BackupSet {
+            name: "test".to_string(),
+            source: Some("~/test".to_string()),
+            sources: None,
+            target: "~/backup".to_string(),
+            exclude: Some(vec!["*.tmp".to_string()]),
+            debounce_seconds: Some(30),
+            retention: Some(RetentionPolicy {
+                keep_last: Some(5),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![set],
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains("\"name\":\"test\""));
+        assert!(json.contains("\"target\":\"~/backup\""));
+    }
+
+    #[test]
+    fn test_shared_repo_flag() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+
+[[backup_set]]
+name = "laptop"
+source = "/home/user"
+target = "/mnt/nas/repo"
+shared_repo = true
+
+[[backup_set]]
+name = "local"
+source = "/home/user/docs"
+target = "/tmp/repo"
+"#,
+        )
+        .unwrap();
+        assert!(config.backup_sets[0].shared_repo);
+        assert!(!config.backup_sets[1].shared_repo);
+
+        // The default is omitted when saving so existing configs stay unchanged.
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(serialized.matches("shared_repo").count(), 1);
+    }
+
+    #[test]
+    fn test_sensitive_classification_requires_remote_target() {
+        let parse = |target: &str, class: &str| -> Result<(), ConfigError> {
+            let config: Config = toml::from_str(&format!(
+                "[global]\n\n[[backup_set]]\nname = \"work\"\nsource = \"/home/user/work\"\ntarget = \"{}\"\nclassification = \"{}\"\n",
+                target, class
+            ))
+            .unwrap();
+            config.check_validity()
+        };
+
+        assert!(parse("/mnt/backup/work", "work").is_ok());
+        assert!(parse("sftp:user@host:/srv/restic", "sensitive").is_ok());
+        let err = parse("/mnt/backup/work", "sensitive").unwrap_err();
+        assert!(err.to_string().contains("remote repository"));
+
+        assert!(toml::from_str::<Config>(
+            "[global]\n\n[[backup_set]]\nname = \"x\"\nsource = \"/a\"\ntarget = \"/b\"\nclassification = \"secret\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_backend_from_target() {
+        assert_eq!(Backend::of("/mnt/backup/work"), Backend::Local);
+        assert_eq!(Backend::of("~/backup"), Backend::Local);
+        assert_eq!(Backend::of("sftp:user@host:/srv/restic"), Backend::Sftp);
+        assert_eq!(Backend::of("s3:s3.amazonaws.com/bucket"), Backend::S3);
+        assert_eq!(Backend::of("rest:https://host:8000/"), Backend::Rest);
+        assert!(!Backend::Local.is_remote());
+        assert!(Backend::Rest.is_remote());
+        assert_eq!(Backend::S3.to_string(), "s3");
+
+        let parse = |target: &str| -> Result<(), ConfigError> {
+            let config: Config = toml::from_str(&format!(
+                "[global]\n\n[[backup_set]]\nname = \"w\"\nsource = \"/a\"\ntarget = \"{}\"\ncredentials_file = \"/etc/creds\"\n",
+                target
+            ))
+            .unwrap();
+            config.check_validity()
+        };
+        assert!(parse("s3:host/bucket").is_ok());
+        let err = parse("/mnt/backup").unwrap_err();
+        assert!(err.to_string().contains("credentials_file"));
+    }
+
+    #[test]
+    fn test_password_source() {
+        let mut set = BackupSet {
+            name: "home".to_string(),
+            source: Some("/home".to_string()),
+            target: "/repo".to_string(),
+            ..Default::default()
+        };
+        assert!(set.password_source().is_shared_default());
+
+        set.password_file = Some("/etc/vigil/home.pw".to_string());
+        assert_eq!(
+            set.password_source().args(),
+            [
+                "--password-file".to_string(),
+                "/etc/vigil/home.pw".to_string()
+            ]
+        );
+
+        set.password_command = Some("pass show backup/home".to_string());
+        assert_eq!(
+            set.password_source(),
+            PasswordSource::Command("pass show backup/home".to_string())
+        );
+        let config = Config {
+            global: GlobalConfig::default(),
+            backup_sets: vec![set],
+        };
+        let err = config.check_validity().unwrap_err();
+        assert!(err.to_string().contains("password_command"));
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let vars = parse_env_file(
+            "# S3 credentials\n\nAWS_ACCESS_KEY_ID=abc\nexport AWS_SECRET_ACCESS_KEY = \"s=cret\"\nRESTIC_REST_PASSWORD='pw'\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("AWS_ACCESS_KEY_ID".to_string(), "abc".to_string()),
+                ("AWS_SECRET_ACCESS_KEY".to_string(), "s=cret".to_string()),
+                ("RESTIC_REST_PASSWORD".to_string(), "pw".to_string()),
+            ]
+        );
+        assert!(parse_env_file("NOT A PAIR").unwrap_err().contains("line 1"));
+        assert!(parse_env_file("BAD-KEY=1").is_err());
+    }
+
+    #[test]
+    fn test_engine_env() {
+        let tmp = tempfile::tempdir().unwrap();
+        let global_file = tmp.path().join("global.env");
+        std::fs::write(
+            &global_file,
+            "GOMAXPROCS=4
+RESTIC_COMPRESSION=auto
+",
+        )
+        .unwrap();
+        let set_file = tmp.path().join("s3.env");
+        std::fs::write(
+            &set_file,
+            "AWS_SECRET_ACCESS_KEY=s3cret
+",
+        )
+        .unwrap();
+        let text = format!(
+            r#"
+[global]
+env_file = "{}"
+env = {{ GOMAXPROCS = "2" }}
+
+[[backup_set]]
+name = "docs"
+source = "/home/me/docs"
+target = "s3:s3.amazonaws.com/bucket"
+env_file = "{}"
+env = {{ RESTIC_COMPRESSION = "max", RESTIC_REST_PASSWORD = "pw" }}
+"#,
+            global_file.display(),
+            set_file.display()
+        );
+        let mut config: Config = toml::from_str(&text).unwrap();
+        config.validate().unwrap();
+        let vars = config.backup_sets[0].process_env(&config.global).unwrap();
+        // Later variables win when the engine's environment is built
+        let effective: BTreeMap<_, _> = vars.iter().cloned().collect();
+        assert_eq!(effective["GOMAXPROCS"], "2");
+        assert_eq!(effective["RESTIC_COMPRESSION"], "max");
+        assert_eq!(effective["AWS_SECRET_ACCESS_KEY"], "s3cret");
+        assert_eq!(
+            masked_env(&vars[vars.len() - 3..]),
+            "AWS_SECRET_ACCESS_KEY=*** RESTIC_COMPRESSION=max RESTIC_REST_PASSWORD=***"
+        );
+
+        let err = toml::from_str::<Config>(&text.replace("RESTIC_COMPRESSION", "RESTIC_PASSWORD"))
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'RESTIC_PASSWORD' is set by vigil"),
+            "{}",
+            err
+        );
+        assert!(validate_env_name("RESTIC_FROM_REPOSITORY").is_err());
+        assert!(validate_env_name("2FAST").is_err());
+
+        std::fs::write(
+            &set_file,
+            "RUSTIC_PASSWORD_FILE=/tmp/pw
+",
+        )
+        .unwrap();
+        let err = toml::from_str::<Config>(&text)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Set 'docs': invalid env_file"),
+            "{}",
+            err
+        );
+        std::fs::remove_file(&global_file).unwrap();
+        let err = toml::from_str::<Config>(&text)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Global settings: cannot read env_file"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_engine_selection() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
 
 [[backup_set]]
-name = "test"
-source = "~/test"
-target = "/tmp/backup"
-"#
+name = "a"
+source = "/a"
+target = "/repo-a"
+
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+engine = "rustic"
+"#,
         )
         .unwrap();
+        assert_eq!(config.backup_sets[0].engine, EngineKind::Restic);
+        assert_eq!(config.backup_sets[1].engine, EngineKind::Rustic);
+        assert_eq!(config.backup_sets[1].engine.binary(), "rustic");
 
-        std::env::set_var("VIGIL_CONFIG", file.path());
-        let config = load_config().unwrap();
-        assert_eq!(config.global.debounce_seconds, 30);
-        assert_eq!(config.backup_sets.len(), 1);
-        assert_eq!(config.backup_sets[0].name, "test");
+        let serialized = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(serialized.matches("engine").count(), 1);
     }
 
     #[test]
-    fn test_path_expansion() {
-        let home = directories::BaseDirs::new()
-            .unwrap()
-            .home_dir()
-            .to_path_buf();
-        let home_str = home.to_string_lossy();
+    fn test_snapshot_source() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
 
-        let set = BackupSet {
-            name: "test".to_string(),
-            source: Some("~/test".to_string()),
-            sources: None,
-            target: "~/backup".to_string(),
-            exclude: None,
-            debounce_seconds: None,
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+snapshot_source = "btrfs"
+
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+snapshot_source = "lvm"
+lvm_snapshot_size = "2G"
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        assert_eq!(
+            config.backup_sets[0].snapshot_source,
+            Some(SnapshotSource::Btrfs)
+        );
+        assert_eq!(
+            config.backup_sets[1].snapshot_source,
+            Some(SnapshotSource::Lvm)
+        );
+
+        let mut invalid = config.clone();
+        invalid.backup_sets[0].lvm_snapshot_size = Some("1G".to_string());
+        let err = invalid.check_validity().unwrap_err().to_string();
+        assert!(err.contains("lvm_snapshot_size"));
+    }
+
+    #[test]
+    fn test_resolve_effective_config() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+debounce_seconds = 120
+retention = { keep_last = 5 }
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+retention = { keep_daily = 7 }
+"#,
+        )
+        .unwrap();
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(effective.set.debounce_seconds, Some(120));
+        assert_eq!(
+            effective.set.retention.as_ref().unwrap().keep_daily,
+            Some(7)
+        );
+        assert_eq!(effective.set.mount_prefetch_depth, Some(0));
+        assert_eq!(effective.sources["debounce_seconds"], SettingSource::Global);
+        assert_eq!(effective.sources["retention"], SettingSource::Set);
+        assert_eq!(
+            effective.sources["mount_prefetch_depth"],
+            SettingSource::Default
+        );
+
+        let no_global = GlobalConfig {
             retention: None,
+            ..Default::default()
         };
+        let mut set = config.backup_sets[0].clone();
+        set.retention = None;
+        let effective = set.resolve(&no_global);
+        assert!(effective.set.retention.is_none());
+        assert_eq!(effective.sources["retention"], SettingSource::Default);
+    }
 
-        let mut config = Config {
-            global: GlobalConfig::default(),
-            backup_sets: vec![set],
-        };
+    #[test]
+    fn test_retry_policy() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+retry_backoff_secs = 30
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+max_retries = 5
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(effective.set.max_retries, Some(5));
+        assert_eq!(effective.set.retry_backoff_secs, Some(30));
+        assert_eq!(effective.sources["max_retries"], SettingSource::Set);
+        assert_eq!(
+            effective.sources["retry_backoff_secs"],
+            SettingSource::Global
+        );
+        assert_eq!(GlobalConfig::default().max_retries, 3);
+
+        let delays: Vec<u64> = (1..=5).map(|n| retry_delay_secs(30, n)).collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480]);
+        assert_eq!(retry_delay_secs(60, 7), MAX_RETRY_DELAY_SECS);
+        assert_eq!(retry_delay_secs(60, 200), MAX_RETRY_DELAY_SECS);
+
+        config.backup_sets[0].retry_backoff_secs = Some(0);
+        assert!(config.check_validity().is_err());
+    }
+
+    #[test]
+    fn test_change_threshold() {
+        let mut set = BackupSet::default();
+        assert!(set.meets_change_threshold(0, Some(0)));
+
+        set.min_changed_files = Some(5);
+        assert!(!set.meets_change_threshold(4, None));
+        assert!(set.meets_change_threshold(5, Some(0)));
+
+        // Either minimum is enough once both are set
+        set.min_changed_bytes = Some(1024 * 1024);
+        assert!(!set.meets_change_threshold(1, Some(4096)));
+        assert!(set.meets_change_threshold(1, Some(2 * 1024 * 1024)));
+        assert!(set.meets_change_threshold(6, Some(0)));
+        assert!(set.meets_change_threshold(1, None));
+
+        set.min_changed_files = None;
+        assert!(!set.meets_change_threshold(1000, Some(10)));
+    }
+
+    #[test]
+    fn test_throttle_settings() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+upload_limit_kib = 512
+priority = "idle"
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "sftp:host:/repo-a"
+download_limit_kib = 2048
+priority = "normal"
+"#,
+        )
+        .unwrap();
+        let set = &config.backup_sets[0];
+        let throttle = set.throttle().or(config.global.throttle());
+        assert_eq!(throttle.upload_limit_kib, Some(512));
+        assert_eq!(throttle.download_limit_kib, Some(2048));
+        assert_eq!(throttle.priority, Some(Priority::Normal));
+
+        let effective = set.resolve(&config.global);
+        assert_eq!(effective.set.upload_limit_kib, Some(512));
+        assert_eq!(effective.sources["upload_limit_kib"], SettingSource::Global);
+        assert_eq!(effective.sources["download_limit_kib"], SettingSource::Set);
+        assert_eq!(effective.sources["priority"], SettingSource::Set);
+
+        let mut rustic = config.clone();
+        rustic.backup_sets[0].engine = EngineKind::Rustic;
+        assert!(rustic.check_validity().is_err());
+        rustic.backup_sets[0].download_limit_kib = None;
+        assert!(rustic.check_validity().is_ok());
+    }
+
+    #[test]
+    fn test_backup_hooks() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+
+[[backup_set]]
+name = "db"
+source = "/var/backups/db"
+target = "/repo-db"
+pre_backup_command = "pg_dump app > /var/backups/db/app.sql"
+post_backup_command = "rm /var/backups/db/app.sql"
+hook_failure = "continue"
+"#,
+        )
+        .unwrap();
+        let set = &config.backup_sets[0];
+        assert_eq!(set.hook_failure, HookFailure::Continue);
+        assert!(set.pre_backup_command.is_some());
+        assert!(config.check_validity().is_ok());
+
+        config.backup_sets[0].hook_timeout_secs = Some(0);
+        assert!(config.check_validity().is_err());
+        assert_eq!(BackupSet::default().hook_failure, HookFailure::Abort);
+    }
+
+    #[test]
+    fn test_exclude_markers() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+
+[[backup_set]]
+name = "code"
+source = "/home/user/code"
+target = "/repo-code"
+exclude_if_present = [".nobackup", "TAG:hello: world"]
+exclude_caches = true
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        let markers = config.backup_sets[0].exclude_markers();
+        assert_eq!(markers.len(), 3);
+        assert_eq!(markers[0].restic_arg(), ".nobackup");
+        assert_eq!(markers[1].header.as_deref(), Some("hello: world"));
+        assert_eq!(markers[2], ExcludeMarker::cachedir_tag());
+
+        // restic cannot read .gitignore files, rustic cannot check headers
+        config.backup_sets[0].use_gitignore = true;
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].engine = EngineKind::Rustic;
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].exclude_if_present.pop();
+        assert!(config.check_validity().is_ok());
+        assert_eq!(config.backup_sets[0].exclude_markers()[1].header, None);
+        // Canaries are read back with `restic dump`
+        config.backup_sets[0].canary = true;
+        assert!(config.check_validity().is_err());
+
+        assert!(ExcludeMarker::parse("sub/.nobackup").is_err());
+        assert!(ExcludeMarker::parse(":header").is_err());
+    }
+
+    #[test]
+    fn test_logical_path() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+
+[[backup_set]]
+name = "home"
+source = "/mnt/new-disk/me"
+target = "/repo-home"
+logical_path = "/home/me"
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        assert_eq!(
+            config.backup_sets[0].logical_path_tag().as_deref(),
+            Some("vigil-path:/home/me")
+        );
+
+        // Tags are split on commas
+        config.backup_sets[0].logical_path = Some("/home/me,old".to_string());
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].logical_path = Some("/home/me".to_string());
+        config.backup_sets[0].engine = EngineKind::Rustic;
+        assert!(config.check_validity().is_err());
+    }
+
+    #[test]
+    fn test_files_from() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
 
+[[backup_set]]
+name = "curated"
+files_from = "/home/me/backup.list"
+target = "/repo-curated"
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+
+        config.backup_sets[0].source = Some("/home/me".to_string());
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].source = None;
+        config.backup_sets[0].snapshot_source = Some(SnapshotSource::Btrfs);
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].snapshot_source = None;
+        config.backup_sets[0].engine = EngineKind::Rustic;
+        assert!(config.check_validity().is_err());
+
+        assert_eq!(
+            parse_file_list("# taxes\n/home/me/taxes.ods\n\n  /home/me/photos/best  \n"),
+            vec!["/home/me/taxes.ods", "/home/me/photos/best"]
+        );
+    }
+
+    #[test]
+    fn test_list_files_checked_on_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        let list = tmp.path().join("backup.list");
+        let excludes = tmp.path().join("excludes.txt");
+        std::fs::write(&list, "/home/me/taxes.ods\n").unwrap();
+        std::fs::write(&excludes, "# build output\n*.o\n/home/me/tmp\n").unwrap();
+        let text = format!(
+            "[global]\n\n[[backup_set]]\nname = \"curated\"\ninclude_file = {:?}\nexclude_file = {:?}\ntarget = \"/repo\"\n",
+            list, excludes
+        );
+        let mut config: Config = toml::from_str(&text).unwrap();
         config.validate().unwrap();
+        let set = &config.backup_sets[0];
+        assert_eq!(set.files_from.as_deref(), list.to_str());
+        let patterns = set.exclude_file_patterns().unwrap();
+        assert_eq!(
+            patterns.iter().map(|p| p.restic_arg()).collect::<Vec<_>>(),
+            ["*.o", "/home/me/tmp"]
+        );
+
+        std::fs::write(&excludes, "!*.keep\n").unwrap();
+        let err = toml::from_str::<Config>(&text)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("negated"), "{}", err);
+
+        std::fs::remove_file(&list).unwrap();
+        let err = toml::from_str::<Config>(&text)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cannot read its files_from list"),
+            "{}",
+            err
+        );
+
+        assert!(parse_exclude_file("$HOME/cache\n").is_err());
+        assert!(parse_exclude_file("\n# only comments\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_own_paths_excluded() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+
+[[backup_set]]
+name = "home"
+source = "/home/user"
+target = "/repo-home"
+exclude = ["*.tmp"]
 
+[[backup_set]]
+name = "docs"
+sources = ["/home/user/docs"]
+target = "/repo-docs"
+"#,
+        )
+        .unwrap();
+        let own = [
+            PathBuf::from("/home/user/.local/share/vigil"),
+            PathBuf::from("/run/user/1000/vigil.sock"),
+        ];
         assert_eq!(
-            config.backup_sets[0].source.as_ref().unwrap(),
-            &format!("{}/test", home_str)
+            config.backup_sets[0].own_paths_within(&own),
+            vec![own[0].clone()]
         );
-        assert_eq!(config.backup_sets[0].target, format!("{}/backup", home_str));
+        assert!(config.backup_sets[1].own_paths_within(&own).is_empty());
+
+        let warnings = config.warnings_for(&own);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("Set 'home' contains vigil's own data"));
+        assert!(warnings[1].contains("both back up /home/user/docs"));
+
+        let pattern = ExcludePattern::for_path(Path::new("/data/[x]*/vigil")).unwrap();
+        assert_eq!(pattern.restic_arg(), r"/data/\[x\]\*/vigil");
+        assert!(ExcludePattern::for_path(Path::new("/a/{b}")).is_none());
+    }
+
+    #[test]
+    fn test_notification_policy() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global.notifications]
+events = ["failure", "unreachable"]
+backend = "command"
+command = "ntfy publish backups \"$VIGIL_BODY\""
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+
+[backup_set.notifications]
+events = ["success", "failure"]
+
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+notifications = { backend = "none" }
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+
+        let a = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(a.sources["notifications"], SettingSource::Set);
+        assert_eq!(a.set.notifications.backend(), NotifyBackend::Command);
+        assert!(a.set.notifications.notifies(NotifyEvent::Success));
+        assert!(!a.set.notifications.notifies(NotifyEvent::Unreachable));
+
+        let b = config.backup_sets[1].resolve(&config.global);
+        assert_eq!(b.set.notifications.backend(), NotifyBackend::None);
+        assert!(b.set.notifications.notifies(NotifyEvent::Unreachable));
+
+        // Without any table, failures, prunes and unreachable repositories notify on the desktop
+        let default = NotificationPolicy::default();
+        assert_eq!(default.backend(), NotifyBackend::Desktop);
+        assert!(!default.notifies(NotifyEvent::Success));
+        assert!(default.notifies(NotifyEvent::Prune));
+
+        config.global.notifications.command = None;
+        assert!(config.check_validity().is_err());
+        config.global.notifications.backend = Some(NotifyBackend::Desktop);
+        config.backup_sets[0].notifications.backend = Some(NotifyBackend::Command);
+        assert!(config.check_validity().is_err());
+    }
+
+    #[test]
+    fn test_render_notify_command() {
+        assert_eq!(
+            render_notify_command("notify {event} {set} {message}").unwrap(),
+            r#"notify "$1" "$2" "$3""#
+        );
+        assert_eq!(
+            render_notify_command(r#"curl -d "msg={message} ({set})" it\'s"#).unwrap(),
+            r#"curl -d "msg=${3} (${2})" it\'s"#
+        );
+        // Shell braces and non-word braces are not placeholders
+        assert_eq!(
+            render_notify_command("echo ${HOME} {1,2} {} {set}").unwrap(),
+            r#"echo ${HOME} {1,2} {} "$2""#
+        );
+        assert!(render_notify_command("notify {sett}")
+            .unwrap_err()
+            .contains("{event}, {set}, {message}"));
+        assert!(render_notify_command("notify 'msg={message}'")
+            .unwrap_err()
+            .contains("single quotes"));
+
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+[[backup_set]]
+name = "home"
+source = "/h"
+target = "/r"
+notify_command = "my-notify {event} {set} {message}"
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        config.backup_sets[0].notify_command = Some("my-notify {level}".to_string());
+        assert!(config.check_validity().is_err());
+        config.backup_sets[0].notify_command = Some(" ".to_string());
+        assert!(config.check_validity().is_err());
+    }
+
+    #[test]
+    fn test_max_concurrent_jobs() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+max_concurrent_jobs = 2
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.global.max_concurrent_jobs, Some(2));
+        assert!(config.check_validity().is_ok());
+
+        config.global.max_concurrent_jobs = Some(0);
+        assert!(config.check_validity().is_err());
+        assert_eq!(GlobalConfig::default().max_concurrent_jobs, None);
+    }
+
+    #[test]
+    fn test_path_overlaps() {
+        let text = r#"
+[global]
+
+[[backup_set]]
+name = "home"
+source = "/home/user"
+target = "/home/user/backups/home"
+
+[[backup_set]]
+name = "docs"
+sources = ["/srv/docs"]
+target = "/srv/docs/.repo"
+
+[[backup_set]]
+name = "music"
+source = "/srv/music"
+target = "sftp:nas:/srv/docs/music"
+"#;
+        let mut config: Config = toml::from_str(text).unwrap();
+        assert_eq!(
+            config.path_overlaps(),
+            vec![
+                "Set 'home' has its target /home/user/backups/home inside its own source /home/user; each backup changes the source and triggers another",
+                "Set 'docs' has its target /srv/docs/.repo inside its own source /srv/docs; each backup changes the source and triggers another",
+            ]
+        );
+        assert!(config.validate().is_ok());
+
+        // An anchored exclude of the repository breaks the loop
+        config.backup_sets[0].exclude = Some(vec!["/home/user/backups".to_string()]);
+        config.backup_sets[1].target = "/home/user/docs-repo".to_string();
+        config.backup_sets[2].source = Some("/home/user/music".to_string());
+        assert_eq!(
+            config.path_overlaps(),
+            vec![
+                "Set 'docs' has its target /home/user/docs-repo inside source /home/user of set 'home'; each backup of 'docs' triggers a backup of 'home'",
+                "Sets 'home' and 'music' both back up /home/user/music; a change there starts a backup of each",
+            ]
+        );
+
+        let mut strict: Config =
+            toml::from_str(&text.replace("[global]", "[global]\nstrict = true")).unwrap();
+        let err = strict.validate().unwrap_err();
+        assert!(err.to_string().contains("global.strict is set"), "{}", err);
+        assert!(
+            err.to_string().contains("Set 'docs' has its target"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_restore_shield_secs() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+restore_shield_secs = 300
+
+[[backup_set]]
+name = "home"
+sources = ["/home/user/docs", "/home/user/photos"]
+target = "/srv/repo"
+"#,
+        )
+        .unwrap();
+        assert_eq!(GlobalConfig::default().restore_shield_secs, 600);
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(effective.set.restore_shield_secs, Some(300));
+        assert_eq!(
+            effective.sources["restore_shield_secs"],
+            SettingSource::Global
+        );
+        config.backup_sets[0].restore_shield_secs = Some(0);
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(effective.set.restore_shield_secs, Some(0));
+        assert_eq!(effective.sources["restore_shield_secs"], SettingSource::Set);
+
+        let set = &config.backup_sets[0];
+        assert!(set.overlaps_sources(Path::new("/home/user/docs/restored")));
+        assert!(set.overlaps_sources(Path::new("/home/user")));
+        assert!(!set.overlaps_sources(Path::new("/home/user/docs-restored")));
+        assert!(!set.overlaps_sources(Path::new("/tmp/restore")));
+    }
+
+    #[test]
+    fn test_shutdown_grace_secs() {
+        let mut config: Config = toml::from_str(
+            r#"
+[global]
+shutdown_grace_secs = 30
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.global.shutdown_grace_secs, Some(30));
+        assert!(config.check_validity().is_ok());
+        assert!(config.warnings_for(&[]).is_empty());
+
+        config.global.shutdown_grace_secs = Some(SYSTEMD_STOP_TIMEOUT_SECS);
+        let warnings = config.warnings_for(&[]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("TimeoutStopSec"), "{}", warnings[0]);
+        assert_eq!(GlobalConfig::default().shutdown_grace_secs, None);
+    }
+
+    #[test]
+    fn test_mount_prefetch_depth() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+mount_prefetch_depth = 3
+
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
+
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+mount_prefetch_depth = 0
+"#,
+        )
+        .unwrap();
+        let global = config.global.mount_prefetch_depth;
+        assert_eq!(global, Some(3));
+        assert_eq!(config.backup_sets[0].prefetch_depth(global), 3);
+        assert_eq!(config.backup_sets[1].prefetch_depth(global), 0);
+        assert_eq!(config.backup_sets[0].prefetch_depth(None), 0);
     }
 
     #[test]
-    fn test_mutually_exclusive_sources() {
-        let config_str = r#"
+    fn test_auto_prune_defaults_on() {
+        let config: Config = toml::from_str(
+            r#"
 [global]
-debounce_seconds = 60
 
 [[backup_set]]
-name = "test"
-source = "~/test"
-sources = ["~/test1", "~/test2"]
-target = "/tmp/backup"
-"#;
-        let mut config: Config = toml::from_str(config_str).unwrap();
-        let result = config.validate();
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("cannot have both 'source' and 'sources'"));
+name = "a"
+source = "/a"
+target = "/repo-a"
+
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+auto_prune = false
+"#,
+        )
+        .unwrap();
+        assert!(config.backup_sets[0].auto_prune());
+        assert!(!config.backup_sets[1].auto_prune());
     }
 
     #[test]
-    fn test_duplicate_names() {
-        let config_str = r#"
+    fn test_replica_target() {
+        let mut config: Config = toml::from_str(
+            r#"
 [global]
-debounce_seconds = 60
 
 [[backup_set]]
-name = "dup"
-source = "~/test1"
-target = "/tmp/backup1"
+name = "a"
+source = "/a"
+target = "/repo-a"
+replica_target = "sftp:nas:/backups/a"
 
 [[backup_set]]
-name = "dup"
-source = "~/test2"
-target = "/tmp/backup2"
-"#;
-        let mut config: Config = toml::from_str(config_str).unwrap();
-        let result = config.validate();
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("Duplicate backup set name"));
+name = "b"
+source = "/b"
+target = "/repo-b"
+replica_target = "/mnt/usb/b"
+auto_replicate = false
+
+[[backup_set]]
+name = "c"
+source = "/c"
+target = "/repo-c"
+"#,
+        )
+        .unwrap();
+        assert!(config.check_validity().is_ok());
+        assert!(config.backup_sets[0].auto_replicate());
+        assert!(!config.backup_sets[1].auto_replicate());
+        assert!(!config.backup_sets[2].auto_replicate());
+
+        assert_eq!(config.backup_sets[0].max_copy_lag_hours(Some(24)), Some(24));
+        config.backup_sets[0].max_copy_lag_hours = Some(0);
+        assert_eq!(config.backup_sets[0].max_copy_lag_hours(Some(24)), None);
+        assert_eq!(config.backup_sets[2].max_copy_lag_hours(Some(24)), None);
+        config.backup_sets[2].max_copy_lag_hours = Some(6);
+        let err = config.check_validity().unwrap_err();
+        assert!(
+            err.to_string().contains("without a replica_target"),
+            "{}",
+            err
+        );
+        config.backup_sets[2].max_copy_lag_hours = None;
+
+        config.backup_sets[1].replica_target = Some("/repo-b".to_string());
+        assert!(config.check_validity().is_err());
+        config.backup_sets[1].replica_target = None;
+        config.backup_sets[0].engine = EngineKind::Rustic;
+        assert!(config.check_validity().is_err());
+
+        assert_eq!(
+            PasswordSource::Command("pass show backup".to_string()).from_repo_args(),
+            ["--from-password-command", "pass show backup"]
+        );
     }
 
     #[test]
-    fn test_config_serialization() {
-        let set = BackupSet {
-            name: "test".to_string(),
-            source: Some("~/test".to_string()),
-            sources: None,
-            target: "~/backup".to_string(),
-            exclude: Some(vec!["*.tmp".to_string()]),
-            debounce_seconds: Some(30),
-            retention: Some(RetentionPolicy {
-                keep_last: Some(5),
-                ..Default::default()
-            }),
-        };
+    fn test_mount_idle_timeout() {
+        let config: Config = toml::from_str(
+            r#"
+[global]
+mount_idle_timeout_secs = 1800
 
-        let config = Config {
-            global: GlobalConfig::default(),
-            backup_sets: vec![set],
-        };
+[[backup_set]]
+name = "a"
+source = "/a"
+target = "/repo-a"
 
-        let json = serde_json::to_string(&config).unwrap();
-        assert!(json.contains("\"name\":\"test\""));
-        assert!(json.contains("\"target\":\"~/backup\""));
+[[backup_set]]
+name = "b"
+source = "/b"
+target = "/repo-b"
+mount_idle_timeout_secs = 0
+"#,
+        )
+        .unwrap();
+        let global = config.global.mount_idle_timeout_secs;
+        assert_eq!(config.backup_sets[0].mount_idle_timeout(global), Some(1800));
+        assert_eq!(config.backup_sets[1].mount_idle_timeout(global), None);
+        assert_eq!(config.backup_sets[0].mount_idle_timeout(None), None);
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(effective.set.mount_idle_timeout_secs, Some(1800));
+        assert_eq!(
+            effective.sources["mount_idle_timeout_secs"],
+            SettingSource::Global
+        );
     }
 
     #[test]
@@ -338,6 +3830,7 @@ target = "/tmp/backup2"
                 exclude: None,
                 debounce_seconds: None,
                 retention: None,
+                ..Default::default()
             }],
         };
 
@@ -348,4 +3841,518 @@ target = "/tmp/backup2"
         assert_eq!(loaded.backup_sets.len(), 1);
         assert_eq!(loaded.backup_sets[0].name, "test");
     }
+
+    #[test]
+    #[serial]
+    fn test_modify_config() {
+        let temp = tempfile::tempdir().unwrap();
+        let config_path = temp.path().join("config.toml");
+        std::env::set_var("VIGIL_CONFIG", &config_path);
+
+        let set = BackupSet {
+            name: "docs".to_string(),
+            source: Some("/docs".to_string()),
+            target: "/repo".to_string(),
+            ..Default::default()
+        };
+
+        // A missing file starts from an empty configuration
+        let config = modify_config(&[ConfigOp::AddSet {
+            set: Box::new(set.clone()),
+        }])
+        .unwrap();
+        assert_eq!(config.backup_sets.len(), 1);
+        assert!(!temp.path().join("config.toml.tmp").exists());
+
+        let err = modify_config(&[ConfigOp::AddSet { set: Box::new(set) }]).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        let config = modify_config(&[ConfigOp::SetPasswordFile {
+            name: "docs".to_string(),
+            password_file: "/pw/docs".to_string(),
+        }])
+        .unwrap();
+        assert_eq!(
+            config.backup_sets[0].password_source(),
+            PasswordSource::File(PathBuf::from("/pw/docs"))
+        );
+
+        let config = modify_config(&[ConfigOp::RemoveSet {
+            name: "docs".to_string(),
+        }])
+        .unwrap();
+        assert!(config.backup_sets.is_empty());
+        assert!(load_config_raw().unwrap().backup_sets.is_empty());
+
+        let err = modify_config(&[ConfigOp::RemoveSet {
+            name: "docs".to_string(),
+        }])
+        .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_modify_config_keeps_comments() {
+        let temp = tempfile::tempdir().unwrap();
+        let config_path = temp.path().join("config.toml");
+        std::env::set_var("VIGIL_CONFIG", &config_path);
+        std::fs::write(
+            &config_path,
+            r#"# Laptop backups
+[global]
+debounce_seconds = 120 # quiet period
+
+[[backup_set]]
+# Everything under home
+name = "home"
+source = "/home/me"
+target = "/repo/home"
+"#,
+        )
+        .unwrap();
+
+        let set = BackupSet {
+            name: "docs".to_string(),
+            source: Some("/docs".to_string()),
+            target: "/repo/docs".to_string(),
+            exclude: Some(vec!["*.tmp".to_string()]),
+            retention: Some(RetentionPolicy {
+                keep_last: Some(5),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let config = modify_config(&[ConfigOp::AddSet { set: Box::new(set) }]).unwrap();
+        assert_eq!(config.backup_sets.len(), 2);
+        let text = std::fs::read_to_string(&config_path).unwrap();
+        assert!(text.starts_with("# Laptop backups\n"), "{}", text);
+        assert!(text.contains("debounce_seconds = 120 # quiet period"));
+        assert!(text.contains("# Everything under home"));
+        let loaded = load_config_raw().unwrap();
+        assert_eq!(
+            loaded.backup_sets[1].exclude,
+            Some(vec!["*.tmp".to_string()])
+        );
+        assert_eq!(
+            loaded.backup_sets[1].retention.as_ref().unwrap().keep_last,
+            Some(5)
+        );
+
+        modify_config(&[ConfigOp::RemoveSet {
+            name: "docs".to_string(),
+        }])
+        .unwrap();
+        let text = std::fs::read_to_string(&config_path).unwrap();
+        assert!(text.contains("# Everything under home"));
+        assert!(!text.contains("docs"));
+    }
+
+    #[test]
+    fn test_setting_key() {
+        let key = SettingKey::parse("backup_set.home.retention").unwrap();
+        assert_eq!(key.set.as_deref(), Some("home"));
+        assert_eq!(key.field(), "retention");
+        assert_eq!(key.to_string(), "backup_set.home.retention");
+        let key = SettingKey::parse("global.retention.keep_daily").unwrap();
+        assert_eq!(key.set, None);
+        assert_eq!(key.path, vec!["retention", "keep_daily"]);
+        assert!(SettingKey::parse("debounce_seconds").is_err());
+        assert!(SettingKey::parse("backup_set.home").is_err());
+        assert!(SettingKey::parse("global.a..b").is_err());
+
+        let parse = |key: &str, raw: &str| SettingKey::parse(key).unwrap().parse_value(raw);
+        assert_eq!(parse("global.debounce_seconds", "5m"), Ok(300.into()));
+        assert_eq!(parse("global.debounce_seconds", "45"), Ok(45.into()));
+        assert!(parse("global.debounce_seconds", "soon").is_err());
+        assert_eq!(parse("global.auto_unlock", "false"), Ok(false.into()));
+        assert_eq!(
+            parse("backup_set.home.exclude", r#"["*.tmp", "cache"]"#),
+            Ok(serde_json::json!(["*.tmp", "cache"]))
+        );
+        assert_eq!(
+            parse("backup_set.home.target", "/mnt/backup"),
+            Ok("/mnt/backup".into())
+        );
+        assert_eq!(
+            parse("backup_set.home.retention", "last=3,daily=7"),
+            Ok(serde_json::json!({ "keep_last": 3, "keep_daily": 7 }))
+        );
+
+        let config: Config = toml::from_str(
+            "[global]\n[[backup_set]]\nname = \"home\"\nsource = \"/h\"\ntarget = \"/r\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .setting_value("backup_set.home.target", "2024")
+                .unwrap(),
+            serde_json::json!("2024")
+        );
+        assert_eq!(
+            config.setting_value("global.max_retries", "5").unwrap(),
+            serde_json::json!(5)
+        );
+        assert!(config.setting_value("global.max_retries", "many").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_modify_config_set_setting() {
+        let temp = tempfile::tempdir().unwrap();
+        let config_path = temp.path().join("config.toml");
+        std::env::set_var("VIGIL_CONFIG", &config_path);
+        std::fs::write(
+            &config_path,
+            r#"[global]
+debounce_seconds = 120 # quiet period
+
+[[backup_set]]
+# Everything under home
+name = "home"
+source = "/home/me"
+target = "/repo/home"
+"#,
+        )
+        .unwrap();
+        let set = |key: &str, raw: &str| {
+            let value = SettingKey::parse(key).unwrap().parse_value(raw).unwrap();
+            modify_config(&[ConfigOp::Set {
+                key: key.to_string(),
+                value,
+            }])
+        };
+
+        set("global.debounce_seconds", "1m").unwrap();
+        set("backup_set.home.retention", "last=3,weekly=2").unwrap();
+        set("global.retention.keep_daily", "7").unwrap();
+        let text = std::fs::read_to_string(&config_path).unwrap();
+        assert!(
+            text.contains("debounce_seconds = 60 # quiet period"),
+            "{}",
+            text
+        );
+        assert!(text.contains("# Everything under home"), "{}", text);
+        let config = load_config_raw().unwrap();
+        assert_eq!(config.global.debounce_seconds, 60);
+        assert_eq!(
+            config.global.retention.as_ref().unwrap().keep_daily,
+            Some(7)
+        );
+        let retention = config.backup_sets[0].retention.clone().unwrap();
+        assert_eq!(
+            (retention.keep_last, retention.keep_weekly),
+            (Some(3), Some(2))
+        );
+        assert_eq!(
+            config
+                .get_setting(&SettingKey::parse("backup_set.home.retention.keep_last").unwrap())
+                .unwrap(),
+            Some(3.into())
+        );
+
+        // Wrong types, unknown settings and sets leave the file alone
+        assert!(set("global.auto_unlock", "sometimes").is_err());
+        assert!(set("global.debounce_secnds", "30").is_err());
+        assert!(set("backup_set.work.target", "/repo").is_err());
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), text);
+
+        modify_config(&[ConfigOp::Set {
+            key: "backup_set.home.retention".to_string(),
+            value: serde_json::Value::Null,
+        }])
+        .unwrap();
+        assert!(load_config_raw().unwrap().backup_sets[0]
+            .retention
+            .is_none());
+    }
+
+    #[test]
+    fn test_retention_policy_parse() {
+        let policy = RetentionPolicy::parse("last=10, daily=7,monthly=12,per_tag").unwrap();
+        assert_eq!(
+            policy,
+            RetentionPolicy {
+                keep_last: Some(10),
+                keep_daily: Some(7),
+                keep_weekly: None,
+                keep_monthly: Some(12),
+                per_tag: true,
+                ..Default::default()
+            }
+        );
+        assert!(RetentionPolicy::parse("").is_err());
+        assert!(RetentionPolicy::parse("per_tag").is_err());
+        assert!(RetentionPolicy::parse("hourly=3").is_err());
+        assert!(RetentionPolicy::parse("last=many").is_err());
+        assert!(RetentionPolicy::parse("last").is_err());
+
+        let policy = RetentionPolicy::parse("yearly=3,within=1y6m,tag=keep,tag=release").unwrap();
+        assert_eq!(policy.keep_yearly, Some(3));
+        assert_eq!(policy.keep_within.as_deref(), Some("1y6m"));
+        assert_eq!(policy.keep_tag, vec!["keep", "release"]);
+        assert!(RetentionPolicy::parse("tag=keep").is_err());
+        assert!(RetentionPolicy::parse("within=30s").is_err());
+        assert!(RetentionPolicy::parse("within=30").is_err());
+        assert!(RetentionPolicy::parse("within=0d").is_err());
+        assert!(RetentionPolicy::parse("last=3,tag=a,b").is_err());
+    }
+
+    #[test]
+    fn test_retention_checked_on_load() {
+        let toml = r#"
+[global]
+retention = { keep_within = "30d", keep_tag = ["pinned"] }
+
+[[backup_set]]
+name = "home"
+source = "/home/user"
+target = "/backup/home"
+retention = { keep_yearly = 5, keep_within = "2w" }
+"#;
+        let err = toml::from_str::<Config>(toml)
+            .unwrap()
+            .check_validity()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Set 'home' has an invalid retention: invalid keep_within '2w'"),
+            "{}",
+            err
+        );
+        let config = toml::from_str::<Config>(&toml.replace("2w", "14d")).unwrap();
+        config.check_validity().unwrap();
+        let global = config.global.retention.as_ref().unwrap();
+        assert_eq!(global.keep_tag, vec!["pinned"]);
+        let effective = config.backup_sets[0].resolve(&config.global);
+        assert_eq!(
+            effective.set.retention.as_ref().unwrap().rules(),
+            "yearly=5,within=14d"
+        );
+    }
+
+    #[test]
+    fn test_retention_policy_budget() {
+        let policy = RetentionPolicy::parse("last=10,daily=7,monthly=12,per_tag").unwrap();
+        assert_eq!(policy.max_kept(), Some(29));
+        assert_eq!(policy.rules(), "last=10,daily=7,monthly=12,per_tag");
+        assert_eq!(RetentionPolicy::parse(&policy.rules()).unwrap(), policy);
+
+        let open = RetentionPolicy::parse("last=3,within=30d,tag=keep").unwrap();
+        assert_eq!(open.max_kept(), None);
+        assert!(open.has_keep_rules());
+        assert_eq!(open.rules(), "last=3,within=30d,tag=keep");
+
+        let empty = RetentionPolicy::default();
+        assert!(!empty.has_keep_rules());
+        assert_eq!(empty.max_kept(), None);
+        assert_eq!(empty.rules(), "");
+    }
+
+    #[test]
+    fn test_exclude_pattern_normalization() {
+        let p = ExcludePattern::parse("./cache//tmp/").unwrap();
+        assert_eq!(p.restic_arg(), "cache/tmp");
+        assert_eq!(p.globs(), vec!["**/cache/tmp"]);
+        assert!(!p.is_anchored());
+
+        let p = ExcludePattern::parse("/home/u/.cache").unwrap();
+        assert!(p.is_anchored());
+        assert_eq!(p.globs(), vec!["/home/u/.cache"]);
+
+        let p = ExcludePattern::parse("build/**").unwrap();
+        assert_eq!(p.globs(), vec!["**/build", "**/build/**"]);
+        let p = ExcludePattern::parse("[^.]*").unwrap();
+        assert_eq!(p.globs(), vec!["**/[^/.]*"]);
+
+        assert!(ExcludePattern::parse("*.{tmp,bak}").is_err());
+        assert!(ExcludePattern::parse("[!a]*").is_err());
+        assert!(ExcludePattern::parse("a**b").is_err());
+        assert!(ExcludePattern::parse("/").is_err());
+        assert!(ExcludePattern::parse("a/**/b").is_ok());
+        assert_eq!(
+            ExcludePattern::parse("a/**/**").unwrap().restic_arg(),
+            "a/**"
+        );
+    }
+
+    #[test]
+    fn test_schedule_parse() {
+        assert_eq!(
+            Schedule::parse("1h30m").unwrap(),
+            Schedule::Every(std::time::Duration::from_secs(5400))
+        );
+        assert_eq!(
+            Schedule::parse("1d").unwrap(),
+            Schedule::Every(std::time::Duration::from_secs(86400))
+        );
+        assert!(matches!(
+            Schedule::parse("0 3 * * *").unwrap(),
+            Schedule::Cron(_)
+        ));
+        assert!(Schedule::parse("0m").is_err());
+        assert!(Schedule::parse("6").is_err());
+        assert!(Schedule::parse("6w").is_err());
+        assert!(Schedule::parse("61 * * * *").is_err());
+
+        let now = Utc::now();
+        let every = Schedule::parse("30m").unwrap();
+        assert_eq!(
+            every.next_after(now),
+            Some(now + chrono::Duration::minutes(30))
+        );
+        let hourly = Schedule::parse("0 * * * *").unwrap();
+        let next = hourly.next_after(now).unwrap();
+        assert!(next > now && next <= now + chrono::Duration::hours(1));
+    }
+
+    #[test]
+    fn test_engine_process_env_layers_global_and_set() {
+        let tmp = tempfile::tempdir().unwrap();
+        let creds = tmp.path().join("creds.env");
+        std::fs::write(&creds, "AWS_ACCESS_KEY_ID=abc\nGOMAXPROCS=3\n").unwrap();
+        let global = EngineEnv {
+            env_file: None,
+            env: vec![
+                ("GOMAXPROCS".to_string(), "2".to_string()),
+                ("RESTIC_COMPRESSION".to_string(), "auto".to_string()),
+            ],
+        };
+        let set_env = EngineEnv {
+            env_file: None,
+            env: vec![("RESTIC_COMPRESSION".to_string(), "max".to_string())],
+        };
+        let vars = engine_process_env(&global, Some(&creds), &set_env).unwrap();
+        let names: Vec<String> = vars.into_iter().map(|(k, v)| k + "=" + &v).collect();
+        assert_eq!(
+            names,
+            [
+                "GOMAXPROCS=2",
+                "RESTIC_COMPRESSION=auto",
+                "AWS_ACCESS_KEY_ID=abc",
+                "GOMAXPROCS=3",
+                "RESTIC_COMPRESSION=max"
+            ]
+        );
+
+        let missing = tmp.path().join("missing");
+        let err = engine_process_env(&global, Some(&missing), &set_env).unwrap_err();
+        assert!(err.to_string().contains("cannot read credentials file"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_restic_env_passthrough() {
+        let toml = r#"
+[global]
+restic_env_passthrough = ["RESTIC_CACHE_DIR"]
+"#;
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.validate().unwrap();
+
+        std::env::set_var("RESTIC_REPOSITORY", "/leaked");
+        std::env::set_var("RESTIC_CACHE_DIR", "/cache");
+        let blocked = blocked_engine_env(&config.global.restic_env_passthrough);
+        std::env::remove_var("RESTIC_REPOSITORY");
+        std::env::remove_var("RESTIC_CACHE_DIR");
+        assert!(blocked.contains(&"RESTIC_REPOSITORY".to_string()));
+        assert!(!blocked.contains(&"RESTIC_CACHE_DIR".to_string()));
+
+        config.global.restic_env_passthrough = vec!["AWS_PROFILE".to_string()];
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("must start with RESTIC_"));
+    }
+
+    #[test]
+    fn test_maintenance_window() {
+        let t = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let night = MaintenanceWindow::parse("02:00-06:00").unwrap();
+        assert!(night.contains(t("02:00")));
+        assert!(night.contains(t("05:59")));
+        assert!(!night.contains(t("06:00")));
+        assert_eq!(night.until_open(t("03:00")), chrono::Duration::zero());
+        assert_eq!(night.until_open(t("01:30")), chrono::Duration::minutes(30));
+        assert_eq!(night.until_open(t("23:00")), chrono::Duration::hours(3));
+
+        // Spans midnight
+        let late = MaintenanceWindow::parse("22:00 - 04:00").unwrap();
+        assert!(late.contains(t("23:30")));
+        assert!(late.contains(t("01:00")));
+        assert!(!late.contains(t("12:00")));
+        assert_eq!(late.until_open(t("12:00")), chrono::Duration::hours(10));
+
+        assert!(MaintenanceWindow::parse("02:00").is_err());
+        assert!(MaintenanceWindow::parse("25:00-06:00").is_err());
+        assert!(MaintenanceWindow::parse("02:00-02:00").is_err());
+
+        let toml = |extra: &str| {
+            format!(
+                "[global]\n{}\n[[backup_set]]\nname = \"home\"\nsource = \"/tmp\"\ntarget = \"/tmp/repo\"\ndefer_backups = true\n",
+                extra
+            )
+        };
+        let err = toml::from_str::<Config>(&toml(""))
+            .unwrap()
+            .check_validity()
+            .unwrap_err();
+        assert!(err.to_string().contains("no maintenance_window"));
+        let config: Config = toml::from_str(&toml("maintenance_window = \"02:00-06:00\"")).unwrap();
+        config.check_validity().unwrap();
+        assert_eq!(
+            config.backup_sets[0].maintenance_window(config.global.maintenance_window.as_deref()),
+            Some(night)
+        );
+    }
+
+    #[test]
+    fn test_invalid_schedule_rejected() {
+        let toml = r#"
+[global]
+
+[[backup_set]]
+name = "docs"
+source = "/home/user/docs"
+target = "/backup/docs"
+schedule = "every day"
+"#;
+        let mut config: Config = toml::from_str(toml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("invalid schedule"));
+    }
+
+    #[test]
+    fn test_tags_and_per_tag_retention() {
+        let toml = r#"
+[global]
+
+[[backup_set]]
+name = "docs"
+source = "/home/user/docs"
+target = "/backup/docs"
+tags = ["laptop", "docs"]
+retention = { keep_last = 5, per_tag = true }
+"#;
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.backup_sets[0].tags, vec!["laptop", "docs"]);
+        assert!(config.backup_sets[0].retention.as_ref().unwrap().per_tag);
+
+        config.backup_sets[0].tags.push("a,b".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("invalid tag 'a,b'"));
+
+        assert!(validate_tag("pre-upgrade").is_ok());
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag("--host").is_err());
+    }
+
+    #[test]
+    fn test_config_op_serialization() {
+        let op = ConfigOp::RemoveSet {
+            name: "docs".to_string(),
+        };
+        let json = serde_json::to_string(&op).unwrap();
+        assert_eq!(json, r#"{"op":"remove_set","name":"docs"}"#);
+        assert_eq!(serde_json::from_str::<ConfigOp>(&json).unwrap(), op);
+    }
 }
@@ -0,0 +1,258 @@
+//! Human-readable sizes and durations, shared by the CLI, the TUI and daemon logs.
+//!
+//! The free functions always use [`Locale::C`] so log lines and scripted output
+//! stay stable; interactive output goes through [`Locale::current`].
+
+use chrono::Duration;
+use std::sync::OnceLock;
+
+/// Unit family used for byte sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB, TiB (what restic prints).
+    Binary,
+    /// Powers of 1000: kB, MB, GB, TB.
+    Decimal,
+}
+
+/// Formatting conventions for human-readable output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    pub units: SizeUnits,
+    pub decimal_separator: char,
+}
+
+/// Languages whose numeric locale writes `1,5` rather than `1.5`.
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id", "is",
+    "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr",
+    "uk", "vi",
+];
+
+impl Locale {
+    /// Binary units with a decimal point, independent of the environment.
+    pub const C: Locale = Locale {
+        units: SizeUnits::Binary,
+        decimal_separator: '.',
+    };
+
+    /// Builds the locale from the process environment: the decimal separator
+    /// follows `LC_ALL`, `LC_NUMERIC` or `LANG` (first one set), and
+    /// `VIGIL_SIZE_UNITS=si` switches sizes to powers of 1000.
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let numeric = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|key| var(key))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = numeric
+            .split(['_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let decimal_separator = if DECIMAL_COMMA_LANGUAGES.contains(&language.as_str()) {
+            ','
+        } else {
+            '.'
+        };
+        let units = match var("VIGIL_SIZE_UNITS").as_deref() {
+            Some("si") | Some("decimal") => SizeUnits::Decimal,
+            _ => SizeUnits::Binary,
+        };
+        Self {
+            units,
+            decimal_separator,
+        }
+    }
+
+    /// The environment locale, read once per process.
+    pub fn current() -> &'static Locale {
+        static CURRENT: OnceLock<Locale> = OnceLock::new();
+        CURRENT.get_or_init(Locale::from_env)
+    }
+
+    fn decimal(&self, value: f64) -> String {
+        let text = format!("{:.1}", value);
+        if self.decimal_separator == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    /// Scales `bytes` to the largest unit it reaches, e.g. `(1.5, "MiB")`.
+    fn scale(&self, bytes: u64) -> Option<(f64, &'static str)> {
+        let (base, units) = match self.units {
+            SizeUnits::Binary => (1024.0, ["KiB", "MiB", "GiB", "TiB"]),
+            SizeUnits::Decimal => (1000.0, ["kB", "MB", "GB", "TB"]),
+        };
+        let mut value = bytes as f64;
+        if value < base {
+            return None;
+        }
+        let mut unit = units[0];
+        value /= base;
+        for next in &units[1..] {
+            if value < base {
+                break;
+            }
+            value /= base;
+            unit = next;
+        }
+        Some((value, unit))
+    }
+
+    /// Formats a byte count, e.g. `512 B`, `1.5 MiB` or `2.0 GB`.
+    pub fn format_size(&self, bytes: u64) -> String {
+        match self.scale(bytes) {
+            None => format!("{} B", bytes),
+            Some((value, unit)) => format!("{} {}", self.decimal(value), unit),
+        }
+    }
+
+    /// Narrow form of [`Locale::format_size`] for tables, e.g. `512B` or `1.5M`.
+    pub fn format_size_compact(&self, bytes: u64) -> String {
+        match self.scale(bytes) {
+            None => format!("{}B", bytes),
+            Some((value, unit)) => {
+                format!("{}{}", self.decimal(value), &unit[..1].to_ascii_uppercase())
+            }
+        }
+    }
+
+    /// Formats an elapsed time in seconds, e.g. `4.2s`, `3m 12s` or `1h 05m`.
+    pub fn format_secs(&self, secs: f64) -> String {
+        let secs = secs.max(0.0);
+        if secs < 60.0 {
+            return format!("{}s", self.decimal(secs));
+        }
+        let whole = secs as u64;
+        if whole < 3600 {
+            format!("{}m {:02}s", whole / 60, whole % 60)
+        } else {
+            format!("{}h {:02}m", whole / 3600, (whole % 3600) / 60)
+        }
+    }
+}
+
+/// [`Locale::format_size`] in the C locale.
+pub fn format_size(bytes: u64) -> String {
+    Locale::C.format_size(bytes)
+}
+
+/// [`Locale::format_secs`] in the C locale.
+pub fn format_secs(secs: f64) -> String {
+    Locale::C.format_secs(secs)
+}
+
+/// Short duration for narrow tables and ETAs, e.g. `5m`, `2h`, `3d`.
+/// Negative durations are shown as `0s`.
+pub fn format_compact(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}d", secs / 86400)
+    }
+}
+
+/// Relative time for something that happened `duration` ago, e.g. `2 mins ago`.
+/// Negative durations (clock skew) are shown as `just now`.
+pub fn format_relative(duration: Duration) -> String {
+    let secs = duration.num_seconds();
+    if secs < 0 {
+        return "just now".to_string();
+    }
+    let (count, unit) = match secs {
+        0..=59 => return format!("{}s ago", secs),
+        60..=3599 => (secs / 60, "min"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(vars: &[(&str, &str)]) -> Locale {
+        Locale::from_vars(|key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_format_size_binary() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(format_size(2 * 1024u64.pow(4)), "2.0 TiB");
+    }
+
+    #[test]
+    fn test_format_size_locale() {
+        let de = locale(&[("LANG", "de_DE.UTF-8"), ("VIGIL_SIZE_UNITS", "si")]);
+        assert_eq!(de.format_size(1_500_000), "1,5 MB");
+        assert_eq!(de.format_size_compact(2_000_000_000), "2,0G");
+        assert_eq!(de.format_secs(4.21), "4,2s");
+        assert_eq!(Locale::C.format_size_compact(1536), "1.5K");
+        assert_eq!(Locale::C.format_size_compact(12), "12B");
+    }
+
+    #[test]
+    fn test_locale_precedence() {
+        assert_eq!(locale(&[]), Locale::C);
+        assert_eq!(locale(&[("LANG", "C.UTF-8")]), Locale::C);
+        let lc_all = locale(&[("LC_ALL", "en_US.UTF-8"), ("LANG", "fr_FR.UTF-8")]);
+        assert_eq!(lc_all.decimal_separator, '.');
+        let numeric = locale(&[("LC_ALL", ""), ("LC_NUMERIC", "nl_NL"), ("LANG", "en_GB")]);
+        assert_eq!(numeric.decimal_separator, ',');
+    }
+
+    #[test]
+    fn test_format_secs() {
+        assert_eq!(format_secs(-1.0), "0.0s");
+        assert_eq!(format_secs(4.21), "4.2s");
+        assert_eq!(format_secs(192.0), "3m 12s");
+        assert_eq!(format_secs(3900.0), "1h 05m");
+    }
+
+    #[test]
+    fn test_format_compact() {
+        assert_eq!(format_compact(Duration::seconds(-5)), "0s");
+        assert_eq!(format_compact(Duration::seconds(90)), "1m");
+        assert_eq!(format_compact(Duration::seconds(7200)), "2h");
+        assert_eq!(format_compact(Duration::seconds(259200)), "3d");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(Duration::seconds(0)), "0s ago");
+        assert_eq!(format_relative(Duration::seconds(59)), "59s ago");
+        assert_eq!(format_relative(Duration::seconds(60)), "1 min ago");
+        assert_eq!(format_relative(Duration::seconds(3599)), "59 mins ago");
+        assert_eq!(format_relative(Duration::seconds(3600)), "1 hour ago");
+        assert_eq!(format_relative(Duration::seconds(86399)), "23 hours ago");
+        assert_eq!(format_relative(Duration::seconds(86400)), "1 day ago");
+        assert_eq!(format_relative(Duration::seconds(604800)), "7 days ago");
+        assert_eq!(format_relative(Duration::seconds(-3600)), "just now");
+    }
+}
@@ -1,38 +1,355 @@
-use crate::types::{SetStatus, SnapshotInfo};
+use crate::attest::SignedAttestation;
+use crate::config::{ConfigOp, EffectiveConfig};
+use crate::types::{
+    BackupResult, BackupRun, CapabilityStatus, CompactStatus, FileEntry, HealthIssue, HealthLevel,
+    HistoryOp, RepairOutcome, RepairStep, RepoOpResult, RepoStats, RestoreRecord, Scanning,
+    SetInfo, SetStatus, SnapshotInfo, UnmountReason,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// JSON Schema (draft 2020-12) of the IPC wire format, generated from the
+/// types in this module. A message is either a `Request` or a `Response`;
+/// every nested type is listed under `$defs`.
+pub fn schema() -> serde_json::Value {
+    let mut generator = schemars::SchemaGenerator::default();
+    let request = generator.subschema_for::<Envelope<Request>>();
+    let response = generator.subschema_for::<Envelope<Response>>();
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "vigil IPC",
+        "description": "Newline-delimited JSON over the daemon's Unix socket. Clients send a Request per line, optionally with an `id`; the daemon replies with a Response carrying the same `id`, and also writes broadcast events (Response::Event, without an `id`) to the connection.",
+        "anyOf": [request, response],
+        "$defs": generator.take_definitions(true),
+    })
+}
+
+/// One line on the socket: a message plus the `id` that correlates a reply
+/// with its request. The daemon copies a request's `id` into every line of
+/// its reply; broadcast events carry none.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[schemars(rename = "{T}Line")]
+pub struct Envelope<T> {
+    /// Chosen by the client for a request and copied into every line of the
+    /// daemon's reply to it, so a client with several requests in flight can
+    /// tell the replies apart. `None` on requests sent without one and on
+    /// their replies, and on broadcast events, which answer no request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    /// The `Request` or `Response` itself, flattened into the same object.
+    #[serde(flatten)]
+    pub message: T,
+}
+
+impl<T> Envelope<T> {
+    /// Wraps `message` with `id`; pass the request's `id` for a reply and
+    /// `None` for a broadcast event.
+    pub fn new(id: Option<u64>, message: T) -> Self {
+        Self { id, message }
+    }
+}
 
 /// IPC Request from client (CLI/TUI) to daemon.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 #[serde(tag = "type", content = "payload")]
 pub enum Request {
     /// Get status of all backup sets.
     Status,
-    /// Trigger a backup. If set_name is None, all sets are backed up.
-    Backup { set_name: Option<String> },
+    /// Summarize the health of all sets for monitoring. Replies with `Health`.
+    Health,
+    /// One-line summary of all sets for status bars. Replies with `StatusLine`.
+    StatusLine,
+    /// Trigger a backup. If set_name is None, all sets are backed up. `tags`
+    /// are added to the configured tags of the snapshots this run creates.
+    Backup {
+        set_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+    },
+    /// Back up a set now, tagged `rescue`, ignoring the exclude patterns that
+    /// `include` lifts: patterns equal to an include pattern, or matching one
+    /// read as a path. The configuration is unchanged. Replies with
+    /// `RescueStarted`, then progress like `Backup`.
+    Rebackup {
+        set_name: String,
+        include: Vec<String>,
+    },
     /// Run retention cleanup. If set_name is None, all sets are pruned.
     Prune { set_name: Option<String> },
-    /// List snapshots for a specific set.
+    /// List the snapshots a prune of the set would forget under its
+    /// effective retention policy, without changing the repository.
+    /// Replies with `PrunePreview`.
+    PrunePreview { set_name: String },
+    /// Remove one snapshot of a set, named by its ID or a unique prefix, and
+    /// with `prune` the data only it referenced. Replies with `Forgotten`.
+    Forget {
+        set_name: String,
+        snapshot_id: String,
+        prune: bool,
+    },
+    /// Copy a set's snapshots that its `replica_target` lacks into it,
+    /// creating the replica if needed. Replies with `ReplicationComplete`.
+    Replicate { set_name: String },
+    /// List snapshots for a specific set, optionally only those carrying `tag`
+    /// and taken between `since` and `until` (both inclusive).
     Snapshots {
         set_name: String,
         limit: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<DateTime<Utc>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<DateTime<Utc>>,
+    },
+    /// Repository statistics of a set, with the size of each snapshot.
+    Stats { set_name: String },
+    /// Recorded backup runs of a set, oldest first; `limit` keeps the newest
+    /// of each kind. `ops` selects the kinds of records; empty means backups only.
+    History {
+        set_name: String,
+        limit: Option<usize>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        ops: Vec<HistoryOp>,
     },
     /// Mount a snapshot. If snapshot_id is None, the latest is mounted.
     Mount {
         set_name: String,
         snapshot_id: Option<String>,
     },
-    /// Unmount a set. If set_name is None, all sets are unmounted.
-    Unmount { set_name: Option<String> },
-    /// Request graceful daemon shutdown.
-    Shutdown,
+    /// Unmount a set's snapshots. With snapshot_id, only that snapshot of the
+    /// set is unmounted; if set_name is None, all sets are unmounted.
+    Unmount {
+        set_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        snapshot_id: Option<String>,
+    },
+    /// Request graceful daemon shutdown. Unless `force` is set, the daemon
+    /// refuses with `JobsRunning` while backups or prunes are in flight.
+    Shutdown {
+        #[serde(default)]
+        force: bool,
+    },
     /// Reload configuration from disk.
     ReloadConfig,
     /// Health check.
     Ping,
+    /// List optional system tools detected by the daemon.
+    Capabilities,
+    /// Get detailed information about a set, including which hosts write to its repository.
+    Info { set_name: String },
+    /// Build and sign an attestation of a set's repository for auditors.
+    /// Replies with `Attestation`.
+    Attest { set_name: String },
+    /// Initialize repositories. If set_name is None, all sets are initialized.
+    Init { set_name: Option<String> },
+    /// Quick accessibility check of repositories. If set_name is None, all sets are checked.
+    Check { set_name: Option<String> },
+    /// Deep repository check: verifies the structure and reads back `subset` of
+    /// the data (`5%`, `1/10` or a size such as `500M`). If set_name is None,
+    /// all sets are verified. Progress arrives as `VerifyProgress` events.
+    /// Sets outside their maintenance window are queued for it and reported
+    /// as `Deferred`, unless `now` is set.
+    Verify {
+        set_name: Option<String>,
+        subset: String,
+        #[serde(default)]
+        now: bool,
+    },
+    /// Run one step of a guided repair on a set's repository. restic output
+    /// arrives as `RepairProgress` events; the reply is `RepairResult`.
+    Repair { set_name: String, step: RepairStep },
+    /// Remove a set's stale repository locks, or every lock with `remove_all`
+    /// (`restic unlock --remove-all`). Refused while a backup writes to the
+    /// repository.
+    Unlock { set_name: String, remove_all: bool },
+    /// First step of a purge or reinit: announce the intent to delete a set's
+    /// repository. The daemon only purges a set in the config, or one removed
+    /// from it whose repository it recorded. Replies with `PurgeToken`.
+    PurgeToken { set_name: String },
+    /// Delete a set's repository, mount point and cached metadata, confirmed
+    /// by a token from `PurgeToken`. Replies with `Purged`.
+    Purge { set_name: String, token: String },
+    /// Replace a tracked set's local repository with a fresh one, confirmed by
+    /// a token from `PurgeToken`. The old repository is moved aside, or
+    /// deleted with `discard_old`. Replies with `Reinitialized`.
+    Reinit {
+        set_name: String,
+        token: String,
+        #[serde(default)]
+        discard_old: bool,
+    },
+    /// End the pause of a set's change-triggered backups after a restore
+    /// into its sources early. Fails unless the set is paused.
+    Resume { set_name: String },
+    /// Remove stale mount directories. With dry_run, only report what would be removed.
+    Clean { dry_run: bool },
+    /// Get a set's configuration after global fallbacks are applied.
+    EffectiveConfig { set_name: String },
+    /// Restore a snapshot (the latest if snapshot_id is None) into `target`, an
+    /// absolute path to a missing or empty directory. With `path`, only that
+    /// path from the snapshot is restored. Progress and the outcome arrive as events.
+    Restore {
+        set_name: String,
+        snapshot_id: Option<String>,
+        path: Option<String>,
+        target: String,
+    },
+    /// Stream one file from a snapshot ("latest" for the newest). Files larger
+    /// than `max_bytes` (capped by the daemon) are refused before any data is sent.
+    /// Replies with `FileChunk`s followed by `CatComplete`.
+    Cat {
+        set_name: String,
+        snapshot_id: String,
+        path: String,
+        max_bytes: Option<u64>,
+    },
+    /// List a directory of a snapshot without mounting it. `path` defaults to
+    /// `/`; `recursive` also lists everything below it.
+    ListFiles {
+        set_name: String,
+        snapshot_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        #[serde(default)]
+        recursive: bool,
+    },
+    /// List the paths that differ between two snapshots of a set. `snapshot_b`
+    /// defaults to the latest snapshot; either may be "latest".
+    Diff {
+        set_name: String,
+        snapshot_a: String,
+        snapshot_b: Option<String>,
+    },
+    /// Apply edits to the configuration file. The daemon serializes edits,
+    /// writes atomically, and reloads before replying.
+    ModifyConfig { ops: Vec<ConfigOp> },
+    /// Receive only the broadcast events that concern `sets` and have one of
+    /// `kinds` (e.g. `"BackupComplete"`) on this connection. `None` means no
+    /// restriction. Replaces any earlier subscription.
+    Subscribe {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sets: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kinds: Option<Vec<String>>,
+    },
+    /// Stop receiving broadcast events on this connection.
+    Unsubscribe,
+    /// Replace the daemon's log filter (`EnvFilter` syntax, e.g. `debug` or
+    /// `info,vigil_daemon::watcher=trace`) until the next restart.
+    SetLogLevel { filter: String },
+    /// Hand the listening socket over to a replacing daemon (`vigil-daemon
+    /// --replace`). The daemon saves its state, replies `HandoffReady` with
+    /// the socket attached (`SCM_RIGHTS`), stops accepting connections and
+    /// exits once its running jobs finish.
+    Handoff,
+    /// Sent by a replaced daemon to its replacement when a backup that kept
+    /// running after `Handoff` finishes. Only accepted from the replaced
+    /// daemon's process, for the sets `HandoffReady` listed.
+    HandoffResult {
+        set_name: String,
+        result: BackupResult,
+    },
 }
 
+impl Request {
+    /// The request's `type`, as named in `permissions.toml`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::Status => "Status",
+            Request::Health => "Health",
+            Request::StatusLine => "StatusLine",
+            Request::Backup { .. } => "Backup",
+            Request::Rebackup { .. } => "Rebackup",
+            Request::Prune { .. } => "Prune",
+            Request::PrunePreview { .. } => "PrunePreview",
+            Request::Forget { .. } => "Forget",
+            Request::Replicate { .. } => "Replicate",
+            Request::Snapshots { .. } => "Snapshots",
+            Request::Stats { .. } => "Stats",
+            Request::History { .. } => "History",
+            Request::Mount { .. } => "Mount",
+            Request::Unmount { .. } => "Unmount",
+            Request::Shutdown { .. } => "Shutdown",
+            Request::ReloadConfig => "ReloadConfig",
+            Request::Ping => "Ping",
+            Request::Capabilities => "Capabilities",
+            Request::Info { .. } => "Info",
+            Request::Attest { .. } => "Attest",
+            Request::Init { .. } => "Init",
+            Request::Check { .. } => "Check",
+            Request::Verify { .. } => "Verify",
+            Request::Repair { .. } => "Repair",
+            Request::Unlock { .. } => "Unlock",
+            Request::PurgeToken { .. } => "PurgeToken",
+            Request::Purge { .. } => "Purge",
+            Request::Reinit { .. } => "Reinit",
+            Request::Resume { .. } => "Resume",
+            Request::Clean { .. } => "Clean",
+            Request::EffectiveConfig { .. } => "EffectiveConfig",
+            Request::Restore { .. } => "Restore",
+            Request::Cat { .. } => "Cat",
+            Request::ListFiles { .. } => "ListFiles",
+            Request::Diff { .. } => "Diff",
+            Request::ModifyConfig { .. } => "ModifyConfig",
+            Request::Subscribe { .. } => "Subscribe",
+            Request::Unsubscribe => "Unsubscribe",
+            Request::SetLogLevel { .. } => "SetLogLevel",
+            Request::Handoff => "Handoff",
+            Request::HandoffResult { .. } => "HandoffResult",
+        }
+    }
+}
+
+/// Every request type, in the order of [`Request::kind`].
+pub const REQUEST_KINDS: &[&str] = &[
+    "Status",
+    "Health",
+    "StatusLine",
+    "Backup",
+    "Rebackup",
+    "Prune",
+    "PrunePreview",
+    "Forget",
+    "Replicate",
+    "Snapshots",
+    "Stats",
+    "History",
+    "Mount",
+    "Unmount",
+    "Shutdown",
+    "ReloadConfig",
+    "Ping",
+    "Capabilities",
+    "Info",
+    "Attest",
+    "Init",
+    "Check",
+    "Verify",
+    "Repair",
+    "Unlock",
+    "PurgeToken",
+    "Purge",
+    "Reinit",
+    "Resume",
+    "Clean",
+    "EffectiveConfig",
+    "Restore",
+    "Cat",
+    "ListFiles",
+    "Diff",
+    "ModifyConfig",
+    "Subscribe",
+    "Unsubscribe",
+    "SetLogLevel",
+    "Handoff",
+    "HandoffResult",
+];
+
 /// IPC Response from daemon to client.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 #[serde(tag = "type", content = "payload")]
 pub enum Response {
     /// Request succeeded.
@@ -41,23 +358,191 @@ pub enum Response {
     Error { code: String, message: String },
     /// Health check response.
     Pong,
+    /// Request needs an optional system tool that is not installed.
+    CapabilityMissing { name: String, hint: String },
+    /// A non-forced shutdown was refused because these sets have jobs in flight.
+    JobsRunning { sets: Vec<String> },
+    /// An event the daemon broadcasts to every connection, not a reply.
+    Event(ResponseData),
+}
+
+impl Response {
+    /// Whether this is an event the daemon broadcasts to every connection,
+    /// rather than a reply to a request sent on it.
+    pub fn is_broadcast(&self) -> bool {
+        matches!(self, Response::Event(_))
+    }
+
+    /// The `kind` of a broadcast event, as used by `Subscribe`.
+    pub fn event_kind(&self) -> Option<&'static str> {
+        match self {
+            Response::Event(data) => match data {
+                ResponseData::BackupStarted { .. } => Some("BackupStarted"),
+                ResponseData::BackupProgress { .. } => Some("BackupProgress"),
+                ResponseData::BackupComplete { .. } => Some("BackupComplete"),
+                ResponseData::BackupFailed { .. } => Some("BackupFailed"),
+                ResponseData::PruneComplete { .. } => Some("PruneComplete"),
+                ResponseData::ReplicationComplete { .. } => Some("ReplicationComplete"),
+                ResponseData::ReplicationFailed { .. } => Some("ReplicationFailed"),
+                ResponseData::RestoreProgress { .. } => Some("RestoreProgress"),
+                ResponseData::RestoreComplete { .. } => Some("RestoreComplete"),
+                ResponseData::RestoreFailed { .. } => Some("RestoreFailed"),
+                ResponseData::StatusInitialized { .. } => Some("StatusInitialized"),
+                ResponseData::VerifyProgress { .. } => Some("VerifyProgress"),
+                ResponseData::RepairProgress { .. } => Some("RepairProgress"),
+                ResponseData::Unmounted { .. } => Some("Unmounted"),
+                ResponseData::ShuttingDown { .. } => Some("ShuttingDown"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The set a broadcast event is about. `None` for daemon-wide events.
+    pub fn event_set(&self) -> Option<&str> {
+        match self {
+            Response::Event(
+                ResponseData::BackupStarted { set_name, .. }
+                | ResponseData::BackupProgress { set_name, .. }
+                | ResponseData::BackupComplete { set_name, .. }
+                | ResponseData::BackupFailed { set_name, .. }
+                | ResponseData::PruneComplete { set_name, .. }
+                | ResponseData::ReplicationComplete { set_name, .. }
+                | ResponseData::ReplicationFailed { set_name, .. }
+                | ResponseData::RestoreProgress { set_name, .. }
+                | ResponseData::RestoreComplete { set_name, .. }
+                | ResponseData::RestoreFailed { set_name, .. }
+                | ResponseData::VerifyProgress { set_name, .. }
+                | ResponseData::RepairProgress { set_name, .. }
+                | ResponseData::Unmounted { set_name, .. },
+            ) => Some(set_name),
+            _ => None,
+        }
+    }
+}
+
+/// Every broadcast event kind, in the order of [`Response::event_kind`].
+pub const EVENT_KINDS: &[&str] = &[
+    "BackupStarted",
+    "BackupProgress",
+    "BackupComplete",
+    "BackupFailed",
+    "PruneComplete",
+    "ReplicationComplete",
+    "ReplicationFailed",
+    "RestoreProgress",
+    "RestoreComplete",
+    "RestoreFailed",
+    "StatusInitialized",
+    "VerifyProgress",
+    "RepairProgress",
+    "Unmounted",
+    "ShuttingDown",
+];
+
+/// Which broadcast events a connection receives. The default lets every event
+/// through, which is what a connection gets until it sends `Subscribe`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    pub sets: Option<Vec<String>>,
+    pub kinds: Option<Vec<String>>,
+}
+
+impl EventFilter {
+    /// Builds a filter from a `Subscribe` request, rejecting unknown kinds.
+    pub fn new(sets: Option<Vec<String>>, kinds: Option<Vec<String>>) -> Result<Self, String> {
+        if let Some(unknown) = kinds
+            .iter()
+            .flatten()
+            .find(|kind| !EVENT_KINDS.contains(&kind.as_str()))
+        {
+            return Err(format!(
+                "Unknown event kind '{}' (expected one of: {})",
+                unknown,
+                EVENT_KINDS.join(", ")
+            ));
+        }
+        Ok(Self { sets, kinds })
+    }
+
+    /// Whether `event` passes the filter. Daemon-wide events such as
+    /// `StatusInitialized` pass any set restriction.
+    pub fn matches(&self, event: &Response) -> bool {
+        let Some(kind) = event.event_kind() else {
+            return false;
+        };
+        if let Some(ref kinds) = self.kinds {
+            if !kinds.iter().any(|k| k == kind) {
+                return false;
+            }
+        }
+        match (&self.sets, event.event_set()) {
+            (Some(sets), Some(set)) => sets.iter().any(|s| s == set),
+            _ => true,
+        }
+    }
 }
 
 /// Success data payload for an IPC response.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 #[serde(tag = "kind")]
 pub enum ResponseData {
     /// Status of all backup sets.
     Status { sets: Vec<SetStatus> },
+    /// Result of `Health`: the worst level of any issue, and every issue found.
+    Health {
+        overall: HealthLevel,
+        issues: Vec<HealthIssue>,
+    },
+    /// Result of `StatusLine`.
+    StatusLine { status: CompactStatus },
     /// List of snapshots.
     Snapshots { snapshots: Vec<SnapshotInfo> },
-    /// Confirmation that a backup set has started backing up.
-    BackupStarted { set_name: String },
+    /// Response to `Stats`; `snapshots` are ordered oldest first.
+    Stats {
+        stats: RepoStats,
+        snapshots: Vec<SnapshotInfo>,
+    },
+    /// Backup runs and restores of a set, each oldest first.
+    History {
+        runs: Vec<BackupRun>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        restores: Vec<RestoreRecord>,
+    },
+    /// Confirmation that a backup set has started backing up. Also broadcast
+    /// when a backup of the set actually begins, after any wait for a job slot.
+    BackupStarted {
+        set_name: String,
+        /// In the broadcast, the latest paths whose changes led to this
+        /// backup (at most 20). Empty in the reply, and when nothing changed
+        /// since the previous backup started.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        recent_changes: Vec<PathBuf>,
+    },
+    /// Confirmation that a rescue backup started, with the exclude patterns
+    /// it ignores.
+    RescueStarted {
+        set_name: String,
+        lifted_excludes: Vec<String>,
+    },
     /// Result of triggering backups for multiple sets.
     BackupsTriggered {
         started: Vec<String>,
         failed: Vec<(String, String)>, // (set_name, error_message)
     },
+    /// Progress of a running backup. `total_bytes` is 0 and `eta_secs` null
+    /// until restic has scanned enough of the set; `scanning` is set until
+    /// restic has found every file.
+    BackupProgress {
+        set_name: String,
+        percent: f64,
+        bytes_done: u64,
+        total_bytes: u64,
+        eta_secs: Option<u64>,
+        current_file: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scanning: Option<Scanning>,
+    },
     /// Confirmation that a backup operation has completed.
     BackupComplete {
         set_name: String,
@@ -67,13 +552,47 @@ pub enum ResponseData {
     },
     /// Notification that a backup operation failed.
     BackupFailed { set_name: String, error: String },
-    /// The local path where a snapshot was mounted.
-    MountPath { path: String },
+    /// The local path where a snapshot was mounted. `warming` is true while the
+    /// daemon prefetches the top directory levels in the background.
+    MountPath {
+        path: String,
+        #[serde(default)]
+        warming: bool,
+    },
+    /// Broadcast when a set's snapshot was unmounted, on request or after
+    /// its idle timeout.
+    Unmounted {
+        set_name: String,
+        /// Short ID of the unmounted snapshot, or `latest`.
+        #[serde(default)]
+        snapshot: String,
+        reason: UnmountReason,
+    },
+    /// Broadcast when the daemon starts shutting down. Backups of the
+    /// `running` sets get up to `grace_secs` to finish before they are
+    /// cancelled and re-run at the next start.
+    ShuttingDown {
+        grace_secs: u64,
+        running: Vec<String>,
+    },
     /// Result of a prune operation for a single set.
     PruneResult {
         set_name: String,
         reclaimed_bytes: u64,
     },
+    /// Result of `PrunePreview`: the snapshots that would be forgotten,
+    /// oldest first, and how many would be kept.
+    PrunePreview {
+        set_name: String,
+        to_remove: Vec<SnapshotInfo>,
+        to_keep: usize,
+    },
+    /// Result of `Forget`. `reclaimed_bytes` is only set when it pruned.
+    Forgotten {
+        set_name: String,
+        snapshot_id: String,
+        reclaimed_bytes: Option<u64>,
+    },
     /// Result of triggering prunes for multiple sets.
     PrunesTriggered {
         succeeded: Vec<(String, u64)>, // (set_name, reclaimed_bytes)
@@ -84,6 +603,135 @@ pub enum ResponseData {
         set_name: String,
         reclaimed_bytes: u64,
     },
+    /// A set's snapshots were copied to its replica, `copied` of them new.
+    /// The reply to `Replicate`, and broadcast after each replication.
+    ReplicationComplete {
+        set_name: String,
+        replica_target: String,
+        copied: usize,
+    },
+    /// Broadcast when copying a set's snapshots to its replica failed.
+    ReplicationFailed { set_name: String, error: String },
+    /// Availability of optional system tools.
+    Capabilities { capabilities: Vec<CapabilityStatus> },
+    /// Detailed information about a single set.
+    Info { info: Box<SetInfo> },
+    /// Signed attestation of a set, as `vigil attest` writes it.
+    Attestation { document: Box<SignedAttestation> },
+    /// Per-set results of an `Init` request.
+    InitResults { results: Vec<RepoOpResult> },
+    /// Per-set results of a `Check` request.
+    CheckResults { results: Vec<RepoOpResult> },
+    /// Per-set results of a `Verify` request.
+    VerifyResults { results: Vec<RepoOpResult> },
+    /// Progress of reading back repository data during `Verify`.
+    VerifyProgress {
+        set_name: String,
+        percent: f64,
+        eta_secs: Option<u64>,
+    },
+    /// Result of a `Repair` step.
+    RepairResult { outcome: RepairOutcome },
+    /// One line of restic output while a `Repair` step runs.
+    RepairProgress {
+        set_name: String,
+        step: RepairStep,
+        line: String,
+    },
+    /// Result of `Unlock`: locks removed and locks still held.
+    Unlocked {
+        set_name: String,
+        removed: usize,
+        remaining: usize,
+    },
+    /// Result of `PurgeToken`: the repository that would be deleted and the
+    /// single-use token confirming it, valid for `expires_in_secs`.
+    PurgeToken {
+        set_name: String,
+        target: String,
+        token: String,
+        expires_in_secs: u64,
+    },
+    /// Result of `Purge`. A remote repository is not deleted.
+    Purged {
+        set_name: String,
+        target: String,
+        repository_deleted: bool,
+    },
+    /// Result of `Reinit`: where the old repository was moved, unless it was
+    /// discarded.
+    Reinitialized {
+        set_name: String,
+        target: String,
+        archived_to: Option<String>,
+    },
+    /// Result of a mount directory cleanup.
+    CleanReport {
+        dry_run: bool,
+        removed: Vec<String>,
+        skipped: Vec<(String, String)>, // (path, reason)
+    },
+    /// Confirmation that a restore has started.
+    RestoreStarted {
+        set_name: String,
+        snapshot_id: String,
+        target: String,
+    },
+    /// Progress of a running restore. `eta_secs` is null until enough has been restored.
+    RestoreProgress {
+        set_name: String,
+        target: String,
+        percent: f64,
+        eta_secs: Option<u64>,
+    },
+    /// Notification that a restore completed.
+    RestoreComplete {
+        set_name: String,
+        target: String,
+        files_restored: u64,
+        bytes_restored: u64,
+        duration_secs: f64,
+    },
+    /// Notification that a restore failed.
+    RestoreFailed {
+        set_name: String,
+        target: String,
+        error: String,
+    },
+    /// A piece of a file streamed by `Cat`.
+    FileChunk {
+        #[serde(with = "hex_bytes")]
+        #[schemars(with = "String")]
+        data: Vec<u8>,
+    },
+    /// End of a `Cat` stream.
+    CatComplete { bytes: u64 },
+    /// Contents of a snapshot directory in the order restic walks it.
+    /// `truncated` is set when the listing stopped at the daemon's limit.
+    FileList {
+        entries: Vec<FileEntry>,
+        truncated: bool,
+    },
+    /// Paths that differ between two snapshots, `snapshot_a` being the older.
+    Diff {
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    },
+    /// Resolved configuration of a single set.
+    EffectiveConfig { config: Box<EffectiveConfig> },
+    /// Notification that the startup status refresh of all sets finished.
+    StatusInitialized { sets: usize, duration_secs: f64 },
+    /// The daemon's log filter after `SetLogLevel`, and the one it replaced.
+    LogLevel { filter: String, previous: String },
+    /// Reply to `Handoff`, carrying the listening socket as ancillary data.
+    /// `running` lists the sets whose backups keep running in the replaced
+    /// daemon; it sends their results with `HandoffResult`.
+    HandoffReady {
+        pid: u32,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        running: Vec<String>,
+    },
 }
 
 /// Common error codes used in IPC error responses.
@@ -95,4 +743,23 @@ pub mod error_codes {
     pub const NOT_MOUNTED: &str = "NotMounted";
     pub const DAEMON_BUSY: &str = "DaemonBusy";
     pub const INVALID_REQUEST: &str = "InvalidRequest";
+    pub const CONFIG_INVALID: &str = "ConfigInvalid";
+    pub const RESTORE_FAILED: &str = "RestoreFailed";
+    pub const FILE_TOO_LARGE: &str = "FileTooLarge";
+    pub const APPEND_ONLY: &str = "AppendOnly";
+    pub const PERMISSION_DENIED: &str = "PermissionDenied";
+}
+
+/// Encodes bytes as a hex string so binary data stays valid line-delimited JSON.
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        hex::decode(encoded).map_err(serde::de::Error::custom)
+    }
 }
@@ -1,9 +1,14 @@
 //! Shared library for vigil.
 //! Includes config parsing, type definitions, and IPC message types.
 
+pub mod attest;
 pub mod config;
+pub mod humanize;
 pub mod ipc;
 pub mod paths;
+pub mod permissions;
+pub mod platform;
+pub mod transport;
 pub mod types;
 
 #[cfg(test)]
@@ -38,12 +43,30 @@ mod tests {
                 duration_secs: 5.5,
                 success: true,
                 error_message: None,
+                peak_rss_bytes: Some(48 * 1024 * 1024),
+                cpu_secs: Some(2.5),
+                hooks: Vec::new(),
             }),
             source_paths: vec![PathBuf::from("/home/user/docs")],
             target: PathBuf::from("/mnt/backup"),
             is_mounted: false,
+            mount_warming: false,
+            mounts: Vec::new(),
+            pending_changes: 0,
             snapshot_count: Some(5),
             total_bytes: Some(1024 * 1024),
+            initializing: false,
+            next_run: None,
+            last_verified: None,
+            verification_stale: false,
+            append_only: false,
+            skipped_trivial: None,
+            target_free_bytes: None,
+            growth_bytes_per_day: None,
+            watch_events_coalesced: 0,
+            watch_events_dropped: 0,
+            recent_changes: Vec::new(),
+            replica_lag: None,
         };
 
         let resp = Response::Ok(Some(ResponseData::Status { sets: vec![status] }));
@@ -57,13 +80,317 @@ mod tests {
     fn test_ipc_roundtrip_backup_request() {
         let req = Request::Backup {
             set_name: Some("personal".to_string()),
+            tags: vec![],
         };
         let json = serde_json::to_string(&req).unwrap();
         assert!(json.contains("\"type\":\"Backup\""));
         assert!(json.contains("\"set_name\":\"personal\""));
+        assert!(!json.contains("tags"));
 
         let decoded: Request = serde_json::from_str(&json).unwrap();
         assert_eq!(req, decoded);
+
+        let req = Request::Backup {
+            set_name: None,
+            tags: vec!["pre-upgrade".to_string()],
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        let decoded: Request = serde_json::from_str(&json).unwrap();
+        assert_eq!(req, decoded);
+    }
+
+    #[test]
+    fn test_ipc_roundtrip_snapshots_range() {
+        let req = Request::Snapshots {
+            set_name: "home".to_string(),
+            limit: Some(10),
+            tag: None,
+            since: Some(Utc::now()),
+            until: None,
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(!json.contains("until"));
+        assert_eq!(req, serde_json::from_str::<Request>(&json).unwrap());
+
+        // Requests from older clients carry no range
+        let decoded: Request =
+            serde_json::from_str(r#"{"type":"Snapshots","payload":{"set_name":"home","limit":5}}"#)
+                .unwrap();
+        assert!(matches!(
+            decoded,
+            Request::Snapshots {
+                since: None,
+                until: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_ipc_roundtrip_shutdown() {
+        let req = Request::Shutdown { force: true };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(req, serde_json::from_str::<Request>(&json).unwrap());
+
+        let decoded: Request = serde_json::from_str(r#"{"type":"Shutdown","payload":{}}"#).unwrap();
+        assert_eq!(decoded, Request::Shutdown { force: false });
+
+        let resp = Response::JobsRunning {
+            sets: vec!["home".to_string()],
+        };
+        let json = serde_json::to_string(&resp).unwrap();
+        assert_eq!(resp, serde_json::from_str::<Response>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_ipc_roundtrip_capabilities() {
+        let resp = Response::Ok(Some(ResponseData::Capabilities {
+            capabilities: vec![CapabilityStatus {
+                name: "fusermount3".to_string(),
+                available: false,
+                hint: Some("Install FUSE 3".to_string()),
+            }],
+        }));
+        let json = serde_json::to_string(&resp).unwrap();
+        let decoded: Response = serde_json::from_str(&json).unwrap();
+        assert_eq!(resp, decoded);
+
+        let missing = Response::CapabilityMissing {
+            name: "fusermount3".to_string(),
+            hint: "Install FUSE 3".to_string(),
+        };
+        let json = serde_json::to_string(&missing).unwrap();
+        assert!(json.contains(r#""type":"CapabilityMissing""#));
+        let decoded: Response = serde_json::from_str(&json).unwrap();
+        assert_eq!(missing, decoded);
+    }
+
+    #[test]
+    fn test_ipc_roundtrip_info() {
+        let req = Request::Info {
+            set_name: "laptop".to_string(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(req, serde_json::from_str::<Request>(&json).unwrap());
+
+        let resp = Response::Ok(Some(ResponseData::Info {
+            info: Box::new(SetInfo {
+                status: SetStatus {
+                    name: "laptop".to_string(),
+                    state: JobState::Idle,
+                    last_backup: None,
+                    source_paths: vec![PathBuf::from("/home/user")],
+                    target: PathBuf::from("/mnt/nas/repo"),
+                    is_mounted: false,
+                    mount_warming: false,
+                    mounts: Vec::new(),
+                    pending_changes: 0,
+                    snapshot_count: Some(3),
+                    total_bytes: None,
+                    initializing: false,
+                    next_run: None,
+                    last_verified: None,
+                    verification_stale: false,
+                    append_only: false,
+                    skipped_trivial: None,
+                    target_free_bytes: None,
+                    growth_bytes_per_day: None,
+                    watch_events_coalesced: 0,
+                    watch_events_dropped: 0,
+                    recent_changes: Vec::new(),
+                    replica_lag: None,
+                },
+                shared_repo: false,
+                local_hostname: "laptop".to_string(),
+                hosts: vec![HostSnapshots {
+                    hostname: "desktop".to_string(),
+                    snapshot_count: 3,
+                    latest: Utc::now(),
+                    is_local: false,
+                }],
+                pending_paths: vec!["/home/user/notes.txt".to_string()],
+                retention: Some(crate::config::RetentionPolicy {
+                    keep_last: Some(10),
+                    ..Default::default()
+                }),
+            }),
+        }));
+        let json = serde_json::to_string(&resp).unwrap();
+        assert!(json.contains(r#""kind":"Info""#));
+        assert_eq!(resp, serde_json::from_str::<Response>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_file_chunk_is_hex_encoded() {
+        let resp = Response::Ok(Some(ResponseData::FileChunk {
+            data: vec![0x00, 0xff, b'\n'],
+        }));
+        let json = serde_json::to_string(&resp).unwrap();
+        assert!(json.contains(r#""data":"00ff0a""#));
+        assert_eq!(resp, serde_json::from_str::<Response>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_is_broadcast() {
+        assert!(Response::Event(ResponseData::BackupFailed {
+            set_name: "home".into(),
+            error: "boom".into(),
+        })
+        .is_broadcast());
+        assert!(!Response::Ok(Some(ResponseData::BackupStarted {
+            set_name: "home".into(),
+            recent_changes: Vec::new(),
+        }))
+        .is_broadcast());
+        // Event data in a reply is not an event
+        assert!(!Response::Ok(Some(ResponseData::BackupFailed {
+            set_name: "home".into(),
+            error: "boom".into(),
+        }))
+        .is_broadcast());
+        assert!(!Response::Ok(None).is_broadcast());
+        assert!(!Response::Pong.is_broadcast());
+    }
+
+    #[test]
+    fn test_envelope_correlates_replies() {
+        let req: Envelope<Request> =
+            serde_json::from_str(r#"{"id":7,"type":"Info","payload":{"set_name":"home"}}"#)
+                .unwrap();
+        assert_eq!(req.id, Some(7));
+        assert_eq!(
+            req.message,
+            Request::Info {
+                set_name: "home".to_string()
+            }
+        );
+        // Requests without an id stay valid
+        let req: Envelope<Request> = serde_json::from_str(r#"{"type":"Ping"}"#).unwrap();
+        assert_eq!(req, Envelope::new(None, Request::Ping));
+
+        let reply = Envelope::new(Some(7), Response::Pong);
+        let json = serde_json::to_string(&reply).unwrap();
+        assert_eq!(json, r#"{"id":7,"type":"Pong"}"#);
+        assert_eq!(
+            reply,
+            serde_json::from_str::<Envelope<Response>>(&json).unwrap()
+        );
+
+        let event = Envelope::new(
+            None,
+            Response::Event(ResponseData::BackupFailed {
+                set_name: "home".into(),
+                error: "boom".into(),
+            }),
+        );
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with(r#"{"type":"Event","payload":{"kind":"BackupFailed""#));
+        assert_eq!(
+            event,
+            serde_json::from_str::<Envelope<Response>>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ipc_schema_snapshot() {
+        let expected = include_str!("../schema/ipc.json");
+        let actual = serde_json::to_string_pretty(&schema()).unwrap() + "\n";
+        assert!(
+            actual == expected,
+            "The IPC wire format changed. Regenerate the snapshot with `cargo run -p vigil -- schema ipc > crates/vigil-lib/schema/ipc.json` and review the diff."
+        );
+    }
+
+    #[test]
+    fn test_request_kinds_match_wire_types() {
+        let schema = schema();
+        let wire: Vec<&str> = schema["$defs"]["RequestLine"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant["properties"]["type"]["const"].as_str().unwrap())
+            .collect();
+        assert_eq!(wire, REQUEST_KINDS);
+
+        assert_eq!(Request::Ping.kind(), "Ping");
+        let backup = Request::Backup {
+            set_name: None,
+            tags: vec![],
+        };
+        assert_eq!(
+            serde_json::to_value(&backup).unwrap()["type"],
+            backup.kind()
+        );
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let failed = |set: &str| {
+            Response::Event(ResponseData::BackupFailed {
+                set_name: set.into(),
+                error: "boom".into(),
+            })
+        };
+        let initialized = Response::Event(ResponseData::StatusInitialized {
+            sets: 2,
+            duration_secs: 0.1,
+        });
+        assert!(EventFilter::default().matches(&failed("home")));
+        assert!(!EventFilter::default().matches(&Response::Pong));
+
+        let filter = EventFilter::new(Some(vec!["home".into()]), None).unwrap();
+        assert!(filter.matches(&failed("home")));
+        assert!(!filter.matches(&failed("work")));
+        // Daemon-wide events are not tied to a set
+        assert!(filter.matches(&initialized));
+
+        let filter = EventFilter::new(None, Some(vec!["BackupComplete".into()])).unwrap();
+        assert!(!filter.matches(&failed("home")));
+        assert!(!filter.matches(&initialized));
+        assert!(EventFilter::new(None, Some(vec!["Backup".into()])).is_err());
+
+        let req: Request = serde_json::from_str(r#"{"type":"Subscribe","payload":{}}"#).unwrap();
+        assert_eq!(
+            req,
+            Request::Subscribe {
+                sets: None,
+                kinds: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_snapshot_filter() {
+        let now = Utc::now();
+        let snapshot = SnapshotInfo {
+            id: "a".repeat(64),
+            short_id: "aaaaaaaa".to_string(),
+            timestamp: now,
+            paths: vec![PathBuf::from("/home")],
+            tags: vec!["daily".to_string()],
+            total_bytes: None,
+            hostname: None,
+        };
+        let hour = chrono::Duration::hours(1);
+        assert!(SnapshotFilter::default().matches(&snapshot));
+        let range = SnapshotFilter {
+            since: Some(now - hour),
+            until: Some(now),
+            ..Default::default()
+        };
+        assert!(range.matches(&snapshot));
+        let later = SnapshotFilter {
+            since: Some(now + hour),
+            ..Default::default()
+        };
+        assert!(!later.matches(&snapshot));
+        let tagged = SnapshotFilter {
+            tag: Some("weekly".to_string()),
+            until: Some(now + hour),
+            ..Default::default()
+        };
+        assert!(!tagged.is_empty());
+        assert!(!tagged.matches(&snapshot));
     }
 
     #[test]
@@ -71,7 +398,25 @@ mod tests {
         let states = vec![
             JobState::Idle,
             JobState::Debouncing { remaining_secs: 45 },
-            JobState::Running,
+            JobState::running(),
+            JobState::Running {
+                percent: Some(42.5),
+                eta_secs: Some(180),
+                scanning: None,
+            },
+            JobState::Running {
+                percent: None,
+                eta_secs: None,
+                scanning: Some(Scanning {
+                    files: 52000,
+                    bytes: 9_000_000_000,
+                }),
+            },
+            JobState::Scheduled {
+                at: chrono::DateTime::parse_from_rfc3339("2026-10-17T02:00:00Z")
+                    .unwrap()
+                    .with_timezone(&chrono::Utc),
+            },
             JobState::Error,
         ];
 
@@ -87,6 +432,7 @@ mod tests {
         // Test Request format matches spec: {"type":"Backup","payload":{"set_name":"personal"}}
         let req = Request::Backup {
             set_name: Some("personal".to_string()),
+            tags: vec![],
         };
         let json = serde_json::to_string(&req).unwrap();
         println!("\nActual Backup request: {}", json);
@@ -102,6 +448,7 @@ mod tests {
         // Test Response format matches spec: {"type":"Ok","payload":{"kind":"BackupStarted",...}}
         let resp = Response::Ok(Some(ResponseData::BackupStarted {
             set_name: "personal".to_string(),
+            recent_changes: Vec::new(),
         }));
         let json = serde_json::to_string(&resp).unwrap();
         println!("Actual BackupStarted: {}", json);
@@ -110,16 +457,29 @@ mod tests {
             json.contains(r#""kind":"BackupStarted""#),
             "Should have kind:BackupStarted"
         );
+        assert!(!json.contains("recent_changes"), "Empty list is omitted");
+        let started: Response = serde_json::from_str(
+            r#"{"type":"Event","payload":{"kind":"BackupStarted","set_name":"personal","recent_changes":["/home/u/a.txt"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            started,
+            Response::Event(ResponseData::BackupStarted {
+                set_name: "personal".to_string(),
+                recent_changes: vec!["/home/u/a.txt".into()],
+            })
+        );
 
         // Test BackupComplete response
-        let complete = Response::Ok(Some(ResponseData::BackupComplete {
+        let complete = Response::Event(ResponseData::BackupComplete {
             set_name: "personal".to_string(),
             snapshot_id: "a1b2c3d4".to_string(),
             added_bytes: 1048576,
             duration_secs: 4.2,
-        }));
+        });
         let json = serde_json::to_string(&complete).unwrap();
         println!("Actual BackupComplete: {}", json);
+        assert!(json.contains(r#""type":"Event""#), "Should have type:Event");
         assert!(
             json.contains(r#""kind":"BackupComplete""#),
             "Should have kind:BackupComplete"
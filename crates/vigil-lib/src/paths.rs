@@ -31,6 +31,18 @@ pub fn password_path() -> PathBuf {
     config_dir().join(".repo_password")
 }
 
+/// Returns the per-set password file written by `vigil password`:
+/// `~/.config/vigil/passwords/<set-name>`
+pub fn set_password_path(set_name: &str) -> PathBuf {
+    config_dir().join("passwords").join(set_name)
+}
+
+/// Returns the file limiting which requests each client program may send:
+/// `~/.config/vigil/permissions.toml`
+pub fn permissions_path() -> PathBuf {
+    config_dir().join("permissions.toml")
+}
+
 /// Returns the active configuration path, respecting `VIGIL_CONFIG` environment variable.
 pub fn active_config_path() -> PathBuf {
     std::env::var("VIGIL_CONFIG")
@@ -38,8 +50,8 @@ pub fn active_config_path() -> PathBuf {
         .unwrap_or_else(|_| config_path())
 }
 
-/// Returns the log file path: `~/.local/share/vigil/vigil.log`
-pub fn log_path() -> PathBuf {
+/// Returns the data directory: `~/.local/share/vigil/`
+pub fn data_dir() -> PathBuf {
     project_dirs()
         .map(|d| d.data_dir().to_path_buf())
         .unwrap_or_else(|| {
@@ -51,45 +63,103 @@ pub fn log_path() -> PathBuf {
             path.push("vigil");
             path
         })
-        .join("vigil.log")
+}
+
+/// Returns the log file path: `~/.local/share/vigil/vigil.log`
+pub fn log_path() -> PathBuf {
+    data_dir().join("vigil.log")
+}
+
+/// Returns the per-set snapshot statistics cache: `~/.local/share/vigil/cache/<set>/snapshot_stats.json`
+pub fn snapshot_stats_cache_path(set_name: &str) -> PathBuf {
+    data_dir()
+        .join("cache")
+        .join(set_name)
+        .join("snapshot_stats.json")
+}
+
+/// Returns the per-set journal of changes not yet backed up: `~/.local/share/vigil/journal/<set>.json`
+pub fn change_journal_path(set_name: &str) -> PathBuf {
+    data_dir()
+        .join("journal")
+        .join(format!("{}.json", set_name))
+}
+
+/// Returns the changes captured by a backup that keeps running in a replaced
+/// daemon, kept apart from the set's journal until the replacement learns how
+/// the backup went: `~/.local/share/vigil/journal/<set>.handoff.json`
+pub fn handoff_journal_path(set_name: &str) -> PathBuf {
+    data_dir()
+        .join("journal")
+        .join(format!("{}.handoff.json", set_name))
+}
+
+/// Returns the per-set log of backup runs: `~/.local/share/vigil/history/<set>.jsonl`
+pub fn history_path(set_name: &str) -> PathBuf {
+    data_dir()
+        .join("history")
+        .join(format!("{}.jsonl", set_name))
+}
+
+/// Returns the per-set log of restores from the set's snapshots: `~/.local/share/vigil/history/<set>.restores.jsonl`
+pub fn restore_history_path(set_name: &str) -> PathBuf {
+    data_dir()
+        .join("history")
+        .join(format!("{}.restores.jsonl", set_name))
+}
+
+/// Returns the per-set directory of full engine output, one file per backup run: `~/.local/share/vigil/runs/<set>/`
+pub fn run_log_dir(set_name: &str) -> PathBuf {
+    data_dir().join("runs").join(set_name)
+}
+
+/// Returns the per-set record of the last deep verification: `~/.local/share/vigil/verify/<set>.json`
+pub fn verification_path(set_name: &str) -> PathBuf {
+    data_dir().join("verify").join(format!("{}.json", set_name))
+}
+
+/// Returns the key the daemon signs attestations with: `~/.local/share/vigil/attest.key`
+pub fn attestation_key_path() -> PathBuf {
+    data_dir().join("attest.key")
+}
+
+/// Returns the daemon's per-set results kept across restarts: `~/.local/share/vigil/state.json`
+pub fn daemon_state_path() -> PathBuf {
+    data_dir().join("state.json")
 }
 
 /// Returns the Unix socket path.
 /// Respects `$XDG_RUNTIME_DIR/vigil.sock` with fallback to `/tmp/vigil-$UID.sock`.
+/// Without Unix sockets the file holds the daemon's localhost TCP address.
 pub fn socket_path() -> PathBuf {
-    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-        PathBuf::from(runtime_dir).join("vigil.sock")
-    } else {
-        let uid = unsafe { libc::getuid() };
-        PathBuf::from(format!("/tmp/vigil-{}.sock", uid))
-    }
+    runtime_file("sock")
 }
 
 /// Returns the PID file path.
 /// Respects `$XDG_RUNTIME_DIR/vigil.pid` with fallback to `/tmp/vigil-$UID.pid`.
 pub fn pid_path() -> PathBuf {
+    runtime_file("pid")
+}
+
+/// `vigil.<ext>` in the runtime directory, or `vigil-<user>.<ext>` in the
+/// temporary directory without one.
+fn runtime_file(ext: &str) -> PathBuf {
     if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-        PathBuf::from(runtime_dir).join("vigil.pid")
+        PathBuf::from(runtime_dir).join(format!("vigil.{}", ext))
     } else {
-        let uid = unsafe { libc::getuid() };
-        PathBuf::from(format!("/tmp/vigil-{}.pid", uid))
+        std::env::temp_dir().join(format!("vigil-{}.{}", crate::platform::user_id(), ext))
     }
 }
 
+/// Returns the paths the daemon writes to while it runs: the data directory
+/// (log, caches, journals, mounts) and the socket and PID files.
+pub fn own_paths() -> Vec<PathBuf> {
+    vec![data_dir(), socket_path(), pid_path()]
+}
+
 /// Returns the base directory for FUSE mounts: `~/.local/share/vigil/mnt/`
 pub fn mount_base_dir() -> PathBuf {
-    project_dirs()
-        .map(|d| d.data_dir().to_path_buf())
-        .unwrap_or_else(|| {
-            let mut path = std::env::var_os("HOME")
-                .map(PathBuf::from)
-                .unwrap_or_else(|| PathBuf::from("/tmp"));
-            path.push(".local");
-            path.push("share");
-            path.push("vigil");
-            path
-        })
-        .join("mnt")
+    data_dir().join("mnt")
 }
 
 /// Returns the mount path for a specific backup set.
@@ -97,41 +167,74 @@ pub fn mount_path(set_name: &str) -> PathBuf {
     mount_base_dir().join(set_name)
 }
 
-/// Checks if the given path is a current mount point by reading /proc/mounts.
-/// This is used to synchronize daemon state with the filesystem on restart.
-pub fn is_mount_point(path: &std::path::Path) -> bool {
-    if !path.exists() {
-        return false;
+/// Returns the name a snapshot is mounted under: its short ID, or `latest`
+/// when no snapshot was given.
+pub fn mount_key(snapshot_id: Option<&str>) -> String {
+    match snapshot_id {
+        Some(id) => id.chars().take(8).collect(),
+        None => "latest".to_string(),
     }
+}
 
-    let target = match path.canonicalize() {
-        Ok(p) => p,
-        Err(_) => return false,
-    };
-
-    let mounts = match std::fs::read_to_string("/proc/mounts") {
-        Ok(s) => s,
-        Err(_) => return false,
-    };
-
-    for line in mounts.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let mount_point = parts[1];
-            // Restic mounts appear as 'restic' or similar in /proc/mounts
-            if let Ok(p) = std::path::Path::new(mount_point).canonicalize() {
-                if p == target {
-                    return true;
-                }
-            }
-        }
-    }
+/// Returns where a snapshot of a set is mounted: `mnt/<set>/<snapshot>`, so
+/// several snapshots of one set can be browsed at once.
+pub fn snapshot_mount_path(set_name: &str, snapshot_id: Option<&str>) -> PathBuf {
+    mount_path(set_name).join(mount_key(snapshot_id))
+}
 
-    false
+/// Returns the base directory for filesystem snapshots taken during backup:
+/// `~/.local/share/vigil/fs-snapshots/`
+pub fn fs_snapshot_base_dir() -> PathBuf {
+    data_dir().join("fs-snapshots")
+}
+
+/// Returns where LVM snapshots of a set are mounted during backup:
+/// `~/.local/share/vigil/fs-snapshots/<set>/`
+pub fn fs_snapshot_dir(set_name: &str) -> PathBuf {
+    fs_snapshot_base_dir().join(set_name)
+}
+
+/// Returns every directory vigil mounts snapshots under: FUSE mounts of
+/// repositories and filesystem snapshots read during backup.
+pub fn mount_roots() -> Vec<PathBuf> {
+    vec![mount_base_dir(), fs_snapshot_base_dir()]
+}
+
+/// Checks if the given path is a current mount point (see
+/// [`crate::platform::is_mount_point`]). This is used to synchronize daemon
+/// state with the filesystem on restart.
+pub fn is_mount_point(path: &std::path::Path) -> bool {
+    crate::platform::is_mount_point(path)
+}
+
+/// Directory `vigil self-install` copies the binaries to: `$XDG_BIN_HOME`,
+/// or `~/.local/bin`
+pub fn user_bin_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .and_then(|d| d.executable_dir().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| {
+            let mut path = std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("/tmp"));
+            path.push(".local");
+            path.push("bin");
+            path
+        })
 }
 
 /// Returns the path to the systemd user unit: `~/.config/systemd/user/vigil-daemon.service`
 pub fn systemd_unit_path() -> PathBuf {
+    systemd_user_dir().join("vigil-daemon.service")
+}
+
+/// Returns the path to the systemd socket unit that starts the daemon on
+/// demand: `~/.config/systemd/user/vigil-daemon.socket`
+pub fn systemd_socket_unit_path() -> PathBuf {
+    systemd_user_dir().join("vigil-daemon.socket")
+}
+
+/// `~/.config/systemd/user/`
+fn systemd_user_dir() -> PathBuf {
     let mut path = project_dirs()
         .map(|d| d.config_dir().to_path_buf()) // This is ~/.config/vigil
         .and_then(|p| p.parent().map(|p| p.to_path_buf())) // This is ~/.config
@@ -144,7 +247,6 @@ pub fn systemd_unit_path() -> PathBuf {
         });
     path.push("systemd");
     path.push("user");
-    path.push("vigil-daemon.service");
     path
 }
 
@@ -165,6 +267,27 @@ mod tests {
         assert!(log_path().ends_with("vigil/vigil.log"));
     }
 
+    #[test]
+    fn test_snapshot_stats_cache_path() {
+        let p = snapshot_stats_cache_path("test");
+        assert!(p.starts_with(data_dir()));
+        assert!(p.ends_with("cache/test/snapshot_stats.json"));
+    }
+
+    #[test]
+    fn test_change_journal_path() {
+        let p = change_journal_path("test");
+        assert!(p.starts_with(data_dir()));
+        assert!(p.ends_with("journal/test.json"));
+    }
+
+    #[test]
+    fn test_verification_path() {
+        let p = verification_path("test");
+        assert!(p.starts_with(data_dir()));
+        assert!(p.ends_with("verify/test.json"));
+    }
+
     #[test]
     fn test_socket_pid_paths() {
         // Just verify they don't panic and look reasonable
@@ -179,12 +302,17 @@ mod tests {
         let base = mount_base_dir();
         assert!(base.ends_with("vigil/mnt"));
         assert!(mount_path("test").ends_with("vigil/mnt/test"));
+        assert!(snapshot_mount_path("test", None).ends_with("vigil/mnt/test/latest"));
+        assert!(snapshot_mount_path("test", Some("0123456789abcdef"))
+            .ends_with("vigil/mnt/test/01234567"));
+        assert!(fs_snapshot_dir("test").ends_with("vigil/fs-snapshots/test"));
     }
 
     #[test]
     fn test_systemd_path() {
         let p = systemd_unit_path();
         assert!(p.ends_with("systemd/user/vigil-daemon.service"));
+        assert_eq!(systemd_socket_unit_path().parent(), p.parent());
     }
 
     #[test]
@@ -0,0 +1,169 @@
+//! Optional limits on the IPC requests each client program may send, read
+//! from `~/.config/vigil/permissions.toml`. Without the file every client may
+//! send every request.
+
+use crate::config::{expand_home, ConfigError};
+use crate::ipc::REQUEST_KINDS;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Stands for every request type in an `allow` or `default` list.
+pub const ALL_REQUESTS: &str = "*";
+
+/// Requests every client may send, whatever the file says.
+const ALWAYS_ALLOWED: &[&str] = &["Ping"];
+
+/// Contents of `permissions.toml`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Permissions {
+    /// Requests allowed to clients no `[[client]]` entry matches. Omitted,
+    /// they may only send `Ping`.
+    #[serde(default)]
+    pub default: Vec<String>,
+    #[serde(default, rename = "client")]
+    pub clients: Vec<ClientRule>,
+}
+
+/// Requests one program may send. The first entry naming a client's
+/// executable applies.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ClientRule {
+    /// Absolute path of the program, e.g. `/usr/bin/python3`. Scripts run
+    /// through their interpreter, so name the interpreter.
+    pub exe: PathBuf,
+    pub allow: Vec<String>,
+}
+
+impl Permissions {
+    /// Reads the permissions file at `path`; `None` if there is none.
+    pub fn load(path: &Path) -> Result<Option<Self>, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Parses and validates the text of a permissions file, expanding `~/`
+    /// in executable paths.
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut permissions: Permissions = toml::from_str(text)?;
+        for client in &mut permissions.clients {
+            client.exe = PathBuf::from(expand_home(&client.exe.to_string_lossy()));
+            if !client.exe.is_absolute() {
+                return Err(ConfigError::Validation(format!(
+                    "Client executable '{}' must be an absolute path",
+                    client.exe.display()
+                )));
+            }
+        }
+        let lists = std::iter::once(&permissions.default)
+            .chain(permissions.clients.iter().map(|c| &c.allow));
+        for kind in lists.flatten() {
+            if kind != ALL_REQUESTS && !REQUEST_KINDS.contains(&kind.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "Unknown request type '{}' (expected \"*\" or one of: {})",
+                    kind,
+                    REQUEST_KINDS.join(", ")
+                )));
+            }
+        }
+        Ok(permissions)
+    }
+
+    /// Whether a client running `exe` may send a request of type `kind`.
+    /// Clients whose executable is unknown get the `default` list.
+    pub fn allows(&self, exe: Option<&Path>, kind: &str) -> bool {
+        if ALWAYS_ALLOWED.contains(&kind) {
+            return true;
+        }
+        let allowed = exe
+            .and_then(|exe| self.clients.iter().find(|c| c.matches(exe)))
+            .map_or(&self.default, |c| &c.allow);
+        allowed.iter().any(|k| k == ALL_REQUESTS || k == kind)
+    }
+}
+
+impl ClientRule {
+    /// Whether the rule names `exe`, a resolved path such as
+    /// `/proc/<pid>/exe` gives. The configured path may be a symlink.
+    fn matches(&self, exe: &Path) -> bool {
+        self.exe == exe
+            || self
+                .exe
+                .canonicalize()
+                .is_ok_and(|resolved| resolved == exe)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions_limit_listed_clients() {
+        let permissions = Permissions::parse(
+            r#"
+default = ["Status"]
+
+[[client]]
+exe = "/usr/bin/python3"
+allow = ["Status", "Snapshots"]
+
+[[client]]
+exe = "/opt/tools/backup-admin"
+allow = ["*"]
+"#,
+        )
+        .unwrap();
+        let python = Some(Path::new("/usr/bin/python3"));
+        assert!(permissions.allows(python, "Snapshots"));
+        assert!(!permissions.allows(python, "Purge"));
+        assert!(permissions.allows(python, "Ping"));
+        assert!(permissions.allows(Some(Path::new("/opt/tools/backup-admin")), "Purge"));
+
+        let other = Some(Path::new("/usr/bin/socat"));
+        assert!(permissions.allows(other, "Status"));
+        assert!(!permissions.allows(other, "Snapshots"));
+        assert!(!permissions.allows(None, "Backup"));
+
+        let empty = Permissions::parse("").unwrap();
+        assert!(empty.allows(None, "Ping"));
+        assert!(!empty.allows(None, "Status"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permissions_match_symlinked_exe() {
+        let tmp = tempfile::tempdir().unwrap();
+        let real = tmp.path().join("python3.12");
+        std::fs::write(&real, "").unwrap();
+        let link = tmp.path().join("python3");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let permissions = Permissions::parse(&format!(
+            "[[client]]\nexe = \"{}\"\nallow = [\"Status\"]\n",
+            link.display()
+        ))
+        .unwrap();
+        let resolved = real.canonicalize().unwrap();
+        assert!(permissions.allows(Some(&resolved), "Status"));
+    }
+
+    #[test]
+    fn test_permissions_validation() {
+        let err = Permissions::parse("default = [\"Statuz\"]").unwrap_err();
+        assert!(err.to_string().contains("Unknown request type 'Statuz'"));
+
+        let err = Permissions::parse("[[client]]\nexe = \"python3\"\nallow = []\n").unwrap_err();
+        assert!(err.to_string().contains("absolute path"));
+
+        assert!(Permissions::parse("defaults = []").is_err());
+        assert!(
+            Permissions::load(Path::new("/nonexistent/permissions.toml"))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Operating-system specifics: the user ID in runtime file names, mount
+//! detection and FUSE unmounting. Linux is the primary platform; macOS mounts
+//! through macFUSE, and other systems run without mounts.
+
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Whether snapshots can be mounted as folders on this platform at all.
+pub const SUPPORTS_MOUNTS: bool = cfg!(any(target_os = "linux", target_os = "macos"));
+
+/// Tool that unmounts FUSE file systems, as the capability check names it.
+#[cfg(target_os = "macos")]
+pub const FUSE_HELPER: &str = "umount";
+#[cfg(not(target_os = "macos"))]
+pub const FUSE_HELPER: &str = "fusermount3";
+
+/// Where macFUSE installs its file system bundle. `umount` ships with macOS,
+/// so this tells whether FUSE mounts can actually work.
+#[cfg(target_os = "macos")]
+pub const MACFUSE_BUNDLE: &str = "/Library/Filesystems/macfuse.fs";
+
+/// Identifies the current user in runtime file names such as
+/// `/tmp/vigil-<id>.sock`: the numeric UID on Unix, the user name elsewhere.
+pub fn user_id() -> String {
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid has no preconditions and cannot fail
+        unsafe { libc::getuid() }.to_string()
+    }
+    #[cfg(not(unix))]
+    {
+        std::env::var("USERNAME").unwrap_or_else(|_| "user".to_string())
+    }
+}
+
+/// Sets the Unix permission bits of `path`, e.g. `0o600` for a password
+/// file. Other platforms rely on the ACLs of the user's profile directory.
+pub fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Opens a new file at `path` for writing, failing if it exists. On Unix it
+/// is created with permission bits `mode` rather than narrowed afterwards.
+pub fn create_new_with_mode(path: &Path, mode: u32) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    options.open(path)
+}
+
+/// Checks if a process with `pid` is running. A process the user may not
+/// signal or query still counts, as it exists.
+pub fn process_exists(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        // SAFETY: signal 0 only checks that the process can be signalled
+        let sent = unsafe { libc::kill(pid, 0) } == 0;
+        sent || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::{
+            CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE,
+        };
+        use windows_sys::Win32::System::Threading::{
+            GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+        // SAFETY: the handle is checked before use and closed exactly once
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null() {
+                return GetLastError() == ERROR_ACCESS_DENIED;
+            }
+            let mut code = 0u32;
+            let queried = GetExitCodeProcess(handle, &mut code) != 0;
+            CloseHandle(handle);
+            queried && code == STILL_ACTIVE as u32
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Login name of the user `uid`, or `None` when the system has no entry for
+/// it. Only Unix has numeric user IDs.
+pub fn user_name(uid: u32) -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = vec![0 as libc::c_char; 16 * 1024];
+        // SAFETY: an all-zero passwd is a valid value for getpwuid_r to fill
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        // SAFETY: every pointer is valid for the call and `buf.len()` is the
+        // size of `buf`; the strings in `entry` point into `buf`
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found) };
+        if rc != 0 || found.is_null() || entry.pw_name.is_null() {
+            return None;
+        }
+        // SAFETY: getpwuid_r succeeded, so pw_name is a NUL-terminated string in `buf`
+        let name = unsafe { std::ffi::CStr::from_ptr(entry.pw_name) };
+        Some(name.to_string_lossy().into_owned())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = uid;
+        None
+    }
+}
+
+/// Checks if `path` is a current mount point. Linux reads `/proc/mounts`;
+/// other Unix systems compare the device of the path with its parent's, which
+/// differs exactly at a mount point. Always false without mount support.
+pub fn is_mount_point(path: &Path) -> bool {
+    let Ok(target) = path.canonicalize() else {
+        return false;
+    };
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+            return false;
+        };
+        listed_in_mounts(&mounts, &target)
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let Some(parent) = target.parent() else {
+            return false;
+        };
+        match (std::fs::metadata(&target), std::fs::metadata(parent)) {
+            (Ok(own), Ok(parent)) => own.dev() != parent.dev(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = target;
+        false
+    }
+}
+
+/// Whether `/proc/mounts` content lists `target` as a mount point.
+#[cfg(target_os = "linux")]
+fn listed_in_mounts(mounts: &str, target: &Path) -> bool {
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter_map(|mount_point| Path::new(mount_point).canonicalize().ok())
+        .any(|p| p == target)
+}
+
+/// Program and arguments that unmount the FUSE mount at `path`, or `None`
+/// on platforms without mounts.
+pub fn unmount_command(path: &Path) -> Option<(&'static str, Vec<OsString>)> {
+    if cfg!(target_os = "linux") {
+        Some((FUSE_HELPER, vec!["-u".into(), path.into()]))
+    } else if cfg!(target_os = "macos") {
+        Some((FUSE_HELPER, vec![path.into()]))
+    } else {
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmount_command() {
+        let (program, args) = unmount_command(Path::new("/mnt/snap")).unwrap();
+        assert_eq!(program, "fusermount3");
+        assert_eq!(
+            args,
+            vec![OsString::from("-u"), OsString::from("/mnt/snap")]
+        );
+    }
+
+    #[test]
+    fn test_listed_in_mounts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().canonicalize().unwrap();
+        let mounts = format!(
+            "proc /proc proc rw 0 0\nrestic {} fuse.restic ro 0 0\n",
+            target.display()
+        );
+        assert!(listed_in_mounts(&mounts, &target));
+        assert!(!listed_in_mounts("proc /proc proc rw 0 0\n", &target));
+        assert!(is_mount_point(Path::new("/proc")));
+        assert!(!is_mount_point(&target.join("missing")));
+    }
+
+    #[test]
+    fn test_user_name() {
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert_eq!(user_name(u32::MAX - 1), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! The connection between the CLI and the daemon. On Unix it is the socket at
+//! [`crate::paths::socket_path`]. Elsewhere the daemon listens on a localhost
+//! TCP port and writes its address to that file instead, followed by a random
+//! token that clients send as the first line of every connection.
+
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[cfg(unix)]
+pub type IpcStream = tokio::net::UnixStream;
+#[cfg(not(unix))]
+pub type IpcStream = tokio::net::TcpStream;
+
+#[cfg(unix)]
+pub type IpcListener = tokio::net::UnixListener;
+
+/// Write half of an [`IpcStream`] after `into_split`.
+#[cfg(unix)]
+pub type IpcWriteHalf = tokio::net::unix::OwnedWriteHalf;
+#[cfg(not(unix))]
+pub type IpcWriteHalf = tokio::net::tcp::OwnedWriteHalf;
+
+/// How long a client of the localhost fallback has to send its token.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listens on a localhost port. Any local user can connect, so every
+/// connection has to pass [`authenticate`] with [`IpcListener::token`].
+#[cfg(not(unix))]
+pub struct IpcListener {
+    listener: tokio::net::TcpListener,
+    token: String,
+}
+
+#[cfg(not(unix))]
+impl IpcListener {
+    /// Accepts the next connection, which has not sent its token yet.
+    pub async fn accept(&self) -> io::Result<(IpcStream, std::net::SocketAddr)> {
+        self.listener.accept().await
+    }
+
+    /// The token clients send as their first line.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+/// Connects to the running daemon.
+pub async fn connect() -> io::Result<IpcStream> {
+    #[cfg(unix)]
+    {
+        IpcStream::connect(crate::paths::socket_path()).await
+    }
+    #[cfg(not(unix))]
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let contents = std::fs::read_to_string(crate::paths::socket_path())?;
+        let mut lines = contents.lines();
+        let (Some(address), Some(token)) = (lines.next(), lines.next()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The daemon's address file has no token",
+            ));
+        };
+        let mut stream = IpcStream::connect(address.trim()).await?;
+        stream
+            .write_all(format!("{}\n", token.trim()).as_bytes())
+            .await?;
+        Ok(stream)
+    }
+}
+
+/// Listens for clients at the path from [`crate::paths::socket_path`],
+/// replacing a stale socket file.
+pub async fn bind() -> io::Result<IpcListener> {
+    let path = crate::paths::socket_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    #[cfg(unix)]
+    {
+        IpcListener::bind(&path)
+    }
+    #[cfg(not(unix))]
+    {
+        use std::io::Write;
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+        let token = hex::encode(bytes);
+        // Any local user can reach a localhost port, so clients prove they
+        // can read this file in the user's own temporary directory
+        let mut file = crate::platform::create_new_with_mode(&path, 0o600)?;
+        writeln!(file, "{}\n{}", listener.local_addr()?, token)?;
+        Ok(IpcListener { listener, token })
+    }
+}
+
+/// Reads the first line of a connection to the localhost fallback and
+/// refuses the connection unless it is `token`. Unix sockets need no token,
+/// as the socket's permissions keep other users out. Reads byte by byte, so
+/// the request after the token stays in the stream.
+pub async fn authenticate<S: AsyncRead + Unpin>(stream: &mut S, token: &str) -> io::Result<()> {
+    tokio::time::timeout(AUTH_TIMEOUT, read_token(stream, token))
+        .await
+        .unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "No IPC token received",
+            ))
+        })
+}
+
+async fn read_token<S: AsyncRead + Unpin>(stream: &mut S, token: &str) -> io::Result<()> {
+    let denied = || io::Error::new(io::ErrorKind::PermissionDenied, "Invalid IPC token");
+    let mut line = Vec::with_capacity(token.len() + 1);
+    loop {
+        match stream.read_u8().await? {
+            b'\n' => break,
+            _ if line.len() > token.len() => return Err(denied()),
+            byte => line.push(byte),
+        }
+    }
+    let line = line.strip_suffix(b"\r").unwrap_or(&line);
+    // Compared in full, so the time taken does not tell how much matched
+    let differing = line
+        .iter()
+        .zip(token.as_bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if line.len() == token.len() && differing == 0 {
+        Ok(())
+    } else {
+        Err(denied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_authenticate() -> io::Result<()> {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(b"abc123\n{\"type\":\"Ping\"}\n").await?;
+        authenticate(&mut server, "abc123").await?;
+        // The request after the token is left for the daemon
+        let mut rest = [0u8; 16];
+        server.read_exact(&mut rest).await?;
+        assert_eq!(&rest, b"{\"type\":\"Ping\"}\n");
+
+        for sent in [&b"abc124\n"[..], b"abc12\n", b"abc1234\n", b"\n"] {
+            let (mut client, mut server) = tokio::io::duplex(256);
+            client.write_all(sent).await?;
+            let err = authenticate(&mut server, "abc123").await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        }
+
+        // A client that never ends its line is cut off after the token's length
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(&[b'a'; 64]).await?;
+        let err = authenticate(&mut server, "abc123").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        Ok(())
+    }
+}
@@ -1,23 +1,65 @@
+use crate::config::RetentionPolicy;
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Current state of a backup set job.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 #[serde(tag = "type", content = "payload")]
 pub enum JobState {
     /// No activity.
     Idle,
     /// Waiting after a file change before triggering a backup.
     Debouncing { remaining_secs: u64 },
-    /// Backup operation is currently in progress.
-    Running,
+    /// A debounced backup of a set with `defer_backups` waits for the
+    /// maintenance window, which opens at `at`.
+    Scheduled { at: DateTime<Utc> },
+    /// Change-triggered backups wait after a restore into the set's sources,
+    /// until `until`, or while `until` is null for as long as the restore
+    /// runs. Changes made meanwhile are backed up once the pause ends.
+    Paused { until: Option<DateTime<Utc>> },
+    /// A backup is due but waits for a free job slot (`max_concurrent_jobs`).
+    Queued,
+    /// Backup operation is currently in progress. `percent` (0-100) and
+    /// `eta_secs` are filled in once the engine reports progress, and stay
+    /// null while it is `scanning`.
+    Running {
+        percent: Option<f64>,
+        eta_secs: Option<u64>,
+        /// What restic found so far while it is still walking the sources.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scanning: Option<Scanning>,
+    },
+    /// The last backup failed and is retried automatically in
+    /// `next_retry_secs`. `attempt` counts the retries, starting at 1.
+    Retrying { attempt: u32, next_retry_secs: u64 },
     /// The last backup operation failed.
     Error,
 }
 
+impl JobState {
+    /// A running job that has not reported progress yet.
+    pub fn running() -> Self {
+        JobState::Running {
+            percent: None,
+            eta_secs: None,
+            scanning: None,
+        }
+    }
+}
+
+/// Files and bytes restic has discovered in a backup's sources before it
+/// finished scanning them. Until then its percentage is measured against a
+/// total that keeps growing, so it is not shown.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub struct Scanning {
+    pub files: u64,
+    pub bytes: u64,
+}
+
 /// Summary status of a backup set.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 pub struct SetStatus {
     /// Unique identifier for the backup set.
     pub name: String,
@@ -29,16 +71,89 @@ pub struct SetStatus {
     pub source_paths: Vec<PathBuf>,
     /// Restic repository target path.
     pub target: PathBuf,
-    /// Whether the backup set is currently mounted via FUSE.
+    /// Whether any snapshot of the backup set is currently mounted via FUSE.
     pub is_mounted: bool,
+    /// Whether a mounted snapshot is still being prefetched to warm the cache.
+    #[serde(default)]
+    pub mount_warming: bool,
+    /// Every snapshot of the set currently mounted.
+    #[serde(default)]
+    pub mounts: Vec<MountInfo>,
+    /// Number of changed paths not yet captured by a successful backup.
+    #[serde(default)]
+    pub pending_changes: u64,
     /// Number of snapshots in the repository.
     pub snapshot_count: Option<usize>,
     /// Total size of the repository directory in bytes.
     pub total_bytes: Option<u64>,
+    /// Whether the daemon is still loading the repository status after startup.
+    /// `last_backup`, `snapshot_count` and `total_bytes` are empty until it finishes.
+    #[serde(default)]
+    pub initializing: bool,
+    /// When the set's `schedule` next starts a backup, if it has one.
+    #[serde(default)]
+    pub next_run: Option<DateTime<Utc>>,
+    /// When `vigil check --deep` last verified the repository successfully.
+    #[serde(default)]
+    pub last_verified: Option<DateTime<Utc>>,
+    /// Whether the last verification is older than `verify_max_age_days`, or
+    /// missing while that option is set.
+    #[serde(default)]
+    pub verification_stale: bool,
+    /// Whether the repository is append-only, configured or detected from a
+    /// rejected prune. Retention is then not applied by the daemon.
+    #[serde(default)]
+    pub append_only: bool,
+    /// When the last debounced backup was skipped because the pending changes
+    /// were below the set's min-change threshold (`SkippedTrivial`). They are
+    /// carried over; cleared when the next backup starts.
+    #[serde(default)]
+    pub skipped_trivial: Option<DateTime<Utc>>,
+    /// Free space on the filesystem holding a local repository, as of the last
+    /// status refresh. `None` for remote repositories.
+    #[serde(default)]
+    pub target_free_bytes: Option<u64>,
+    /// Bytes added to the repository per day, averaged over the last week of
+    /// backup history. `None` without backups in that week.
+    #[serde(default)]
+    pub growth_bytes_per_day: Option<u64>,
+    /// File watcher events for the set folded into an earlier event for the
+    /// same path since the daemon started, e.g. during a build in a source.
+    #[serde(default)]
+    pub watch_events_coalesced: u64,
+    /// File watcher events past the per-tick path limit since the daemon
+    /// started. They still count as changes but their paths are not listed.
+    #[serde(default)]
+    pub watch_events_dropped: u64,
+    /// Latest paths the watcher reported changed since the last backup
+    /// started, oldest first and truncated to the daemon's limit. Kept until
+    /// the next change after a backup, so it names what the last run saved.
+    #[serde(default)]
+    pub recent_changes: Vec<PathBuf>,
+    /// How far the set's replica trails its repository, as of the last
+    /// comparison. Only compared with `max_copy_lag_hours` set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replica_lag: Option<ReplicaLag>,
+}
+
+/// The outcome of comparing a set's replica with its repository.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ReplicaLag {
+    /// When the snapshots were compared.
+    pub checked_at: DateTime<Utc>,
+    /// Snapshots of the repository the replica lacks.
+    pub missing_snapshots: usize,
+    /// Age of the oldest snapshot the replica lacks; 0 when it has them all.
+    pub lag_secs: u64,
+    /// Whether `lag_secs` exceeds the set's `max_copy_lag_hours`.
+    pub behind: bool,
+    /// Why the repositories could not be compared. The other fields are then 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Results of a single backup operation.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 pub struct BackupResult {
     /// Restic snapshot ID.
     pub snapshot_id: String,
@@ -52,10 +167,49 @@ pub struct BackupResult {
     pub success: bool,
     /// Error message if the backup failed.
     pub error_message: Option<String>,
+    /// Peak resident memory of the backup process, if measured.
+    #[serde(default)]
+    pub peak_rss_bytes: Option<u64>,
+    /// User plus system CPU time of the backup process, if measured.
+    #[serde(default)]
+    pub cpu_secs: Option<f64>,
+    /// Pre- and post-backup commands that ran around this backup, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookOutcome>,
+}
+
+/// When a backup hook runs relative to the backup.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+impl std::fmt::Display for HookStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookStage::Pre => write!(f, "pre-backup"),
+            HookStage::Post => write!(f, "post-backup"),
+        }
+    }
+}
+
+/// Result of a set's `pre_backup_command` or `post_backup_command`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct HookOutcome {
+    pub stage: HookStage,
+    /// Whether the command exited with status 0.
+    pub success: bool,
+    /// Exit status, or None when the command could not start, timed out or was cancelled.
+    pub exit_code: Option<i32>,
+    /// Combined stdout and stderr, keeping only the end of long output.
+    pub output: String,
+    pub duration_secs: f64,
 }
 
 /// Information about a restic snapshot.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
 pub struct SnapshotInfo {
     /// Full 64-character hex restic snapshot ID.
     pub id: String,
@@ -69,4 +223,392 @@ pub struct SnapshotInfo {
     pub tags: Vec<String>,
     /// Total size of the snapshot in bytes, if available.
     pub total_bytes: Option<u64>,
+    /// Hostname of the machine that created the snapshot.
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+/// A file or directory in a snapshot, as `vigil ls` lists it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct FileEntry {
+    /// Absolute path in the snapshot.
+    pub path: String,
+    /// `file`, `dir`, `symlink`, or another restic node type such as `fifo`.
+    #[serde(rename = "type")]
+    pub node_type: String,
+    /// Size in bytes, for regular files.
+    pub size: Option<u64>,
+    /// Modification time as recorded in the snapshot.
+    pub mtime: Option<DateTime<Utc>>,
+}
+
+/// One backup attempt of a set, successful or not, as kept in its history.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct BackupRun {
+    pub set_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    /// Snapshot created by a successful run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<String>,
+    pub added_bytes: u64,
+    /// Why the run failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The debounced backup was skipped because fewer changes were pending
+    /// than the set's `min_changed_files`/`min_changed_bytes`. Counts as a
+    /// success without a snapshot.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped_trivial: bool,
+}
+
+impl BackupRun {
+    pub fn duration_secs(&self) -> f64 {
+        (self.finished_at - self.started_at)
+            .num_milliseconds()
+            .max(0) as f64
+            / 1000.0
+    }
+}
+
+/// A kind of record in a set's history, as selected by `vigil history --ops`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryOp {
+    /// Backup runs ([`BackupRun`]).
+    Backup,
+    /// Restores from the set's snapshots ([`RestoreRecord`]).
+    Restore,
+}
+
+/// Who asked the daemon for an operation, as far as the connection tells.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct Requester {
+    /// User ID of the client process. Only Unix sockets report it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Login name of `uid`, if the system knows one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Executable of the client process, e.g. `/usr/bin/vigil`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+}
+
+impl std::fmt::Display for Requester {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.user, self.uid) {
+            (Some(user), _) => write!(f, "{}", user),
+            (None, Some(uid)) => write!(f, "uid {}", uid),
+            (None, None) => write!(f, "unknown"),
+        }
+    }
+}
+
+/// One restore from a set's snapshots, successful or not, as kept in its
+/// history for auditing recoveries.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct RestoreRecord {
+    pub set_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Snapshot restored from. A request for the latest snapshot records the
+    /// ID it resolved to, or `latest` if it could not be resolved.
+    pub snapshot_id: String,
+    /// The only path of the snapshot that was restored; `None` for all of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Directory the files were restored into.
+    pub target: String,
+    pub requested_by: Requester,
+    pub success: bool,
+    pub files_restored: u64,
+    pub bytes_restored: u64,
+    /// Why the restore failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RestoreRecord {
+    pub fn duration_secs(&self) -> f64 {
+        (self.finished_at - self.started_at)
+            .num_milliseconds()
+            .max(0) as f64
+            / 1000.0
+    }
+}
+
+/// Repository statistics from `restic stats`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+pub struct RepoStats {
+    /// Snapshots in the repository.
+    pub snapshot_count: u64,
+    /// Sum of the restore sizes of all snapshots.
+    pub restore_bytes: u64,
+    /// Files across all snapshots, counted once per snapshot.
+    pub file_count: u64,
+    /// Unique data after deduplication, before compression.
+    pub raw_bytes: u64,
+    /// Unique data as stored in the repository, after compression.
+    pub stored_bytes: u64,
+    /// Unique blobs (file chunks and trees).
+    pub blob_count: u64,
+}
+
+impl RepoStats {
+    /// How many bytes of snapshot data each byte of unique data stands for.
+    pub fn dedup_ratio(&self) -> Option<f64> {
+        (self.raw_bytes > 0).then(|| self.restore_bytes as f64 / self.raw_bytes as f64)
+    }
+
+    /// How much compression shrinks the unique data.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.stored_bytes > 0).then(|| self.raw_bytes as f64 / self.stored_bytes as f64)
+    }
+}
+
+/// Criteria for listing snapshots. Unset fields match every snapshot.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotFilter {
+    /// Only snapshots carrying this tag.
+    pub tag: Option<String>,
+    /// Only snapshots taken at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only snapshots taken at or before this time.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl SnapshotFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tag.is_none() && self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn matches(&self, snapshot: &SnapshotInfo) -> bool {
+        self.tag
+            .as_ref()
+            .is_none_or(|tag| snapshot.tags.contains(tag))
+            && self.since.is_none_or(|since| snapshot.timestamp >= since)
+            && self.until.is_none_or(|until| snapshot.timestamp <= until)
+    }
+}
+
+/// Outcome of a quick repository operation (`init` or `check`) for one set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoOpStatus {
+    /// A new repository was created.
+    Initialized,
+    /// The repository already existed.
+    AlreadyInitialized,
+    /// The repository could be opened and listed.
+    Accessible,
+    /// The repository's structure and the requested share of its data were read back intact.
+    Verified,
+    /// The deep check waits for the set's maintenance window; see `deferred_until`.
+    Deferred,
+    /// The operation failed; see `error`.
+    Failed,
+}
+
+/// Result of a quick repository operation for a single set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct RepoOpResult {
+    /// Name of the backup set.
+    pub set_name: String,
+    /// What happened.
+    pub status: RepoOpStatus,
+    /// Restic error output, if the operation failed.
+    pub error: Option<String>,
+    /// When a deferred deep check runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deferred_until: Option<DateTime<Utc>>,
+}
+
+/// One step of a guided repository repair (`vigil check --repair`).
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepairStep {
+    /// `restic check`, finding out whether (still) anything needs repair.
+    Check,
+    /// `restic repair index`, rebuilding the index from the pack files.
+    Index,
+    /// `restic repair snapshots --forget`, rewriting snapshots without data
+    /// that can no longer be read and forgetting the damaged originals.
+    Snapshots,
+}
+
+impl std::fmt::Display for RepairStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepairStep::Check => write!(f, "restic check"),
+            RepairStep::Index => write!(f, "restic repair index"),
+            RepairStep::Snapshots => write!(f, "restic repair snapshots --forget"),
+        }
+    }
+}
+
+/// Result of one repair step.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct RepairOutcome {
+    pub set_name: String,
+    pub step: RepairStep,
+    /// For `check`, whether the repository has no errors; otherwise whether
+    /// the step finished.
+    pub success: bool,
+    /// restic's output, or its error output when the step failed.
+    pub output: String,
+}
+
+/// Outcome of the most recent deep verification (`vigil check --deep`) of a set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct VerifyOutcome {
+    pub checked_at: DateTime<Utc>,
+    /// Share of the data that was read back, e.g. `5%`.
+    pub subset: String,
+    pub success: bool,
+    /// Why the verification failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Snapshots in a repository grouped by the host that created them.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct HostSnapshots {
+    /// Hostname recorded by restic.
+    pub hostname: String,
+    /// Number of snapshots created by this host.
+    pub snapshot_count: usize,
+    /// Timestamp of the host's most recent snapshot.
+    pub latest: DateTime<Utc>,
+    /// Whether this is the machine the daemon runs on.
+    pub is_local: bool,
+}
+
+/// Detailed information about a single backup set.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct SetInfo {
+    /// Current status of the set.
+    pub status: SetStatus,
+    /// Whether the set is configured with `shared_repo = true`.
+    pub shared_repo: bool,
+    /// Hostname of the machine the daemon runs on.
+    pub local_hostname: String,
+    /// Snapshot counts per host, local host first.
+    pub hosts: Vec<HostSnapshots>,
+    /// Sample of changed paths not yet backed up (at most 20).
+    #[serde(default)]
+    pub pending_paths: Vec<String>,
+    /// The retention policy prunes apply: the set's own, else the global one.
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+}
+
+/// Availability of an optional system tool detected by the daemon.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CapabilityStatus {
+    /// Name of the tool (e.g. `fusermount3`).
+    pub name: String,
+    /// Whether the tool was found at daemon startup.
+    pub available: bool,
+    /// How to make the tool available, if it is missing.
+    pub hint: Option<String>,
+}
+
+/// Overall health of the daemon's sets, ordered from best to worst.
+#[derive(
+    Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+pub enum HealthLevel {
+    #[default]
+    Healthy,
+    /// Backups are behind or being retried, but nothing has given up yet.
+    Degraded,
+    /// A set's backups fail or its repository cannot be reached.
+    Failing,
+}
+
+impl std::fmt::Display for HealthLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthLevel::Healthy => write!(f, "healthy"),
+            HealthLevel::Degraded => write!(f, "degraded"),
+            HealthLevel::Failing => write!(f, "failing"),
+        }
+    }
+}
+
+/// What is wrong with a set, as reported by `Health`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthIssueKind {
+    /// The last backup failed and will not be retried.
+    Failed,
+    /// The last backup failed and is being retried.
+    Retrying,
+    /// The repository could not be reached on the last status refresh.
+    Unreachable,
+    /// The last successful backup is older than `max_age_hours` while
+    /// changes are pending or a schedule is due.
+    Stale,
+    /// The replica trails the repository by more than `max_copy_lag_hours`,
+    /// or could not be compared with it.
+    ReplicaLag,
+}
+
+/// A snapshot mounted for browsing.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct MountInfo {
+    /// Short ID of the mounted snapshot, or `latest`.
+    pub snapshot: String,
+    /// Where the snapshot is mounted.
+    pub path: PathBuf,
+    /// Whether the mount is still being prefetched to warm the cache.
+    #[serde(default)]
+    pub warming: bool,
+}
+
+/// Why a set's snapshot was unmounted, as reported by `Unmounted`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmountReason {
+    /// A client sent `Unmount`.
+    Requested,
+    /// Nothing used the mount for `mount_idle_timeout_secs`.
+    Idle,
+    /// The mount went away on its own: its process exited, or it was
+    /// unmounted outside the daemon.
+    Lost,
+}
+
+/// Summary of all sets in one line, for status bars (`StatusLine`).
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct CompactStatus {
+    /// Pre-rendered summary, e.g. `ok · last 2h · 12 pending` or
+    /// `backing up home 42%`.
+    pub text: String,
+    /// One line per set, for a tooltip.
+    pub tooltip: String,
+    /// Worst health of any set, as `Health` reports it.
+    pub level: HealthLevel,
+    /// Number of configured sets.
+    pub sets: usize,
+    /// Sets with a backup running.
+    pub running: Vec<String>,
+    /// Sets whose health is `failing`.
+    pub failing: Vec<String>,
+    /// Changed paths not yet backed up, across all sets.
+    pub pending_changes: u64,
+    /// When the most recent successful backup of any set finished.
+    pub last_backup: Option<DateTime<Utc>>,
+}
+
+/// One problem found by a health check.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct HealthIssue {
+    pub set_name: String,
+    pub kind: HealthIssueKind,
+    /// How much the issue degrades overall health.
+    pub level: HealthLevel,
+    pub message: String,
 }
@@ -1,12 +1,28 @@
 use anyhow::{anyhow, Context};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, Utc};
 use clap::{Parser, Subcommand};
 use std::io::IsTerminal;
+use std::path::PathBuf;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::UnixStream;
-use vigil_lib::ipc::{Request, Response, ResponseData};
+use vigil_lib::attest::SignedAttestation;
+use vigil_lib::config::engine_command;
+use vigil_lib::humanize::{format_compact, format_relative, Locale};
+use vigil_lib::ipc::{Envelope, Request, Response, ResponseData};
 use vigil_lib::paths;
-use vigil_lib::types::{JobState, SetStatus};
+use vigil_lib::transport::{self, IpcStream};
+use vigil_lib::types::{
+    BackupResult, BackupRun, CapabilityStatus, CompactStatus, FileEntry, HealthIssue, HealthLevel,
+    HistoryOp, HookOutcome, JobState, RepairOutcome, RepairStep, RepoOpResult, RepoOpStatus,
+    RepoStats, RestoreRecord, Scanning, SetInfo, SetStatus, SnapshotFilter, SnapshotInfo,
+};
+
+mod plan;
+mod tui;
+
+use plan::{Plan, PlannedAction};
+
+/// Default size limit for `vigil cat`. The daemon caps requests at 256 MiB.
+const CAT_DEFAULT_MAX_BYTES: u64 = 16 * 1024 * 1024;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -29,6 +45,13 @@ enum Commands {
     Init {
         /// Name of the backup set to initialize. If omitted, initializes all sets.
         set: Option<String>,
+        /// Replace the set's existing repository with a new, empty one; confirm
+        /// by typing the set name. The old repository is moved aside.
+        #[arg(long, requires = "set")]
+        force_new: bool,
+        /// With --force-new, delete the old repository instead of moving it aside
+        #[arg(long, requires = "force_new")]
+        discard_old: bool,
     },
     /// Start a backup now
     Backup {
@@ -40,9 +63,59 @@ enum Commands {
         /// Maximum time to wait for completion (in seconds)
         #[arg(long)]
         timeout: Option<u64>,
+        /// Tag the new snapshot, on top of the set's configured tags (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Back up a set now, ignoring the excludes that kept the given paths out
+    ///
+    /// Exclude patterns equal to an --include pattern, or matching an --include
+    /// path, are skipped for this one run. The snapshot is tagged `rescue`; the
+    /// configuration is not changed.
+    Rebackup {
+        /// Name of the backup set
+        set: String,
+        /// Path or exclude pattern to capture (repeatable)
+        #[arg(long = "include", value_name = "PATTERN", required = true)]
+        include: Vec<String>,
+        /// Do not wait for the backup to complete
+        #[arg(long, conflicts_with = "timeout")]
+        no_wait: bool,
+        /// Maximum time to wait for completion (in seconds)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Watch a backup that is already running until it finishes
+    Attach {
+        /// Name of the backup set
+        set: String,
+    },
+    /// Print daemon events (backups starting, progress, outcomes, prunes) as
+    /// they happen, until interrupted
+    Watch {
+        /// Only events of this set (repeatable)
+        #[arg(long = "set", value_name = "SET")]
+        sets: Vec<String>,
     },
     /// Show health summary and recent snapshots
-    Status,
+    Status {
+        /// Columns to show, comma-separated (name,state,snapshots,size,last,next,mounted)
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<StatusColumn>>,
+        /// Maximum table width in characters. Defaults to the terminal width.
+        #[arg(long)]
+        max_width: Option<usize>,
+        /// Also show free space on each target, how fast the repository grows
+        /// and the paths that changed most recently
+        #[arg(long, short)]
+        verbose: bool,
+        /// Print a single compact line for status bars such as polybar or waybar.
+        /// With --json, prints a waybar module object instead.
+        #[arg(long, conflicts_with_all = ["columns", "max_width", "verbose"])]
+        oneline: bool,
+    },
+    /// Check backup health for monitoring; exits 1 when degraded, 2 when failing
+    Health,
     /// Mount a backup as a folder
     Mount {
         /// Name of the backup set to mount
@@ -50,15 +123,109 @@ enum Commands {
         /// Specific snapshot ID to mount. If omitted, mounts the latest one.
         snapshot_id: Option<String>,
     },
+    /// Restore files from a backup into a new folder
+    Restore {
+        /// Name of the backup set to restore from
+        set: String,
+        /// Snapshot ID to restore. If omitted, restores the latest one.
+        #[arg(long)]
+        snapshot: Option<String>,
+        /// Only restore this file or directory (as it appears in the backup)
+        #[arg(long)]
+        path: Option<String>,
+        /// Folder to restore into; must be missing or empty. Defaults to ./<set>-restore
+        #[arg(long)]
+        target: Option<PathBuf>,
+    },
+    /// End the pause of change-triggered backups after a restore into a set's sources
+    Resume {
+        /// Name of the backup set
+        set: String,
+    },
+    /// Print one file from a backup without mounting it
+    Cat {
+        /// Name of the backup set
+        set: String,
+        /// Snapshot ID, or "latest"
+        snapshot_id: String,
+        /// Absolute path of the file as it appears in the backup
+        path: String,
+        /// Write the file here instead of to stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Refuse files larger than this many bytes
+        #[arg(long, default_value_t = CAT_DEFAULT_MAX_BYTES)]
+        max_size: u64,
+    },
+    /// Save one file from a backup to disk without mounting it
+    Extract {
+        /// Name of the backup set
+        set: String,
+        /// Snapshot ID, or "latest"
+        snapshot_id: String,
+        /// Absolute path of the file as it appears in the backup
+        path: String,
+        /// File to write. Defaults to the file's name in the current directory.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Refuse files larger than this many bytes
+        #[arg(long, default_value_t = CAT_DEFAULT_MAX_BYTES)]
+        max_size: u64,
+    },
+    /// List the files in a backup without mounting it
+    Ls {
+        /// Name of the backup set
+        set: String,
+        /// Snapshot ID, or "latest"
+        snapshot_id: String,
+        /// Absolute path of the directory as it appears in the backup. Defaults to the root.
+        path: Option<String>,
+        /// Also list the contents of subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        /// Show the type, size and modification time of each entry
+        #[arg(short, long)]
+        long: bool,
+    },
     /// Unmount previously mounted folders
     Unmount {
         /// Name of the backup set to unmount. If omitted, unmounts all.
         set: Option<String>,
+        /// Only unmount this snapshot of the set, leaving its other mounts
+        #[arg(long, requires = "set")]
+        snapshot: Option<String>,
+        /// Only show what would be unmounted
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Clean up old backups according to retention policy
     Prune {
         /// Name of the backup set to prune. If omitted, prunes all.
         set: Option<String>,
+        /// Only list the snapshots the retention policy would forget
+        #[arg(long, requires = "set")]
+        dry_run: bool,
+    },
+    /// Delete one snapshot, e.g. a backup that captured secrets
+    Forget {
+        /// Name of the backup set
+        set: String,
+        /// ID of the snapshot, or a unique prefix of it
+        snapshot_id: String,
+        /// Also remove the data only this snapshot referenced
+        #[arg(long)]
+        prune: bool,
+        /// Do not ask for confirmation
+        #[arg(long)]
+        yes: bool,
+        /// Only show which snapshot would be forgotten
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Copy a set's snapshots to its replica_target now
+    Replicate {
+        /// Name of the backup set
+        set: String,
     },
     /// Launch interactive dashboard
     Tui,
@@ -67,11 +234,26 @@ enum Commands {
         #[command(subcommand)]
         subcommand: ServiceSubcommand,
     },
+    /// Copy vigil and vigil-daemon into a bin directory and install the service there
+    SelfInstall {
+        /// Destination directory. Defaults to $XDG_BIN_HOME or ~/.local/bin.
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Only copy the binaries; do not install the background service
+        #[arg(long, conflicts_with = "on_demand")]
+        no_service: bool,
+        /// Only enable the socket, as with `service install --on-demand`
+        #[arg(long)]
+        on_demand: bool,
+    },
     /// Tail the log file
     Logs {
         /// Follow mode
         #[arg(short, long)]
         follow: bool,
+        /// Show the full restic output of the set's latest backup instead
+        #[arg(long, value_name = "SET", conflicts_with = "follow")]
+        run: Option<String>,
     },
     /// List all defined backup sets
     List,
@@ -79,9 +261,12 @@ enum Commands {
     Purge {
         /// Name of the backup set to delete
         set: String,
-        /// Skip confirmation and force deletion even if set is in configuration
+        /// Delete even if the set is in configuration; confirm by typing the set name
         #[arg(long)]
         force: bool,
+        /// Only show what would be deleted
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Show all available backups for a set
     Snapshots {
@@ -90,6 +275,74 @@ enum Commands {
         /// Limit the number of backups shown
         #[arg(long, default_value = "10")]
         limit: usize,
+        /// Only show snapshots carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only show snapshots taken since this time: a date (2026-10-13), a
+        /// local date and time (2026-10-13 14:00), or an age such as 3d or 12h
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+        /// Only show snapshots taken until this time; a date includes the whole day
+        #[arg(long, value_parser = parse_until)]
+        until: Option<DateTime<Utc>>,
+    },
+    /// Show deduplication, file counts and snapshot sizes of a set's repository
+    Stats {
+        /// Name of the backup set
+        set: String,
+        /// Number of most recent snapshots listed with their size
+        #[arg(long, default_value = "10")]
+        limit: usize,
+    },
+    /// Show recent backup attempts of a set, including failed ones, or the
+    /// restores from it
+    History {
+        /// Name of the backup set
+        set: String,
+        /// Number of most recent runs shown, per kind of operation
+        #[arg(long, default_value = "20")]
+        limit: usize,
+        /// Operations to show, comma-separated (backup,restore)
+        #[arg(long, value_delimiter = ',', default_value = "backup", value_parser = parse_history_op)]
+        ops: Vec<HistoryOp>,
+    },
+    /// Show which files changed between two backups
+    Diff {
+        /// Name of the backup set
+        set: String,
+        /// The older snapshot ID, or "latest"
+        snapshot_a: String,
+        /// The newer snapshot ID. If omitted, compares against the latest backup.
+        snapshot_b: Option<String>,
+    },
+    /// Show details about a backup set and which machines write to its repository
+    Info {
+        /// Name of the backup set
+        set: String,
+    },
+    /// Export a signed attestation of a set's repository for auditors, or check one
+    Attest {
+        /// Name of the backup set
+        #[arg(required_unless_present = "verify", conflicts_with = "verify")]
+        set: Option<String>,
+        /// Write the attestation to this file instead of standard output
+        #[arg(short, long, conflicts_with = "verify")]
+        output: Option<PathBuf>,
+        /// Check the signature of an attestation file instead of creating one
+        #[arg(long, value_name = "FILE")]
+        verify: Option<PathBuf>,
+        /// With --verify, the signer's public key, as hex or a file holding it.
+        /// Defaults to this machine's key
+        #[arg(long, value_name = "HEX|FILE", conflicts_with_all = ["set", "output"])]
+        public_key: Option<String>,
+    },
+    /// Change the repository password of a backup set
+    Password {
+        /// Name of the backup set
+        set: String,
+        /// Read the new password from this file instead of prompting
+        #[arg(long)]
+        new_password_file: Option<PathBuf>,
     },
     /// Check if configuration and repositories are healthy
     Check {
@@ -98,6 +351,32 @@ enum Commands {
         /// Only check configuration, do not try to reach repositories
         #[arg(long)]
         config_only: bool,
+        /// Verify repository integrity with `restic check`, reading back part of the data
+        #[arg(long, conflicts_with = "config_only")]
+        deep: bool,
+        /// Share of the data read by --deep: a percentage (5%), a fraction (1/10) or a size (500M)
+        #[arg(long, default_value = "5%", requires = "deep")]
+        subset: String,
+        /// Run --deep at once instead of waiting for the maintenance window
+        #[arg(long, requires = "deep")]
+        now: bool,
+        /// Walk through repairing a damaged repository, confirming each step
+        #[arg(long, requires = "set", conflicts_with_all = ["config_only", "deep"])]
+        repair: bool,
+        /// Run every repair step without asking
+        #[arg(long, requires = "repair")]
+        yes: bool,
+    },
+    /// Remove stale locks left in a set's repository by crashed operations
+    Unlock {
+        /// Name of the backup set
+        set: String,
+        /// Remove every lock, including those of operations that may still be running
+        #[arg(long)]
+        all: bool,
+        /// Do not ask before removing every lock
+        #[arg(long, requires = "all")]
+        yes: bool,
     },
     /// Guided first-time setup
     Setup,
@@ -109,6 +388,12 @@ enum Commands {
         source: String,
         /// Restic repository target path
         target: String,
+        /// Pattern to exclude from the set; repeat for several
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Retention policy instead of the global one, e.g. `last=10,daily=7,weekly=4`
+        #[arg(long, value_name = "RULES", value_parser = vigil_lib::config::RetentionPolicy::parse)]
+        retention: Option<vigil_lib::config::RetentionPolicy>,
     },
     /// Remove a backup set from the configuration
     Untrack {
@@ -117,13 +402,83 @@ enum Commands {
         /// Also permanently delete the repository data
         #[arg(long)]
         purge: bool,
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove leftover mount folders of sets that no longer exist
+    Clean {
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigSubcommand,
+    },
+    /// Stop the background service
+    Shutdown {
+        /// Stop even if backups or cleanups are still running
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print machine-readable schemas of vigil's interfaces
+    Schema {
+        #[command(subcommand)]
+        subcommand: SchemaSubcommand,
+    },
+    /// Change the daemon's log verbosity until it restarts
+    LogLevel {
+        /// Level or filter, e.g. `debug` or `info,vigil_daemon::watcher=trace`
+        filter: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigSubcommand {
+    /// Show a set's settings after defaults from [global] are applied
+    Effective {
+        /// Name of the backup set
+        set: String,
+    },
+    /// Check the configuration file for errors; exits 1 when it is invalid
+    Validate {
+        /// File to check instead of the active configuration
+        file: Option<PathBuf>,
     },
+    /// Print one setting, e.g. `global.debounce_seconds` or `backup_set.home.retention`
+    Get {
+        /// `global.<setting>` or `backup_set.<name>.<setting>`
+        key: String,
+    },
+    /// Change one setting, keeping the rest of the file as written
+    Set {
+        /// `global.<setting>` or `backup_set.<name>.<setting>`
+        key: String,
+        /// New value: a TOML value, a retention policy such as `last=10,daily=7`
+        /// or, for `*_seconds` settings, an interval such as `5m`
+        value: String,
+    },
+    /// Open the configuration in $EDITOR and validate it before saving
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum SchemaSubcommand {
+    /// JSON Schema of the daemon's IPC requests, responses and events
+    Ipc,
 }
 
 #[derive(Subcommand)]
 enum ServiceSubcommand {
     /// Generate and enable the background service
-    Install,
+    Install {
+        /// Only enable the socket, so the daemon starts at the first `vigil`
+        /// command instead of at login (no file watching until then)
+        #[arg(long)]
+        on_demand: bool,
+    },
     /// Stop and disable the background service
     Stop,
     /// Reload the daemon configuration
@@ -133,6 +488,9 @@ enum ServiceSubcommand {
         /// Also remove configuration, logs, and password files
         #[arg(long)]
         purge: bool,
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
@@ -144,34 +502,120 @@ async fn main() -> anyhow::Result<()> {
     let quiet = cli.quiet;
 
     match cli.command {
-        Commands::Init { set } => {
+        Commands::Init {
+            set: Some(set),
+            force_new: true,
+            discard_old,
+        } => {
+            handle_init_force_new(set, discard_old, json, quiet).await?;
+        }
+        Commands::Init { set, .. } => {
             handle_init(set, json, quiet).await?;
         }
         Commands::Backup {
             set,
             no_wait,
             timeout,
+            tags,
+        } => {
+            let request = Request::Backup {
+                set_name: set.clone(),
+                tags,
+            };
+            handle_backup(request, set, no_wait, timeout, json, quiet).await?;
+        }
+        Commands::Rebackup {
+            set,
+            include,
+            no_wait,
+            timeout,
         } => {
-            handle_backup(set, no_wait, timeout, json, quiet).await?;
+            let request = Request::Rebackup {
+                set_name: set.clone(),
+                include,
+            };
+            handle_backup(request, Some(set), no_wait, timeout, json, quiet).await?;
         }
-        Commands::Status => {
-            handle_status(json, quiet).await?;
+        Commands::Status { oneline: true, .. } => handle_status_line(json).await?,
+        Commands::Status {
+            columns,
+            max_width,
+            verbose,
+            oneline: false,
+        } => {
+            handle_status(columns, max_width, verbose, json, quiet).await?;
         }
+        Commands::Health => handle_health(json, quiet).await?,
         Commands::Mount { set, snapshot_id } => {
             handle_mount(set, snapshot_id, json, quiet).await?;
         }
-        Commands::Unmount { set } => {
-            handle_unmount(set, json, quiet).await?;
+        Commands::Restore {
+            set,
+            snapshot,
+            path,
+            target,
+        } => {
+            handle_restore(set, snapshot, path, target, json, quiet).await?;
+        }
+        Commands::Resume { set } => handle_resume(set, json, quiet).await?,
+        Commands::Cat {
+            set,
+            snapshot_id,
+            path,
+            output,
+            max_size,
+        } => {
+            handle_cat(set, snapshot_id, path, output, max_size, json, quiet).await?;
+        }
+        Commands::Extract {
+            set,
+            snapshot_id,
+            path,
+            output,
+            max_size,
+        } => {
+            let output = match output {
+                Some(output) => output,
+                // An empty base leaves the output relative to the current directory
+                None => extract_output(&path, std::path::Path::new(""))?,
+            };
+            handle_cat(set, snapshot_id, path, Some(output), max_size, json, quiet).await?;
         }
-        Commands::Prune { set } => {
-            handle_prune(set, json, quiet).await?;
+        Commands::Ls {
+            set,
+            snapshot_id,
+            path,
+            recursive,
+            long,
+        } => {
+            handle_ls(set, snapshot_id, path, recursive, long, json, quiet).await?;
         }
-        Commands::Logs { follow } => {
-            handle_logs(follow, json, quiet).await?;
+        Commands::Unmount {
+            set,
+            snapshot,
+            dry_run,
+        } => {
+            handle_unmount(set, snapshot, dry_run, json, quiet).await?;
         }
+        Commands::Prune { set, dry_run } => match set {
+            Some(set) if dry_run => handle_prune_preview(set, json, quiet).await?,
+            set => handle_prune(set, json, quiet).await?,
+        },
+        Commands::Forget {
+            set,
+            snapshot_id,
+            prune,
+            yes,
+            dry_run,
+        } => handle_forget(set, snapshot_id, prune, yes, dry_run, json, quiet).await?,
+        Commands::Replicate { set } => handle_replicate(set, json, quiet).await?,
+        Commands::Logs { follow, run } => match run {
+            Some(set) => handle_run_log(&set, json, quiet)?,
+            None => handle_logs(follow, json, quiet).await?,
+        },
         Commands::Service { subcommand } => match subcommand {
-            ServiceSubcommand::Install => {
-                handle_bootstrap(json, quiet).await?;
+            ServiceSubcommand::Install { on_demand } => {
+                handle_bootstrap(on_demand, None, json, quiet).await?;
             }
             ServiceSubcommand::Stop => {
                 handle_disable(json, quiet).await?;
@@ -179,22 +623,83 @@ async fn main() -> anyhow::Result<()> {
             ServiceSubcommand::Reload => {
                 handle_reload(json, quiet).await?;
             }
-            ServiceSubcommand::Uninstall { purge } => {
-                handle_uninstall(purge, json, quiet).await?;
+            ServiceSubcommand::Uninstall { purge, dry_run } => {
+                handle_uninstall(purge, dry_run, json, quiet).await?;
             }
         },
-        Commands::Purge { set, force } => {
-            handle_purge(set, force, json, quiet).await?;
+        Commands::SelfInstall {
+            dir,
+            no_service,
+            on_demand,
+        } => {
+            handle_self_install(dir, no_service, on_demand, json, quiet).await?;
+        }
+        Commands::Purge {
+            set,
+            force,
+            dry_run,
+        } => {
+            handle_purge(set, force, dry_run, json, quiet).await?;
         }
         Commands::List => {
             handle_list(json, quiet).await?;
         }
-        Commands::Snapshots { set, limit } => {
-            handle_snapshots(set, limit, json, quiet).await?;
+        Commands::Snapshots {
+            set,
+            limit,
+            tag,
+            since,
+            until,
+        } => {
+            let filter = SnapshotFilter { tag, since, until };
+            handle_snapshots(set, limit, filter, json, quiet).await?;
+        }
+        Commands::Stats { set, limit } => {
+            handle_stats(set, limit, json, quiet).await?;
+        }
+        Commands::History { set, limit, ops } => {
+            handle_history(set, limit, ops, json, quiet).await?;
+        }
+        Commands::Diff {
+            set,
+            snapshot_a,
+            snapshot_b,
+        } => {
+            handle_diff(set, snapshot_a, snapshot_b, json, quiet).await?;
+        }
+        Commands::Info { set } => {
+            handle_info(set, json, quiet).await?;
+        }
+        Commands::Attest {
+            set,
+            output,
+            verify,
+            public_key,
+        } => match (verify, set) {
+            (Some(file), _) => handle_attest_verify(file, public_key, json, quiet)?,
+            (None, Some(set)) => handle_attest(set, output, json, quiet).await?,
+            (None, None) => unreachable!("clap requires a set without --verify"),
+        },
+        Commands::Password {
+            set,
+            new_password_file,
+        } => {
+            handle_password(set, new_password_file, json, quiet).await?;
         }
-        Commands::Check { set, config_only } => {
-            handle_check(set, config_only, json, quiet).await?;
+        Commands::Check {
+            set,
+            config_only,
+            deep,
+            subset,
+            now,
+            repair,
+            yes,
+        } => {
+            let deep = deep.then_some((subset, now));
+            let repair = repair.then_some(yes);
+            handle_check(set, config_only, deep, repair, json, quiet).await?;
         }
+        Commands::Unlock { set, all, yes } => handle_unlock(set, all, yes, json, quiet).await?,
         Commands::Setup => {
             handle_setup(json, quiet).await?;
         }
@@ -202,10 +707,42 @@ async fn main() -> anyhow::Result<()> {
             name,
             source,
             target,
-        } => handle_track(name, source, target, json, quiet).await?,
-        Commands::Untrack { name, purge } => handle_untrack(name, purge, json, quiet).await?,
-        Commands::Tui => {
-            println!("Command not yet implemented.");
+            exclude,
+            retention,
+        } => handle_track(name, source, target, exclude, retention, json, quiet).await?,
+        Commands::Untrack {
+            name,
+            purge,
+            dry_run,
+        } => handle_untrack(name, purge, dry_run, json, quiet).await?,
+        Commands::Clean { dry_run } => handle_clean(dry_run, json, quiet).await?,
+        Commands::Config { subcommand } => match subcommand {
+            ConfigSubcommand::Effective { set } => {
+                handle_config_effective(set, json, quiet).await?
+            }
+            ConfigSubcommand::Validate { file } => handle_config_validate(file, json, quiet)?,
+            ConfigSubcommand::Get { key } => handle_config_get(key, json)?,
+            ConfigSubcommand::Set { key, value } => {
+                handle_config_set(key, value, json, quiet).await?
+            }
+            ConfigSubcommand::Edit => handle_config_edit().await?,
+        },
+        Commands::Shutdown { force } => handle_shutdown(force, json, quiet).await?,
+        Commands::Schema { subcommand } => match subcommand {
+            SchemaSubcommand::Ipc => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&vigil_lib::ipc::schema())?
+                );
+            }
+        },
+        Commands::LogLevel { filter } => handle_log_level(filter, json, quiet).await?,
+        Commands::Tui => tui::run().await?,
+        Commands::Attach { set } => {
+            handle_attach(set, json, quiet).await?;
+        }
+        Commands::Watch { sets } => {
+            handle_watch(sets, json, quiet).await?;
         }
     }
 
@@ -215,8 +752,13 @@ async fn main() -> anyhow::Result<()> {
 async fn handle_init(set_name: Option<String>, json: bool, quiet: bool) -> anyhow::Result<()> {
     let config = vigil_lib::config::load_config().context("Failed to load configuration")?;
     let password_path = paths::password_path();
+    let needs_shared_password = config
+        .backup_sets
+        .iter()
+        .filter(|s| set_name.as_ref().is_none_or(|name| &s.name == name))
+        .any(|s| s.password_source().is_shared_default());
 
-    if !password_path.exists() {
+    if needs_shared_password && !password_path.exists() {
         if !quiet && !json {
             println!("Repository password file not found.");
         }
@@ -239,19 +781,18 @@ async fn handle_init(set_name: Option<String>, json: bool, quiet: bool) -> anyho
             std::fs::create_dir_all(parent)?;
         }
 
-        use std::os::unix::fs::PermissionsExt;
         std::fs::write(&password_path, password)?;
-        std::fs::set_permissions(&password_path, std::fs::Permissions::from_mode(0o600))?;
+        vigil_lib::platform::set_mode(&password_path, 0o600)?;
         if !quiet && !json {
             println!("Password saved to {:?}", password_path);
         }
     }
 
-    let sets_to_init: Vec<_> = if let Some(name) = set_name {
+    let sets_to_init: Vec<_> = if let Some(ref name) = set_name {
         let set = config
             .backup_sets
             .iter()
-            .find(|s| s.name == name)
+            .find(|s| &s.name == name)
             .ok_or_else(|| anyhow!("Backup set '{}' not found in config", name))?;
         vec![set]
     } else {
@@ -267,58 +808,45 @@ async fn handle_init(set_name: Option<String>, json: bool, quiet: bool) -> anyho
         return Ok(());
     }
 
-    let mut results = Vec::new();
-    let mut failed = false;
-
-    for set in sets_to_init {
-        if !quiet && !json {
-            println!(
-                "Initializing repository for set '{}' at '{}'...",
-                set.name, set.target
-            );
-        }
-
-        let output = tokio::process::Command::new("restic")
-            .arg("init")
-            .arg("--repo")
-            .arg(&set.target)
-            .arg("--password-file")
-            .arg(&password_path)
-            .output()
-            .await?;
-
-        if output.status.success() {
-            if !quiet && !json {
-                println!("Successfully initialized set '{}'.", set.name);
+    let outcomes = match daemon_repo_op(Request::Init { set_name }).await? {
+        Some(outcomes) => {
+            for outcome in &outcomes {
+                print_init_result(outcome, json, quiet);
             }
-            results.push(serde_json::json!({
-                "set": set.name,
-                "status": "initialized"
-            }));
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("repository master key and config already initialized")
-                || stderr.contains("config already initialized")
-                || stderr.contains("config file already exists")
-            {
+            outcomes
+        }
+        None => {
+            let mut outcomes = Vec::new();
+            for set in sets_to_init {
                 if !quiet && !json {
-                    println!("Set '{}' is already initialized.", set.name);
+                    println!(
+                        "Initializing repository for set '{}' at '{}'...",
+                        set.name, set.target
+                    );
                 }
-                results.push(serde_json::json!({
-                    "set": set.name,
-                    "status": "already_initialized"
-                }));
-            } else {
-                eprintln!("Failed to initialize set '{}': {}", set.name, stderr.trim());
-                failed = true;
-                results.push(serde_json::json!({
-                    "set": set.name,
-                    "status": "failed",
-                    "error": stderr.trim()
-                }));
+                let outcome = init_repo_directly(set, &config.global).await?;
+                print_init_result(&outcome, json, quiet);
+                outcomes.push(outcome);
             }
+            outcomes
         }
-    }
+    };
+
+    let failed = outcomes.iter().any(|o| o.status == RepoOpStatus::Failed);
+    let results: Vec<_> = outcomes
+        .iter()
+        .map(|o| match o.status {
+            RepoOpStatus::Failed => serde_json::json!({
+                "set": o.set_name,
+                "status": "failed",
+                "error": o.error.as_deref().unwrap_or_default()
+            }),
+            RepoOpStatus::AlreadyInitialized => {
+                serde_json::json!({ "set": o.set_name, "status": "already_initialized" })
+            }
+            _ => serde_json::json!({ "set": o.set_name, "status": "initialized" }),
+        })
+        .collect();
 
     if json {
         println!("{}", serde_json::to_string_pretty(&results)?);
@@ -331,10 +859,12 @@ async fn handle_init(set_name: Option<String>, json: bool, quiet: bool) -> anyho
     Ok(())
 }
 
-async fn handle_backup(
-    set_name: Option<String>,
-    no_wait: bool,
-    timeout: Option<u64>,
+/// Replaces a set's repository with a fresh one. Uses the purge handshake:
+/// the daemon issues a token for the repository and the user confirms by
+/// typing the set name, even in --json or --quiet mode.
+async fn handle_init_force_new(
+    set_name: String,
+    discard_old: bool,
     json: bool,
     quiet: bool,
 ) -> anyhow::Result<()> {
@@ -342,39 +872,231 @@ async fn handle_backup(
     let mut reader = BufReader::new(&mut stream);
     send_request(
         reader.get_mut(),
-        Request::Backup {
+        Request::PurgeToken {
             set_name: set_name.clone(),
         },
     )
     .await?;
-    let mut expected_sets = std::collections::HashSet::new();
-    let mut completed_count = 0;
-    let mut had_failures = false;
-    let mut initial_response_received = false;
+    let (target_path, token) = match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::PurgeToken { target, token, .. })) => (target, token),
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    };
 
-    let timeout_duration = timeout.map(std::time::Duration::from_secs);
-    let start_instant = std::time::Instant::now();
+    let warning = if discard_old {
+        format!(
+            "WARNING: This will permanently delete ALL backup data for '{}' at '{}' and create an empty repository in its place!",
+            set_name, target_path
+        )
+    } else {
+        format!(
+            "This will move the repository for '{}' at '{}' aside and create an empty one in its place.\nThe old repository is kept next to it as '{}.old-<timestamp>'.",
+            set_name,
+            target_path,
+            target_path.trim_end_matches('/')
+        )
+    };
+    if json || quiet {
+        // Keep machine-readable stdout clean
+        eprintln!("{}", warning);
+        eprint!("Type the set name to confirm: ");
+    } else {
+        println!("{}", warning);
+        print!("Type the set name to confirm: ");
+    }
+    use std::io::Write;
+    std::io::stdout().flush()?;
+    std::io::stderr().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if input.trim() != set_name {
+        anyhow::bail!(
+            "Reinitialization cancelled: the typed name does not match '{}'",
+            set_name
+        );
+    }
 
-    loop {
-        if let Some(d) = timeout_duration {
-            if start_instant.elapsed() > d {
-                anyhow::bail!("Timeout waiting for backup completion");
+    if !quiet && !json {
+        println!("Creating a new repository for '{}'...", set_name);
+    }
+    send_request(
+        reader.get_mut(),
+        Request::Reinit {
+            set_name: set_name.clone(),
+            token,
+            discard_old,
+        },
+    )
+    .await?;
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::Reinitialized {
+            target,
+            archived_to,
+            ..
+        })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "set": set_name,
+                        "status": "reinitialized",
+                        "target": target,
+                        "archived_to": archived_to,
+                    })
+                );
+            } else if !quiet {
+                if let Some(archived_to) = archived_to {
+                    println!("Old repository moved to '{}'.", archived_to);
+                }
+                println!(
+                    "Successfully initialized a new repository for set '{}'.",
+                    set_name
+                );
             }
         }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    }
+    Ok(())
+}
 
-        let recv_timeout = std::time::Duration::from_millis(500);
-        let res = tokio::time::timeout(recv_timeout, receive_response(&mut reader)).await;
+/// Sends an `Init` or `Check` request to the daemon so the operation is logged and
+/// serialized with running jobs. Returns `None` if the daemon is unreachable (or
+/// too old to know the request), in which case the caller runs restic directly.
+async fn daemon_repo_op(request: Request) -> anyhow::Result<Option<Vec<RepoOpResult>>> {
+    let Ok(mut stream) = transport::connect().await else {
+        return Ok(None);
+    };
+    send_request(&mut stream, request).await?;
+    let mut reader = BufReader::new(&mut stream);
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::InitResults { results }))
+        | Response::Ok(Some(ResponseData::CheckResults { results })) => Ok(Some(results)),
+        Response::Error { code, .. } if code == vigil_lib::ipc::error_codes::INVALID_REQUEST => {
+            Ok(None)
+        }
+        Response::Error { message, .. } => Err(anyhow!(message)),
+        _ => Err(anyhow!("Unexpected response from service daemon")),
+    }
+}
 
-        let response = match res {
-            Ok(Ok(r)) => r,
-            Ok(Err(e)) => return Err(e),
-            Err(_) => continue, // Timeout, check global timeout and loop
-        };
+/// Runs the set's engine `init` without the daemon.
+async fn init_repo_directly(
+    set: &vigil_lib::config::BackupSet,
+    global: &vigil_lib::config::GlobalConfig,
+) -> anyhow::Result<RepoOpResult> {
+    let output = engine_command(
+        set.engine.binary(),
+        &set.process_env(global)?,
+        &global.restic_env_passthrough,
+    )
+    .arg("init")
+    .arg("-r")
+    .arg(&set.target)
+    .args(set.password_source().args())
+    .output()
+    .await?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (status, error) = if output.status.success() {
+        (RepoOpStatus::Initialized, None)
+    } else if stderr.contains("repository master key and config already initialized")
+        || stderr.contains("config already initialized")
+        || stderr.contains("config file already exists")
+    {
+        (RepoOpStatus::AlreadyInitialized, None)
+    } else {
+        (RepoOpStatus::Failed, Some(stderr.trim().to_string()))
+    };
+    Ok(RepoOpResult {
+        set_name: set.name.clone(),
+        status,
+        error,
+        deferred_until: None,
+    })
+}
+
+fn print_init_result(outcome: &RepoOpResult, json: bool, quiet: bool) {
+    match outcome.status {
+        RepoOpStatus::Failed => eprintln!(
+            "Failed to initialize set '{}': {}",
+            outcome.set_name,
+            outcome.error.as_deref().unwrap_or_default()
+        ),
+        _ if quiet || json => {}
+        RepoOpStatus::AlreadyInitialized => {
+            println!("Set '{}' is already initialized.", outcome.set_name)
+        }
+        _ => println!("Successfully initialized set '{}'.", outcome.set_name),
+    }
+}
+
+/// Sends `request`, a `Backup` or `Rebackup` of `set_name` (all sets when
+/// `None`), and follows the backups it starts.
+async fn handle_backup(
+    request: Request,
+    set_name: Option<String>,
+    no_wait: bool,
+    timeout: Option<u64>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    let request_id = send_request(reader.get_mut(), request).await?;
+    let mut expected_sets = std::collections::HashSet::new();
+    let mut completed_count = 0;
+    let mut had_failures = false;
+    let mut initial_response_received = false;
+    let mut outcomes = std::collections::BTreeMap::new();
+    let mut timed_out = false;
+
+    let timeout_duration = timeout.map(std::time::Duration::from_secs);
+    let start_instant = std::time::Instant::now();
+    let mut progress = ProgressLine::new(json, quiet);
+
+    loop {
+        if let Some(d) = timeout_duration {
+            if start_instant.elapsed() > d {
+                timed_out = true;
+                break;
+            }
+        }
+
+        let recv_timeout = std::time::Duration::from_millis(500);
+        let res = tokio::time::timeout(recv_timeout, receive_message(&mut reader)).await;
+
+        let Envelope {
+            id,
+            message: response,
+        } = match res {
+            Ok(Ok(r)) => r,
+            Ok(Err(e)) => return Err(e),
+            Err(_) => continue, // Timeout, check global timeout and loop
+        };
+        if !response.is_broadcast() && id != Some(request_id) {
+            continue;
+        }
+
+        // Clear the progress bar before printing anything else
+        if !matches!(
+            response,
+            Response::Event(ResponseData::BackupProgress { .. })
+        ) {
+            progress.clear()?;
+        }
 
         match response {
             Response::Ok(Some(ref data)) => match data {
                 ResponseData::BackupStarted {
                     set_name: started_set,
+                    ..
                 } => {
                     if json {
                         println!("{}", serde_json::to_string(data)?);
@@ -382,6 +1104,24 @@ async fn handle_backup(
                         println!("Backup started for set '{}'.", started_set);
                     }
                     expected_sets.insert(started_set.clone());
+                    outcomes.insert(started_set.clone(), SetOutcome::Started);
+                    initial_response_received = true;
+                }
+                ResponseData::RescueStarted {
+                    set_name: started_set,
+                    lifted_excludes,
+                } => {
+                    if json {
+                        println!("{}", serde_json::to_string(data)?);
+                    } else if !quiet {
+                        println!(
+                            "Rescue backup started for set '{}', ignoring excludes: {}",
+                            started_set,
+                            lifted_excludes.join(", ")
+                        );
+                    }
+                    expected_sets.insert(started_set.clone());
+                    outcomes.insert(started_set.clone(), SetOutcome::Started);
                     initial_response_received = true;
                 }
                 ResponseData::BackupsTriggered { started, failed } => {
@@ -393,13 +1133,33 @@ async fn handle_backup(
                             println!("Backup triggered for set '{}'.", set);
                         }
                         expected_sets.insert(set.clone());
+                        outcomes.insert(set.clone(), SetOutcome::Started);
                     }
                     for (set, error) in failed {
                         eprintln!("Failed to trigger backup for set '{}': {}", set, error);
                         had_failures = true;
+                        outcomes.insert(
+                            set.clone(),
+                            SetOutcome::Failed {
+                                error: error.clone(),
+                            },
+                        );
                     }
                     initial_response_received = true;
                 }
+                _ => {}
+            },
+            Response::Event(ref data) => match data {
+                ResponseData::BackupProgress {
+                    set_name: progress_set,
+                    ..
+                } if expected_sets.contains(progress_set) => {
+                    if json {
+                        println!("{}", serde_json::to_string(data)?);
+                    } else if let Some(line) = backup_progress_line(data) {
+                        progress.draw(&line)?;
+                    }
+                }
                 ResponseData::BackupComplete {
                     set_name: completed_set_name,
                     snapshot_id,
@@ -411,14 +1171,22 @@ async fn handle_backup(
                             println!("{}", serde_json::to_string(data)?);
                         } else if !quiet {
                             println!(
-                                "Backup complete for set '{}': snapshot {}, {} added in {:.1}s",
+                                "Backup complete for set '{}': snapshot {}, {} added in {}",
                                 completed_set_name,
                                 snapshot_id,
                                 format_size(*added_bytes),
-                                duration_secs
+                                format_secs(*duration_secs)
                             );
                         }
                         completed_count += 1;
+                        outcomes.insert(
+                            completed_set_name.clone(),
+                            SetOutcome::Completed {
+                                snapshot_id: snapshot_id.clone(),
+                                added_bytes: *added_bytes,
+                                duration_secs: *duration_secs,
+                            },
+                        );
                     }
 
                     if initial_response_received && completed_count >= expected_sets.len() {
@@ -436,6 +1204,12 @@ async fn handle_backup(
                         eprintln!("Backup failed for set '{}': {}", failed_set, error);
                         had_failures = true;
                         completed_count += 1;
+                        outcomes.insert(
+                            failed_set.clone(),
+                            SetOutcome::Failed {
+                                error: error.clone(),
+                            },
+                        );
                     }
                     if initial_response_received && completed_count >= expected_sets.len() {
                         break;
@@ -443,9 +1217,7 @@ async fn handle_backup(
                 }
                 _ => {}
             },
-            Response::Ok(None) => {
-                // Some Ok(None) might be returned for other requests, but here we expect data
-            }
+            Response::Ok(None) => {}
             Response::Error { code, message } => {
                 eprintln!("Error from service daemon ({}): {}", code, message);
                 if code == vigil_lib::ipc::error_codes::RESTIC_ERROR
@@ -456,7 +1228,11 @@ async fn handle_backup(
                     std::process::exit(1);
                 }
             }
-            Response::Pong => {}
+            Response::CapabilityMissing { name, hint } => {
+                eprintln!("Error: Required tool '{}' is not available. {}", name, hint);
+                std::process::exit(1);
+            }
+            Response::Pong | Response::JobsRunning { .. } => {}
         }
 
         if no_wait && initial_response_received {
@@ -468,6 +1244,22 @@ async fn handle_backup(
         }
     }
 
+    progress.clear()?;
+    // Backing up all sets interleaves their output; close with one overview
+    if set_name.is_none() && !outcomes.is_empty() {
+        if json {
+            println!("{}", backup_summary_json(&outcomes));
+        } else if !quiet {
+            println!();
+            for line in render_backup_summary(&outcomes) {
+                println!("{}", line);
+            }
+        }
+    }
+    if timed_out {
+        anyhow::bail!("Timeout waiting for backup completion");
+    }
+
     // Exit with code 4 (restic error) if any backups failed
     if had_failures {
         std::process::exit(4);
@@ -476,30 +1268,467 @@ async fn handle_backup(
     Ok(())
 }
 
-fn format_size(bytes: u64) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1} KiB", bytes as f64 / 1024.0)
-    } else if bytes < 1024 * 1024 * 1024 {
-        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
-    } else {
-        format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+/// What became of one set's backup by the time `vigil backup` stopped waiting.
+#[derive(Debug, Clone, PartialEq)]
+enum SetOutcome {
+    /// Still running, e.g. with `--no-wait` or after `--timeout`.
+    Started,
+    Completed {
+        snapshot_id: String,
+        added_bytes: u64,
+        duration_secs: f64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl SetOutcome {
+    fn status(&self) -> &'static str {
+        match self {
+            SetOutcome::Started => "started",
+            SetOutcome::Completed { .. } => "completed",
+            SetOutcome::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// The final `--json` line of `vigil backup` for all sets.
+fn backup_summary_json(
+    outcomes: &std::collections::BTreeMap<String, SetOutcome>,
+) -> serde_json::Value {
+    let sets: Vec<_> = outcomes
+        .iter()
+        .map(|(set, outcome)| {
+            let mut entry = serde_json::json!({ "set": set, "status": outcome.status() });
+            match outcome {
+                SetOutcome::Started => {}
+                SetOutcome::Completed {
+                    snapshot_id,
+                    added_bytes,
+                    duration_secs,
+                } => {
+                    entry["snapshot_id"] = snapshot_id.as_str().into();
+                    entry["added_bytes"] = (*added_bytes).into();
+                    entry["duration_secs"] = (*duration_secs).into();
+                }
+                SetOutcome::Failed { error } => entry["error"] = error.as_str().into(),
+            }
+            entry
+        })
+        .collect();
+    let count = |status: &str| outcomes.values().filter(|o| o.status() == status).count();
+    serde_json::json!({
+        "kind": "BackupSummary",
+        "completed": count("completed"),
+        "failed": count("failed"),
+        "started": count("started"),
+        "sets": sets,
+    })
+}
+
+/// One line per set, failures showing the first line of their error.
+fn render_backup_summary(outcomes: &std::collections::BTreeMap<String, SetOutcome>) -> Vec<String> {
+    let width = outcomes
+        .keys()
+        .map(|s| s.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(3);
+    let mut lines = vec![format!(
+        "{:<width$} {:<9} {:>10} {:>8}  {}",
+        "SET", "RESULT", "ADDED", "DURATION", "SNAPSHOT / ERROR"
+    )];
+    for (set, outcome) in outcomes {
+        let (result, added, duration, detail) = match outcome {
+            SetOutcome::Started => ("… running", "-".to_string(), "-".to_string(), String::new()),
+            SetOutcome::Completed {
+                snapshot_id,
+                added_bytes,
+                duration_secs,
+            } => (
+                "✓ ok",
+                format_size(*added_bytes),
+                format_secs(*duration_secs),
+                snapshot_id.clone(),
+            ),
+            SetOutcome::Failed { error } => (
+                "✗ fail",
+                "-".to_string(),
+                "-".to_string(),
+                error.lines().next().unwrap_or("Unknown error").to_string(),
+            ),
+        };
+        lines.push(
+            format!(
+                "{:<width$} {:<9} {:>10} {:>8}  {}",
+                set, result, added, duration, detail
+            )
+            .trim_end()
+            .to_string(),
+        );
+    }
+    lines
+}
+
+/// Follows the running backup of `set_name`: progress while it runs, then its
+/// outcome. Useful after interrupting `vigil backup`, which leaves the backup running.
+/// Streams broadcast events until the daemon goes away. `--json` prints each
+/// event as one JSON object per line; otherwise events are described one per
+/// line like in the TUI's event pane, without progress.
+async fn handle_watch(sets: Vec<String>, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+
+    // Subscribing to an unknown set is not an error, so check the names first
+    if !sets.is_empty() {
+        send_request(reader.get_mut(), Request::Status).await?;
+        match receive_reply(&mut reader).await? {
+            Response::Ok(Some(ResponseData::Status { sets: known })) => {
+                if let Some(unknown) = sets.iter().find(|s| !known.iter().any(|k| k.name == **s)) {
+                    eprintln!("Error: Backup set '{}' not found.", unknown);
+                    std::process::exit(1);
+                }
+            }
+            Response::Error { code, message } => {
+                eprintln!("Error from daemon ({}): {}", code, message);
+                std::process::exit(1);
+            }
+            other => return Err(anyhow!("Unexpected response: {:?}", other)),
+        }
+    }
+    let filter = Request::Subscribe {
+        sets: (!sets.is_empty()).then_some(sets),
+        kinds: None,
+    };
+    send_request(reader.get_mut(), filter).await?;
+    if let Response::Error { code, message } = receive_reply(&mut reader).await? {
+        eprintln!("Error from daemon ({}): {}", code, message);
+        std::process::exit(1);
+    }
+    if !json && !quiet {
+        println!("Watching daemon events. Press Ctrl+C to stop.");
+    }
+
+    let mut stopping = false;
+    loop {
+        let response = match receive_response(&mut reader).await {
+            Ok(response) => response,
+            // The daemon closes connections once it has shut down
+            Err(_) if stopping => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let Response::Event(data) = response else {
+            continue;
+        };
+        stopping |= matches!(data, ResponseData::ShuttingDown { .. });
+        if json {
+            println!("{}", serde_json::to_string(&data)?);
+        } else if let Some(description) = tui::describe_event(&data) {
+            println!("{} {}", Local::now().format("%H:%M:%S"), description);
+        }
+    }
+}
+
+async fn handle_attach(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Status).await?;
+
+    let is_outcome = |data: &ResponseData| {
+        matches!(
+            data,
+            ResponseData::BackupComplete { set_name: s, .. }
+                | ResponseData::BackupFailed { set_name: s, .. } if *s == set_name
+        )
+    };
+
+    // Events already arrive on this connection, so an outcome broadcast
+    // before the status reply is not missed
+    let mut outcome = None;
+    let running = loop {
+        match receive_response(&mut reader).await? {
+            Response::Ok(Some(ResponseData::Status { sets })) => {
+                let Some(set) = sets.iter().find(|s| s.name == set_name) else {
+                    eprintln!("Error: Backup set '{}' not found.", set_name);
+                    std::process::exit(1);
+                };
+                break matches!(set.state, JobState::Running { .. } | JobState::Queued);
+            }
+            Response::Event(data) if is_outcome(&data) => outcome = Some(data),
+            Response::Error { code, message } => {
+                eprintln!("Error from daemon ({}): {}", code, message);
+                std::process::exit(1);
+            }
+            _ => {}
+        }
+    };
+
+    if outcome.is_none() && !running {
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({ "set_name": set_name, "running": false })
+            );
+        } else if !quiet {
+            println!("No backup is running for set '{}'.", set_name);
+        }
+        return Ok(());
+    }
+    if outcome.is_none() && !json && !quiet {
+        println!("Attached to the running backup of '{}'.", set_name);
+    }
+
+    let mut progress = ProgressLine::new(json, quiet);
+    let outcome = match outcome {
+        Some(data) => data,
+        None => loop {
+            match receive_response(&mut reader).await? {
+                Response::Event(data) if is_outcome(&data) => break data,
+                Response::Event(
+                    ref data @ ResponseData::BackupProgress {
+                        set_name: ref progress_set,
+                        ..
+                    },
+                ) if *progress_set == set_name => {
+                    if json {
+                        println!("{}", serde_json::to_string(data)?);
+                    } else if let Some(line) = backup_progress_line(data) {
+                        progress.draw(&line)?;
+                    }
+                }
+                _ => {}
+            }
+        },
+    };
+    progress.clear()?;
+
+    if json {
+        println!("{}", serde_json::to_string(&outcome)?);
+    }
+    match outcome {
+        ResponseData::BackupComplete {
+            snapshot_id,
+            added_bytes,
+            duration_secs,
+            ..
+        } => {
+            if !json && !quiet {
+                println!(
+                    "Backup complete for set '{}': snapshot {}, {} added in {}",
+                    set_name,
+                    snapshot_id,
+                    format_size(added_bytes),
+                    format_secs(duration_secs)
+                );
+            }
+            Ok(())
+        }
+        ResponseData::BackupFailed { error, .. } => {
+            eprintln!("Backup failed for set '{}': {}", set_name, error);
+            std::process::exit(4);
+        }
+        _ => unreachable!("outcome is a backup result"),
+    }
+}
+
+/// A status line redrawn in place while stdout is a terminal and neither
+/// `--json` nor `--quiet` is given.
+struct ProgressLine {
+    enabled: bool,
+    drawn: bool,
+}
+
+impl ProgressLine {
+    fn new(json: bool, quiet: bool) -> Self {
+        Self {
+            enabled: !json && !quiet && std::io::stdout().is_terminal(),
+            drawn: false,
+        }
+    }
+
+    fn draw(&mut self, line: &str) -> std::io::Result<()> {
+        use std::io::Write;
+        if self.enabled {
+            print!("\r\x1b[2K{}", line);
+            std::io::stdout().flush()?;
+            self.drawn = true;
+        }
+        Ok(())
+    }
+
+    /// Erases the line so other output starts on a clean line.
+    fn clear(&mut self) -> std::io::Result<()> {
+        use std::io::Write;
+        if self.drawn {
+            print!("\r\x1b[2K");
+            std::io::stdout().flush()?;
+            self.drawn = false;
+        }
+        Ok(())
+    }
+}
+
+/// The progress line for a `BackupProgress` event, fitted to the terminal.
+fn backup_progress_line(data: &ResponseData) -> Option<String> {
+    let ResponseData::BackupProgress {
+        set_name,
+        percent,
+        bytes_done,
+        total_bytes,
+        eta_secs,
+        current_file,
+        scanning,
+    } = data
+    else {
+        return None;
+    };
+    if let Some(scanning) = scanning {
+        return Some(render_scan_progress(
+            set_name,
+            scanning,
+            current_file.as_deref(),
+            terminal_width(),
+        ));
+    }
+    Some(render_backup_progress(
+        set_name,
+        *percent,
+        *bytes_done,
+        *total_bytes,
+        *eta_secs,
+        current_file.as_deref(),
+        terminal_width(),
+    ))
+}
+
+/// Width of the bar drawn by [`render_backup_progress`].
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// One-line progress display for a running backup, e.g.
+/// `personal [#####---------------]  25% 1.0 GiB/4.0 GiB ~3m left  /home/u/a.txt`.
+/// The current file is shortened from the left to fit `max_width`.
+fn render_backup_progress(
+    set_name: &str,
+    percent: f64,
+    bytes_done: u64,
+    total_bytes: u64,
+    eta_secs: Option<u64>,
+    current_file: Option<&str>,
+    max_width: Option<usize>,
+) -> String {
+    let percent = percent.clamp(0.0, 100.0);
+    let filled = (percent / 100.0 * PROGRESS_BAR_WIDTH as f64).floor() as usize;
+    let mut line = format!(
+        "{} [{}{}] {:>3.0}% {}",
+        set_name,
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        percent.floor(),
+        format_size(bytes_done)
+    );
+    if total_bytes > 0 {
+        line.push_str(&format!("/{}", format_size(total_bytes)));
     }
+    if let Some(secs) = eta_secs {
+        line.push_str(&format!(
+            " ~{} left",
+            format_compact(Duration::seconds(secs as i64))
+        ));
+    }
+    with_current_file(line, current_file, max_width)
+}
+
+/// Progress line while restic is still finding the files to back up, e.g.
+/// `home scanning… 52000 files, 8.4 GiB found  /home/u/a.txt`. restic's
+/// percentage is not shown until then, as the total keeps growing.
+fn render_scan_progress(
+    set_name: &str,
+    scanning: &Scanning,
+    current_file: Option<&str>,
+    max_width: Option<usize>,
+) -> String {
+    let line = format!(
+        "{} scanning… {} files, {} found",
+        set_name,
+        scanning.files,
+        format_size(scanning.bytes)
+    );
+    with_current_file(line, current_file, max_width)
+}
+
+/// Appends the file being processed to a progress line, shortened from the
+/// left to fit `max_width`.
+fn with_current_file(
+    mut line: String,
+    current_file: Option<&str>,
+    max_width: Option<usize>,
+) -> String {
+    if let Some(file) = current_file {
+        let room = max_width.map_or(usize::MAX, |w| w.saturating_sub(line.chars().count() + 2));
+        let len = file.chars().count();
+        if room > 1 && len <= room {
+            line.push_str(&format!("  {}", file));
+        } else if room > 1 {
+            let tail: String = file.chars().skip(len - (room - 1)).collect();
+            line.push_str(&format!("  …{}", tail));
+        }
+    }
+    match max_width {
+        Some(width) => truncate_cell(&line, width),
+        None => line,
+    }
+}
+
+/// Sizes in interactive output follow the environment locale.
+fn format_size(bytes: u64) -> String {
+    Locale::current().format_size(bytes)
+}
+
+/// Elapsed seconds in interactive output, e.g. `4.2s` or `3m 12s`.
+fn format_secs(secs: f64) -> String {
+    Locale::current().format_secs(secs)
 }
 
-async fn handle_status(json: bool, quiet: bool) -> anyhow::Result<()> {
+async fn handle_status(
+    columns: Option<Vec<StatusColumn>>,
+    max_width: Option<usize>,
+    verbose: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
     let mut stream = connect_to_daemon().await?;
     let mut reader = BufReader::new(&mut stream);
     send_request(reader.get_mut(), Request::Status).await?;
-    let response = receive_response(&mut reader).await?;
+    let response = receive_reply(&mut reader).await?;
 
     match response {
         Response::Ok(Some(ResponseData::Status { sets })) => {
             if json {
                 println!("{}", serde_json::to_string_pretty(&sets)?);
             } else if !quiet {
-                display_status(sets);
+                let columns = columns.unwrap_or_else(|| StatusColumn::ALL.to_vec());
+                let max_width = max_width.or_else(terminal_width);
+                let mut warnings = verification_warnings(&sets);
+                warnings.extend(skipped_notes(&sets));
+                let mut capacity = Vec::new();
+                if verbose {
+                    capacity.extend(capacity_lines(&sets));
+                    capacity.extend(recent_change_lines(&sets));
+                }
+                display_status(sets, &columns, max_width);
+                if !capacity.is_empty() {
+                    println!();
+                    for line in capacity {
+                        println!("{}", line);
+                    }
+                }
+                if !warnings.is_empty() {
+                    println!();
+                    for warning in warnings {
+                        println!("{}", warning);
+                    }
+                }
             }
         }
         Response::Ok(_) => {
@@ -509,8 +1738,11 @@ async fn handle_status(json: bool, quiet: bool) -> anyhow::Result<()> {
             eprintln!("Error from service daemon ({}): {}", code, message);
             std::process::exit(1);
         }
-        Response::Pong => {
-            println!("Unexpected Pong response.");
+        Response::Pong
+        | Response::CapabilityMissing { .. }
+        | Response::JobsRunning { .. }
+        | Response::Event(_) => {
+            println!("Unexpected response from service daemon.");
         }
     }
 
@@ -528,16 +1760,16 @@ async fn handle_mount(
     send_request(
         reader.get_mut(),
         Request::Mount {
-            set_name,
-            snapshot_id,
+            set_name: set_name.clone(),
+            snapshot_id: snapshot_id.clone(),
         },
     )
     .await?;
 
-    let response = receive_response(&mut reader).await?;
+    let response = receive_reply(&mut reader).await?;
     match response {
         Response::Ok(Some(ref data)) => {
-            if let ResponseData::MountPath { ref path } = data {
+            if let ResponseData::MountPath { ref path, warming } = *data {
                 if json {
                     println!("{}", serde_json::to_string(data)?);
                 } else if !quiet {
@@ -548,8 +1780,27 @@ async fn handle_mount(
                     println!("  by timestamp: {}/snapshots/<timestamp>/", path);
                     println!("  by host:      {}/hosts/<hostname>/", path);
                     println!("  by tags:      {}/tags/<tag>/", path);
+                    if let Some(ref id) = snapshot_id {
+                        println!("  this snapshot: {}/ids/{}/", path, id);
+                    }
                     println!();
-                    println!("Use `cp` to recover files, then `vigil unmount` when done.");
+                    if warming {
+                        println!("Warming cache… the first directory levels are being prefetched in the background.");
+                        println!(
+                            "`vigil status` shows the set as Warming… until prefetching completes."
+                        );
+                        println!();
+                    }
+                    match snapshot_id {
+                        Some(id) => println!(
+                            "Use `cp` to recover files, then `vigil unmount {} --snapshot {}` when done.",
+                            set_name, id
+                        ),
+                        None => println!(
+                            "Use `cp` to recover files, then `vigil unmount {}` when done.",
+                            set_name
+                        ),
+                    }
                 }
             } else {
                 println!("Unexpected response from service daemon.");
@@ -559,6 +1810,11 @@ async fn handle_mount(
             eprintln!("Error mounting snapshot ({}): {}", code, message);
             std::process::exit(5); // Exit code 5 per spec.md Section 12: Mount/unmount error
         }
+        Response::CapabilityMissing { name, hint } => {
+            eprintln!("Error mounting snapshot: '{}' is not installed.", name);
+            eprintln!("  Hint: {}", hint);
+            std::process::exit(5);
+        }
         _ => {
             println!("Unexpected response from service daemon.");
         }
@@ -567,72 +1823,424 @@ async fn handle_mount(
     Ok(())
 }
 
-async fn handle_unmount(set_name: Option<String>, json: bool, quiet: bool) -> anyhow::Result<()> {
+async fn handle_restore(
+    set_name: String,
+    snapshot_id: Option<String>,
+    path: Option<String>,
+    target: Option<PathBuf>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    // The daemon runs in a different working directory, so always send an absolute path
+    let target = std::env::current_dir()?
+        .join(target.unwrap_or_else(|| PathBuf::from(format!("{}-restore", set_name))));
+    let target = target.to_string_lossy().to_string();
+    let show_progress = !json && !quiet && std::io::stdout().is_terminal();
+
     let mut stream = connect_to_daemon().await?;
     let mut reader = BufReader::new(&mut stream);
     send_request(
         reader.get_mut(),
-        Request::Unmount {
+        Request::Restore {
             set_name: set_name.clone(),
+            snapshot_id,
+            path,
+            target: target.clone(),
         },
     )
     .await?;
 
-    let response = receive_response(&mut reader).await?;
-    match response {
-        Response::Ok(_) => {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({
-                        "status": "success",
-                        "unmounted": set_name.as_deref().unwrap_or("all")
-                    })
-                );
-            } else if !quiet {
-                if let Some(name) = set_name {
-                    println!("Successfully unmounted set '{}'.", name);
-                } else {
-                    println!("Successfully unmounted all sets.");
-                }
+    loop {
+        let response = receive_response(&mut reader).await?;
+        let data = match response {
+            Response::Ok(Some(data)) | Response::Event(data) => data,
+            Response::Error { code, message } => {
+                eprintln!("Error restoring ({}): {}", code, message);
+                std::process::exit(4);
             }
-        }
-        Response::Error { code, message } => {
-            eprintln!("Error unmounting ({}): {}", code, message);
-            std::process::exit(5); // Exit code 5 per spec.md Section 12: Mount/unmount error
-        }
-        _ => {
-            println!("Unexpected response from service daemon.");
-        }
+            Response::CapabilityMissing { name, hint } => {
+                eprintln!("Error restoring: '{}' is not installed.", name);
+                eprintln!("  Hint: {}", hint);
+                std::process::exit(1);
+            }
+            _ => continue,
+        };
+
+        // Events for other sets or restores share the connection
+        let ours = |set: &str, dest: &str| set == set_name && dest == target;
+        match data {
+            ResponseData::RestoreStarted {
+                ref snapshot_id,
+                ref target,
+                ..
+            } => {
+                if json {
+                    println!("{}", serde_json::to_string(&data)?);
+                } else if !quiet {
+                    println!(
+                        "Restoring snapshot {} of '{}' into {}",
+                        snapshot_id, set_name, target
+                    );
+                }
+            }
+            ResponseData::RestoreProgress {
+                ref set_name,
+                ref target,
+                percent,
+                eta_secs,
+            } if ours(set_name, target) => {
+                if json {
+                    println!("{}", serde_json::to_string(&data)?);
+                } else if show_progress {
+                    let eta = eta_secs
+                        .map(|secs| {
+                            format!(
+                                " (~{} left)",
+                                format_compact(Duration::seconds(secs as i64))
+                            )
+                        })
+                        .unwrap_or_default();
+                    use std::io::Write;
+                    print!("\r\x1b[2KRestoring… {:.0}%{}", percent.floor(), eta);
+                    std::io::stdout().flush()?;
+                }
+            }
+            ResponseData::RestoreComplete {
+                ref set_name,
+                ref target,
+                files_restored,
+                bytes_restored,
+                duration_secs,
+            } if ours(set_name, target) => {
+                if show_progress {
+                    print!("\r\x1b[2K");
+                }
+                if json {
+                    println!("{}", serde_json::to_string(&data)?);
+                } else if !quiet {
+                    println!(
+                        "Restore complete: {} files, {} in {}",
+                        files_restored,
+                        format_size(bytes_restored),
+                        format_secs(duration_secs)
+                    );
+                }
+                return Ok(());
+            }
+            ResponseData::RestoreFailed {
+                ref set_name,
+                ref target,
+                ref error,
+            } if ours(set_name, target) => {
+                if show_progress {
+                    print!("\r\x1b[2K");
+                }
+                if json {
+                    println!("{}", serde_json::to_string(&data)?);
+                }
+                eprintln!("Restore failed: {}", error);
+                std::process::exit(4);
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn handle_resume(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Resume {
+            set_name: set_name.clone(),
+        },
+    )
+    .await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(_) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "set": set_name, "resumed": true })
+                );
+            } else if !quiet {
+                println!("✓ {}: Change-triggered backups resumed", set_name);
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
     }
 
     Ok(())
 }
 
-async fn handle_logs(follow: bool, _json: bool, quiet: bool) -> anyhow::Result<()> {
+async fn handle_cat(
+    set_name: String,
+    snapshot_id: String,
+    path: String,
+    output: Option<PathBuf>,
+    max_size: u64,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
     use std::io::Write;
-    use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
-    let log_dir = paths::log_path().parent().unwrap().to_path_buf();
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Cat {
+            set_name,
+            snapshot_id,
+            path: path.clone(),
+            max_bytes: Some(max_size),
+        },
+    )
+    .await?;
+
+    // Write to a temporary file next to the output so a failed transfer leaves nothing behind
+    let partial = output.as_ref().map(|out| {
+        let mut tmp = out.clone().into_os_string();
+        tmp.push(".partial");
+        PathBuf::from(tmp)
+    });
+    let mut sink: Box<dyn Write> = match partial {
+        Some(ref tmp) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(tmp).with_context(|| format!("Failed to create {:?}", tmp))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
 
-    let find_latest_log = || {
-        if !log_dir.exists() {
-            return None;
+    let fail = |code: i32, message: String| -> ! {
+        if let Some(ref tmp) = partial {
+            let _ = std::fs::remove_file(tmp);
         }
+        eprintln!("{}", message);
+        std::process::exit(code);
+    };
 
-        let entries = std::fs::read_dir(&log_dir).ok()?;
-        let mut logs: Vec<_> = entries
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                let name = e.file_name();
-                let name_str = name.to_string_lossy();
-                name_str == "vigil.log" || name_str.starts_with("vigil.log.")
-            })
-            .collect();
+    loop {
+        match receive_response(&mut reader).await? {
+            Response::Ok(Some(ResponseData::FileChunk { data })) => {
+                sink.write_all(&data)?;
+            }
+            Response::Ok(Some(ref data @ ResponseData::CatComplete { bytes })) => {
+                sink.flush()?;
+                drop(sink);
+                if let (Some(out), Some(tmp)) = (&output, &partial) {
+                    std::fs::rename(tmp, out)
+                        .with_context(|| format!("Failed to write {:?}", out))?;
+                    if json {
+                        println!("{}", serde_json::to_string(data)?);
+                    } else if !quiet {
+                        println!(
+                            "Wrote {} ({}) to {}",
+                            path,
+                            format_size(bytes),
+                            out.display()
+                        );
+                    }
+                }
+                return Ok(());
+            }
+            Response::Error { code, message } => {
+                // Exit code 4 per spec.md Section 12: Restic error
+                let exit = if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                    4
+                } else {
+                    1
+                };
+                fail(
+                    exit,
+                    format!("Error reading {} ({}): {}", path, code, message),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Where `vigil extract` saves `path` without `--output`: its file name in
+/// `dir`, which must not exist yet.
+fn extract_output(path: &str, dir: &std::path::Path) -> anyhow::Result<PathBuf> {
+    let name = std::path::Path::new(path)
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name; pass --output", path))?;
+    let output = dir.join(name);
+    if output.exists() {
+        anyhow::bail!(
+            "{} already exists; pass --output to choose another file",
+            output.display()
+        );
+    }
+    Ok(output)
+}
+
+async fn handle_unmount(
+    set_name: Option<String>,
+    snapshot_id: Option<String>,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    if dry_run {
+        send_request(reader.get_mut(), Request::Status).await?;
+        let sets = match receive_reply(&mut reader).await? {
+            Response::Ok(Some(ResponseData::Status { sets })) => sets,
+            Response::Error { code, message } => {
+                eprintln!("Error from daemon ({}): {}", code, message);
+                std::process::exit(1);
+            }
+            _ => anyhow::bail!("Unexpected response from daemon."),
+        };
+        if let Some(name) = set_name
+            .as_ref()
+            .filter(|n| !sets.iter().any(|s| &s.name == *n))
+        {
+            anyhow::bail!("Backup set '{}' not found", name);
+        }
+        let mut plan = Plan::default();
+        for set in sets
+            .into_iter()
+            .filter(|s| set_name.as_ref().is_none_or(|n| *n == s.name))
+        {
+            for mount in set.mounts.into_iter().filter(|m| {
+                snapshot_id
+                    .as_ref()
+                    .is_none_or(|id| m.snapshot.starts_with(id.as_str()))
+            }) {
+                plan.push(PlannedAction::Unmount {
+                    set_name: set.name.clone(),
+                    snapshot: mount.snapshot,
+                    path: mount.path,
+                });
+            }
+        }
+        let command = match (&set_name, &snapshot_id) {
+            (Some(name), Some(snapshot)) => format!("unmount {} --snapshot {}", name, snapshot),
+            (Some(name), None) => format!("unmount {}", name),
+            _ => "unmount".to_string(),
+        };
+        return plan.print(&command, json, quiet);
+    }
+    send_request(
+        reader.get_mut(),
+        Request::Unmount {
+            set_name: set_name.clone(),
+            snapshot_id: snapshot_id.clone(),
+        },
+    )
+    .await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(_) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "status": "success",
+                        "unmounted": set_name.as_deref().unwrap_or("all"),
+                        "snapshot": snapshot_id
+                    })
+                );
+            } else if !quiet {
+                match (set_name, snapshot_id) {
+                    (Some(name), Some(snapshot)) => {
+                        println!(
+                            "Successfully unmounted snapshot {} of set '{}'.",
+                            snapshot, name
+                        )
+                    }
+                    (Some(name), None) => println!("Successfully unmounted set '{}'.", name),
+                    _ => println!("Successfully unmounted all sets."),
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error unmounting ({}): {}", code, message);
+            std::process::exit(5); // Exit code 5 per spec.md Section 12: Mount/unmount error
+        }
+        _ => {
+            println!("Unexpected response from service daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// The daemon log file written to most recently. Logs rotate daily into
+/// `vigil.log.YYYY-MM-DD`, so this is the one being appended to.
+fn latest_log_file(log_dir: &std::path::Path) -> Option<PathBuf> {
+    if !log_dir.exists() {
+        return None;
+    }
+
+    let entries = std::fs::read_dir(log_dir).ok()?;
+    let mut logs: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name();
+            let name_str = name.to_string_lossy();
+            name_str == "vigil.log" || name_str.starts_with("vigil.log.")
+        })
+        .collect();
+
+    logs.sort_by_key(|e| e.metadata().and_then(|m| m.modified()).ok());
+    logs.last().map(|e| e.path())
+}
 
-        logs.sort_by_key(|e| e.metadata().and_then(|m| m.modified()).ok());
-        logs.last().map(|e| e.path())
+/// The log of the most recent backup run in `dir`. Run logs are named by
+/// their start time, so names sort in the order the runs started.
+fn latest_run_log(dir: &std::path::Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .max()
+}
+
+fn handle_run_log(set_name: &str, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let dir = paths::run_log_dir(set_name);
+    let Some(path) = latest_run_log(&dir) else {
+        if json {
+            println!("{}", serde_json::json!({ "set": set_name, "path": null }));
+        } else if !quiet {
+            println!("No backup run of set '{}' was logged yet.", set_name);
+        }
+        return Ok(());
     };
+    let output = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "set": set_name, "path": path, "output": output })
+        );
+    } else {
+        if !quiet {
+            println!("==> {} <==", path.display());
+        }
+        print!("{}", output);
+    }
+    Ok(())
+}
+
+async fn handle_logs(follow: bool, _json: bool, quiet: bool) -> anyhow::Result<()> {
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let log_dir = paths::log_path().parent().unwrap().to_path_buf();
+    let find_latest_log = || latest_log_file(&log_dir);
 
     let mut log_path = find_latest_log();
 
@@ -765,13 +2373,190 @@ async fn handle_logs(follow: bool, _json: bool, quiet: bool) -> anyhow::Result<(
     Ok(())
 }
 
-async fn handle_bootstrap(json: bool, quiet: bool) -> anyhow::Result<()> {
+/// The generated service unit, starting the daemon binary at `daemon`. It
+/// requires the socket unit, so systemd always passes the daemon its
+/// listening socket.
+fn service_unit(daemon: &std::path::Path) -> String {
+    format!(
+        r#"[Unit]
+Description=Vigil Daemon - Automated Backup Service
+After=default.target
+Requires=vigil-daemon.socket
+After=vigil-daemon.socket
+
+[Service]
+Type=simple
+ExecStart={}
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy=default.target
+"#,
+        systemd_exec_path(daemon)
+    )
+}
+
+/// `path` as the command of an `ExecStart=` line: `%` specifiers escaped, and
+/// quoted when it contains whitespace or quotes.
+fn systemd_exec_path(path: &std::path::Path) -> String {
+    let escaped = path.to_string_lossy().replace('%', "%%");
+    if escaped.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// The daemon binary the service should run: the one next to this `vigil`,
+/// else the first on `PATH`, else the cargo install location.
+fn daemon_binary() -> PathBuf {
+    let sibling = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("vigil-daemon")));
+    if let Some(path) = sibling.filter(|p| p.is_file()) {
+        return path;
+    }
+    std::env::var_os("PATH")
+        .and_then(|dirs| {
+            std::env::split_paths(&dirs)
+                .map(|dir| dir.join("vigil-daemon"))
+                .find(|p| p.is_file())
+        })
+        .unwrap_or_else(|| {
+            std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(".cargo/bin/vigil-daemon")
+        })
+}
+
+/// Whether `dir` is one of the directories in `path_var` (a `PATH` value).
+fn dir_on_path(dir: &std::path::Path, path_var: &std::ffi::OsStr) -> bool {
+    let canonical = |p: &std::path::Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let dir = canonical(dir);
+    std::env::split_paths(path_var).any(|entry| canonical(&entry) == dir)
+}
+
+/// Copies `src` to `dest` through a temporary file in the same directory, so
+/// a running binary at `dest` is replaced rather than overwritten.
+fn install_binary(src: &std::path::Path, dest: &std::path::Path) -> anyhow::Result<()> {
+    let tmp = dest.with_extension("tmp");
+    std::fs::copy(src, &tmp).with_context(|| format!("Failed to copy {}", src.display()))?;
+    vigil_lib::platform::set_mode(&tmp, 0o755)?;
+    std::fs::rename(&tmp, dest).with_context(|| format!("Failed to install {}", dest.display()))?;
+    Ok(())
+}
+
+async fn handle_self_install(
+    dir: Option<PathBuf>,
+    no_service: bool,
+    on_demand: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let dir = dir.unwrap_or_else(paths::user_bin_dir);
+    let exe = std::env::current_exe().context("Failed to locate the vigil binary")?;
+    let daemon = exe.with_file_name("vigil-daemon");
+    if !daemon.is_file() {
+        anyhow::bail!(
+            "vigil-daemon not found next to {}. Run self-install from the directory holding both binaries.",
+            exe.display()
+        );
+    }
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut installed = Vec::new();
+    for (name, src) in [("vigil", &exe), ("vigil-daemon", &daemon)] {
+        let dest = dir.join(name);
+        let same = dest.canonicalize().ok() == src.canonicalize().ok();
+        if !same {
+            install_binary(src, &dest)?;
+        }
+        if !quiet && !json {
+            if same {
+                println!("{} is already installed at {}", name, dest.display());
+            } else {
+                println!("Installed {} to {}", name, dest.display());
+            }
+        }
+        installed.push(dest);
+    }
+
+    let on_path = std::env::var_os("PATH").is_some_and(|path| dir_on_path(&dir, &path));
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "copied",
+                "dir": dir,
+                "binaries": installed,
+                "on_path": on_path,
+            })
+        );
+    } else if !on_path && !quiet {
+        println!(
+            "Warning: {} is not on your PATH. Add it in your shell profile, e.g. export PATH=\"{}:$PATH\"",
+            dir.display(),
+            dir.display()
+        );
+    }
+
+    if !no_service {
+        handle_bootstrap(on_demand, Some(dir.join("vigil-daemon")), json, quiet).await?;
+    }
+    Ok(())
+}
+
+/// The socket unit listening on `socket_path`, starting the daemon on the first connection.
+fn socket_unit(socket_path: &std::path::Path) -> String {
+    format!(
+        r#"[Unit]
+Description=Vigil Daemon Socket
+
+[Socket]
+ListenStream={}
+SocketMode=0600
+
+[Install]
+WantedBy=sockets.target
+"#,
+        socket_path.display()
+    )
+}
+
+/// The installed units, socket first. Installs from before socket activation
+/// have no socket unit.
+fn installed_units() -> Vec<&'static str> {
+    let mut units = Vec::new();
+    if paths::systemd_socket_unit_path().exists() {
+        units.push("vigil-daemon.socket");
+    }
+    units.push("vigil-daemon.service");
+    units
+}
+
+/// Installs and enables the systemd units, running the daemon binary at
+/// `daemon` (by default the one found by [`daemon_binary`]).
+async fn handle_bootstrap(
+    on_demand: bool,
+    daemon: Option<PathBuf>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
     if !quiet && !json {
         println!("Installing vigil service...");
     }
+    let daemon = daemon.unwrap_or_else(daemon_binary);
+    if !daemon.is_file() && !quiet && !json {
+        println!(
+            "Warning: {} does not exist; the service will fail to start until it is installed.",
+            daemon.display()
+        );
+    }
 
     // 1. Dependency check
-    let deps = ["restic", "fusermount3", "notify-send"];
+    let deps = ["restic", vigil_lib::platform::FUSE_HELPER, "notify-send"];
     let mut missing = Vec::new();
     for dep in deps {
         // Use `which` for dependency check since some tools (e.g., notify-send)
@@ -793,29 +2578,18 @@ async fn handle_bootstrap(json: bool, quiet: bool) -> anyhow::Result<()> {
         println!("Please install them to use all features.");
     }
 
-    // 2. Generate systemd unit file
+    // 2. Generate systemd unit files
     let unit_path = paths::systemd_unit_path();
     if let Some(parent) = unit_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
 
-    let unit_content = r#"[Unit]
-Description=Vigil Daemon - Automated Backup Service
-After=default.target
-
-[Service]
-Type=simple
-ExecStart=%h/.cargo/bin/vigil-daemon
-Restart=on-failure
-RestartSec=5
-
-[Install]
-WantedBy=default.target
-"#;
-
-    std::fs::write(&unit_path, unit_content)?;
+    std::fs::write(&unit_path, service_unit(&daemon))?;
+    let socket_unit_path = paths::systemd_socket_unit_path();
+    std::fs::write(&socket_unit_path, socket_unit(&paths::socket_path()))?;
     if !quiet && !json {
         println!("Generated systemd unit at {:?}", unit_path);
+        println!("Generated systemd socket unit at {:?}", socket_unit_path);
     }
 
     // 3. systemctl --user daemon-reload
@@ -832,23 +2606,44 @@ WantedBy=default.target
         anyhow::bail!("Failed to reload systemd daemon.");
     }
 
-    // 4. systemctl --user enable --now vigil-daemon.service
+    // 4. systemctl --user enable --now vigil-daemon.socket [vigil-daemon.service]
+    let units: &[&str] = if on_demand {
+        &["vigil-daemon.socket"]
+    } else {
+        &["vigil-daemon.socket", "vigil-daemon.service"]
+    };
     if !quiet && !json {
-        println!("Enabling and starting vigil-daemon service...");
+        if on_demand {
+            println!("Enabling vigil-daemon socket (the daemon starts on first use)...");
+        } else {
+            println!("Enabling and starting vigil-daemon service...");
+        }
     }
     let status = tokio::process::Command::new("systemctl")
         .arg("--user")
         .arg("enable")
         .arg("--now")
-        .arg("vigil-daemon.service")
+        .args(units)
         .status()
         .await?;
 
     if status.success() {
         if json {
-            println!("{}", serde_json::json!({ "status": "installed" }));
-        } else if !quiet {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "status": "installed",
+                    "on_demand": on_demand,
+                    "daemon": daemon,
+                })
+            );
+        } else if !quiet {
             println!("Successfully installed vigil-daemon service.");
+            if on_demand {
+                println!(
+                    "Note: changes are only watched once a vigil command has started the daemon."
+                );
+            }
         }
     } else {
         anyhow::bail!("Failed to enable/start vigil-daemon service.");
@@ -887,11 +2682,12 @@ async fn handle_disable(json: bool, quiet: bool) -> anyhow::Result<()> {
         warn_if_mounts_active();
         println!("Stopping and disabling vigil-daemon service...");
     }
+    // The socket goes first, or the next connection would start the daemon again
     let status = tokio::process::Command::new("systemctl")
         .arg("--user")
         .arg("disable")
         .arg("--now")
-        .arg("vigil-daemon.service")
+        .args(installed_units())
         .status()
         .await?;
 
@@ -908,33 +2704,83 @@ async fn handle_disable(json: bool, quiet: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_uninstall(purge: bool, json: bool, quiet: bool) -> anyhow::Result<()> {
+/// What `service uninstall` removes: the installed units, and with `purge`
+/// the configuration and data directories.
+fn uninstall_plan(purge: bool) -> Plan {
+    let mut plan = Plan::default();
+    let unit_files: Vec<PathBuf> = [
+        paths::systemd_socket_unit_path(),
+        paths::systemd_unit_path(),
+    ]
+    .into_iter()
+    .filter(|p| p.exists())
+    .collect();
+    if !unit_files.is_empty() {
+        for unit in installed_units() {
+            plan.push(PlannedAction::DisableUnit {
+                unit: unit.to_string(),
+            });
+        }
+        for path in unit_files {
+            plan.push(PlannedAction::RemoveFile { path });
+        }
+        plan.push(PlannedAction::ReloadSystemd);
+    }
+    if purge {
+        for path in [paths::config_dir(), paths::data_dir()] {
+            if path.exists() {
+                plan.push(PlannedAction::RemoveDir { path });
+            }
+        }
+    }
+    plan
+}
+
+async fn handle_uninstall(
+    purge: bool,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    if dry_run {
+        let command = if purge {
+            "service uninstall --purge"
+        } else {
+            "service uninstall"
+        };
+        return uninstall_plan(purge).print(command, json, quiet);
+    }
     if !quiet && !json {
         warn_if_mounts_active();
         println!("Uninstalling vigil...");
     }
 
     // 1. Stop and disable service
+    let units = installed_units();
     let _ = tokio::process::Command::new("systemctl")
         .arg("--user")
         .arg("stop")
-        .arg("vigil-daemon.service")
+        .args(&units)
         .status()
         .await;
 
     let _ = tokio::process::Command::new("systemctl")
         .arg("--user")
         .arg("disable")
-        .arg("vigil-daemon.service")
+        .args(&units)
         .status()
         .await;
 
-    // 2. Remove unit file
-    let unit_path = paths::systemd_unit_path();
-    if unit_path.exists() {
-        std::fs::remove_file(&unit_path)?;
-        if !quiet && !json {
-            println!("Removed systemd unit {:?}", unit_path);
+    // 2. Remove unit files
+    for unit_path in [
+        paths::systemd_socket_unit_path(),
+        paths::systemd_unit_path(),
+    ] {
+        if unit_path.exists() {
+            std::fs::remove_file(&unit_path)?;
+            if !quiet && !json {
+                println!("Removed systemd unit {:?}", unit_path);
+            }
         }
     }
 
@@ -958,18 +2804,7 @@ async fn handle_uninstall(purge: bool, json: bool, quiet: bool) -> anyhow::Resul
             }
         }
 
-        let data_dir = paths::log_path()
-            .parent()
-            .map(|p| p.to_path_buf())
-            .unwrap_or_else(|| {
-                let mut p = std::env::var_os("HOME")
-                    .map(std::path::PathBuf::from)
-                    .unwrap_or_else(|| std::path::PathBuf::from("/tmp"));
-                p.push(".local");
-                p.push("share");
-                p.push("vigil");
-                p
-            });
+        let data_dir = paths::data_dir();
 
         if data_dir.exists() {
             std::fs::remove_dir_all(&data_dir)?;
@@ -1001,7 +2836,7 @@ async fn handle_prune(set_name: Option<String>, json: bool, quiet: bool) -> anyh
     )
     .await?;
 
-    let response = receive_response(&mut reader).await?;
+    let response = receive_reply(&mut reader).await?;
     match response {
         Response::Ok(Some(ref data)) => match data {
             ResponseData::PruneResult {
@@ -1068,9 +2903,382 @@ async fn handle_prune(set_name: Option<String>, json: bool, quiet: bool) -> anyh
     Ok(())
 }
 
+async fn handle_prune_preview(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::PrunePreview { set_name }).await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(
+            ref data @ ResponseData::PrunePreview {
+                ref set_name,
+                ref to_remove,
+                to_keep,
+            },
+        )) => {
+            if json {
+                println!("{}", serde_json::to_string(data)?);
+            } else if !quiet {
+                if to_remove.is_empty() {
+                    println!(
+                        "Pruning set '{}' would forget nothing; {} snapshot(s) kept.",
+                        set_name, to_keep
+                    );
+                    return Ok(());
+                }
+                println!(
+                    "Pruning set '{}' would forget {} snapshot(s) and keep {}:",
+                    set_name,
+                    to_remove.len(),
+                    to_keep
+                );
+                println!("{:<10} {:<20} {:<10} {:<30}", "ID", "DATE", "SIZE", "PATHS");
+                println!("{}", "-".repeat(70));
+                for s in to_remove {
+                    let size = s
+                        .total_bytes
+                        .map(format_size)
+                        .unwrap_or_else(|| "N/A".to_string());
+                    let paths = s
+                        .paths
+                        .iter()
+                        .map(|p| p.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    println!(
+                        "{:<10} {:<20} {:<10} {:<30}",
+                        s.short_id,
+                        s.timestamp.format("%Y-%m-%d %H:%M").to_string(),
+                        size,
+                        paths
+                    );
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                4
+            } else {
+                1
+            });
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// The snapshot of `set_name` whose ID is `id` or starts with it.
+async fn find_snapshot(set_name: &str, id: &str) -> anyhow::Result<SnapshotInfo> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Snapshots {
+            set_name: set_name.to_string(),
+            limit: None,
+            tag: None,
+            since: None,
+            until: None,
+        },
+    )
+    .await?;
+    let snapshots = match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::Snapshots { snapshots })) => snapshots,
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                4
+            } else {
+                1
+            });
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    };
+    let id = id.to_ascii_lowercase();
+    let mut matches = snapshots.into_iter().filter(|s| s.id.starts_with(&id));
+    match (matches.next(), matches.next()) {
+        (Some(snapshot), None) => Ok(snapshot),
+        (None, _) => anyhow::bail!("No snapshot with ID {} in set '{}'", id, set_name),
+        (Some(_), Some(_)) => anyhow::bail!(
+            "Snapshot ID {} is ambiguous; give more characters of the ID",
+            id
+        ),
+    }
+}
+
+async fn handle_forget(
+    set_name: String,
+    snapshot_id: String,
+    prune: bool,
+    yes: bool,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    if dry_run {
+        let snapshot = find_snapshot(&set_name, &snapshot_id).await?;
+        let mut plan = Plan::default();
+        plan.push(PlannedAction::ForgetSnapshot {
+            set_name: set_name.clone(),
+            snapshot_id: snapshot.short_id,
+            prune,
+        });
+        let command = format!(
+            "forget {} {}{}",
+            set_name,
+            snapshot_id,
+            if prune { " --prune" } else { "" }
+        );
+        return plan.print(&command, json, quiet);
+    }
+    if !yes {
+        if json || quiet {
+            anyhow::bail!("forget requires --yes when running in --json or --quiet mode");
+        }
+        let consequence = if prune {
+            "its data is deleted unless another snapshot uses it"
+        } else {
+            "its data stays in the repository until the next prune"
+        };
+        println!(
+            "Snapshot {} of set '{}' will be removed permanently; {}.",
+            snapshot_id, set_name, consequence
+        );
+        if !confirm_prompt_default_no("Forget this snapshot?")? {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Forget {
+            set_name,
+            snapshot_id,
+            prune,
+        },
+    )
+    .await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(
+            ref data @ ResponseData::Forgotten {
+                ref set_name,
+                ref snapshot_id,
+                reclaimed_bytes,
+            },
+        )) => {
+            if json {
+                println!("{}", serde_json::to_string(data)?);
+            } else if !quiet {
+                match reclaimed_bytes {
+                    Some(bytes) => println!(
+                        "Forgot snapshot {} of set '{}': {} reclaimed",
+                        snapshot_id,
+                        set_name,
+                        format_size(bytes)
+                    ),
+                    None => println!(
+                        "Forgot snapshot {} of set '{}'. Run `vigil prune {}` to reclaim its space.",
+                        snapshot_id, set_name, set_name
+                    ),
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            // Exit code 4 for restic errors per spec.md Section 12
+            std::process::exit(if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                4
+            } else {
+                1
+            });
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes a set's repository password with `restic key passwd`, then stores
+/// the new password where the set reads it.
+///
+/// A set on the shared `.repo_password` is moved to its own password file first,
+/// so other sets keep working. Sets with `password_command` only get the key
+/// changed; the user updates their secret store.
+async fn handle_password(
+    set_name: String,
+    new_password_file: Option<PathBuf>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    use vigil_lib::config::{ConfigOp, EngineKind, PasswordSource};
+
+    let config = vigil_lib::config::load_config().context("Failed to load configuration")?;
+    let set = config
+        .backup_sets
+        .iter()
+        .find(|s| s.name == set_name)
+        .ok_or_else(|| anyhow!("Backup set '{}' not found in config", set_name))?;
+    if set.engine != EngineKind::Restic {
+        anyhow::bail!("Changing the password is only supported for restic sets");
+    }
+
+    let source = set.password_source();
+    let (dest, move_to_own_file) = match source {
+        PasswordSource::Command(_) => (None, false),
+        PasswordSource::File(_) if source.is_shared_default() => {
+            (Some(paths::set_password_path(&set_name)), true)
+        }
+        PasswordSource::File(ref path) => {
+            let sharing: Vec<&str> = config
+                .backup_sets
+                .iter()
+                .filter(|s| s.name != set_name && s.password_source() == source)
+                .map(|s| s.name.as_str())
+                .collect();
+            if !sharing.is_empty() {
+                anyhow::bail!(
+                    "Password file {:?} is also used by {}. Give '{}' its own password_file first.",
+                    path,
+                    sharing.join(", "),
+                    set_name
+                );
+            }
+            (Some(path.clone()), false)
+        }
+    };
+
+    let password = match new_password_file {
+        Some(ref file) => std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {:?}", file))?
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        None => {
+            let prompt = |msg: &str| -> anyhow::Result<String> {
+                if std::io::stdin().is_terminal() {
+                    Ok(rpassword::prompt_password(msg)?)
+                } else {
+                    prompt_user(msg)
+                }
+            };
+            let password = prompt(&format!("New password for '{}': ", set_name))?;
+            if password != prompt("Confirm password: ")? {
+                anyhow::bail!("Passwords do not match.");
+            }
+            password
+        }
+    };
+    if password.is_empty() {
+        anyhow::bail!("Password cannot be empty.");
+    }
+
+    // Stage the new password next to its destination so the final rename is atomic
+    let staging = dest
+        .clone()
+        .unwrap_or_else(|| paths::set_password_path(&set_name))
+        .with_extension("new");
+    if let Some(parent) = staging.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&staging, &password)?;
+    vigil_lib::platform::set_mode(&staging, 0o600)?;
+
+    if !quiet && !json {
+        println!("Changing repository password for '{}'...", set_name);
+    }
+    let output = engine_command(
+        "restic",
+        &set.process_env(&config.global)?,
+        &config.global.restic_env_passthrough,
+    )
+    .arg("key")
+    .arg("passwd")
+    .arg("-r")
+    .arg(&set.target)
+    .args(source.args())
+    .arg("--new-password-file")
+    .arg(&staging)
+    .output()
+    .await;
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            let _ = std::fs::remove_file(&staging);
+            anyhow::bail!(
+                "Failed to change password: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&staging);
+            return Err(e).context("Failed to execute restic");
+        }
+    }
+
+    match dest {
+        Some(ref dest) => std::fs::rename(&staging, dest)
+            .with_context(|| format!("Password changed, but failed to save it to {:?}", dest))?,
+        None => {
+            let _ = std::fs::remove_file(&staging);
+        }
+    }
+
+    if move_to_own_file {
+        let dest = dest.as_ref().expect("moved sets have a destination");
+        let op = ConfigOp::SetPasswordFile {
+            name: set_name.clone(),
+            password_file: dest.to_string_lossy().to_string(),
+        };
+        edit_config(vec![op]).await.with_context(|| {
+            format!(
+                "Password changed and saved to {:?}, but the config could not be updated. Add `password_file = {:?}` to set '{}'",
+                dest, dest, set_name
+            )
+        })?;
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "changed",
+                "set": set_name,
+                "password_file": dest,
+            })
+        );
+    } else if !quiet {
+        println!("✓ Password changed for '{}'.", set_name);
+        match dest {
+            Some(ref dest) if move_to_own_file => {
+                println!("  Saved to {:?}; the set now uses its own password file.", dest)
+            }
+            Some(ref dest) => println!("  Saved to {:?}.", dest),
+            None => println!(
+                "  '{}' reads its password from password_command; update your secret store to the new password.",
+                set_name
+            ),
+        }
+    }
+
+    Ok(())
+}
+
 async fn handle_check(
     set_name: Option<String>,
     config_only: bool,
+    deep: Option<(String, bool)>,
+    repair: Option<bool>,
     json: bool,
     quiet: bool,
 ) -> anyhow::Result<()> {
@@ -1090,15 +3298,58 @@ async fn handle_check(
         }
     };
 
+    // Classification policies are enforced at config load, so any classified set
+    // reaching this point complies with its policy.
+    let classifications: serde_json::Map<String, serde_json::Value> = config
+        .backup_sets
+        .iter()
+        .filter_map(|s| {
+            s.classification
+                .map(|c| (s.name.clone(), serde_json::json!(c.to_string())))
+        })
+        .collect();
+    let append_only: Vec<&str> = config
+        .backup_sets
+        .iter()
+        .filter(|s| s.append_only)
+        .map(|s| s.name.as_str())
+        .collect();
+    let warnings = config.warnings();
+
     if !json && !quiet {
         println!(
             "✓ Configuration valid: {} backup sets defined",
             config.backup_sets.len()
         );
+        for (name, class) in &classifications {
+            println!(
+                "✓ {}: classified as {}, policy satisfied",
+                name,
+                class.as_str().unwrap_or_default()
+            );
+        }
+        for name in &append_only {
+            println!(
+                "ℹ {}: append-only repository, vigil does not prune it; remove old snapshots on the server",
+                name
+            );
+        }
+        for warning in &warnings {
+            println!("⚠ {}", warning);
+        }
     }
 
-    let password_path = paths::password_path();
-    let password_exists = password_path.exists();
+    // Every password file a set reads must exist. Password commands are exercised by the repo check.
+    let missing_password = config
+        .backup_sets
+        .iter()
+        .filter_map(|s| match s.password_source() {
+            vigil_lib::config::PasswordSource::File(path) if !path.exists() => Some(path),
+            _ => None,
+        })
+        .next();
+    let password_exists = missing_password.is_none();
+    let password_path = missing_password.unwrap_or_else(paths::password_path);
 
     if config_only {
         if json {
@@ -1108,7 +3359,10 @@ async fn handle_check(
                     "status": "ok",
                     "config_valid": true,
                     "backup_sets_count": config.backup_sets.len(),
-                    "password_file_exists": password_exists
+                    "password_file_exists": password_exists,
+                    "classifications": classifications,
+                    "append_only": append_only,
+                    "warnings": warnings
                 })
             );
         } else if !quiet {
@@ -1141,11 +3395,11 @@ async fn handle_check(
         println!("✓ Password file exists");
     }
 
-    let sets_to_check: Vec<_> = if let Some(name) = set_name {
+    let sets_to_check: Vec<_> = if let Some(ref name) = set_name {
         let set = config
             .backup_sets
             .iter()
-            .find(|s| s.name == name)
+            .find(|s| &s.name == name)
             .ok_or_else(|| anyhow!("Backup set '{}' not found in config", name))?;
         vec![set]
     } else {
@@ -1164,58 +3418,51 @@ async fn handle_check(
         return Ok(());
     }
 
-    let mut failed = false;
-    let mut results = Vec::new();
+    if let Some((subset, now)) = deep {
+        return handle_verify(set_name, subset, now, json, quiet).await;
+    }
+    if let (Some(yes), Some(set_name)) = (repair, set_name.clone()) {
+        return handle_repair(set_name, yes, json, quiet).await;
+    }
 
-    for set in sets_to_check {
-        if !json && !quiet {
-            print!("Checking '{}'... ", set.name);
-            use std::io::Write;
-            std::io::stdout().flush()?;
+    let outcomes = match daemon_repo_op(Request::Check { set_name }).await? {
+        Some(outcomes) => {
+            for outcome in &outcomes {
+                print_check_result(outcome, json, quiet);
+            }
+            outcomes
         }
-
-        // Use `restic snapshots --latest 1` as a quick check for repo accessibility
-        let output = tokio::process::Command::new("restic")
-            .arg("snapshots")
-            .arg("--repo")
-            .arg(&set.target)
-            .arg("--password-file")
-            .arg(&password_path)
-            .arg("--latest")
-            .arg("1")
-            .arg("--json")
-            .output()
-            .await;
-
-        match output {
-            Ok(output) => {
-                if output.status.success() {
-                    if !json && !quiet {
-                        println!("\r✓ {}: Repository accessible", set.name);
-                    }
-                    results.push(serde_json::json!({ "set": set.name, "accessible": true }));
-                } else {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    if !json {
-                        println!("\r✗ {}: Repository check failed", set.name);
-                        eprintln!("  Error: {}", stderr.trim());
-                        if stderr.contains("repository does not exist") {
-                            eprintln!("  Hint: You might need to initialize the repository first.");
-                            eprintln!("        Run `vigil init {}` to initialize it.", set.name);
-                        }
-                    }
-                    results.push(serde_json::json!({ "set": set.name, "accessible": false, "error": stderr.trim() }));
-                    failed = true;
+        None => {
+            let mut outcomes = Vec::new();
+            for set in sets_to_check {
+                if !json && !quiet {
+                    print!("Checking '{}'... ", set.name);
+                    use std::io::Write;
+                    std::io::stdout().flush()?;
                 }
+                let outcome = check_repo_directly(set, &config.global).await;
+                print_check_result(&outcome, json, quiet);
+                outcomes.push(outcome);
             }
-            Err(e) => {
-                if !json {
-                    println!("\r✗ {}: Failed to execute restic", set.name);
-                    eprintln!("  Error: {}", e);
-                }
-                results.push(serde_json::json!({ "set": set.name, "accessible": false, "error": e.to_string() }));
-                failed = true;
+            outcomes
+        }
+    };
+
+    let failed = outcomes.iter().any(|o| o.status == RepoOpStatus::Failed);
+    let results: Vec<_> = outcomes
+        .iter()
+        .map(|o| match o.error {
+            Some(ref error) => {
+                serde_json::json!({ "set": o.set_name, "accessible": false, "error": error })
             }
+            None => serde_json::json!({ "set": o.set_name, "accessible": true }),
+        })
+        .collect();
+
+    let capabilities = fetch_capabilities().await;
+    if !json && !quiet {
+        if let Some(ref caps) = capabilities {
+            display_capabilities(caps);
         }
     }
 
@@ -1224,7 +3471,11 @@ async fn handle_check(
             "{}",
             serde_json::json!({
                 "status": if failed { "error" } else { "ok" },
-                "results": results
+                "results": results,
+                "capabilities": capabilities,
+                "classifications": classifications,
+                "append_only": append_only,
+                "warnings": warnings
             })
         );
     }
@@ -1236,202 +3487,2081 @@ async fn handle_check(
     Ok(())
 }
 
-async fn handle_purge(
-    set_name: String,
-    force: bool,
+/// Runs a deep repository check through the daemon, which records when each
+/// set was last verified. Sets outside their maintenance window are checked
+/// once it opens, unless `now` is set.
+async fn handle_verify(
+    set_name: Option<String>,
+    subset: String,
+    now: bool,
     json: bool,
     quiet: bool,
 ) -> anyhow::Result<()> {
-    let config_res = vigil_lib::config::load_config();
-    let mut target_path = None;
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Verify {
+            set_name: set_name.clone(),
+            subset,
+            now,
+        },
+    )
+    .await?;
 
-    if let Ok(config) = config_res {
-        if let Some(set) = config.backup_sets.iter().find(|s| s.name == set_name) {
-            if !force {
-                if json || quiet {
-                    anyhow::bail!("Purge requires --force when running in --json or --quiet mode");
-                }
-                println!("Backup set '{}' is still present in config.toml. Remove it first or use --force.", set_name);
-                return Ok(()); // Exit gracefully if not forced and set is in config
+    let mut progress = ProgressLine::new(json, quiet);
+    let results = loop {
+        match receive_response(&mut reader).await? {
+            Response::Ok(Some(ResponseData::VerifyResults { results })) => break results,
+            Response::Event(ResponseData::VerifyProgress {
+                set_name: ref progress_set,
+                percent,
+                eta_secs,
+            }) if set_name.as_ref().is_none_or(|s| s == progress_set) => {
+                let eta = eta_secs
+                    .map(|secs| {
+                        format!(
+                            " (~{} left)",
+                            format_compact(Duration::seconds(secs as i64))
+                        )
+                    })
+                    .unwrap_or_default();
+                progress.draw(&format!(
+                    "Verifying '{}'… {:.0}%{}",
+                    progress_set,
+                    percent.floor(),
+                    eta
+                ))?;
             }
-            target_path = Some(set.target.clone());
+            Response::Error { code, message } => {
+                progress.clear()?;
+                eprintln!("Error verifying ({}): {}", code, message);
+                std::process::exit(1);
+            }
+            _ => {}
         }
-    }
+    };
+    progress.clear()?;
+
+    if !json {
+        for outcome in &results {
+            match outcome.error {
+                None if outcome.status == RepoOpStatus::Deferred && !quiet => {
+                    if let Some(at) = outcome.deferred_until {
+                        println!(
+                            "◷ {}: Deep check deferred to the maintenance window at {}. Use --now to run it at once.",
+                            outcome.set_name,
+                            at.with_timezone(&chrono::Local).format("%H:%M")
+                        );
+                    }
+                }
+                None if !quiet => println!("✓ {}: Repository verified", outcome.set_name),
+                None => {}
+                Some(ref error) => {
+                    println!("✗ {}: Repository verification failed", outcome.set_name);
+                    eprintln!("  Error: {}", error);
+                }
+            }
+        }
+    }
+
+    let failed = results.iter().any(|o| o.status == RepoOpStatus::Failed);
+    if json {
+        let results: Vec<_> = results
+            .iter()
+            .map(|o| match o.error {
+                Some(ref error) => {
+                    serde_json::json!({ "set": o.set_name, "verified": false, "error": error })
+                }
+                None if o.status == RepoOpStatus::Deferred => serde_json::json!({
+                    "set": o.set_name,
+                    "verified": false,
+                    "deferred_until": o.deferred_until
+                }),
+                None => serde_json::json!({ "set": o.set_name, "verified": true }),
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": if failed { "error" } else { "ok" },
+                "results": results
+            })
+        );
+    }
+
+    if failed {
+        std::process::exit(4);
+    }
+    Ok(())
+}
+
+/// Repair steps offered after a failed check, in order, with what each one does.
+const REPAIR_STEPS: [(RepairStep, &str); 2] = [
+    (
+        RepairStep::Index,
+        "Rebuild the repository index from the data files",
+    ),
+    (
+        RepairStep::Snapshots,
+        "Rewrite damaged snapshots without the files that can no longer be read, and forget the originals. Those files are lost from the affected snapshots",
+    ),
+];
+
+/// Guided repair of one set's repository through the daemon: checks it, then
+/// offers each repair step in turn, re-checking after every step until the
+/// check passes.
+async fn handle_repair(set_name: String, yes: bool, json: bool, quiet: bool) -> anyhow::Result<()> {
+    if !yes && (json || quiet) {
+        anyhow::bail!("--repair requires --yes when running in --json or --quiet mode");
+    }
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+
+    let mut steps = Vec::new();
+    let mut check = run_repair_step(&mut reader, &set_name, RepairStep::Check, json, quiet).await?;
+    steps.push(check.clone());
+    if check.success {
+        if !json && !quiet {
+            println!(
+                "✓ {}: Repository has no errors; nothing to repair",
+                set_name
+            );
+        }
+    } else {
+        if !json {
+            println!("✗ {}: Repository check found errors", set_name);
+            eprintln!("  {}", check.output.trim());
+        }
+        let mut stopped = false;
+        for (step, description) in REPAIR_STEPS {
+            if !yes {
+                println!();
+                println!("{} (`{}`).", description, step);
+                if !confirm_prompt_default_no("Run this step?")? {
+                    stopped = true;
+                    break;
+                }
+            }
+            let outcome = run_repair_step(&mut reader, &set_name, step, json, quiet).await?;
+            steps.push(outcome);
+            check = run_repair_step(&mut reader, &set_name, RepairStep::Check, json, quiet).await?;
+            steps.push(check.clone());
+            if check.success {
+                break;
+            }
+            if !json {
+                println!("✗ {}: Repository check still finds errors", set_name);
+            }
+        }
+        if !json {
+            if check.success {
+                println!("✓ {}: Repository repaired; the check passes", set_name);
+            } else if stopped {
+                println!("Repair stopped. The repository still has errors.");
+            } else {
+                eprintln!(
+                    "  The remaining damage cannot be repaired automatically. See https://restic.readthedocs.io/en/stable/077_troubleshooting.html"
+                );
+            }
+        }
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": if check.success { "ok" } else { "error" },
+                "set": set_name,
+                "healthy": check.success,
+                "steps": steps,
+            })
+        );
+    }
+    if !check.success {
+        std::process::exit(4);
+    }
+    Ok(())
+}
+
+/// Runs one repair step through the daemon, printing restic's output as it
+/// arrives. Exits on a daemon error, e.g. when a backup is running.
+async fn run_repair_step(
+    reader: &mut BufReader<&mut IpcStream>,
+    set_name: &str,
+    step: RepairStep,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<RepairOutcome> {
+    if !json && !quiet {
+        println!("Running `{}` on '{}'…", step, set_name);
+    }
+    send_request(
+        reader.get_mut(),
+        Request::Repair {
+            set_name: set_name.to_string(),
+            step,
+        },
+    )
+    .await?;
+    loop {
+        match receive_response(reader).await? {
+            Response::Ok(Some(ResponseData::RepairResult { outcome })) => return Ok(outcome),
+            Response::Event(ResponseData::RepairProgress {
+                set_name: ref progress_set,
+                ref line,
+                ..
+            }) if progress_set == set_name && !json && !quiet => println!("  {}", line),
+            Response::Error { code, message } => {
+                eprintln!("Error from daemon ({}): {}", code, message);
+                std::process::exit(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lists snapshots as a quick check for repo accessibility, without the daemon.
+async fn check_repo_directly(
+    set: &vigil_lib::config::BackupSet,
+    global: &vigil_lib::config::GlobalConfig,
+) -> RepoOpResult {
+    let env = match set.process_env(global) {
+        Ok(env) => env,
+        Err(e) => {
+            return RepoOpResult {
+                set_name: set.name.clone(),
+                status: RepoOpStatus::Failed,
+                error: Some(e.to_string()),
+                deferred_until: None,
+            }
+        }
+    };
+    let mut cmd = engine_command(set.engine.binary(), &env, &global.restic_env_passthrough);
+    cmd.arg("snapshots")
+        .arg("-r")
+        .arg(&set.target)
+        .args(set.password_source().args())
+        .arg("--json");
+    if set.engine == vigil_lib::config::EngineKind::Restic {
+        cmd.arg("--latest").arg("1");
+    }
+    let output = cmd.output().await;
+
+    let error = match output {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Some(format!("Failed to execute {}: {}", set.engine.binary(), e)),
+    };
+    RepoOpResult {
+        set_name: set.name.clone(),
+        status: if error.is_some() {
+            RepoOpStatus::Failed
+        } else {
+            RepoOpStatus::Accessible
+        },
+        error,
+        deferred_until: None,
+    }
+}
+
+fn print_check_result(outcome: &RepoOpResult, json: bool, quiet: bool) {
+    match outcome.error {
+        None => {
+            if !json && !quiet {
+                println!("\r✓ {}: Repository accessible", outcome.set_name);
+            }
+        }
+        Some(ref error) => {
+            if !json {
+                println!("\r✗ {}: Repository check failed", outcome.set_name);
+                eprintln!("  Error: {}", error);
+                if error.contains("repository does not exist") {
+                    eprintln!("  Hint: You might need to initialize the repository first.");
+                    eprintln!(
+                        "        Run `vigil init {}` to initialize it.",
+                        outcome.set_name
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Queries the daemon for its detected optional tools.
+/// Returns `None` if the daemon is not running.
+async fn fetch_capabilities() -> Option<Vec<CapabilityStatus>> {
+    let mut stream = transport::connect().await.ok()?;
+    send_request(&mut stream, Request::Capabilities)
+        .await
+        .ok()?;
+    let mut reader = BufReader::new(&mut stream);
+    match receive_reply(&mut reader).await.ok()? {
+        Response::Ok(Some(ResponseData::Capabilities { capabilities })) => Some(capabilities),
+        _ => None,
+    }
+}
+
+fn display_capabilities(capabilities: &[CapabilityStatus]) {
+    println!("Optional tools:");
+    for cap in capabilities {
+        if cap.available {
+            println!("  ✓ {}", cap.name);
+        } else {
+            println!(
+                "  ✗ {}: {}",
+                cap.name,
+                cap.hint.as_deref().unwrap_or("not available")
+            );
+        }
+    }
+}
+
+/// What purging `set_name`'s repository at `target` removes.
+fn purge_plan(set_name: &str, target: &str) -> Plan {
+    let mut plan = Plan::default();
+    if vigil_lib::config::Backend::of(target).is_remote() {
+        plan.push(PlannedAction::KeepRemoteRepository {
+            target: target.to_string(),
+        });
+    } else {
+        plan.push(PlannedAction::DeleteRepository {
+            target: target.to_string(),
+        });
+    }
+    let cache_dir = paths::data_dir().join("cache").join(set_name);
+    for path in [paths::mount_path(set_name), cache_dir] {
+        if path.exists() {
+            plan.push(PlannedAction::RemoveDir { path });
+        }
+    }
+    plan
+}
+
+async fn handle_purge(
+    set_name: String,
+    force: bool,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    if dry_run {
+        let config = vigil_lib::config::load_config()?;
+        let Some(set) = config.backup_sets.iter().find(|s| s.name == set_name) else {
+            anyhow::bail!("Backup set '{}' not found in the configuration", set_name);
+        };
+        return purge_plan(&set_name, &set.target).print(
+            &format!("purge {}", set_name),
+            json,
+            quiet,
+        );
+    }
+    if !force && (json || quiet) {
+        anyhow::bail!("Purge requires --force when running in --json or --quiet mode");
+    }
+    let configured = vigil_lib::config::load_config()
+        .is_ok_and(|config| config.backup_sets.iter().any(|s| s.name == set_name));
+    if configured && !force {
+        println!(
+            "Backup set '{}' is still present in config.toml. Remove it first or use --force.",
+            set_name
+        );
+        return Ok(()); // Exit gracefully if not forced and set is in config
+    }
+
+    // 1. Announce the purge; the daemon logs it and returns a short-lived token
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::PurgeToken {
+            set_name: set_name.clone(),
+        },
+    )
+    .await?;
+    let (target_path, token) = match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::PurgeToken { target, token, .. })) => (target, token),
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    };
+
+    // 2. Confirm. --force skips the yes/no question but not typing the set name.
+    let warning = format!(
+        "WARNING: This will permanently delete ALL backup data for '{}' at '{}' and can NOT be undone!\nSource files will NOT be affected.",
+        set_name, target_path
+    );
+    if force {
+        if json || quiet {
+            // Keep machine-readable stdout clean
+            eprintln!("{}", warning);
+            eprint!("Type the set name to confirm: ");
+        } else {
+            println!("{}", warning);
+            print!("Type the set name to confirm: ");
+        }
+        use std::io::Write;
+        std::io::stdout().flush()?;
+        std::io::stderr().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if input.trim() != set_name {
+            anyhow::bail!(
+                "Purge cancelled: the typed name does not match '{}'",
+                set_name
+            );
+        }
+    } else {
+        println!("{}", warning);
+        if !confirm_prompt_default_no("Are you sure you want to proceed?")? {
+            println!("Purge cancelled.");
+            return Ok(());
+        }
+    }
+
+    // 3. Purge with the token. The daemon unmounts the set and deletes the
+    // repository, mount point and cached metadata.
+    if !quiet && !json {
+        println!("Deleting backup set '{}'...", set_name);
+    }
+    send_request(
+        reader.get_mut(),
+        Request::Purge {
+            set_name: set_name.clone(),
+            token,
+        },
+    )
+    .await?;
+    let repository_deleted = match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::Purged {
+            repository_deleted, ..
+        })) => repository_deleted,
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    };
+
+    // 4. Reload daemon config to stop tracking it (in case it's still there)
+    let _ = send_request(reader.get_mut(), Request::ReloadConfig).await;
+    let _ = receive_reply(&mut reader).await;
+
+    if !repository_deleted && !json {
+        eprintln!(
+            "Note: '{}' is a remote {} repository and was not deleted. Remove it with your storage provider's tools.",
+            target_path,
+            vigil_lib::config::Backend::of(&target_path)
+        );
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "status": "purged",
+                "set": set_name,
+                "target": target_path,
+                "repository_deleted": repository_deleted
+            })
+        );
+    } else if !quiet {
+        println!("Successfully purged backup set '{}'.", set_name);
+    }
+
+    Ok(())
+}
+
+async fn handle_snapshots(
+    set_name: String,
+    limit: usize,
+    filter: SnapshotFilter,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    if let (Some(since), Some(until)) = (filter.since, filter.until) {
+        if since > until {
+            anyhow::bail!("--since must not be later than --until");
+        }
+    }
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Snapshots {
+            set_name: set_name.clone(),
+            limit: Some(limit),
+            tag: filter.tag.clone(),
+            since: filter.since,
+            until: filter.until,
+        },
+    )
+    .await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::Snapshots { snapshots })) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&snapshots)?);
+            } else if !quiet {
+                if snapshots.is_empty() {
+                    println!(
+                        "No snapshots{} found for set '{}'.",
+                        describe_filter(&filter),
+                        set_name
+                    );
+                    return Ok(());
+                }
+
+                println!(
+                    "{:<10} {:<20} {:<10} {:<15} {:<30}",
+                    "ID", "DATE", "SIZE", "TAGS", "PATHS"
+                );
+                println!("{}", "-".repeat(86));
+
+                for s in snapshots {
+                    let date = s.timestamp.format("%Y-%m-%d %H:%M").to_string();
+                    let size = s
+                        .total_bytes
+                        .map(format_size)
+                        .unwrap_or_else(|| "N/A".to_string());
+                    let paths = s
+                        .paths
+                        .iter()
+                        .map(|p| p.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    let tags = if s.tags.is_empty() {
+                        "-".to_string()
+                    } else {
+                        s.tags.join(",")
+                    };
+
+                    println!(
+                        "{:<10} {:<20} {:<10} {:<15} {:<30}",
+                        s.short_id, date, size, tags, paths
+                    );
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_stats(
+    set_name: String,
+    limit: usize,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Stats {
+            set_name: set_name.clone(),
+        },
+    )
+    .await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::Stats { stats, snapshots })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "stats": stats,
+                        "dedup_ratio": stats.dedup_ratio(),
+                        "compression_ratio": stats.compression_ratio(),
+                        "snapshots": snapshots
+                    })
+                );
+            } else if !quiet {
+                for line in render_stats(&set_name, &stats, &snapshots, limit) {
+                    println!("{}", line);
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lines of `vigil stats`: repository totals, then the newest `limit` snapshots.
+fn render_stats(
+    set_name: &str,
+    stats: &RepoStats,
+    snapshots: &[SnapshotInfo],
+    limit: usize,
+) -> Vec<String> {
+    let ratio = |r: Option<f64>| r.map_or_else(|| "-".to_string(), |r| format!("{:.2}x", r));
+    let mut lines = vec![
+        format!("Repository of '{}':", set_name),
+        format!("  Snapshots:      {}", stats.snapshot_count),
+        format!(
+            "  Restore size:   {} in {} files",
+            format_size(stats.restore_bytes),
+            stats.file_count
+        ),
+        format!(
+            "  Unique data:    {} ({} blobs)",
+            format_size(stats.raw_bytes),
+            stats.blob_count
+        ),
+        format!(
+            "  Stored:         {} (compression {})",
+            format_size(stats.stored_bytes),
+            ratio(stats.compression_ratio())
+        ),
+        format!("  Deduplication:  {}", ratio(stats.dedup_ratio())),
+    ];
+
+    let shown = &snapshots[snapshots.len().saturating_sub(limit)..];
+    if !shown.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("{:<10} {:<20} {:>10}", "ID", "DATE", "SIZE"));
+        for s in shown.iter().rev() {
+            lines.push(format!(
+                "{:<10} {:<20} {:>10}",
+                s.short_id,
+                s.timestamp.format("%Y-%m-%d %H:%M"),
+                s.total_bytes
+                    .map(format_size)
+                    .unwrap_or_else(|| "N/A".to_string())
+            ));
+        }
+    }
+    lines
+}
+
+async fn handle_history(
+    set_name: String,
+    limit: usize,
+    ops: Vec<HistoryOp>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let backups = ops.contains(&HistoryOp::Backup);
+    let restores_shown = ops.contains(&HistoryOp::Restore);
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::History {
+            set_name: set_name.clone(),
+            limit: Some(limit),
+            ops,
+        },
+    )
+    .await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::History { runs, restores })) => {
+            if json {
+                let mut output = serde_json::json!({ "set": set_name });
+                if backups {
+                    output["runs"] = serde_json::json!(runs);
+                }
+                if restores_shown {
+                    output["restores"] = serde_json::json!(restores);
+                }
+                println!("{}", output);
+            } else if !quiet {
+                if backups {
+                    if runs.is_empty() {
+                        println!("No backup runs recorded for '{}' yet.", set_name);
+                    }
+                    for line in render_history(&runs) {
+                        println!("{}", line);
+                    }
+                }
+                if restores_shown {
+                    if backups {
+                        println!();
+                    }
+                    if restores.is_empty() {
+                        println!("No restores recorded for '{}' yet.", set_name);
+                    }
+                    for line in render_restore_history(&restores) {
+                        println!("{}", line);
+                    }
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lines of `vigil history`: one row per run, newest first.
+fn render_history(runs: &[BackupRun]) -> Vec<String> {
+    if runs.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![format!(
+        "{:<17} {:>8} {:<6} {:>10}  {}",
+        "STARTED", "DURATION", "RESULT", "ADDED", "SNAPSHOT / ERROR"
+    )];
+    for run in runs.iter().rev() {
+        let (result, added, detail) = if run.skipped_trivial {
+            (
+                "– skip",
+                "-".to_string(),
+                "too few changes, carried over".to_string(),
+            )
+        } else if run.success {
+            (
+                "✓ ok",
+                format_size(run.added_bytes),
+                run.snapshot_id
+                    .as_deref()
+                    .map(|id| id.chars().take(8).collect())
+                    .unwrap_or_default(),
+            )
+        } else {
+            (
+                "✗ fail",
+                "-".to_string(),
+                run.error
+                    .as_deref()
+                    .and_then(|e| e.lines().next())
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            )
+        };
+        lines.push(format!(
+            "{:<17} {:>8} {:<6} {:>10}  {}",
+            run.started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            format_secs(run.duration_secs()),
+            result,
+            added,
+            detail
+        ));
+    }
+    lines
+}
+
+/// Lines of `vigil history --ops restore`: one row per restore, newest first.
+fn render_restore_history(restores: &[RestoreRecord]) -> Vec<String> {
+    if restores.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![format!(
+        "{:<17} {:>8} {:<6} {:<8} {:<12} {}",
+        "STARTED", "DURATION", "RESULT", "SNAPSHOT", "BY", "RESTORED TO / ERROR"
+    )];
+    for restore in restores.iter().rev() {
+        let (result, detail) = if restore.success {
+            let what = match restore.path {
+                Some(ref path) => format!("{} → {}", path, restore.target),
+                None => restore.target.clone(),
+            };
+            (
+                "✓ ok",
+                format!(
+                    "{} ({} files, {})",
+                    what,
+                    restore.files_restored,
+                    format_size(restore.bytes_restored)
+                ),
+            )
+        } else {
+            (
+                "✗ fail",
+                restore
+                    .error
+                    .as_deref()
+                    .and_then(|e| e.lines().next())
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            )
+        };
+        lines.push(format!(
+            "{:<17} {:>8} {:<6} {:<8} {:<12} {}",
+            restore
+                .started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            format_secs(restore.duration_secs()),
+            result,
+            restore.snapshot_id.chars().take(8).collect::<String>(),
+            restore.requested_by.to_string(),
+            detail
+        ));
+    }
+    lines
+}
+
+/// Clap parser for `--ops`.
+fn parse_history_op(text: &str) -> Result<HistoryOp, String> {
+    match text {
+        "backup" => Ok(HistoryOp::Backup),
+        "restore" => Ok(HistoryOp::Restore),
+        _ => Err(format!(
+            "unknown operation '{}' (expected backup or restore)",
+            text
+        )),
+    }
+}
+
+/// Clap parser for `--since`.
+fn parse_since(text: &str) -> Result<DateTime<Utc>, String> {
+    parse_time_bound(text, false, Utc::now())
+}
+
+/// Clap parser for `--until`.
+fn parse_until(text: &str) -> Result<DateTime<Utc>, String> {
+    parse_time_bound(text, true, Utc::now())
+}
+
+/// Parses an RFC 3339 timestamp, a local date (`2026-10-13`) or date and time
+/// (`2026-10-13 14:00`), or an age before `now` (`3d`, `12h`). With
+/// `end_of_day`, a bare date stands for the last instant of that day.
+fn parse_time_bound(
+    text: &str,
+    end_of_day: bool,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let local = |naive: NaiveDateTime| {
+        naive
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{}' does not exist in the local time zone", text))
+    };
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(text, format) {
+            return local(naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let start = if end_of_day {
+            date.succ_opt()
+        } else {
+            Some(date)
+        }
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| format!("'{}' is out of range", text))?;
+        let start = local(start)?;
+        return Ok(if end_of_day {
+            start - Duration::nanoseconds(1)
+        } else {
+            start
+        });
+    }
+    let age = vigil_lib::config::parse_interval(text).map_err(|e| {
+        format!(
+            "expected a date (2026-10-13), a date and time (2026-10-13 14:00) or an age such as 3d: {}",
+            e
+        )
+    })?;
+    Duration::from_std(age)
+        .ok()
+        .and_then(|age| now.checked_sub_signed(age))
+        .ok_or_else(|| format!("'{}' is too far in the past", text))
+}
+
+/// Suffix describing a snapshot filter, e.g. ` tagged 'daily' since 2026-10-13 00:00`.
+fn describe_filter(filter: &SnapshotFilter) -> String {
+    let mut text = String::new();
+    if let Some(tag) = &filter.tag {
+        text.push_str(&format!(" tagged '{}'", tag));
+    }
+    let local = |time: DateTime<Utc>| time.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    if let Some(since) = filter.since {
+        text.push_str(&format!(" since {}", local(since)));
+    }
+    if let Some(until) = filter.until {
+        text.push_str(&format!(" until {}", local(until)));
+    }
+    text
+}
+
+async fn handle_diff(
+    set_name: String,
+    snapshot_a: String,
+    snapshot_b: Option<String>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Diff {
+            set_name,
+            snapshot_a,
+            snapshot_b,
+        },
+    )
+    .await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(
+            ref data @ ResponseData::Diff {
+                ref added,
+                ref removed,
+                ref modified,
+            },
+        )) => {
+            if json {
+                println!("{}", serde_json::to_string(data)?);
+            } else if !quiet {
+                for line in render_diff(added, removed, modified) {
+                    println!("{}", line);
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_ls(
+    set_name: String,
+    snapshot_id: String,
+    path: Option<String>,
+    recursive: bool,
+    long: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::ListFiles {
+            set_name,
+            snapshot_id,
+            path,
+            recursive,
+        },
+    )
+    .await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(
+            ref data @ ResponseData::FileList {
+                ref entries,
+                truncated,
+            },
+        )) => {
+            if json {
+                println!("{}", serde_json::to_string(data)?);
+            } else if !quiet {
+                for line in render_ls(entries, recursive, long) {
+                    println!("{}", line);
+                }
+            }
+            if truncated && !json {
+                eprintln!(
+                    "Warning: only the first {} entries are shown.",
+                    entries.len()
+                );
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lines of `vigil ls` output. Entries are named by file name, or by full
+/// path when `recursive`; directories end in `/`. `long` prefixes each with
+/// its type, size and local modification time.
+fn render_ls(entries: &[FileEntry], recursive: bool, long: bool) -> Vec<String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let mut name = if recursive {
+                entry.path.clone()
+            } else {
+                entry
+                    .path
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(&entry.path)
+                    .to_string()
+            };
+            if entry.node_type == "dir" {
+                name.push('/');
+            }
+            if !long {
+                return name;
+            }
+            let kind = match entry.node_type.as_str() {
+                "dir" => 'd',
+                "file" => '-',
+                "symlink" => 'l',
+                _ => '?',
+            };
+            let size = entry.size.map_or("-".to_string(), format_size);
+            let mtime = entry.mtime.map_or("-".to_string(), |t| {
+                t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+            });
+            format!("{} {:>10}  {:<16}  {}", kind, size, mtime, name)
+        })
+        .collect()
+}
+
+/// Lines of `vigil diff` output: one `+`, `-` or `M` line per path, then a count summary.
+fn render_diff(added: &[String], removed: &[String], modified: &[String]) -> Vec<String> {
+    if added.is_empty() && removed.is_empty() && modified.is_empty() {
+        return vec!["No differences between the snapshots.".to_string()];
+    }
+    let mut lines: Vec<String> = added
+        .iter()
+        .map(|p| format!("+ {}", p))
+        .chain(removed.iter().map(|p| format!("- {}", p)))
+        .chain(modified.iter().map(|p| format!("M {}", p)))
+        .collect();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} added, {} removed, {} modified",
+        added.len(),
+        removed.len(),
+        modified.len()
+    ));
+    lines
+}
+
+async fn handle_info(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Info { set_name }).await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::Info { info })) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else if !quiet {
+                display_info(&info);
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_attest(
+    set_name: String,
+    output: Option<PathBuf>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Attest { set_name }).await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::Attestation { document })) => {
+            let text = serde_json::to_string_pretty(&document)? + "\n";
+            let Some(output) = output else {
+                print!("{}", text);
+                return Ok(());
+            };
+            std::fs::write(&output, text)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "set_name": document.attestation.set_name,
+                        "output": output,
+                        "public_key": document.signature.public_key,
+                    })
+                );
+            } else if !quiet {
+                println!(
+                    "Wrote a signed attestation of set '{}' to {}.",
+                    document.attestation.set_name,
+                    output.display()
+                );
+                println!("Signing key: {}", document.signature.public_key);
+                println!(
+                    "Check it with `vigil attest --verify {}`.",
+                    output.display()
+                );
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
 
-    // Try to get target path from daemon if not found in config
-    if target_path.is_none() {
-        if let Ok(mut stream) = UnixStream::connect(paths::socket_path()).await {
-            let _ = send_request(&mut stream, Request::Status).await;
-            let mut reader = BufReader::new(&mut stream);
-            if let Ok(Response::Ok(Some(ResponseData::Status { sets }))) =
-                receive_response(&mut reader).await
-            {
-                if let Some(set) = sets.iter().find(|s| s.name == set_name) {
-                    target_path = Some(set.target.to_string_lossy().to_string());
+    Ok(())
+}
+
+/// Checks an attestation file's signature and that it was made with
+/// `public_key`, or else this machine's key. Works without the daemon; exits 1
+/// if the signature does not hold or the signer is not the expected one.
+fn handle_attest_verify(
+    file: PathBuf,
+    public_key: Option<String>,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    let document = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let pinned = public_key
+        .as_deref()
+        .map(vigil_lib::attest::read_public_key)
+        .transpose()?;
+    let local_key = vigil_lib::attest::local_public_key(&paths::attestation_key_path());
+    let verified = SignedAttestation::verify(&document).and_then(|signed| {
+        let signer = match (&pinned, &local_key) {
+            (Some(key), _) => {
+                signed.check_signer(key)?;
+                Signer::Pinned
+            }
+            (None, Some(key)) => {
+                signed.check_signer(key).map_err(|e| {
+                    anyhow!(
+                        "{} (this machine's key); pass --public-key to check a key from another machine",
+                        e
+                    )
+                })?;
+                Signer::Local
+            }
+            (None, None) => anyhow::bail!(
+                "This machine has no signing key to check the signer against; pass --public-key"
+            ),
+        };
+        Ok((signed, signer))
+    });
+    match verified {
+        Ok((signed, signer)) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "valid": true,
+                        "signer": signer.as_str(),
+                        "attestation": signed.attestation,
+                        "public_key": signed.signature.public_key,
+                    })
+                );
+            } else if !quiet {
+                for line in attestation_lines(&signed, signer) {
+                    println!("{}", line);
+                }
+            }
+            Ok(())
+        }
+        Err(e) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({"valid": false, "error": e.to_string()})
+                );
+            } else {
+                eprintln!("✗ {} is not a valid attestation: {}", file.display(), e);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Which key `vigil attest --verify` accepted an attestation's signer against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signer {
+    /// The key passed with `--public-key`.
+    Pinned,
+    /// This machine's signing key.
+    Local,
+}
+
+impl Signer {
+    fn as_str(self) -> &'static str {
+        match self {
+            Signer::Pinned => "pinned",
+            Signer::Local => "local",
+        }
+    }
+}
+
+/// Describes a verified attestation and whose key signed it.
+fn attestation_lines(signed: &SignedAttestation, signer: Signer) -> Vec<String> {
+    let a = &signed.attestation;
+    let time = |t: DateTime<Utc>| t.format("%Y-%m-%d %H:%M UTC").to_string();
+    let latest = match &a.latest_snapshot {
+        Some(s) => format!(
+            ", latest {} at {}",
+            s.id.chars().take(8).collect::<String>(),
+            time(s.time)
+        ),
+        None => String::new(),
+    };
+    let check = match &a.last_check {
+        Some(c) if c.success => format!("passed {} ({} read back)", time(c.checked_at), c.subset),
+        Some(c) => format!(
+            "FAILED {}: {}",
+            time(c.checked_at),
+            c.error.as_deref().unwrap_or("unknown error")
+        ),
+        None => "never run".to_string(),
+    };
+    let signer = match signer {
+        Signer::Pinned => "the key given with --public-key",
+        Signer::Local => "this machine's key",
+    };
+    vec![
+        "✓ Signature valid".to_string(),
+        format!("Set:        {} on {}", a.set_name, a.hostname),
+        format!("Created:    {}", time(a.created_at)),
+        format!("Repository: {} (ID {})", a.repository, a.repository_id),
+        format!("Snapshots:  {}{}", a.snapshot_count, latest),
+        format!("Last check: {}", check),
+        format!(
+            "Tools:      vigil {}, {}",
+            a.tools.vigil, a.tools.engine_version
+        ),
+        format!("Signed by:  {}", signed.signature.public_key),
+        format!("            ({})", signer),
+    ]
+}
+
+fn display_info(info: &SetInfo) {
+    let status = &info.status;
+    println!("Set:        {}", status.name);
+    println!(
+        "Sources:    {}",
+        status
+            .source_paths
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!("Repository: {}", status.target.display());
+    println!(
+        "Snapshots:  {}",
+        status
+            .snapshot_count
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    );
+    println!(
+        "Size:       {}",
+        status
+            .total_bytes
+            .map(format_size)
+            .unwrap_or_else(|| "-".to_string())
+    );
+    if let Some(usage) = status.last_backup.as_ref().and_then(format_backup_usage) {
+        println!("Resources:  {} (last backup)", usage);
+    }
+    if let Some(result) = status.last_backup.as_ref().filter(|r| !r.hooks.is_empty()) {
+        println!("Hooks:      {} (last backup)", format_hooks(&result.hooks));
+    }
+    println!(
+        "Shared:     {}",
+        if info.shared_repo { "Yes" } else { "No" }
+    );
+    for line in retention_lines(info) {
+        println!("{}", line);
+    }
+    println!("Pending:    {} changed path(s)", status.pending_changes);
+    for path in &info.pending_paths {
+        println!("  {}", path);
+    }
+    let unlisted = status
+        .pending_changes
+        .saturating_sub(info.pending_paths.len() as u64);
+    if !info.pending_paths.is_empty() && unlisted > 0 {
+        println!("  … and {} more", unlisted);
+    }
+
+    if info.hosts.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{:<25} {:<10} {:<20}", "HOST", "SNAPSHOTS", "LATEST");
+    println!("{}", "-".repeat(55));
+    for host in &info.hosts {
+        let name = match (host.hostname.is_empty(), host.is_local) {
+            (true, _) => "(unknown)".to_string(),
+            (false, true) => format!("{} (this machine)", host.hostname),
+            (false, false) => host.hostname.clone(),
+        };
+        let latest = format_relative(Utc::now().signed_duration_since(host.latest));
+        println!("{:<25} {:<10} {:<20}", name, host.snapshot_count, latest);
+    }
+
+    let foreign = info.hosts.iter().filter(|h| !h.is_local).count();
+    if foreign > 0 && !info.shared_repo {
+        println!();
+        println!(
+            "Warning: {} other host(s) write to this repository. Add `shared_repo = true` to this set so prune only removes this machine's snapshots.",
+            foreign
+        );
+    }
+}
+
+/// `info` lines on the effective retention policy: its rules, the most
+/// snapshots it can keep, and how the stored snapshots compare. A policy
+/// without keep rules is flagged, as every prune of the set would fail.
+fn retention_lines(info: &SetInfo) -> Vec<String> {
+    if info.status.append_only {
+        return vec![
+            "Retention:  not applied (append-only repository; prune on the server)".to_string(),
+        ];
+    }
+    let Some(policy) = &info.retention else {
+        return vec!["Retention:  none (snapshots are never pruned)".to_string()];
+    };
+    if !policy.has_keep_rules() {
+        return vec![
+            "Retention:  no keep rules".to_string(),
+            "Warning: this policy can never prune. Set keep_last, keep_daily, keep_weekly, keep_monthly, keep_yearly or keep_within; until then every prune of this set fails.".to_string(),
+        ];
+    }
+    let group = if policy.per_tag {
+        "host, paths and tags"
+    } else {
+        "host and paths"
+    };
+    // keep_within and keep_tag keep however many snapshots match them
+    let Some(budget) = policy.max_kept() else {
+        return vec![format!(
+            "Retention:  {} (no fixed limit per {})",
+            policy.rules(),
+            group
+        )];
+    };
+    let mut lines = vec![format!(
+        "Retention:  {} (keeps at most {} snapshot(s) per {})",
+        policy.rules(),
+        budget,
+        group
+    )];
+    // Prunes only thin this machine's snapshots of a shared repository
+    let stored = if info.shared_repo {
+        info.hosts
+            .iter()
+            .find(|h| h.is_local)
+            .map_or(0, |h| h.snapshot_count as u64)
+    } else {
+        match info.status.snapshot_count {
+            Some(count) => count as u64,
+            None => return lines,
+        }
+    };
+    // With several groups the budget applies to each of them
+    let groups = if info.shared_repo {
+        1
+    } else {
+        info.hosts.len()
+    };
+    if policy.per_tag {
+        lines.push(format!(
+            "Budget:     {} snapshot(s) stored; the budget applies to each combination of tags",
+            stored
+        ));
+    } else if groups > 1 {
+        lines.push(format!(
+            "Budget:     {} snapshot(s) stored; the budget applies to each of {} hosts",
+            stored, groups
+        ));
+    } else if stored > budget {
+        lines.push(format!(
+            "Budget:     {} snapshot(s) stored, {} more than the policy keeps; the next prune forgets them",
+            stored,
+            stored - budget
+        ));
+    } else {
+        lines.push(format!(
+            "Budget:     {} of {} snapshot(s) used",
+            stored, budget
+        ));
+    }
+    lines
+}
+
+/// Outcome of each hook around a backup, e.g. `pre-backup ✓ 1.2s, post-backup ✗ exit 3`.
+fn format_hooks(hooks: &[HookOutcome]) -> String {
+    hooks
+        .iter()
+        .map(|hook| {
+            let result = match (hook.success, hook.exit_code) {
+                (true, _) => format!("✓ {}", format_secs(hook.duration_secs)),
+                (false, Some(code)) => format!("✗ exit {}", code),
+                (false, None) => "✗ killed".to_string(),
+            };
+            format!("{} {}", hook.stage, result)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Peak memory and CPU time of a backup, when the daemon measured them.
+fn format_backup_usage(result: &BackupResult) -> Option<String> {
+    Some(format!(
+        "{} peak RSS, {} CPU",
+        format_size(result.peak_rss_bytes?),
+        format_secs(result.cpu_secs?)
+    ))
+}
+
+async fn handle_clean(dry_run: bool, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Clean { dry_run }).await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::CleanReport {
+            dry_run,
+            removed,
+            skipped,
+        })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "dry_run": dry_run,
+                        "removed": removed,
+                        "skipped": skipped
+                            .iter()
+                            .map(|(path, reason)| serde_json::json!({ "path": path, "reason": reason }))
+                            .collect::<Vec<_>>()
+                    })
+                );
+            } else if !quiet {
+                if removed.is_empty() && skipped.is_empty() {
+                    println!("No stale mount folders found.");
+                }
+                let verb = if dry_run { "Would remove" } else { "Removed" };
+                for path in &removed {
+                    println!("{} {}", verb, path);
+                }
+                for (path, reason) in &skipped {
+                    println!("Kept {} ({})", path, reason);
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_replicate(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Replicate { set_name }).await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(
+            ref data @ ResponseData::ReplicationComplete {
+                ref set_name,
+                ref replica_target,
+                copied,
+            },
+        )) => {
+            if json {
+                println!("{}", serde_json::to_string(data)?);
+            } else if !quiet {
+                println!(
+                    "✓ {}: Copied {} snapshot(s) to {}",
+                    set_name, copied, replica_target
+                );
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
+                std::process::exit(4);
+            } else {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_unlock(
+    set_name: String,
+    remove_all: bool,
+    yes: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    if remove_all && !yes {
+        if json || quiet {
+            anyhow::bail!("--all requires --yes when running in --json or --quiet mode");
+        }
+        println!(
+            "This removes every lock on the repository of '{}', including those of backups or prunes still running on this or another machine. Removing a live lock can corrupt the repository.",
+            set_name
+        );
+        if !confirm_prompt_default_no("Remove all locks?")? {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(
+        reader.get_mut(),
+        Request::Unlock {
+            set_name,
+            remove_all,
+        },
+    )
+    .await?;
+
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::Unlocked {
+            set_name,
+            removed,
+            remaining,
+        })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "set": set_name,
+                        "removed": removed,
+                        "remaining": remaining,
+                    })
+                );
+            } else if !quiet {
+                println!("✓ {}: Removed {} lock(s)", set_name, removed);
+                if remaining > 0 && !remove_all {
+                    println!(
+                        "  {} lock(s) belong to operations that may still be running. If none is, run `vigil unlock {} --all`.",
+                        remaining, set_name
+                    );
+                }
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
+
+    Ok(())
+}
+
+/// One line for the overall level, then one per issue.
+fn render_health(overall: HealthLevel, issues: &[HealthIssue]) -> Vec<String> {
+    let mut lines = vec![match issues.len() {
+        0 => format!("Health: {}", overall),
+        1 => format!("Health: {} (1 issue)", overall),
+        n => format!("Health: {} ({} issues)", overall, n),
+    }];
+    for issue in issues {
+        let icon = match issue.level {
+            HealthLevel::Healthy => "✓",
+            HealthLevel::Degraded => "⚠",
+            HealthLevel::Failing => "✗",
+        };
+        lines.push(format!("  {} {}: {}", icon, issue.set_name, issue.message));
+    }
+    lines
+}
+
+/// The waybar custom module object for `status`: `class` follows the health
+/// level so the bar can style it, `alt` names the activity for format icons.
+fn waybar_module(status: &CompactStatus) -> serde_json::Value {
+    serde_json::json!({
+        "text": status.text,
+        "tooltip": status.tooltip,
+        "class": status.level.to_string(),
+        "alt": if status.running.is_empty() { "idle" } else { "running" },
+    })
+}
+
+async fn handle_status_line(json: bool) -> anyhow::Result<()> {
+    // Status bars poll this; a stopped daemon is a state to show, not an error
+    let Ok(mut stream) = transport::connect().await else {
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "text": "daemon not running",
+                    "tooltip": "The vigil daemon is not running",
+                    "class": "stopped",
+                    "alt": "stopped",
+                })
+            );
+        } else {
+            println!("daemon not running");
+        }
+        return Ok(());
+    };
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::StatusLine).await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::StatusLine { status })) => {
+            if json {
+                println!("{}", waybar_module(&status));
+            } else {
+                println!("{}", status.text);
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => anyhow::bail!("Unexpected response from daemon."),
+    }
+    Ok(())
+}
+
+async fn handle_health(json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::Health).await?;
+
+    match receive_reply(&mut reader).await? {
+        Response::Ok(Some(ResponseData::Health { overall, issues })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "overall": overall, "issues": issues })
+                );
+            } else if !quiet {
+                for line in render_health(overall, &issues) {
+                    println!("{}", line);
                 }
             }
+            // Nagios plugin convention: 0 OK, 1 WARNING, 2 CRITICAL
+            match overall {
+                HealthLevel::Healthy => {}
+                HealthLevel::Degraded => std::process::exit(1),
+                HealthLevel::Failing => std::process::exit(2),
+            }
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error from daemon ({}): {}", code, message);
+            std::process::exit(1);
         }
+        _ => anyhow::bail!("Unexpected response from daemon."),
     }
+    Ok(())
+}
 
-    let target_path = target_path.ok_or_else(|| {
-        anyhow!(
-            "Could not determine target path for backup set '{}'. Is it in config.toml?",
-            set_name
-        )
-    })?;
-
-    if !force {
-        if json || quiet {
-            anyhow::bail!("Purge requires --force when running in --json or --quiet mode");
-        }
+fn handle_config_validate(file: Option<PathBuf>, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let path = file.unwrap_or_else(paths::active_config_path);
+    let result = std::fs::read_to_string(&path)
+        .map_err(vigil_lib::config::ConfigError::from)
+        .and_then(|content| vigil_lib::config::parse_config_text(&content));
+    let warnings = result
+        .as_ref()
+        .map(|config| config.warnings())
+        .unwrap_or_default();
+    if json {
         println!(
-            "WARNING: This will permanently delete ALL backup data for '{}' at '{}' and can NOT be undone!",
-            set_name, target_path
+            "{}",
+            serde_json::json!({
+                "path": path,
+                "valid": result.is_ok(),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+                "sets": result.as_ref().map(|c| c.backup_sets.len()).ok(),
+                "warnings": warnings,
+            })
         );
-        println!("Source files will NOT be affected.");
-        print!("Are you sure you want to proceed? [y/N]: ");
-        use std::io::Write;
-        std::io::stdout().flush()?;
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-        if input.trim().to_lowercase() != "y" {
-            println!("Purge cancelled.");
-            return Ok(());
+    } else {
+        match &result {
+            Ok(config) if !quiet => {
+                println!(
+                    "{} is valid ({} backup set{}).",
+                    path.display(),
+                    config.backup_sets.len(),
+                    if config.backup_sets.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    }
+                );
+                for warning in &warnings {
+                    println!("  ⚠ {}", warning);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("{} is invalid: {}", path.display(), e),
         }
     }
-
-    if !quiet && !json {
-        println!("Unmounting set '{}' if active...", set_name);
+    if result.is_err() {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    // 1. Unmount if mounted
-    if let Ok(mut stream) = UnixStream::connect(paths::socket_path()).await {
-        let mut reader = BufReader::new(&mut stream);
-        let _ = send_request(
-            reader.get_mut(),
-            Request::Unmount {
-                set_name: Some(set_name.clone()),
-            },
-        )
-        .await;
-        let _ = receive_response(&mut reader).await; // Ignore response details
+/// A setting's value as it would appear in the file; strings unquoted.
+fn render_setting(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => toml::Value::try_from(other)
+            .map(|v| v.to_string())
+            .unwrap_or_else(|_| other.to_string()),
+    }
+}
 
-        // 2. Reload daemon config to stop tracking it (in case it's still there)
-        if !quiet && !json {
-            println!("Refreshing daemon configuration...");
+fn handle_config_get(key: String, json: bool) -> anyhow::Result<()> {
+    let key = vigil_lib::config::SettingKey::parse(&key).map_err(|e| anyhow!(e))?;
+    let config = vigil_lib::config::load_config_raw().context("Failed to load configuration")?;
+    match config.get_setting(&key)? {
+        Some(value) if json => println!("{}", value),
+        Some(value) => println!("{}", render_setting(&value)),
+        None => {
+            eprintln!("'{}' is not set", key);
+            std::process::exit(1);
         }
-        let _ = send_request(reader.get_mut(), Request::ReloadConfig).await;
-        let _ = receive_response(&mut reader).await;
     }
+    Ok(())
+}
 
-    // 3. Delete repository
-    if !quiet && !json {
-        println!("Deleting Restic repository at '{}'...", target_path);
+async fn handle_config_set(
+    key: String,
+    value: String,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    // Missing files start empty, as with `track`
+    let config = match vigil_lib::config::load_config_raw() {
+        Ok(config) => config,
+        Err(vigil_lib::config::ConfigError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            vigil_lib::config::Config {
+                global: Default::default(),
+                backup_sets: Vec::new(),
+            }
+        }
+        Err(e) => return Err(e).context("Failed to load configuration"),
+    };
+    let parsed = config.setting_value(&key, &value)?;
+    let daemon_running = edit_config(vec![vigil_lib::config::ConfigOp::Set {
+        key: key.clone(),
+        value: parsed.clone(),
+    }])
+    .await?;
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "key": key, "value": parsed, "daemon_applied": daemon_running })
+        );
+    } else if !quiet {
+        println!("{} = {}", key, render_setting(&parsed));
+        if daemon_running {
+            println!("The daemon has applied the change.");
+        }
     }
-    let path = std::path::Path::new(&target_path);
-    if path.exists() {
-        if path.is_dir() {
-            std::fs::remove_dir_all(path).context("Failed to remove repository directory")?;
-        } else {
+    Ok(())
+}
+
+/// The user's editor: `$VISUAL`, then `$EDITOR`, then `vi`.
+fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+async fn handle_config_edit() -> anyhow::Result<()> {
+    let path = paths::active_config_path();
+    let original = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => "[global]\n".to_string(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    // A sibling file, so the editor sees a .toml name and the final write stays on one filesystem
+    let draft = path.with_extension("edit.toml");
+    std::fs::write(&draft, &original).with_context(|| format!("Failed to write {:?}", draft))?;
+
+    let editor = editor_command();
+    let edited = loop {
+        // Through the shell, so an editor setting such as `code --wait` works
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&draft)
+            .status()
+            .with_context(|| format!("Failed to start editor '{}'", editor))?;
+        if !status.success() {
+            let _ = std::fs::remove_file(&draft);
             anyhow::bail!(
-                "Target path '{}' exists but is not a directory. Refusing to delete.",
-                target_path
+                "Editor '{}' exited with {}; changes discarded",
+                editor,
+                status
             );
         }
-    } else if !quiet && !json {
-        println!("Repository directory does not exist, skipping.");
+        let content = std::fs::read_to_string(&draft)?;
+        if content == original {
+            let _ = std::fs::remove_file(&draft);
+            println!("No changes.");
+            return Ok(());
+        }
+        match vigil_lib::config::parse_config_text(&content) {
+            Ok(_) => break content,
+            Err(e) => {
+                eprintln!("The configuration is invalid: {}", e);
+                if !confirm_prompt("Edit again?")? {
+                    let _ = std::fs::remove_file(&draft);
+                    println!("Changes discarded.");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    // Do not overwrite edits made meanwhile, e.g. by `track` or the daemon
+    let current = std::fs::read_to_string(&path).unwrap_or_else(|_| "[global]\n".to_string());
+    if current != original {
+        anyhow::bail!(
+            "{:?} changed while it was being edited; your version is kept in {:?}",
+            path,
+            draft
+        );
+    }
+    let config = vigil_lib::config::replace_config_text(&edited)?;
+    let _ = std::fs::remove_file(&draft);
+    println!("Saved {}.", path.display());
+    for warning in config.warnings() {
+        println!("  ⚠ {}", warning);
     }
 
-    // 4. Delete mount point
-    let mount_path = paths::mount_path(&set_name);
-    if mount_path.exists() {
-        if !quiet && !json {
-            println!("Deleting mount point at {:?}...", mount_path);
-        }
-        // We try a few times because unmount might take a moment to propagate in the kernel
-        let mut success = false;
-        for _ in 0..5 {
-            if std::fs::remove_dir_all(&mount_path).is_ok() {
-                success = true;
-                break;
+    if transport::connect().await.is_ok() && confirm_prompt("Reload the daemon now?")? {
+        handle_reload(false, false).await?;
+    }
+    Ok(())
+}
+
+async fn handle_config_effective(set_name: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    // Prefer the daemon's view, which reflects the configuration it actually loaded
+    let effective = match transport::connect().await {
+        Ok(mut stream) => {
+            send_request(
+                &mut stream,
+                Request::EffectiveConfig {
+                    set_name: set_name.clone(),
+                },
+            )
+            .await?;
+            let mut reader = BufReader::new(&mut stream);
+            match receive_reply(&mut reader).await? {
+                Response::Ok(Some(ResponseData::EffectiveConfig { config })) => Some(*config),
+                Response::Error { code, .. }
+                    if code == vigil_lib::ipc::error_codes::INVALID_REQUEST =>
+                {
+                    None
+                }
+                Response::Error { code, message } => {
+                    eprintln!("Error from daemon ({}): {}", code, message);
+                    std::process::exit(1);
+                }
+                _ => anyhow::bail!("Unexpected response from service daemon"),
             }
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
         }
-        if !success && !quiet && !json {
-            println!(
-                "Warning: Could not remove mount point directory {:?}. It might still be busy.",
-                mount_path
-            );
+        Err(_) => None,
+    };
+
+    let effective = match effective {
+        Some(effective) => effective,
+        None => {
+            let config = match vigil_lib::config::load_config() {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("Error: Failed to load configuration: {}", e);
+                    std::process::exit(2);
+                }
+            };
+            let Some(set) = config.backup_sets.iter().find(|s| s.name == set_name) else {
+                eprintln!(
+                    "Error: Backup set '{}' not found in configuration.",
+                    set_name
+                );
+                std::process::exit(2);
+            };
+            if !json && !quiet {
+                println!("(service daemon not running; resolved from the configuration file)");
+                println!();
+            }
+            set.resolve(&config.global)
         }
-    }
+    };
 
     if json {
-        println!(
-            "{}",
-            serde_json::json!({ "status": "purged", "set": set_name, "target": target_path })
-        );
+        println!("{}", serde_json::to_string_pretty(&effective)?);
     } else if !quiet {
-        println!("Successfully purged backup set '{}'.", set_name);
+        display_effective_config(&effective)?;
     }
+    Ok(())
+}
 
+fn display_effective_config(effective: &vigil_lib::config::EffectiveConfig) -> anyhow::Result<()> {
+    print!("{}", toml::to_string_pretty(&effective.set)?);
+    println!();
+    println!("{:<22} SOURCE", "SETTING");
+    for (setting, source) in &effective.sources {
+        println!("{:<22} {}", setting, source);
+    }
     Ok(())
 }
 
-async fn handle_snapshots(
-    set_name: String,
-    limit: usize,
-    json: bool,
-    quiet: bool,
-) -> anyhow::Result<()> {
+async fn handle_shutdown(force: bool, json: bool, quiet: bool) -> anyhow::Result<()> {
     let mut stream = connect_to_daemon().await?;
     let mut reader = BufReader::new(&mut stream);
-    send_request(
-        reader.get_mut(),
-        Request::Snapshots {
-            set_name: set_name.clone(),
-            limit: Some(limit),
-        },
-    )
-    .await?;
+    send_request(reader.get_mut(), Request::Shutdown { force }).await?;
 
-    let response = receive_response(&mut reader).await?;
+    let response = receive_reply(&mut reader).await?;
     match response {
-        Response::Ok(Some(ResponseData::Snapshots { snapshots })) => {
+        Response::Ok(_) => {
             if json {
-                println!("{}", serde_json::to_string_pretty(&snapshots)?);
+                println!("{}", serde_json::json!({ "status": "success" }));
             } else if !quiet {
-                if snapshots.is_empty() {
-                    println!("No snapshots found for set '{}'.", set_name);
-                    return Ok(());
-                }
+                println!("Service daemon is shutting down.");
+            }
+        }
+        Response::JobsRunning { sets } => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "status": "refused", "running": sets })
+                );
+            } else {
+                eprintln!(
+                    "Error: jobs are still running for: {}. The daemon was not stopped.",
+                    sets.join(", ")
+                );
+                eprintln!("  Hint: Wait for them to finish, or run `vigil shutdown --force` to stop anyway.");
+            }
+            std::process::exit(1);
+        }
+        Response::Error { code, message } => {
+            eprintln!("Error stopping daemon ({}): {}", code, message);
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Unexpected response from daemon.");
+        }
+    }
 
-                println!("{:<10} {:<20} {:<10} {:<30}", "ID", "DATE", "SIZE", "PATHS");
-                println!("{}", "-".repeat(70));
+    Ok(())
+}
 
-                for s in snapshots {
-                    let date = s.timestamp.format("%Y-%m-%d %H:%M").to_string();
-                    let size = s
-                        .total_bytes
-                        .map(format_size)
-                        .unwrap_or_else(|| "N/A".to_string());
-                    let paths = s
-                        .paths
-                        .iter()
-                        .map(|p| p.to_string_lossy())
-                        .collect::<Vec<_>>()
-                        .join(", ");
+async fn handle_log_level(filter: String, json: bool, quiet: bool) -> anyhow::Result<()> {
+    let mut stream = connect_to_daemon().await?;
+    let mut reader = BufReader::new(&mut stream);
+    send_request(reader.get_mut(), Request::SetLogLevel { filter }).await?;
 
-                    println!("{:<10} {:<20} {:<10} {:<30}", s.short_id, date, size, paths);
-                }
+    let response = receive_reply(&mut reader).await?;
+    match response {
+        Response::Ok(Some(ResponseData::LogLevel { filter, previous })) => {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "filter": filter, "previous": previous })
+                );
+            } else if !quiet {
+                println!("Daemon log level set to '{}' (was '{}').", filter, previous);
+                println!("  Restore it with `vigil log-level {}`.", previous);
             }
         }
         Response::Error { code, message } => {
-            eprintln!("Error from daemon ({}): {}", code, message);
-            if code == vigil_lib::ipc::error_codes::RESTIC_ERROR {
-                std::process::exit(4);
-            } else {
-                std::process::exit(1);
-            }
+            eprintln!("Error changing log level ({}): {}", code, message);
+            std::process::exit(1);
         }
         _ => {
             println!("Unexpected response from daemon.");
@@ -1446,7 +5576,7 @@ async fn handle_reload(json: bool, quiet: bool) -> anyhow::Result<()> {
     let mut reader = BufReader::new(&mut stream);
     send_request(reader.get_mut(), Request::ReloadConfig).await?;
 
-    let response = receive_response(&mut reader).await?;
+    let response = receive_reply(&mut reader).await?;
     match response {
         Response::Ok(_) => {
             if json {
@@ -1504,6 +5634,8 @@ async fn handle_list(json: bool, quiet: bool) -> anyhow::Result<()> {
                         first.clone()
                     }
                 }
+            } else if let Some(ref list) = set.files_from {
+                format!("files from {}", list)
             } else {
                 "None".to_string()
             };
@@ -1558,12 +5690,7 @@ async fn handle_setup(json: bool, quiet: bool) -> anyhow::Result<()> {
         }
 
         use std::io::Write;
-        use std::os::unix::fs::OpenOptionsExt;
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .mode(0o600)
-            .open(&password_path)?;
+        let mut file = vigil_lib::platform::create_new_with_mode(&password_path, 0o600)?;
         file.write_all(password.as_bytes())?;
         if !quiet {
             println!("✔ Password saved to {:?}", password_path);
@@ -1587,7 +5714,9 @@ async fn handle_setup(json: bool, quiet: bool) -> anyhow::Result<()> {
                     println!(
                         "  - {} ({} ⮕ {})",
                         set.name,
-                        set.source.unwrap_or_else(|| "multi".to_string()),
+                        set.source
+                            .or(set.files_from.map(|list| format!("files from {}", list)))
+                            .unwrap_or_else(|| "multi".to_string()),
                         set.target
                     );
                 }
@@ -1630,6 +5759,7 @@ async fn handle_setup(json: bool, quiet: bool) -> anyhow::Result<()> {
                 exclude: None,
                 debounce_seconds: None,
                 retention: None,
+                ..Default::default()
             }],
         };
 
@@ -1668,7 +5798,7 @@ async fn handle_setup(json: bool, quiet: bool) -> anyhow::Result<()> {
         println!("🤖 Step 3: Service Installation");
         let install_now = confirm_prompt("Would you like to install the background service now?")?;
         if install_now {
-            handle_bootstrap(json, quiet).await?;
+            handle_bootstrap(false, None, json, quiet).await?;
         }
     }
 
@@ -1704,9 +5834,14 @@ fn confirm_prompt(msg: &str) -> anyhow::Result<bool> {
     Ok(response.to_lowercase() != "n")
 }
 
-async fn connect_to_daemon() -> anyhow::Result<UnixStream> {
-    let socket_path = paths::socket_path();
-    UnixStream::connect(&socket_path).await.map_err(|e| {
+/// Like `confirm_prompt`, but only an explicit "y" confirms.
+fn confirm_prompt_default_no(msg: &str) -> anyhow::Result<bool> {
+    let response = prompt_user(&format!("{} [y/N]: ", msg))?;
+    Ok(response.eq_ignore_ascii_case("y"))
+}
+
+async fn connect_to_daemon() -> anyhow::Result<IpcStream> {
+    transport::connect().await.map_err(|e| {
         if e.kind() == std::io::ErrorKind::NotFound
             || e.kind() == std::io::ErrorKind::ConnectionRefused
         {
@@ -1718,108 +5853,460 @@ async fn connect_to_daemon() -> anyhow::Result<UnixStream> {
     })
 }
 
-async fn send_request(stream: &mut UnixStream, request: Request) -> anyhow::Result<()> {
-    let json = serde_json::to_string(&request)?;
+/// Sends `request` and returns the id the daemon's reply will carry.
+async fn send_request(stream: &mut IpcStream, request: Request) -> anyhow::Result<u64> {
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let json = serde_json::to_string(&Envelope::new(Some(id), request))?;
     stream.write_all(json.as_bytes()).await?;
     stream.write_all(b"\n").await?;
-    Ok(())
+    Ok(id)
+}
+
+/// Receives the next line from the daemon with the id of the request it
+/// answers; broadcast events carry none.
+async fn receive_message<R: AsyncBufReadExt + Unpin>(
+    reader: &mut R,
+) -> anyhow::Result<Envelope<Response>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    if line.is_empty() {
+        return Err(anyhow!("Connection closed by service daemon"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+async fn receive_response<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Response> {
+    Ok(receive_message(reader).await?.message)
+}
+
+/// Receives the reply to a request, skipping events the daemon broadcasts to
+/// every connection in the meantime.
+async fn receive_reply<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Response> {
+    loop {
+        let response = receive_response(reader).await?;
+        if !response.is_broadcast() {
+            return Ok(response);
+        }
+    }
+}
+
+/// Warnings for sets whose repository is due for a `check --deep`.
+fn verification_warnings(sets: &[SetStatus]) -> Vec<String> {
+    sets.iter()
+        .filter(|s| s.verification_stale)
+        .map(|s| {
+            let last = match s.last_verified {
+                Some(at) => format!("last verified {}", format_relative(Utc::now() - at)),
+                None => "never verified".to_string(),
+            };
+            format!(
+                "⚠ {}: repository {}. Run `vigil check --deep {}`.",
+                s.name, last, s.name
+            )
+        })
+        .collect()
+}
+
+/// Notes for sets whose last debounced backup was skipped for too few changes.
+fn skipped_notes(sets: &[SetStatus]) -> Vec<String> {
+    sets.iter()
+        .filter_map(|s| {
+            let at = s.skipped_trivial?;
+            Some(format!(
+                "ℹ {}: backup skipped {}; {} pending change(s) are below the min-change threshold and carry over to the next backup.",
+                s.name,
+                format_relative(Utc::now() - at),
+                s.pending_changes
+            ))
+        })
+        .collect()
+}
+
+/// `status --verbose` lines on each set's free target space and growth, with
+/// an estimate of when the target fills up at the current rate, on how far
+/// its replica lags behind, and on watcher events coalesced or dropped under
+/// heavy churn.
+fn capacity_lines(sets: &[SetStatus]) -> Vec<String> {
+    sets.iter()
+        .filter_map(|s| {
+            let mut parts = Vec::new();
+            if let Some(free) = s.target_free_bytes {
+                parts.push(format!("{} free on target", format_size(free)));
+            }
+            match s.growth_bytes_per_day {
+                Some(0) => parts.push("no growth this week".to_string()),
+                Some(growth) => {
+                    parts.push(format!("growing {}/day", format_size(growth)));
+                    if let Some(free) = s.target_free_bytes {
+                        parts.push(format!("full in ~{} days", free / growth));
+                    }
+                }
+                None => {}
+            }
+            match &s.replica_lag {
+                Some(lag) if lag.error.is_some() => parts.push(format!(
+                    "replica not compared: {}",
+                    lag.error.as_deref().unwrap_or_default()
+                )),
+                Some(lag) if lag.missing_snapshots > 0 => parts.push(format!(
+                    "replica lacks {} snapshot(s), {} behind{}",
+                    lag.missing_snapshots,
+                    format_compact(Duration::seconds(lag.lag_secs as i64)),
+                    if lag.behind {
+                        " (over max_copy_lag_hours)"
+                    } else {
+                        ""
+                    }
+                )),
+                Some(_) => parts.push("replica up to date".to_string()),
+                None => {}
+            }
+            if s.watch_events_coalesced > 0 || s.watch_events_dropped > 0 {
+                parts.push(format!(
+                    "{} watcher event(s) coalesced, {} dropped",
+                    s.watch_events_coalesced, s.watch_events_dropped
+                ));
+            }
+            (!parts.is_empty()).then(|| format!("{}: {}", s.name, parts.join(", ")))
+        })
+        .collect()
+}
+
+/// `status --verbose` lines listing the paths each set's watcher saw change
+/// most recently, newest first.
+fn recent_change_lines(sets: &[SetStatus]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for s in sets.iter().filter(|s| !s.recent_changes.is_empty()) {
+        lines.push(format!("{}: recently changed", s.name));
+        lines.extend(
+            s.recent_changes
+                .iter()
+                .rev()
+                .map(|p| format!("  {}", p.display())),
+        );
+    }
+    lines
+}
+
+/// Columns available in the `status` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum StatusColumn {
+    Name,
+    State,
+    Snapshots,
+    Size,
+    Last,
+    Next,
+    Mounted,
+}
+
+impl StatusColumn {
+    /// All columns, in default display order.
+    const ALL: [StatusColumn; 7] = [
+        StatusColumn::Name,
+        StatusColumn::State,
+        StatusColumn::Snapshots,
+        StatusColumn::Size,
+        StatusColumn::Last,
+        StatusColumn::Next,
+        StatusColumn::Mounted,
+    ];
+
+    fn header(self, compact: bool) -> &'static str {
+        match (self, compact) {
+            (StatusColumn::Name, _) => "NAME",
+            (StatusColumn::State, _) => "STATE",
+            (StatusColumn::Snapshots, false) => "SNAPSHOTS",
+            (StatusColumn::Snapshots, true) => "SNAPS",
+            (StatusColumn::Size, _) => "SIZE",
+            (StatusColumn::Last, false) => "LAST BACKUP",
+            (StatusColumn::Last, true) => "LAST",
+            (StatusColumn::Next, false) => "NEXT RUN",
+            (StatusColumn::Next, true) => "NEXT",
+            (StatusColumn::Mounted, false) => "MOUNTED",
+            (StatusColumn::Mounted, true) => "MNT",
+        }
+    }
+
+    /// Column width used when the table has room to spare.
+    fn default_width(self) -> usize {
+        match self {
+            StatusColumn::Name | StatusColumn::State => 15,
+            StatusColumn::Snapshots
+            | StatusColumn::Size
+            | StatusColumn::Next
+            | StatusColumn::Mounted => 10,
+            StatusColumn::Last => 20,
+        }
+    }
+
+    /// Whether cells may be cut short with an ellipsis to fit the width.
+    /// Numeric columns are never truncated, since a partial number is misleading.
+    fn truncatable(self) -> bool {
+        matches!(
+            self,
+            StatusColumn::Name | StatusColumn::State | StatusColumn::Last
+        )
+    }
+
+    fn cell(self, set: &SetStatus, compact: bool) -> String {
+        match self {
+            StatusColumn::Name => set.name.clone(),
+            StatusColumn::State => match (&set.state, compact) {
+                (JobState::Idle, _) => "Idle".to_string(),
+                (JobState::Debouncing { remaining_secs }, false) => {
+                    format!("Debounce({}s)", remaining_secs)
+                }
+                (JobState::Debouncing { remaining_secs }, true) => {
+                    format!("Deb {}s", remaining_secs)
+                }
+                (
+                    JobState::Running {
+                        percent,
+                        eta_secs,
+                        scanning,
+                    },
+                    compact,
+                ) => format_running(*percent, *eta_secs, *scanning, compact),
+                (
+                    JobState::Retrying {
+                        attempt,
+                        next_retry_secs,
+                    },
+                    false,
+                ) => format!("Retry {} ({}s)", attempt, next_retry_secs),
+                (
+                    JobState::Retrying {
+                        next_retry_secs, ..
+                    },
+                    true,
+                ) => format!("Rty {}s", next_retry_secs),
+                (JobState::Scheduled { at }, false) => format!(
+                    "Deferred ({})",
+                    at.with_timezone(&chrono::Local).format("%H:%M")
+                ),
+                (JobState::Scheduled { at }, true) => {
+                    format!("Def {}", at.with_timezone(&chrono::Local).format("%H:%M"))
+                }
+                (JobState::Paused { until: Some(until) }, false) => format!(
+                    "Paused (post-restore, until {})",
+                    until.with_timezone(&chrono::Local).format("%H:%M")
+                ),
+                (JobState::Paused { until: None }, false) => "Paused (post-restore)".to_string(),
+                (JobState::Paused { .. }, true) => "Paused".to_string(),
+                (JobState::Queued, _) => "Queued".to_string(),
+                (JobState::Error, false) => "Error".to_string(),
+                (JobState::Error, true) => "Err".to_string(),
+            },
+            StatusColumn::Snapshots | StatusColumn::Size | StatusColumn::Last
+                if set.initializing =>
+            {
+                if compact { "init" } else { "initializing" }.to_string()
+            }
+            StatusColumn::Snapshots => set
+                .snapshot_count
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            StatusColumn::Size => match set.total_bytes {
+                Some(bytes) if compact => Locale::current().format_size_compact(bytes),
+                Some(bytes) => format_size(bytes),
+                None => "-".to_string(),
+            },
+            StatusColumn::Last => match set.last_backup {
+                Some(ref result) => {
+                    let duration = Utc::now().signed_duration_since(result.timestamp);
+                    let time_str = if compact {
+                        format_compact(duration)
+                    } else {
+                        format_relative(duration)
+                    };
+                    match (result.success, compact) {
+                        (true, _) => time_str,
+                        (false, false) => format!("{} (fail)", time_str),
+                        (false, true) => format!("{} !", time_str),
+                    }
+                }
+                None => "Never".to_string(),
+            },
+            StatusColumn::Next => match set.next_run {
+                Some(next) => {
+                    let duration = next.signed_duration_since(Utc::now());
+                    match (duration.num_seconds() <= 0, compact) {
+                        (true, _) => "due".to_string(),
+                        (false, false) => format!("in {}", format_compact(duration)),
+                        (false, true) => format_compact(duration),
+                    }
+                }
+                None => "-".to_string(),
+            },
+            StatusColumn::Mounted if set.is_mounted && set.mount_warming => {
+                if compact { "W" } else { "Warming…" }.to_string()
+            }
+            StatusColumn::Mounted if set.mounts.len() > 1 => {
+                if compact {
+                    set.mounts.len().to_string()
+                } else {
+                    format!("Yes ({})", set.mounts.len())
+                }
+            }
+            StatusColumn::Mounted => match (set.is_mounted, compact) {
+                (true, false) => "Yes".to_string(),
+                (false, false) => "No".to_string(),
+                (true, true) => "Y".to_string(),
+                (false, true) => "N".to_string(),
+            },
+        }
+    }
 }
 
-async fn receive_response<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Response> {
-    let mut line = String::new();
-    reader.read_line(&mut line).await?;
-    if line.is_empty() {
-        return Err(anyhow!("Connection closed by service daemon"));
+/// Width of the attached terminal, if stdout is one.
+fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
     }
-    let response: Response = serde_json::from_str(&line)?;
-    Ok(response)
+    crossterm::terminal::size().ok().map(|(w, _)| w as usize)
 }
 
-fn display_status(sets: Vec<SetStatus>) {
+fn display_status(sets: Vec<SetStatus>, columns: &[StatusColumn], max_width: Option<usize>) {
     if sets.is_empty() {
         println!("No backup sets configured.");
         return;
     }
 
-    println!(
-        "{:<15} {:<15} {:<10} {:<10} {:<20} {:<10}",
-        "NAME", "STATE", "SNAPSHOTS", "SIZE", "LAST BACKUP", "MOUNTED"
-    );
-    println!("{}", "-".repeat(95));
+    for line in render_status_table(&sets, columns, max_width) {
+        println!("{}", line);
+    }
+}
 
-    for set in sets {
-        let state_str = match set.state {
-            JobState::Idle => "Idle".to_string(),
-            JobState::Debouncing { remaining_secs } => {
-                format!("Debounce({}s)", remaining_secs)
-            }
-            JobState::Running => "Running".to_string(),
-            JobState::Error => "Error".to_string(),
-        };
+/// Lays out the status table for the selected columns.
+///
+/// Columns get their default widths when they fit. If the table is wider than
+/// `max_width`, it switches to abbreviated headers and values, and then shrinks
+/// the widest truncatable column (name, state, last backup) one character at a
+/// time until the table fits or every such column is at its header width.
+fn render_status_table(
+    sets: &[SetStatus],
+    columns: &[StatusColumn],
+    max_width: Option<usize>,
+) -> Vec<String> {
+    let mut selected: Vec<StatusColumn> = Vec::new();
+    for column in columns {
+        if !selected.contains(column) {
+            selected.push(*column);
+        }
+    }
 
-        let last_backup_str = match set.last_backup {
-            Some(ref result) => {
-                let now = Utc::now();
-                let duration = now.signed_duration_since(result.timestamp);
-                let time_str = format_human_duration(duration);
-                if result.success {
-                    time_str
-                } else {
-                    format!("{} (fail)", time_str)
-                }
+    let build = |compact: bool| -> (Vec<&'static str>, Vec<Vec<String>>) {
+        let headers = selected.iter().map(|c| c.header(compact)).collect();
+        let rows = sets
+            .iter()
+            .map(|set| selected.iter().map(|c| c.cell(set, compact)).collect())
+            .collect();
+        (headers, rows)
+    };
+    let natural_widths = |headers: &[&str], rows: &[Vec<String>]| -> Vec<usize> {
+        (0..headers.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(headers[i].chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    };
+    let total = |widths: &[usize]| widths.iter().sum::<usize>() + widths.len().saturating_sub(1);
+
+    let (mut headers, mut rows) = build(false);
+    let mut widths: Vec<usize> = natural_widths(&headers, &rows)
+        .into_iter()
+        .zip(&selected)
+        .map(|(w, c)| w.max(c.default_width()))
+        .collect();
+
+    if let Some(max) = max_width {
+        if total(&widths) > max {
+            // Drop the padding first, then fall back to abbreviations.
+            widths = natural_widths(&headers, &rows);
+            if total(&widths) > max {
+                (headers, rows) = build(true);
+                widths = natural_widths(&headers, &rows);
             }
-            None => "Never".to_string(),
-        };
-
-        let mounted_str = if set.is_mounted { "Yes" } else { "No" };
+        }
+        while total(&widths) > max {
+            let widest = (0..selected.len())
+                .filter(|&i| selected[i].truncatable() && widths[i] > headers[i].chars().count())
+                .max_by_key(|&i| widths[i]);
+            match widest {
+                Some(i) => widths[i] -= 1,
+                None => break,
+            }
+        }
+    }
 
-        let snapshots_str = set
-            .snapshot_count
-            .map(|c| c.to_string())
-            .unwrap_or_else(|| "-".to_string());
+    let format_row = |cells: Vec<String>| -> String {
+        cells
+            .into_iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", truncate_cell(&cell, width)))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end()
+            .to_string()
+    };
 
-        let size_str = set
-            .total_bytes
-            .map(format_size)
-            .unwrap_or_else(|| "-".to_string());
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    lines.push(format_row(headers.iter().map(|h| h.to_string()).collect()));
+    lines.push("-".repeat(total(&widths)));
+    lines.extend(rows.into_iter().map(format_row));
+    lines
+}
 
-        println!(
-            "{:<15} {:<15} {:<10} {:<10} {:<20} {:<10}",
-            set.name, state_str, snapshots_str, size_str, last_backup_str, mounted_str
-        );
+/// Cuts `text` to at most `width` characters, marking the cut with an ellipsis.
+fn truncate_cell(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
     }
+    if width == 0 {
+        return String::new();
+    }
+    let mut cut: String = text.chars().take(width - 1).collect();
+    cut.push('…');
+    cut
 }
 
-/// Formats a chrono Duration into a human-readable relative time string.
-/// Handles negative durations gracefully by showing "just now".
-fn format_human_duration(duration: Duration) -> String {
-    let secs = duration.num_seconds();
-    if secs < 0 {
-        return "just now".to_string();
-    }
-    if secs < 60 {
-        format!("{}s ago", secs)
-    } else if secs < 3600 {
-        let mins = secs / 60;
-        if mins == 1 {
-            "1 min ago".to_string()
+/// Formats the state cell of a running backup, e.g. `Running 42% ~3m` or `Run 42%`.
+fn format_running(
+    percent: Option<f64>,
+    eta_secs: Option<u64>,
+    scanning: Option<Scanning>,
+    compact: bool,
+) -> String {
+    if let Some(scanning) = scanning {
+        return if compact {
+            format!(
+                "Scan {}",
+                Locale::current().format_size_compact(scanning.bytes)
+            )
         } else {
-            format!("{} mins ago", mins)
-        }
-    } else if secs < 86400 {
-        let hours = secs / 3600;
-        if hours == 1 {
-            "1 hour ago".to_string()
-        } else {
-            format!("{} hours ago", hours)
-        }
-    } else {
-        let days = secs / 86400;
-        if days == 1 {
-            "1 day ago".to_string()
-        } else {
-            format!("{} days ago", days)
-        }
+            format!(
+                "Scanning {} files, {}",
+                scanning.files,
+                format_size(scanning.bytes)
+            )
+        };
+    }
+    let label = if compact { "Run" } else { "Running" };
+    match (percent, eta_secs) {
+        (Some(percent), Some(eta)) if !compact => format!(
+            "{} {:.0}% ~{}",
+            label,
+            percent.floor(),
+            format_compact(Duration::seconds(eta as i64))
+        ),
+        (Some(percent), _) => format!("{} {:.0}%", label, percent.floor()),
+        (None, _) => label.to_string(),
     }
 }
 
@@ -1827,10 +6314,12 @@ async fn handle_track(
     name: String,
     source: String,
     target: String,
+    exclude: Vec<String>,
+    retention: Option<vigil_lib::config::RetentionPolicy>,
     json: bool,
     quiet: bool,
 ) -> anyhow::Result<()> {
-    use vigil_lib::config::{load_config_raw, save_config, BackupSet};
+    use vigil_lib::config::{BackupSet, ConfigOp};
 
     // Validate name: alphanumeric, hyphens, and underscores only
     if !name
@@ -1847,36 +6336,17 @@ async fn handle_track(
         println!("Tracking new backup set '{}'...", name);
     }
 
-    let mut config = match load_config_raw() {
-        Ok(c) => c,
-        Err(vigil_lib::config::ConfigError::Io(ref e))
-            if e.kind() == std::io::ErrorKind::NotFound =>
-        {
-            // Config doesn't exist yet — create a new one
-            use vigil_lib::config::{Config, GlobalConfig};
-            Config {
-                global: GlobalConfig::default(),
-                backup_sets: Vec::new(),
-            }
-        }
-        Err(e) => return Err(anyhow!(e).context("Failed to load configuration")),
-    };
-
-    if config.backup_sets.iter().any(|s| s.name == name) {
-        return Err(anyhow!("Backup set '{}' already exists", name));
-    }
-
-    config.backup_sets.push(BackupSet {
+    let set = BackupSet {
         name: name.clone(),
         source: Some(source),
         sources: None,
         target,
-        exclude: None,
+        exclude: (!exclude.is_empty()).then_some(exclude),
         debounce_seconds: None,
-        retention: None,
-    });
-
-    save_config(&config).context("Failed to save configuration")?;
+        retention,
+        ..Default::default()
+    };
+    let daemon_running = edit_config(vec![ConfigOp::AddSet { set: Box::new(set) }]).await?;
 
     if !quiet && !json {
         println!("Config updated. Initializing repository...");
@@ -1889,18 +6359,14 @@ async fn handle_track(
         println!("Reloading service...");
     }
 
-    // Reload the daemon
-    match handle_reload(json, true).await {
-        Ok(_) => {
-            if !quiet && !json {
-                println!("Service reloaded successfully.");
-            }
-        }
-        Err(_) => {
-            if !quiet && !json {
-                println!("Note: Service daemon not running, skip reload.");
-            }
+    // Reload so the daemon refreshes the status of the newly initialized repository
+    if daemon_running {
+        handle_reload(json, true).await?;
+        if !quiet && !json {
+            println!("Service reloaded successfully.");
         }
+    } else if !quiet && !json {
+        println!("Note: Service daemon not running, skip reload.");
     }
 
     if !quiet && !json {
@@ -1912,21 +6378,37 @@ async fn handle_track(
     Ok(())
 }
 
-async fn handle_untrack(name: String, purge: bool, json: bool, quiet: bool) -> anyhow::Result<()> {
-    use vigil_lib::config::{load_config_raw, save_config};
+async fn handle_untrack(
+    name: String,
+    purge: bool,
+    dry_run: bool,
+    json: bool,
+    quiet: bool,
+) -> anyhow::Result<()> {
+    use vigil_lib::config::{load_config_raw, ConfigOp};
+
+    let config = load_config_raw().context("Failed to load configuration")?;
+    let Some(set) = config.backup_sets.iter().find(|s| s.name == name) else {
+        return Err(anyhow!("Backup set '{}' not found", name));
+    };
+
+    if dry_run {
+        let mut plan = Plan::default();
+        if purge {
+            plan.extend(purge_plan(&name, &set.target));
+        }
+        plan.push(PlannedAction::RemoveSet {
+            set_name: name.clone(),
+            config: paths::active_config_path(),
+        });
+        let command = format!("untrack {}{}", name, if purge { " --purge" } else { "" });
+        return plan.print(&command, json, quiet);
+    }
 
     if !quiet && !json {
         println!("Untracking backup set '{}'...", name);
     }
 
-    let mut config = load_config_raw().context("Failed to load configuration")?;
-
-    let set_index = config
-        .backup_sets
-        .iter()
-        .position(|s| s.name == name)
-        .ok_or_else(|| anyhow!("Backup set '{}' not found", name))?;
-
     if purge {
         if !quiet && !json {
             println!(
@@ -1935,27 +6417,16 @@ async fn handle_untrack(name: String, purge: bool, json: bool, quiet: bool) -> a
             );
         }
         // Use force=true because it's still in config
-        handle_purge(name.clone(), true, json, quiet).await?;
+        handle_purge(name.clone(), true, false, json, quiet).await?;
     }
 
-    config.backup_sets.remove(set_index);
-    save_config(&config).context("Failed to save configuration")?;
+    let daemon_running = edit_config(vec![ConfigOp::RemoveSet { name: name.clone() }]).await?;
 
     if !quiet && !json {
-        println!("Config updated. Reloading service...");
-    }
-
-    // Reload the daemon
-    match handle_reload(json, true).await {
-        Ok(_) => {
-            if !quiet && !json {
-                println!("Service reloaded successfully.");
-            }
-        }
-        Err(_) => {
-            if !quiet && !json {
-                println!("Note: Service daemon not running, skip reload.");
-            }
+        if daemon_running {
+            println!("Config updated and service reloaded.");
+        } else {
+            println!("Config updated. Note: Service daemon not running, skip reload.");
         }
     }
 
@@ -1968,58 +6439,787 @@ async fn handle_untrack(name: String, purge: bool, json: bool, quiet: bool) -> a
     Ok(())
 }
 
+/// Applies configuration edits, preferring the daemon so concurrent edits are
+/// serialized and take effect before it replies. Falls back to editing the file
+/// directly when the daemon is not running or does not support edits.
+///
+/// Returns whether the daemon is running and has picked up the change.
+async fn edit_config(ops: Vec<vigil_lib::config::ConfigOp>) -> anyhow::Result<bool> {
+    let Ok(mut stream) = transport::connect().await else {
+        vigil_lib::config::modify_config(&ops).context("Failed to update configuration")?;
+        return Ok(false);
+    };
+
+    send_request(&mut stream, Request::ModifyConfig { ops: ops.clone() }).await?;
+    let mut reader = BufReader::new(&mut stream);
+    match receive_reply(&mut reader).await? {
+        Response::Ok(_) => Ok(true),
+        Response::Error { code, .. } if code == vigil_lib::ipc::error_codes::INVALID_REQUEST => {
+            // Older daemon: edit the file ourselves and ask it to reload
+            vigil_lib::config::modify_config(&ops).context("Failed to update configuration")?;
+            handle_reload(false, true).await?;
+            Ok(true)
+        }
+        Response::Error { message, .. } => Err(anyhow!(message)),
+        _ => Err(anyhow!("Unexpected response from service daemon")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use vigil_lib::config::RetentionPolicy;
+    use vigil_lib::types::{HookStage, HostSnapshots, MountInfo};
+
+    #[test]
+    fn test_render_history() {
+        let started_at = Utc::now();
+        let runs = [
+            BackupRun {
+                set_name: "home".to_string(),
+                started_at,
+                finished_at: started_at + Duration::seconds(42),
+                success: true,
+                snapshot_id: Some("a1b2c3d4e5f6".to_string()),
+                added_bytes: 1536,
+                error: None,
+                skipped_trivial: false,
+            },
+            BackupRun {
+                set_name: "home".to_string(),
+                started_at,
+                finished_at: started_at + Duration::seconds(3),
+                success: false,
+                snapshot_id: None,
+                added_bytes: 0,
+                error: Some("repository is locked\nstderr: ...".to_string()),
+                skipped_trivial: false,
+            },
+        ];
+        let lines = render_history(&runs);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("STARTED"));
+        // Newest first; failures show the first line of their error
+        assert!(lines[1].ends_with("✗ fail          -  repository is locked"));
+        assert!(lines[1].ends_with("✗ fail          -  repository is locked"));
+        assert!(render_history(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_render_restore_history() {
+        let started_at = Utc::now();
+        let restore = RestoreRecord {
+            set_name: "home".to_string(),
+            started_at,
+            finished_at: started_at + Duration::seconds(12),
+            snapshot_id: "a1b2c3d4e5f6".to_string(),
+            path: Some("/home/me/docs".to_string()),
+            target: "/tmp/restore".to_string(),
+            requested_by: vigil_lib::types::Requester {
+                uid: Some(1000),
+                user: Some("alice".to_string()),
+                client: Some("/usr/bin/vigil".to_string()),
+            },
+            success: true,
+            files_restored: 3,
+            bytes_restored: 2048,
+            error: None,
+        };
+        let failed = RestoreRecord {
+            success: false,
+            files_restored: 0,
+            bytes_restored: 0,
+            error: Some("repository is locked\nstderr: ...".to_string()),
+            requested_by: vigil_lib::types::Requester {
+                uid: Some(1001),
+                ..Default::default()
+            },
+            ..restore.clone()
+        };
+        let lines = render_restore_history(&[restore, failed]);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("STARTED"));
+        // Newest first; the requester is named, or their uid without a name
+        assert!(lines[1].ends_with("✗ fail a1b2c3d4 uid 1001     repository is locked"));
+        assert!(lines[2].ends_with(
+            "✓ ok   a1b2c3d4 alice        /home/me/docs → /tmp/restore (3 files, 2.0 KiB)"
+        ));
+        assert!(render_restore_history(&[]).is_empty());
+
+        assert_eq!(parse_history_op("restore"), Ok(HistoryOp::Restore));
+        assert!(parse_history_op("prune").is_err());
+    }
+
+    #[test]
+    fn test_render_health() {
+        assert_eq!(
+            render_health(HealthLevel::Healthy, &[]),
+            vec!["Health: healthy"]
+        );
+        let issues = vec![
+            HealthIssue {
+                set_name: "home".to_string(),
+                kind: vigil_lib::types::HealthIssueKind::Failed,
+                level: HealthLevel::Failing,
+                message: "Last backup failed: repository locked".to_string(),
+            },
+            HealthIssue {
+                set_name: "photos".to_string(),
+                kind: vigil_lib::types::HealthIssueKind::Stale,
+                level: HealthLevel::Degraded,
+                message: "Never backed up".to_string(),
+            },
+        ];
+        assert_eq!(
+            render_health(HealthLevel::Failing, &issues),
+            vec![
+                "Health: failing (2 issues)",
+                "  ✗ home: Last backup failed: repository locked",
+                "  ⚠ photos: Never backed up",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backup_summary() {
+        let outcomes = std::collections::BTreeMap::from([
+            (
+                "home".to_string(),
+                SetOutcome::Completed {
+                    snapshot_id: "a1b2c3d4".to_string(),
+                    added_bytes: 1536,
+                    duration_secs: 42.0,
+                },
+            ),
+            (
+                "photos".to_string(),
+                SetOutcome::Failed {
+                    error: "repository is already locked\nstderr: ...".to_string(),
+                },
+            ),
+            ("work".to_string(), SetOutcome::Started),
+        ]);
+
+        let lines = render_backup_summary(&outcomes);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("SET"));
+        assert!(lines[1].starts_with("home   ✓ ok"));
+        assert!(lines[1].ends_with("a1b2c3d4"));
+        assert!(lines[2].ends_with("repository is already locked"));
+        assert!(lines[3].starts_with("work   … running"));
+
+        let json = backup_summary_json(&outcomes);
+        assert_eq!(json["kind"], "BackupSummary");
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["sets"][0]["added_bytes"], 1536);
+        assert_eq!(json["sets"][1]["status"], "failed");
+        assert!(json["sets"][2].get("error").is_none());
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let stats = RepoStats {
+            snapshot_count: 2,
+            restore_bytes: 20 * 1024 * 1024,
+            file_count: 300,
+            raw_bytes: 4 * 1024 * 1024,
+            stored_bytes: 2 * 1024 * 1024,
+            blob_count: 77,
+        };
+        let snapshot = |id: &str, bytes| SnapshotInfo {
+            id: id.repeat(8),
+            short_id: id.to_string(),
+            timestamp: Utc::now(),
+            paths: vec![],
+            tags: vec![],
+            total_bytes: bytes,
+            hostname: None,
+        };
+        let snapshots = [
+            snapshot("aaaaaaaa", Some(10 * 1024 * 1024)),
+            snapshot("bbbbbbbb", None),
+        ];
+        let lines = render_stats("home", &stats, &snapshots, 1);
+        assert!(lines.contains(&"  Restore size:   20.0 MiB in 300 files".to_string()));
+        assert!(lines.contains(&"  Stored:         2.0 MiB (compression 2.00x)".to_string()));
+        assert!(lines.contains(&"  Deduplication:  5.00x".to_string()));
+        // Only the newest snapshot fits the limit
+        assert!(lines.last().unwrap().starts_with("bbbbbbbb"));
+        assert!(lines.last().unwrap().ends_with("N/A"));
+        assert!(!lines.iter().any(|l| l.starts_with("aaaaaaaa")));
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        let now = Utc::now();
+        let local = |time: DateTime<Utc>| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S%.f")
+                .to_string()
+        };
+        let since = parse_time_bound("2026-10-13", false, now).unwrap();
+        assert_eq!(local(since), "2026-10-13 00:00:00");
+        let until = parse_time_bound("2026-10-13", true, now).unwrap();
+        assert_eq!(local(until), "2026-10-13 23:59:59.999999999");
+        let at = parse_time_bound("2026-10-13 14:30", true, now).unwrap();
+        assert_eq!(local(at), "2026-10-13 14:30:00");
+        assert_eq!(
+            parse_time_bound("3d", false, now).unwrap(),
+            now - Duration::days(3)
+        );
+        assert_eq!(
+            parse_time_bound("2026-10-13T12:00:00Z", false, now)
+                .unwrap()
+                .to_rfc3339(),
+            "2026-10-13T12:00:00+00:00"
+        );
+        assert!(parse_time_bound("last tuesday", false, now).is_err());
+    }
+
+    fn sample_set(name: &str, state: JobState) -> SetStatus {
+        SetStatus {
+            name: name.to_string(),
+            state,
+            last_backup: None,
+            source_paths: vec![],
+            target: std::path::PathBuf::from("/tmp/repo"),
+            is_mounted: false,
+            mount_warming: false,
+            mounts: Vec::new(),
+            pending_changes: 0,
+            snapshot_count: Some(12),
+            total_bytes: Some(3 * 1024 * 1024 * 1024),
+            initializing: false,
+            next_run: None,
+            last_verified: None,
+            verification_stale: false,
+            append_only: false,
+            skipped_trivial: None,
+            target_free_bytes: None,
+            growth_bytes_per_day: None,
+            watch_events_coalesced: 0,
+            watch_events_dropped: 0,
+            recent_changes: Vec::new(),
+            replica_lag: None,
+        }
+    }
+
+    #[test]
+    fn test_status_cells_while_initializing() {
+        let mut set = sample_set("home", JobState::Idle);
+        set.initializing = true;
+        assert_eq!(StatusColumn::Snapshots.cell(&set, false), "initializing");
+        assert_eq!(StatusColumn::Last.cell(&set, true), "init");
+        assert_eq!(StatusColumn::State.cell(&set, false), "Idle");
+    }
+
+    #[test]
+    fn test_format_backup_usage() {
+        let mut result = BackupResult {
+            snapshot_id: "a1b2c3d4".to_string(),
+            timestamp: Utc::now(),
+            added_bytes: 0,
+            duration_secs: 3.0,
+            success: true,
+            error_message: None,
+            peak_rss_bytes: Some(48 * 1024 * 1024),
+            cpu_secs: Some(2.54),
+            hooks: Vec::new(),
+        };
+        assert_eq!(
+            format_backup_usage(&result).as_deref(),
+            Some("48.0 MiB peak RSS, 2.5s CPU")
+        );
+        result.cpu_secs = None;
+        assert_eq!(format_backup_usage(&result), None);
+    }
+
+    #[test]
+    fn test_format_hooks() {
+        let hook = |stage, success, exit_code| HookOutcome {
+            stage,
+            success,
+            exit_code,
+            output: String::new(),
+            duration_secs: 1.21,
+        };
+        assert_eq!(
+            format_hooks(&[
+                hook(HookStage::Pre, true, Some(0)),
+                hook(HookStage::Post, false, Some(3))
+            ]),
+            "pre-backup ✓ 1.2s, post-backup ✗ exit 3"
+        );
+        assert_eq!(
+            format_hooks(&[hook(HookStage::Pre, false, None)]),
+            "pre-backup ✗ killed"
+        );
+    }
+
+    #[test]
+    fn test_verification_warnings() {
+        let fresh = sample_set("fresh", JobState::Idle);
+        let mut never = sample_set("never", JobState::Idle);
+        never.verification_stale = true;
+        let mut old = sample_set("old", JobState::Idle);
+        old.verification_stale = true;
+        old.last_verified = Some(Utc::now() - Duration::days(40));
+
+        let warnings = verification_warnings(&[fresh, never, old]);
+        assert_eq!(
+            warnings,
+            vec![
+                "⚠ never: repository never verified. Run `vigil check --deep never`.",
+                "⚠ old: repository last verified 40 days ago. Run `vigil check --deep old`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_service_unit_exec_path() {
+        let unit = service_unit(std::path::Path::new("/home/me/.local/bin/vigil-daemon"));
+        assert!(unit.contains("\nExecStart=/home/me/.local/bin/vigil-daemon\n"));
+        assert_eq!(
+            systemd_exec_path(std::path::Path::new("/opt/My Apps/100%/vigil-daemon")),
+            "\"/opt/My Apps/100%%/vigil-daemon\""
+        );
+    }
+
+    #[test]
+    fn test_dir_on_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        let path_var = std::env::join_paths(["/usr/bin", bin.to_str().unwrap()]).unwrap();
+        assert!(dir_on_path(&bin, &path_var));
+        assert!(dir_on_path(&tmp.path().join("bin/../bin"), &path_var));
+        assert!(!dir_on_path(tmp.path(), &path_var));
+    }
+
+    #[test]
+    fn test_attestation_lines() {
+        use vigil_lib::attest::{Attestation, AttestationSignature, ToolVersions};
+        use vigil_lib::types::VerifyOutcome;
+
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let mut signed = SignedAttestation {
+            attestation: Attestation {
+                format: 1,
+                set_name: "home".to_string(),
+                hostname: "desk".to_string(),
+                created_at: time("2026-10-16T09:00:00Z"),
+                repository: "/mnt/backup/home".to_string(),
+                repository_id: "5c1b4ef4f1".to_string(),
+                snapshot_count: 0,
+                latest_snapshot: None,
+                last_check: None,
+                tools: ToolVersions {
+                    vigil: "0.1.0".to_string(),
+                    engine: "restic".to_string(),
+                    engine_version: "restic 0.17.3".to_string(),
+                },
+            },
+            signature: AttestationSignature {
+                algorithm: "ed25519".to_string(),
+                public_key: "ab".repeat(32),
+                value: String::new(),
+            },
+        };
+        let lines = attestation_lines(&signed, Signer::Local);
+        assert_eq!(lines[4], "Snapshots:  0");
+        assert_eq!(lines[5], "Last check: never run");
+        assert_eq!(lines[6], "Tools:      vigil 0.1.0, restic 0.17.3");
+        assert_eq!(lines[8], "            (this machine's key)");
+
+        signed.attestation.snapshot_count = 42;
+        signed.attestation.latest_snapshot = Some(vigil_lib::attest::AttestedSnapshot {
+            id: "a1b2c3d4e5f6".to_string(),
+            time: time("2026-10-16T08:30:00Z"),
+        });
+        signed.attestation.last_check = Some(VerifyOutcome {
+            checked_at: time("2026-10-10T03:00:00Z"),
+            subset: "5%".to_string(),
+            success: false,
+            error: Some("pack 1f2e is damaged".to_string()),
+        });
+        let lines = attestation_lines(&signed, Signer::Pinned);
+        assert_eq!(
+            lines[4],
+            "Snapshots:  42, latest a1b2c3d4 at 2026-10-16 08:30 UTC"
+        );
+        assert_eq!(
+            lines[5],
+            "Last check: FAILED 2026-10-10 03:00 UTC: pack 1f2e is damaged"
+        );
+        assert_eq!(lines[8], "            (the key given with --public-key)");
+    }
+
+    #[test]
+    fn test_retention_lines() {
+        let host = |hostname: &str, snapshot_count: usize, is_local: bool| HostSnapshots {
+            hostname: hostname.to_string(),
+            snapshot_count,
+            latest: Utc::now(),
+            is_local,
+        };
+        let mut info = SetInfo {
+            status: sample_set("home", JobState::Idle),
+            shared_repo: false,
+            local_hostname: "laptop".to_string(),
+            hosts: vec![host("laptop", 12, true)],
+            pending_paths: Vec::new(),
+            retention: Some(RetentionPolicy::parse("last=5,daily=3").unwrap()),
+        };
+        assert_eq!(
+            retention_lines(&info),
+            vec![
+                "Retention:  last=5,daily=3 (keeps at most 8 snapshot(s) per host and paths)",
+                "Budget:     12 snapshot(s) stored, 4 more than the policy keeps; the next prune forgets them",
+            ]
+        );
+
+        info.retention = Some(RetentionPolicy::parse("last=20").unwrap());
+        assert_eq!(
+            retention_lines(&info)[1],
+            "Budget:     12 of 20 snapshot(s) used"
+        );
+
+        // A shared repository is only thinned for this machine
+        info.shared_repo = true;
+        info.hosts.push(host("desktop", 30, false));
+        assert_eq!(
+            retention_lines(&info)[1],
+            "Budget:     12 of 20 snapshot(s) used"
+        );
+
+        info.retention = Some(RetentionPolicy::parse("last=5,within=30d").unwrap());
+        assert_eq!(
+            retention_lines(&info),
+            vec!["Retention:  last=5,within=30d (no fixed limit per host and paths)"]
+        );
+
+        info.retention = Some(RetentionPolicy::default());
+        let lines = retention_lines(&info);
+        assert_eq!(lines[0], "Retention:  no keep rules");
+        assert!(lines[1].contains("can never prune"), "{}", lines[1]);
+
+        info.retention = None;
+        assert_eq!(
+            retention_lines(&info),
+            vec!["Retention:  none (snapshots are never pruned)"]
+        );
+    }
+
+    #[test]
+    fn test_capacity_lines() {
+        use vigil_lib::types::ReplicaLag;
+
+        let mut home = sample_set("home", JobState::Idle);
+        home.target_free_bytes = Some(100 * 1024 * 1024 * 1024);
+        home.growth_bytes_per_day = Some(1024 * 1024 * 1024);
+        let mut remote = sample_set("remote", JobState::Idle);
+        remote.growth_bytes_per_day = Some(0);
+        let mut new = sample_set("new", JobState::Idle);
+        new.target_free_bytes = Some(1024);
+        let mut build = sample_set("build", JobState::Idle);
+        build.watch_events_coalesced = 48_210;
+        build.watch_events_dropped = 12;
+        let lag = |missing_snapshots, lag_secs, behind, error: Option<&str>| ReplicaLag {
+            checked_at: Utc::now(),
+            missing_snapshots,
+            lag_secs,
+            behind,
+            error: error.map(str::to_string),
+        };
+        let mut mirrored = sample_set("mirrored", JobState::Idle);
+        mirrored.replica_lag = Some(lag(0, 0, false, None));
+        let mut lagging = sample_set("lagging", JobState::Idle);
+        lagging.replica_lag = Some(lag(3, 30 * 3600, true, None));
+        let mut offline = sample_set("offline", JobState::Idle);
+        offline.replica_lag = Some(lag(0, 0, false, Some("repository not found")));
+        let lines = capacity_lines(&[
+            home,
+            remote,
+            new,
+            build,
+            mirrored,
+            lagging,
+            offline,
+            sample_set("unknown", JobState::Idle),
+        ]);
+        assert_eq!(
+            lines,
+            vec![
+                "home: 100.0 GiB free on target, growing 1.0 GiB/day, full in ~100 days",
+                "remote: no growth this week",
+                "new: 1.0 KiB free on target",
+                "build: 48210 watcher event(s) coalesced, 12 dropped",
+                "mirrored: replica up to date",
+                "lagging: replica lacks 3 snapshot(s), 1d behind (over max_copy_lag_hours)",
+                "offline: replica not compared: repository not found",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recent_change_lines() {
+        let mut home = sample_set("home", JobState::Idle);
+        home.recent_changes = vec!["/home/u/a.txt".into(), "/home/u/b.txt".into()];
+        let lines = recent_change_lines(&[sample_set("work", JobState::Idle), home]);
+        assert_eq!(
+            lines,
+            vec![
+                "home: recently changed",
+                "  /home/u/b.txt",
+                "  /home/u/a.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skipped_trivial_notes() {
+        let mut home = sample_set("home", JobState::Idle);
+        home.skipped_trivial = Some(Utc::now() - Duration::minutes(5));
+        home.pending_changes = 2;
+        let notes = skipped_notes(&[sample_set("work", JobState::Idle), home]);
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].starts_with("ℹ home: backup skipped 5 mins ago; 2 pending"));
+
+        let run = BackupRun {
+            set_name: "home".to_string(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            success: true,
+            snapshot_id: None,
+            added_bytes: 0,
+            error: None,
+            skipped_trivial: true,
+        };
+        let lines = render_history(&[run]);
+        assert!(lines[1].contains("– skip"));
+        assert!(lines[1].ends_with("too few changes, carried over"));
+    }
+
+    #[test]
+    fn test_status_table_default_layout() {
+        let sets = vec![sample_set("home", JobState::Idle)];
+        let lines = render_status_table(&sets, &StatusColumn::ALL, None);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("NAME            STATE"));
+        assert!(lines[0].contains("LAST BACKUP"));
+        assert_eq!(lines[1].len(), 96);
+        assert!(lines[2].contains("3.0 GiB"));
+    }
+
+    #[test]
+    fn test_status_table_column_selection() {
+        let sets = vec![sample_set("home", JobState::Idle)];
+        let columns = [StatusColumn::Size, StatusColumn::Name, StatusColumn::Size];
+        let lines = render_status_table(&sets, &columns, None);
+        assert_eq!(lines[0], "SIZE       NAME");
+        assert_eq!(lines[2], "3.0 GiB    home");
+    }
+
+    #[test]
+    fn test_status_table_abbreviates_to_fit() {
+        let sets = vec![sample_set(
+            "home",
+            JobState::Debouncing { remaining_secs: 45 },
+        )];
+        let lines = render_status_table(&sets, &StatusColumn::ALL, Some(40));
+        assert!(lines[0].contains("SNAPS"));
+        assert!(lines[0].contains("MNT"));
+        assert!(lines[2].contains("Deb 45s"));
+        assert!(lines[2].contains("3.0G"));
+        assert!(lines.iter().all(|l| l.chars().count() <= 40));
+    }
+
+    #[test]
+    fn test_status_table_truncates_long_names() {
+        let sets = vec![sample_set("a-very-long-backup-set-name", JobState::Idle)];
+        let columns = [StatusColumn::Name, StatusColumn::State];
+        let lines = render_status_table(&sets, &columns, Some(16));
+        assert!(lines.iter().all(|l| l.chars().count() <= 16));
+        assert!(lines[2].starts_with("a-very-lo…"));
+        assert!(lines[2].ends_with("Idle"));
+    }
 
     #[test]
-    fn test_format_human_duration_seconds() {
-        assert_eq!(format_human_duration(Duration::seconds(0)), "0s ago");
-        assert_eq!(format_human_duration(Duration::seconds(30)), "30s ago");
-        assert_eq!(format_human_duration(Duration::seconds(59)), "59s ago");
+    fn test_status_state_cell_shows_progress() {
+        let mut set = sample_set("home", JobState::running());
+        assert_eq!(StatusColumn::State.cell(&set, false), "Running");
+        set.state = JobState::Running {
+            percent: Some(42.7),
+            eta_secs: Some(200),
+            scanning: None,
+        };
+        assert_eq!(StatusColumn::State.cell(&set, false), "Running 42% ~3m");
+        assert_eq!(StatusColumn::State.cell(&set, true), "Run 42%");
+        set.state = JobState::Running {
+            percent: Some(0.5),
+            eta_secs: None,
+            scanning: None,
+        };
+        assert_eq!(StatusColumn::State.cell(&set, false), "Running 0%");
+        set.state = JobState::Running {
+            percent: None,
+            eta_secs: None,
+            scanning: Some(Scanning {
+                files: 52000,
+                bytes: 9 * 1024 * 1024 * 1024,
+            }),
+        };
+        assert_eq!(
+            StatusColumn::State.cell(&set, false),
+            "Scanning 52000 files, 9.0 GiB"
+        );
+        assert!(StatusColumn::State.cell(&set, true).starts_with("Scan 9"));
+
+        set.state = JobState::Retrying {
+            attempt: 2,
+            next_retry_secs: 95,
+        };
+        assert_eq!(StatusColumn::State.cell(&set, false), "Retry 2 (95s)");
+        assert_eq!(StatusColumn::State.cell(&set, true), "Rty 95s");
+    }
+
+    #[test]
+    fn test_status_next_run_cell() {
+        let mut set = sample_set("home", JobState::Idle);
+        assert_eq!(StatusColumn::Next.cell(&set, false), "-");
+        set.next_run = Some(Utc::now() + Duration::minutes(90) + Duration::seconds(5));
+        assert_eq!(StatusColumn::Next.cell(&set, false), "in 1h");
+        assert_eq!(StatusColumn::Next.cell(&set, true), "1h");
+        set.next_run = Some(Utc::now() - Duration::seconds(5));
+        assert_eq!(StatusColumn::Next.cell(&set, false), "due");
     }
 
     #[test]
-    fn test_format_human_duration_minutes() {
-        assert_eq!(format_human_duration(Duration::seconds(60)), "1 min ago");
-        assert_eq!(format_human_duration(Duration::seconds(61)), "1 min ago");
-        assert_eq!(format_human_duration(Duration::seconds(120)), "2 mins ago");
+    fn test_render_backup_progress() {
+        let gib = 1024 * 1024 * 1024;
+        let base = "home [#####---------------]  25% 1.0 GiB/4.0 GiB ~3m left";
+        let render = |file, width| {
+            render_backup_progress("home", 25.0, gib, 4 * gib, Some(180), file, width)
+        };
+        assert_eq!(render(None, None), base);
+        assert_eq!(
+            render(Some("/home/u/a.txt"), None),
+            format!("{}  /home/u/a.txt", base)
+        );
+        let width = base.chars().count() + 8;
+        assert_eq!(
+            render(Some("/home/u/a.txt"), Some(width)),
+            format!("{}  …a.txt", base)
+        );
+        assert_eq!(
+            render_backup_progress("home", 0.0, 512, 0, None, None, None),
+            "home [--------------------]   0% 512 B"
+        );
+        let scanning = Scanning {
+            files: 52000,
+            bytes: 8 * gib,
+        };
         assert_eq!(
-            format_human_duration(Duration::seconds(3599)),
-            "59 mins ago"
+            render_scan_progress("home", &scanning, Some("/home/u/a.txt"), None),
+            "home scanning… 52000 files, 8.0 GiB found  /home/u/a.txt"
         );
     }
 
     #[test]
-    fn test_format_human_duration_hours() {
-        assert_eq!(format_human_duration(Duration::seconds(3600)), "1 hour ago");
+    fn test_status_mounted_cell_shows_warming() {
+        let mut set = sample_set("home", JobState::Idle);
+        set.is_mounted = true;
+        assert_eq!(StatusColumn::Mounted.cell(&set, false), "Yes");
+        set.mount_warming = true;
+        assert_eq!(StatusColumn::Mounted.cell(&set, false), "Warming…");
+        assert_eq!(StatusColumn::Mounted.cell(&set, true), "W");
+
+        set.mount_warming = false;
+        set.mounts = ["latest", "0123abcd"]
+            .iter()
+            .map(|snapshot| MountInfo {
+                snapshot: snapshot.to_string(),
+                path: PathBuf::from("/mnt").join(snapshot),
+                warming: false,
+            })
+            .collect();
+        assert_eq!(StatusColumn::Mounted.cell(&set, false), "Yes (2)");
+        assert_eq!(StatusColumn::Mounted.cell(&set, true), "2");
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let paths = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
         assert_eq!(
-            format_human_duration(Duration::seconds(7200)),
-            "2 hours ago"
+            render_diff(&paths(&["/a"]), &paths(&["/b", "/c"]), &paths(&["/d"])),
+            vec![
+                "+ /a",
+                "- /b",
+                "- /c",
+                "M /d",
+                "",
+                "1 added, 2 removed, 1 modified"
+            ]
         );
         assert_eq!(
-            format_human_duration(Duration::seconds(86399)),
-            "23 hours ago"
+            render_diff(&[], &[], &[]),
+            vec!["No differences between the snapshots."]
         );
     }
 
     #[test]
-    fn test_format_human_duration_days() {
-        assert_eq!(format_human_duration(Duration::seconds(86400)), "1 day ago");
+    fn test_extract_output() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("config.toml"), "").unwrap();
+        assert_eq!(
+            extract_output("/home/u/notes.md", tmp.path()).unwrap(),
+            tmp.path().join("notes.md")
+        );
+        assert!(extract_output("/", tmp.path()).is_err());
+        assert!(
+            extract_output("/home/u/.config/vigil/config.toml", tmp.path())
+                .unwrap_err()
+                .to_string()
+                .contains("already exists")
+        );
+    }
+
+    #[test]
+    fn test_render_ls() {
+        let mtime: DateTime<Utc> = "2026-10-02T08:30:00Z".parse().unwrap();
+        let entries = vec![
+            FileEntry {
+                path: "/home/u/a.conf".to_string(),
+                node_type: "file".to_string(),
+                size: Some(42),
+                mtime: Some(mtime),
+            },
+            FileEntry {
+                path: "/home/u/docs".to_string(),
+                node_type: "dir".to_string(),
+                size: None,
+                mtime: None,
+            },
+        ];
+        assert_eq!(render_ls(&entries, false, false), vec!["a.conf", "docs/"]);
         assert_eq!(
-            format_human_duration(Duration::seconds(172800)),
-            "2 days ago"
+            render_ls(&entries, true, false),
+            vec!["/home/u/a.conf", "/home/u/docs/"]
         );
+        let local = mtime.with_timezone(&Local).format("%Y-%m-%d %H:%M");
         assert_eq!(
-            format_human_duration(Duration::seconds(604800)),
-            "7 days ago"
+            render_ls(&entries, false, true),
+            vec![
+                format!("-       42 B  {}  a.conf", local),
+                "d          -  -                 docs/".to_string(),
+            ]
         );
     }
 
     #[test]
-    fn test_format_human_duration_negative() {
-        // Edge case: negative durations (clock skew) should show "just now"
-        assert_eq!(format_human_duration(Duration::seconds(-1)), "just now");
-        assert_eq!(format_human_duration(Duration::seconds(-3600)), "just now");
+    fn test_truncate_cell() {
+        assert_eq!(truncate_cell("home", 10), "home");
+        assert_eq!(truncate_cell("documents", 5), "docu…");
+        assert_eq!(truncate_cell("documents", 0), "");
     }
 }
@@ -0,0 +1,166 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// One change a destructive command would make, as reported by `--dry-run`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// Stop and disable a systemd user unit.
+    DisableUnit { unit: String },
+    /// Reload systemd's unit files.
+    ReloadSystemd,
+    /// Delete a file.
+    RemoveFile { path: PathBuf },
+    /// Delete a directory and everything in it.
+    RemoveDir { path: PathBuf },
+    /// Delete a local repository.
+    DeleteRepository { target: String },
+    /// Leave a remote repository in place; vigil cannot delete it.
+    KeepRemoteRepository { target: String },
+    /// Unmount a mounted snapshot (`latest` for the newest).
+    Unmount {
+        set_name: String,
+        snapshot: String,
+        path: PathBuf,
+    },
+    /// Remove a backup set from the configuration file.
+    RemoveSet { set_name: String, config: PathBuf },
+    /// Forget a snapshot, and with `prune` the data only it referenced.
+    ForgetSnapshot {
+        set_name: String,
+        snapshot_id: String,
+        prune: bool,
+    },
+}
+
+impl std::fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedAction::DisableUnit { unit } => write!(f, "Stop and disable {}", unit),
+            PlannedAction::ReloadSystemd => write!(f, "Reload systemd user units"),
+            PlannedAction::RemoveFile { path } => write!(f, "Remove file {}", path.display()),
+            PlannedAction::RemoveDir { path } => {
+                write!(f, "Remove directory {} and its contents", path.display())
+            }
+            PlannedAction::DeleteRepository { target } => {
+                write!(f, "Delete repository {}", target)
+            }
+            PlannedAction::KeepRemoteRepository { target } => write!(
+                f,
+                "Leave remote repository {} in place (delete it with your storage provider's tools)",
+                target
+            ),
+            PlannedAction::Unmount {
+                set_name,
+                snapshot,
+                path,
+            } => write!(
+                f,
+                "Unmount snapshot {} of set '{}' from {}",
+                snapshot,
+                set_name,
+                path.display()
+            ),
+            PlannedAction::RemoveSet { set_name, config } => {
+                write!(f, "Remove set '{}' from {}", set_name, config.display())
+            }
+            PlannedAction::ForgetSnapshot {
+                set_name,
+                snapshot_id,
+                prune,
+            } => {
+                write!(f, "Forget snapshot {} of set '{}'", snapshot_id, set_name)?;
+                if *prune {
+                    write!(f, " and delete the data only it referenced")
+                } else {
+                    write!(f, "; its data stays until the next prune")
+                }
+            }
+        }
+    }
+}
+
+/// What a command would do, in order. Destructive commands build one for
+/// `--dry-run` and print it instead of acting.
+#[derive(Debug, Default)]
+pub struct Plan {
+    actions: Vec<PlannedAction>,
+}
+
+impl Plan {
+    pub fn push(&mut self, action: PlannedAction) {
+        self.actions.push(action);
+    }
+
+    pub fn extend(&mut self, other: Plan) {
+        self.actions.extend(other.actions);
+    }
+
+    /// Lines describing the plan of `vigil <command>`.
+    pub fn lines(&self, command: &str) -> Vec<String> {
+        if self.actions.is_empty() {
+            return vec![format!(
+                "Dry run: `vigil {}` would change nothing.",
+                command
+            )];
+        }
+        let mut lines = vec![format!("Dry run: `vigil {}` would:", command)];
+        lines.extend(self.actions.iter().map(|a| format!("  - {}", a)));
+        lines
+    }
+
+    /// Prints the plan of `vigil <command>`; with `json` as
+    /// `{"dry_run", "command", "actions"}`.
+    pub fn print(&self, command: &str, json: bool, quiet: bool) -> anyhow::Result<()> {
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "dry_run": true,
+                    "command": command,
+                    "actions": self.actions,
+                })
+            );
+        } else if !quiet {
+            for line in self.lines(command) {
+                println!("{}", line);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_lines_and_json() {
+        let mut plan = Plan::default();
+        assert_eq!(
+            plan.lines("unmount"),
+            vec!["Dry run: `vigil unmount` would change nothing."]
+        );
+
+        plan.push(PlannedAction::DeleteRepository {
+            target: "/mnt/backup/home".to_string(),
+        });
+        plan.push(PlannedAction::ForgetSnapshot {
+            set_name: "home".to_string(),
+            snapshot_id: "1a2b3c4d".to_string(),
+            prune: false,
+        });
+        assert_eq!(
+            plan.lines("purge home"),
+            vec![
+                "Dry run: `vigil purge home` would:",
+                "  - Delete repository /mnt/backup/home",
+                "  - Forget snapshot 1a2b3c4d of set 'home'; its data stays until the next prune",
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&plan.actions[0]).unwrap(),
+            serde_json::json!({"action": "delete_repository", "target": "/mnt/backup/home"})
+        );
+    }
+}
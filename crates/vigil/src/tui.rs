@@ -0,0 +1,1084 @@
+//! Interactive dashboard for `vigil tui`.
+//!
+//! One long-lived daemon connection polls `Status` and receives the broadcast
+//! events every connection gets. Actions (backup, mount, prune) use a fresh
+//! connection each, so a slow prune never stalls the status updates. The log
+//! pane follows the daemon's log file, like `vigil logs --follow`.
+
+use crate::{format_running, format_secs, format_size, latest_log_file};
+use chrono::Utc;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use vigil_lib::humanize::format_relative;
+use vigil_lib::ipc::{Request, Response, ResponseData};
+use vigil_lib::paths;
+use vigil_lib::transport::{self, IpcWriteHalf};
+use vigil_lib::types::{JobState, SetStatus, UnmountReason};
+
+/// How often the dashboard asks the daemon for fresh status.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the input thread waits for a key before checking whether to exit.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Number of event log lines kept.
+const MAX_EVENTS: usize = 200;
+/// How often the log pane checks the daemon log for new lines.
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How much of the existing log the pane starts with.
+const LOG_TAIL_BYTES: u64 = 64 * 1024;
+/// Number of daemon log lines kept.
+const MAX_LOG_LINES: usize = 2000;
+/// Lines scrolled by PgUp/PgDn in the log pane.
+const LOG_PAGE: usize = 10;
+
+/// Everything the dashboard reacts to.
+#[derive(Debug)]
+enum AppEvent {
+    Key(KeyEvent),
+    /// A line from the long-lived connection: a status reply or a broadcast event.
+    Daemon(Response),
+    /// The reply to an action sent on its own connection.
+    ActionReply {
+        label: String,
+        response: Response,
+    },
+    ActionFailed {
+        label: String,
+        error: String,
+    },
+    Disconnected,
+    /// New complete lines from the daemon log.
+    LogLines(Vec<String>),
+}
+
+/// What a key press asks for.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Quit,
+    Send { label: String, request: Request },
+}
+
+#[derive(Default)]
+struct App {
+    sets: Vec<SetStatus>,
+    selected: usize,
+    /// Newest last.
+    events: VecDeque<String>,
+    connected: bool,
+    show_help: bool,
+    /// Daemon log lines, newest last.
+    logs: VecDeque<String>,
+    /// Show log lines of every set instead of only the selected one.
+    logs_all_sets: bool,
+    /// While paused, the log pane keeps showing the same lines.
+    logs_paused: bool,
+    /// How many matching lines the log pane is scrolled up from the newest.
+    log_scroll: usize,
+}
+
+impl App {
+    fn selected_set(&self) -> Option<&SetStatus> {
+        self.sets.get(self.selected)
+    }
+
+    fn log(&mut self, message: String) {
+        let stamp = chrono::Local::now().format("%H:%M:%S");
+        self.events.push_back(format!("{} {}", stamp, message));
+        while self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    /// Whether a daemon log line belongs in the log pane.
+    fn log_matches(&self, line: &str) -> bool {
+        if self.logs_all_sets {
+            return true;
+        }
+        self.selected_set()
+            .is_some_and(|set| mentions_set(line, &set.name))
+    }
+
+    fn push_logs(&mut self, lines: Vec<String>) {
+        for line in lines {
+            // Keep a paused view on the same lines
+            if self.logs_paused && self.log_matches(&line) {
+                self.log_scroll += 1;
+            }
+            self.logs.push_back(line);
+        }
+        while self.logs.len() > MAX_LOG_LINES {
+            self.logs.pop_front();
+        }
+    }
+
+    /// Selecting another set or filter shows the newest lines again.
+    fn reset_log_view(&mut self) {
+        self.log_scroll = 0;
+        self.logs_paused = false;
+    }
+
+    fn update_sets(&mut self, mut sets: Vec<SetStatus>) {
+        let selected_name = self.selected_set().map(|s| s.name.clone());
+        sets.sort_by(|a, b| a.name.cmp(&b.name));
+        self.selected = selected_name
+            .and_then(|name| sets.iter().position(|s| s.name == name))
+            .unwrap_or(0)
+            .min(sets.len().saturating_sub(1));
+        self.sets = sets;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        if self.show_help {
+            self.show_help = false;
+            return None;
+        }
+        let selected = self.selected_set().map(|s| (s.name.clone(), s.is_mounted));
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::Quit)
+            }
+            KeyCode::Char('?') => {
+                self.show_help = true;
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < self.sets.len() {
+                    self.selected += 1;
+                    self.reset_log_view();
+                }
+                None
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                if self.selected > 0 {
+                    self.selected -= 1;
+                    self.reset_log_view();
+                }
+                None
+            }
+            KeyCode::Char(' ') => {
+                if self.logs_paused {
+                    self.reset_log_view();
+                } else {
+                    self.logs_paused = true;
+                }
+                None
+            }
+            KeyCode::PageUp => {
+                self.logs_paused = true;
+                self.log_scroll += LOG_PAGE;
+                None
+            }
+            KeyCode::PageDown => {
+                self.log_scroll = self.log_scroll.saturating_sub(LOG_PAGE);
+                None
+            }
+            KeyCode::Char('f') => {
+                self.logs_all_sets = !self.logs_all_sets;
+                self.reset_log_view();
+                None
+            }
+            KeyCode::Char('b') => selected.map(|(name, _)| Action::Send {
+                label: format!("{}: backup", name),
+                request: Request::Backup {
+                    set_name: Some(name),
+                    tags: vec![],
+                },
+            }),
+            KeyCode::Char('a') => Some(Action::Send {
+                label: "backup all".to_string(),
+                request: Request::Backup {
+                    set_name: None,
+                    tags: vec![],
+                },
+            }),
+            KeyCode::Char('m') => selected.map(|(name, is_mounted)| {
+                if is_mounted {
+                    Action::Send {
+                        label: format!("{}: unmount", name),
+                        request: Request::Unmount {
+                            set_name: Some(name),
+                            snapshot_id: None,
+                        },
+                    }
+                } else {
+                    Action::Send {
+                        label: format!("{}: mount", name),
+                        request: Request::Mount {
+                            set_name: name,
+                            snapshot_id: None,
+                        },
+                    }
+                }
+            }),
+            KeyCode::Char('p') => selected.map(|(name, _)| Action::Send {
+                label: format!("{}: prune", name),
+                request: Request::Prune {
+                    set_name: Some(name),
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Applies a line from the long-lived connection.
+    fn handle_daemon(&mut self, response: Response) {
+        match response {
+            Response::Ok(Some(ResponseData::Status { sets })) => {
+                self.connected = true;
+                self.update_sets(sets);
+            }
+            Response::Ok(Some(data)) | Response::Event(data) => {
+                if let Some(message) = describe_event(&data) {
+                    self.log(message);
+                }
+            }
+            Response::Error { message, .. } => self.log(format!("error: {}", message)),
+            _ => {}
+        }
+    }
+
+    /// Logs the outcome of an action.
+    fn handle_action_reply(&mut self, label: &str, response: Response) {
+        let message = match response {
+            Response::Ok(Some(ResponseData::BackupStarted { .. })) => format!("{} started", label),
+            Response::Ok(Some(ResponseData::BackupsTriggered { started, failed })) => {
+                let mut message = format!("{}: started {}", label, started.join(", "));
+                for (set, error) in failed {
+                    message.push_str(&format!("; {} failed: {}", set, error));
+                }
+                message
+            }
+            Response::Ok(Some(ResponseData::MountPath { path, .. })) => {
+                format!("{}ed at {}", label, path)
+            }
+            Response::Ok(Some(ResponseData::PruneResult {
+                reclaimed_bytes, ..
+            })) => format!("{} reclaimed {}", label, format_size(reclaimed_bytes)),
+            Response::Ok(_) => format!("{} done", label),
+            Response::Error { message, .. } => format!("{} failed: {}", label, message),
+            Response::CapabilityMissing { name, hint } => {
+                format!("{} needs {}: {}", label, name, hint)
+            }
+            other => format!("{}: unexpected reply {:?}", label, other),
+        };
+        self.log(message);
+    }
+}
+
+/// One-line description of a broadcast event, or `None` for events too
+/// frequent to log (restore progress) and replies that are not events.
+pub(crate) fn describe_event(data: &ResponseData) -> Option<String> {
+    match data {
+        ResponseData::BackupStarted {
+            set_name,
+            recent_changes,
+        } => Some(match recent_changes.as_slice() {
+            [] => format!("{}: backup started", set_name),
+            [path] => format!(
+                "{}: backup started after a change to {}",
+                set_name,
+                path.display()
+            ),
+            [.., path] => format!(
+                "{}: backup started after changes to {} and {} more",
+                set_name,
+                path.display(),
+                recent_changes.len() - 1
+            ),
+        }),
+        ResponseData::BackupComplete {
+            set_name,
+            snapshot_id,
+            added_bytes,
+            duration_secs,
+        } => Some(format!(
+            "{}: backup complete, snapshot {} (+{}, {})",
+            set_name,
+            &snapshot_id[..snapshot_id.len().min(8)],
+            format_size(*added_bytes),
+            format_secs(*duration_secs)
+        )),
+        ResponseData::BackupFailed { set_name, error } => {
+            Some(format!("{}: backup failed: {}", set_name, error))
+        }
+        ResponseData::PruneComplete {
+            set_name,
+            reclaimed_bytes,
+        } => Some(format!(
+            "{}: prune complete, reclaimed {}",
+            set_name,
+            format_size(*reclaimed_bytes)
+        )),
+        ResponseData::ReplicationComplete {
+            set_name,
+            replica_target,
+            copied,
+        } => Some(format!(
+            "{}: replicated to {}, {} snapshot(s) copied",
+            set_name, replica_target, copied
+        )),
+        ResponseData::ReplicationFailed { set_name, error } => {
+            Some(format!("{}: replication failed: {}", set_name, error))
+        }
+        ResponseData::RestoreStarted {
+            set_name, target, ..
+        } => Some(format!("{}: restore into {} started", set_name, target)),
+        ResponseData::RestoreComplete {
+            set_name,
+            target,
+            files_restored,
+            ..
+        } => Some(format!(
+            "{}: restored {} files into {}",
+            set_name, files_restored, target
+        )),
+        ResponseData::RestoreFailed {
+            set_name, error, ..
+        } => Some(format!("{}: restore failed: {}", set_name, error)),
+        ResponseData::Unmounted {
+            set_name,
+            snapshot,
+            reason,
+        } => {
+            let what = match snapshot.as_str() {
+                "" | "latest" => "latest snapshot".to_string(),
+                id => format!("snapshot {}", id),
+            };
+            Some(match reason {
+                UnmountReason::Idle => {
+                    format!("{}: unmounted {} after going unused", set_name, what)
+                }
+                UnmountReason::Requested => format!("{}: unmounted {}", set_name, what),
+                UnmountReason::Lost => format!("{}: {} is no longer mounted", set_name, what),
+            })
+        }
+        ResponseData::StatusInitialized {
+            sets,
+            duration_secs,
+        } => Some(format!(
+            "status of {} sets loaded in {}",
+            sets,
+            format_secs(*duration_secs)
+        )),
+        ResponseData::ShuttingDown {
+            grace_secs,
+            running,
+        } => Some(if running.is_empty() || *grace_secs == 0 {
+            "daemon shutting down".to_string()
+        } else {
+            format!(
+                "daemon shutting down, waiting up to {} for {}",
+                format_secs(*grace_secs as f64),
+                running.join(", ")
+            )
+        }),
+        _ => None,
+    }
+}
+
+async fn write_request(writer: &mut IpcWriteHalf, request: &Request) -> anyhow::Result<()> {
+    let json = serde_json::to_string(request)? + "\n";
+    writer.write_all(json.as_bytes()).await?;
+    Ok(())
+}
+
+/// Sends `request` on its own connection and reports the first reply that is
+/// not a broadcast event.
+fn spawn_action(label: String, request: Request, tx: mpsc::UnboundedSender<AppEvent>) {
+    tokio::spawn(async move {
+        let result = async {
+            let stream = transport::connect().await?;
+            let (reader, mut writer) = stream.into_split();
+            write_request(&mut writer, &request).await?;
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await? {
+                let response: Response = serde_json::from_str(&line)?;
+                if !response.is_broadcast() {
+                    return Ok(response);
+                }
+            }
+            Err(anyhow::anyhow!("Connection closed by service daemon"))
+        }
+        .await;
+        let _ = tx.send(match result {
+            Ok(response) => AppEvent::ActionReply { label, response },
+            Err(e) => AppEvent::ActionFailed {
+                label,
+                error: e.to_string(),
+            },
+        });
+    });
+}
+
+/// Whether `line` names `set` as a whole word, e.g. `Pruning set home` or
+/// `Backup for set 'home' failed`, but not `homework`.
+fn mentions_set(line: &str, set: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    line.match_indices(set).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + set.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// Color of a daemon log line by its level, the second field of the log format.
+fn log_level_style(line: &str) -> Style {
+    match line.split_whitespace().nth(1) {
+        Some("ERROR") => Style::default().fg(Color::Red),
+        Some("WARN") => Style::default().fg(Color::Yellow),
+        Some("DEBUG") | Some("TRACE") => Style::default().fg(Color::DarkGray),
+        _ => Style::default(),
+    }
+}
+
+/// Appends `chunk` to `partial` and returns the lines it completes, leaving an
+/// unterminated last line in `partial`.
+fn split_lines(partial: &mut String, chunk: &str) -> Vec<String> {
+    partial.push_str(chunk);
+    let Some(end) = partial.rfind('\n') else {
+        return Vec::new();
+    };
+    let rest = partial.split_off(end + 1);
+    let lines = partial.lines().map(str::to_string).collect();
+    *partial = rest;
+    lines
+}
+
+/// Follows the newest daemon log file, starting with its last `LOG_TAIL_BYTES`,
+/// and moves on to the next file when the daily log rotates.
+async fn tail_logs(tx: mpsc::UnboundedSender<AppEvent>) {
+    let log_dir = paths::log_path()
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+    let mut current: Option<(PathBuf, u64)> = None;
+    let mut partial = String::new();
+    let mut skip_partial_first_line = false;
+    while !tx.is_closed() {
+        if let Some(latest) = latest_log_file(&log_dir) {
+            if current.as_ref().map(|(path, _)| path) != Some(&latest) {
+                let start = match current {
+                    // Rotated: the new file only holds lines not seen yet
+                    Some(_) => 0,
+                    None => {
+                        let len = tokio::fs::metadata(&latest)
+                            .await
+                            .map(|m| m.len())
+                            .unwrap_or(0);
+                        len.saturating_sub(LOG_TAIL_BYTES)
+                    }
+                };
+                skip_partial_first_line = start > 0;
+                partial.clear();
+                current = Some((latest, start));
+            }
+        }
+        if let Some((path, pos)) = current.as_mut() {
+            if let Ok(mut file) = tokio::fs::File::open(&path).await {
+                let len = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+                if len < *pos {
+                    // Truncated
+                    *pos = 0;
+                    partial.clear();
+                }
+                let mut buffer = Vec::new();
+                if file.seek(std::io::SeekFrom::Start(*pos)).await.is_ok()
+                    && file.read_to_end(&mut buffer).await.is_ok()
+                {
+                    *pos += buffer.len() as u64;
+                    let mut lines = split_lines(&mut partial, &String::from_utf8_lossy(&buffer));
+                    if skip_partial_first_line && !lines.is_empty() {
+                        lines.remove(0);
+                        skip_partial_first_line = false;
+                    }
+                    if !lines.is_empty() && tx.send(AppEvent::LogLines(lines)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(LOG_POLL_INTERVAL).await;
+    }
+}
+
+/// Runs the dashboard until the user quits.
+pub async fn run() -> anyhow::Result<()> {
+    let stream = crate::connect_to_daemon().await?;
+    let (reader, mut writer) = stream.into_split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let daemon_tx = tx.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Ok(response) = serde_json::from_str::<Response>(&line) {
+                if daemon_tx.send(AppEvent::Daemon(response)).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = daemon_tx.send(AppEvent::Disconnected);
+    });
+
+    tokio::spawn(tail_logs(tx.clone()));
+
+    let key_tx = tx.clone();
+    std::thread::spawn(move || loop {
+        match event::poll(KEY_POLL_INTERVAL) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read() {
+                    if key_tx.send(AppEvent::Key(key)).is_err() {
+                        break;
+                    }
+                }
+            }
+            Ok(false) if key_tx.is_closed() => break,
+            Ok(false) => {}
+            Err(_) => break,
+        }
+    });
+
+    enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let mut app = App {
+        connected: true,
+        ..Default::default()
+    };
+    let mut poll = tokio::time::interval(STATUS_POLL_INTERVAL);
+    let result: anyhow::Result<()> = async {
+        loop {
+            terminal.draw(|f| draw(f, &app))?;
+            tokio::select! {
+                _ = poll.tick() => {
+                    if app.connected {
+                        write_request(&mut writer, &Request::Status).await?;
+                    }
+                }
+                Some(event) = rx.recv() => match event {
+                    AppEvent::Key(key) => match app.handle_key(key) {
+                        Some(Action::Quit) => break,
+                        Some(Action::Send { label, request }) => {
+                            app.log(format!("{}...", label));
+                            spawn_action(label, request, tx.clone());
+                        }
+                        None => {}
+                    },
+                    AppEvent::Daemon(response) => app.handle_daemon(response),
+                    AppEvent::ActionReply { label, response } => {
+                        app.handle_action_reply(&label, response)
+                    }
+                    AppEvent::ActionFailed { label, error } => {
+                        app.log(format!("{} failed: {}", label, error))
+                    }
+                    AppEvent::Disconnected => {
+                        app.connected = false;
+                        app.log("connection to the service daemon lost".to_string());
+                    }
+                    AppEvent::LogLines(lines) => app.push_logs(lines),
+                },
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn draw(f: &mut Frame, app: &App) {
+    let set_rows = (app.sets.len().max(1) * 3) as u16 + 2;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(set_rows),
+            Constraint::Min(3),
+            Constraint::Percentage(40),
+            Constraint::Length(3),
+        ])
+        .split(f.size());
+
+    draw_header(f, app, chunks[0]);
+    draw_sets(f, app, chunks[1]);
+    draw_events(f, app, chunks[2]);
+    draw_logs(f, app, chunks[3]);
+
+    let keys = Paragraph::new(
+        "[b]ackup  [a]ll  [m]ount/unmount  [p]rune  [↑↓]select  [space]pause logs  [?]help  [q]uit",
+    )
+    .block(Block::default().borders(Borders::ALL));
+    f.render_widget(keys, chunks[4]);
+
+    if app.show_help {
+        draw_help(f);
+    }
+}
+
+/// Overall health shown in the header.
+fn health(app: &App) -> (String, Color) {
+    if !app.connected {
+        return ("● Service offline".to_string(), Color::Red);
+    }
+    let errors = app
+        .sets
+        .iter()
+        .filter(|s| s.state == JobState::Error)
+        .count();
+    match errors {
+        0 => ("● All Systems OK".to_string(), Color::Green),
+        1 => ("● 1 set failing".to_string(), Color::Red),
+        n => (format!("● {} sets failing", n), Color::Red),
+    }
+}
+
+fn draw_header(f: &mut Frame, app: &App, area: Rect) {
+    let (health, color) = health(app);
+    let title = format!("vigil v{}", env!("CARGO_PKG_VERSION"));
+    let padding =
+        (area.width as usize).saturating_sub(title.chars().count() + health.chars().count() + 4);
+    let line = Line::from(vec![
+        Span::styled(title, Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(" ".repeat(padding)),
+        Span::styled(health, Style::default().fg(color)),
+    ]);
+    f.render_widget(
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL)),
+        area,
+    );
+}
+
+/// Symbol, label and color for a set's state.
+fn state_label(state: &JobState) -> (&'static str, String, Color) {
+    match state {
+        JobState::Idle => ("●", "Idle".to_string(), Color::Green),
+        JobState::Debouncing { .. } => ("◐", "Debouncing".to_string(), Color::Yellow),
+        JobState::Running {
+            percent,
+            eta_secs,
+            scanning,
+        } => (
+            "◌",
+            format_running(*percent, *eta_secs, *scanning, false),
+            Color::Cyan,
+        ),
+        JobState::Retrying { attempt, .. } => ("↻", format!("Retry {}", attempt), Color::Yellow),
+        JobState::Scheduled { .. } => ("◷", "Deferred".to_string(), Color::Yellow),
+        JobState::Paused { .. } => ("‖", "Paused (post-restore)".to_string(), Color::Yellow),
+        JobState::Queued => ("◷", "Queued".to_string(), Color::Cyan),
+        JobState::Error => ("✗", "Error".to_string(), Color::Red),
+    }
+}
+
+/// Second column of a set's status line: what the set is waiting for or last did.
+fn detail(set: &SetStatus) -> String {
+    if let JobState::Debouncing { remaining_secs } = set.state {
+        return format!("Waiting: {}s", remaining_secs);
+    }
+    if let JobState::Retrying {
+        next_retry_secs, ..
+    } = set.state
+    {
+        return format!("Retry in: {}s", next_retry_secs);
+    }
+    if let JobState::Scheduled { at } = set.state {
+        return format!(
+            "Window: {}",
+            at.with_timezone(&chrono::Local).format("%H:%M")
+        );
+    }
+    if let JobState::Paused { until } = set.state {
+        return match until {
+            Some(until) => format!(
+                "Resumes: {}",
+                until.with_timezone(&chrono::Local).format("%H:%M")
+            ),
+            None => "Resumes: after restore".to_string(),
+        };
+    }
+    if set.initializing {
+        return "Last: initializing".to_string();
+    }
+    let last = match set.last_backup {
+        Some(ref result) => {
+            let ago = format_relative(Utc::now().signed_duration_since(result.timestamp));
+            if result.success {
+                format!("Last: {}", ago)
+            } else {
+                format!("Last: {} (fail)", ago)
+            }
+        }
+        None => "Last: never".to_string(),
+    };
+    let mut parts = vec![last];
+    if let Some(count) = set.snapshot_count {
+        parts.push(format!("{} snapshots", count));
+    }
+    if let Some(bytes) = set.total_bytes {
+        parts.push(format_size(bytes));
+    }
+    if set.pending_changes > 0 && set.skipped_trivial.is_some() {
+        parts.push(format!("{} pending (below threshold)", set.pending_changes));
+    } else if set.pending_changes > 0 {
+        parts.push(format!("{} pending", set.pending_changes));
+    }
+    if set.mounts.len() > 1 {
+        parts.push(format!("{} snapshots mounted", set.mounts.len()));
+    } else if set.is_mounted {
+        parts.push("mounted".to_string());
+    }
+    parts.join("  ")
+}
+
+fn source_summary(set: &SetStatus) -> String {
+    let source = match set.source_paths.as_slice() {
+        [single] => single.display().to_string(),
+        paths => format!("{} sources", paths.len()),
+    };
+    format!("{} → {}", source, set.target.display())
+}
+
+fn draw_sets(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Backup sets ");
+    if app.sets.is_empty() {
+        let text = if app.connected {
+            "Waiting for status..."
+        } else {
+            "No status available."
+        };
+        f.render_widget(Paragraph::new(text).block(block), area);
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .sets
+        .iter()
+        .enumerate()
+        .map(|(i, set)| {
+            let (symbol, label, color) = state_label(&set.state);
+            let name_style = if i == app.selected {
+                Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+            } else {
+                Style::default().add_modifier(Modifier::BOLD)
+            };
+            ListItem::new(vec![
+                Line::from(vec![
+                    Span::styled(format!("{:<15}", set.name), name_style),
+                    Span::raw(" "),
+                    Span::raw(source_summary(set)),
+                ]),
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} {:<14}", symbol, label),
+                        Style::default().fg(color),
+                    ),
+                    Span::raw(detail(set)),
+                ]),
+                Line::from(""),
+            ])
+        })
+        .collect();
+    f.render_widget(List::new(items).block(block), area);
+}
+
+fn draw_events(f: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = app
+        .events
+        .iter()
+        .skip(app.events.len().saturating_sub(visible))
+        .map(|e| Line::from(e.as_str()))
+        .collect();
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Events ")),
+        area,
+    );
+}
+
+fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let matching: Vec<&String> = app.logs.iter().filter(|l| app.log_matches(l)).collect();
+    let scroll = app.log_scroll.min(matching.len().saturating_sub(visible));
+    let end = matching.len() - scroll;
+    let lines: Vec<Line> = matching[end.saturating_sub(visible)..end]
+        .iter()
+        .map(|l| Line::styled(l.as_str(), log_level_style(l)))
+        .collect();
+
+    let scope = match (app.logs_all_sets, app.selected_set()) {
+        (false, Some(set)) => set.name.clone(),
+        _ => "all sets".to_string(),
+    };
+    let mut title = format!(" Logs: {} ", scope);
+    if app.logs_paused {
+        title.push_str("(paused) ");
+    }
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn draw_help(f: &mut Frame) {
+    let area = f.size();
+    let width = 50.min(area.width);
+    let height = 15.min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let text = vec![
+        Line::from("↑/k ↓/j  select a set"),
+        Line::from("b        back up the selected set now"),
+        Line::from("a        back up all sets"),
+        Line::from("m        mount the latest snapshot, or unmount"),
+        Line::from("p        prune the selected set"),
+        Line::from("space    pause or resume the log pane"),
+        Line::from("PgUp/Dn  scroll the log pane"),
+        Line::from("f        logs of the selected set or all sets"),
+        Line::from("?        show this help"),
+        Line::from("q/Esc    quit"),
+        Line::from(""),
+        Line::from("Press any key to close."),
+    ];
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(" Help ")),
+        popup,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn set(name: &str, state: JobState) -> SetStatus {
+        SetStatus {
+            name: name.to_string(),
+            state,
+            last_backup: None,
+            source_paths: vec!["/home/u/docs".into()],
+            target: "/mnt/backup/docs".into(),
+            is_mounted: false,
+            mount_warming: false,
+            mounts: Vec::new(),
+            pending_changes: 0,
+            snapshot_count: Some(4),
+            total_bytes: Some(2048),
+            initializing: false,
+            next_run: None,
+            last_verified: None,
+            verification_stale: false,
+            append_only: false,
+            skipped_trivial: None,
+            target_free_bytes: None,
+            growth_bytes_per_day: None,
+            watch_events_coalesced: 0,
+            watch_events_dropped: 0,
+            recent_changes: Vec::new(),
+            replica_lag: None,
+        }
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_keys_map_to_requests() {
+        let mut app = App::default();
+        assert_eq!(app.handle_key(key('b')), None);
+        app.update_sets(vec![
+            set("work", JobState::Idle),
+            set("home", JobState::Idle),
+        ]);
+        assert_eq!(app.sets[0].name, "home");
+
+        app.handle_key(key('j'));
+        assert_eq!(
+            app.handle_key(key('b')),
+            Some(Action::Send {
+                label: "work: backup".to_string(),
+                request: Request::Backup {
+                    set_name: Some("work".to_string()),
+                    tags: vec![],
+                }
+            })
+        );
+        app.sets[1].is_mounted = true;
+        assert!(matches!(
+            app.handle_key(key('m')),
+            Some(Action::Send {
+                request: Request::Unmount { .. },
+                ..
+            })
+        ));
+        assert_eq!(app.handle_key(key('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn test_selection_follows_set_across_updates() {
+        let mut app = App::default();
+        app.update_sets(vec![set("a", JobState::Idle), set("b", JobState::Idle)]);
+        app.selected = 1;
+        app.update_sets(vec![
+            set("b", JobState::Idle),
+            set("0", JobState::Idle),
+            set("a", JobState::Idle),
+        ]);
+        assert_eq!(app.selected_set().unwrap().name, "b");
+        app.update_sets(vec![set("a", JobState::Idle)]);
+        assert_eq!(app.selected, 0);
+    }
+
+    #[test]
+    fn test_broadcast_events_are_logged() {
+        let mut app = App::default();
+        app.handle_daemon(Response::Event(ResponseData::BackupFailed {
+            set_name: "home".to_string(),
+            error: "repository locked".to_string(),
+        }));
+        app.handle_daemon(Response::Event(ResponseData::RestoreProgress {
+            set_name: "home".to_string(),
+            target: "/tmp/r".to_string(),
+            percent: 10.0,
+            eta_secs: None,
+        }));
+        assert_eq!(app.events.len(), 1);
+        assert!(app.events[0].ends_with("home: backup failed: repository locked"));
+        assert!(Response::Event(ResponseData::PruneComplete {
+            set_name: "home".to_string(),
+            reclaimed_bytes: 0,
+        })
+        .is_broadcast());
+        assert!(!Response::Ok(None).is_broadcast());
+
+        let started = |paths: &[&str]| ResponseData::BackupStarted {
+            set_name: "home".to_string(),
+            recent_changes: paths.iter().map(Into::into).collect(),
+        };
+        assert_eq!(
+            describe_event(&started(&[])).unwrap(),
+            "home: backup started"
+        );
+        assert_eq!(
+            describe_event(&started(&["/home/u/a.txt", "/home/u/b.txt"])).unwrap(),
+            "home: backup started after changes to /home/u/b.txt and 1 more"
+        );
+    }
+
+    #[test]
+    fn test_mentions_set() {
+        assert!(mentions_set(
+            "INFO vigil_daemon::manager: Pruning set home",
+            "home"
+        ));
+        assert!(mentions_set("ERROR Backup for set 'home' failed", "home"));
+        assert!(!mentions_set("INFO Pruning set homework", "home"));
+        assert!(!mentions_set("INFO Pruning set my-home", "home"));
+    }
+
+    #[test]
+    fn test_split_lines_keeps_partial_line() {
+        let mut partial = String::new();
+        assert!(split_lines(&mut partial, "first li").is_empty());
+        assert_eq!(
+            split_lines(&mut partial, "ne\nsecond\nthi"),
+            vec!["first line", "second"]
+        );
+        assert_eq!(partial, "thi");
+        assert_eq!(split_lines(&mut partial, "rd\n"), vec!["third"]);
+        assert!(partial.is_empty());
+    }
+
+    #[test]
+    fn test_log_pane_filters_and_pauses() {
+        let mut app = App::default();
+        app.update_sets(vec![
+            set("home", JobState::Idle),
+            set("work", JobState::Idle),
+        ]);
+        app.push_logs(vec![
+            "t  INFO Pruning set home".to_string(),
+            "t  INFO Pruning set work".to_string(),
+        ]);
+        let shown: Vec<&String> = app.logs.iter().filter(|l| app.log_matches(l)).collect();
+        assert_eq!(shown, vec!["t  INFO Pruning set home"]);
+
+        app.handle_key(key(' '));
+        assert!(app.logs_paused);
+        app.push_logs(vec![
+            "t  WARN Backup for set 'home' failed".to_string(),
+            "t  INFO Pruning set work".to_string(),
+        ]);
+        assert_eq!(app.log_scroll, 1);
+
+        app.handle_key(key('f'));
+        assert!(app.logs_all_sets);
+        assert!(!app.logs_paused);
+        assert_eq!(app.log_scroll, 0);
+        assert!(app.log_matches("t  INFO Pruning set work"));
+        assert_eq!(
+            log_level_style("t  WARN Backup for set 'home' failed"),
+            Style::default().fg(Color::Yellow)
+        );
+    }
+
+    #[test]
+    fn test_dashboard_renders_sets_and_health() -> anyhow::Result<()> {
+        let mut app = App {
+            connected: true,
+            ..Default::default()
+        };
+        app.update_sets(vec![
+            set("docs", JobState::Debouncing { remaining_secs: 45 }),
+            set("home", JobState::Error),
+        ]);
+        let mut terminal = Terminal::new(TestBackend::new(90, 24))?;
+        terminal.draw(|f| draw(f, &app))?;
+
+        let buffer = terminal.backend().buffer();
+        let text: String = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect();
+        assert!(text.contains("1 set failing"));
+        assert!(text.contains("/home/u/docs → /mnt/backup/docs"));
+        assert!(text.contains("Waiting: 45s"));
+        assert!(text.contains("[b]ackup"));
+        assert!(text.contains("Logs: docs"));
+        Ok(())
+    }
+}
@@ -228,3 +228,19 @@ async fn test_cli_backup_failure() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[ignore] // Requires restic
+async fn test_cli_attach_without_running_backup() -> Result<()> {
+    let env = TestEnv::setup().await?;
+
+    let (success, stdout, stderr) = env.run_cli(&["attach", "test-set"])?;
+    assert!(success, "CLI failed: {}", stderr);
+    assert!(stdout.contains("No backup is running for set 'test-set'"));
+
+    let (success, _, stderr) = env.run_cli(&["attach", "missing"])?;
+    assert!(!success);
+    assert!(stderr.contains("Backup set 'missing' not found"));
+
+    Ok(())
+}
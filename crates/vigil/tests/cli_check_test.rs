@@ -97,6 +97,35 @@ debounce_seconds = "invalid" # Type mismatch
     }
 }
 
+#[test]
+fn test_check_classification_policy_violation() {
+    let mut file = NamedTempFile::new().unwrap();
+    writeln!(
+        file,
+        r#"
+[global]
+
+[[backup_set]]
+name = "payroll"
+source = "/tmp/source"
+target = "/tmp/payroll_repo"
+classification = "sensitive"
+"#
+    )
+    .unwrap();
+
+    let output = Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", file.path())
+        .arg("check")
+        .arg("--config-only")
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("classified as sensitive"));
+}
+
 #[test]
 fn test_check_repo_failure() {
     let mut file = NamedTempFile::new().unwrap();
@@ -0,0 +1,223 @@
+use std::io::Write;
+use std::process::Command;
+use tempfile::NamedTempFile;
+
+fn get_binary_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().expect("failed to get current exe");
+    path.pop(); // deps
+    if path.file_name().is_some_and(|n| n == "deps") {
+        path.pop(); // debug/release
+    }
+    path.push("vigil");
+
+    if path.exists() {
+        path
+    } else {
+        std::path::PathBuf::from("vigil")
+    }
+}
+
+#[test]
+fn test_config_effective_without_daemon() {
+    let mut file = NamedTempFile::new().unwrap();
+    writeln!(
+        file,
+        r#"
+[global]
+debounce_seconds = 90
+retention = {{ keep_last = 4 }}
+
+[[backup_set]]
+name = "test"
+source = "~/test"
+target = "/tmp/backup"
+"#
+    )
+    .unwrap();
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", file.path())
+        .env("XDG_CONFIG_HOME", temp_dir.path())
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .env("HOME", temp_dir.path())
+        .args(["--json", "config", "effective", "test"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(
+        output.status.success(),
+        "Command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["set"]["debounce_seconds"], 90);
+    assert_eq!(json["set"]["retention"]["keep_last"], 4);
+    assert_eq!(
+        json["set"]["source"],
+        temp_dir.path().join("test").to_string_lossy().as_ref()
+    );
+    assert_eq!(json["sources"]["debounce_seconds"], "global");
+    assert_eq!(json["sources"]["retention"], "global");
+}
+
+#[test]
+fn test_config_effective_unknown_set() {
+    let mut file = NamedTempFile::new().unwrap();
+    writeln!(
+        file,
+        r#"
+[global]
+
+[[backup_set]]
+name = "test"
+source = "/tmp/test"
+target = "/tmp/backup"
+"#
+    )
+    .unwrap();
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", file.path())
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .args(["config", "effective", "missing"])
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+fn run_config(config_path: &std::path::Path, args: &[&str]) -> std::process::Output {
+    let temp_dir = tempfile::tempdir().unwrap();
+    Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", config_path)
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .env("HOME", temp_dir.path())
+        .args(args)
+        .output()
+        .expect("Failed to execute command")
+}
+
+#[test]
+fn test_config_validate() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[global]\n[[backup_set]]\nname = \"a\"\nsource = \"/tmp/a\"\ntarget = \"/tmp/r\"\n",
+    )
+    .unwrap();
+    let output = run_config(&path, &["--json", "config", "validate"]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["sets"], 1);
+
+    // Two sets with the same name
+    let broken = temp_dir.path().join("broken.toml");
+    std::fs::write(
+        &broken,
+        "[global]\n[[backup_set]]\nname = \"a\"\nsource = \"/a\"\ntarget = \"/r\"\n\
+         [[backup_set]]\nname = \"a\"\nsource = \"/b\"\ntarget = \"/s\"\n",
+    )
+    .unwrap();
+    let output = run_config(&path, &["config", "validate", broken.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is invalid"));
+}
+
+#[test]
+fn test_config_set_and_get() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[global]\ndebounce_seconds = 30 # short\n\n[[backup_set]]\nname = \"home\"\nsource = \"/tmp/h\"\ntarget = \"/tmp/r\"\n",
+    )
+    .unwrap();
+
+    let output = run_config(&path, &["config", "set", "global.debounce_seconds", "2m"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = run_config(
+        &path,
+        &[
+            "config",
+            "set",
+            "backup_set.home.retention",
+            "last=5,daily=7",
+        ],
+    );
+    assert!(output.status.success());
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        content.contains("debounce_seconds = 120 # short"),
+        "{}",
+        content
+    );
+
+    let output = run_config(&path, &["config", "get", "global.debounce_seconds"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "120");
+    let output = run_config(
+        &path,
+        &["--json", "config", "get", "backup_set.home.retention"],
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json, serde_json::json!({ "keep_last": 5, "keep_daily": 7 }));
+
+    let output = run_config(&path, &["config", "get", "global.max_age_hours"]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = run_config(&path, &["config", "set", "global.max_retries", "lots"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_config_edit_validates_before_saving() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(&path, "[global]\ndebounce_seconds = 30\n").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", &path)
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .env("EDITOR", "sed -i s/30/45/")
+        .env_remove("VISUAL")
+        .args(["config", "edit"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "[global]\ndebounce_seconds = 45\n"
+    );
+    assert!(!temp_dir.path().join("config.edit.toml").exists());
+
+    // An invalid edit is not saved; answering "n" discards it
+    let mut child = Command::new(get_binary_path())
+        .env("VIGIL_CONFIG", &path)
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .env("EDITOR", "sed -i s/45/forty/")
+        .env_remove("VISUAL")
+        .args(["config", "edit"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"n\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid"));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "[global]\ndebounce_seconds = 45\n"
+    );
+}
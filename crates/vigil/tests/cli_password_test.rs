@@ -0,0 +1,67 @@
+use anyhow::Result;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn run_password(config: &str, temp_dir: &TempDir, set: &str) -> Result<std::process::Output> {
+    let config_file_path = temp_dir.path().join("config.toml");
+    fs::write(&config_file_path, config)?;
+    let new_password = temp_dir.path().join("new.pw");
+    fs::write(&new_password, "new-secret\n")?;
+
+    Ok(Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("vigil")
+        .arg("--")
+        .arg("password")
+        .arg(set)
+        .arg("--new-password-file")
+        .arg(&new_password)
+        .env("VIGIL_CONFIG", &config_file_path)
+        .env("XDG_CONFIG_HOME", temp_dir.path().join("config"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()?)
+}
+
+#[test]
+fn test_cli_password_refuses_shared_password_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config = r#"
+[global]
+
+[[backup_set]]
+name = "a"
+source = "/tmp/src-a"
+target = "/tmp/tgt-a"
+password_file = "/tmp/shared.pw"
+
+[[backup_set]]
+name = "b"
+source = "/tmp/src-b"
+target = "/tmp/tgt-b"
+password_file = "/tmp/shared.pw"
+"#;
+    let output = run_password(config, &temp_dir, "a")?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("also used by b"), "stderr: {}", stderr);
+    Ok(())
+}
+
+#[test]
+fn test_cli_password_unknown_set() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config = r#"
+[global]
+
+[[backup_set]]
+name = "a"
+source = "/tmp/src-a"
+target = "/tmp/tgt-a"
+"#;
+    let output = run_password(config, &temp_dir, "missing")?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not found"));
+    Ok(())
+}
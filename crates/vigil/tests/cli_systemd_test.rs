@@ -47,7 +47,9 @@ async fn test_bootstrap_unit_file_generation() {
     if unit_path.exists() {
         let content = fs::read_to_string(&unit_path).unwrap();
         assert!(content.contains("Description=Vigil Daemon"));
-        assert!(content.contains("ExecStart=%h/.cargo/bin/vigil-daemon"));
+        // Points at the daemon built next to the CLI, not a fixed cargo path
+        let daemon = bin_path.with_file_name("vigil-daemon");
+        assert!(content.contains(&format!("ExecStart={}\n", daemon.display())));
     } else {
         println!("Unit file not generated, possibly due to early failure.");
     }
@@ -75,6 +77,74 @@ async fn test_bootstrap_unit_file_generation() {
     }
 }
 
+#[test]
+fn test_self_install_copies_binaries() {
+    let temp = tempdir().unwrap();
+    let bin_dir = temp.path().join("bin");
+    let bin_path = env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("vigil");
+    if !bin_path.with_file_name("vigil-daemon").exists() {
+        println!("vigil-daemon not built, skipping");
+        return;
+    }
+
+    let output = Command::new(&bin_path)
+        .args(["--json", "self-install", "--no-service", "--dir"])
+        .arg(&bin_dir)
+        .env("PATH", "/usr/bin:/bin")
+        .output()
+        .expect("Failed to run vigil");
+    assert!(output.status.success(), "{:?}", output);
+
+    for name in ["vigil", "vigil-daemon"] {
+        let installed = bin_dir.join(name);
+        assert_eq!(
+            fs::metadata(&installed).unwrap().len(),
+            fs::metadata(bin_path.with_file_name(name)).unwrap().len()
+        );
+    }
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["status"], "copied");
+    assert_eq!(report["on_path"], false);
+}
+
+#[test]
+fn test_uninstall_dry_run_keeps_files() {
+    let temp = tempdir().unwrap();
+    let home = temp.path();
+    let config_dir = home.join(".config/vigil");
+    let data_dir = home.join(".local/share/vigil");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "test").unwrap();
+    fs::create_dir_all(&data_dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vigil"))
+        .args(["service", "uninstall", "--purge", "--dry-run"])
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_DATA_HOME", home.join(".local/share"))
+        .env("XDG_RUNTIME_DIR", home)
+        .output()
+        .expect("Failed to run vigil");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("Dry run: `vigil service uninstall --purge` would:"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(&format!("Remove directory {}", config_dir.display())));
+    assert!(stdout.contains(&format!("Remove directory {}", data_dir.display())));
+    assert!(config_dir.join("config.toml").exists());
+    assert!(data_dir.exists());
+}
+
 #[tokio::test]
 #[serial]
 async fn test_uninstall_cleanups() {
@@ -46,6 +46,65 @@ fn test_track_creates_config() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[serial]
+fn test_track_with_exclude_and_retention() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config_file = temp_dir.path().join("config.toml");
+    fs::write(
+        &config_file,
+        "# My backups\n[global]\ndebounce_seconds = 60 # one minute\n",
+    )?;
+
+    let output = Command::new(vigil())
+        .args([
+            "track",
+            "docs",
+            "/tmp/src",
+            "/tmp/tgt",
+            "--exclude",
+            "*.tmp",
+            "--exclude",
+            "cache",
+            "--retention",
+            "last=5,daily=7",
+        ])
+        .env("VIGIL_CONFIG", &config_file)
+        .output()?;
+    let _ = output;
+
+    let content = fs::read_to_string(&config_file)?;
+    assert!(content.starts_with("# My backups\n"), "{}", content);
+    assert!(content.contains("debounce_seconds = 60 # one minute"));
+    let config: toml::Value = toml::from_str(&content)?;
+    let set = &config["backup_set"].as_array().unwrap()[0];
+    assert_eq!(
+        set["exclude"].as_array().unwrap(),
+        &vec![toml::Value::from("*.tmp"), toml::Value::from("cache")]
+    );
+    assert_eq!(set["retention"]["keep_last"].as_integer(), Some(5));
+    assert_eq!(set["retention"]["keep_daily"].as_integer(), Some(7));
+
+    let output = Command::new(vigil())
+        .args([
+            "track",
+            "bad",
+            "/tmp/src",
+            "/tmp/tgt2",
+            "--retention",
+            "hourly=3",
+        ])
+        .env("VIGIL_CONFIG", &config_file)
+        .output()?;
+    assert!(
+        !output.status.success(),
+        "should reject unknown retention rule"
+    );
+    assert!(!fs::read_to_string(&config_file)?.contains("bad"));
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn test_track_duplicate_name() -> Result<()> {
@@ -176,3 +235,50 @@ target = "/tmp/tgt"
 
     Ok(())
 }
+
+#[test]
+#[serial]
+fn test_untrack_dry_run_changes_nothing() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let config_file = temp_dir.path().join("config.toml");
+    let target = temp_dir.path().join("repo");
+    fs::create_dir_all(target.join("data"))?;
+
+    let config_content = format!(
+        r#"
+[global]
+debounce_seconds = 60
+
+[[backup_set]]
+name = "removeme"
+source = "/tmp/src"
+target = "{}"
+"#,
+        target.display()
+    );
+    fs::write(&config_file, &config_content)?;
+
+    let output = Command::new(vigil())
+        .args(["--json", "untrack", "removeme", "--purge", "--dry-run"])
+        .env("VIGIL_CONFIG", &config_file)
+        .env("XDG_DATA_HOME", temp_dir.path().join("data"))
+        .env("XDG_RUNTIME_DIR", temp_dir.path())
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(plan["dry_run"], true);
+    assert_eq!(plan["command"], "untrack removeme --purge");
+    let actions = plan["actions"].as_array().unwrap();
+    assert_eq!(actions[0]["action"], "delete_repository");
+    assert_eq!(actions[0]["target"], target.display().to_string());
+    assert_eq!(actions.last().unwrap()["action"], "remove_set");
+
+    assert_eq!(fs::read_to_string(&config_file)?, config_content);
+    assert!(target.join("data").is_dir());
+    Ok(())
+}